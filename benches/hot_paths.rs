@@ -0,0 +1,136 @@
+//! Timings of per-request hot paths: meme item mapping, submission and image validation, and
+//! image key derivation. Run with `cargo bench --bench hot_paths`; pass a substring of a
+//! benchmark name to run only those (`cargo bench --bench hot_paths -- keys`).
+//!
+//! Each benchmark runs for about a second after a warm-up and reports the median, p99 and
+//! mean time per iteration, so runs before and after a change can be compared.
+
+use axum_meme_posting_example::{
+    content_filter::{ContentFilter, FilterMode},
+    keys::{ContentHashKeys, DatePrefixedKeys, FlatKeys, KeyStrategy},
+    models::{Meme, MemeStatus, Visibility, INITIAL_VERSION},
+    repositories::{item_to_meme, meme_to_item},
+    testing::sample_png,
+    validation::{validate_image, validate_submission, MemeSubmission, ValidationErrors, ValidationLimits},
+};
+use chrono::Utc;
+use std::{
+    hint::black_box,
+    time::{Duration, Instant},
+};
+use uuid::Uuid;
+
+const WARM_UP: Duration = Duration::from_millis(200);
+const MEASURE: Duration = Duration::from_secs(1);
+/// Iterations timed together, so timer overhead does not dominate fast functions.
+const BATCH: u32 = 100;
+
+/// Times `run` and prints one line of results, unless `filter` excludes it.
+fn bench<T>(filter: Option<&str>, name: &str, mut run: impl FnMut() -> T) {
+    if filter.is_some_and(|filter| !name.contains(filter)) {
+        return;
+    }
+    let warm_up_until = Instant::now() + WARM_UP;
+    while Instant::now() < warm_up_until {
+        black_box(run());
+    }
+    let mut per_iteration = Vec::new();
+    let started = Instant::now();
+    while started.elapsed() < MEASURE {
+        let batch_started = Instant::now();
+        for _ in 0..BATCH {
+            black_box(run());
+        }
+        per_iteration.push(batch_started.elapsed() / BATCH);
+    }
+    per_iteration.sort_unstable();
+    let percentile = |p: f64| per_iteration[((per_iteration.len() as f64 * p).ceil() as usize).clamp(1, per_iteration.len()) - 1];
+    let mean = per_iteration.iter().sum::<Duration>() / per_iteration.len() as u32;
+    println!(
+        "{:<36} median {:>10.2?}  p99 {:>10.2?}  mean {:>10.2?}  ({} iterations)",
+        name,
+        percentile(0.5),
+        percentile(0.99),
+        mean,
+        per_iteration.len() as u32 * BATCH
+    );
+}
+
+/// A meme with every optional attribute set, as stored after an upload with OCR and colors.
+fn sample_meme() -> Meme {
+    let now = Utc::now();
+    Meme {
+        meme_id: Uuid::new_v4(),
+        title: "When the build finally passes".to_string(),
+        description: "A classic, posted again for everyone who missed it the first time.".to_string(),
+        image_key: format!("{}.png", Uuid::new_v4()),
+        tags: vec!["programming".to_string(), "classic".to_string(), "reaction".to_string()],
+        source_url: Some("https://example.com/memes/build.png".to_string()),
+        expires_at: Some(now + chrono::Duration::days(7)),
+        created_at: Some(now),
+        version: INITIAL_VERSION,
+        like_count: 42,
+        visibility: Visibility::Public,
+        publish_at: None,
+        status: MemeStatus::Published,
+        view_count: 1337,
+        caption_text: Some("IT WORKS\nDON'T TOUCH IT".to_string()),
+        dominant_color: Some("#1e90ff".to_string()),
+        palette: vec!["#1e90ff".to_string(), "#ffffff".to_string(), "#000000".to_string()],
+        width: Some(640),
+        height: Some(480),
+        size_bytes: Some(48_213),
+        content_hash: Some("9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08".to_string()),
+        updated_at: None,
+    }
+}
+
+fn sample_submission() -> MemeSubmission {
+    MemeSubmission {
+        title: Some("  When the build finally passes  ".to_string()),
+        description: Some("A classic, posted again for everyone who missed it the first time.".to_string()),
+        tags: vec!["Programming".to_string(), "classic".to_string(), "reaction".to_string()],
+        expires_in: Some("604800".to_string()),
+        publish_at: None,
+        status: None,
+    }
+}
+
+fn limits() -> ValidationLimits {
+    ValidationLimits {
+        max_title_length: 100,
+        max_description_length: 1000,
+        max_tags: 10,
+        max_tag_length: 30,
+        max_expires_in_secs: 30 * 24 * 60 * 60,
+        max_upload_bytes: 10 * 1024 * 1024,
+        allowed_image_types: vec!["image/png".to_string(), "image/jpeg".to_string()],
+        max_uploads_per_day: None,
+        max_image_dimension: Some(4096),
+    }
+}
+
+fn main() {
+    // `cargo bench` passes `--bench`; anything else is a name filter
+    let filter = std::env::args().skip(1).find(|arg| !arg.starts_with("--"));
+    let filter = filter.as_deref();
+
+    let meme = sample_meme();
+    let item = meme_to_item(&meme);
+    bench(filter, "item_mapping/meme_to_item", || meme_to_item(&meme));
+    bench(filter, "item_mapping/item_to_meme", || item_to_meme(&item).expect("valid item"));
+
+    let limits = limits();
+    let no_filter = ContentFilter::default();
+    let blocklist = ContentFilter::build(FilterMode::Mask, ["spam", "scam", "re:fre+ money", "click here"]).expect("valid terms");
+    bench(filter, "validation/submission", || validate_submission(sample_submission(), &limits, &no_filter));
+    bench(filter, "validation/submission_with_blocklist", || validate_submission(sample_submission(), &limits, &blocklist));
+    let png = sample_png();
+    bench(filter, "validation/image", || validate_image(&mut ValidationErrors::new(), &png, &limits));
+
+    let meme_id = Uuid::new_v4();
+    let image = vec![0x5a; 1024 * 1024];
+    bench(filter, "keys/flat", || FlatKeys.image_key(meme_id, "png", &image));
+    bench(filter, "keys/date", || DatePrefixedKeys.image_key(meme_id, "png", &image));
+    bench(filter, "keys/content_hash_1mib", || ContentHashKeys.image_key(meme_id, "png", &image));
+}
@@ -0,0 +1,151 @@
+//! Buffer copies on the upload path: the previous `Vec<u8>` handling against the shared
+//! `Bytes` path, for a few image sizes. Run with `cargo bench --bench upload_path`.
+//!
+//! Both variants take the image as the multipart extractor hands it over and do what
+//! `services::create_meme` does with it: hash it, pass it to color extraction and the
+//! scanner, and upload it through the retry decorator. The `Vec` variant replays the copies
+//! the old path made (`to_vec()` of the field, a clone each for colors and the scanner, and
+//! one per upload attempt); the `Bytes` variant goes through the real `WithRetry` decorator.
+//! Allocations are counted by a wrapping global allocator.
+
+use async_trait::async_trait;
+use aws_sdk_s3::primitives::ByteStream;
+use axum::body::Bytes;
+use axum_meme_posting_example::{
+    domain::{FileStorage, ObjectMetadata, StoredObject, UploadOptions},
+    errors::StorageError,
+    retry::{RetryPolicy, WithRetry},
+};
+use sha2::{Digest, Sha256};
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    hint::black_box,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+/// Counts the bytes of every allocation, then defers to the system allocator.
+struct CountingAllocator;
+
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATED.fetch_add(layout.size(), Ordering::Relaxed);
+        // SAFETY: forwarded unchanged from the caller
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        // SAFETY: `ptr` was allocated by `System` with this layout
+        unsafe { System.dealloc(ptr, layout) }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATED.fetch_add(new_size, Ordering::Relaxed);
+        // SAFETY: forwarded unchanged from the caller
+        unsafe { System.realloc(ptr, layout, new_size) }
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+const SIZES: [usize; 3] = [100 * 1024, 1024 * 1024, 5 * 1024 * 1024];
+const ITERATIONS: u32 = 200;
+
+/// Takes uploads the way `S3FileStorage` does, turning them into a `ByteStream`, and drops them.
+struct SinkStorage;
+
+#[async_trait]
+impl FileStorage for SinkStorage {
+    async fn upload(&self, _key: &str, data: Bytes, _options: UploadOptions) -> Result<(), StorageError> {
+        black_box(ByteStream::from(data));
+        Ok(())
+    }
+
+    async fn download(&self, key: &str) -> Result<(ByteStream, ObjectMetadata), StorageError> {
+        Err(StorageError::NotFound(key.to_string()))
+    }
+
+    async fn head(&self, key: &str) -> Result<ObjectMetadata, StorageError> {
+        Err(StorageError::NotFound(key.to_string()))
+    }
+
+    async fn list(&self) -> Result<Vec<StoredObject>, StorageError> {
+        Ok(Vec::new())
+    }
+
+    async fn presigned_url(&self, key: &str, _expires_in: Duration) -> Result<String, StorageError> {
+        Ok(key.to_string())
+    }
+
+    async fn delete(&self, _key: &str) -> Result<(), StorageError> {
+        Ok(())
+    }
+
+    async fn copy(&self, _from: &str, _to: &str) -> Result<(), StorageError> {
+        Ok(())
+    }
+
+    async fn ping(&self) -> Result<(), StorageError> {
+        Ok(())
+    }
+}
+
+/// The old path: the image as an owned `Vec`, copied for every consumer and upload attempt.
+fn vec_path(field: &Bytes) {
+    let data = field.to_vec();
+    black_box(Sha256::digest(&data));
+    let colors = data.clone();
+    let scanned = data.clone();
+    let attempt = data.clone();
+    black_box(ByteStream::from(attempt));
+    black_box((colors, scanned));
+}
+
+/// The current path: one shared buffer, handed to every consumer by reference count.
+async fn bytes_path(field: &Bytes, storage: &WithRetry<SinkStorage>) {
+    let data = field.clone();
+    black_box(Sha256::digest(&data));
+    let colors = data.clone();
+    let scanned = data.clone();
+    storage.upload("meme.png", data, UploadOptions::default()).await.expect("sink uploads succeed");
+    black_box((colors, scanned));
+}
+
+/// Mean bytes allocated and time taken per run of `run`.
+fn measure(mut run: impl FnMut()) -> (usize, Duration) {
+    run(); // Warm-up
+    let allocated_before = ALLOCATED.load(Ordering::Relaxed);
+    let started = Instant::now();
+    for _ in 0..ITERATIONS {
+        run();
+    }
+    let elapsed = started.elapsed();
+    ((ALLOCATED.load(Ordering::Relaxed) - allocated_before) / ITERATIONS as usize, elapsed / ITERATIONS)
+}
+
+fn main() {
+    let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().expect("tokio runtime");
+    let policy = Arc::new(RetryPolicy::new("bench", 3, Duration::from_millis(1), Duration::from_millis(1), 0.2));
+    let storage = WithRetry::new(SinkStorage, policy);
+
+    println!("{:>10}  {:>16}  {:>16}  {:>12}  {:>12}", "image", "vec allocated", "bytes allocated", "vec time", "bytes time");
+    for size in SIZES {
+        let field = Bytes::from(vec![0x5a; size]);
+        let (vec_allocated, vec_time) = measure(|| vec_path(&field));
+        let (bytes_allocated, bytes_time) = measure(|| runtime.block_on(bytes_path(&field, &storage)));
+        println!(
+            "{:>7} KiB  {:>12} KiB  {:>12} KiB  {:>12.1?}  {:>12.1?}",
+            size / 1024,
+            vec_allocated / 1024,
+            bytes_allocated / 1024,
+            vec_time,
+            bytes_time
+        );
+    }
+}
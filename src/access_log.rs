@@ -0,0 +1,213 @@
+//! Access log (`APP_ACCESS_LOG_FORMAT`): one line per request, separate from the tracing output, in
+//! the Common Log Format (extended with referer, user agent and latency) or as JSON. Lines are
+//! written to stdout or appended to `APP_ACCESS_LOG_PATH` once the response body has been
+//! sent, so bytes and latency cover the whole response.
+
+use crate::{auth::REQUEST_ID_HEADER, client_ip::ClientIp, config::Config, errors::AppError};
+use axum::{
+    body::{Body, Bytes, HttpBody},
+    extract::{Request, State},
+    http::header,
+    middleware::Next,
+    response::Response,
+};
+use chrono::{DateTime, Utc};
+use http_body::{Frame, SizeHint};
+use serde::Serialize;
+use std::{
+    fs::OpenOptions,
+    io::Write,
+    pin::Pin,
+    str::FromStr,
+    sync::{Arc, Mutex},
+    task::{Context, Poll},
+    time::Instant,
+};
+
+/// Format of access log lines (`APP_ACCESS_LOG_FORMAT`).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AccessLogFormat {
+    /// No access log.
+    #[default]
+    Off,
+    /// `203.0.113.7 - - [10/Oct/2026:13:55:36 +0000] "GET /memes HTTP/1.1" 200 2326 "-" "curl/8.5.0" 12`:
+    /// the Common Log Format, then the referer, user agent and latency in milliseconds.
+    Common,
+    /// One JSON object per line.
+    Json,
+}
+
+impl FromStr for AccessLogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "off" => Ok(AccessLogFormat::Off),
+            "common" => Ok(AccessLogFormat::Common),
+            "json" => Ok(AccessLogFormat::Json),
+            other => Err(format!("unknown access log format '{}' (expected off, common or json)", other)),
+        }
+    }
+}
+
+/// Writes access log lines in the configured format.
+pub struct AccessLog {
+    format: AccessLogFormat,
+    output: Mutex<Box<dyn Write + Send>>,
+}
+
+impl AccessLog {
+    /// Opens the configured destination; `None` when the access log is off.
+    pub fn from_config(config: &Config) -> Result<Option<Arc<Self>>, AppError> {
+        if config.access_log_format == AccessLogFormat::Off {
+            return Ok(None);
+        }
+        let output: Box<dyn Write + Send> = match &config.access_log_path {
+            Some(path) => Box::new(OpenOptions::new().create(true).append(true).open(path).map_err(|e| {
+                AppError::InitError(format!("Failed to open access log {}: {}", path.display(), e))
+            })?),
+            None => Box::new(std::io::stdout()),
+        };
+        Ok(Some(Arc::new(AccessLog {
+            format: config.access_log_format,
+            output: Mutex::new(output),
+        })))
+    }
+
+    fn write(&self, entry: &Entry) {
+        let line = match self.format {
+            AccessLogFormat::Common => entry.common(),
+            AccessLogFormat::Json => serde_json::to_string(entry).unwrap_or_default(),
+            AccessLogFormat::Off => return,
+        };
+        let mut output = self.output.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        if let Err(e) = output.write_all(format!("{}\n", line).as_bytes()).and_then(|_| output.flush()) {
+            tracing::warn!(error = %e, "Failed to write access log");
+        }
+    }
+}
+
+/// One request, as logged. The query string is left out, as it may carry signatures.
+#[derive(Serialize)]
+struct Entry {
+    time: DateTime<Utc>,
+    client_ip: Option<String>,
+    method: String,
+    path: String,
+    protocol: String,
+    status: u16,
+    bytes: u64,
+    latency_ms: u64,
+    referer: Option<String>,
+    user_agent: Option<String>,
+    request_id: Option<String>,
+}
+
+impl Entry {
+    fn common(&self) -> String {
+        format!(
+            "{} - - [{}] \"{} {} {}\" {} {} \"{}\" \"{}\" {}",
+            self.client_ip.as_deref().unwrap_or("-"),
+            self.time.format("%d/%b/%Y:%H:%M:%S %z"),
+            self.method,
+            self.path,
+            self.protocol,
+            self.status,
+            self.bytes,
+            escape(self.referer.as_deref()),
+            escape(self.user_agent.as_deref()),
+            self.latency_ms,
+        )
+    }
+}
+
+/// A quoted field of a common log line, `-` when missing.
+fn escape(value: Option<&str>) -> String {
+    value.map_or_else(|| "-".to_string(), |value| value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Middleware writing an access log line for every request once its response body is done
+/// (or dropped). A no-op when the access log is off.
+pub async fn log_access(State(access_log): State<Option<Arc<AccessLog>>>, request: Request, next: Next) -> Response {
+    let Some(access_log) = access_log else {
+        return next.run(request).await;
+    };
+    let headers = request.headers();
+    let header = |name: &str| headers.get(name).and_then(|value| value.to_str().ok()).map(str::to_string);
+    let entry = Entry {
+        time: Utc::now(),
+        client_ip: request.extensions().get::<ClientIp>().map(|ClientIp(ip)| ip.to_string()),
+        method: request.method().to_string(),
+        path: request.uri().path().to_string(),
+        protocol: format!("{:?}", request.version()),
+        status: 0,
+        bytes: 0,
+        latency_ms: 0,
+        referer: header(header::REFERER.as_str()),
+        user_agent: header(header::USER_AGENT.as_str()),
+        request_id: header(REQUEST_ID_HEADER),
+    };
+    let started = Instant::now();
+    let response = next.run(request).await;
+    let entry = Entry { status: response.status().as_u16(), ..entry };
+    response.map(|body| Body::new(LoggedBody { inner: body, pending: Some(PendingEntry { entry, started, access_log }) }))
+}
+
+/// An entry waiting for its response body to finish.
+struct PendingEntry {
+    entry: Entry,
+    started: Instant,
+    access_log: Arc<AccessLog>,
+}
+
+/// Response body counting the bytes sent, and writing its entry when it ends or is dropped.
+struct LoggedBody {
+    inner: Body,
+    pending: Option<PendingEntry>,
+}
+
+impl LoggedBody {
+    fn finish(&mut self) {
+        if let Some(PendingEntry { mut entry, started, access_log }) = self.pending.take() {
+            entry.latency_ms = started.elapsed().as_millis() as u64;
+            access_log.write(&entry);
+        }
+    }
+}
+
+impl HttpBody for LoggedBody {
+    type Data = Bytes;
+    type Error = axum::Error;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let polled = Pin::new(&mut self.inner).poll_frame(cx);
+        match &polled {
+            Poll::Ready(Some(Ok(frame))) => {
+                if let (Some(data), Some(pending)) = (frame.data_ref(), self.pending.as_mut()) {
+                    pending.entry.bytes += data.len() as u64;
+                }
+            }
+            Poll::Ready(None) => self.finish(),
+            _ => {}
+        }
+        polled
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+impl Drop for LoggedBody {
+    fn drop(&mut self) {
+        self.finish();
+    }
+}
@@ -0,0 +1,338 @@
+use crate::{
+    audit::{DEFAULT_AUDIT_PAGE, MAX_AUDIT_PAGE},
+    backends::StorageBackend,
+    backfill::{self, BackfillField},
+    backup,
+    config::{Config, ReadEndpoint},
+    content_filter,
+    domain::{AuditEntry, BackfillProgress, TableInfo, TenantOverrides},
+    errors::AppError,
+    expiry,
+    export::ExportManifest,
+    formats::Payload,
+    handlers::meme_view,
+    import,
+    log_level::{self, LogLevelChange, LogLevelReport},
+    models::{Meme, MemeView},
+    services::{self, Caller},
+    tenant::TenantSettings,
+    AppState,
+};
+use axum::{
+    body::Body,
+    extract::{Path, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use chrono::{DateTime, Utc};
+use futures::TryStreamExt;
+use serde::{Deserialize, Serialize};
+use std::{io, sync::Arc};
+use tokio_util::io::StreamReader;
+use uuid::Uuid;
+
+/// Largest JSON manifest accepted by POST /import.
+const MAX_MANIFEST_BYTES: usize = 10 * 1024 * 1024;
+
+#[derive(Deserialize)]
+pub struct BlocklistTermRequest {
+    pub term: String,
+}
+
+/// Handler for GET /admin/blocklist. Lists admin-managed terms (config terms are not included).
+pub async fn list_blocklist(
+    State(state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, AppError> {
+    let terms = state.blocklist_repo.list_terms().await?;
+    Ok(Json(serde_json::json!({ "terms": terms })))
+}
+
+/// Handler for POST /admin/blocklist. Stores a term and reloads the active filter.
+pub async fn add_blocklist_term(
+    State(state): State<Arc<AppState>>,
+    Payload(request): Payload<BlocklistTermRequest>,
+) -> Result<StatusCode, AppError> {
+    let term = request.term.trim();
+    if term.is_empty() {
+        return Err(AppError::InvalidInput("term must not be empty".to_string()));
+    }
+    content_filter::validate_term(term)
+        .map_err(|e| AppError::InvalidInput(format!("Invalid term pattern: {}", e)))?;
+
+    state.blocklist_repo.add_term(term).await?;
+    reload_content_filter(&state).await?;
+    tracing::info!(%term, "Blocklist term added");
+    Ok(StatusCode::CREATED)
+}
+
+/// Handler for DELETE /admin/blocklist/{term}. Removes a term and reloads the active filter.
+pub async fn remove_blocklist_term(
+    State(state): State<Arc<AppState>>,
+    Path(term): Path<String>,
+) -> Result<StatusCode, AppError> {
+    state.blocklist_repo.remove_term(&term).await?;
+    reload_content_filter(&state).await?;
+    tracing::info!(%term, "Blocklist term removed");
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Handler for POST /import. Accepts either an export ZIP (`application/zip`), read as a
+/// stream, or a bare JSON manifest (`application/json`) whose images are already in storage.
+/// Memes are validated like uploads and only created if their ID is not stored yet;
+/// the response reports the outcome per meme.
+pub async fn import_memes(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    body: Body,
+) -> Result<impl IntoResponse, AppError> {
+    let content_type = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default();
+    let limits = services::upload_limits(&state).await?;
+    let filter = state.content_filter.read().expect("content filter lock poisoned").clone();
+
+    let report = if content_type.starts_with("application/json") {
+        let bytes = axum::body::to_bytes(body, MAX_MANIFEST_BYTES)
+            .await
+            .map_err(|e| AppError::InvalidInput(format!("Failed to read manifest: {}", e)))?;
+        let manifest: ExportManifest = serde_json::from_slice(&bytes)
+            .map_err(|e| AppError::InvalidInput(format!("Invalid manifest: {}", e)))?;
+        import::import_manifest(state.meme_repo.as_ref(), &limits, &filter, manifest).await?
+    } else if content_type.starts_with("application/zip") || content_type.starts_with("application/octet-stream") {
+        let reader = StreamReader::new(body.into_data_stream().map_err(io::Error::other));
+        import::import_archive(state.meme_repo.as_ref(), state.file_storage.as_ref(), &limits, &filter, reader).await?
+    } else {
+        return Err(AppError::InvalidInput(
+            "Content-Type must be application/zip or application/json".to_string(),
+        ));
+    };
+
+    Ok(Json(report))
+}
+
+/// Handler for POST /admin/backups. Writes a JSONL metadata backup to the backup prefix.
+pub async fn create_backup(
+    State(state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, AppError> {
+    let summary = backup::run_backup(
+        state.meme_repo.as_ref(),
+        state.file_storage.as_ref(),
+        &state.config.backup_prefix,
+    )
+    .await?;
+    Ok((StatusCode::CREATED, Json(summary)))
+}
+
+#[derive(Deserialize)]
+pub struct RestoreBackupRequest {
+    /// Storage key of the backup file, as returned when it was created.
+    pub key: String,
+}
+
+/// Handler for POST /admin/backups/restore. Recreates memes missing from the table.
+pub async fn restore_backup(
+    State(state): State<Arc<AppState>>,
+    Payload(request): Payload<RestoreBackupRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    let report = backup::restore(&state, &request.key).await?;
+    Ok(Json(report))
+}
+
+/// Handler for POST /admin/expired/purge. Deletes expired memes and their images now,
+/// for deployments (such as Lambda) where the periodic cleanup job does not run.
+pub async fn purge_expired(
+    State(state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, AppError> {
+    let summary = expiry::purge_expired(state.meme_repo.as_ref(), state.file_storage.as_ref(), state.meme_history.as_ref()).await?;
+    Ok(Json(summary))
+}
+
+/// Handler for GET /admin/quarantine. Lists the memes awaiting approval with `APP_MODERATION`,
+/// oldest upload first. Reads the whole meme table.
+pub async fn list_quarantine(
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<Vec<MemeView>>, AppError> {
+    let mut memes: Vec<Meme> = state.meme_repo.list_all().await?.into_iter().filter(Meme::is_awaiting_approval).collect();
+    memes.sort_by_key(|meme| meme.created_at);
+    let mut views = Vec::with_capacity(memes.len());
+    for meme in memes {
+        views.push(meme_view(&state, meme).await?);
+    }
+    Ok(Json(views))
+}
+
+/// Handler for POST /admin/quarantine/{id}/approve. Releases the meme's image from
+/// quarantine, after which the meme appears according to its status and visibility. To
+/// reject a meme instead, delete it.
+pub async fn approve_meme(
+    State(state): State<Arc<AppState>>,
+    caller: Caller,
+    Path(id_str): Path<String>,
+) -> Result<impl IntoResponse, AppError> {
+    let meme_id = Uuid::parse_str(&id_str)?;
+    let current = state.meme_repo.get_by_id(meme_id, state.config.read_consistency(ReadEndpoint::Writes)).await?.ok_or(AppError::MemeNotFound(meme_id))?;
+    let meme = services::approve_meme(&state, current, &caller).await?;
+    let etag = meme.etag();
+    Ok(([(header::ETAG, etag)], Json(meme_view(&state, meme).await?)))
+}
+
+/// Rebuilds the in-memory filter from configured and stored terms and swaps it into the state.
+async fn reload_content_filter(state: &AppState) -> Result<(), AppError> {
+    let filter = content_filter::load(&state.config, state.blocklist_repo.as_ref()).await?;
+    *state.content_filter.write().expect("content filter lock poisoned") = Arc::new(filter);
+    Ok(())
+}
+
+/// Bucket (or Azure container, or GCS bucket) totals reported by GET /admin/resources.
+#[derive(Serialize)]
+pub struct BucketUsage {
+    pub name: String,
+    /// Every object in the bucket: images, backups and orphans alike.
+    pub object_count: u64,
+    pub total_bytes: u64,
+}
+
+/// Body of the GET /admin/resources response.
+#[derive(Serialize)]
+pub struct ResourcesReport {
+    pub table: TableInfo,
+    /// Memes that have not expired, counted on the spot; the table's item count lags.
+    pub meme_count: u64,
+    pub bucket: BucketUsage,
+    /// Effective settings, secrets redacted.
+    pub config: Config,
+}
+
+/// Handler for GET /admin/resources. Describes and counts the meme table and lists the
+/// bucket, so it costs a count scan and a full bucket listing; meant for occasional
+/// operator checks.
+pub async fn describe_resources(
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<ResourcesReport>, AppError> {
+    let table = state.meme_repo.describe().await?;
+    let meme_count = state.meme_repo.count().await?;
+    let objects = state.file_storage.list().await?;
+    let bucket = BucketUsage {
+        name: match state.config.storage_backend {
+            StorageBackend::S3 => state.config.meme_bucket_name.clone(),
+            StorageBackend::Azure => state.config.azure_container_name.clone(),
+            StorageBackend::Gcs => state.config.gcs_bucket_name.clone(),
+            StorageBackend::Filesystem => state.config.filesystem_root.display().to_string(),
+        },
+        object_count: objects.len() as u64,
+        total_bytes: objects.iter().map(|object| object.size).sum(),
+    };
+    Ok(Json(ResourcesReport { table, meme_count, bucket, config: state.config.as_ref().clone() }))
+}
+
+/// Query parameters for GET /admin/audit.
+#[derive(Deserialize)]
+pub struct AuditQuery {
+    /// RFC 3339 time from which entries are returned; the whole log when omitted.
+    pub since: Option<DateTime<Utc>>,
+    /// At most this many entries (default 100, at most 1000).
+    pub limit: Option<usize>,
+}
+
+/// Body of the GET /admin/audit response.
+#[derive(Serialize)]
+pub struct AuditLogPage {
+    /// Oldest first. A full page may continue: ask again with `since` set to the last
+    /// entry's `recorded_at` (that entry is returned again).
+    pub entries: Vec<AuditEntry>,
+}
+
+/// Handler for GET /admin/audit. Lists recorded meme changes of the tenant (or deployment)
+/// the request is for.
+pub async fn list_audit_log(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<AuditQuery>,
+) -> Result<Json<AuditLogPage>, AppError> {
+    let limit = query.limit.unwrap_or(DEFAULT_AUDIT_PAGE);
+    if limit == 0 || limit > MAX_AUDIT_PAGE {
+        return Err(AppError::InvalidInput(format!("limit must be between 1 and {}", MAX_AUDIT_PAGE)));
+    }
+    let since = query.since.unwrap_or(DateTime::UNIX_EPOCH);
+    let entries = state.audit_log.list_since(since, limit).await?;
+    Ok(Json(AuditLogPage { entries }))
+}
+
+/// Body of the GET/PUT /admin/tenant-config responses.
+#[derive(Serialize)]
+pub struct TenantConfigReport {
+    pub tenant: String,
+    pub overrides: TenantOverrides,
+}
+
+/// The settings of the tenant a request is for; the deployment itself has none.
+fn tenant_settings(state: &AppState) -> Result<&TenantSettings, AppError> {
+    state.tenant_settings.as_deref().ok_or_else(|| {
+        AppError::InvalidInput("Tenant settings need a tenant; name one with the X-Tenant-Id header".to_string())
+    })
+}
+
+/// Handler for GET /admin/tenant-config. Shows the overrides of the request's tenant.
+pub async fn get_tenant_config(
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<TenantConfigReport>, AppError> {
+    let settings = tenant_settings(&state)?;
+    let overrides = settings.overrides().await?;
+    Ok(Json(TenantConfigReport { tenant: settings.tenant().to_string(), overrides: overrides.as_ref().clone() }))
+}
+
+/// Handler for PUT /admin/tenant-config. Replaces the overrides of the request's tenant.
+/// Other instances pick the change up once their cached copy expires.
+pub async fn replace_tenant_config(
+    State(state): State<Arc<AppState>>,
+    Payload(mut overrides): Payload<TenantOverrides>,
+) -> Result<Json<TenantConfigReport>, AppError> {
+    let settings = tenant_settings(&state)?;
+    if overrides.max_upload_bytes == Some(0) {
+        return Err(AppError::InvalidInput("max_upload_bytes must be at least 1".to_string()));
+    }
+    if let Some(types) = &mut overrides.allowed_image_types {
+        types.iter_mut().for_each(|media_type| *media_type = media_type.trim().to_ascii_lowercase());
+        types.retain(|media_type| !media_type.is_empty());
+    }
+    let overrides = settings.replace(overrides).await?;
+    Ok(Json(TenantConfigReport { tenant: settings.tenant().to_string(), overrides: overrides.as_ref().clone() }))
+}
+
+/// Handler for GET /admin/log_level. Shows this instance's log filter.
+pub async fn get_log_level() -> Result<Json<LogLevelReport>, AppError> {
+    Ok(Json(log_level::current()?))
+}
+
+/// Handler for PUT /admin/log_level. Changes this instance's log filter, e.g. to debug a
+/// module, until it restarts or the optional `reset_after_secs` pass.
+pub async fn set_log_level(Payload(change): Payload<LogLevelChange>) -> Result<Json<LogLevelReport>, AppError> {
+    Ok(Json(log_level::change(change)?))
+}
+
+/// Handler for GET /admin/backfills. Shows the progress of every backfill started so far.
+pub async fn list_backfills(
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<Vec<BackfillProgress>>, AppError> {
+    Ok(Json(state.backfill_repo.list().await?))
+}
+
+/// Query parameters for POST /admin/backfills/{field}.
+#[derive(Deserialize)]
+pub struct StartBackfillQuery {
+    /// Starts over from the first meme instead of resuming an interrupted run.
+    pub restart: Option<bool>,
+}
+
+/// Handler for POST /admin/backfills/{field}. Starts filling `created_at` or `content_hash`
+/// in on memes stored without it, in the background; poll GET /admin/backfills for progress.
+pub async fn start_backfill(
+    State(state): State<Arc<AppState>>,
+    Path(field): Path<String>,
+    Query(query): Query<StartBackfillQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    let field: BackfillField = field.parse().map_err(AppError::InvalidInput)?;
+    let progress = backfill::start(state, field, query.restart.unwrap_or(false)).await?;
+    Ok((StatusCode::ACCEPTED, Json(progress)))
+}
@@ -0,0 +1,130 @@
+use crate::{
+    errors::AppError,
+    models::{Meme, MemeView},
+    AppState,
+};
+use async_trait::async_trait;
+use std::sync::Arc;
+use tokio::{sync::broadcast::error::RecvError, task::JoinHandle};
+use tokio_util::sync::CancellationToken;
+use url::Url;
+use uuid::Uuid;
+
+/// Tells a chat platform about memes as they become publicly listed on this instance (see
+/// [`AppState::new_memes`]). Each meme is announced at most once; a failed announcement is
+/// logged and not retried.
+#[async_trait]
+pub trait MemeAnnouncer: Send + Sync + 'static {
+    /// Label used in logs and the `meme_announcement_failures_total` metric.
+    fn name(&self) -> &'static str;
+    async fn announce(&self, state: &AppState, meme: Meme) -> anyhow::Result<()>;
+}
+
+/// Spawns a task feeding [`AppState::new_memes`] to `announcer`. A slow announcer skips the
+/// memes it fell too far behind on. The task exits once `shutdown` is cancelled.
+#[cfg_attr(feature = "lambda", allow(dead_code))] // Not run on Lambda
+pub fn spawn_announcer(state: Arc<AppState>, announcer: Arc<dyn MemeAnnouncer>, shutdown: CancellationToken) -> JoinHandle<()> {
+    tracing::info!(announcer = announcer.name(), "Announcing new memes");
+    let mut new_memes = state.new_memes.subscribe();
+    tokio::spawn(async move {
+        loop {
+            let meme = tokio::select! {
+                received = new_memes.recv() => match received {
+                    Ok(meme) => meme,
+                    Err(RecvError::Lagged(skipped)) => {
+                        tracing::warn!(announcer = announcer.name(), skipped, "Announcements fell behind; skipped memes");
+                        continue;
+                    }
+                    Err(RecvError::Closed) => break,
+                },
+                _ = shutdown.cancelled() => break,
+            };
+            let meme_id = meme.meme_id;
+            if let Err(e) = announcer.announce(&state, meme).await {
+                metrics::counter!("meme_announcement_failures_total", "announcer" => announcer.name()).increment(1);
+                tracing::warn!(announcer = announcer.name(), %meme_id, error = %e, "Failed to announce meme");
+            }
+        }
+    })
+}
+
+/// A random meme of the public listing, for chat commands; `None` when there is none.
+pub(crate) async fn random_listed_meme(state: &AppState) -> Result<Option<Meme>, AppError> {
+    let now = chrono::Utc::now();
+    let mut memes = state.meme_repo.list_all().await?;
+    memes.retain(|meme| meme.is_listed(now));
+    if memes.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(memes.swap_remove(fastrand::usize(..memes.len()))))
+}
+
+/// The most recently uploaded meme of the public listing; `None` when there is none.
+#[cfg(feature = "telegram")]
+pub(crate) async fn latest_listed_meme(state: &AppState) -> Result<Option<Meme>, AppError> {
+    let now = chrono::Utc::now();
+    Ok(state.meme_repo.list_sorted(crate::models::SortOrder::Newest).await?.into_iter().find(|meme| meme.is_listed(now)))
+}
+
+/// The ID of the meme a link to this server points at: `{base}/meme/{id}` or one of its
+/// subpaths, where `base` is the instance's public URL. Links elsewhere are not memes,
+/// whatever their path.
+pub(crate) fn linked_meme(link: &str, base: &str) -> Option<Uuid> {
+    let (url, base) = (Url::parse(link).ok()?, Url::parse(base).ok()?);
+    if url.origin() != base.origin() {
+        return None;
+    }
+    let rest = url.path().strip_prefix(base.path().trim_end_matches('/'))?;
+    let mut segments = rest.strip_prefix('/')?.split('/');
+    if segments.next()? != "meme" {
+        return None;
+    }
+    segments.next()?.parse().ok()
+}
+
+/// Whether chat platforms can show the meme's image: they only fetch absolute URLs, and the
+/// filesystem backend serves images by path.
+pub(crate) fn has_absolute_image_url(view: &MemeView) -> bool {
+    view.image_url.starts_with("http://") || view.image_url.starts_with("https://")
+}
+
+/// `text` cut to at most `max` characters, for platforms' length limits.
+pub(crate) fn truncate(text: &str, max: usize) -> String {
+    match text.char_indices().nth(max) {
+        Some((end, _)) => text[..end].to_string(),
+        None => text.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ID: &str = "6f1c3a52-0f0e-4d1a-9a57-3c4a2b1d9e10";
+
+    #[test]
+    fn links_to_this_server_point_at_memes() {
+        let id: Uuid = ID.parse().unwrap();
+        let base = "https://memes.example.com";
+        assert_eq!(linked_meme(&format!("{}/meme/{}", base, ID), base), Some(id));
+        assert_eq!(linked_meme(&format!("https://MEMES.example.com:443/meme/{}/image?x=1", ID), base), Some(id));
+        assert_eq!(linked_meme(&format!("https://example.com/memes/meme/{}", ID), "https://example.com/memes"), Some(id));
+        assert_eq!(linked_meme(&format!("{}/memes/{}", base, ID), base), None);
+        assert_eq!(linked_meme(&format!("{}/meme/not-an-id", base), base), None);
+    }
+
+    #[test]
+    fn links_elsewhere_are_not_memes() {
+        let base = "https://memes.example.com";
+        for link in [
+            format!("https://evil.example.com/meme/{}", ID),
+            format!("http://memes.example.com/meme/{}", ID),
+            format!("https://memes.example.com:8443/meme/{}", ID),
+            format!("https://memes.example.com.evil.example/meme/{}", ID),
+            format!("https://example.com/other/meme/{}", ID),
+        ] {
+            assert_eq!(linked_meme(&link, base), None, "{}", link);
+        }
+        assert_eq!(linked_meme(&format!("https://example.com/memesx/meme/{}", ID), "https://example.com/memes"), None);
+    }
+}
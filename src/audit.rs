@@ -0,0 +1,61 @@
+use crate::{
+    domain::{AuditAction, AuditEntry, FieldChange},
+    models::Meme,
+    services::Caller,
+    AppState,
+};
+use chrono::Utc;
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+use uuid::Uuid;
+
+/// Entries returned by `GET /admin/audit` without a `limit`.
+pub const DEFAULT_AUDIT_PAGE: usize = 100;
+/// Most entries returned by one `GET /admin/audit` call.
+pub const MAX_AUDIT_PAGE: usize = 1000;
+
+/// Appends a change to the audit log. `before` is `None` for created memes and `after` for
+/// deleted ones. The change itself has already been stored, so a failed write is logged and
+/// counted rather than failing the request.
+pub async fn record(state: &AppState, caller: &Caller, action: AuditAction, before: Option<&Meme>, after: Option<&Meme>) {
+    let Some(meme_id) = after.or(before).map(|meme| meme.meme_id) else { return };
+    let entry = AuditEntry {
+        id: Uuid::new_v4(),
+        recorded_at: Utc::now(),
+        action,
+        meme_id,
+        actor: caller.actor.as_str().to_string(),
+        request_id: caller.request_id.clone(),
+        client_ip: caller.client_ip,
+        changes: changes(before, after),
+    };
+    if let Err(e) = state.audit_log.append(&entry).await {
+        metrics::counter!("audit_write_failures_total").increment(1);
+        tracing::error!(%meme_id, action = ?action, error = %e, "Failed to record change in the audit log");
+    }
+}
+
+/// The fields that differ between the two versions of a meme, by their JSON names.
+fn changes(before: Option<&Meme>, after: Option<&Meme>) -> BTreeMap<String, FieldChange> {
+    let before = fields_of(before);
+    let mut after = fields_of(after);
+    let mut changes = BTreeMap::new();
+    for (name, old) in before {
+        let new = after.remove(&name);
+        if new.as_ref() != Some(&old) {
+            changes.insert(name, FieldChange { before: Some(old), after: new });
+        }
+    }
+    for (name, new) in after {
+        changes.insert(name, FieldChange { before: None, after: Some(new) });
+    }
+    changes
+}
+
+/// A meme's fields as JSON, leaving out unset ones.
+fn fields_of(meme: Option<&Meme>) -> Map<String, Value> {
+    match meme.map(serde_json::to_value) {
+        Some(Ok(Value::Object(fields))) => fields.into_iter().filter(|(_, value)| !value.is_null()).collect(),
+        _ => Map::new(),
+    }
+}
@@ -0,0 +1,87 @@
+use crate::{
+    client_ip::ClientIp,
+    errors::AppError,
+    services::{Actor, Caller},
+    AppState,
+};
+use axum::{
+    extract::{FromRequestParts, Request, State},
+    http::{header, request::Parts, HeaderMap},
+    middleware::Next,
+    response::Response,
+};
+use std::sync::Arc;
+
+/// Middleware guarding `/admin` routes with the static bearer token from `APP_ADMIN_TOKEN`.
+/// When no token is configured the admin API is disabled and every request is refused.
+pub async fn require_admin(
+    State(state): State<Arc<AppState>>,
+    request: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    let Some(expected) = state.config.admin_token.as_deref() else {
+        return Err(AppError::Forbidden("Admin API is disabled".to_string()));
+    };
+
+    let provided = bearer_token(request.headers())
+        .ok_or_else(|| AppError::Unauthorized("Missing bearer token".to_string()))?;
+
+    if !constant_time_eq(provided.as_bytes(), expected.as_bytes()) {
+        tracing::warn!(path = %request.uri().path(), "Rejected admin request with invalid token");
+        return Err(AppError::Unauthorized("Invalid bearer token".to_string()));
+    }
+
+    Ok(next.run(request).await)
+}
+
+/// Extractor telling whether the request carries the meme owner's credentials. Memes have no
+/// per-user owners, so the admin token acts as the owner of every meme. Requests without a
+/// bearer token are simply not the owner; a wrong token is rejected like on `/admin`.
+#[derive(Debug, Clone, Copy)]
+pub struct OwnerAccess(pub bool);
+
+impl FromRequestParts<Arc<AppState>> for OwnerAccess {
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &Arc<AppState>) -> Result<Self, Self::Rejection> {
+        let Some(provided) = bearer_token(&parts.headers) else {
+            return Ok(OwnerAccess(false));
+        };
+        match state.config.admin_token.as_deref() {
+            Some(expected) if constant_time_eq(provided.as_bytes(), expected.as_bytes()) => Ok(OwnerAccess(true)),
+            _ => {
+                tracing::warn!(path = %parts.uri.path(), "Rejected request with invalid owner token");
+                Err(AppError::Unauthorized("Invalid bearer token".to_string()))
+            }
+        }
+    }
+}
+
+/// Header carrying the request ID, set by the router (or the client) on every request.
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// Identifies who is making a change for the audit log: the owner or an anonymous client
+/// (as [`OwnerAccess`] decides), with the request's `X-Request-Id` and [`ClientIp`].
+impl FromRequestParts<Arc<AppState>> for Caller {
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &Arc<AppState>) -> Result<Self, Self::Rejection> {
+        let OwnerAccess(is_owner) = OwnerAccess::from_request_parts(parts, state).await?;
+        let request_id = parts.headers.get(REQUEST_ID_HEADER).and_then(|value| value.to_str().ok()).map(str::to_string);
+        let client_ip = parts.extensions.get::<ClientIp>().map(|ClientIp(ip)| *ip);
+        Ok(Caller { actor: Actor::from_owner_access(is_owner), request_id, client_ip })
+    }
+}
+
+/// The token of an `Authorization: Bearer` header, if present.
+fn bearer_token(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+}
+
+/// Compares two byte strings without short-circuiting on the first mismatch.
+pub(crate) fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
@@ -1,21 +1,38 @@
 use crate::config::Config;
 use crate::errors::AppError;
-use aws_config::{Region, BehaviorVersion, SdkConfig};
+use crate::sdk_metrics::SdkCallMetrics;
+use crate::xray::XrayTracing;
+use aws_config::{retry::RetryConfig, timeout::TimeoutConfig, Region, BehaviorVersion, SdkConfig};
+use aws_sdk_bedrockruntime::Client as BedrockRuntimeClient;
 use aws_sdk_dynamodb::Client as DynamoDbClient;
+use aws_sdk_dynamodbstreams::Client as DynamoDbStreamsClient;
 use aws_sdk_s3::Client as S3Client;
-use tracing;
+use aws_sdk_sesv2::Client as SesClient;
+use aws_sdk_textract::Client as TextractClient;
+use std::time::Duration;
+
+/// Per-request limit for calls to SES.
+const SES_TIMEOUT: Duration = Duration::from_secs(10);
 
 // Creates the base AWS SDK configuration based on application config.
 // Reads region and optional endpoint URL from `Config`.
 // Uses the default credential provider chain (which reads env vars, profiles, etc.).
+// SDK-level retries are turned off: runtime calls are retried by `retry::WithRetry` under a
+// shared budget, and startup calls have their own backoff in startup.rs.
 pub async fn create_sdk_config(config: &Config) -> Result<SdkConfig, AppError> {
-    let region = Region::new(config.aws_region.clone());
-    tracing::info!(sdk_region = %config.aws_region, "Setting SDK region");
+    let sdk_config = load_sdk_config(&config.aws_region, config.localstack_endpoint.as_deref()).await;
+    Ok(sdk_config.into_builder().retry_config(RetryConfig::disabled()).build())
+}
+
+// Loads an SdkConfig for an explicit region and optional endpoint override.
+// Also used while loading configuration, before a `Config` exists.
+pub async fn load_sdk_config(region: &str, endpoint_url: Option<&str>) -> SdkConfig {
+    tracing::info!(sdk_region = %region, "Setting SDK region");
 
     let mut config_loader = aws_config::defaults(BehaviorVersion::latest())
-        .region(region); 
+        .region(Region::new(region.to_string()));
 
-    if let Some(endpoint_url) = &config.localstack_endpoint {
+    if let Some(endpoint_url) = endpoint_url {
         tracing::info!("Using localstack endpoint override: {}", endpoint_url);
         config_loader = config_loader.endpoint_url(endpoint_url);
     } else {
@@ -23,20 +40,80 @@ pub async fn create_sdk_config(config: &Config) -> Result<SdkConfig, AppError> {
     }
 
     // Load the configuration.
-    let sdk_config_result = config_loader.load().await;
-
-    Ok(sdk_config_result)
+    config_loader.load().await
 }
 
-// Creates a DynamoDB client from a shared SdkConfig.
+// Creates a DynamoDB client from a shared SdkConfig, recording per-operation metrics and
+// X-Ray subsegments (when the request making the call is traced).
 pub fn create_dynamodb_client(sdk_config: &SdkConfig) -> DynamoDbClient {
-    DynamoDbClient::new(sdk_config)
+    let dynamodb_config = aws_sdk_dynamodb::config::Builder::from(sdk_config)
+        .interceptor(SdkCallMetrics)
+        .interceptor(XrayTracing)
+        .build();
+    DynamoDbClient::from_conf(dynamodb_config)
+}
+
+// Creates a DynamoDB client for another region than the given client's (the failover
+// region), keeping its credentials, endpoint override, retry settings and interceptors.
+pub fn create_regional_dynamodb_client(db_client: &DynamoDbClient, region: &str) -> DynamoDbClient {
+    DynamoDbClient::from_conf(db_client.config().to_builder().region(Region::new(region.to_string())).build())
+}
+
+// Creates a DynamoDB Streams client (for the change stream consumer) from a shared SdkConfig.
+#[cfg_attr(feature = "lambda", allow(dead_code))]
+pub fn create_dynamodb_streams_client(sdk_config: &SdkConfig) -> DynamoDbStreamsClient {
+    DynamoDbStreamsClient::new(sdk_config)
+}
+
+// Creates a Textract client (for reading the text on uploaded images) from a shared SdkConfig.
+pub fn create_textract_client(sdk_config: &SdkConfig) -> TextractClient {
+    TextractClient::new(sdk_config)
 }
 
-// Creates an S3 client from a shared SdkConfig.
-pub fn create_s3_client(sdk_config: &SdkConfig) -> S3Client {
-    let s3_config_builder = aws_sdk_s3::config::Builder::from(sdk_config)
-        .force_path_style(true);
+// Creates a Bedrock Runtime client (for embedding memes for semantic search) from a shared SdkConfig.
+pub fn create_bedrock_runtime_client(sdk_config: &SdkConfig) -> BedrockRuntimeClient {
+    BedrockRuntimeClient::new(sdk_config)
+}
+
+// Creates an SES v2 client (for email notifications) from a shared SdkConfig, recording
+// per-operation metrics and X-Ray subsegments like the DynamoDB client.
+pub fn create_ses_client(sdk_config: &SdkConfig) -> SesClient {
+    let ses_config = aws_sdk_sesv2::config::Builder::from(sdk_config)
+        .timeout_config(TimeoutConfig::builder().operation_timeout(SES_TIMEOUT).build())
+        .interceptor(SdkCallMetrics)
+        .interceptor(XrayTracing)
+        .build();
+    SesClient::from_conf(ses_config)
+}
+
+// Creates an S3 client from a shared SdkConfig, recording per-operation metrics and X-Ray
+// subsegments like the DynamoDB client. `APP_S3_REGION` and `APP_S3_ENDPOINT_URL` override
+// the shared region and endpoint for S3 alone. Buckets are addressed by path (which
+// LocalStack and most S3-compatible stores expect), except with Transfer Acceleration,
+// whose endpoints need the bucket in the host name.
+pub fn create_s3_client(sdk_config: &SdkConfig, config: &Config) -> S3Client {
+    let mut s3_config_builder = aws_sdk_s3::config::Builder::from(sdk_config)
+        .force_path_style(!config.s3_accelerate)
+        .accelerate(config.s3_accelerate)
+        .interceptor(SdkCallMetrics)
+        .interceptor(XrayTracing);
+    if let Some(region) = &config.s3_region {
+        tracing::info!(s3_region = %region, "Setting S3 region");
+        s3_config_builder = s3_config_builder.region(Region::new(region.clone()));
+    }
+    if let Some(endpoint_url) = &config.s3_endpoint_url {
+        tracing::info!(s3_endpoint_url = %endpoint_url, "Using S3 endpoint override");
+        s3_config_builder = s3_config_builder.endpoint_url(endpoint_url);
+    }
+    if config.s3_accelerate {
+        tracing::info!("Using S3 Transfer Acceleration endpoints");
+    }
     let s3_config = s3_config_builder.build();
     S3Client::from_conf(s3_config)
-}
\ No newline at end of file
+}
+
+// The same S3 client without Transfer Acceleration, for bucket configuration calls
+// (CreateBucket, PutBucketAccelerateConfiguration, ...) that accelerated endpoints do not serve.
+pub fn without_acceleration(s3_client: &S3Client) -> S3Client {
+    S3Client::from_conf(s3_client.config().to_builder().accelerate(false).force_path_style(true).build())
+}
@@ -0,0 +1,219 @@
+use crate::{
+    config::Config,
+    domain::{FileStorage, ObjectMetadata, StoredObject, UploadOptions},
+    errors::{AppError, StorageError},
+};
+use async_trait::async_trait;
+use aws_sdk_s3::primitives::ByteStream;
+use axum::body::Bytes;
+use azure_core::{request_options::Metadata, StatusCode};
+use azure_storage::{shared_access_signature::service_sas::BlobSasPermissions, CloudLocation, StorageCredentials};
+use azure_storage_blobs::{blob::BlobProperties, prelude::*};
+use chrono::DateTime;
+use futures::StreamExt;
+use std::time::Duration;
+use tracing::info;
+
+/// Implements `FileStorage` on an Azure Blob Storage container, authenticated with the
+/// account key (`APP_AZURE_STORAGE_ACCOUNT` / `APP_AZURE_STORAGE_ACCESS_KEY`). Blob names are
+/// the storage keys under the same tenant prefix S3 uses.
+#[derive(Debug, Clone)]
+pub struct AzureBlobStorage {
+    container: ContainerClient,
+    key_prefix: String,
+    blob_tagging: bool, // Blob index tags, with APP_S3_OBJECT_TAGGING
+}
+
+impl AzureBlobStorage {
+    pub fn from_config(config: &Config) -> Result<Self, AppError> {
+        Ok(Self {
+            container: container_client(config)?,
+            key_prefix: config.s3_key_prefix(),
+            blob_tagging: config.s3_object_tagging,
+        })
+    }
+
+    fn blob(&self, key: &str) -> BlobClient {
+        self.container.blob_client(format!("{}{}", self.key_prefix, key))
+    }
+}
+
+fn container_client(config: &Config) -> Result<ContainerClient, AppError> {
+    let (Some(account), Some(access_key)) = (&config.azure_storage_account, &config.azure_storage_access_key) else {
+        return Err(AppError::InitError("Azure storage needs APP_AZURE_STORAGE_ACCOUNT and APP_AZURE_STORAGE_ACCESS_KEY".to_string()));
+    };
+    let credentials = StorageCredentials::access_key(account.clone(), access_key.clone());
+    let location = match &config.azure_blob_endpoint {
+        Some(uri) => CloudLocation::Custom { account: account.clone(), uri: uri.clone() },
+        None => CloudLocation::Public { account: account.clone() },
+    };
+    Ok(ClientBuilder::with_location(location, credentials).container_client(config.azure_container_name.clone()))
+}
+
+fn is_not_found(error: &azure_core::Error) -> bool {
+    error.as_http_error().is_some_and(|http| http.status() == StatusCode::NotFound)
+}
+
+fn backend_error(error: azure_core::Error, context: String) -> StorageError {
+    StorageError::BackendError(anyhow::Error::new(error).context(context))
+}
+
+fn object_metadata(properties: &BlobProperties) -> ObjectMetadata {
+    ObjectMetadata {
+        content_type: Some(properties.content_type.clone()),
+        content_length: Some(properties.content_length),
+        etag: Some(properties.etag.to_string()),
+        last_modified: DateTime::from_timestamp(properties.last_modified.unix_timestamp(), properties.last_modified.nanosecond()),
+    }
+}
+
+/// Checks the container exists, creating it in `create` mode. Startup only, like the S3
+/// bucket checks; the container is shared by every tenant.
+pub async fn ensure_container(config: &Config, create: bool) -> Result<(), AppError> {
+    let container = container_client(config)?;
+    let name = &config.azure_container_name;
+    let exists = container
+        .exists()
+        .await
+        .map_err(|e| AppError::InitError(format!("Failed to check Azure container '{}': {}", name, e)))?;
+    if exists {
+        info!(container = %name, "Azure container verified.");
+        return Ok(());
+    }
+    if !create {
+        return Err(AppError::InitError(format!(
+            "Azure container '{}' does not exist. Create it, or set APP_RESOURCE_INIT=create",
+            name
+        )));
+    }
+    match container.create().await {
+        Ok(_) => {}
+        // Another instance created it in the meantime
+        Err(e) if e.as_http_error().is_some_and(|http| http.status() == StatusCode::Conflict) => {}
+        Err(e) => return Err(AppError::InitError(format!("Failed to create Azure container '{}': {}", name, e))),
+    }
+    info!(container = %name, "Azure container created.");
+    Ok(())
+}
+
+#[async_trait]
+impl FileStorage for AzureBlobStorage {
+    /// Uploads a block blob with the content type and metadata, and index tags when enabled.
+    /// Metadata names must be C# identifiers on Azure, so `-` becomes `_`.
+    async fn upload(&self, key: &str, data: Bytes, options: UploadOptions) -> Result<(), StorageError> {
+        let content_type = options.content_type.unwrap_or_else(|| "application/octet-stream".to_string());
+        let mut metadata = Metadata::new();
+        for (name, value) in options.metadata {
+            metadata.insert(name.replace('-', "_"), value);
+        }
+        let mut request = self.blob(key).put_block_blob(data).content_type(content_type).metadata(metadata);
+        if self.blob_tagging && !options.tags.is_empty() {
+            let mut tags = Tags::new();
+            for (name, value) in options.tags {
+                tags.insert(name, value);
+            }
+            request = request.tags(tags);
+        }
+        request
+            .await
+            .map_err(|e| StorageError::UploadFailed(format!("Azure: Failed to upload blob '{}': {}", key, e)))?;
+        tracing::debug!(blob = %key, "Azure: Upload successful");
+        Ok(())
+    }
+
+    /// Downloads the blob in chunks and hands it over as one buffer, which is how the image
+    /// handlers consume downloads anyway.
+    async fn download(&self, key: &str) -> Result<(ByteStream, ObjectMetadata), StorageError> {
+        let mut chunks = self.blob(key).get().into_stream();
+        let mut data = Vec::new();
+        let mut metadata = None;
+        while let Some(chunk) = chunks.next().await {
+            let chunk = chunk.map_err(|e| {
+                if is_not_found(&e) {
+                    StorageError::NotFound(key.to_string())
+                } else {
+                    backend_error(e, format!("Azure: Failed to download blob '{}'", key))
+                }
+            })?;
+            metadata.get_or_insert_with(|| object_metadata(&chunk.blob.properties));
+            let bytes = chunk.data.collect().await.map_err(|e| backend_error(e, format!("Azure: Failed to read blob '{}'", key)))?;
+            data.extend_from_slice(&bytes);
+        }
+        let mut metadata = metadata.unwrap_or_default();
+        metadata.content_length = Some(data.len() as u64); // Properties of a ranged chunk describe the chunk
+        Ok((ByteStream::from(data), metadata))
+    }
+
+    async fn head(&self, key: &str) -> Result<ObjectMetadata, StorageError> {
+        let response = self.blob(key).get_properties().await.map_err(|e| {
+            if is_not_found(&e) {
+                StorageError::NotFound(key.to_string())
+            } else {
+                backend_error(e, format!("Azure: Failed to read properties of blob '{}'", key))
+            }
+        })?;
+        Ok(object_metadata(&response.blob.properties))
+    }
+
+    async fn list(&self) -> Result<Vec<StoredObject>, StorageError> {
+        let mut request = self.container.list_blobs();
+        if !self.key_prefix.is_empty() {
+            request = request.prefix(self.key_prefix.clone());
+        }
+        let mut pages = request.into_stream();
+        let mut objects = Vec::new();
+        while let Some(page) = pages.next().await {
+            let page = page.map_err(|e| backend_error(e, format!("Azure: Failed to list container '{}'", self.container.container_name())))?;
+            for blob in page.blobs.blobs() {
+                let Some(key) = blob.name.strip_prefix(self.key_prefix.as_str()) else { continue };
+                objects.push(StoredObject { key: key.to_string(), size: blob.properties.content_length });
+            }
+        }
+        Ok(objects)
+    }
+
+    /// A read-only service SAS for the blob, signed with the account key.
+    async fn presigned_url(&self, key: &str, expires_in: Duration) -> Result<String, StorageError> {
+        let blob = self.blob(key);
+        let permissions = BlobSasPermissions { read: true, ..BlobSasPermissions::default() };
+        let signature = blob
+            .shared_access_signature(permissions, time::OffsetDateTime::now_utc() + expires_in)
+            .await
+            .map_err(|e| backend_error(e, format!("Azure: Failed to sign blob '{}'", key)))?;
+        let url = blob
+            .generate_signed_blob_url(&signature)
+            .map_err(|e| backend_error(e, format!("Azure: Failed to build URL of blob '{}'", key)))?;
+        Ok(url.to_string())
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), StorageError> {
+        match self.blob(key).delete().await {
+            Ok(_) => Ok(()),
+            // Deleting a missing object succeeds on S3; match that
+            Err(e) if is_not_found(&e) => Ok(()),
+            Err(e) => Err(backend_error(e, format!("Azure: Failed to delete blob '{}'", key))),
+        }
+    }
+
+    /// Copies within the container with Copy Blob From URL, authorized by a short-lived SAS
+    /// of the source. Metadata is copied along; index tags are not.
+    async fn copy(&self, from: &str, to: &str) -> Result<(), StorageError> {
+        let source = self.presigned_url(from, Duration::from_secs(5 * 60)).await?;
+        let source = azure_core::Url::parse(&source)
+            .map_err(|e| StorageError::BackendError(anyhow::Error::new(e).context(format!("Azure: Invalid URL of blob '{}'", from))))?;
+        match self.blob(to).copy_from_url(source).await {
+            Ok(_) => Ok(()),
+            Err(e) if is_not_found(&e) => Err(StorageError::NotFound(from.to_string())),
+            Err(e) => Err(backend_error(e, format!("Azure: Failed to copy blob '{}' to '{}'", from, to))),
+        }
+    }
+
+    /// Checks the container's properties.
+    async fn ping(&self) -> Result<(), StorageError> {
+        self.container
+            .get_properties()
+            .await
+            .map_err(|e| backend_error(e, format!("Azure: Container '{}' is not reachable", self.container.container_name())))?;
+        Ok(())
+    }
+}
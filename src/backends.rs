@@ -0,0 +1,387 @@
+use crate::{
+    aws_clients,
+    circuit_breaker::{CircuitBreaker, WithBreaker},
+    config::Config,
+    domain::{
+        AuditRepository, BackfillRepository, BlocklistRepository, FileStorage, FollowerRepository, MemeHistoryRepository,
+        MemeRepository, ShortlinkRepository, TenantConfigRepository, TrendingRepository, VectorIndex,
+    },
+    errors::AppError,
+    failover::{FailoverMemeRepository, RegionFailover},
+    filesystem_storage::FilesystemStorage,
+    hedging::{HedgePolicy, HedgedMemeRepository},
+    instrumentation::{InstrumentedRepository, InstrumentedStorage},
+    repositories::{
+        DynamoDbAuditRepository, DynamoDbBackfillRepository, DynamoDbBlocklistRepository, DynamoDbFollowerRepository,
+        DynamoDbMemeHistoryRepository, DynamoDbMemeRepository, DynamoDbShortlinkRepository, DynamoDbTenantConfigRepository, DynamoDbTrendingRepository,
+        DynamoDbVectorIndex,
+    },
+    retry::{RetryPolicy, WithRetry},
+    storage::S3FileStorage,
+};
+#[cfg(feature = "chaos")]
+use crate::chaos::{ChaosPolicy, WithChaos};
+use aws_sdk_dynamodb::Client as DynamoDbClient;
+use aws_sdk_s3::Client as S3Client;
+use serde::Serialize;
+use std::{str::FromStr, sync::Arc, time::Duration};
+
+/// Where meme metadata is stored (`APP_REPOSITORY_BACKEND`). Blocklist terms, tenant
+/// overrides, the audit log and stream checkpoints stay in the DynamoDB meta table, except with `sqlite`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RepositoryBackend {
+    #[default]
+    DynamoDb,
+    /// MongoDB; needs the `mongodb` feature.
+    MongoDb,
+    /// A local SQLite file, for runs without AWS; needs the `sqlite` feature (on by default).
+    /// Blocklist terms and tenant overrides move into the file as well.
+    Sqlite,
+}
+
+impl FromStr for RepositoryBackend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "dynamodb" => Ok(RepositoryBackend::DynamoDb),
+            "mongodb" => Ok(RepositoryBackend::MongoDb),
+            "sqlite" => Ok(RepositoryBackend::Sqlite),
+            other => Err(format!("unknown repository backend '{}' (expected dynamodb, mongodb or sqlite)", other)),
+        }
+    }
+}
+
+/// Where images are stored (`APP_STORAGE_BACKEND`). Metadata stays in DynamoDB either way.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StorageBackend {
+    #[default]
+    S3,
+    /// Azure Blob Storage; needs the `azure` feature.
+    Azure,
+    /// Google Cloud Storage; needs the `gcs` feature.
+    Gcs,
+    /// A local directory, for runs without AWS.
+    Filesystem,
+}
+
+impl FromStr for StorageBackend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "s3" => Ok(StorageBackend::S3),
+            "azure" => Ok(StorageBackend::Azure),
+            "gcs" => Ok(StorageBackend::Gcs),
+            "filesystem" => Ok(StorageBackend::Filesystem),
+            other => Err(format!("unknown storage backend '{}' (expected s3, azure, gcs or filesystem)", other)),
+        }
+    }
+}
+
+/// The decorators every backend call goes through: one circuit breaker and one retry
+/// policy per backend, shared by every repository or storage of a state that uses it.
+/// Breakers and metrics keep the `dynamodb` and `s3` names whichever backend is selected.
+pub struct Resilience {
+    dynamodb_breaker: Arc<CircuitBreaker>,
+    s3_breaker: Arc<CircuitBreaker>,
+    dynamodb_retry: Arc<RetryPolicy>,
+    s3_retry: Arc<RetryPolicy>,
+    // Breaker and retries of the failover region's meme table, when there is one
+    dynamodb_secondary: Option<(Arc<CircuitBreaker>, Arc<RetryPolicy>)>,
+    instrumented: bool,
+    slow_call_threshold: Option<Duration>,
+    #[cfg(feature = "chaos")]
+    dynamodb_chaos: ChaosPolicy,
+    #[cfg(feature = "chaos")]
+    s3_chaos: ChaosPolicy,
+}
+
+impl Resilience {
+    pub fn from_config(config: &Config) -> Self {
+        let breaker_open = Duration::from_secs(config.breaker_open_secs);
+        let retry_policy = |backend| {
+            Arc::new(RetryPolicy::new(
+                backend,
+                config.retry_max_attempts,
+                Duration::from_millis(config.retry_base_delay_ms),
+                Duration::from_millis(config.retry_max_delay_ms),
+                config.retry_budget_ratio,
+            ))
+        };
+        Self {
+            dynamodb_breaker: Arc::new(CircuitBreaker::new("dynamodb", config.breaker_failure_threshold, breaker_open)),
+            s3_breaker: Arc::new(CircuitBreaker::new("s3", config.breaker_failure_threshold, breaker_open)),
+            dynamodb_retry: retry_policy("dynamodb"),
+            s3_retry: retry_policy("s3"),
+            dynamodb_secondary: config.dynamodb_secondary_region.as_ref().map(|_| {
+                (
+                    Arc::new(CircuitBreaker::new("dynamodb_secondary", config.breaker_failure_threshold, breaker_open)),
+                    retry_policy("dynamodb_secondary"),
+                )
+            }),
+            instrumented: config.backend_instrumentation,
+            slow_call_threshold: Some(Duration::from_millis(config.slow_backend_call_threshold_ms))
+                .filter(|threshold| !threshold.is_zero()),
+            #[cfg(feature = "chaos")]
+            dynamodb_chaos: ChaosPolicy::for_backend(config, "dynamodb"),
+            #[cfg(feature = "chaos")]
+            s3_chaos: ChaosPolicy::for_backend(config, "s3"),
+        }
+    }
+
+    /// The breakers, for `/health` and the breaker metrics.
+    pub fn circuit_breakers(&self) -> Vec<Arc<CircuitBreaker>> {
+        let mut breakers = vec![self.dynamodb_breaker.clone(), self.s3_breaker.clone()];
+        breakers.extend(self.dynamodb_secondary.as_ref().map(|(breaker, _)| breaker.clone()));
+        breakers
+    }
+
+    /// Decorates a meme repository. Fault injection (with `chaos`) sits innermost, then
+    /// instrumentation (when enabled) so every attempt is measured on its own.
+    pub fn meme_repository(&self, inner: impl MemeRepository) -> Arc<dyn MemeRepository> {
+        #[cfg(feature = "chaos")]
+        let inner = WithChaos::new(inner, "dynamodb", self.dynamodb_chaos);
+        if self.instrumented {
+            Arc::new(resilient(InstrumentedRepository::new(inner, "dynamodb", self.slow_call_threshold), &self.dynamodb_retry, &self.dynamodb_breaker))
+        } else {
+            Arc::new(resilient(inner, &self.dynamodb_retry, &self.dynamodb_breaker))
+        }
+    }
+
+    /// Decorates the failover region's meme repository like [`Resilience::meme_repository`],
+    /// under its own `dynamodb_secondary` breaker and retries (and no fault injection, so
+    /// faults in `dynamodb` exercise the failover); `None` without a secondary region.
+    pub fn secondary_meme_repository(&self, inner: impl MemeRepository) -> Option<Arc<dyn MemeRepository>> {
+        let (breaker, retry) = self.dynamodb_secondary.as_ref()?;
+        if self.instrumented {
+            Some(Arc::new(resilient(InstrumentedRepository::new(inner, "dynamodb_secondary", self.slow_call_threshold), retry, breaker)))
+        } else {
+            Some(Arc::new(resilient(inner, retry, breaker)))
+        }
+    }
+
+    /// Decorates a blocklist repository like [`Resilience::meme_repository`].
+    pub fn blocklist_repository(&self, inner: impl BlocklistRepository) -> Arc<dyn BlocklistRepository> {
+        #[cfg(feature = "chaos")]
+        let inner = WithChaos::new(inner, "dynamodb", self.dynamodb_chaos);
+        if self.instrumented {
+            Arc::new(resilient(InstrumentedRepository::new(inner, "dynamodb", self.slow_call_threshold), &self.dynamodb_retry, &self.dynamodb_breaker))
+        } else {
+            Arc::new(resilient(inner, &self.dynamodb_retry, &self.dynamodb_breaker))
+        }
+    }
+
+    /// Decorates a file storage like [`Resilience::meme_repository`].
+    pub fn file_storage(&self, inner: impl FileStorage) -> Arc<dyn FileStorage> {
+        #[cfg(feature = "chaos")]
+        let inner = WithChaos::new(inner, "s3", self.s3_chaos);
+        if self.instrumented {
+            Arc::new(resilient(InstrumentedStorage::new(inner, "s3", self.slow_call_threshold), &self.s3_retry, &self.s3_breaker))
+        } else {
+            Arc::new(resilient(inner, &self.s3_retry, &self.s3_breaker))
+        }
+    }
+}
+
+/// Wraps a repository or storage in retries and then the backend's circuit breaker, so a
+/// breaker counts one failure per exhausted retry sequence.
+fn resilient<T>(inner: T, retry: &Arc<RetryPolicy>, breaker: &Arc<CircuitBreaker>) -> WithBreaker<WithRetry<T>> {
+    WithBreaker::new(WithRetry::new(inner, retry.clone()), breaker.clone())
+}
+
+/// The configured meme repository (`APP_REPOSITORY_BACKEND`), decorated. With
+/// `APP_HEDGED_READS` slow lookups are hedged. With `APP_DYNAMODB_SECONDARY_REGION` it fails
+/// over to the table's replica there, and the failover state is returned for the health
+/// checks of the primary region.
+pub async fn meme_repository(
+    config: &Config,
+    db_client: &DynamoDbClient,
+    resilience: &Resilience,
+) -> Result<(Arc<dyn MemeRepository>, Option<Arc<RegionFailover>>), AppError> {
+    let mut primary = resilience.meme_repository(build_meme_repository(config, db_client).await?);
+    if config.hedged_reads {
+        // Outside the retries, so a hedge also covers a lookup stuck in backoff
+        let policy = HedgePolicy::new("dynamodb", config.hedge_percentile, Duration::from_millis(config.hedge_min_delay_ms));
+        primary = Arc::new(HedgedMemeRepository::new(primary, policy));
+    }
+    let Some(secondary_region) = &config.dynamodb_secondary_region else {
+        return Ok((primary, None));
+    };
+    let secondary_client = aws_clients::create_regional_dynamodb_client(db_client, secondary_region);
+    let secondary = resilience
+        .secondary_meme_repository(
+            DynamoDbMemeRepository::new(secondary_client, config.dynamodb_table_name.clone())
+                .with_scan_segments(config.dynamodb_scan_segments),
+        )
+        .expect("resilience of a config with a secondary region covers it");
+    let failover = Arc::new(RegionFailover::new(
+        config.aws_region.clone(),
+        secondary_region.clone(),
+        primary.clone(),
+        config.failover_threshold,
+    ));
+    let repository = FailoverMemeRepository::new(primary, secondary, failover.clone(), config.failover_writes);
+    Ok((Arc::new(repository), Some(failover)))
+}
+
+/// The configured file storage (`APP_STORAGE_BACKEND`), decorated.
+pub async fn file_storage(config: &Config, s3_client: &S3Client, resilience: &Resilience) -> Result<Arc<dyn FileStorage>, AppError> {
+    Ok(resilience.file_storage(build_file_storage(config, s3_client).await?))
+}
+
+/// Builds the configured meme repository, undecorated. Config loading already rejected
+/// backends whose feature is not compiled in.
+async fn build_meme_repository(config: &Config, db_client: &DynamoDbClient) -> Result<Box<dyn MemeRepository>, AppError> {
+    match config.repository_backend {
+        RepositoryBackend::DynamoDb => Ok(Box::new(
+            DynamoDbMemeRepository::new(db_client.clone(), config.dynamodb_table_name.clone())
+                .with_scan_segments(config.dynamodb_scan_segments),
+        )),
+        #[cfg(feature = "mongodb")]
+        RepositoryBackend::MongoDb => Ok(Box::new(crate::mongo_repository::MongoMemeRepository::from_config(config).await?)),
+        #[cfg(not(feature = "mongodb"))]
+        RepositoryBackend::MongoDb => Err(AppError::InitError("APP_REPOSITORY_BACKEND=mongodb needs the `mongodb` feature".to_string())),
+        #[cfg(feature = "sqlite")]
+        RepositoryBackend::Sqlite => Ok(Box::new(crate::sqlite_repository::SqliteMemeRepository::open(config)?)),
+        #[cfg(not(feature = "sqlite"))]
+        RepositoryBackend::Sqlite => Err(AppError::InitError("APP_REPOSITORY_BACKEND=sqlite needs the `sqlite` feature".to_string())),
+    }
+}
+
+/// Builds the blocklist repository, undecorated: the SQLite file with the `sqlite`
+/// backend, the DynamoDB meta table otherwise.
+pub fn build_blocklist_repository(config: &Config, db_client: &DynamoDbClient) -> Result<Box<dyn BlocklistRepository>, AppError> {
+    match config.repository_backend {
+        #[cfg(feature = "sqlite")]
+        RepositoryBackend::Sqlite => Ok(Box::new(crate::sqlite_repository::SqliteBlocklistRepository::open(config)?)),
+        _ => Ok(Box::new(DynamoDbBlocklistRepository::new(db_client.clone(), config.meta_table_name.clone()))),
+    }
+}
+
+/// Builds the tenant overrides repository, from the same store as the blocklist.
+pub fn build_tenant_config_repository(
+    config: &Config,
+    db_client: &DynamoDbClient,
+) -> Result<Arc<dyn TenantConfigRepository>, AppError> {
+    match config.repository_backend {
+        #[cfg(feature = "sqlite")]
+        RepositoryBackend::Sqlite => Ok(Arc::new(crate::sqlite_repository::SqliteTenantConfigRepository::open(config)?)),
+        _ => Ok(Arc::new(DynamoDbTenantConfigRepository::new(db_client.clone(), config.meta_table_name.clone()))),
+    }
+}
+
+/// Builds the audit log of this configuration's tenant (or of the deployment), from the same
+/// store as the blocklist.
+pub fn build_audit_repository(config: &Config, db_client: &DynamoDbClient) -> Result<Arc<dyn AuditRepository>, AppError> {
+    match config.repository_backend {
+        #[cfg(feature = "sqlite")]
+        RepositoryBackend::Sqlite => Ok(Arc::new(crate::sqlite_repository::SqliteAuditRepository::open(config)?)),
+        _ => Ok(Arc::new(DynamoDbAuditRepository::new(
+            db_client.clone(),
+            config.meta_table_name.clone(),
+            config.tenant.as_deref(),
+        ))),
+    }
+}
+
+/// Builds the store of earlier meme versions, from the same store as the blocklist.
+pub fn build_history_repository(
+    config: &Config,
+    db_client: &DynamoDbClient,
+) -> Result<Arc<dyn MemeHistoryRepository>, AppError> {
+    match config.repository_backend {
+        #[cfg(feature = "sqlite")]
+        RepositoryBackend::Sqlite => Ok(Arc::new(crate::sqlite_repository::SqliteMemeHistoryRepository::open(config)?)),
+        _ => Ok(Arc::new(DynamoDbMemeHistoryRepository::new(db_client.clone(), config.meta_table_name.clone()))),
+    }
+}
+
+/// Builds the store of this configuration's trending rankings, from the same store as the
+/// blocklist.
+pub fn build_trending_repository(config: &Config, db_client: &DynamoDbClient) -> Result<Arc<dyn TrendingRepository>, AppError> {
+    match config.repository_backend {
+        #[cfg(feature = "sqlite")]
+        RepositoryBackend::Sqlite => Ok(Arc::new(crate::sqlite_repository::SqliteTrendingRepository::open(config)?)),
+        _ => Ok(Arc::new(DynamoDbTrendingRepository::new(
+            db_client.clone(),
+            config.meta_table_name.clone(),
+            config.tenant.as_deref(),
+        ))),
+    }
+}
+
+/// Builds the store of this configuration's backfill progress, from the same store as the
+/// blocklist.
+pub fn build_backfill_repository(config: &Config, db_client: &DynamoDbClient) -> Result<Arc<dyn BackfillRepository>, AppError> {
+    match config.repository_backend {
+        #[cfg(feature = "sqlite")]
+        RepositoryBackend::Sqlite => Ok(Arc::new(crate::sqlite_repository::SqliteBackfillRepository::open(config)?)),
+        _ => Ok(Arc::new(DynamoDbBackfillRepository::new(
+            db_client.clone(),
+            config.meta_table_name.clone(),
+            config.tenant.as_deref(),
+        ))),
+    }
+}
+
+/// Builds the store of this configuration's shortlinks, from the same store as the
+/// blocklist.
+pub fn build_shortlink_repository(config: &Config, db_client: &DynamoDbClient) -> Result<Arc<dyn ShortlinkRepository>, AppError> {
+    match config.repository_backend {
+        #[cfg(feature = "sqlite")]
+        RepositoryBackend::Sqlite => Ok(Arc::new(crate::sqlite_repository::SqliteShortlinkRepository::open(config)?)),
+        _ => Ok(Arc::new(DynamoDbShortlinkRepository::new(
+            db_client.clone(),
+            config.meta_table_name.clone(),
+            config.tenant.as_deref(),
+        ))),
+    }
+}
+
+/// Builds the store of the ActivityPub actor's followers, from the same store as the
+/// blocklist.
+pub fn build_follower_repository(config: &Config, db_client: &DynamoDbClient) -> Result<Arc<dyn FollowerRepository>, AppError> {
+    match config.repository_backend {
+        #[cfg(feature = "sqlite")]
+        RepositoryBackend::Sqlite => Ok(Arc::new(crate::sqlite_repository::SqliteFollowerRepository::open(config)?)),
+        _ => Ok(Arc::new(DynamoDbFollowerRepository::new(db_client.clone(), config.meta_table_name.clone()))),
+    }
+}
+
+/// Builds the index of this configuration's meme vectors, from the same store as the
+/// blocklist.
+pub fn build_vector_index(config: &Config, db_client: &DynamoDbClient) -> Result<Arc<dyn VectorIndex>, AppError> {
+    match config.repository_backend {
+        #[cfg(feature = "sqlite")]
+        RepositoryBackend::Sqlite => Ok(Arc::new(crate::sqlite_repository::SqliteVectorIndex::open(config)?)),
+        _ => Ok(Arc::new(DynamoDbVectorIndex::new(
+            db_client.clone(),
+            config.meta_table_name.clone(),
+            config.tenant.as_deref(),
+        ))),
+    }
+}
+
+/// Builds the configured storage backend, undecorated. Config loading already rejected
+/// backends whose feature is not compiled in.
+async fn build_file_storage(config: &Config, s3_client: &S3Client) -> Result<Box<dyn FileStorage>, AppError> {
+    match config.storage_backend {
+        StorageBackend::S3 => Ok(Box::new(
+            S3FileStorage::new(s3_client.clone(), config.meme_bucket_name.clone(), config.s3_key_prefix())
+                .with_kms_key(config.s3_upload_kms_key_id.clone())
+                .with_object_tagging(config.s3_object_tagging),
+        )),
+        #[cfg(feature = "azure")]
+        StorageBackend::Azure => Ok(Box::new(crate::azure_blob::AzureBlobStorage::from_config(config)?)),
+        #[cfg(not(feature = "azure"))]
+        StorageBackend::Azure => Err(AppError::InitError("APP_STORAGE_BACKEND=azure needs the `azure` feature".to_string())),
+        #[cfg(feature = "gcs")]
+        StorageBackend::Gcs => Ok(Box::new(crate::gcs_storage::GcsFileStorage::from_config(config).await?)),
+        #[cfg(not(feature = "gcs"))]
+        StorageBackend::Gcs => Err(AppError::InitError("APP_STORAGE_BACKEND=gcs needs the `gcs` feature".to_string())),
+        StorageBackend::Filesystem => Ok(Box::new(FilesystemStorage::from_config(config))),
+    }
+}
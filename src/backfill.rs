@@ -0,0 +1,219 @@
+//! Backfills of meme attributes added after memes were stored: `created_at` (from the time
+//! the image was written) and `content_hash` (SHA-256 of the image). A run visits every meme
+//! in ID order at `APP_BACKFILL_RATE` memes per second, so the table and bucket keep
+//! serving traffic, and saves its progress as it goes. Started through
+//! `POST /admin/backfills/{field}`; a run cut short by a restart or failures resumes after
+//! the last meme it got to when started again.
+
+use crate::{
+    domain::{BackfillProgress, BackfillState, ConsistencyLevel},
+    errors::{AppError, RepoError, StorageError},
+    models::Meme,
+    AppState,
+};
+use chrono::{DateTime, Utc};
+use sha2::{Digest, Sha256};
+use std::{
+    str::FromStr,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tokio::time::MissedTickBehavior;
+
+/// Progress is saved after this many memes, or after [`SAVE_INTERVAL`], whichever comes first.
+const SAVE_EVERY: u64 = 25;
+const SAVE_INTERVAL: Duration = Duration::from_secs(10);
+
+/// A running backfill whose progress was not saved for this long is taken for dead (its
+/// instance stopped), and can be started again.
+const STALE_AFTER: chrono::Duration = chrono::Duration::minutes(2);
+
+/// A run stops after this many memes in a row failed, taking the backend to be down.
+const MAX_CONSECUTIVE_FAILURES: u32 = 10;
+
+/// How often a lost race with another write is retried before the meme counts as failed.
+const UPDATE_ATTEMPTS: usize = 3;
+
+/// An attribute a backfill fills in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BackfillField {
+    CreatedAt,
+    ContentHash,
+}
+
+impl BackfillField {
+    pub const ALL: [BackfillField; 2] = [BackfillField::CreatedAt, BackfillField::ContentHash];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            BackfillField::CreatedAt => "created_at",
+            BackfillField::ContentHash => "content_hash",
+        }
+    }
+
+    fn is_missing(self, meme: &Meme) -> bool {
+        match self {
+            BackfillField::CreatedAt => meme.created_at.is_none(),
+            BackfillField::ContentHash => meme.content_hash.is_none(),
+        }
+    }
+}
+
+impl FromStr for BackfillField {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        BackfillField::ALL
+            .into_iter()
+            .find(|field| field.name() == s)
+            .ok_or_else(|| format!("unknown backfill '{}' (expected created_at or content_hash)", s))
+    }
+}
+
+/// A value worked out for a meme.
+enum Filled {
+    CreatedAt(DateTime<Utc>),
+    ContentHash(String),
+}
+
+/// Starts a backfill of `field` in the background and returns its initial progress. An
+/// interrupted run is resumed unless `restart` is set; a completed one starts over, for
+/// memes stored without the attribute since. Fails with `AppError::Conflict` while a run
+/// is under way, on this instance or another.
+pub async fn start(state: Arc<AppState>, field: BackfillField, restart: bool) -> Result<BackfillProgress, AppError> {
+    let now = Utc::now();
+    let previous = state.backfill_repo.load(field.name()).await?;
+    if let Some(previous) = &previous
+        && previous.state == BackfillState::Running
+        && now - previous.updated_at < STALE_AFTER
+    {
+        return Err(AppError::Conflict(format!("The {} backfill is already running", field.name())));
+    }
+
+    let mut memes = state.meme_repo.list_all().await?;
+    memes.sort_by_key(|meme| meme.meme_id);
+    let progress = match previous.filter(|previous| !restart && previous.state != BackfillState::Completed) {
+        Some(previous) => {
+            memes.retain(|meme| previous.last_meme_id.is_none_or(|last| meme.meme_id > last));
+            BackfillProgress { state: BackfillState::Running, updated_at: now, error: None, ..previous }
+        }
+        None => BackfillProgress {
+            field: field.name().to_string(),
+            state: BackfillState::Running,
+            started_at: now,
+            updated_at: now,
+            last_meme_id: None,
+            total: memes.len() as u64,
+            scanned: 0,
+            updated: 0,
+            failed: 0,
+            error: None,
+        },
+    };
+    state.backfill_repo.save(&progress).await?;
+    tracing::info!(field = field.name(), remaining = memes.len(), resumed = progress.scanned > 0, "Backfill started");
+    tokio::spawn(run(state, field, memes, progress.clone()));
+    Ok(progress)
+}
+
+async fn run(state: Arc<AppState>, field: BackfillField, memes: Vec<Meme>, mut progress: BackfillProgress) {
+    let mut ticker = tokio::time::interval(Duration::from_secs_f64(1.0 / f64::from(state.config.backfill_rate)));
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    let mut last_saved = Instant::now();
+    let mut consecutive_failures = 0;
+
+    for meme in memes {
+        let meme_id = meme.meme_id;
+        if field.is_missing(&meme) {
+            ticker.tick().await;
+            match fill(&state, field, meme).await {
+                Ok(updated) => {
+                    progress.updated += u64::from(updated);
+                    consecutive_failures = 0;
+                }
+                Err(e) => {
+                    tracing::warn!(field = field.name(), %meme_id, error = %e, "Failed to backfill meme");
+                    progress.failed += 1;
+                    consecutive_failures += 1;
+                    if consecutive_failures >= MAX_CONSECUTIVE_FAILURES {
+                        // Resumes at this meme
+                        progress.state = BackfillState::Interrupted;
+                        progress.error = Some(format!("Stopped after {} failures in a row; last: {}", consecutive_failures, e));
+                        break;
+                    }
+                }
+            }
+        }
+        progress.scanned += 1;
+        progress.last_meme_id = Some(meme_id);
+        if progress.scanned.is_multiple_of(SAVE_EVERY) || last_saved.elapsed() >= SAVE_INTERVAL {
+            save(&state, &mut progress).await;
+            last_saved = Instant::now();
+        }
+    }
+
+    if progress.state == BackfillState::Running {
+        progress.state = BackfillState::Completed;
+    }
+    save(&state, &mut progress).await;
+    tracing::info!(
+        field = field.name(),
+        state = ?progress.state,
+        scanned = progress.scanned,
+        updated = progress.updated,
+        failed = progress.failed,
+        "Backfill finished"
+    );
+}
+
+/// Saves the progress, stamping it. A failed save is only logged: the run goes on, and at
+/// worst repeats some memes when resumed.
+async fn save(state: &AppState, progress: &mut BackfillProgress) {
+    progress.updated_at = Utc::now();
+    if let Err(e) = state.backfill_repo.save(progress).await {
+        tracing::warn!(field = %progress.field, error = %e, "Failed to save backfill progress");
+    }
+}
+
+/// Works out the meme's value and stores it as the meme's next version. Returns whether the
+/// meme was updated: not when it was deleted or got the value meanwhile, or when its image
+/// has no modification time.
+async fn fill(state: &AppState, field: BackfillField, meme: Meme) -> Result<bool, AppError> {
+    let value = match field {
+        BackfillField::CreatedAt => match state.file_storage.head(&meme.image_key).await?.last_modified {
+            Some(written_at) => Filled::CreatedAt(written_at),
+            None => return Ok(false),
+        },
+        BackfillField::ContentHash => {
+            let (body, _) = state.file_storage.download(&meme.image_key).await?;
+            let data = body
+                .collect()
+                .await
+                .map_err(|e| StorageError::BackendError(anyhow::Error::new(e).context(format!("Failed to read image '{}'", meme.image_key))))?
+                .into_bytes();
+            Filled::ContentHash(hex::encode(Sha256::digest(&data)))
+        }
+    };
+
+    let mut current = meme;
+    for _ in 0..UPDATE_ATTEMPTS {
+        if !field.is_missing(&current) {
+            return Ok(false);
+        }
+        let mut updated = Meme { version: current.version + 1, ..current.clone() };
+        match &value {
+            Filled::CreatedAt(created_at) => updated.created_at = Some(*created_at),
+            Filled::ContentHash(content_hash) => updated.content_hash = Some(content_hash.clone()),
+        }
+        match state.meme_repo.update(&updated, current.version).await {
+            Ok(()) => return Ok(true),
+            Err(RepoError::VersionConflict { .. }) => match state.meme_repo.get_by_id(current.meme_id, ConsistencyLevel::Strong).await? {
+                Some(meme) => current = meme,
+                None => return Ok(false),
+            },
+            Err(RepoError::NotFound(_)) => return Ok(false),
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Err(AppError::Conflict(format!("Meme {} kept changing while it was backfilled", current.meme_id)))
+}
@@ -0,0 +1,122 @@
+use crate::{
+    content_filter::ContentFilter,
+    domain::{FileStorage, MemeRepository, UploadOptions},
+    errors::{AppError, StorageError},
+    export::{ExportManifest, ARCHIVE_VERSION},
+    import::{self, ImportReport},
+    models::Meme,
+    services,
+    validation::ValidationLimits,
+    AppState,
+};
+use serde::Serialize;
+use std::{sync::Arc, time::Duration};
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+
+/// Result of a completed metadata backup.
+#[derive(Serialize, Debug)]
+pub struct BackupSummary {
+    /// Storage key of the written JSONL file.
+    pub key: String,
+    pub memes: usize,
+}
+
+/// Writes every meme's metadata as one JSON object per line to `{prefix}/memes-{timestamp}.jsonl`.
+pub async fn run_backup(
+    repo: &dyn MemeRepository,
+    storage: &dyn FileStorage,
+    prefix: &str,
+) -> Result<BackupSummary, AppError> {
+    let memes = repo.list_all().await?;
+
+    let mut jsonl = Vec::new();
+    for meme in &memes {
+        serde_json::to_writer(&mut jsonl, meme)
+            .map_err(|e| AppError::InternalServerError(format!("Failed to serialize meme for backup: {}", e)))?;
+        jsonl.push(b'\n');
+    }
+
+    let key = format!(
+        "{}/memes-{}.jsonl",
+        prefix.trim_end_matches('/'),
+        chrono::Utc::now().format("%Y%m%dT%H%M%SZ")
+    );
+    storage
+        .upload(&key, jsonl.into(), UploadOptions::with_content_type("application/x-ndjson"))
+        .await?;
+
+    tracing::info!(backup_key = %key, memes = memes.len(), "Metadata backup written");
+    Ok(BackupSummary { key, memes: memes.len() })
+}
+
+/// Recreates memes from a JSONL backup, validated like uploads. Memes that still exist are
+/// skipped, so a restore can be re-run safely. Images are expected to still be present in storage.
+pub async fn restore_backup(
+    repo: &dyn MemeRepository,
+    storage: &dyn FileStorage,
+    limits: &ValidationLimits,
+    filter: &ContentFilter,
+    key: &str,
+) -> Result<ImportReport, AppError> {
+    let (byte_stream, _) = storage.download(key).await?;
+    let data = byte_stream
+        .collect()
+        .await
+        .map_err(|e| AppError::StorageError(StorageError::BackendError(anyhow::Error::new(e).context("Failed to read backup file"))))?
+        .into_bytes();
+
+    let memes = data
+        .split(|b| *b == b'\n')
+        .enumerate()
+        .filter(|(_, line)| !line.iter().all(u8::is_ascii_whitespace))
+        .map(|(number, line)| {
+            serde_json::from_slice::<Meme>(line).map_err(|e| {
+                AppError::InvalidInput(format!("Invalid backup line {} in '{}': {}", number + 1, key, e))
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    tracing::info!(backup_key = %key, memes = memes.len(), "Restoring metadata backup");
+    let manifest = ExportManifest { version: ARCHIVE_VERSION, memes, missing_images: Vec::new() };
+    import::import_manifest(repo, limits, filter, manifest).await
+}
+
+/// Restores the backup at `key` into `state`'s meme table with its current upload limits and
+/// blocklist, as `POST /admin/backups/restore` and the `restore-backup` command do.
+pub async fn restore(state: &AppState, key: &str) -> Result<ImportReport, AppError> {
+    let limits = services::upload_limits(state).await?;
+    let filter = state.content_filter.read().expect("content filter lock poisoned").clone();
+    restore_backup(state.meme_repo.as_ref(), state.file_storage.as_ref(), &limits, &filter, key).await
+}
+
+/// Spawns a task that writes a backup every `interval`. Failures are logged and retried
+/// on the next tick. The task exits once `shutdown` is cancelled, after finishing any
+/// backup that is already running.
+#[cfg_attr(feature = "lambda", allow(dead_code))] // Not scheduled on Lambda
+pub fn spawn_scheduled_backups(
+    state: Arc<AppState>,
+    interval: Duration,
+    shutdown: CancellationToken,
+) -> JoinHandle<()> {
+    tracing::info!(interval_secs = interval.as_secs(), "Scheduling periodic metadata backups");
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        ticker.tick().await; // First tick completes immediately; skip backing up at startup
+        loop {
+            tokio::select! {
+                _ = ticker.tick() => {}
+                _ = shutdown.cancelled() => break,
+            }
+            if let Err(e) = run_backup(
+                state.meme_repo.as_ref(),
+                state.file_storage.as_ref(),
+                &state.config.backup_prefix,
+            )
+            .await
+            {
+                tracing::error!(error = %e, "Scheduled metadata backup failed");
+            }
+        }
+    })
+}
@@ -0,0 +1,319 @@
+//! Drives a running server with concurrent requests and reports latency percentiles per
+//! operation: `cargo run --release --bin loadtest -- --url http://localhost:3000`.
+//!
+//! Workers pick operations at random by `--mix` weights: fetching a meme by ID, listing
+//! memes, and uploading a small image through `POST /memes`. With `--max-p99-ms` or
+//! `--max-error-rate` the run fails when an operation is slower or fails more often, so it
+//! can gate a deployment or a CI job.
+
+use anyhow::{bail, Context};
+use base64::{prelude::BASE64_STANDARD, Engine};
+use clap::Parser;
+use reqwest::{header, Client, StatusCode};
+use std::{
+    collections::BTreeMap,
+    str::FromStr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// A 1x1 PNG, the image of every upload.
+const PNG: [u8; 67] = [
+    0x89, 0x50, 0x4e, 0x47, 0x0d, 0x0a, 0x1a, 0x0a, 0x00, 0x00, 0x00, 0x0d, 0x49, 0x48, 0x44, 0x52,
+    0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x01, 0x08, 0x06, 0x00, 0x00, 0x00, 0x1f, 0x15, 0xc4,
+    0x89, 0x00, 0x00, 0x00, 0x0a, 0x49, 0x44, 0x41, 0x54, 0x78, 0x9c, 0x63, 0x00, 0x01, 0x00, 0x00,
+    0x05, 0x00, 0x01, 0x0d, 0x0a, 0x2d, 0xb4, 0x00, 0x00, 0x00, 0x00, 0x49, 0x45, 0x4e, 0x44, 0xae,
+    0x42, 0x60, 0x82,
+];
+
+/// Memes uploaded before the run when the server has fewer to fetch.
+const MIN_MEMES: usize = 5;
+
+#[derive(Parser, Debug)]
+#[command(about = "Load-test a running meme server and report latency percentiles")]
+struct Args {
+    /// Base URL of the server
+    #[arg(long, default_value = "http://127.0.0.1:3000")]
+    url: String,
+
+    /// Number of requests in flight at the same time
+    #[arg(long, short = 'c', default_value_t = 16, value_parser = clap::value_parser!(u16).range(1..))]
+    concurrency: u16,
+
+    /// How long to send requests for, in seconds
+    #[arg(long, short = 'd', default_value_t = 30, value_parser = clap::value_parser!(u64).range(1..))]
+    duration_secs: u64,
+
+    /// Relative weights of the operations, e.g. `get=8,list=1,upload=1`
+    #[arg(long, default_value = "get=8,list=1,upload=1")]
+    mix: Mix,
+
+    /// Bearer token sent with every request (APP_ADMIN_TOKEN), to act as the owner
+    #[arg(long)]
+    token: Option<String>,
+
+    /// Per-request timeout, in seconds
+    #[arg(long, default_value_t = 10)]
+    timeout_secs: u64,
+
+    /// Fail when any operation's p99 latency exceeds this many milliseconds
+    #[arg(long)]
+    max_p99_ms: Option<u64>,
+
+    /// Fail when any operation's share of failed requests (0..=1) exceeds this
+    #[arg(long)]
+    max_error_rate: Option<f64>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Operation {
+    Get,
+    List,
+    Upload,
+}
+
+impl Operation {
+    fn name(self) -> &'static str {
+        match self {
+            Operation::Get => "get",
+            Operation::List => "list",
+            Operation::Upload => "upload",
+        }
+    }
+}
+
+/// Operations with their weights.
+#[derive(Clone, Debug)]
+struct Mix(Vec<(Operation, u32)>);
+
+impl Mix {
+    fn pick(&self) -> Operation {
+        let total: u32 = self.0.iter().map(|(_, weight)| weight).sum();
+        let mut roll = fastrand::u32(0..total);
+        for (operation, weight) in &self.0 {
+            if roll < *weight {
+                return *operation;
+            }
+            roll -= weight;
+        }
+        unreachable!("the roll is below the total weight")
+    }
+}
+
+impl FromStr for Mix {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut weights = Vec::new();
+        for entry in s.split(',').map(str::trim).filter(|entry| !entry.is_empty()) {
+            let (name, weight) = entry.split_once('=').ok_or_else(|| format!("expected OPERATION=WEIGHT, got '{}'", entry))?;
+            let operation = match name.trim() {
+                "get" => Operation::Get,
+                "list" => Operation::List,
+                "upload" => Operation::Upload,
+                other => return Err(format!("unknown operation '{}' (expected get, list or upload)", other)),
+            };
+            let weight: u32 = weight.trim().parse().map_err(|_| format!("invalid weight '{}'", weight))?;
+            if weight > 0 {
+                weights.push((operation, weight));
+            }
+        }
+        if weights.is_empty() {
+            return Err("at least one operation needs a weight above 0".to_string());
+        }
+        Ok(Mix(weights))
+    }
+}
+
+/// Latencies of successful requests and the count of failed ones, per operation.
+#[derive(Default)]
+struct Results {
+    latencies: Vec<Duration>,
+    errors: u64,
+}
+
+struct LoadTest {
+    client: Client,
+    base_url: String,
+    meme_ids: Mutex<Vec<String>>,
+}
+
+impl LoadTest {
+    fn url(&self, path: &str) -> String {
+        format!("{}{}", self.base_url, path)
+    }
+
+    /// Runs one operation; `Err` carries why it failed.
+    async fn run(&self, operation: Operation) -> Result<(), String> {
+        let response = match operation {
+            Operation::Get => {
+                let id = {
+                    let ids = self.meme_ids.lock().expect("meme id lock poisoned");
+                    ids[fastrand::usize(..ids.len())].clone()
+                };
+                self.client.get(self.url(&format!("/meme/{}", id))).send().await
+            }
+            Operation::List => self.client.get(self.url("/memes")).send().await,
+            Operation::Upload => {
+                let response = self.upload().await?;
+                let id = response["meme_id"].as_str().ok_or("upload response has no meme_id")?.to_string();
+                self.meme_ids.lock().expect("meme id lock poisoned").push(id);
+                return Ok(());
+            }
+        };
+        let response = response.map_err(|e| e.to_string())?;
+        let status = response.status();
+        // The body is part of the latency a client sees
+        response.bytes().await.map_err(|e| e.to_string())?;
+        if status.is_success() { Ok(()) } else { Err(format!("HTTP {}", status)) }
+    }
+
+    async fn upload(&self) -> Result<serde_json::Value, String> {
+        let body = serde_json::json!({
+            "title": format!("Load test {}", fastrand::u32(..)),
+            "description": "Uploaded by the load test",
+            "tags": ["loadtest"],
+            "image_base64": BASE64_STANDARD.encode(PNG),
+            "filename": "loadtest.png",
+        });
+        let response = self
+            .client
+            .post(self.url("/memes"))
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(body.to_string())
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+        let status = response.status();
+        let bytes = response.bytes().await.map_err(|e| e.to_string())?;
+        if status != StatusCode::CREATED {
+            return Err(format!("HTTP {}", status));
+        }
+        serde_json::from_slice(&bytes).map_err(|e| e.to_string())
+    }
+
+    /// Collects meme IDs to fetch, uploading a few when the server has too few.
+    async fn prepare(&self) -> anyhow::Result<()> {
+        let response = self.client.get(self.url("/memes")).send().await.context("Failed to list memes")?;
+        if !response.status().is_success() {
+            bail!("Listing memes failed with HTTP {}", response.status());
+        }
+        let memes: Vec<serde_json::Value> = serde_json::from_slice(&response.bytes().await?).context("Unexpected /memes response")?;
+        let mut ids: Vec<String> = memes.iter().filter_map(|meme| meme["meme_id"].as_str().map(str::to_string)).collect();
+        while ids.len() < MIN_MEMES {
+            let meme = self.upload().await.map_err(|e| anyhow::anyhow!("Failed to upload a meme to fetch: {}", e))?;
+            ids.extend(meme["meme_id"].as_str().map(str::to_string));
+        }
+        *self.meme_ids.lock().expect("meme id lock poisoned") = ids;
+        Ok(())
+    }
+}
+
+/// The `p`th percentile (0..=100) of sorted latencies, by nearest rank.
+fn percentile(sorted: &[Duration], p: f64) -> Duration {
+    if sorted.is_empty() {
+        return Duration::ZERO;
+    }
+    let rank = ((sorted.len() as f64 * p / 100.0).ceil() as usize).clamp(1, sorted.len());
+    sorted[rank - 1]
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let args = Args::parse();
+    let mut headers = header::HeaderMap::new();
+    if let Some(token) = &args.token {
+        let mut value = header::HeaderValue::from_str(&format!("Bearer {}", token)).context("Invalid token")?;
+        value.set_sensitive(true);
+        headers.insert(header::AUTHORIZATION, value);
+    }
+    let client = Client::builder()
+        .default_headers(headers)
+        .timeout(Duration::from_secs(args.timeout_secs))
+        .pool_max_idle_per_host(usize::from(args.concurrency))
+        .build()
+        .context("Failed to build the HTTP client")?;
+    let test = Arc::new(LoadTest {
+        client,
+        base_url: args.url.trim_end_matches('/').to_string(),
+        meme_ids: Mutex::new(Vec::new()),
+    });
+    test.prepare().await?;
+
+    println!(
+        "Load testing {} with {} concurrent requests for {}s...",
+        test.base_url, args.concurrency, args.duration_secs
+    );
+    let started = Instant::now();
+    let deadline = started + Duration::from_secs(args.duration_secs);
+    let results: Arc<Mutex<BTreeMap<Operation, Results>>> = Arc::default();
+    let workers: Vec<_> = (0..args.concurrency)
+        .map(|_| {
+            let (test, results, mix) = (test.clone(), results.clone(), args.mix.clone());
+            tokio::spawn(async move {
+                let mut first_error_logged = false;
+                while Instant::now() < deadline {
+                    let operation = mix.pick();
+                    let request_started = Instant::now();
+                    let outcome = test.run(operation).await;
+                    let latency = request_started.elapsed();
+                    let mut results = results.lock().expect("results lock poisoned");
+                    let entry = results.entry(operation).or_default();
+                    match outcome {
+                        Ok(()) => entry.latencies.push(latency),
+                        Err(e) => {
+                            entry.errors += 1;
+                            if !first_error_logged {
+                                eprintln!("{} failed: {}", operation.name(), e);
+                                first_error_logged = true;
+                            }
+                        }
+                    }
+                }
+            })
+        })
+        .collect();
+    for worker in workers {
+        worker.await.context("A worker panicked")?;
+    }
+    let elapsed = started.elapsed();
+
+    let results = std::mem::take(&mut *results.lock().expect("results lock poisoned"));
+    println!(
+        "\n{:<8} {:>9} {:>8} {:>9} {:>10} {:>10} {:>10} {:>10} {:>10}",
+        "op", "requests", "errors", "req/s", "p50", "p90", "p99", "p99.9", "max"
+    );
+    let mut failures = Vec::new();
+    for (operation, mut result) in results {
+        result.latencies.sort_unstable();
+        let requests = result.latencies.len() as u64 + result.errors;
+        let error_rate = result.errors as f64 / requests.max(1) as f64;
+        let p99 = percentile(&result.latencies, 99.0);
+        println!(
+            "{:<8} {:>9} {:>8} {:>9.1} {:>10.2?} {:>10.2?} {:>10.2?} {:>10.2?} {:>10.2?}",
+            operation.name(),
+            requests,
+            result.errors,
+            requests as f64 / elapsed.as_secs_f64(),
+            percentile(&result.latencies, 50.0),
+            percentile(&result.latencies, 90.0),
+            p99,
+            percentile(&result.latencies, 99.9),
+            result.latencies.last().copied().unwrap_or_default(),
+        );
+        if let Some(max_p99_ms) = args.max_p99_ms
+            && p99 > Duration::from_millis(max_p99_ms)
+        {
+            failures.push(format!("{} p99 of {:.2?} exceeds {}ms", operation.name(), p99, max_p99_ms));
+        }
+        if let Some(max_error_rate) = args.max_error_rate
+            && error_rate > max_error_rate
+        {
+            failures.push(format!("{} error rate of {:.3} exceeds {}", operation.name(), error_rate, max_error_rate));
+        }
+    }
+    if !failures.is_empty() {
+        bail!("Load test failed: {}", failures.join("; "));
+    }
+    Ok(())
+}
@@ -0,0 +1,209 @@
+//! Generates fake memes against the configured backends, for demos and load tests.
+//!
+//! Unlike `cargo run -- seed`, which loads hand-written fixtures, this makes up titles,
+//! descriptions, tags and placeholder images: `cargo run --bin seed -- --count 500`.
+
+use anyhow::bail;
+use axum_meme_posting_example::{
+    build_app_state,
+    config::Config,
+    services::{self, Caller, ImageInput, ImageUpload},
+    validation::MemeSubmission,
+    AppState,
+};
+use clap::Parser;
+use futures::StreamExt;
+use std::{path::PathBuf, str::FromStr, sync::Arc};
+use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
+
+const LOREM: &[&str] = &[
+    "lorem", "ipsum", "dolor", "sit", "amet", "consectetur", "adipiscing", "elit", "sed", "do",
+    "eiusmod", "tempor", "incididunt", "ut", "labore", "et", "dolore", "magna", "aliqua", "enim",
+    "ad", "minim", "veniam", "quis", "nostrud", "exercitation", "ullamco", "laboris", "nisi",
+    "aliquip", "ex", "ea", "commodo", "consequat", "duis", "aute", "irure", "in", "voluptate",
+    "velit", "esse", "cillum", "fugiat", "nulla", "pariatur",
+];
+/// Largest accepted width or height, to keep generated images within upload limits.
+const MAX_DIMENSION: u32 = 4096;
+
+#[derive(Parser, Debug)]
+#[command(about = "Generate fake memes against the configured backends")]
+struct Args {
+    /// TOML config file to layer under environment variables (overrides APP_CONFIG_FILE)
+    #[arg(long, value_name = "PATH")]
+    config: Option<PathBuf>,
+
+    /// Number of memes to create
+    #[arg(long, short = 'n', default_value_t = 20)]
+    count: u64,
+
+    /// Tags to choose from (comma-separated)
+    #[arg(long, value_delimiter = ',', default_value = "funny,cats,programming,reaction,classic")]
+    tags: Vec<String>,
+
+    /// Maximum number of tags per meme, picked at random from --tags
+    #[arg(long, default_value_t = 2)]
+    tags_per_meme: usize,
+
+    /// Image sizes to choose from, as WIDTHxHEIGHT (comma-separated)
+    #[arg(long, value_delimiter = ',', default_value = "640x480")]
+    sizes: Vec<ImageSize>,
+
+    /// Number of memes uploaded at the same time
+    #[arg(long, default_value_t = 4, value_parser = clap::value_parser!(u16).range(1..))]
+    concurrency: u16,
+
+    /// Seed for the random generator, to repeat a run exactly
+    #[arg(long)]
+    random_seed: Option<u64>,
+}
+
+/// Dimensions of a generated placeholder image.
+#[derive(Clone, Copy, Debug)]
+struct ImageSize {
+    width: u32,
+    height: u32,
+}
+
+impl FromStr for ImageSize {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let (width, height) = value
+            .split_once(['x', 'X'])
+            .ok_or_else(|| format!("expected WIDTHxHEIGHT, got '{}'", value))?;
+        let parse = |part: &str| match part.trim().parse::<u32>() {
+            Ok(n) if (1..=MAX_DIMENSION).contains(&n) => Ok(n),
+            _ => Err(format!("'{}' is not a dimension between 1 and {}", part, MAX_DIMENSION)),
+        };
+        Ok(Self { width: parse(width)?, height: parse(height)? })
+    }
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let args = Args::parse();
+
+    tracing_subscriber::registry()
+        .with(EnvFilter::try_from_default_env().unwrap_or_else(|_| "axum_meme_posting_example=info,seed=info,warn".into()))
+        .with(tracing_subscriber::fmt::layer())
+        .init();
+
+    let config = Config::load(args.config.as_deref()).await?;
+    let state = build_app_state(config).await?;
+
+    let base_seed = args.random_seed.unwrap_or_else(|| fastrand::u64(..));
+    tracing::info!(count = args.count, random_seed = base_seed, "Generating memes");
+    let args = Arc::new(args);
+    let failed = futures::stream::iter(0..args.count)
+        .map(|index| {
+            // One generator per meme keeps runs repeatable regardless of completion order
+            let rng = fastrand::Rng::with_seed(base_seed.wrapping_add(index));
+            create_fake_meme(&state, &args, rng)
+        })
+        .buffer_unordered(usize::from(args.concurrency))
+        .filter(|result| std::future::ready(result.is_err()))
+        .count()
+        .await as u64;
+
+    println!("Created {} of {} meme(s) (random seed {}).", args.count - failed, args.count, base_seed);
+    if failed > 0 {
+        bail!("{} meme(s) could not be created", failed);
+    }
+    Ok(())
+}
+
+/// Creates one meme through the regular upload path, so it is validated and stored like any
+/// other. Failures are logged and returned for counting.
+async fn create_fake_meme(state: &AppState, args: &Args, mut rng: fastrand::Rng) -> Result<(), ()> {
+    let title = capitalize(&words(&mut rng, 2..=5));
+    let description = (0..rng.usize(1..=2))
+        .map(|_| format!("{}.", capitalize(&words(&mut rng, 6..=12))))
+        .collect::<Vec<_>>()
+        .join(" ");
+    let mut tags = args.tags.clone();
+    rng.shuffle(&mut tags);
+    tags.truncate(rng.usize(0..=args.tags_per_meme));
+    let size = args.sizes[rng.usize(..args.sizes.len())];
+
+    let submission = MemeSubmission {
+        title: Some(title),
+        description: Some(description),
+        tags,
+        expires_in: None,
+        publish_at: None,
+        status: None,
+    };
+    let image = ImageInput::Provided(ImageUpload {
+        data: placeholder_bmp(size, &mut rng).into(),
+        filename: Some("placeholder.bmp".to_string()),
+        content_type: None,
+        source_url: None,
+    });
+    match services::create_meme(state, submission, image, &Caller::seed()).await {
+        Ok(meme) => {
+            tracing::info!(meme_id = %meme.meme_id, title = %meme.title, "Generated meme");
+            Ok(())
+        }
+        Err(e) => {
+            tracing::error!(error = %e, "Failed to create generated meme");
+            Err(())
+        }
+    }
+}
+
+fn words(rng: &mut fastrand::Rng, count: std::ops::RangeInclusive<usize>) -> String {
+    (0..rng.usize(count))
+        .map(|_| LOREM[rng.usize(..LOREM.len())])
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn capitalize(text: &str) -> String {
+    let mut chars = text.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+/// Encodes an uncompressed 24-bit BMP filled with a diagonal gradient between two random
+/// colours. Uncompressed, so the stored size follows the requested dimensions.
+fn placeholder_bmp(size: ImageSize, rng: &mut fastrand::Rng) -> Vec<u8> {
+    const HEADER_LEN: usize = 14 + 40; // BITMAPFILEHEADER + BITMAPINFOHEADER
+    let (width, height) = (size.width as usize, size.height as usize);
+    let row_len = (width * 3).div_ceil(4) * 4; // Rows are padded to 4 bytes
+    let pixel_len = row_len * height;
+
+    let mut bmp = Vec::with_capacity(HEADER_LEN + pixel_len);
+    bmp.extend_from_slice(b"BM");
+    bmp.extend_from_slice(&((HEADER_LEN + pixel_len) as u32).to_le_bytes());
+    bmp.extend_from_slice(&0u32.to_le_bytes()); // Reserved
+    bmp.extend_from_slice(&(HEADER_LEN as u32).to_le_bytes()); // Pixel data offset
+    bmp.extend_from_slice(&40u32.to_le_bytes());
+    bmp.extend_from_slice(&size.width.to_le_bytes());
+    bmp.extend_from_slice(&size.height.to_le_bytes()); // Positive height: rows stored bottom-up
+    bmp.extend_from_slice(&1u16.to_le_bytes()); // Colour planes
+    bmp.extend_from_slice(&24u16.to_le_bytes()); // Bits per pixel
+    bmp.extend_from_slice(&0u32.to_le_bytes()); // BI_RGB (uncompressed)
+    bmp.extend_from_slice(&(pixel_len as u32).to_le_bytes());
+    bmp.extend_from_slice(&2835u32.to_le_bytes()); // 72 DPI, horizontally
+    bmp.extend_from_slice(&2835u32.to_le_bytes()); // and vertically
+    bmp.extend_from_slice(&0u32.to_le_bytes()); // No palette
+    bmp.extend_from_slice(&0u32.to_le_bytes());
+
+    let from = [rng.u8(..), rng.u8(..), rng.u8(..)];
+    let to = [rng.u8(..), rng.u8(..), rng.u8(..)];
+    let span = (width + height).saturating_sub(2).max(1) as u32;
+    for y in 0..height {
+        for x in 0..width {
+            let t = (x + y) as u32;
+            for channel in [2, 1, 0] { // BMP stores pixels as BGR
+                let (a, b) = (u32::from(from[channel]), u32::from(to[channel]));
+                bmp.push(((a * (span - t) + b * t) / span) as u8);
+            }
+        }
+        bmp.resize(bmp.len() + row_len - width * 3, 0);
+    }
+    bmp
+}
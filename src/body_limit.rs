@@ -0,0 +1,249 @@
+use crate::{errors::AppError, fetcher::sniff_image_type};
+use axum::{
+    body::Body,
+    extract::Request,
+    http::header,
+    response::{IntoResponse, Response},
+};
+use futures::future::{self, Either};
+use http_body_util::Limited;
+use serde::Serialize;
+use std::{
+    convert::Infallible,
+    str::FromStr,
+    sync::Arc,
+    task::{Context, Poll},
+};
+use tower::{Layer, Service};
+
+/// Largest body accepted for one media type (`image/png`) or a whole kind of them (`image/*`).
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct MediaTypeLimit {
+    pub media_type: String,
+    pub max_bytes: usize,
+}
+
+/// Body limits by media type, as configured: comma-separated `<media type>=<bytes>` entries,
+/// e.g. `application/json=2097152,image/*=10485760`.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+#[serde(transparent)]
+pub struct MediaTypeLimits(pub Vec<MediaTypeLimit>);
+
+impl FromStr for MediaTypeLimits {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut limits: Vec<MediaTypeLimit> = Vec::new();
+        for entry in s.split(',').map(str::trim).filter(|entry| !entry.is_empty()) {
+            let (media_type, max_bytes) = entry
+                .split_once('=')
+                .ok_or_else(|| format!("expected <media type>=<bytes>, got '{}'", entry))?;
+            let media_type = media_type.trim().to_ascii_lowercase();
+            let is_media_type = media_type
+                .split_once('/')
+                .is_some_and(|(kind, subtype)| !kind.is_empty() && kind != "*" && !subtype.is_empty());
+            if !is_media_type {
+                return Err(format!("'{}' is not a media type like image/png or image/*", media_type));
+            }
+            let max_bytes = max_bytes
+                .trim()
+                .parse()
+                .map_err(|_| format!("the limit of {} must be a number of bytes", media_type))?;
+            if limits.iter().any(|limit| limit.media_type == media_type) {
+                return Err(format!("{} is listed more than once", media_type));
+            }
+            limits.push(MediaTypeLimit { media_type, max_bytes });
+        }
+        Ok(MediaTypeLimits(limits))
+    }
+}
+
+/// The body limits of a group of routes: per media type, and a default for other types and
+/// requests without a `Content-Type`. On the upload routes, the per-type limits also apply to
+/// the uploaded file (see [`BodyLimits::check_file`]).
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct BodyLimits {
+    pub by_media_type: MediaTypeLimits,
+    pub default: usize,
+}
+
+impl BodyLimits {
+    /// The limit for a request's `Content-Type`: an exact match, else one for its kind
+    /// (`image/*`), else the default. Parameters such as `charset` are ignored.
+    pub fn limit_for(&self, content_type: Option<&str>) -> usize {
+        content_type.and_then(|value| self.listed_limit(value)).unwrap_or(self.default)
+    }
+
+    /// The limit listed for a media type, exactly or for its kind; `None` when neither is.
+    pub fn listed_limit(&self, content_type: &str) -> Option<usize> {
+        let media_type = content_type.split(';').next().unwrap_or_default().trim().to_ascii_lowercase();
+        let kind = media_type.split_once('/').map(|(kind, _)| format!("{}/*", kind));
+        let find = |wanted: &str| self.by_media_type.0.iter().find(|limit| limit.media_type == wanted);
+        find(&media_type)
+            .or_else(|| kind.as_deref().and_then(find))
+            .map(|limit| limit.max_bytes)
+    }
+
+    /// Refuses an uploaded file over the limit listed for its type, with a 413. Both the type
+    /// sniffed from its bytes and the declared one (the multipart part's, `content_type` or
+    /// the tus `filetype`) count, and the smaller limit wins, so mislabeling a file does not
+    /// dodge its limit. Files of unlisted types are left to the upload size limit.
+    pub fn check_file(&self, declared_type: Option<&str>, data: &[u8]) -> Result<(), AppError> {
+        let sniffed_type = sniff_image_type(data).map(|(media_type, _)| media_type);
+        let limited = [sniffed_type, declared_type]
+            .into_iter()
+            .flatten()
+            .filter_map(|media_type| Some((media_type, self.listed_limit(media_type)?)))
+            .min_by_key(|(_, limit)| *limit);
+        match limited {
+            Some((media_type, limit)) if data.len() > limit => Err(AppError::PayloadTooLarge(format!(
+                "{} files are limited to {} bytes (got {})",
+                media_type,
+                limit,
+                data.len()
+            ))),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Layer enforcing [`BodyLimits`] on the routes it wraps. Replaces axum's single
+/// `DefaultBodyLimit`, which the router disables: requests declaring a larger
+/// `Content-Length` are refused with a 413 right away, and other bodies fail once they pass
+/// the limit, which extractors also report as 413. Handlers reading the raw body see the
+/// error too.
+#[derive(Clone)]
+pub struct BodyLimitLayer {
+    limits: Arc<BodyLimits>,
+}
+
+impl BodyLimitLayer {
+    pub fn new(limits: BodyLimits) -> Self {
+        Self { limits: Arc::new(limits) }
+    }
+}
+
+impl<S> Layer<S> for BodyLimitLayer {
+    type Service = BodyLimit<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        BodyLimit { inner, limits: self.limits.clone() }
+    }
+}
+
+/// Service built by [`BodyLimitLayer`].
+#[derive(Clone)]
+pub struct BodyLimit<S> {
+    inner: S,
+    limits: Arc<BodyLimits>,
+}
+
+impl<S> Service<Request> for BodyLimit<S>
+where
+    S: Service<Request, Response = Response, Error = Infallible>,
+{
+    type Response = Response;
+    type Error = Infallible;
+    type Future = Either<future::Ready<Result<Response, Infallible>>, S::Future>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request) -> Self::Future {
+        let content_type = request.headers().get(header::CONTENT_TYPE).and_then(|value| value.to_str().ok());
+        let limit = self.limits.limit_for(content_type);
+        let declared_length = request
+            .headers()
+            .get(header::CONTENT_LENGTH)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse::<u64>().ok());
+        if declared_length.is_some_and(|length| length > limit as u64) {
+            let error = AppError::PayloadTooLarge(format!(
+                "{} bodies are limited to {} bytes",
+                content_type.unwrap_or("Request"),
+                limit
+            ));
+            return Either::Left(future::ready(Ok(error.into_response())));
+        }
+        let request = request.map(|body| Body::new(Limited::new(body, limit)));
+        Either::Right(self.inner.call(request))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limits(by_media_type: &str) -> BodyLimits {
+        BodyLimits { by_media_type: by_media_type.parse().unwrap(), default: 100 }
+    }
+
+    #[test]
+    fn limit_lists_are_parsed() {
+        let parsed: MediaTypeLimits = " application/json=2048, IMAGE/*=4096 ,".parse().unwrap();
+        assert_eq!(
+            parsed.0,
+            vec![
+                MediaTypeLimit { media_type: "application/json".to_string(), max_bytes: 2048 },
+                MediaTypeLimit { media_type: "image/*".to_string(), max_bytes: 4096 },
+            ]
+        );
+        assert_eq!("".parse::<MediaTypeLimits>().unwrap(), MediaTypeLimits::default());
+    }
+
+    #[test]
+    fn malformed_limit_lists_are_refused() {
+        for (value, error) in [
+            ("image/png", "expected <media type>=<bytes>, got 'image/png'"),
+            ("png=10", "'png' is not a media type like image/png or image/*"),
+            ("*/*=10", "'*/*' is not a media type like image/png or image/*"),
+            ("image/=10", "'image/' is not a media type like image/png or image/*"),
+            ("image/png=10MB", "the limit of image/png must be a number of bytes"),
+            ("image/png=-1", "the limit of image/png must be a number of bytes"),
+            ("image/png=10,Image/PNG=20", "image/png is listed more than once"),
+        ] {
+            assert_eq!(value.parse::<MediaTypeLimits>().unwrap_err(), error, "{}", value);
+        }
+    }
+
+    #[test]
+    fn exact_types_win_over_wildcards_and_the_default() {
+        let limits = limits("image/png=10,image/*=20");
+        assert_eq!(limits.limit_for(Some("image/png")), 10);
+        assert_eq!(limits.limit_for(Some("image/gif")), 20);
+        assert_eq!(limits.limit_for(Some("application/json")), 100);
+        assert_eq!(limits.limit_for(Some("garbage")), 100);
+    }
+
+    #[test]
+    fn lookups_ignore_parameters_and_case() {
+        let limits = limits("application/json=10,text/*=20");
+        assert_eq!(limits.limit_for(Some("application/json; charset=utf-8")), 10);
+        assert_eq!(limits.limit_for(Some("Application/JSON")), 10);
+        assert_eq!(limits.limit_for(Some(" TEXT/Plain ;charset=UTF-8")), 20);
+    }
+
+    #[test]
+    fn files_are_limited_by_their_sniffed_or_declared_type() {
+        let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+        png.resize(67, 0);
+        let limits = limits("image/*=66,video/mp4=10");
+        let error = limits.check_file(Some("image/png"), &png).unwrap_err();
+        assert!(matches!(error, AppError::PayloadTooLarge(message) if message == "image/png files are limited to 66 bytes (got 67)"));
+        // Mislabeled files are held to the smaller limit of both types
+        assert!(limits.check_file(Some("application/octet-stream"), &png).is_err());
+        assert!(limits.check_file(Some("video/mp4"), &png).is_err());
+        assert!(limits.check_file(Some("video/mp4"), b"0123456789").is_ok());
+        // Unlisted types are not limited here, not even by the default
+        assert!(limits.check_file(Some("text/plain"), &[0; 1000]).is_ok());
+        assert!(limits.check_file(None, &[0; 1000]).is_ok());
+        assert!(self::limits("image/*=67").check_file(None, &png).is_ok());
+    }
+
+    #[test]
+    fn requests_without_a_content_type_get_the_default() {
+        assert_eq!(limits("application/json=10").limit_for(None), 100);
+        assert_eq!(limits("").limit_for(Some("")), 100);
+    }
+}
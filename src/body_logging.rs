@@ -0,0 +1,119 @@
+//! Debug logging of JSON request and response bodies (`APP_DEBUG_BODY_LOGGING`), to help
+//! debug client integrations. Bodies up to `APP_DEBUG_BODY_MAX_BYTES` are logged whole,
+//! with the values of `APP_DEBUG_BODY_REDACT` fields replaced at any depth; headers of the
+//! same names are redacted too. Larger and streamed bodies are only noted, since redaction
+//! needs the whole document. Off by default: bodies hold user content.
+
+use crate::{auth::REQUEST_ID_HEADER, errors::AppError, AppState};
+use axum::{
+    body::{Body, HttpBody},
+    extract::{Request, State},
+    http::{header, HeaderMap},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use serde_json::{Map, Value};
+use std::sync::Arc;
+
+/// What redacted values are replaced with, as in the configuration report.
+const REDACTED: &str = "[redacted]";
+
+/// Middleware logging the JSON bodies of requests and their responses at debug level.
+/// A no-op unless enabled.
+pub async fn log_bodies(State(state): State<Arc<AppState>>, request: Request, next: Next) -> Response {
+    let config = &state.config;
+    if !config.debug_body_logging {
+        return next.run(request).await;
+    }
+    let redact = config.debug_body_redact.as_slice();
+    let request_id = request.headers().get(REQUEST_ID_HEADER).and_then(|value| value.to_str().ok()).map(str::to_string);
+
+    let (parts, body) = request.into_parts();
+    let (body, logged) = match capture(&parts.headers, body, config.debug_body_max_bytes, redact).await {
+        Ok(captured) => captured,
+        Err(e) => return AppError::InvalidInput(format!("Failed to read the request body: {}", e)).into_response(),
+    };
+    tracing::debug!(
+        request_id = request_id.as_deref().unwrap_or("-"),
+        method = %parts.method,
+        path = parts.uri.path(),
+        headers = %headers(&parts.headers, redact),
+        body = %logged,
+        "Request body"
+    );
+    let response = next.run(Request::from_parts(parts, body)).await;
+
+    let (parts, body) = response.into_parts();
+    let (body, logged) = match capture(&parts.headers, body, config.debug_body_max_bytes, redact).await {
+        Ok(captured) => captured,
+        Err(e) => return AppError::InternalServerError(format!("Failed to read the response body: {}", e)).into_response(),
+    };
+    tracing::debug!(
+        request_id = request_id.as_deref().unwrap_or("-"),
+        status = parts.status.as_u16(),
+        body = %logged,
+        "Response body"
+    );
+    Response::from_parts(parts, body)
+}
+
+/// Buffers a JSON body whose size is known to fit `max_bytes`, returning it with its redacted
+/// form. Other bodies pass through untouched, with a note of why they were not logged.
+async fn capture(headers: &HeaderMap, body: Body, max_bytes: usize, redact: &[String]) -> Result<(Body, String), axum::Error> {
+    let is_json = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.split(';').next())
+        .is_some_and(|media_type| {
+            let media_type = media_type.trim().to_ascii_lowercase();
+            media_type == "application/json" || media_type.ends_with("+json")
+        });
+    if !is_json {
+        return Ok((body, "(not JSON)".to_string()));
+    }
+    match body.size_hint().exact() {
+        Some(size) if size as usize <= max_bytes => {}
+        Some(size) => return Ok((body, format!("({} bytes, over the {} byte cap)", size, max_bytes))),
+        None => return Ok((body, "(streamed)".to_string())),
+    }
+    let bytes = axum::body::to_bytes(body, max_bytes).await?;
+    let logged = match serde_json::from_slice::<Value>(&bytes) {
+        Ok(mut document) => {
+            redact_fields(&mut document, redact);
+            document.to_string()
+        }
+        Err(_) => format!("({} bytes of malformed JSON)", bytes.len()),
+    };
+    Ok((Body::from(bytes), logged))
+}
+
+/// Replaces the values of fields named in `redact` (lowercase), in objects at any depth.
+fn redact_fields(value: &mut Value, redact: &[String]) {
+    match value {
+        Value::Object(fields) => {
+            for (name, field) in fields.iter_mut() {
+                if redact.iter().any(|redacted| name.eq_ignore_ascii_case(redacted)) {
+                    *field = Value::String(REDACTED.to_string());
+                } else {
+                    redact_fields(field, redact);
+                }
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(|item| redact_fields(item, redact)),
+        _ => {}
+    }
+}
+
+/// The request headers as a JSON object, with the values of `redact` headers replaced.
+fn headers(headers: &HeaderMap, redact: &[String]) -> Value {
+    let mut logged = Map::new();
+    for (name, value) in headers {
+        let value = if redact.iter().any(|redacted| redacted == name.as_str()) {
+            REDACTED.to_string()
+        } else {
+            String::from_utf8_lossy(value.as_bytes()).into_owned()
+        };
+        logged.insert(name.to_string(), Value::String(value));
+    }
+    Value::Object(logged)
+}
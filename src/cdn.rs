@@ -0,0 +1,145 @@
+use crate::{
+    auth::OwnerAccess,
+    config::Config,
+    errors::AppError,
+    models::{Meme, Visibility},
+    AppState,
+};
+use axum::{
+    extract::State,
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+};
+use base64::{prelude::BASE64_STANDARD, Engine};
+use chrono::{DateTime, Utc};
+use rsa::{
+    pkcs1::DecodeRsaPrivateKey,
+    pkcs1v15::SigningKey,
+    pkcs8::DecodePrivateKey,
+    signature::{SignatureEncoding, Signer},
+    RsaPrivateKey,
+};
+use sha1::Sha1;
+use std::{sync::Arc, time::Duration};
+
+/// Signs CloudFront URLs and cookies with the private key of a CloudFront key pair
+/// (`APP_CDN_KEY_PAIR_ID` / `APP_CDN_PRIVATE_KEY`).
+pub struct CdnSigner {
+    key_pair_id: String,
+    signing_key: SigningKey<Sha1>,
+}
+
+impl CdnSigner {
+    /// Builds the signer when a key pair is configured. The key may be PKCS#1
+    /// (`BEGIN RSA PRIVATE KEY`) or PKCS#8 (`BEGIN PRIVATE KEY`) PEM.
+    pub fn from_config(config: &Config) -> Result<Option<Self>, AppError> {
+        let (Some(key_pair_id), Some(pem)) = (&config.cdn_key_pair_id, &config.cdn_private_key) else {
+            return Ok(None);
+        };
+        let private_key = RsaPrivateKey::from_pkcs1_pem(pem)
+            .or_else(|_| RsaPrivateKey::from_pkcs8_pem(pem))
+            .map_err(|e| AppError::InitError(format!("APP_CDN_PRIVATE_KEY is not an RSA private key in PEM form: {}", e)))?;
+        Ok(Some(Self { key_pair_id: key_pair_id.clone(), signing_key: SigningKey::new(private_key) }))
+    }
+
+    /// Appends a canned-policy signature to `url`, valid until `expires_at`.
+    pub fn sign_url(&self, url: &str, expires_at: DateTime<Utc>) -> String {
+        let policy = policy(url, expires_at);
+        let separator = if url.contains('?') { '&' } else { '?' };
+        format!(
+            "{}{}Expires={}&Signature={}&Key-Pair-Id={}",
+            url,
+            separator,
+            expires_at.timestamp(),
+            self.sign(&policy),
+            self.key_pair_id
+        )
+    }
+
+    /// The `CloudFront-*` cookies granting access to every URL matching `resource` (which
+    /// may end in `*`) until `expires_at`, as name/value pairs.
+    pub fn signed_cookies(&self, resource: &str, expires_at: DateTime<Utc>) -> [(&'static str, String); 3] {
+        let policy = policy(resource, expires_at);
+        [
+            ("CloudFront-Policy", cloudfront_base64(policy.as_bytes())),
+            ("CloudFront-Signature", self.sign(&policy)),
+            ("CloudFront-Key-Pair-Id", self.key_pair_id.clone()),
+        ]
+    }
+
+    fn sign(&self, policy: &str) -> String {
+        cloudfront_base64(&self.signing_key.sign(policy.as_bytes()).to_bytes())
+    }
+}
+
+/// A policy statement granting `resource` until `expires_at`. A canned policy when used for
+/// a single URL; CloudFront rebuilds it from `Expires`, so the JSON must match byte for byte.
+fn policy(resource: &str, expires_at: DateTime<Utc>) -> String {
+    format!(
+        r#"{{"Statement":[{{"Resource":"{}","Condition":{{"DateLessThan":{{"AWS:EpochTime":{}}}}}}}]}}"#,
+        resource,
+        expires_at.timestamp()
+    )
+}
+
+/// Base64 with the substitutions CloudFront expects in URLs and cookies.
+fn cloudfront_base64(bytes: &[u8]) -> String {
+    BASE64_STANDARD.encode(bytes).replace('+', "-").replace('=', "_").replace('/', "~")
+}
+
+/// Where a client can fetch a meme's image directly, valid for `APP_IMAGE_URL_TTL_SECS`.
+/// With `APP_CDN_BASE_URL` set it is a CDN URL, signed for private memes (or always, with
+/// `APP_CDN_SIGN_ALL_URLS`). Private memes get a presigned S3 URL when no key pair is set,
+/// so they never get a permanent link; without a CDN every meme does. Memes awaiting
+/// approval, which only moderators see, always get a presigned URL.
+pub async fn image_url(state: &AppState, meme: &Meme) -> Result<String, AppError> {
+    let config = &state.config;
+    let ttl = Duration::from_secs(config.image_url_ttl_secs);
+    if meme.is_awaiting_approval() {
+        return Ok(state.file_storage.presigned_url(&meme.image_key, ttl).await?);
+    }
+    let needs_signature = meme.visibility == Visibility::Private || config.cdn_sign_all_urls;
+    match (&config.cdn_base_url, &state.cdn_signer) {
+        (Some(base), Some(signer)) if needs_signature => {
+            let expires_at = Utc::now() + ttl;
+            Ok(signer.sign_url(&cdn_url(config, base, &meme.image_key), expires_at))
+        }
+        (Some(base), _) if !needs_signature => Ok(cdn_url(config, base, &meme.image_key)),
+        _ => Ok(state.file_storage.presigned_url(&meme.image_key, ttl).await?),
+    }
+}
+
+/// The CDN URL of a storage key; the distribution's origin is the bucket root.
+fn cdn_url(config: &Config, base: &str, key: &str) -> String {
+    format!("{}/{}{}", base, config.s3_key_prefix(), key)
+}
+
+/// Handler for POST /cdn/cookies. Sets CloudFront signed cookies covering every image of
+/// this deployment (or tenant) for `APP_IMAGE_URL_TTL_SECS`, so an owner's browser can load
+/// private images from the CDN without signed URLs. Needs owner credentials.
+pub async fn issue_signed_cookies(
+    State(state): State<Arc<AppState>>,
+    OwnerAccess(is_owner): OwnerAccess,
+) -> Result<(StatusCode, HeaderMap), AppError> {
+    if !is_owner {
+        return Err(AppError::Unauthorized("Signed cookies need owner credentials".to_string()));
+    }
+    let config = &state.config;
+    let (Some(base), Some(signer)) = (&config.cdn_base_url, &state.cdn_signer) else {
+        return Err(AppError::Forbidden("CDN signed cookies are not configured".to_string()));
+    };
+
+    let resource = cdn_url(config, base, "*");
+    let expires_at = Utc::now() + Duration::from_secs(config.image_url_ttl_secs);
+    let mut headers = HeaderMap::new();
+    for (name, value) in signer.signed_cookies(&resource, expires_at) {
+        let mut cookie = format!("{}={}; Path=/; Max-Age={}; Secure; HttpOnly", name, value, config.image_url_ttl_secs);
+        if let Some(domain) = &config.cdn_cookie_domain {
+            cookie.push_str(&format!("; Domain={}", domain));
+        }
+        let cookie = HeaderValue::try_from(cookie)
+            .map_err(|e| AppError::InternalServerError(format!("Invalid signed cookie: {}", e)))?;
+        headers.append(header::SET_COOKIE, cookie);
+    }
+    tracing::debug!(%resource, %expires_at, "Issued CloudFront signed cookies");
+    Ok((StatusCode::NO_CONTENT, headers))
+}
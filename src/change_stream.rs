@@ -0,0 +1,432 @@
+use crate::{
+    domain::CheckpointRepository,
+    errors::AppError,
+    models::Meme,
+    repositories::item_to_meme,
+};
+use async_trait::async_trait;
+use aws_sdk_dynamodb::{types::AttributeValue, Client as DynamoDbClient};
+use aws_sdk_dynamodbstreams::{
+    error::DisplayErrorContext,
+    operation::get_shard_iterator::GetShardIteratorError,
+    types::{AttributeValue as StreamValue, OperationType, Record, ShardIteratorType},
+    Client as StreamsClient,
+};
+use serde::Serialize;
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+    time::Duration,
+};
+use tokio::{task::JoinHandle, time::Instant};
+use tokio_util::sync::CancellationToken;
+use uuid::Uuid;
+
+/// How often the shard list is re-read to pick up shards created by DynamoDB.
+const SHARD_REFRESH_INTERVAL: Duration = Duration::from_secs(60);
+/// Attempts per handler and record before the record is skipped for that handler.
+const HANDLER_ATTEMPTS: u32 = 3;
+const HANDLER_RETRY_DELAY: Duration = Duration::from_millis(200);
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeKind {
+    Created,
+    Updated,
+    Removed,
+}
+
+/// A committed write to the meme table, decoded from a stream record.
+#[derive(Serialize, Debug, Clone)]
+pub struct MemeChange {
+    /// Stream sequence number; unique per change, so handlers can use it to drop redeliveries.
+    pub sequence_number: String,
+    pub kind: ChangeKind,
+    pub meme_id: Uuid,
+    /// The meme before the write (updates and removals).
+    pub old: Option<Meme>,
+    /// The meme after the write (creates and updates).
+    pub new: Option<Meme>,
+    /// True when DynamoDB TTL removed the item rather than the application.
+    pub removed_by_ttl: bool,
+}
+
+/// A side effect driven by committed meme changes. Records are delivered at least once,
+/// so handlers must tolerate seeing the same change again after a restart or retry.
+#[async_trait]
+pub trait ChangeHandler: Send + Sync + 'static {
+    /// Label used in logs and the `stream_handler_failures_total` metric.
+    fn name(&self) -> &'static str;
+    async fn handle(&self, change: &MemeChange) -> anyhow::Result<()>;
+}
+
+/// Reads the meme table's DynamoDB stream and feeds every change to the registered handlers.
+///
+/// Shards are read parent-first so changes to one meme are seen in commit order. Progress is
+/// checkpointed per shard after each batch; on a fresh start the consumer begins at the tip of
+/// the open shards rather than replaying the stream's 24 hours of history.
+pub struct StreamConsumer {
+    client: StreamsClient,
+    stream_arn: String,
+    checkpoints: Arc<dyn CheckpointRepository>,
+    handlers: Vec<Arc<dyn ChangeHandler>>,
+    poll_interval: Duration,
+}
+
+/// Reading position in one shard.
+struct ShardCursor {
+    parent_id: Option<String>,
+    /// Set once DynamoDB closed the shard; closed shards end when their iterator runs out.
+    closed: bool,
+    iterator: Option<String>,
+    /// Last sequence number handed to the handlers, used to recreate a lost iterator.
+    position: Option<String>,
+    loaded_checkpoint: bool,
+}
+
+impl StreamConsumer {
+    /// Looks up the table's stream. Fails if streams are disabled, so a misconfigured
+    /// consumer stops startup instead of silently doing nothing.
+    pub async fn connect(
+        db_client: &DynamoDbClient,
+        client: StreamsClient,
+        table_name: &str,
+        checkpoints: Arc<dyn CheckpointRepository>,
+        handlers: Vec<Arc<dyn ChangeHandler>>,
+        poll_interval: Duration,
+    ) -> Result<Self, AppError> {
+        let table = db_client
+            .describe_table()
+            .table_name(table_name)
+            .send()
+            .await
+            .map_err(|e| AppError::InitError(format!("Failed to describe DynamoDB table '{}': {}", table_name, DisplayErrorContext(&e))))?;
+        let stream_arn = table.table.and_then(|t| t.latest_stream_arn).ok_or_else(|| {
+            AppError::InitError(format!(
+                "DynamoDB table '{}' has no stream; run with APP_RESOURCE_INIT=create or enable a NEW_AND_OLD_IMAGES stream",
+                table_name
+            ))
+        })?;
+        Ok(Self { client, stream_arn, checkpoints, handlers, poll_interval })
+    }
+
+    /// Spawns the consumer loop. It exits once `shutdown` is cancelled, after finishing and
+    /// checkpointing the batch it is processing.
+    pub fn spawn(self, shutdown: CancellationToken) -> JoinHandle<()> {
+        let handlers: Vec<_> = self.handlers.iter().map(|h| h.name()).collect();
+        tracing::info!(stream_arn = %self.stream_arn, ?handlers, "Starting change stream consumer");
+        tokio::spawn(async move { self.run(shutdown).await })
+    }
+
+    async fn run(self, shutdown: CancellationToken) {
+        let mut shards: HashMap<String, ShardCursor> = HashMap::new();
+        // Shards no longer polled, and the subset read to their end during this run (whose
+        // children start at TRIM_HORIZON rather than at the tip)
+        let mut done: HashSet<String> = HashSet::new();
+        let mut drained: HashSet<String> = HashSet::new();
+        let mut next_refresh = Instant::now();
+
+        while !shutdown.is_cancelled() {
+            if Instant::now() >= next_refresh {
+                if let Err(e) = self.refresh_shards(&mut shards, &done).await {
+                    tracing::warn!(error = %e, "Failed to list change stream shards");
+                }
+                next_refresh = Instant::now() + SHARD_REFRESH_INTERVAL;
+            }
+
+            let ready: Vec<String> = shards
+                .iter()
+                .filter(|(_, cursor)| cursor.parent_id.as_ref().is_none_or(|parent| !shards.contains_key(parent)))
+                .map(|(shard_id, _)| shard_id.clone())
+                .collect();
+
+            let mut received = false;
+            for shard_id in ready {
+                if shutdown.is_cancelled() {
+                    break;
+                }
+                let Some(cursor) = shards.get_mut(&shard_id) else { continue };
+                match self.poll_shard(&shard_id, cursor, &drained).await {
+                    Ok(ShardPoll::Records(count)) => received |= count > 0,
+                    Ok(ShardPoll::Finished) => {
+                        tracing::debug!(%shard_id, "Change stream shard finished");
+                        shards.remove(&shard_id);
+                        drained.insert(shard_id.clone());
+                        done.insert(shard_id);
+                        next_refresh = Instant::now(); // Pick up its children right away
+                    }
+                    Ok(ShardPoll::Skipped) => {
+                        shards.remove(&shard_id);
+                        done.insert(shard_id);
+                    }
+                    Err(e) => {
+                        tracing::warn!(%shard_id, error = %e, "Failed to read change stream shard");
+                        cursor.iterator = None; // Recreated from the last position next round
+                    }
+                }
+            }
+
+            if !received {
+                tokio::select! {
+                    _ = tokio::time::sleep(self.poll_interval) => {}
+                    _ = shutdown.cancelled() => {}
+                }
+            }
+        }
+        tracing::info!("Change stream consumer stopped");
+    }
+
+    /// Adds shards that appeared since the last refresh.
+    async fn refresh_shards(
+        &self,
+        shards: &mut HashMap<String, ShardCursor>,
+        done: &HashSet<String>,
+    ) -> Result<(), AppError> {
+        let mut start_after: Option<String> = None;
+        loop {
+            let resp = self.client
+                .describe_stream()
+                .stream_arn(&self.stream_arn)
+                .set_exclusive_start_shard_id(start_after.take())
+                .send()
+                .await
+                .map_err(|e| AppError::InternalServerError(format!("DescribeStream failed: {}", DisplayErrorContext(&e))))?;
+            let Some(description) = resp.stream_description else { break };
+
+            for shard in description.shards.unwrap_or_default() {
+                let Some(shard_id) = shard.shard_id else { continue };
+                if done.contains(&shard_id) {
+                    continue;
+                }
+                let closed = shard
+                    .sequence_number_range
+                    .as_ref()
+                    .is_some_and(|range| range.ending_sequence_number.is_some());
+                shards
+                    .entry(shard_id)
+                    .and_modify(|cursor| cursor.closed = closed)
+                    .or_insert(ShardCursor {
+                        parent_id: shard.parent_shard_id,
+                        closed,
+                        iterator: None,
+                        position: None,
+                        loaded_checkpoint: false,
+                    });
+            }
+
+            match description.last_evaluated_shard_id {
+                Some(last) => start_after = Some(last),
+                None => break,
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads and handles one batch from a shard.
+    async fn poll_shard(
+        &self,
+        shard_id: &str,
+        cursor: &mut ShardCursor,
+        drained: &HashSet<String>,
+    ) -> Result<ShardPoll, AppError> {
+        if cursor.iterator.is_none() {
+            match self.open_iterator(shard_id, cursor, drained).await? {
+                Some(iterator) => cursor.iterator = Some(iterator),
+                // A closed shard from before this consumer existed: nothing to catch up on
+                None => return Ok(ShardPoll::Skipped),
+            }
+        }
+
+        let resp = self.client
+            .get_records()
+            .set_shard_iterator(cursor.iterator.clone())
+            .send()
+            .await
+            .map_err(|e| AppError::InternalServerError(format!("GetRecords failed: {}", DisplayErrorContext(&e))))?;
+
+        let records = resp.records.unwrap_or_default();
+        let mut last_sequence = None;
+        for record in &records {
+            match decode_record(record) {
+                Some(change) => {
+                    self.dispatch(&change).await;
+                    last_sequence = Some(change.sequence_number);
+                }
+                None => {
+                    tracing::warn!(%shard_id, event_id = ?record.event_id, "Skipping undecodable change stream record");
+                    if let Some(sequence) = record.dynamodb.as_ref().and_then(|r| r.sequence_number.clone()) {
+                        last_sequence = Some(sequence);
+                    }
+                }
+            }
+        }
+        metrics::counter!("stream_records_processed_total").increment(records.len() as u64);
+
+        if let Some(sequence) = last_sequence {
+            // At-least-once: a failed checkpoint only means a replay after restart
+            if let Err(e) = self.checkpoints.save_checkpoint(shard_id, &sequence).await {
+                tracing::warn!(%shard_id, error = %e, "Failed to save change stream checkpoint");
+            }
+            cursor.position = Some(sequence);
+        }
+
+        cursor.iterator = resp.next_shard_iterator;
+        if cursor.iterator.is_none() {
+            return Ok(ShardPoll::Finished);
+        }
+        Ok(ShardPoll::Records(records.len()))
+    }
+
+    /// Chooses where to start reading a shard: after the checkpoint if there is one, from the
+    /// start if it or its parent was already read in this run, otherwise at the tip of an
+    /// open shard.
+    async fn open_iterator(
+        &self,
+        shard_id: &str,
+        cursor: &mut ShardCursor,
+        drained: &HashSet<String>,
+    ) -> Result<Option<String>, AppError> {
+        // Reopening after a read error: nothing handled yet, so reread what the shard holds
+        let reopening = cursor.loaded_checkpoint;
+        if !cursor.loaded_checkpoint {
+            cursor.position = self.checkpoints.get_checkpoint(shard_id).await?;
+            cursor.loaded_checkpoint = true;
+        }
+
+        if let Some(position) = cursor.position.clone() {
+            match self.shard_iterator(shard_id, ShardIteratorType::AfterSequenceNumber, Some(position)).await {
+                Err(e) if matches!(e.as_service_error(), Some(GetShardIteratorError::TrimmedDataAccessException(_))) => {
+                    tracing::warn!(%shard_id, "Change stream checkpoint is older than the stream's retention; changes were missed");
+                }
+                result => {
+                    return result
+                        .map(Some)
+                        .map_err(|e| AppError::InternalServerError(format!("GetShardIterator failed: {}", DisplayErrorContext(&e))));
+                }
+            }
+            return self.start_iterator(shard_id, ShardIteratorType::TrimHorizon).await.map(Some);
+        }
+
+        if reopening || cursor.parent_id.as_ref().is_some_and(|parent| drained.contains(parent)) {
+            return self.start_iterator(shard_id, ShardIteratorType::TrimHorizon).await.map(Some);
+        }
+        if cursor.closed {
+            return Ok(None);
+        }
+        self.start_iterator(shard_id, ShardIteratorType::Latest).await.map(Some)
+    }
+
+    async fn start_iterator(&self, shard_id: &str, iterator_type: ShardIteratorType) -> Result<String, AppError> {
+        self.shard_iterator(shard_id, iterator_type, None)
+            .await
+            .map_err(|e| AppError::InternalServerError(format!("GetShardIterator failed: {}", DisplayErrorContext(&e))))
+    }
+
+    async fn shard_iterator(
+        &self,
+        shard_id: &str,
+        iterator_type: ShardIteratorType,
+        sequence_number: Option<String>,
+    ) -> Result<String, aws_sdk_dynamodbstreams::error::SdkError<GetShardIteratorError>> {
+        let resp = self.client
+            .get_shard_iterator()
+            .stream_arn(&self.stream_arn)
+            .shard_id(shard_id)
+            .shard_iterator_type(iterator_type)
+            .set_sequence_number(sequence_number)
+            .send()
+            .await?;
+        // The service always returns an iterator for an existing shard
+        Ok(resp.shard_iterator.unwrap_or_default())
+    }
+
+    /// Hands a change to every handler, retrying failures a few times. A handler that keeps
+    /// failing is skipped for this change so one broken side effect cannot stall the others.
+    async fn dispatch(&self, change: &MemeChange) {
+        for handler in &self.handlers {
+            let mut delay = HANDLER_RETRY_DELAY;
+            for attempt in 1..=HANDLER_ATTEMPTS {
+                match handler.handle(change).await {
+                    Ok(()) => break,
+                    Err(e) if attempt < HANDLER_ATTEMPTS => {
+                        tracing::debug!(handler = handler.name(), attempt, error = %e, "Change handler failed, retrying");
+                        tokio::time::sleep(delay).await;
+                        delay *= 2;
+                    }
+                    Err(e) => {
+                        tracing::error!(
+                            handler = handler.name(),
+                            meme_id = %change.meme_id,
+                            sequence_number = %change.sequence_number,
+                            error = %e,
+                            "Change handler failed, skipping change"
+                        );
+                        metrics::counter!("stream_handler_failures_total", "handler" => handler.name()).increment(1);
+                    }
+                }
+            }
+        }
+    }
+}
+
+enum ShardPoll {
+    Records(usize),
+    /// The shard was closed and read to its end.
+    Finished,
+    /// The shard was closed before the consumer first saw it and is not read.
+    Skipped,
+}
+
+/// Decodes a stream record into a `MemeChange`. Returns `None` for records that are not
+/// meme items or cannot be parsed.
+fn decode_record(record: &Record) -> Option<MemeChange> {
+    let kind = match record.event_name.as_ref()? {
+        OperationType::Insert => ChangeKind::Created,
+        OperationType::Modify => ChangeKind::Updated,
+        OperationType::Remove => ChangeKind::Removed,
+        _ => return None,
+    };
+    let stream_record = record.dynamodb.as_ref()?;
+    let meme_id = stream_record.keys.as_ref()?.get("meme_id")?.as_s().ok()?.parse().ok()?;
+    let decode_image = |image: &Option<HashMap<String, StreamValue>>| -> Option<Option<Meme>> {
+        match image {
+            Some(image) => item_to_meme(&convert_item(image)?).ok().map(Some),
+            None => Some(None),
+        }
+    };
+    // TTL deletions are attributed to the DynamoDB service principal
+    let removed_by_ttl = record.user_identity.as_ref().is_some_and(|identity| {
+        identity.r#type.as_deref() == Some("Service") && identity.principal_id.as_deref() == Some("dynamodb.amazonaws.com")
+    });
+
+    Some(MemeChange {
+        sequence_number: stream_record.sequence_number.clone()?,
+        kind,
+        meme_id,
+        old: decode_image(&stream_record.old_image)?,
+        new: decode_image(&stream_record.new_image)?,
+        removed_by_ttl,
+    })
+}
+
+/// Streams records use their own `AttributeValue` type; convert to the DynamoDB one so
+/// `item_to_meme` can be shared.
+fn convert_item(item: &HashMap<String, StreamValue>) -> Option<HashMap<String, AttributeValue>> {
+    item.iter().map(|(key, value)| Some((key.clone(), convert_value(value)?))).collect()
+}
+
+fn convert_value(value: &StreamValue) -> Option<AttributeValue> {
+    Some(match value {
+        StreamValue::S(s) => AttributeValue::S(s.clone()),
+        StreamValue::N(n) => AttributeValue::N(n.clone()),
+        StreamValue::B(b) => AttributeValue::B(b.clone()),
+        StreamValue::Ss(values) => AttributeValue::Ss(values.clone()),
+        StreamValue::Ns(values) => AttributeValue::Ns(values.clone()),
+        StreamValue::Bs(values) => AttributeValue::Bs(values.clone()),
+        StreamValue::Bool(b) => AttributeValue::Bool(*b),
+        StreamValue::Null(b) => AttributeValue::Null(*b),
+        StreamValue::L(values) => AttributeValue::L(values.iter().map(convert_value).collect::<Option<_>>()?),
+        StreamValue::M(map) => AttributeValue::M(convert_item(map)?),
+        _ => return None,
+    })
+}
@@ -0,0 +1,237 @@
+use crate::{
+    config::Config,
+    domain::{BlocklistRepository, ConsistencyLevel, FileStorage, MemeRepository, ObjectMetadata, StoredObject, TableInfo, UploadOptions},
+    errors::{RepoError, StorageError},
+    models::{Meme, SortOrder},
+};
+use anyhow::anyhow;
+use async_trait::async_trait;
+use aws_sdk_s3::primitives::ByteStream;
+use axum::body::Bytes;
+use chrono::{DateTime, Utc};
+use std::time::Duration;
+use uuid::Uuid;
+
+/// Faults injected into the calls of one backend.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ChaosPolicy {
+    /// Probability (0..=1) that a call fails without reaching the backend.
+    error_rate: f64,
+    /// Upper bound of the random delay added before every call.
+    max_latency: Duration,
+}
+
+impl ChaosPolicy {
+    /// Reads `APP_CHAOS_*` for `backend`; backends not in `APP_CHAOS_BACKENDS` get no faults.
+    pub fn for_backend(config: &Config, backend: &str) -> Self {
+        if !config.chaos_backends.iter().any(|b| b == backend) {
+            return Self::default();
+        }
+        let policy = Self {
+            error_rate: config.chaos_error_rate,
+            max_latency: Duration::from_millis(config.chaos_latency_ms),
+        };
+        if policy.is_active() {
+            tracing::warn!(
+                backend,
+                error_rate = policy.error_rate,
+                max_latency_ms = config.chaos_latency_ms,
+                "Fault injection enabled"
+            );
+        }
+        policy
+    }
+
+    fn is_active(&self) -> bool {
+        self.error_rate > 0.0 || !self.max_latency.is_zero()
+    }
+}
+
+/// Wraps a repository or storage and injects latency and failures according to a
+/// [`ChaosPolicy`], so retries, circuit breakers and upload compensation can be exercised
+/// without a misbehaving backend.
+///
+/// Injected failures are `BackendError`s, which the retry and breaker layers treat like real
+/// backend outages. Layered innermost, so instrumentation records them as failed calls.
+pub struct WithChaos<T> {
+    inner: T,
+    backend: &'static str,
+    policy: ChaosPolicy,
+}
+
+impl<T> WithChaos<T> {
+    pub fn new(inner: T, backend: &'static str, policy: ChaosPolicy) -> Self {
+        Self { inner, backend, policy }
+    }
+
+    /// Sleeps for a random share of the latency budget, then decides whether `operation`
+    /// fails. Returns the message of the injected failure.
+    async fn inject(&self, operation: &'static str) -> Option<String> {
+        if !self.policy.max_latency.is_zero() {
+            let max_ms = self.policy.max_latency.as_millis() as u64;
+            tokio::time::sleep(Duration::from_millis(fastrand::u64(0..=max_ms))).await;
+        }
+        if fastrand::f64() >= self.policy.error_rate {
+            return None;
+        }
+        metrics::counter!("chaos_faults_injected_total", "backend" => self.backend, "operation" => operation).increment(1);
+        tracing::debug!(backend = self.backend, operation, "Injected backend failure");
+        Some(format!("chaos: injected {} failure in {}", self.backend, operation))
+    }
+
+    async fn repo_fault(&self, operation: &'static str) -> Result<(), RepoError> {
+        match self.inject(operation).await {
+            Some(message) => Err(RepoError::BackendError(anyhow!(message))),
+            None => Ok(()),
+        }
+    }
+
+    async fn storage_fault(&self, operation: &'static str) -> Result<(), StorageError> {
+        match self.inject(operation).await {
+            Some(message) => Err(StorageError::BackendError(anyhow!(message))),
+            None => Ok(()),
+        }
+    }
+}
+
+#[async_trait]
+impl<T: MemeRepository> MemeRepository for WithChaos<T> {
+    async fn create(&self, meme: &Meme) -> Result<(), RepoError> {
+        self.repo_fault("create").await?;
+        self.inner.create(meme).await
+    }
+
+    async fn get_by_id(&self, id: Uuid, consistency: ConsistencyLevel) -> Result<Option<Meme>, RepoError> {
+        self.repo_fault("get_by_id").await?;
+        self.inner.get_by_id(id, consistency).await
+    }
+
+    async fn exists(&self, id: Uuid) -> Result<bool, RepoError> {
+        self.repo_fault("exists").await?;
+        self.inner.exists(id).await
+    }
+
+    async fn list_all(&self) -> Result<Vec<Meme>, RepoError> {
+        self.repo_fault("list_all").await?;
+        self.inner.list_all().await
+    }
+
+    async fn list_sorted(&self, order: SortOrder) -> Result<Vec<Meme>, RepoError> {
+        self.repo_fault("list_sorted").await?;
+        self.inner.list_sorted(order).await
+    }
+
+    async fn list_expired(&self, now: DateTime<Utc>) -> Result<Vec<Meme>, RepoError> {
+        self.repo_fault("list_expired").await?;
+        self.inner.list_expired(now).await
+    }
+
+    async fn list_due_for_publishing(&self, now: DateTime<Utc>) -> Result<Vec<Meme>, RepoError> {
+        self.repo_fault("list_due_for_publishing").await?;
+        self.inner.list_due_for_publishing(now).await
+    }
+
+    async fn update(&self, meme: &Meme, expected_version: u64) -> Result<(), RepoError> {
+        self.repo_fault("update").await?;
+        self.inner.update(meme, expected_version).await
+    }
+
+    async fn add_like(&self, id: Uuid) -> Result<u64, RepoError> {
+        self.repo_fault("add_like").await?;
+        self.inner.add_like(id).await
+    }
+
+    async fn add_views(&self, id: Uuid, views: u64) -> Result<(), RepoError> {
+        self.repo_fault("add_views").await?;
+        self.inner.add_views(id, views).await
+    }
+
+    async fn delete(&self, id: Uuid) -> Result<(), RepoError> {
+        self.repo_fault("delete").await?;
+        self.inner.delete(id).await
+    }
+
+    async fn describe(&self) -> Result<TableInfo, RepoError> {
+        self.repo_fault("describe").await?;
+        self.inner.describe().await
+    }
+
+    async fn count_created_since(&self, since: DateTime<Utc>) -> Result<u64, RepoError> {
+        self.repo_fault("count_created_since").await?;
+        self.inner.count_created_since(since).await
+    }
+
+    async fn count(&self) -> Result<u64, RepoError> {
+        self.repo_fault("count").await?;
+        self.inner.count().await
+    }
+
+    async fn ping(&self) -> Result<(), RepoError> {
+        self.inner.ping().await
+    }
+}
+
+#[async_trait]
+impl<T: BlocklistRepository> BlocklistRepository for WithChaos<T> {
+    async fn list_terms(&self) -> Result<Vec<String>, RepoError> {
+        self.repo_fault("list_terms").await?;
+        self.inner.list_terms().await
+    }
+
+    async fn add_term(&self, term: &str) -> Result<(), RepoError> {
+        self.repo_fault("add_term").await?;
+        self.inner.add_term(term).await
+    }
+
+    async fn remove_term(&self, term: &str) -> Result<(), RepoError> {
+        self.repo_fault("remove_term").await?;
+        self.inner.remove_term(term).await
+    }
+}
+
+#[async_trait]
+impl<T: FileStorage> FileStorage for WithChaos<T> {
+    async fn upload(&self, key: &str, data: Bytes, options: UploadOptions) -> Result<(), StorageError> {
+        self.storage_fault("upload").await?;
+        self.inner.upload(key, data, options).await
+    }
+
+    async fn download(&self, key: &str) -> Result<(ByteStream, ObjectMetadata), StorageError> {
+        self.storage_fault("download").await?;
+        self.inner.download(key).await
+    }
+
+    async fn head(&self, key: &str) -> Result<ObjectMetadata, StorageError> {
+        self.storage_fault("head").await?;
+        self.inner.head(key).await
+    }
+
+    async fn list(&self) -> Result<Vec<StoredObject>, StorageError> {
+        self.storage_fault("list").await?;
+        self.inner.list().await
+    }
+
+    /// No faults: signing makes no backend call.
+    async fn presigned_url(&self, key: &str, expires_in: Duration) -> Result<String, StorageError> {
+        self.inner.presigned_url(key, expires_in).await
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), StorageError> {
+        self.storage_fault("delete").await?;
+        self.inner.delete(key).await
+    }
+
+    async fn copy(&self, from: &str, to: &str) -> Result<(), StorageError> {
+        self.storage_fault("copy").await?;
+        self.inner.copy(from, to).await
+    }
+
+    async fn rename(&self, from: &str, to: &str) -> Result<(), StorageError> {
+        self.storage_fault("rename").await?;
+        self.inner.rename(from, to).await
+    }
+
+    async fn ping(&self) -> Result<(), StorageError> {
+        self.inner.ping().await
+    }
+}
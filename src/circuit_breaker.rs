@@ -0,0 +1,440 @@
+use crate::{
+    domain::{BlocklistRepository, ConsistencyLevel, FileStorage, MemeRepository, ObjectMetadata, StoredObject, TableInfo, UploadOptions},
+    errors::{RepoError, StorageError},
+    models::{Meme, SortOrder},
+};
+use async_trait::async_trait;
+use aws_sdk_s3::primitives::ByteStream;
+use axum::body::Bytes;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::{
+    future::Future,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BreakerState {
+    /// Calls pass through; consecutive failures are counted.
+    Closed,
+    /// Calls fail fast without reaching the backend.
+    Open,
+    /// The open period has elapsed; a single probe call decides whether to close again.
+    HalfOpen,
+}
+
+impl BreakerState {
+    /// Value exported on the `circuit_breaker_state` gauge.
+    fn gauge_value(self) -> f64 {
+        match self {
+            BreakerState::Closed => 0.0,
+            BreakerState::Open => 1.0,
+            BreakerState::HalfOpen => 2.0,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct BreakerInner {
+    state: BreakerState,
+    consecutive_failures: u32,
+    opened_at: Instant,
+    probe_in_flight: bool,
+}
+
+/// Tracks the health of one backend and short-circuits calls after repeated failures.
+///
+/// After `failure_threshold` consecutive failures the breaker opens and rejects calls for
+/// `open_duration`. It then lets one probe call through: success closes it, failure opens
+/// it again. A threshold of 0 disables the breaker.
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    name: &'static str,
+    failure_threshold: u32,
+    open_duration: Duration,
+    inner: Mutex<BreakerInner>,
+}
+
+impl CircuitBreaker {
+    pub fn new(name: &'static str, failure_threshold: u32, open_duration: Duration) -> Self {
+        metrics::gauge!("circuit_breaker_state", "backend" => name).set(BreakerState::Closed.gauge_value());
+        Self {
+            name,
+            failure_threshold,
+            open_duration,
+            inner: Mutex::new(BreakerInner {
+                state: BreakerState::Closed,
+                consecutive_failures: 0,
+                opened_at: Instant::now(),
+                probe_in_flight: false,
+            }),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Current state, reporting an expired open period as half-open.
+    pub fn state(&self) -> BreakerState {
+        let inner = self.lock();
+        match inner.state {
+            BreakerState::Open if inner.opened_at.elapsed() >= self.open_duration => BreakerState::HalfOpen,
+            state => state,
+        }
+    }
+
+    /// Runs `call` unless the breaker is open. Errors for which `is_failure` returns true
+    /// count towards opening the breaker; `unavailable` builds the error returned while open.
+    pub async fn call<T, E, F>(
+        &self,
+        call: F,
+        is_failure: impl Fn(&E) -> bool,
+        unavailable: impl FnOnce(&'static str) -> E,
+    ) -> Result<T, E>
+    where
+        F: Future<Output = Result<T, E>>,
+    {
+        let Some(mut permit) = self.acquire() else {
+            metrics::counter!("circuit_breaker_rejected_total", "backend" => self.name).increment(1);
+            return Err(unavailable(self.name));
+        };
+        let result = call.await;
+        permit.finish(!matches!(&result, Err(e) if is_failure(e)));
+        result
+    }
+
+    fn acquire(&self) -> Option<Permit<'_>> {
+        if self.failure_threshold == 0 {
+            return Some(Permit { breaker: self, probe: false, finished: true });
+        }
+        let mut inner = self.lock();
+        match inner.state {
+            BreakerState::Closed => Some(Permit { breaker: self, probe: false, finished: false }),
+            BreakerState::Open if inner.opened_at.elapsed() >= self.open_duration => {
+                self.transition(&mut inner, BreakerState::HalfOpen);
+                inner.probe_in_flight = true;
+                Some(Permit { breaker: self, probe: true, finished: false })
+            }
+            BreakerState::HalfOpen if !inner.probe_in_flight => {
+                inner.probe_in_flight = true;
+                Some(Permit { breaker: self, probe: true, finished: false })
+            }
+            BreakerState::Open | BreakerState::HalfOpen => None,
+        }
+    }
+
+    fn record(&self, success: bool, probe: bool) {
+        let mut inner = self.lock();
+        if probe {
+            inner.probe_in_flight = false;
+        }
+        if success {
+            inner.consecutive_failures = 0;
+            if inner.state != BreakerState::Closed {
+                tracing::info!(backend = self.name, "Circuit breaker closed; backend recovered");
+                self.transition(&mut inner, BreakerState::Closed);
+            }
+            return;
+        }
+
+        inner.consecutive_failures += 1;
+        let should_open = inner.state == BreakerState::HalfOpen
+            || (inner.state == BreakerState::Closed && inner.consecutive_failures >= self.failure_threshold);
+        if should_open {
+            tracing::warn!(
+                backend = self.name,
+                failures = inner.consecutive_failures,
+                open_secs = self.open_duration.as_secs(),
+                "Circuit breaker opened"
+            );
+            inner.opened_at = Instant::now();
+            self.transition(&mut inner, BreakerState::Open);
+            metrics::counter!("circuit_breaker_opened_total", "backend" => self.name).increment(1);
+        }
+    }
+
+    fn transition(&self, inner: &mut BreakerInner, state: BreakerState) {
+        inner.state = state;
+        metrics::gauge!("circuit_breaker_state", "backend" => self.name).set(state.gauge_value());
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, BreakerInner> {
+        self.inner.lock().expect("circuit breaker lock poisoned")
+    }
+}
+
+/// Permission to make one call. A probe that is dropped without finishing (e.g. the request
+/// timed out) frees the probe slot so another call can try.
+struct Permit<'a> {
+    breaker: &'a CircuitBreaker,
+    probe: bool,
+    finished: bool,
+}
+
+impl Permit<'_> {
+    fn finish(&mut self, success: bool) {
+        if !self.finished {
+            self.finished = true;
+            self.breaker.record(success, self.probe);
+        }
+    }
+}
+
+impl Drop for Permit<'_> {
+    fn drop(&mut self) {
+        if !self.finished && self.probe {
+            self.breaker.lock().probe_in_flight = false;
+        }
+    }
+}
+
+/// Decorates a repository or storage backend with a [`CircuitBreaker`].
+pub struct WithBreaker<T> {
+    inner: T,
+    breaker: Arc<CircuitBreaker>,
+}
+
+impl<T> WithBreaker<T> {
+    pub fn new(inner: T, breaker: Arc<CircuitBreaker>) -> Self {
+        Self { inner, breaker }
+    }
+}
+
+/// Only backend failures trip the breaker; missing or malformed items say nothing about availability.
+fn repo_failure(e: &RepoError) -> bool {
+    matches!(e, RepoError::BackendError(_))
+}
+
+fn storage_failure(e: &StorageError) -> bool {
+    matches!(e, StorageError::UploadFailed(_) | StorageError::BackendError(_))
+}
+
+#[async_trait]
+impl<T: MemeRepository> MemeRepository for WithBreaker<T> {
+    async fn create(&self, meme: &Meme) -> Result<(), RepoError> {
+        self.breaker.call(self.inner.create(meme), repo_failure, RepoError::Unavailable).await
+    }
+
+    async fn get_by_id(&self, id: Uuid, consistency: ConsistencyLevel) -> Result<Option<Meme>, RepoError> {
+        self.breaker.call(self.inner.get_by_id(id, consistency), repo_failure, RepoError::Unavailable).await
+    }
+
+    async fn exists(&self, id: Uuid) -> Result<bool, RepoError> {
+        self.breaker.call(self.inner.exists(id), repo_failure, RepoError::Unavailable).await
+    }
+
+    async fn list_all(&self) -> Result<Vec<Meme>, RepoError> {
+        self.breaker.call(self.inner.list_all(), repo_failure, RepoError::Unavailable).await
+    }
+
+    async fn list_sorted(&self, order: SortOrder) -> Result<Vec<Meme>, RepoError> {
+        self.breaker.call(self.inner.list_sorted(order), repo_failure, RepoError::Unavailable).await
+    }
+
+    async fn list_expired(&self, now: DateTime<Utc>) -> Result<Vec<Meme>, RepoError> {
+        self.breaker.call(self.inner.list_expired(now), repo_failure, RepoError::Unavailable).await
+    }
+
+    async fn list_due_for_publishing(&self, now: DateTime<Utc>) -> Result<Vec<Meme>, RepoError> {
+        self.breaker.call(self.inner.list_due_for_publishing(now), repo_failure, RepoError::Unavailable).await
+    }
+
+    async fn update(&self, meme: &Meme, expected_version: u64) -> Result<(), RepoError> {
+        self.breaker.call(self.inner.update(meme, expected_version), repo_failure, RepoError::Unavailable).await
+    }
+
+    async fn add_like(&self, id: Uuid) -> Result<u64, RepoError> {
+        self.breaker.call(self.inner.add_like(id), repo_failure, RepoError::Unavailable).await
+    }
+
+    async fn add_views(&self, id: Uuid, views: u64) -> Result<(), RepoError> {
+        self.breaker.call(self.inner.add_views(id, views), repo_failure, RepoError::Unavailable).await
+    }
+
+    async fn delete(&self, id: Uuid) -> Result<(), RepoError> {
+        self.breaker.call(self.inner.delete(id), repo_failure, RepoError::Unavailable).await
+    }
+
+    async fn describe(&self) -> Result<TableInfo, RepoError> {
+        self.breaker.call(self.inner.describe(), repo_failure, RepoError::Unavailable).await
+    }
+
+    async fn count_created_since(&self, since: DateTime<Utc>) -> Result<u64, RepoError> {
+        self.breaker.call(self.inner.count_created_since(since), repo_failure, RepoError::Unavailable).await
+    }
+
+    async fn count(&self) -> Result<u64, RepoError> {
+        self.breaker.call(self.inner.count(), repo_failure, RepoError::Unavailable).await
+    }
+
+    async fn ping(&self) -> Result<(), RepoError> {
+        self.inner.ping().await
+    }
+}
+
+#[async_trait]
+impl<T: BlocklistRepository> BlocklistRepository for WithBreaker<T> {
+    async fn list_terms(&self) -> Result<Vec<String>, RepoError> {
+        self.breaker.call(self.inner.list_terms(), repo_failure, RepoError::Unavailable).await
+    }
+
+    async fn add_term(&self, term: &str) -> Result<(), RepoError> {
+        self.breaker.call(self.inner.add_term(term), repo_failure, RepoError::Unavailable).await
+    }
+
+    async fn remove_term(&self, term: &str) -> Result<(), RepoError> {
+        self.breaker.call(self.inner.remove_term(term), repo_failure, RepoError::Unavailable).await
+    }
+}
+
+#[async_trait]
+impl<T: FileStorage> FileStorage for WithBreaker<T> {
+    async fn upload(&self, key: &str, data: Bytes, options: UploadOptions) -> Result<(), StorageError> {
+        self.breaker
+            .call(self.inner.upload(key, data, options), storage_failure, StorageError::Unavailable)
+            .await
+    }
+
+    async fn download(&self, key: &str) -> Result<(ByteStream, ObjectMetadata), StorageError> {
+        self.breaker.call(self.inner.download(key), storage_failure, StorageError::Unavailable).await
+    }
+
+    async fn head(&self, key: &str) -> Result<ObjectMetadata, StorageError> {
+        self.breaker.call(self.inner.head(key), storage_failure, StorageError::Unavailable).await
+    }
+
+    async fn list(&self) -> Result<Vec<StoredObject>, StorageError> {
+        self.breaker.call(self.inner.list(), storage_failure, StorageError::Unavailable).await
+    }
+
+    /// Bypasses the breaker: signing works whether or not the backend is up.
+    async fn presigned_url(&self, key: &str, expires_in: Duration) -> Result<String, StorageError> {
+        self.inner.presigned_url(key, expires_in).await
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), StorageError> {
+        self.breaker.call(self.inner.delete(key), storage_failure, StorageError::Unavailable).await
+    }
+
+    async fn copy(&self, from: &str, to: &str) -> Result<(), StorageError> {
+        self.breaker.call(self.inner.copy(from, to), storage_failure, StorageError::Unavailable).await
+    }
+
+    async fn rename(&self, from: &str, to: &str) -> Result<(), StorageError> {
+        self.breaker.call(self.inner.rename(from, to), storage_failure, StorageError::Unavailable).await
+    }
+
+    async fn ping(&self) -> Result<(), StorageError> {
+        self.inner.ping().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DOWN: &str = "down";
+    const MISSING: &str = "missing";
+
+    /// Makes one call through `breaker` that ends with `result`; only [`DOWN`] counts as a
+    /// failure.
+    async fn call(breaker: &CircuitBreaker, result: Result<(), &'static str>) -> Result<(), &'static str> {
+        breaker.call(async { result }, |e| *e == DOWN, |_| "unavailable").await
+    }
+
+    fn raw_state(breaker: &CircuitBreaker) -> BreakerState {
+        breaker.lock().state
+    }
+
+    #[tokio::test]
+    async fn opens_after_consecutive_failures() {
+        let breaker = CircuitBreaker::new("test", 3, Duration::from_secs(60));
+        for _ in 0..2 {
+            assert_eq!(call(&breaker, Err(DOWN)).await, Err(DOWN));
+        }
+        // A success resets the count, and errors that are not failures do not add to it
+        assert_eq!(call(&breaker, Ok(())).await, Ok(()));
+        assert_eq!(call(&breaker, Err(MISSING)).await, Err(MISSING));
+        for _ in 0..2 {
+            assert_eq!(call(&breaker, Err(DOWN)).await, Err(DOWN));
+        }
+        assert_eq!(breaker.state(), BreakerState::Closed);
+        assert_eq!(call(&breaker, Err(DOWN)).await, Err(DOWN));
+        assert_eq!(breaker.state(), BreakerState::Open);
+    }
+
+    #[tokio::test]
+    async fn rejects_calls_without_running_them_while_open() {
+        let breaker = CircuitBreaker::new("test", 1, Duration::from_secs(60));
+        call(&breaker, Err(DOWN)).await.unwrap_err();
+        let mut ran = false;
+        let backend = async {
+            ran = true;
+            Ok(())
+        };
+        let result: Result<(), &str> = breaker.call(backend, |e| *e == DOWN, |name| name).await;
+        assert_eq!(result, Err("test"));
+        assert!(!ran);
+        assert_eq!(breaker.state(), BreakerState::Open);
+    }
+
+    #[tokio::test]
+    async fn lets_exactly_one_probe_through_once_the_open_period_ends() {
+        let breaker = CircuitBreaker::new("test", 1, Duration::ZERO);
+        call(&breaker, Err(DOWN)).await.unwrap_err();
+        assert_eq!(raw_state(&breaker), BreakerState::Open);
+        assert_eq!(breaker.state(), BreakerState::HalfOpen);
+
+        let mut probe = breaker.acquire().expect("a probe is let through");
+        assert!(probe.probe);
+        assert!(breaker.acquire().is_none());
+        assert_eq!(call(&breaker, Ok(())).await, Err("unavailable"));
+
+        probe.finish(true);
+        assert_eq!(breaker.state(), BreakerState::Closed);
+        assert_eq!(call(&breaker, Ok(())).await, Ok(()));
+        assert_eq!(call(&breaker, Ok(())).await, Ok(()));
+    }
+
+    #[tokio::test]
+    async fn reopens_when_the_probe_fails() {
+        let breaker = CircuitBreaker::new("test", 3, Duration::ZERO);
+        for _ in 0..3 {
+            call(&breaker, Err(DOWN)).await.unwrap_err();
+        }
+        let opened_at = breaker.lock().opened_at;
+
+        // One failed probe is enough, however high the threshold
+        assert_eq!(call(&breaker, Err(DOWN)).await, Err(DOWN));
+        assert_eq!(raw_state(&breaker), BreakerState::Open);
+        assert!(breaker.lock().opened_at > opened_at);
+        assert!(!breaker.lock().probe_in_flight);
+    }
+
+    #[test]
+    fn a_dropped_probe_frees_the_slot() {
+        let breaker = CircuitBreaker::new("test", 1, Duration::ZERO);
+        breaker.acquire().unwrap().finish(false);
+
+        let probe = breaker.acquire().expect("a probe is let through");
+        assert!(breaker.acquire().is_none());
+        drop(probe);
+        assert_eq!(raw_state(&breaker), BreakerState::HalfOpen);
+        let probe = breaker.acquire().expect("the slot was freed");
+        assert!(probe.probe);
+    }
+
+    #[tokio::test]
+    async fn a_threshold_of_zero_never_opens() {
+        let breaker = CircuitBreaker::new("test", 0, Duration::from_secs(60));
+        for _ in 0..10 {
+            assert_eq!(call(&breaker, Err(DOWN)).await, Err(DOWN));
+        }
+        assert_eq!(breaker.state(), BreakerState::Closed);
+        assert_eq!(call(&breaker, Ok(())).await, Ok(()));
+    }
+}
@@ -0,0 +1,243 @@
+//! Typed HTTP client of the public API (only with the `client` feature), for bots, scripts and
+//! tests that talk to a running server.
+//!
+//! Responses are the server's own [`MemeView`]s, and failures carry the server's
+//! [`ErrorBody`] with its status, so callers can tell a missing meme from a rejected upload
+//! without parsing messages:
+//!
+//! ```no_run
+//! # async fn demo() -> Result<(), axum_meme_posting_example::client::ClientError> {
+//! use axum_meme_posting_example::client::{MemeClient, NewMeme};
+//!
+//! let client = MemeClient::new("http://localhost:3000");
+//! let image = std::fs::read("cat.png").expect("readable image");
+//! let meme = client.upload(NewMeme::new("Cat", "A cat", image, "cat.png").tags(["cats"])).await?;
+//! let png = client.download_image(meme.meme.meme_id).await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::{
+    errors::ErrorBody,
+    models::{MemeStatus, MemeView, SortOrder},
+};
+use axum::body::Bytes;
+use chrono::{DateTime, SecondsFormat, Utc};
+use reqwest::{
+    multipart::{Form, Part},
+    RequestBuilder, Response, StatusCode,
+};
+use serde::de::DeserializeOwned;
+use thiserror::Error;
+use uuid::Uuid;
+
+#[derive(Error, Debug)]
+pub enum ClientError {
+    /// The server answered with an error status.
+    #[error("Server responded with {status}: {}", describe(body))]
+    Api { status: StatusCode, body: ErrorBody },
+    /// The request did not get an answer, or the answer was not what the API sends.
+    #[error(transparent)]
+    Http(#[from] reqwest::Error),
+}
+
+impl ClientError {
+    /// The status of an error response from the server.
+    pub fn status(&self) -> Option<StatusCode> {
+        match self {
+            ClientError::Api { status, .. } => Some(*status),
+            ClientError::Http(e) => e.status(),
+        }
+    }
+
+    /// Whether the meme (or route) does not exist, or is hidden from the caller.
+    pub fn is_not_found(&self) -> bool {
+        self.status() == Some(StatusCode::NOT_FOUND)
+    }
+}
+
+/// A meme to upload through `POST /upload_meme`.
+#[derive(Debug, Clone)]
+pub struct NewMeme {
+    pub title: String,
+    pub description: String,
+    pub tags: Vec<String>,
+    pub image: Bytes,
+    /// Sent as the image part's filename; the server derives the stored extension from it.
+    pub filename: String,
+    /// The image's MIME type; the server sniffs the type from the bytes either way.
+    pub content_type: Option<String>,
+    /// Seconds until the meme expires; permanent when unset.
+    pub expires_in: Option<u64>,
+    /// When a scheduled meme appears publicly.
+    pub publish_at: Option<DateTime<Utc>>,
+    pub status: Option<MemeStatus>,
+}
+
+impl NewMeme {
+    pub fn new(title: impl Into<String>, description: impl Into<String>, image: impl Into<Bytes>, filename: impl Into<String>) -> Self {
+        Self {
+            title: title.into(),
+            description: description.into(),
+            tags: Vec::new(),
+            image: image.into(),
+            filename: filename.into(),
+            content_type: None,
+            expires_in: None,
+            publish_at: None,
+            status: None,
+        }
+    }
+
+    pub fn tags<I, S>(mut self, tags: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.tags = tags.into_iter().map(Into::into).collect();
+        self
+    }
+
+    pub fn content_type(mut self, content_type: impl Into<String>) -> Self {
+        self.content_type = Some(content_type.into());
+        self
+    }
+
+    pub fn expires_in(mut self, seconds: u64) -> Self {
+        self.expires_in = Some(seconds);
+        self
+    }
+
+    /// Uploads the meme as a draft published at `publish_at`.
+    pub fn publish_at(mut self, publish_at: DateTime<Utc>) -> Self {
+        self.publish_at = Some(publish_at);
+        self
+    }
+
+    pub fn status(mut self, status: MemeStatus) -> Self {
+        self.status = Some(status);
+        self
+    }
+
+    /// The multipart form of `POST /upload_meme`.
+    fn into_form(self) -> Result<Form, ClientError> {
+        let length = self.image.len() as u64;
+        let mut image = Part::stream_with_length(self.image, length).file_name(self.filename);
+        if let Some(content_type) = &self.content_type {
+            image = image.mime_str(content_type)?;
+        }
+        let mut form = Form::new().text("title", self.title).text("description", self.description);
+        if !self.tags.is_empty() {
+            form = form.text("tags", self.tags.join(","));
+        }
+        if let Some(expires_in) = self.expires_in {
+            form = form.text("expires_in", expires_in.to_string());
+        }
+        if let Some(publish_at) = self.publish_at {
+            form = form.text("publish_at", publish_at.to_rfc3339_opts(SecondsFormat::Secs, true));
+        }
+        if let Some(status) = self.status {
+            form = form.text("status", status.as_str());
+        }
+        Ok(form.part("image", image))
+    }
+}
+
+/// A client of one server. Cheap to clone; clones share the connection pool.
+#[derive(Debug, Clone)]
+pub struct MemeClient {
+    http: reqwest::Client,
+    base_url: String,
+    token: Option<String>,
+}
+
+impl MemeClient {
+    /// A client of the server at `base_url`, e.g. `http://localhost:3000`.
+    pub fn new(base_url: impl Into<String>) -> Self {
+        Self::with_http_client(base_url, reqwest::Client::new())
+    }
+
+    /// Like [`MemeClient::new`], sending requests through `http` (for its timeouts, proxy or
+    /// default headers).
+    pub fn with_http_client(base_url: impl Into<String>, http: reqwest::Client) -> Self {
+        Self { http, base_url: base_url.into().trim_end_matches('/').to_string(), token: None }
+    }
+
+    /// Sends `token` as the bearer token of every request, to act as the owner
+    /// (`APP_ADMIN_TOKEN`): private memes and drafts become visible and deletable.
+    pub fn with_token(mut self, token: impl Into<String>) -> Self {
+        self.token = Some(token.into());
+        self
+    }
+
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    /// Uploads a meme; returns it as stored.
+    pub async fn upload(&self, meme: NewMeme) -> Result<MemeView, ClientError> {
+        let form = meme.into_form()?;
+        json(self.send(self.http.post(self.url("/upload_meme")).multipart(form)).await?).await
+    }
+
+    /// Fetches a meme by ID. Fails with a 404 [`ClientError::Api`] when there is none.
+    pub async fn get(&self, id: Uuid) -> Result<MemeView, ClientError> {
+        json(self.send(self.http.get(self.url(&format!("/meme/{}", id)))).await?).await
+    }
+
+    /// Lists the published public memes, in no particular order.
+    pub async fn list(&self) -> Result<Vec<MemeView>, ClientError> {
+        json(self.send(self.http.get(self.url("/memes"))).await?).await
+    }
+
+    /// Lists the published public memes in `order`.
+    pub async fn list_sorted(&self, order: SortOrder) -> Result<Vec<MemeView>, ClientError> {
+        json(self.send(self.http.get(self.url("/memes")).query(&[("sort", order)])).await?).await
+    }
+
+    /// Deletes a meme and its image.
+    pub async fn delete(&self, id: Uuid) -> Result<(), ClientError> {
+        self.send(self.http.delete(self.url(&format!("/meme/{}", id)))).await?;
+        Ok(())
+    }
+
+    /// Downloads a meme's image.
+    pub async fn download_image(&self, id: Uuid) -> Result<Bytes, ClientError> {
+        Ok(self.send(self.http.get(self.url(&format!("/meme/{}/download", id)))).await?.bytes().await?)
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}{}", self.base_url, path)
+    }
+
+    /// Sends `request` with the token, turning error statuses into [`ClientError::Api`].
+    async fn send(&self, mut request: RequestBuilder) -> Result<Response, ClientError> {
+        if let Some(token) = &self.token {
+            request = request.bearer_auth(token);
+        }
+        let response = request.send().await?;
+        let status = response.status();
+        if status.is_success() {
+            return Ok(response);
+        }
+        // Errors from in front of the server (a proxy, a load balancer) may not be JSON
+        let text = response.text().await?;
+        let body = serde_json::from_str(&text).unwrap_or_else(|_| ErrorBody {
+            error: if text.is_empty() { status.to_string() } else { text },
+            fields: None,
+            request_id: None,
+        });
+        Err(ClientError::Api { status, body })
+    }
+}
+
+/// The error message, with what failed per field for validation errors.
+fn describe(body: &ErrorBody) -> String {
+    let Some(fields) = &body.fields else { return body.error.clone() };
+    let failures: Vec<String> = fields.iter().map(|(field, messages)| format!("{} {}", field, messages.join(", "))).collect();
+    format!("{} ({})", body.error, failures.join("; "))
+}
+
+async fn json<T: DeserializeOwned>(response: Response) -> Result<T, ClientError> {
+    Ok(response.json().await?)
+}
@@ -0,0 +1,282 @@
+//! Client IP resolution. A request's client is the peer of its connection, unless that peer is
+//! one of `APP_CLIENT_TRUSTED_PROXIES`: then the proxy headers (`APP_CLIENT_IP_HEADERS`) are
+//! walked from the nearest hop back, skipping further trusted proxies. Behind a load balancer
+//! speaking the PROXY protocol (`APP_SERVER_PROXY_PROTOCOL`), the peer is the source address
+//! from the connection's PROXY header.
+//!
+//! The resolved address is put on each request as [`ClientIp`] by [`resolve_client_ip`].
+
+use crate::{config::Config, AppState};
+use axum::{
+    extract::{connect_info::Connected, ConnectInfo, OptionalFromRequestParts, Request, State},
+    http::{request::Parts, HeaderMap},
+    middleware::Next,
+    response::Response,
+    serve::{IncomingStream, Listener},
+};
+use ipnet::IpNet;
+use std::{
+    convert::Infallible,
+    io,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    sync::Arc,
+    time::Duration,
+};
+use tokio::{
+    io::AsyncReadExt,
+    net::{TcpListener, TcpStream},
+    sync::mpsc,
+};
+
+/// How long a new connection may take to send its PROXY protocol header.
+const PROXY_HEADER_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The client's address, as resolved by [`resolve_client_ip`]. Extract it as
+/// `Option<ClientIp>`: it is unknown on Unix sockets and Lambda without proxy headers.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ClientIp(pub IpAddr);
+
+impl<S: Send + Sync> OptionalFromRequestParts<S> for ClientIp {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Option<Self>, Self::Rejection> {
+        Ok(parts.extensions.get::<ClientIp>().copied())
+    }
+}
+
+/// Address of a connection's peer, for `into_make_service_with_connect_info`. With the
+/// PROXY protocol, the source address its header declared.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PeerAddr(pub SocketAddr);
+
+impl Connected<IncomingStream<'_, TcpListener>> for PeerAddr {
+    fn connect_info(stream: IncomingStream<'_, TcpListener>) -> Self {
+        PeerAddr(*stream.remote_addr())
+    }
+}
+
+impl Connected<IncomingStream<'_, ProxyProtocolListener>> for PeerAddr {
+    fn connect_info(stream: IncomingStream<'_, ProxyProtocolListener>) -> Self {
+        PeerAddr(*stream.remote_addr())
+    }
+}
+
+/// For `axum-server`, which serves HTTPS.
+impl Connected<SocketAddr> for PeerAddr {
+    fn connect_info(address: SocketAddr) -> Self {
+        PeerAddr(address)
+    }
+}
+
+/// Parses an entry of `APP_CLIENT_TRUSTED_PROXIES`: a CIDR block, or a single address.
+pub fn parse_network(entry: &str) -> Option<IpNet> {
+    entry.parse::<IpNet>().ok().or_else(|| entry.parse::<IpAddr>().ok().map(IpNet::from))
+}
+
+fn is_trusted(trusted_proxies: &[IpNet], ip: IpAddr) -> bool {
+    trusted_proxies.iter().any(|network| network.contains(&ip))
+}
+
+/// Middleware putting the request's [`ClientIp`] in its extensions.
+pub async fn resolve_client_ip(State(state): State<Arc<AppState>>, mut request: Request, next: Next) -> Response {
+    let peer = request.extensions().get::<ConnectInfo<PeerAddr>>().map(|ConnectInfo(PeerAddr(peer))| peer.ip());
+    if let Some(ip) = client_ip(&state.config, peer, request.headers()) {
+        request.extensions_mut().insert(ClientIp(ip));
+    }
+    next.run(request).await
+}
+
+/// The client behind `peer`. Headers are only read when the peer is a trusted proxy, or
+/// unknown: Unix sockets are only reachable from the host, and Lambda is only invoked
+/// through API Gateway or a function URL, which both append the client to
+/// `X-Forwarded-For`. Of the configured headers, the first present is used.
+fn client_ip(config: &Config, peer: Option<IpAddr>, headers: &HeaderMap) -> Option<IpAddr> {
+    if peer.is_some_and(|peer| !is_trusted(&config.client_trusted_proxies, peer)) {
+        return peer;
+    }
+    let Some(name) = config.client_ip_headers.iter().find(|name| headers.contains_key(name.as_str())) else {
+        return peer;
+    };
+    let mut client = peer;
+    // Each proxy appends the address it received the request from
+    for hop in hops(headers, name).into_iter().rev() {
+        let Some(ip) = hop else { break }; // Obfuscated or unknown: the hop before it is as far as we know
+        client = Some(ip);
+        if !is_trusted(&config.client_trusted_proxies, ip) {
+            break;
+        }
+    }
+    client
+}
+
+/// The addresses listed by all `name` headers, in order: `for=` of each `Forwarded` element,
+/// or the comma-separated entries of `X-Forwarded-For` and similar headers.
+fn hops(headers: &HeaderMap, name: &str) -> Vec<Option<IpAddr>> {
+    let forwarded = name.eq_ignore_ascii_case("forwarded");
+    headers
+        .get_all(name)
+        .iter()
+        .flat_map(|value| value.to_str().unwrap_or_default().split(','))
+        .map(|element| {
+            if forwarded {
+                element
+                    .split(';')
+                    .filter_map(|pair| pair.split_once('='))
+                    .find(|(key, _)| key.trim().eq_ignore_ascii_case("for"))
+                    .and_then(|(_, node)| parse_node(node))
+            } else {
+                parse_node(element)
+            }
+        })
+        .collect()
+}
+
+/// An address as proxies write it: `192.0.2.60`, `2001:db8::17`, `"[2001:db8::17]:4711"` or
+/// `192.0.2.60:4711`. `None` for `unknown` and obfuscated identifiers.
+fn parse_node(node: &str) -> Option<IpAddr> {
+    let node = node.trim().trim_matches('"');
+    node.parse::<IpAddr>()
+        .ok()
+        .or_else(|| node.parse::<SocketAddr>().ok().map(|address| address.ip()))
+        .or_else(|| node.strip_prefix('[')?.strip_suffix(']')?.parse::<IpAddr>().ok())
+}
+
+/// A TCP listener expecting a PROXY protocol (v1 or v2) header on every connection, as sent by
+/// load balancers such as AWS NLB and HAProxy. Connections are accepted, and their headers
+/// read, on a background task, so a slow connection does not hold up the others; ones
+/// without a valid header are dropped. The declared source address is only used when the
+/// connection comes from a trusted proxy.
+pub struct ProxyProtocolListener {
+    incoming: mpsc::Receiver<(TcpStream, SocketAddr)>,
+    local_addr: SocketAddr,
+}
+
+impl ProxyProtocolListener {
+    pub fn new(listener: TcpListener, trusted_proxies: Vec<IpNet>) -> io::Result<Self> {
+        let local_addr = listener.local_addr()?;
+        let (sender, incoming) = mpsc::channel(64);
+        tokio::spawn(accept_connections(listener, trusted_proxies.into(), sender));
+        Ok(Self { incoming, local_addr })
+    }
+}
+
+impl Listener for ProxyProtocolListener {
+    type Io = TcpStream;
+    type Addr = SocketAddr;
+
+    async fn accept(&mut self) -> (Self::Io, Self::Addr) {
+        match self.incoming.recv().await {
+            Some(connection) => connection,
+            None => std::future::pending().await, // The accept task only ends with the listener
+        }
+    }
+
+    fn local_addr(&self) -> io::Result<Self::Addr> {
+        Ok(self.local_addr)
+    }
+}
+
+/// Accepts connections until the [`ProxyProtocolListener`] is dropped, reading each one's
+/// header on its own task.
+async fn accept_connections(
+    listener: TcpListener,
+    trusted_proxies: Arc<[IpNet]>,
+    sender: mpsc::Sender<(TcpStream, SocketAddr)>,
+) {
+    loop {
+        let (mut stream, peer) = tokio::select! {
+            _ = sender.closed() => return,
+            accepted = listener.accept() => match accepted {
+                Ok(accepted) => accepted,
+                Err(e) => {
+                    // Usually too many open files; give connections time to close
+                    tracing::error!(error = %e, "Failed to accept connection");
+                    tokio::time::sleep(Duration::from_secs(1)).await;
+                    continue;
+                }
+            },
+        };
+        let (sender, trusted_proxies) = (sender.clone(), trusted_proxies.clone());
+        tokio::spawn(async move {
+            let source = match tokio::time::timeout(PROXY_HEADER_TIMEOUT, read_proxy_header(&mut stream)).await {
+                Ok(Ok(source)) => source,
+                Ok(Err(e)) => {
+                    tracing::debug!(%peer, error = %e, "Dropped connection without a valid PROXY protocol header");
+                    return;
+                }
+                Err(_) => {
+                    tracing::debug!(%peer, "Dropped connection that sent no PROXY protocol header in time");
+                    return;
+                }
+            };
+            let address = source.filter(|_| is_trusted(&trusted_proxies, peer.ip())).unwrap_or(peer);
+            let _ = sender.send((stream, address)).await; // Fails only once the server is gone
+        });
+    }
+}
+
+/// Signature opening a v2 header.
+const PROXY_V2_SIGNATURE: [u8; 12] = *b"\r\n\r\n\0\r\nQUIT\n";
+/// Longest v1 header, including its CRLF.
+const PROXY_V1_MAX_LENGTH: usize = 107;
+
+/// Reads a PROXY protocol header, and nothing after it. `None` when it declares no source
+/// address: health checks (`LOCAL`, `UNKNOWN`) and non-TCP connections.
+async fn read_proxy_header(stream: &mut TcpStream) -> io::Result<Option<SocketAddr>> {
+    let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message.to_string());
+
+    let mut start = [0u8; 6];
+    stream.read_exact(&mut start).await?;
+    if &start == b"PROXY " {
+        let mut line = start.to_vec();
+        while !line.ends_with(b"\r\n") {
+            if line.len() == PROXY_V1_MAX_LENGTH {
+                return Err(invalid("v1 header is too long"));
+            }
+            line.push(stream.read_u8().await?);
+        }
+        let line = std::str::from_utf8(&line).map_err(|_| invalid("v1 header is not ASCII"))?;
+        let fields: Vec<&str> = line.trim_end().split(' ').collect();
+        return match fields.as_slice() {
+            ["PROXY", "TCP4" | "TCP6", source, _, port, _] => {
+                let ip: IpAddr = source.parse().map_err(|_| invalid("v1 header has an invalid source address"))?;
+                let port: u16 = port.parse().map_err(|_| invalid("v1 header has an invalid source port"))?;
+                Ok(Some(SocketAddr::new(ip, port)))
+            }
+            ["PROXY", "UNKNOWN", ..] => Ok(None),
+            _ => Err(invalid("malformed v1 header")),
+        };
+    }
+
+    if start != PROXY_V2_SIGNATURE[..6] {
+        return Err(invalid("no PROXY protocol signature"));
+    }
+    let mut rest = [0u8; 10];
+    stream.read_exact(&mut rest).await?;
+    if rest[..6] != PROXY_V2_SIGNATURE[6..] {
+        return Err(invalid("no PROXY protocol signature"));
+    }
+    let (version_command, family) = (rest[6], rest[7]);
+    if version_command >> 4 != 2 {
+        return Err(invalid("unsupported v2 version"));
+    }
+    let mut addresses = vec![0u8; u16::from_be_bytes([rest[8], rest[9]]) as usize];
+    stream.read_exact(&mut addresses).await?; // TLVs after the addresses are ignored
+    if version_command & 0x0f == 0 {
+        return Ok(None); // LOCAL
+    }
+    let source = match family {
+        0x11 if addresses.len() >= 12 => {
+            let ip = Ipv4Addr::from(<[u8; 4]>::try_from(&addresses[0..4]).unwrap());
+            SocketAddr::new(ip.into(), u16::from_be_bytes([addresses[8], addresses[9]]))
+        }
+        0x21 if addresses.len() >= 36 => {
+            let ip = Ipv6Addr::from(<[u8; 16]>::try_from(&addresses[0..16]).unwrap());
+            SocketAddr::new(ip.into(), u16::from_be_bytes([addresses[32], addresses[33]]))
+        }
+        0x11 | 0x21 => return Err(invalid("v2 header is too short for its addresses")),
+        _ => return Ok(None), // UDP and Unix sockets
+    };
+    Ok(Some(source))
+}
+
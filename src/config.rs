@@ -1,73 +1,1550 @@
-use std::{env, net::SocketAddr, str::FromStr};
+use crate::content_filter::FilterMode;
+use crate::remote_config::{self, ConfigSourceKind};
+use crate::keys::KeyLayout;
+use crate::embeddings::EmbeddingBackend;
+use crate::ocr::OcrBackend;
+use crate::access_log::AccessLogFormat;
+use crate::client_ip;
+use crate::domain::ConsistencyLevel;
+use crate::scanning::ScannerBackend;
+use crate::backends::{RepositoryBackend, StorageBackend};
+use crate::body_limit::{BodyLimits, MediaTypeLimits};
+use crate::emf::EmfDestination;
+use crate::telemetry::MetricsSink;
+use crate::startup::{BucketEncryption, ResourceInitMode, TableCapacity, Throughput};
+use axum::http::{HeaderName, Method};
+use ipnet::IpNet;
+use serde::{Serialize, Serializer};
+use std::{
+    collections::HashMap,
+    env, fs,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
 use thiserror::Error;
 
 #[derive(Error, Debug)]
 pub enum ConfigError {
-    #[error("Missing required environment variable: {0}")]
+    #[error("Missing required setting: {0} (set the environment variable or its config file key)")]
     MissingVar(String),
-    #[error("Invalid environment variable format for {0}: {1}")]
+    #[error("Invalid format for setting {0}: {1}")]
     InvalidVar(String, String),
+    #[error("Failed to read config file {0}: {1}")]
+    FileError(String, String),
+    #[error("Failed to load settings from {0}: {1}")]
+    RemoteError(String, String),
     #[error("Failed to load .env file: {0}")]
     DotEnvError(#[from] dotenvy::Error),
 }
 
-#[derive(Clone, Debug)] // Clone needed for AppState, Debug for logging
+/// Effective settings. Serializes for `GET /admin/resources`, with secrets redacted.
+#[derive(Clone, Debug, Serialize)] // Clone needed for AppState, Debug for logging
 pub struct Config {
-    pub bind_address: SocketAddr,
+    #[cfg_attr(feature = "lambda", allow(dead_code))] // The Lambda runtime owns the socket
+    pub bind_address: ListenAddress,
+    #[cfg_attr(feature = "lambda", allow(dead_code))]
+    pub server_socket_mode: u32, // Permissions of Unix socket files, e.g. 0o660
+    pub public_url: Option<String>, // Where clients reach the server, for absolute links
     pub meme_bucket_name: String,
     pub dynamodb_table_name: String, // Added
+    pub meta_table_name: String, // Auxiliary pk/sk table (blocklist terms, etc.)
+    pub dynamodb_scan_segments: u32, // Parallel segments of full-table scans
     pub aws_region: String,
     pub localstack_endpoint: Option<String>,
+    // Validation limits for submitted meme metadata
+    pub max_title_length: usize,
+    pub max_description_length: usize,
+    pub max_tags: usize,
+    pub max_tag_length: usize,
+    pub max_expires_in_secs: u64, // Longest lifetime accepted for ephemeral memes
+    // Upload limits; tenants may override them (see `domain::TenantOverrides`)
+    pub max_upload_bytes: usize,
+    pub allowed_image_types: Vec<String>, // Any type is accepted when empty
+    pub max_uploads_per_day: Option<u64>, // Unlimited when unset
+    pub max_image_dimension: Option<u32>, // Longest image side in pixels; unlimited when unset
+    // Request body limits by media type, for uploads and for all other routes
+    pub upload_body_limits: BodyLimits,
+    pub body_limits: BodyLimits,
+    // Content filtering for titles/descriptions
+    pub content_filter_mode: FilterMode,
+    pub content_filter_terms: Vec<String>,
+    // Limits for server-side image downloads (JSON uploads with `source_url`)
+    pub fetch_timeout_secs: u64,
+    pub fetch_max_bytes: usize,
+    // Metadata backups (JSONL in the meme bucket)
+    pub backup_prefix: String,
+    pub backup_interval_secs: Option<u64>, // Periodic backups are disabled when unset
+    // Purging of expired memes (image and metadata); 0 disables the periodic job
+    #[cfg_attr(feature = "lambda", allow(dead_code))] // Not scheduled on Lambda
+    pub expiry_cleanup_interval_secs: u64,
+    // How often scheduled drafts whose time has come are published; 0 disables
+    #[cfg_attr(feature = "lambda", allow(dead_code))] // Not scheduled on Lambda
+    pub publish_interval_secs: u64,
+    // How often the /stats aggregation job runs; also how stale served statistics may get
+    pub stats_interval_secs: u64,
+    // How long a generated /sitemap.xml is served before the memes are listed again; 0
+    // lists them on every request
+    pub sitemap_ttl_secs: u64,
+    // How often the trending job flushes views and ranks memes; also how stale served
+    // rankings may get
+    pub trending_interval_secs: u64,
+    // Age at which a meme's popularity counts half as much in the trending rankings
+    pub trending_half_life_hours: u64,
+    // Memes kept per trending window
+    pub trending_limit: usize,
+    // Memes per second a backfill (`/admin/backfills`) updates
+    pub backfill_rate: u32,
+    // Engine reading the text on uploaded images into `caption_text`; `none` turns OCR off
+    pub ocr_backend: OcrBackend,
+    pub ocr_timeout_secs: u64,
+    // Command run by the `tesseract` OCR backend (needs the `tesseract` feature)
+    #[cfg_attr(not(feature = "tesseract"), allow(dead_code))]
+    pub tesseract_command: String,
+    // Keeps new uploads in quarantine, hidden until an admin approves them
+    pub moderation_enabled: bool,
+    // Malware scanner run on uploads before they are released; `none` turns scanning off
+    pub scanner_backend: ScannerBackend,
+    // clamd's TCP address (`host:port`) for the `clamav` scanner
+    pub clamav_address: String,
+    pub scan_timeout_secs: u64,
+    // Model embedding memes and queries for semantic search; `none` turns it off
+    pub embedding_backend: EmbeddingBackend,
+    // Bedrock model ID used by the `bedrock` embedding backend
+    pub embedding_model: String,
+    pub embedding_timeout_secs: u64,
+    // Consumer of the meme table's DynamoDB stream, driving side effects from committed writes
+    pub stream_consumer_enabled: bool,
+    #[cfg_attr(feature = "lambda", allow(dead_code))]
+    pub stream_poll_interval_ms: u64,
+    // Endpoints notified of meme changes by the stream consumer, optionally HMAC-signed
+    #[cfg_attr(feature = "lambda", allow(dead_code))]
+    pub webhook_urls: Vec<String>,
+    #[cfg_attr(feature = "lambda", allow(dead_code))]
+    #[serde(serialize_with = "redact")]
+    pub webhook_secret: Option<String>,
+    // Emails sent through Amazon SES when a meme is approved, rejected from moderation or
+    // about to expire; off without recipients
+    pub notification_emails: Vec<String>,
+    pub notification_sender: Option<String>, // An identity verified in SES
+    // How long before its expiry a meme is reported as about to expire; 0 turns it off
+    #[cfg_attr(feature = "lambda", allow(dead_code))] // Checked by the expiry cleanup job
+    pub expiry_notice_secs: u64,
+    // Slack app: `/meme` slash commands and link unfurls, signed with the app's signing
+    // secret, and notifications of new memes through an incoming webhook
+    #[serde(serialize_with = "redact")]
+    pub slack_signing_secret: Option<String>, // `/slack/command` and `/slack/events` are off when unset
+    #[serde(serialize_with = "redact")]
+    pub slack_bot_token: Option<String>, // Unfurls links to memes with chat.unfurl
+    #[serde(serialize_with = "redact")]
+    pub slack_webhook_url: Option<String>, // Incoming webhook URLs hold a secret
+    // Discord bot (needs the `discord` feature): new memes are posted to the channel, and
+    // `/meme upload` interactions are checked against the application's public key
+    #[cfg_attr(not(feature = "discord"), allow(dead_code))]
+    #[serde(serialize_with = "redact")]
+    pub discord_bot_token: Option<String>,
+    #[cfg_attr(not(feature = "discord"), allow(dead_code))]
+    pub discord_application_id: Option<u64>, // Registers the `/meme` command at startup
+    #[cfg_attr(not(feature = "discord"), allow(dead_code))]
+    pub discord_public_key: Option<String>, // Hex Ed25519 key; `/discord/interactions` is off when unset
+    #[cfg_attr(not(feature = "discord"), allow(dead_code))]
+    pub discord_channel_id: Option<u64>,
+    // Telegram bot (needs the `telegram` feature), polling for photos and commands, or
+    // taking them at `/telegram/webhook` when the webhook secret is set
+    #[cfg_attr(not(feature = "telegram"), allow(dead_code))]
+    #[serde(serialize_with = "redact")]
+    pub telegram_bot_token: Option<String>,
+    #[cfg_attr(not(feature = "telegram"), allow(dead_code))]
+    #[serde(serialize_with = "redact")]
+    pub telegram_webhook_secret: Option<String>, // Sent back by Telegram with every update
+    // ActivityPub actor of the instance, for following its memes from the fediverse; off
+    // without a key. Its URLs start with `public_url`
+    #[serde(serialize_with = "redact")]
+    pub activitypub_private_key: Option<String>, // Signs deliveries and fetches (PEM)
+    pub activitypub_username: String, // Followed as @<username>@<host of public_url>
+    // Bearer token required for /admin routes; admin API is disabled when unset
+    #[serde(serialize_with = "redact")]
+    pub admin_token: Option<String>,
+    // Separate listener for /admin, /import, /metrics and /healthz; served on the main port when unset
+    pub admin_address: Option<ListenAddress>,
+    // Communities served by this deployment, each with its own meme table and image prefix
+    pub tenants: Vec<String>,
+    // Parent domain whose subdomains name tenants, e.g. "memes.example.com"
+    pub tenant_domain: Option<String>,
+    // How long a tenant's overrides are cached before they are read again
+    pub tenant_config_ttl_secs: u64,
+    // Tenant this configuration is scoped to (see `Config::for_tenant`); not a setting
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tenant: Option<String>,
+    // HMAC key for share links; sharing is disabled when unset
+    #[serde(serialize_with = "redact")]
+    pub share_secret: Option<String>,
+    pub share_link_ttl_secs: u64,
+    pub share_link_max_ttl_secs: u64, // Longest lifetime a caller may ask for
+    // Direct image URLs in responses: CDN when a base URL is set, presigned S3 otherwise
+    pub cdn_base_url: Option<String>,
+    pub cdn_key_pair_id: Option<String>, // CloudFront key pair signing private memes' URLs
+    #[serde(serialize_with = "redact")]
+    pub cdn_private_key: Option<String>,
+    pub cdn_sign_all_urls: bool, // For distributions that require signed URLs everywhere
+    pub cdn_cookie_domain: Option<String>,
+    pub image_url_ttl_secs: u64,
+    // HTTPS on the main listener when both paths are set (PEM files)
+    pub tls_cert_path: Option<PathBuf>,
+    #[cfg_attr(feature = "lambda", allow(dead_code))]
+    pub tls_key_path: Option<PathBuf>,
+    // Plain-HTTP listener that redirects every request to the HTTPS port
+    #[cfg_attr(feature = "lambda", allow(dead_code))]
+    pub tls_redirect_address: Option<SocketAddr>,
+    // Per-call latency/error/item metrics and tracing spans for DynamoDB/S3 calls
+    pub backend_instrumentation: bool,
+    // Requests and backend calls taking this long are logged as warnings; 0 disables
+    pub slow_request_threshold_ms: u64,
+    pub slow_backend_call_threshold_ms: u64, // Needs backend instrumentation
+    // Logs JSON request/response bodies up to the cap at debug level, redacting these fields and headers
+    pub debug_body_logging: bool,
+    pub debug_body_max_bytes: usize,
+    pub debug_body_redact: Vec<String>,
+    // One line per request, in the Common Log Format or JSON; stdout unless a path is set
+    pub access_log_format: AccessLogFormat,
+    pub access_log_path: Option<PathBuf>,
+    // Proxies whose headers name the client; the first of the headers present is read
+    pub client_trusted_proxies: Vec<IpNet>,
+    pub client_ip_headers: Vec<String>,
+    #[cfg_attr(feature = "lambda", allow(dead_code))]
+    pub server_proxy_protocol: bool, // PROXY protocol header on every connection of APP_SERVER_ADDRESS
+    // Where metrics go; EMF documents are flushed periodically under a CloudWatch namespace
+    pub metrics_sink: MetricsSink,
+    pub runtime_metrics_interval_secs: u64, // Tokio runtime sampling; 0 disables
+    pub emf_namespace: String,
+    pub emf_flush_interval_secs: u64,
+    pub emf_destination: EmfDestination, // stdout unless APP_EMF_AGENT_ENDPOINT is set
+    // X-Ray segments for sampled requests and their DynamoDB/S3 calls, sent to the daemon
+    pub xray_enabled: bool,
+    pub xray_daemon_address: String,
+    pub xray_service_name: String,
+    pub xray_sample_rate: f64, // Share of requests sampled when the caller did not decide
+    // Sentry project receiving 5xx errors; error reporting is off when unset
+    #[serde(serialize_with = "redact")]
+    pub sentry_dsn: Option<String>,
+    pub sentry_environment: Option<String>, // e.g. "production"; Sentry's default when unset
+    // Fault injection into DynamoDB/S3 calls (needs the `chaos` feature); 0 disables
+    #[cfg_attr(not(feature = "chaos"), allow(dead_code))]
+    pub chaos_error_rate: f64,
+    #[cfg_attr(not(feature = "chaos"), allow(dead_code))]
+    pub chaos_latency_ms: u64,
+    #[cfg_attr(not(feature = "chaos"), allow(dead_code))]
+    pub chaos_backends: Vec<String>,
+    // Retries of runtime DynamoDB/S3 calls; 1 attempt disables retrying
+    pub retry_max_attempts: u32,
+    pub retry_base_delay_ms: u64,
+    pub retry_max_delay_ms: u64,
+    pub retry_budget_ratio: f64,
+    // Circuit breakers around DynamoDB and S3; a threshold of 0 disables them
+    pub breaker_failure_threshold: u32,
+    pub breaker_open_secs: u64,
+    // Whether startup creates, verifies or trusts the tables and bucket
+    pub resource_init: ResourceInitMode,
+    // Billing of the tables startup creates; existing tables are only checked against it
+    pub dynamodb_capacity: TableCapacity,
+    pub dynamodb_meta_capacity: TableCapacity,
+    // Endpoints whose meme lookups are strongly consistent, so they see writes made just
+    // before; the rest read eventually consistent copies at half the cost
+    pub consistent_reads: Vec<ReadEndpoint>,
+    // Region holding a Global Tables replica of the meme table that reads (and, with
+    // `failover_writes`, writes) fail over to; no failover when unset
+    pub dynamodb_secondary_region: Option<String>,
+    pub failover_writes: bool,
+    // How often the primary region is checked, and how many checks in a row fail over or back
+    pub failover_check_interval_secs: u64,
+    pub failover_threshold: u32,
+    // Meme lookups still unanswered after this percentile of recent lookup latencies (but
+    // at least the minimum delay) are sent a second time; off unless `hedged_reads`
+    pub hedged_reads: bool,
+    pub hedge_percentile: f64,
+    pub hedge_min_delay_ms: u64,
+    // Bucket hardening applied when resources are created
+    pub s3_block_public_access: bool,
+    pub s3_encryption: BucketEncryption,
+    pub s3_kms_key_id: Option<String>,
+    pub s3_versioning: bool,
+    pub s3_abort_incomplete_upload_days: Option<i32>, // No lifecycle rule when unset
+    // Where images are stored; the S3 settings below only apply to `s3`
+    pub storage_backend: StorageBackend,
+    // Azure Blob Storage (needs the `azure` feature); the container defaults to the bucket name
+    #[cfg_attr(not(feature = "azure"), allow(dead_code))]
+    pub azure_storage_account: Option<String>,
+    #[cfg_attr(not(feature = "azure"), allow(dead_code))]
+    #[serde(serialize_with = "redact")]
+    pub azure_storage_access_key: Option<String>,
+    pub azure_container_name: String,
+    #[cfg_attr(not(feature = "azure"), allow(dead_code))]
+    pub azure_blob_endpoint: Option<String>, // e.g. Azurite; the public cloud when unset
+    // Directory the `filesystem` storage backend keeps images in
+    pub filesystem_root: PathBuf,
+    // Google Cloud Storage (needs the `gcs` feature); the bucket defaults to the S3 bucket name
+    pub gcs_bucket_name: String,
+    #[cfg_attr(not(feature = "gcs"), allow(dead_code))]
+    #[serde(serialize_with = "redact")]
+    pub gcs_service_account_key: Option<String>, // JSON key; application default credentials when unset
+    #[cfg_attr(not(feature = "gcs"), allow(dead_code))]
+    pub gcs_project_id: Option<String>, // Only needed to create the bucket
+    #[cfg_attr(not(feature = "gcs"), allow(dead_code))]
+    pub gcs_endpoint: Option<String>, // e.g. an emulator; Google's endpoints when unset
+    // Where meme metadata is stored; a MongoDB collection is named like the meme table
+    pub repository_backend: RepositoryBackend,
+    #[cfg_attr(not(feature = "mongodb"), allow(dead_code))]
+    #[serde(serialize_with = "redact")]
+    pub mongodb_uri: Option<String>, // Connection string; may hold credentials
+    #[cfg_attr(not(feature = "mongodb"), allow(dead_code))]
+    pub mongodb_database: String,
+    // Database file of the `sqlite` repository backend; blocklist and tenant overrides live
+    // there too
+    #[cfg_attr(not(feature = "sqlite"), allow(dead_code))]
+    pub sqlite_path: PathBuf,
+    // KMS key every uploaded object is encrypted with (SSE-KMS); bucket default when unset
+    pub s3_upload_kms_key_id: Option<String>,
+    // Tag uploaded images with meme ID, uploader and content hash (needs s3:PutObjectTagging)
+    pub s3_object_tagging: bool,
+    // Region of the bucket when it is not AWS_REGION (the tables' region)
+    pub s3_region: Option<String>,
+    // Endpoint of S3 calls alone, e.g. a VPC interface endpoint; overrides AWS_ENDPOINT_URL
+    pub s3_endpoint_url: Option<String>,
+    // Send object transfers through S3 Transfer Acceleration's edge locations
+    pub s3_accelerate: bool,
+    // Layout of the storage keys given to new images
+    pub image_key_layout: KeyLayout,
+    // Time allowed for in-flight requests and background jobs to finish after SIGTERM
+    #[cfg_attr(feature = "lambda", allow(dead_code))]
+    pub shutdown_grace_secs: u64,
+    // Request time budgets; uploads and imports get the longer one
+    pub request_timeout_secs: u64,
+    pub upload_timeout_secs: u64,
+    // CORS policy; "*" in a list allows anything, an empty origin list allows no cross-origin requests
+    pub cors_allowed_origins: Vec<String>,
+    pub cors_allowed_methods: Vec<String>,
+    pub cors_allowed_headers: Vec<String>,
+    pub cors_max_age_secs: u64,
 }
 
 impl Config {
-    /// Loads configuration from environment variables.
-    /// Reads from a .env file if present.
-    pub fn load() -> Result<Self, ConfigError> {
+    /// Loads configuration from environment variables layered over an optional TOML file,
+    /// plus SSM Parameter Store / Secrets Manager when `APP_CONFIG_SOURCE=ssm`.
+    /// Reads from a .env file if present. An explicit `config_file` (from the `--config` CLI
+    /// flag) takes precedence over `APP_CONFIG_FILE`. See [`ConfigSource`] for the lookup rules.
+    pub async fn load(config_file: Option<&Path>) -> Result<Self, ConfigError> {
         // Attempt to load .env file, ignore if not found
         dotenvy::dotenv().ok();
 
+        let mut source = ConfigSource::new(config_file)?;
+        // The source selector itself can only come from the environment or the config file
+        if source.parse_or("APP_CONFIG_SOURCE", ConfigSourceKind::Env)? == ConfigSourceKind::Ssm {
+            let remote_values = remote_config::fetch(&source).await?;
+            source.set_remote_values(remote_values);
+        }
+        Self::from_source(&source)
+    }
+
+    /// Builds the configuration from already-resolved layered values.
+    pub fn from_source(source: &ConfigSource) -> Result<Self, ConfigError> {
         // --- Application Specific Config ---
-        let bind_address_str = env::var("APP_SERVER_ADDRESS")
-            .unwrap_or_else(|_| "0.0.0.0:3000".to_string());
-        let bind_address = SocketAddr::from_str(&bind_address_str)
-            .map_err(|e| ConfigError::InvalidVar("APP_SERVER_ADDRESS".into(), e.to_string()))?;
+        let bind_address_str = source.get("APP_SERVER_ADDRESS")
+            .unwrap_or_else(|| "0.0.0.0:3000".to_string());
+        let bind_address = ListenAddress::from_str(&bind_address_str)
+            .map_err(|e| ConfigError::InvalidVar("APP_SERVER_ADDRESS".into(), e))?;
+        let server_socket_mode = match source.get("APP_SERVER_SOCKET_MODE") {
+            Some(mode) => u32::from_str_radix(mode.trim_start_matches("0o"), 8)
+                .ok()
+                .filter(|mode| *mode <= 0o777)
+                .ok_or_else(|| ConfigError::InvalidVar("APP_SERVER_SOCKET_MODE".into(), format!("'{}' is not an octal file mode", mode)))?,
+            None => 0o660,
+        };
+        let public_url = source.get("APP_PUBLIC_URL").filter(|url| !url.is_empty()).map(|url| url.trim_end_matches('/').to_string());
+        if let Some(url) = &public_url {
+            check_cdn_base_url(url).map_err(|e| ConfigError::InvalidVar("APP_PUBLIC_URL".into(), format!("'{}': {}", url, e)))?;
+        }
+
+        // Backends first: local runs need neither a bucket nor a table name
+        let storage_backend = source.parse_or("APP_STORAGE_BACKEND", StorageBackend::S3)?;
+        let repository_backend = source.parse_or("APP_REPOSITORY_BACKEND", RepositoryBackend::DynamoDb)?;
 
         // Required variables - return specific error if missing
-        let meme_bucket_name = env::var("APP_S3_BUCKET_NAME")
-            .map_err(|_| ConfigError::MissingVar("APP_S3_BUCKET_NAME".into()))?;
+        let meme_bucket_name = match storage_backend {
+            StorageBackend::Filesystem => source.get("APP_S3_BUCKET_NAME").unwrap_or_else(|| "memes".to_string()),
+            _ => source.require("APP_S3_BUCKET_NAME")?,
+        };
+
+        // Also names the SQLite table and MongoDB collection
+        let dynamodb_table_name = match repository_backend {
+            RepositoryBackend::Sqlite => source.get("APP_DYNAMODB_TABLE_NAME").unwrap_or_else(|| "memes".to_string()),
+            _ => source.require("APP_DYNAMODB_TABLE_NAME")?,
+        };
+
+        let meta_table_name = source.get("APP_DYNAMODB_META_TABLE_NAME")
+            .unwrap_or_else(|| format!("{}-meta", dynamodb_table_name));
 
-        let dynamodb_table_name = env::var("APP_DYNAMODB_TABLE_NAME")
-            .map_err(|_| ConfigError::MissingVar("APP_DYNAMODB_TABLE_NAME".into()))?;
+        let dynamodb_scan_segments: u32 = source.parse_or("APP_DYNAMODB_SCAN_SEGMENTS", 1)?;
+        if !(1..=MAX_SCAN_SEGMENTS).contains(&dynamodb_scan_segments) {
+            return Err(ConfigError::InvalidVar(
+                "APP_DYNAMODB_SCAN_SEGMENTS".into(),
+                format!("must be between 1 and {}", MAX_SCAN_SEGMENTS),
+            ));
+        }
 
 
         // --- AWS Related Config ---
         // Use standard AWS SDK environment variables
-        // Prefer AWS_REGION if set, fallback to AWS_DEFAULT_REGION, then to hardcoded default
-        let aws_region = env::var("AWS_REGION")
-            .or_else(|_| env::var("AWS_DEFAULT_REGION"))
-            .unwrap_or_else(|_| "ca-central-1".to_string()); // Default region
+        let aws_region = source.aws_region();
 
         // Optional override for LocalStack/testing
-        let localstack_endpoint = env::var("AWS_ENDPOINT_URL").ok();
+        let localstack_endpoint = source.get("AWS_ENDPOINT_URL");
+
+        // --- Validation Limits ---
+        let max_title_length = source.parse_or("APP_MAX_TITLE_LENGTH", 100)?;
+        let max_description_length = source.parse_or("APP_MAX_DESCRIPTION_LENGTH", 1000)?;
+        let max_tags = source.parse_or("APP_MAX_TAGS", 10)?;
+        let max_tag_length = source.parse_or("APP_MAX_TAG_LENGTH", 32)?;
+        let max_expires_in_secs = source.parse_or("APP_MAX_EXPIRES_IN_SECS", 30 * 24 * 60 * 60)?;
+
+        // --- Upload Limits ---
+        let max_upload_bytes: usize = source.parse_or("APP_MAX_UPLOAD_BYTES", 10 * 1024 * 1024)?;
+        if max_upload_bytes == 0 {
+            return Err(ConfigError::InvalidVar("APP_MAX_UPLOAD_BYTES".into(), "must be at least 1".into()));
+        }
+        let allowed_image_types: Vec<String> = split_list(&source.get("APP_ALLOWED_IMAGE_TYPES").unwrap_or_default())
+            .into_iter()
+            .map(|media_type| media_type.to_ascii_lowercase())
+            .collect();
+        let max_uploads_per_day = source.parse_optional("APP_MAX_UPLOADS_PER_DAY")?;
+        let max_image_dimension = source.parse_optional("APP_MAX_IMAGE_DIMENSION")?;
+        // By default uploads fit an image of APP_MAX_UPLOAD_BYTES encoded as base64 in JSON
+        let upload_body_limits = BodyLimits {
+            by_media_type: source.parse_or("APP_UPLOAD_BODY_LIMITS", MediaTypeLimits::default())?,
+            default: source.parse_or("APP_MAX_UPLOAD_BODY_BYTES", max_upload_bytes.saturating_mul(4) / 3 + 1024 * 1024)?,
+        };
+        let body_limits = BodyLimits {
+            by_media_type: source.parse_or("APP_BODY_LIMITS", MediaTypeLimits::default())?,
+            default: source.parse_or("APP_MAX_BODY_BYTES", 2 * 1024 * 1024)?,
+        };
+
+        // --- Content Filter ---
+        let content_filter_mode = source.parse_or("APP_CONTENT_FILTER_MODE", FilterMode::Reject)?;
+        let content_filter_terms = source.get("APP_CONTENT_FILTER_TERMS")
+            .map(|terms| split_list(&terms))
+            .unwrap_or_default();
+
+        // --- Remote Image Fetching ---
+        let fetch_timeout_secs = source.parse_or("APP_FETCH_TIMEOUT_SECS", 10)?;
+        let fetch_max_bytes = source.parse_or("APP_FETCH_MAX_BYTES", 10 * 1024 * 1024)?;
+
+        // --- Backups ---
+        let backup_prefix = source.get("APP_BACKUP_PREFIX").unwrap_or_else(|| "backups".to_string());
+        let backup_interval_secs = source.parse_optional("APP_BACKUP_INTERVAL_SECS")?;
+
+        // --- Expiring Memes ---
+        let expiry_cleanup_interval_secs = source.parse_or("APP_EXPIRY_CLEANUP_INTERVAL_SECS", 900)?;
+
+        // --- Scheduled Publishing ---
+        let publish_interval_secs = source.parse_or("APP_PUBLISH_INTERVAL_SECS", 60)?;
+
+        // --- Statistics ---
+        let stats_interval_secs: u64 = source.parse_or("APP_STATS_INTERVAL_SECS", 300)?;
+        if stats_interval_secs == 0 {
+            return Err(ConfigError::InvalidVar("APP_STATS_INTERVAL_SECS".into(), "must be at least 1".into()));
+        }
+
+        // --- Sitemap ---
+        let sitemap_ttl_secs = source.parse_or("APP_SITEMAP_TTL_SECS", 3600)?;
+
+        // --- Trending ---
+        let trending_interval_secs: u64 = source.parse_or("APP_TRENDING_INTERVAL_SECS", 300)?;
+        if trending_interval_secs == 0 {
+            return Err(ConfigError::InvalidVar("APP_TRENDING_INTERVAL_SECS".into(), "must be at least 1".into()));
+        }
+        let trending_half_life_hours: u64 = source.parse_or("APP_TRENDING_HALF_LIFE_HOURS", 24)?;
+        if trending_half_life_hours == 0 {
+            return Err(ConfigError::InvalidVar("APP_TRENDING_HALF_LIFE_HOURS".into(), "must be at least 1".into()));
+        }
+        let trending_limit: usize = source.parse_or("APP_TRENDING_LIMIT", 50)?;
+        if !(1..=MAX_TRENDING_LIMIT).contains(&trending_limit) {
+            return Err(ConfigError::InvalidVar(
+                "APP_TRENDING_LIMIT".into(),
+                format!("must be between 1 and {}", MAX_TRENDING_LIMIT),
+            ));
+        }
+
+        // --- Backfills ---
+        let backfill_rate: u32 = source.parse_or("APP_BACKFILL_RATE", 5)?;
+        if backfill_rate == 0 {
+            return Err(ConfigError::InvalidVar("APP_BACKFILL_RATE".into(), "must be at least 1".into()));
+        }
+
+        // --- OCR ---
+        let ocr_backend = source.parse_or("APP_OCR_BACKEND", OcrBackend::None)?;
+        if ocr_backend == OcrBackend::Tesseract && !cfg!(feature = "tesseract") {
+            return Err(ConfigError::InvalidVar("APP_OCR_BACKEND".into(), "tesseract needs a build with the `tesseract` feature".into()));
+        }
+        let ocr_timeout_secs: u64 = source.parse_or("APP_OCR_TIMEOUT_SECS", 10)?;
+        if ocr_timeout_secs == 0 {
+            return Err(ConfigError::InvalidVar("APP_OCR_TIMEOUT_SECS".into(), "must be at least 1".into()));
+        }
+        let tesseract_command = source.get("APP_TESSERACT_COMMAND").filter(|command| !command.is_empty()).unwrap_or_else(|| "tesseract".to_string());
+
+        // --- Moderation ---
+        let moderation_enabled = source.parse_or("APP_MODERATION", false)?;
+
+        // --- Malware Scanning ---
+        let scanner_backend = source.parse_or("APP_SCANNER_BACKEND", ScannerBackend::None)?;
+        let clamav_address = source.get("APP_CLAMAV_ADDRESS").filter(|address| !address.is_empty()).unwrap_or_else(|| "127.0.0.1:3310".to_string());
+        let scan_timeout_secs: u64 = source.parse_or("APP_SCAN_TIMEOUT_SECS", 30)?;
+        if scan_timeout_secs == 0 {
+            return Err(ConfigError::InvalidVar("APP_SCAN_TIMEOUT_SECS".into(), "must be at least 1".into()));
+        }
+
+        // --- Semantic Search ---
+        let embedding_backend = source.parse_or("APP_EMBEDDING_BACKEND", EmbeddingBackend::None)?;
+        let embedding_model = source.get("APP_EMBEDDING_MODEL").filter(|model| !model.is_empty()).unwrap_or_else(|| "amazon.titan-embed-text-v2:0".to_string());
+        let embedding_timeout_secs: u64 = source.parse_or("APP_EMBEDDING_TIMEOUT_SECS", 10)?;
+        if embedding_timeout_secs == 0 {
+            return Err(ConfigError::InvalidVar("APP_EMBEDDING_TIMEOUT_SECS".into(), "must be at least 1".into()));
+        }
+
+        // --- Change Stream Consumer ---
+        let stream_consumer_enabled = source.parse_or("APP_STREAM_CONSUMER", false)?;
+        let stream_poll_interval_ms = source.parse_or("APP_STREAM_POLL_INTERVAL_MS", 1000)?;
+        if stream_poll_interval_ms == 0 {
+            return Err(ConfigError::InvalidVar("APP_STREAM_POLL_INTERVAL_MS".into(), "must be at least 1".into()));
+        }
+
+        // --- Webhooks ---
+        let webhook_urls = split_list(&source.get("APP_WEBHOOK_URLS").unwrap_or_default());
+        for url in &webhook_urls {
+            check_http_url(url)
+                .map_err(|e| ConfigError::InvalidVar("APP_WEBHOOK_URLS".into(), format!("'{}': {}", url, e)))?;
+        }
+        if !webhook_urls.is_empty() && !stream_consumer_enabled {
+            return Err(ConfigError::InvalidVar(
+                "APP_WEBHOOK_URLS".into(),
+                "webhooks are delivered by the change stream consumer; set APP_STREAM_CONSUMER=true".into(),
+            ));
+        }
+        let webhook_secret = source.get("APP_WEBHOOK_SECRET").filter(|s| !s.is_empty());
+
+        // --- Email Notifications ---
+        let notification_emails = split_list(&source.get("APP_NOTIFICATION_EMAILS").unwrap_or_default());
+        if let Some(address) = notification_emails.iter().find(|address| !address.contains('@')) {
+            return Err(ConfigError::InvalidVar("APP_NOTIFICATION_EMAILS".into(), format!("'{}' is not an email address", address)));
+        }
+        let notification_sender = source.get("APP_NOTIFICATION_SENDER").filter(|sender| !sender.is_empty());
+        if !notification_emails.is_empty() && notification_sender.is_none() {
+            return Err(ConfigError::InvalidVar(
+                "APP_NOTIFICATION_EMAILS".into(),
+                "notifications need APP_NOTIFICATION_SENDER, an address verified in SES".into(),
+            ));
+        }
+        let expiry_notice_secs = source.parse_or("APP_EXPIRY_NOTICE_SECS", 86_400)?;
+
+        // --- Slack App ---
+        let slack_signing_secret = source.get("APP_SLACK_SIGNING_SECRET").filter(|s| !s.is_empty());
+        let slack_bot_token = source.get("APP_SLACK_BOT_TOKEN").filter(|t| !t.is_empty());
+        if slack_bot_token.is_some() && slack_signing_secret.is_none() {
+            return Err(ConfigError::InvalidVar(
+                "APP_SLACK_BOT_TOKEN".into(),
+                "unfurls answer events on /slack/events, which needs APP_SLACK_SIGNING_SECRET".into(),
+            ));
+        }
+        let slack_webhook_url = source.get("APP_SLACK_WEBHOOK_URL").filter(|url| !url.is_empty());
+        if let Some(url) = &slack_webhook_url {
+            // Not echoed: the URL is the webhook's credential
+            check_http_url(url).map_err(|e| ConfigError::InvalidVar("APP_SLACK_WEBHOOK_URL".into(), e))?;
+        }
+
+        // --- Discord Bot ---
+        let discord_bot_token = source.get("APP_DISCORD_BOT_TOKEN").filter(|t| !t.is_empty());
+        let discord_application_id = parse_snowflake(source, "APP_DISCORD_APPLICATION_ID")?;
+        let discord_public_key = source.get("APP_DISCORD_PUBLIC_KEY").filter(|key| !key.is_empty());
+        let discord_channel_id = parse_snowflake(source, "APP_DISCORD_CHANNEL_ID")?;
+        if !cfg!(feature = "discord") {
+            let configured = [
+                ("APP_DISCORD_BOT_TOKEN", discord_bot_token.is_some()),
+                ("APP_DISCORD_APPLICATION_ID", discord_application_id.is_some()),
+                ("APP_DISCORD_PUBLIC_KEY", discord_public_key.is_some()),
+                ("APP_DISCORD_CHANNEL_ID", discord_channel_id.is_some()),
+            ];
+            if let Some((var, _)) = configured.iter().find(|(_, set)| *set) {
+                return Err(ConfigError::InvalidVar((*var).into(), "the Discord bot needs a build with the `discord` feature".into()));
+            }
+        }
+        if let Some(key) = &discord_public_key
+            && hex::decode(key).map(|bytes| bytes.len()) != Ok(32)
+        {
+            return Err(ConfigError::InvalidVar("APP_DISCORD_PUBLIC_KEY".into(), "must be the 64 hex digits of the application's public key".into()));
+        }
+        if discord_bot_token.is_none() {
+            if discord_channel_id.is_some() {
+                return Err(ConfigError::InvalidVar("APP_DISCORD_CHANNEL_ID".into(), "posting memes needs APP_DISCORD_BOT_TOKEN".into()));
+            }
+            if discord_application_id.is_some() {
+                return Err(ConfigError::InvalidVar("APP_DISCORD_APPLICATION_ID".into(), "registering the /meme command needs APP_DISCORD_BOT_TOKEN".into()));
+            }
+        }
+
+        // --- Telegram Bot ---
+        let telegram_bot_token = source.get("APP_TELEGRAM_BOT_TOKEN").filter(|t| !t.is_empty());
+        if telegram_bot_token.is_some() && !cfg!(feature = "telegram") {
+            return Err(ConfigError::InvalidVar("APP_TELEGRAM_BOT_TOKEN".into(), "the Telegram bot needs a build with the `telegram` feature".into()));
+        }
+        let telegram_webhook_secret = source.get("APP_TELEGRAM_WEBHOOK_SECRET").filter(|secret| !secret.is_empty());
+        if let Some(secret) = &telegram_webhook_secret {
+            let reason = if telegram_bot_token.is_none() {
+                Some("the webhook needs APP_TELEGRAM_BOT_TOKEN")
+            } else if public_url.is_none() {
+                Some("requires APP_PUBLIC_URL, which the webhook's URL starts with")
+            } else if secret.len() > 256 || !secret.bytes().all(|byte| byte.is_ascii_alphanumeric() || byte == b'_' || byte == b'-') {
+                Some("must be at most 256 letters, digits, '_' and '-'")
+            } else {
+                None
+            };
+            if let Some(reason) = reason {
+                return Err(ConfigError::InvalidVar("APP_TELEGRAM_WEBHOOK_SECRET".into(), reason.into()));
+            }
+        }
+
+        // --- ActivityPub ---
+        // PEM keys may come from a single-line environment variable with escaped newlines
+        let activitypub_private_key =
+            source.get("APP_ACTIVITYPUB_PRIVATE_KEY").filter(|key| !key.is_empty()).map(|key| key.replace("\\n", "\n"));
+        if activitypub_private_key.is_some() && public_url.is_none() {
+            return Err(ConfigError::InvalidVar(
+                "APP_ACTIVITYPUB_PRIVATE_KEY".into(),
+                "requires APP_PUBLIC_URL, which the actor's URLs start with".into(),
+            ));
+        }
+        let activitypub_username = source.get("APP_ACTIVITYPUB_USERNAME").unwrap_or_else(|| "memes".to_string());
+        if activitypub_username.is_empty()
+            || !activitypub_username.bytes().all(|byte| byte.is_ascii_lowercase() || byte.is_ascii_digit() || byte == b'_')
+        {
+            return Err(ConfigError::InvalidVar(
+                "APP_ACTIVITYPUB_USERNAME".into(),
+                format!("'{}' must be lowercase letters, digits and '_'", activitypub_username),
+            ));
+        }
+
+        // --- Admin API ---
+        let admin_token = source.get("APP_ADMIN_TOKEN").filter(|t| !t.is_empty());
+        let admin_address: Option<ListenAddress> = source.parse_optional("APP_ADMIN_ADDRESS")?;
+        if admin_address.as_ref().is_some_and(|address| *address == bind_address) {
+            return Err(ConfigError::InvalidVar("APP_ADMIN_ADDRESS".into(), "must differ from APP_SERVER_ADDRESS".into()));
+        }
+
+        // --- Tenants ---
+        let tenants = split_list(&source.get("APP_TENANTS").unwrap_or_default());
+        if let Some(invalid) = tenants.iter().find(|tenant| !crate::tenant::is_valid_tenant_id(tenant)) {
+            return Err(ConfigError::InvalidVar(
+                "APP_TENANTS".into(),
+                format!("'{}' is not a tenant ID (lowercase letters, digits and '-', at most 32 characters)", invalid),
+            ));
+        }
+        let tenant_domain = source.get("APP_TENANT_DOMAIN").map(|domain| domain.trim_matches('.').to_ascii_lowercase());
+        if tenant_domain.is_some() && tenants.is_empty() {
+            return Err(ConfigError::InvalidVar("APP_TENANT_DOMAIN".into(), "needs APP_TENANTS".into()));
+        }
+        let tenant_config_ttl_secs = source.parse_or("APP_TENANT_CONFIG_TTL_SECS", 60)?;
+
+        // --- Share Links ---
+        let share_secret = source.get("APP_SHARE_SECRET").filter(|s| !s.is_empty());
+        if share_secret.as_ref().is_some_and(|s| s.len() < MIN_SHARE_SECRET_LEN) {
+            return Err(ConfigError::InvalidVar(
+                "APP_SHARE_SECRET".into(),
+                format!("must be at least {} characters", MIN_SHARE_SECRET_LEN),
+            ));
+        }
+        let share_link_ttl_secs: u64 = source.parse_or("APP_SHARE_LINK_TTL_SECS", 24 * 60 * 60)?;
+        let share_link_max_ttl_secs: u64 = source.parse_or("APP_SHARE_LINK_MAX_TTL_SECS", 7 * 24 * 60 * 60)?;
+        if share_link_ttl_secs == 0 || share_link_ttl_secs > share_link_max_ttl_secs {
+            return Err(ConfigError::InvalidVar(
+                "APP_SHARE_LINK_TTL_SECS".into(),
+                "must be between 1 and APP_SHARE_LINK_MAX_TTL_SECS".into(),
+            ));
+        }
+
+        // --- CDN ---
+        let cdn_base_url = source.get("APP_CDN_BASE_URL").map(|url| url.trim_end_matches('/').to_string());
+        if let Some(url) = &cdn_base_url {
+            check_cdn_base_url(url).map_err(|e| ConfigError::InvalidVar("APP_CDN_BASE_URL".into(), format!("'{}': {}", url, e)))?;
+        }
+        let cdn_key_pair_id = source.get("APP_CDN_KEY_PAIR_ID").filter(|id| !id.is_empty());
+        // PEM keys may come from a single-line environment variable with escaped newlines
+        let cdn_private_key = source.get("APP_CDN_PRIVATE_KEY").filter(|key| !key.is_empty()).map(|key| key.replace("\\n", "\n"));
+        if cdn_key_pair_id.is_some() != cdn_private_key.is_some() {
+            return Err(ConfigError::InvalidVar(
+                "APP_CDN_KEY_PAIR_ID".into(),
+                "APP_CDN_KEY_PAIR_ID and APP_CDN_PRIVATE_KEY must be set together".into(),
+            ));
+        }
+        if cdn_key_pair_id.is_some() && cdn_base_url.is_none() {
+            return Err(ConfigError::InvalidVar("APP_CDN_KEY_PAIR_ID".into(), "requires APP_CDN_BASE_URL".into()));
+        }
+        let cdn_sign_all_urls = source.parse_or("APP_CDN_SIGN_ALL_URLS", false)?;
+        if cdn_sign_all_urls && cdn_key_pair_id.is_none() {
+            return Err(ConfigError::InvalidVar("APP_CDN_SIGN_ALL_URLS".into(), "requires APP_CDN_KEY_PAIR_ID".into()));
+        }
+        let cdn_cookie_domain = source.get("APP_CDN_COOKIE_DOMAIN").filter(|domain| !domain.is_empty());
+        let image_url_ttl_secs: u64 = source.parse_or("APP_IMAGE_URL_TTL_SECS", 3600)?;
+        if image_url_ttl_secs == 0 || image_url_ttl_secs > MAX_PRESIGNED_URL_SECS {
+            return Err(ConfigError::InvalidVar(
+                "APP_IMAGE_URL_TTL_SECS".into(),
+                format!("must be between 1 and {} (7 days, the S3 presigning limit)", MAX_PRESIGNED_URL_SECS),
+            ));
+        }
+
+        // --- TLS ---
+        let tls_cert_path: Option<PathBuf> = source.parse_optional("APP_TLS_CERT_PATH")?;
+        let tls_key_path: Option<PathBuf> = source.parse_optional("APP_TLS_KEY_PATH")?;
+        if tls_cert_path.is_some() != tls_key_path.is_some() {
+            return Err(ConfigError::InvalidVar(
+                "APP_TLS_CERT_PATH".into(),
+                "APP_TLS_CERT_PATH and APP_TLS_KEY_PATH must be set together".into(),
+            ));
+        }
+        if tls_cert_path.is_some() && matches!(bind_address, ListenAddress::Unix(_)) {
+            return Err(ConfigError::InvalidVar("APP_TLS_CERT_PATH".into(), "TLS is not supported on a Unix socket".into()));
+        }
+        let tls_redirect_address: Option<SocketAddr> = source.parse_optional("APP_TLS_REDIRECT_ADDRESS")?;
+        if tls_redirect_address.is_some() && tls_cert_path.is_none() {
+            return Err(ConfigError::InvalidVar("APP_TLS_REDIRECT_ADDRESS".into(), "requires APP_TLS_CERT_PATH and APP_TLS_KEY_PATH".into()));
+        }
+        let redirect_conflicts = |address: &ListenAddress| tls_redirect_address.is_some_and(|r| *address == ListenAddress::Tcp(r));
+        if redirect_conflicts(&bind_address) || admin_address.as_ref().is_some_and(redirect_conflicts) {
+            return Err(ConfigError::InvalidVar("APP_TLS_REDIRECT_ADDRESS".into(), "must differ from the other listen addresses".into()));
+        }
+
+        // --- Retries ---
+        let retry_max_attempts = source.parse_or("APP_RETRY_MAX_ATTEMPTS", 3)?;
+        let retry_base_delay_ms = source.parse_or("APP_RETRY_BASE_DELAY_MS", 100)?;
+        let retry_max_delay_ms = source.parse_or("APP_RETRY_MAX_DELAY_MS", 2000)?;
+        let retry_budget_ratio: f64 = source.parse_or("APP_RETRY_BUDGET_RATIO", 0.2)?;
+        if !(0.0..=1.0).contains(&retry_budget_ratio) {
+            return Err(ConfigError::InvalidVar("APP_RETRY_BUDGET_RATIO".into(), "must be between 0 and 1".into()));
+        }
+
+        // --- Circuit Breakers ---
+        let breaker_failure_threshold = source.parse_or("APP_BREAKER_FAILURE_THRESHOLD", 5)?;
+        let breaker_open_secs = source.parse_or("APP_BREAKER_OPEN_SECS", 30)?;
+
+        // --- Resource Initialization ---
+        // Lambda cold starts should not pay for (or need IAM permissions for) resource creation
+        let default_resource_init = if cfg!(feature = "lambda") { ResourceInitMode::Skip } else { ResourceInitMode::Create };
+        let resource_init = source.parse_or("APP_RESOURCE_INIT", default_resource_init)?;
+
+        // --- DynamoDB Capacity ---
+        let capacity_keys = [
+            "APP_DYNAMODB_READ_CAPACITY",
+            "APP_DYNAMODB_WRITE_CAPACITY",
+            "APP_DYNAMODB_INDEX_READ_CAPACITY",
+            "APP_DYNAMODB_INDEX_WRITE_CAPACITY",
+            "APP_DYNAMODB_META_READ_CAPACITY",
+            "APP_DYNAMODB_META_WRITE_CAPACITY",
+        ];
+        let capacity_units = |key: &str, default: i64| -> Result<i64, ConfigError> {
+            let units = source.parse_or(key, default)?;
+            if units < 1 {
+                return Err(ConfigError::InvalidVar(key.into(), "must be at least 1".into()));
+            }
+            Ok(units)
+        };
+        let (dynamodb_capacity, dynamodb_meta_capacity) =
+            match source.get("APP_DYNAMODB_BILLING_MODE").unwrap_or_default().to_ascii_lowercase().as_str() {
+                "" | "pay_per_request" => {
+                    if let Some(key) = capacity_keys.into_iter().find(|key| source.get(key).is_some_and(|v| !v.is_empty())) {
+                        return Err(ConfigError::InvalidVar(key.into(), "requires APP_DYNAMODB_BILLING_MODE=provisioned".into()));
+                    }
+                    (TableCapacity::PayPerRequest, TableCapacity::PayPerRequest)
+                }
+                "provisioned" => {
+                    let table = Throughput {
+                        read: capacity_units("APP_DYNAMODB_READ_CAPACITY", 5)?,
+                        write: capacity_units("APP_DYNAMODB_WRITE_CAPACITY", 5)?,
+                    };
+                    let indexes = Throughput {
+                        read: capacity_units("APP_DYNAMODB_INDEX_READ_CAPACITY", table.read)?,
+                        write: capacity_units("APP_DYNAMODB_INDEX_WRITE_CAPACITY", table.write)?,
+                    };
+                    let meta = Throughput {
+                        read: capacity_units("APP_DYNAMODB_META_READ_CAPACITY", table.read)?,
+                        write: capacity_units("APP_DYNAMODB_META_WRITE_CAPACITY", table.write)?,
+                    };
+                    // The metadata table has no indexes
+                    (TableCapacity::Provisioned { table, indexes }, TableCapacity::Provisioned { table: meta, indexes: meta })
+                }
+                other => {
+                    return Err(ConfigError::InvalidVar(
+                        "APP_DYNAMODB_BILLING_MODE".into(),
+                        format!("unknown billing mode '{}' (expected pay_per_request or provisioned)", other),
+                    ))
+                }
+            };
+        // --- Region Failover ---
+        let dynamodb_secondary_region = source.get("APP_DYNAMODB_SECONDARY_REGION").filter(|region| !region.is_empty());
+        if let Some(region) = &dynamodb_secondary_region {
+            if *region == aws_region {
+                return Err(ConfigError::InvalidVar("APP_DYNAMODB_SECONDARY_REGION".into(), "must differ from AWS_REGION".into()));
+            }
+            if repository_backend != RepositoryBackend::DynamoDb {
+                return Err(ConfigError::InvalidVar(
+                    "APP_DYNAMODB_SECONDARY_REGION".into(),
+                    "requires APP_REPOSITORY_BACKEND=dynamodb".into(),
+                ));
+            }
+        }
+        let failover_writes = source.parse_or("APP_FAILOVER_WRITES", false)?;
+        if failover_writes && dynamodb_secondary_region.is_none() {
+            return Err(ConfigError::InvalidVar("APP_FAILOVER_WRITES".into(), "requires APP_DYNAMODB_SECONDARY_REGION".into()));
+        }
+        let failover_check_interval_secs: u64 = source.parse_or("APP_FAILOVER_CHECK_INTERVAL_SECS", 10)?;
+        if failover_check_interval_secs == 0 {
+            return Err(ConfigError::InvalidVar("APP_FAILOVER_CHECK_INTERVAL_SECS".into(), "must be at least 1".into()));
+        }
+        let failover_threshold: u32 = source.parse_or("APP_FAILOVER_THRESHOLD", 3)?;
+        if failover_threshold == 0 {
+            return Err(ConfigError::InvalidVar("APP_FAILOVER_THRESHOLD".into(), "must be at least 1".into()));
+        }
+
+        // --- Hedged Reads ---
+        let hedged_reads = source.parse_or("APP_HEDGED_READS", false)?;
+        if hedged_reads && repository_backend != RepositoryBackend::DynamoDb {
+            return Err(ConfigError::InvalidVar("APP_HEDGED_READS".into(), "requires APP_REPOSITORY_BACKEND=dynamodb".into()));
+        }
+        let hedge_percentile: f64 = source.parse_or("APP_HEDGE_PERCENTILE", 95.0)?;
+        if !(50.0..100.0).contains(&hedge_percentile) {
+            return Err(ConfigError::InvalidVar("APP_HEDGE_PERCENTILE".into(), "must be at least 50 and below 100".into()));
+        }
+        let hedge_min_delay_ms: u64 = source.parse_or("APP_HEDGE_MIN_DELAY_MS", 5)?;
+
+        let consistent_reads = split_list(&source.get("APP_CONSISTENT_READS").unwrap_or_default())
+            .iter()
+            .map(|name| name.parse().map_err(|e| ConfigError::InvalidVar("APP_CONSISTENT_READS".into(), e)))
+            .collect::<Result<Vec<ReadEndpoint>, _>>()?;
+
+        // --- S3 Bucket Hardening ---
+        let s3_block_public_access = source.parse_or("APP_S3_BLOCK_PUBLIC_ACCESS", true)?;
+        let s3_encryption = source.parse_or("APP_S3_ENCRYPTION", BucketEncryption::S3Managed)?;
+        let s3_kms_key_id = source.get("APP_S3_KMS_KEY_ID").filter(|id| !id.is_empty());
+        if s3_kms_key_id.is_some() && s3_encryption != BucketEncryption::Kms {
+            return Err(ConfigError::InvalidVar("APP_S3_KMS_KEY_ID".into(), "requires APP_S3_ENCRYPTION=kms".into()));
+        }
+        let s3_versioning = source.parse_or("APP_S3_VERSIONING", false)?;
+        let s3_abort_incomplete_upload_days: Option<i32> = source.parse_optional("APP_S3_ABORT_INCOMPLETE_UPLOAD_DAYS")?;
+        if s3_abort_incomplete_upload_days.is_some_and(|days| days < 1) {
+            return Err(ConfigError::InvalidVar("APP_S3_ABORT_INCOMPLETE_UPLOAD_DAYS".into(), "must be at least 1".into()));
+        }
+
+        // --- Storage Backend ---
+        if storage_backend == StorageBackend::Azure && !cfg!(feature = "azure") {
+            return Err(ConfigError::InvalidVar("APP_STORAGE_BACKEND".into(), "azure needs a build with the `azure` feature".into()));
+        }
+        let azure_storage_account = source.get("APP_AZURE_STORAGE_ACCOUNT").filter(|account| !account.is_empty());
+        let azure_storage_access_key = source.get("APP_AZURE_STORAGE_ACCESS_KEY").filter(|key| !key.is_empty());
+        if storage_backend == StorageBackend::Azure && (azure_storage_account.is_none() || azure_storage_access_key.is_none()) {
+            return Err(ConfigError::InvalidVar(
+                "APP_AZURE_STORAGE_ACCOUNT".into(),
+                "APP_AZURE_STORAGE_ACCOUNT and APP_AZURE_STORAGE_ACCESS_KEY are required with APP_STORAGE_BACKEND=azure".into(),
+            ));
+        }
+        let azure_container_name = source.get("APP_AZURE_CONTAINER_NAME").filter(|name| !name.is_empty()).unwrap_or_else(|| meme_bucket_name.clone());
+        let azure_blob_endpoint = source.get("APP_AZURE_BLOB_ENDPOINT").filter(|url| !url.is_empty()).map(|url| url.trim_end_matches('/').to_string());
+        if storage_backend == StorageBackend::Gcs && !cfg!(feature = "gcs") {
+            return Err(ConfigError::InvalidVar("APP_STORAGE_BACKEND".into(), "gcs needs a build with the `gcs` feature".into()));
+        }
+        let gcs_bucket_name = source.get("APP_GCS_BUCKET_NAME").filter(|name| !name.is_empty()).unwrap_or_else(|| meme_bucket_name.clone());
+        let gcs_service_account_key = source.get("APP_GCS_SERVICE_ACCOUNT_KEY").filter(|key| !key.is_empty());
+        if let Some(key) = &gcs_service_account_key {
+            serde_json::from_str::<serde_json::Value>(key)
+                .map_err(|e| ConfigError::InvalidVar("APP_GCS_SERVICE_ACCOUNT_KEY".into(), format!("not a JSON key: {}", e)))?;
+        }
+        let gcs_project_id = source.get("APP_GCS_PROJECT_ID").filter(|id| !id.is_empty());
+        let gcs_endpoint = source.get("APP_GCS_ENDPOINT").filter(|url| !url.is_empty()).map(|url| url.trim_end_matches('/').to_string());
+        let filesystem_root = source.parse_or("APP_FILESYSTEM_ROOT", PathBuf::from("data/images"))?;
+
+        // --- Repository Backend ---
+        if repository_backend == RepositoryBackend::MongoDb && !cfg!(feature = "mongodb") {
+            return Err(ConfigError::InvalidVar("APP_REPOSITORY_BACKEND".into(), "mongodb needs a build with the `mongodb` feature".into()));
+        }
+        let mongodb_uri = source.get("APP_MONGODB_URI").filter(|uri| !uri.is_empty());
+        if repository_backend == RepositoryBackend::MongoDb && mongodb_uri.is_none() {
+            return Err(ConfigError::InvalidVar("APP_MONGODB_URI".into(), "required with APP_REPOSITORY_BACKEND=mongodb".into()));
+        }
+        if repository_backend == RepositoryBackend::Sqlite && !cfg!(feature = "sqlite") {
+            return Err(ConfigError::InvalidVar("APP_REPOSITORY_BACKEND".into(), "sqlite needs a build with the `sqlite` feature".into()));
+        }
+        if repository_backend != RepositoryBackend::DynamoDb && stream_consumer_enabled {
+            return Err(ConfigError::InvalidVar(
+                "APP_STREAM_CONSUMER".into(),
+                "the change stream consumer reads DynamoDB Streams and needs APP_REPOSITORY_BACKEND=dynamodb".into(),
+            ));
+        }
+        let mongodb_database = source.get("APP_MONGODB_DATABASE").filter(|name| !name.is_empty()).unwrap_or_else(|| "memes".to_string());
+        let sqlite_path = source.parse_or("APP_SQLITE_PATH", PathBuf::from("data/memes.db"))?;
+
+        // --- Upload Encryption ---
+        let s3_upload_kms_key_id = source.get("APP_S3_UPLOAD_KMS_KEY_ID").filter(|id| !id.is_empty());
+        if s3_upload_kms_key_id.is_some() && storage_backend != StorageBackend::S3 {
+            return Err(ConfigError::InvalidVar("APP_S3_UPLOAD_KMS_KEY_ID".into(), "only applies to APP_STORAGE_BACKEND=s3".into()));
+        }
+
+        // --- Object Tagging ---
+        let s3_object_tagging = source.parse_or("APP_S3_OBJECT_TAGGING", false)?;
+
+        // --- S3 Endpoint ---
+        let s3_region = source.get("APP_S3_REGION").filter(|region| !region.is_empty());
+        let s3_endpoint_url = source.get("APP_S3_ENDPOINT_URL").filter(|url| !url.is_empty());
+        if let Some(url) = &s3_endpoint_url {
+            check_http_url(url).map_err(|e| ConfigError::InvalidVar("APP_S3_ENDPOINT_URL".into(), e))?;
+        }
+        let s3_accelerate = source.parse_or("APP_S3_ACCELERATE", false)?;
+        if s3_accelerate {
+            if s3_endpoint_url.is_some() || localstack_endpoint.is_some() {
+                return Err(ConfigError::InvalidVar(
+                    "APP_S3_ACCELERATE".into(),
+                    "cannot be combined with an endpoint override (APP_S3_ENDPOINT_URL or AWS_ENDPOINT_URL)".into(),
+                ));
+            }
+            // Accelerated requests address the bucket as a DNS name
+            if storage_backend == StorageBackend::S3 && meme_bucket_name.contains('.') {
+                return Err(ConfigError::InvalidVar(
+                    "APP_S3_ACCELERATE".into(),
+                    format!("bucket '{}' has dots in its name, which Transfer Acceleration does not support", meme_bucket_name),
+                ));
+            }
+        }
+
+        // --- Image Keys ---
+        let image_key_layout = source.parse_or("APP_IMAGE_KEY_LAYOUT", KeyLayout::Flat)?;
+
+        // --- Backend Instrumentation ---
+        let backend_instrumentation = source.parse_or("APP_BACKEND_INSTRUMENTATION", true)?;
+
+        // --- Slow Request Logging ---
+        let slow_request_threshold_ms = source.parse_or("APP_SLOW_REQUEST_THRESHOLD_MS", 1000)?;
+        let slow_backend_call_threshold_ms = source.parse_or("APP_SLOW_BACKEND_CALL_THRESHOLD_MS", 500)?;
+
+        // --- Body Debug Logging ---
+        let debug_body_logging = source.parse_or("APP_DEBUG_BODY_LOGGING", false)?;
+        let debug_body_max_bytes = source.parse_or("APP_DEBUG_BODY_MAX_BYTES", 8192)?;
+        let debug_body_redact: Vec<String> = split_list(&source.get("APP_DEBUG_BODY_REDACT").unwrap_or_else(|| DEFAULT_DEBUG_BODY_REDACT.into()))
+            .into_iter()
+            .map(|name| name.to_ascii_lowercase())
+            .collect();
+
+        // --- Access Log ---
+        let access_log_format = source.parse_or("APP_ACCESS_LOG_FORMAT", AccessLogFormat::Off)?;
+        let access_log_path: Option<PathBuf> = source.parse_optional("APP_ACCESS_LOG_PATH")?;
+
+        // --- Client IP ---
+        let client_trusted_proxies = split_list(&source.get("APP_CLIENT_TRUSTED_PROXIES").unwrap_or_default())
+            .iter()
+            .map(|entry| {
+                client_ip::parse_network(entry).ok_or_else(|| {
+                    ConfigError::InvalidVar("APP_CLIENT_TRUSTED_PROXIES".into(), format!("'{}' is not an IP address or CIDR block", entry))
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let client_ip_headers: Vec<String> = split_list(&source.get("APP_CLIENT_IP_HEADERS").unwrap_or_else(|| "x-forwarded-for,forwarded".into()))
+            .into_iter()
+            .map(|name| name.to_ascii_lowercase())
+            .collect();
+        if let Some(name) = client_ip_headers.iter().find(|name| HeaderName::from_str(name).is_err()) {
+            return Err(ConfigError::InvalidVar("APP_CLIENT_IP_HEADERS".into(), format!("'{}' is not a header name", name)));
+        }
+        let server_proxy_protocol = source.parse_or("APP_SERVER_PROXY_PROTOCOL", false)?;
+        if server_proxy_protocol && (tls_cert_path.is_some() || matches!(bind_address, ListenAddress::Unix(_))) {
+            return Err(ConfigError::InvalidVar(
+                "APP_SERVER_PROXY_PROTOCOL".into(),
+                "is only supported on a plain TCP APP_SERVER_ADDRESS".into(),
+            ));
+        }
+
+        // --- Metrics Sink ---
+        let metrics_sink = source.parse_or("APP_METRICS_SINK", MetricsSink::Prometheus)?;
+        let runtime_metrics_interval_secs = source.parse_or("APP_RUNTIME_METRICS_INTERVAL_SECS", 10)?;
+        let emf_namespace = source.get("APP_EMF_NAMESPACE").filter(|namespace| !namespace.is_empty()).unwrap_or_else(|| "MemeService".to_string());
+        let emf_flush_interval_secs = source.parse_or("APP_EMF_FLUSH_INTERVAL_SECS", 60)?;
+        if emf_flush_interval_secs == 0 {
+            return Err(ConfigError::InvalidVar("APP_EMF_FLUSH_INTERVAL_SECS".into(), "must be at least 1".into()));
+        }
+        let emf_destination = EmfDestination::parse(source.get("APP_EMF_AGENT_ENDPOINT").filter(|endpoint| !endpoint.is_empty()).as_deref())
+            .map_err(|e| ConfigError::InvalidVar("APP_EMF_AGENT_ENDPOINT".into(), e))?;
+
+        // --- X-Ray ---
+        let xray_enabled = source.parse_or("APP_XRAY_ENABLED", false)?;
+        // AWS_XRAY_DAEMON_ADDRESS is what the X-Ray SDKs and the Lambda runtime use
+        let xray_daemon_address = source.get("APP_XRAY_DAEMON_ADDRESS")
+            .or_else(|| source.get("AWS_XRAY_DAEMON_ADDRESS"))
+            .filter(|address| !address.is_empty())
+            .unwrap_or_else(|| "127.0.0.1:2000".to_string());
+        let xray_service_name = source.get("APP_XRAY_SERVICE_NAME").filter(|name| !name.is_empty()).unwrap_or_else(|| "meme-service".to_string());
+        let xray_sample_rate: f64 = source.parse_or("APP_XRAY_SAMPLE_RATE", 0.05)?;
+        if !(0.0..=1.0).contains(&xray_sample_rate) {
+            return Err(ConfigError::InvalidVar("APP_XRAY_SAMPLE_RATE".into(), "must be between 0 and 1".into()));
+        }
+
+        // --- Error Reporting ---
+        let sentry_dsn = source.get("APP_SENTRY_DSN").filter(|dsn| !dsn.is_empty());
+        if let Some(dsn) = &sentry_dsn {
+            dsn.parse::<sentry::types::Dsn>()
+                .map_err(|e| ConfigError::InvalidVar("APP_SENTRY_DSN".into(), e.to_string()))?;
+        }
+        let sentry_environment = source.get("APP_SENTRY_ENVIRONMENT").filter(|environment| !environment.is_empty());
+
+        // --- Fault Injection ---
+        let chaos_error_rate: f64 = source.parse_or("APP_CHAOS_ERROR_RATE", 0.0)?;
+        if !(0.0..=1.0).contains(&chaos_error_rate) {
+            return Err(ConfigError::InvalidVar("APP_CHAOS_ERROR_RATE".into(), "must be between 0 and 1".into()));
+        }
+        let chaos_latency_ms = source.parse_or("APP_CHAOS_LATENCY_MS", 0)?;
+        let chaos_backends = split_list(&source.get("APP_CHAOS_BACKENDS").unwrap_or_else(|| "dynamodb,s3".to_string()));
+        if let Some(other) = chaos_backends.iter().find(|b| !matches!(b.as_str(), "dynamodb" | "s3")) {
+            return Err(ConfigError::InvalidVar(
+                "APP_CHAOS_BACKENDS".into(),
+                format!("'{}': expected dynamodb or s3", other),
+            ));
+        }
+        if !cfg!(feature = "chaos") && (chaos_error_rate > 0.0 || chaos_latency_ms > 0) {
+            let key = if chaos_error_rate > 0.0 { "APP_CHAOS_ERROR_RATE" } else { "APP_CHAOS_LATENCY_MS" };
+            return Err(ConfigError::InvalidVar(
+                key.into(),
+                "fault injection requires building with --features chaos".into(),
+            ));
+        }
+
+        // --- Shutdown ---
+        let shutdown_grace_secs = source.parse_or("APP_SHUTDOWN_GRACE_SECS", 30)?;
+
+        // --- Request Timeouts ---
+        let request_timeout_secs = source.parse_or("APP_REQUEST_TIMEOUT_SECS", 30)?;
+        let upload_timeout_secs = source.parse_or("APP_UPLOAD_TIMEOUT_SECS", 120)?;
+
+        // --- CORS ---
+        let cors_allowed_origins = cors_list(source, "APP_CORS_ALLOWED_ORIGINS", "", check_origin)?;
+        let cors_allowed_methods = cors_list(source, "APP_CORS_ALLOWED_METHODS", "GET,POST,PATCH,DELETE", |method| {
+            Method::from_str(method).map(|_| ()).map_err(|e| e.to_string())
+        })?;
+        let cors_allowed_headers = cors_list(source, "APP_CORS_ALLOWED_HEADERS", "content-type,authorization,if-match", |header| {
+            HeaderName::from_str(header).map(|_| ()).map_err(|e| e.to_string())
+        })?;
+        let cors_max_age_secs = source.parse_or("APP_CORS_MAX_AGE_SECS", 600)?;
 
         info!(
+            config_file = ?source.file_path(),
+            remote_settings = source.remote_value_count(),
             bind_address = %bind_address,
+            tls = tls_cert_path.is_some(),
+            stream_consumer = stream_consumer_enabled,
             bucket_name = %meme_bucket_name,
             table_name = %dynamodb_table_name,
+            meta_table_name = %meta_table_name,
             region = %aws_region,
             endpoint_url = ?localstack_endpoint,
+            cors_allowed_origins = ?cors_allowed_origins,
             "Configuration loaded"
         ); // Added info log
 
         Ok(Config {
             bind_address,
+            server_socket_mode,
+            public_url,
             meme_bucket_name,
             dynamodb_table_name, // Include new field
+            meta_table_name,
+            dynamodb_scan_segments,
             aws_region,
             localstack_endpoint,
+            max_title_length,
+            max_description_length,
+            max_tags,
+            max_tag_length,
+            max_expires_in_secs,
+            max_upload_bytes,
+            allowed_image_types,
+            max_uploads_per_day,
+            max_image_dimension,
+            upload_body_limits,
+            body_limits,
+            content_filter_mode,
+            content_filter_terms,
+            fetch_timeout_secs,
+            fetch_max_bytes,
+            backup_prefix,
+            backup_interval_secs,
+            expiry_cleanup_interval_secs,
+            publish_interval_secs,
+            stats_interval_secs,
+            sitemap_ttl_secs,
+            trending_interval_secs,
+            trending_half_life_hours,
+            trending_limit,
+            backfill_rate,
+            ocr_backend,
+            ocr_timeout_secs,
+            tesseract_command,
+            moderation_enabled,
+            scanner_backend,
+            clamav_address,
+            scan_timeout_secs,
+            embedding_backend,
+            embedding_model,
+            embedding_timeout_secs,
+            stream_consumer_enabled,
+            stream_poll_interval_ms,
+            webhook_urls,
+            webhook_secret,
+            notification_emails,
+            notification_sender,
+            expiry_notice_secs,
+            slack_signing_secret,
+            slack_bot_token,
+            slack_webhook_url,
+            discord_bot_token,
+            discord_application_id,
+            discord_public_key,
+            discord_channel_id,
+            telegram_bot_token,
+            telegram_webhook_secret,
+            activitypub_private_key,
+            activitypub_username,
+            admin_token,
+            admin_address,
+            tenants,
+            tenant_domain,
+            tenant_config_ttl_secs,
+            tenant: None,
+            share_secret,
+            share_link_ttl_secs,
+            share_link_max_ttl_secs,
+            cdn_base_url,
+            cdn_key_pair_id,
+            cdn_private_key,
+            cdn_sign_all_urls,
+            cdn_cookie_domain,
+            image_url_ttl_secs,
+            tls_cert_path,
+            tls_key_path,
+            tls_redirect_address,
+            backend_instrumentation,
+            slow_request_threshold_ms,
+            slow_backend_call_threshold_ms,
+            debug_body_logging,
+            debug_body_max_bytes,
+            debug_body_redact,
+            access_log_format,
+            access_log_path,
+            client_trusted_proxies,
+            client_ip_headers,
+            server_proxy_protocol,
+            metrics_sink,
+            runtime_metrics_interval_secs,
+            emf_namespace,
+            emf_flush_interval_secs,
+            emf_destination,
+            xray_enabled,
+            xray_daemon_address,
+            xray_service_name,
+            xray_sample_rate,
+            sentry_dsn,
+            sentry_environment,
+            chaos_error_rate,
+            chaos_latency_ms,
+            chaos_backends,
+            retry_max_attempts,
+            retry_base_delay_ms,
+            retry_max_delay_ms,
+            retry_budget_ratio,
+            breaker_failure_threshold,
+            breaker_open_secs,
+            resource_init,
+            dynamodb_capacity,
+            dynamodb_meta_capacity,
+            consistent_reads,
+            dynamodb_secondary_region,
+            failover_writes,
+            failover_check_interval_secs,
+            failover_threshold,
+            hedged_reads,
+            hedge_percentile,
+            hedge_min_delay_ms,
+            s3_block_public_access,
+            s3_encryption,
+            s3_kms_key_id,
+            s3_versioning,
+            s3_abort_incomplete_upload_days,
+            storage_backend,
+            azure_storage_account,
+            azure_storage_access_key,
+            azure_container_name,
+            azure_blob_endpoint,
+            filesystem_root,
+            gcs_bucket_name,
+            gcs_service_account_key,
+            gcs_project_id,
+            gcs_endpoint,
+            repository_backend,
+            mongodb_uri,
+            mongodb_database,
+            sqlite_path,
+            s3_upload_kms_key_id,
+            s3_object_tagging,
+            s3_region,
+            s3_endpoint_url,
+            s3_accelerate,
+            image_key_layout,
+            shutdown_grace_secs,
+            request_timeout_secs,
+            upload_timeout_secs,
+            cors_allowed_origins,
+            cors_allowed_methods,
+            cors_allowed_headers,
+            cors_max_age_secs,
+        })
+    }
+
+    /// The configuration of one tenant: memes go to its own table, `<table>-<tenant>`, and
+    /// images under `tenants/<tenant>/` in the shared bucket. Everything else is shared.
+    pub fn for_tenant(&self, tenant: &str) -> Config {
+        Config {
+            dynamodb_table_name: format!("{}-{}", self.dynamodb_table_name, tenant),
+            tenants: Vec::new(),
+            tenant: Some(tenant.to_string()),
+            ..self.clone()
+        }
+    }
+
+    /// How up to date the meme lookups of `endpoint` must be (`APP_CONSISTENT_READS`).
+    pub fn read_consistency(&self, endpoint: ReadEndpoint) -> ConsistencyLevel {
+        if self.consistent_reads.contains(&endpoint) {
+            ConsistencyLevel::Strong
+        } else {
+            ConsistencyLevel::Eventual
+        }
+    }
+
+    /// Region of the meme bucket: `APP_S3_REGION`, else `AWS_REGION`.
+    pub fn s3_region(&self) -> &str {
+        self.s3_region.as_deref().unwrap_or(&self.aws_region)
+    }
+
+    /// Prefix of this configuration's keys in the meme bucket; empty outside tenants.
+    pub fn s3_key_prefix(&self) -> String {
+        self.tenant.as_ref().map(|tenant| format!("tenants/{}/", tenant)).unwrap_or_default()
+    }
+}
+
+/// Serializes a secret setting as whether it is set, never its value.
+fn redact<S: Serializer>(secret: &Option<String>, serializer: S) -> Result<S::Ok, S::Error> {
+    secret.as_ref().map(|_| "[redacted]").serialize(serializer)
+}
+
+/// Groups of endpoints whose meme lookups `APP_CONSISTENT_READS` can make strongly consistent.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReadEndpoint {
+    /// GET /meme/{id} and /meme/{id}/history, e.g. right after an upload.
+    GetMeme,
+    /// Access checks of GET/HEAD /images/{key}, and GET /meme/{id}/download.
+    Images,
+    /// The read before PATCH, revert, publish, delete, approval and likes; a stale copy fails
+    /// the conditional ones with 412.
+    Writes,
+    /// GET /shared/{token} and its image.
+    Shared,
+    /// Memes named in GET /export?ids=.
+    Export,
+}
+
+impl FromStr for ReadEndpoint {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "get_meme" => Ok(ReadEndpoint::GetMeme),
+            "images" => Ok(ReadEndpoint::Images),
+            "writes" => Ok(ReadEndpoint::Writes),
+            "shared" => Ok(ReadEndpoint::Shared),
+            "export" => Ok(ReadEndpoint::Export),
+            other => Err(format!(
+                "unknown endpoint '{}' (expected get_meme, images, writes, shared or export)",
+                other
+            )),
+        }
+    }
+}
+
+/// Where a server listens: a TCP socket address or, with a `unix:` prefix, a Unix socket path.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ListenAddress {
+    Tcp(SocketAddr),
+    Unix(PathBuf),
+}
+
+impl FromStr for ListenAddress {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.strip_prefix("unix:") {
+            Some("") => Err("unix socket path is empty".to_string()),
+            Some(path) if cfg!(unix) => Ok(ListenAddress::Unix(PathBuf::from(path))),
+            Some(_) => Err("unix sockets are not supported on this platform".to_string()),
+            None => SocketAddr::from_str(s).map(ListenAddress::Tcp).map_err(|e| e.to_string()),
+        }
+    }
+}
+
+/// Serialized in the form it is configured in, e.g. `0.0.0.0:3000` or `unix:/run/memes.sock`.
+impl Serialize for ListenAddress {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            ListenAddress::Tcp(address) => serializer.collect_str(address),
+            ListenAddress::Unix(path) => serializer.collect_str(&format_args!("unix:{}", path.display())),
+        }
+    }
+}
+
+impl std::fmt::Display for ListenAddress {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ListenAddress::Tcp(address) => write!(f, "http://{}", address),
+            ListenAddress::Unix(path) => write!(f, "unix:{}", path.display()),
+        }
+    }
+}
+
+/// Layered lookup of raw configuration values.
+///
+/// Settings are named by their environment variable. Precedence, highest first:
+/// 1. The environment variable itself (including values from `.env`).
+/// 2. Values fetched from Secrets Manager, then SSM Parameter Store, when
+///    `APP_CONFIG_SOURCE=ssm` (see [`remote_config::fetch`]).
+/// 3. The TOML config file, where the key is the variable name lowercased with any
+///    `APP_` prefix removed (`APP_S3_BUCKET_NAME` -> `s3_bucket_name`). Nested tables are
+///    joined with `_`, so `[content_filter] mode = "mask"` sets `APP_CONTENT_FILTER_MODE`.
+/// 4. The built-in default, if any.
+#[derive(Debug, Default)]
+pub struct ConfigSource {
+    file_path: Option<PathBuf>,
+    file_values: HashMap<String, String>,
+    remote_values: HashMap<String, String>,
+}
+
+impl ConfigSource {
+    /// Reads the config file from `explicit_path`, else `APP_CONFIG_FILE`, else `./config.toml`
+    /// if it exists. An explicitly named file must exist.
+    pub fn new(explicit_path: Option<&Path>) -> Result<Self, ConfigError> {
+        let configured = explicit_path
+            .map(Path::to_path_buf)
+            .or_else(|| env::var_os("APP_CONFIG_FILE").map(PathBuf::from));
+        let path = match configured {
+            Some(path) => path,
+            None if Path::new(DEFAULT_CONFIG_FILE).is_file() => PathBuf::from(DEFAULT_CONFIG_FILE),
+            None => return Ok(Self::default()),
+        };
+
+        let contents = fs::read_to_string(&path)
+            .map_err(|e| ConfigError::FileError(path.display().to_string(), e.to_string()))?;
+        let table: toml::Table = contents
+            .parse()
+            .map_err(|e: toml::de::Error| ConfigError::FileError(path.display().to_string(), e.to_string()))?;
+
+        let mut file_values = HashMap::new();
+        flatten_table("", &table, &mut file_values);
+        Ok(Self { file_path: Some(path), file_values, ..Self::default() })
+    }
+
+    /// Builds a source from explicit settings keyed by environment variable name, layered
+    /// like config file values (so the environment still wins). Used by the test harness.
+    pub fn from_values<K: AsRef<str>>(values: impl IntoIterator<Item = (K, String)>) -> Self {
+        let file_values = values
+            .into_iter()
+            .map(|(key, value)| (file_key(key.as_ref()), value))
+            .collect();
+        Self { file_values, ..Self::default() }
+    }
+
+    pub fn file_path(&self) -> Option<&Path> {
+        self.file_path.as_deref()
+    }
+
+    /// Adds values fetched from a remote source, keyed like config file keys.
+    pub fn set_remote_values(&mut self, values: HashMap<String, String>) {
+        self.remote_values = values;
+    }
+
+    pub fn remote_value_count(&self) -> usize {
+        self.remote_values.len()
+    }
+
+    /// Returns the raw value for the setting named by environment variable `key`.
+    pub fn get(&self, key: &str) -> Option<String> {
+        env::var(key).ok().or_else(|| {
+            let key = file_key(key);
+            self.remote_values.get(&key).or_else(|| self.file_values.get(&key)).cloned()
         })
     }
+
+    /// Prefer AWS_REGION if set, fallback to AWS_DEFAULT_REGION, then to hardcoded default.
+    pub fn aws_region(&self) -> String {
+        self.get("AWS_REGION")
+            .or_else(|| self.get("AWS_DEFAULT_REGION"))
+            .unwrap_or_else(|| "ca-central-1".to_string()) // Default region
+    }
+
+    /// Like [`ConfigSource::get`], but a missing value is an error.
+    pub fn require(&self, key: &str) -> Result<String, ConfigError> {
+        self.get(key).ok_or_else(|| ConfigError::MissingVar(key.into()))
+    }
+
+    /// Parses an optional setting, falling back to `default` when unset.
+    pub fn parse_or<T>(&self, key: &str, default: T) -> Result<T, ConfigError>
+    where
+        T: FromStr,
+        T::Err: std::fmt::Display,
+    {
+        Ok(self.parse_optional(key)?.unwrap_or(default))
+    }
+
+    /// Parses an optional setting, returning `None` when unset.
+    pub fn parse_optional<T>(&self, key: &str) -> Result<Option<T>, ConfigError>
+    where
+        T: FromStr,
+        T::Err: std::fmt::Display,
+    {
+        self.get(key)
+            .map(|value| {
+                value
+                    .parse()
+                    .map_err(|e: T::Err| ConfigError::InvalidVar(key.into(), e.to_string()))
+            })
+            .transpose()
+    }
+}
+
+/// Config file read when neither a CLI flag nor `APP_CONFIG_FILE` names one.
+const DEFAULT_CONFIG_FILE: &str = "config.toml";
+
+/// Fields and headers redacted from logged bodies unless `APP_DEBUG_BODY_REDACT` is set.
+const DEFAULT_DEBUG_BODY_REDACT: &str =
+    "authorization,cookie,set-cookie,x-api-key,password,secret,token,access_token,refresh_token,share_token,admin_token,webhook_secret";
+/// Longest lifetime S3 accepts for presigned URLs.
+const MAX_PRESIGNED_URL_SECS: u64 = 7 * 24 * 60 * 60;
+/// Shortest accepted share link key; HMAC-SHA256 keys should carry at least 256 bits.
+const MIN_SHARE_SECRET_LEN: usize = 32;
+/// Most parallel scan segments accepted. DynamoDB allows a million, but each is a concurrent
+/// request, and far fewer already use up a table's read capacity.
+const MAX_SCAN_SEGMENTS: u32 = 1000;
+/// Most memes kept per trending window. All windows share one item of at most 400 KB, which
+/// this many memes per window leave room for at the default text limits.
+const MAX_TRENDING_LIMIT: usize = 50;
+
+/// Maps an environment variable name to its config file key.
+pub(crate) fn file_key(env_key: &str) -> String {
+    let lower = env_key.to_ascii_lowercase();
+    lower.strip_prefix("app_").map(str::to_string).unwrap_or(lower)
+}
+
+/// Flattens nested TOML tables into `parent_child` keys with string values.
+/// Arrays become comma-separated lists.
+fn flatten_table(prefix: &str, table: &toml::Table, out: &mut HashMap<String, String>) {
+    for (key, value) in table {
+        let full_key = if prefix.is_empty() { key.to_ascii_lowercase() } else { format!("{}_{}", prefix, key.to_ascii_lowercase()) };
+        match value {
+            toml::Value::Table(nested) => flatten_table(&full_key, nested, out),
+            toml::Value::Array(items) => {
+                let joined = items.iter().map(toml_scalar_to_string).collect::<Vec<_>>().join(",");
+                out.insert(full_key, joined);
+            }
+            scalar => {
+                out.insert(full_key, toml_scalar_to_string(scalar));
+            }
+        }
+    }
+}
+
+fn toml_scalar_to_string(value: &toml::Value) -> String {
+    match value {
+        toml::Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+/// Reads a comma-separated CORS list, checking every entry other than a lone `*`.
+fn cors_list(
+    source: &ConfigSource,
+    key: &str,
+    default: &str,
+    check: impl Fn(&str) -> Result<(), String>,
+) -> Result<Vec<String>, ConfigError> {
+    let values = split_list(&source.get(key).unwrap_or_else(|| default.to_string()));
+    if values.iter().any(|v| v == "*") {
+        if values.len() > 1 {
+            return Err(ConfigError::InvalidVar(key.into(), "'*' cannot be combined with other values".into()));
+        }
+        return Ok(values);
+    }
+    for value in &values {
+        check(value).map_err(|e| ConfigError::InvalidVar(key.into(), format!("'{}': {}", value, e)))?;
+    }
+    Ok(values)
+}
+
+/// Accepts `scheme://host[:port]` origins as sent by browsers in the `Origin` header.
+fn check_origin(origin: &str) -> Result<(), String> {
+    let url = url::Url::parse(origin).map_err(|e| e.to_string())?;
+    if !matches!(url.scheme(), "http" | "https") || url.host().is_none() {
+        return Err("expected an http(s) origin".into());
+    }
+    if url.path() != "/" || origin.ends_with('/') || url.query().is_some() {
+        return Err("origins must not include a path".into());
+    }
+    Ok(())
+}
+
+/// Accepts http(s) base URLs without query or fragment, e.g. `https://cdn.example.com/memes`
+/// (also the server's own `APP_PUBLIC_URL`).
+fn check_cdn_base_url(value: &str) -> Result<(), String> {
+    let url = url::Url::parse(value).map_err(|e| e.to_string())?;
+    if !matches!(url.scheme(), "http" | "https") || url.host().is_none() {
+        return Err("expected an http(s) URL".into());
+    }
+    if url.query().is_some() || url.fragment().is_some() {
+        return Err("must not have a query or fragment".into());
+    }
+    Ok(())
+}
+
+/// Reads a Discord ID (a "snowflake", a decimal 64-bit number).
+fn parse_snowflake(source: &ConfigSource, key: &str) -> Result<Option<u64>, ConfigError> {
+    source
+        .get(key)
+        .filter(|id| !id.is_empty())
+        .map(|id| id.parse().map_err(|_| ConfigError::InvalidVar(key.into(), format!("'{}' is not a Discord ID", id))))
+        .transpose()
+}
+
+/// Accepts absolute http(s) URLs (webhook targets, endpoint overrides).
+fn check_http_url(value: &str) -> Result<(), String> {
+    let url = url::Url::parse(value).map_err(|e| e.to_string())?;
+    if !matches!(url.scheme(), "http" | "https") || url.host().is_none() {
+        return Err("expected an http(s) URL".into());
+    }
+    Ok(())
+}
+
+/// Splits a comma-separated list, dropping empty entries.
+fn split_list(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(str::to_string)
+        .collect()
 }
 
 use tracing::info;
@@ -0,0 +1,160 @@
+use crate::{config::Config, domain::BlocklistRepository, errors::AppError};
+use regex::{Regex, RegexBuilder};
+use serde::Serialize;
+use std::str::FromStr;
+
+/// What to do when a title or description matches a blocked term.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FilterMode {
+    /// Filtering is disabled entirely.
+    Off,
+    /// Reject the submission with a validation error on the offending field.
+    Reject,
+    /// Accept the submission but replace each match with `*` characters.
+    Mask,
+}
+
+impl FromStr for FilterMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "off" => Ok(FilterMode::Off),
+            "reject" => Ok(FilterMode::Reject),
+            "mask" => Ok(FilterMode::Mask),
+            other => Err(format!("unknown filter mode '{}' (expected off, reject or mask)", other)),
+        }
+    }
+}
+
+/// Result of running text through the filter.
+#[derive(Debug, PartialEq, Eq)]
+pub enum FilterOutcome {
+    /// No blocked terms found (or filtering is off).
+    Clean,
+    /// Blocked terms were replaced; contains the masked text.
+    Masked(String),
+    /// Blocked terms were found and the mode is `Reject`.
+    Rejected,
+}
+
+/// Compiled keyword/regex blocklist applied to user-supplied text.
+///
+/// Terms are matched case-insensitively. Plain terms match whole words;
+/// terms prefixed with `re:` are used as raw regular expressions.
+#[derive(Debug, Clone)]
+pub struct ContentFilter {
+    mode: FilterMode,
+    matchers: Vec<Regex>,
+}
+
+impl ContentFilter {
+    /// Builds a filter from the statically configured terms plus terms stored by admins.
+    pub fn build<'a>(
+        mode: FilterMode,
+        terms: impl IntoIterator<Item = &'a str>,
+    ) -> Result<Self, regex::Error> {
+        let matchers = terms
+            .into_iter()
+            .map(str::trim)
+            .filter(|term| !term.is_empty())
+            .map(compile_term)
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self { mode, matchers })
+    }
+
+    /// Number of compiled terms, for logging.
+    pub fn term_count(&self) -> usize {
+        self.matchers.len()
+    }
+
+    /// Checks `text` against the blocklist according to the configured mode.
+    pub fn apply(&self, text: &str) -> FilterOutcome {
+        if self.mode == FilterMode::Off || !self.matchers.iter().any(|m| m.is_match(text)) {
+            return FilterOutcome::Clean;
+        }
+        match self.mode {
+            FilterMode::Reject => FilterOutcome::Rejected,
+            _ => {
+                let masked = self.matchers.iter().fold(text.to_string(), |acc, matcher| {
+                    matcher
+                        .replace_all(&acc, |caps: &regex::Captures| "*".repeat(caps[0].chars().count()))
+                        .into_owned()
+                });
+                FilterOutcome::Masked(masked)
+            }
+        }
+    }
+}
+
+impl Default for ContentFilter {
+    fn default() -> Self {
+        Self { mode: FilterMode::Off, matchers: Vec::new() }
+    }
+}
+
+/// Validates a single term without building a whole filter (used by the admin API).
+pub fn validate_term(term: &str) -> Result<(), regex::Error> {
+    compile_term(term.trim()).map(|_| ())
+}
+
+fn compile_term(term: &str) -> Result<Regex, regex::Error> {
+    let pattern = match term.strip_prefix("re:") {
+        Some(raw) => raw.to_string(),
+        None => format!(r"\b{}\b", regex::escape(term)),
+    };
+    RegexBuilder::new(&pattern).case_insensitive(true).build()
+}
+
+/// Loads stored terms and builds the active filter together with the configured terms.
+pub async fn load(
+    config: &Config,
+    blocklist_repo: &dyn BlocklistRepository,
+) -> Result<ContentFilter, AppError> {
+    let stored_terms = blocklist_repo.list_terms().await?;
+    let filter = ContentFilter::build(
+        config.content_filter_mode,
+        config.content_filter_terms.iter().chain(&stored_terms).map(String::as_str),
+    )
+    .map_err(|e| AppError::InternalServerError(format!("Failed to compile content filter: {}", e)))?;
+    tracing::info!(mode = ?config.content_filter_mode, terms = filter.term_count(), "Content filter loaded");
+    Ok(filter)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plain_terms_match_whole_words_without_regard_to_case() {
+        let filter = ContentFilter::build(FilterMode::Reject, ["heck", "  ", "a.b"]).unwrap();
+        assert_eq!(filter.term_count(), 2);
+        assert_eq!(filter.apply("What the HECK"), FilterOutcome::Rejected);
+        assert_eq!(filter.apply("heckling"), FilterOutcome::Clean);
+        // Regex characters in plain terms are literal
+        assert_eq!(filter.apply("aXb"), FilterOutcome::Clean);
+        assert_eq!(filter.apply("see a.b here"), FilterOutcome::Rejected);
+    }
+
+    #[test]
+    fn masks_every_match_with_as_many_stars() {
+        let filter = ContentFilter::build(FilterMode::Mask, ["heck", "re:d[a-z]+n"]).unwrap();
+        assert_eq!(filter.apply("Heck, darn, heck"), FilterOutcome::Masked("****, ****, ****".to_string()));
+        assert_eq!(filter.apply("fine"), FilterOutcome::Clean);
+    }
+
+    #[test]
+    fn off_mode_lets_everything_through() {
+        let filter = ContentFilter::build(FilterMode::Off, ["heck"]).unwrap();
+        assert_eq!(filter.apply("heck"), FilterOutcome::Clean);
+    }
+
+    #[test]
+    fn parses_modes_and_validates_terms() {
+        assert_eq!("MASK".parse::<FilterMode>(), Ok(FilterMode::Mask));
+        assert!("block".parse::<FilterMode>().is_err());
+        assert!(validate_term("re:(unclosed").is_err());
+        assert!(validate_term("(plain is escaped").is_ok());
+    }
+}
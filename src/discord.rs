@@ -0,0 +1,464 @@
+//! Discord bot (only with the `discord` feature), run by the server over Discord's HTTP APIs
+//! rather than a gateway connection:
+//!
+//! - Memes are posted to `APP_DISCORD_CHANNEL_ID` as they become publicly listed (see
+//!   [`crate::announcements`]).
+//! - `/meme upload` is a slash command whose interactions Discord POSTs to
+//!   `/discord/interactions` (the application's Interactions Endpoint URL). The attached
+//!   image becomes a meme through [`services::create_meme`], held to the same validation,
+//!   filtering and quotas as uploads through the API.
+//!
+//! The command is registered for the application at startup when
+//! `APP_DISCORD_APPLICATION_ID` is set.
+//!
+//! This is not built on serenity: its bots live on a gateway connection, one long-lived
+//! websocket per bot, which every instance would hold (and have Discord deliver each event
+//! to) and which nothing holds on Lambda. Interactions arrive as ordinary requests any
+//! instance answers, and the two REST calls the bot makes need no more than reqwest.
+
+use crate::{
+    announcements::{has_absolute_image_url, truncate, MemeAnnouncer},
+    config::Config,
+    errors::AppError,
+    handlers,
+    models::Meme,
+    services::{self, Caller, ImageInput, ImageUpload},
+    validation::{self, MemeSubmission},
+    AppState,
+};
+use anyhow::{bail, Context};
+use async_trait::async_trait;
+use axum::{
+    body::Bytes,
+    extract::State,
+    http::HeaderMap,
+    Json,
+};
+use chrono::Utc;
+use reqwest::{header, redirect, Client, RequestBuilder};
+use ring::signature::{UnparsedPublicKey, ED25519};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+const API_URL: &str = "https://discord.com/api/v10";
+/// Per-request limit for calls to Discord.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+const SIGNATURE_HEADER: &str = "x-signature-ed25519";
+const TIMESTAMP_HEADER: &str = "x-signature-timestamp";
+/// How old a signed interaction may be; older ones are refused as possible replays.
+const MAX_REQUEST_AGE_SECS: i64 = 5 * 60;
+
+// Interaction and response types, see
+// https://discord.com/developers/docs/interactions/receiving-and-responding
+const PING: u8 = 1;
+const APPLICATION_COMMAND: u8 = 2;
+const PONG: u8 = 1;
+const CHANNEL_MESSAGE: u8 = 4;
+const DEFERRED_CHANNEL_MESSAGE: u8 = 5;
+/// Message flag showing a reply to the invoking user only.
+const EPHEMERAL: u32 = 1 << 6;
+
+// Limits of embed fields
+const MAX_EMBED_TITLE: usize = 256;
+const MAX_EMBED_DESCRIPTION: usize = 4096;
+
+/// The bot's side of Discord: its credentials and an HTTP client for Discord's REST API.
+pub struct DiscordBot {
+    http: Client,
+    token: Option<String>,
+    application_id: Option<u64>,
+    public_key: Option<Vec<u8>>,
+    channel_id: Option<u64>,
+}
+
+impl DiscordBot {
+    /// The bot of `config`; `None` without any `APP_DISCORD_*` settings.
+    pub fn from_config(config: &Config) -> Result<Option<Self>, AppError> {
+        if config.discord_bot_token.is_none() && config.discord_public_key.is_none() {
+            return Ok(None);
+        }
+        let http = Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .redirect(redirect::Policy::none())
+            .build()
+            .map_err(|e| AppError::InitError(format!("Failed to build Discord HTTP client: {}", e)))?;
+        Ok(Some(Self {
+            http,
+            token: config.discord_bot_token.clone(),
+            application_id: config.discord_application_id,
+            // Checked when the configuration was loaded
+            public_key: config.discord_public_key.as_deref().map(|key| hex::decode(key).expect("hex public key")),
+            channel_id: config.discord_channel_id,
+        }))
+    }
+
+    /// Whether `/discord/interactions` is served: only with the key to verify them.
+    pub fn accepts_interactions(&self) -> bool {
+        self.public_key.is_some()
+    }
+
+    /// Whether new memes are posted to a channel.
+    pub fn posts_memes(&self) -> bool {
+        self.channel_id.is_some()
+    }
+
+    /// Registers the `/meme` command for the application, replacing its earlier commands.
+    /// Failures are logged; the commands registered before stay in place.
+    pub async fn register_commands(&self) {
+        let (Some(token), Some(application_id)) = (&self.token, self.application_id) else {
+            return;
+        };
+        let commands = json!([{
+            "name": "meme",
+            "description": "Meme posting",
+            "options": [{
+                "type": 1, // SUB_COMMAND
+                "name": "upload",
+                "description": "Upload an image as a meme",
+                "options": [
+                    { "type": 11, "name": "image", "description": "The meme's image", "required": true }, // ATTACHMENT
+                    { "type": 3, "name": "title", "description": "Title of the meme", "required": true }, // STRING
+                    { "type": 3, "name": "description", "description": "What the meme is about", "required": true },
+                    { "type": 3, "name": "tags", "description": "Comma-separated tags" },
+                ],
+            }],
+        }]);
+        let request = self
+            .http
+            .put(format!("{}/applications/{}/commands", API_URL, application_id))
+            .header(header::AUTHORIZATION, format!("Bot {}", token))
+            .json(&commands);
+        match send(request).await {
+            Ok(()) => tracing::info!(application_id, "Registered the Discord /meme command"),
+            Err(e) => tracing::warn!(application_id, error = %e, "Failed to register the Discord /meme command"),
+        }
+    }
+
+    /// Checks that Discord signed `body` with the application's key within the last few
+    /// minutes.
+    fn verify(&self, headers: &HeaderMap, body: &[u8]) -> Result<(), AppError> {
+        let invalid = || AppError::Unauthorized("Invalid interaction signature".to_string());
+        let public_key = self.public_key.as_ref().ok_or_else(invalid)?;
+        let signature = headers.get(SIGNATURE_HEADER).and_then(|value| hex::decode(value.as_bytes()).ok()).ok_or_else(invalid)?;
+        let timestamp = headers.get(TIMESTAMP_HEADER).ok_or_else(invalid)?;
+        let sent_at: i64 = timestamp.to_str().ok().and_then(|value| value.parse().ok()).ok_or_else(invalid)?;
+        if (Utc::now().timestamp() - sent_at).abs() > MAX_REQUEST_AGE_SECS {
+            return Err(invalid());
+        }
+        let message = [timestamp.as_bytes(), body].concat();
+        UnparsedPublicKey::new(&ED25519, public_key).verify(&message, &signature).map_err(|_| invalid())
+    }
+
+    /// Replaces the deferred reply to an interaction with `content`.
+    async fn edit_reply(&self, interaction: &Interaction, content: String) -> anyhow::Result<()> {
+        let request = self
+            .http
+            .patch(format!("{}/webhooks/{}/{}/messages/@original", API_URL, interaction.application_id, interaction.token))
+            .json(&json!({ "content": content }));
+        send(request).await
+    }
+}
+
+#[async_trait]
+impl MemeAnnouncer for DiscordBot {
+    fn name(&self) -> &'static str {
+        "discord"
+    }
+
+    /// Posts `meme` to the configured channel.
+    async fn announce(&self, state: &AppState, meme: Meme) -> anyhow::Result<()> {
+        let (Some(token), Some(channel_id)) = (&self.token, self.channel_id) else {
+            return Ok(());
+        };
+        let view = handlers::meme_view(state, meme).await?;
+        let mut embed = json!({
+            "title": truncate(&view.meme.title, MAX_EMBED_TITLE),
+            "description": truncate(&view.meme.description, MAX_EMBED_DESCRIPTION),
+            "timestamp": view.meme.created_at,
+        });
+        if has_absolute_image_url(&view) {
+            embed["image"] = json!({ "url": view.image_url });
+        }
+        if !view.meme.tags.is_empty() {
+            embed["footer"] = json!({ "text": view.meme.tags.iter().map(|tag| format!("#{}", tag)).collect::<Vec<_>>().join(" ") });
+        }
+        let request = self
+            .http
+            .post(format!("{}/channels/{}/messages", API_URL, channel_id))
+            .header(header::AUTHORIZATION, format!("Bot {}", token))
+            .json(&json!({ "embeds": [embed] }));
+        send(request).await
+    }
+}
+
+/// An interaction as Discord POSTs it; only the fields the bot reads.
+#[derive(Deserialize, Debug)]
+struct Interaction {
+    #[serde(rename = "type")]
+    kind: u8,
+    application_id: String,
+    token: String,
+    data: Option<CommandData>,
+}
+
+#[derive(Deserialize, Debug)]
+struct CommandData {
+    name: String,
+    #[serde(default)]
+    options: Vec<CommandOption>,
+    #[serde(default)]
+    resolved: Resolved,
+}
+
+impl CommandData {
+    /// The options given to `/meme upload`; `None` for any other command.
+    fn upload_options(&self) -> Option<&[CommandOption]> {
+        if self.name != "meme" {
+            return None;
+        }
+        self.options.iter().find(|option| option.name == "upload").map(|upload| upload.options.as_slice())
+    }
+
+    /// The attachment an `image` option refers to.
+    fn attachment(&self, options: &[CommandOption]) -> Option<&Attachment> {
+        text_option(options, "image").and_then(|id| self.resolved.attachments.get(&id))
+    }
+}
+
+/// The value of the string option `name`.
+fn text_option(options: &[CommandOption], name: &str) -> Option<String> {
+    options
+        .iter()
+        .find(|option| option.name == name)
+        .and_then(|option| option.value.as_ref())
+        .and_then(Value::as_str)
+        .map(str::to_string)
+}
+
+/// The meme metadata given in the options of `/meme upload`.
+fn submission(options: &[CommandOption]) -> MemeSubmission {
+    MemeSubmission {
+        title: text_option(options, "title"),
+        description: text_option(options, "description"),
+        tags: text_option(options, "tags").map(|tags| validation::split_tags(&tags).collect()).unwrap_or_default(),
+        ..MemeSubmission::default()
+    }
+}
+
+/// An option of a command: a subcommand with its own options, or a value.
+#[derive(Deserialize, Debug)]
+struct CommandOption {
+    name: String,
+    value: Option<Value>,
+    #[serde(default)]
+    options: Vec<CommandOption>,
+}
+
+/// Objects the options refer to by ID.
+#[derive(Deserialize, Debug, Default)]
+struct Resolved {
+    #[serde(default)]
+    attachments: HashMap<String, Attachment>,
+}
+
+#[derive(Deserialize, Debug)]
+struct Attachment {
+    url: String,
+    filename: String,
+}
+
+/// Handler for POST /discord/interactions. Answers Discord's pings and takes `/meme upload`
+/// commands: the reply is deferred, and edited once the upload is stored or rejected, since
+/// Discord waits only three seconds for an answer.
+pub async fn handle_interaction(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Json<Value>, AppError> {
+    let bot = state.discord.clone().expect("route is only served with a Discord bot");
+    bot.verify(&headers, &body)?;
+    let interaction: Interaction =
+        serde_json::from_slice(&body).map_err(|e| AppError::InvalidInput(format!("Invalid interaction: {}", e)))?;
+
+    match interaction.kind {
+        PING => Ok(Json(json!({ "type": PONG }))),
+        APPLICATION_COMMAND => {
+            if interaction.data.as_ref().and_then(CommandData::upload_options).is_none() {
+                return Ok(reply("Unknown command."));
+            }
+            state.in_flight.clone().spawn(async move {
+                let content = match upload_attachment(&state, &interaction).await {
+                    Ok(meme) => format!("Uploaded **{}** (`{}`).", meme.title, meme.meme_id),
+                    Err(e) => failure_message(&e),
+                };
+                if let Err(e) = bot.edit_reply(&interaction, content).await {
+                    tracing::warn!(error = %e, "Failed to answer a Discord upload");
+                }
+            });
+            Ok(Json(json!({ "type": DEFERRED_CHANNEL_MESSAGE, "data": { "flags": EPHEMERAL } })))
+        }
+        other => {
+            tracing::debug!(interaction_type = other, "Ignoring Discord interaction");
+            Ok(reply("This interaction is not supported."))
+        }
+    }
+}
+
+/// Creates a meme from the attachment and text options of a `/meme upload` command.
+async fn upload_attachment(state: &AppState, interaction: &Interaction) -> Result<Meme, AppError> {
+    let data = interaction.data.as_ref().expect("commands carry data");
+    let options = data.upload_options().expect("an upload command");
+    let image = match data.attachment(options) {
+        None => ImageInput::Missing,
+        Some(attachment) => match state.url_fetcher.fetch(&attachment.url).await {
+            Ok(fetched) => ImageInput::Provided(ImageUpload {
+                data: Bytes::from(fetched.data),
+                filename: Some(attachment.filename.clone()),
+                content_type: Some(fetched.content_type),
+                // Attachment URLs are signed and expire, so they are no use as attribution
+                source_url: None,
+            }),
+            Err(e) => ImageInput::Invalid { field: "image", message: e.to_string() },
+        },
+    };
+    services::create_meme(state, submission(options), image, &Caller::discord()).await
+}
+
+/// What to tell the user about a rejected or failed upload.
+fn failure_message(error: &AppError) -> String {
+    match error {
+        AppError::ValidationFailed(errors) => {
+            let failures: Vec<String> =
+                errors.iter().map(|(field, messages)| format!("{} {}", field, messages.join(", "))).collect();
+            format!("The meme was not uploaded: {}.", failures.join("; "))
+        }
+        AppError::InvalidInput(message) | AppError::PayloadTooLarge(message) | AppError::QuotaExceeded(message) => {
+            format!("The meme was not uploaded: {}.", message)
+        }
+        other => {
+            tracing::warn!(error = %other, "Discord upload failed");
+            "The meme could not be stored, please try again later.".to_string()
+        }
+    }
+}
+
+/// An immediate reply shown only to the user who ran the command.
+fn reply(content: &str) -> Json<Value> {
+    Json(json!({ "type": CHANNEL_MESSAGE, "data": { "content": content, "flags": EPHEMERAL } }))
+}
+
+/// Sends a request to Discord, failing on error statuses with Discord's explanation.
+async fn send(request: RequestBuilder) -> anyhow::Result<()> {
+    let response = request.send().await.context("Discord is unreachable")?;
+    let status = response.status();
+    if !status.is_success() {
+        bail!("Discord responded with {}: {}", status, response.text().await.unwrap_or_default());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+    use ring::signature::{Ed25519KeyPair, KeyPair};
+
+    fn bot_with_key(public_key: Option<Vec<u8>>) -> DiscordBot {
+        DiscordBot { http: Client::new(), token: None, application_id: None, public_key, channel_id: None }
+    }
+
+    fn signed_headers(key_pair: &Ed25519KeyPair, timestamp: &str, body: &[u8]) -> HeaderMap {
+        let signature = key_pair.sign(&[timestamp.as_bytes(), body].concat());
+        let mut headers = HeaderMap::new();
+        headers.insert(SIGNATURE_HEADER, HeaderValue::from_str(&hex::encode(signature.as_ref())).unwrap());
+        headers.insert(TIMESTAMP_HEADER, HeaderValue::from_str(timestamp).unwrap());
+        headers
+    }
+
+    #[test]
+    fn interactions_signed_with_the_application_key_verify() {
+        let key_pair = Ed25519KeyPair::from_seed_unchecked(&[7; 32]).unwrap();
+        let bot = bot_with_key(Some(key_pair.public_key().as_ref().to_vec()));
+        let body = br#"{"type":1}"#;
+        let now = Utc::now().timestamp();
+        let headers = signed_headers(&key_pair, &now.to_string(), body);
+        assert!(bot.verify(&headers, body).is_ok());
+
+        assert!(bot.verify(&headers, br#"{"type":2}"#).is_err());
+        let mut replayed = headers.clone();
+        replayed.insert(TIMESTAMP_HEADER, HeaderValue::from_str(&(now + 1).to_string()).unwrap());
+        assert!(bot.verify(&replayed, body).is_err());
+        let mut unsigned = headers.clone();
+        unsigned.remove(SIGNATURE_HEADER);
+        assert!(bot.verify(&unsigned, body).is_err());
+        let mut garbled = headers.clone();
+        garbled.insert(SIGNATURE_HEADER, HeaderValue::from_static("not hex"));
+        assert!(bot.verify(&garbled, body).is_err());
+
+        let other = Ed25519KeyPair::from_seed_unchecked(&[8; 32]).unwrap();
+        assert!(bot.verify(&signed_headers(&other, &now.to_string(), body), body).is_err());
+        assert!(bot_with_key(None).verify(&headers, body).is_err());
+    }
+
+    #[test]
+    fn stale_interactions_are_refused() {
+        let key_pair = Ed25519KeyPair::from_seed_unchecked(&[7; 32]).unwrap();
+        let bot = bot_with_key(Some(key_pair.public_key().as_ref().to_vec()));
+        let body = br#"{"type":1}"#;
+        let now = Utc::now().timestamp();
+        assert!(bot.verify(&signed_headers(&key_pair, &(now - MAX_REQUEST_AGE_SECS + 5).to_string(), body), body).is_ok());
+        for age in [MAX_REQUEST_AGE_SECS + 1, -(MAX_REQUEST_AGE_SECS + 1)] {
+            let headers = signed_headers(&key_pair, &(now - age).to_string(), body);
+            assert!(matches!(bot.verify(&headers, body), Err(AppError::Unauthorized(_))), "{} seconds old", age);
+        }
+        assert!(bot.verify(&signed_headers(&key_pair, "yesterday", body), body).is_err());
+    }
+
+    #[test]
+    fn upload_commands_are_parsed_with_their_attachment() {
+        let interaction: Interaction = serde_json::from_value(json!({
+            "type": APPLICATION_COMMAND,
+            "application_id": "123",
+            "token": "interaction-token",
+            "data": {
+                "name": "meme",
+                "options": [{
+                    "name": "upload",
+                    "type": 1,
+                    "options": [
+                        { "name": "image", "type": 11, "value": "900" },
+                        { "name": "title", "type": 3, "value": "Cat" },
+                        { "name": "description", "type": 3, "value": "A cat" },
+                        { "name": "tags", "type": 3, "value": "Cats, funny" },
+                    ],
+                }],
+                "resolved": { "attachments": { "900": { "id": "900", "url": "https://cdn.discordapp.com/cat.png", "filename": "cat.png" } } },
+            },
+        }))
+        .unwrap();
+        let data = interaction.data.as_ref().unwrap();
+        let options = data.upload_options().unwrap();
+        let submission = submission(options);
+        assert_eq!(submission.title.as_deref(), Some("Cat"));
+        assert_eq!(submission.description.as_deref(), Some("A cat"));
+        assert_eq!(submission.tags, ["Cats", "funny"]);
+        let attachment = data.attachment(options).unwrap();
+        assert_eq!((attachment.url.as_str(), attachment.filename.as_str()), ("https://cdn.discordapp.com/cat.png", "cat.png"));
+    }
+
+    #[test]
+    fn other_commands_and_missing_options_are_not_uploads() {
+        let data: CommandData = serde_json::from_value(json!({ "name": "meme", "options": [{ "name": "random" }] })).unwrap();
+        assert!(data.upload_options().is_none());
+        let data: CommandData = serde_json::from_value(json!({ "name": "other", "options": [{ "name": "upload" }] })).unwrap();
+        assert!(data.upload_options().is_none());
+
+        // An upload without options: nothing to submit, and no image
+        let data: CommandData = serde_json::from_value(json!({ "name": "meme", "options": [{ "name": "upload" }] })).unwrap();
+        let options = data.upload_options().unwrap();
+        assert!(data.attachment(options).is_none());
+        let submission = submission(options);
+        assert!(submission.title.is_none() && submission.description.is_none() && submission.tags.is_empty());
+    }
+}
@@ -1,24 +1,529 @@
 use crate::errors::{RepoError, StorageError};
-use crate::models::Meme;
+use crate::models::{Meme, SortOrder, TrendingWindow};
 use async_trait::async_trait;
+use axum::body::Bytes;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::net::IpAddr;
+use std::time::Duration;
 use uuid::Uuid;
 use aws_sdk_s3::primitives::ByteStream;
 
+/// What the backend reports about a table, for operators.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TableInfo {
+    pub name: String,
+    pub status: String,
+    /// Approximate; DynamoDB refreshes it about every six hours.
+    pub item_count: Option<u64>,
+    pub size_bytes: Option<u64>,
+    /// Status of each global secondary index, by name.
+    pub indexes: BTreeMap<String, String>,
+}
+
+/// How up to date a read must be.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ConsistencyLevel {
+    /// May miss writes made in about the last second; half the cost of a strong read on
+    /// DynamoDB.
+    #[default]
+    Eventual,
+    /// Sees every write acknowledged before the read started (DynamoDB `ConsistentRead`).
+    Strong,
+}
+
 #[async_trait]
 pub trait MemeRepository: Send + Sync + 'static {
+    /// Stores a new meme. Fails with `RepoError::AlreadyExists` instead of overwriting a meme
+    /// with the same ID.
     async fn create(&self, meme: &Meme) -> Result<(), RepoError>;
-    async fn get_by_id(&self, id: Uuid) -> Result<Option<Meme>, RepoError>;
+    /// Fetches a meme by ID. Expired memes are reported as missing. Backends that only
+    /// offer strong reads ignore `consistency`.
+    async fn get_by_id(&self, id: Uuid, consistency: ConsistencyLevel) -> Result<Option<Meme>, RepoError>;
+    /// Whether a meme with this ID is stored and has not expired, without reading the meme.
+    async fn exists(&self, id: Uuid) -> Result<bool, RepoError>;
+    /// Lists all memes that have not expired.
     async fn list_all(&self) -> Result<Vec<Meme>, RepoError>;
+    /// Lists memes that have not expired in `order`. Memes stored before the order's sort
+    /// attribute was recorded are left out.
+    async fn list_sorted(&self, order: SortOrder) -> Result<Vec<Meme>, RepoError>;
+    /// Lists memes whose expiry time is at or before `now` but that are still stored.
+    async fn list_expired(&self, now: DateTime<Utc>) -> Result<Vec<Meme>, RepoError>;
+    /// Lists memes whose publish time is at or before `now` but that are not flagged as
+    /// published yet.
+    async fn list_due_for_publishing(&self, now: DateTime<Utc>) -> Result<Vec<Meme>, RepoError>;
+    /// Replaces a meme's metadata if its stored version is still `expected_version`.
+    /// Fails with `RepoError::NotFound` if the meme is gone and with
+    /// `RepoError::VersionConflict` if another write got there first.
+    async fn update(&self, meme: &Meme, expected_version: u64) -> Result<(), RepoError>;
+    /// Atomically adds one like and returns the new count, without changing the version.
+    /// Fails with `RepoError::NotFound` if the meme is gone or expired.
+    async fn add_like(&self, id: Uuid) -> Result<u64, RepoError>;
+    /// Atomically adds `views` to the view count, without changing the version.
+    /// Fails with `RepoError::NotFound` if the meme is gone or expired.
+    async fn add_views(&self, id: Uuid, views: u64) -> Result<(), RepoError>;
     /// Deletes a meme's metadata by its unique ID.
     /// Should typically succeed even if the item doesn't exist, unless there's a backend error.
     async fn delete(&self, id: Uuid) -> Result<(), RepoError>;
+    /// Describes the backing table (status, approximate size), for operators.
+    async fn describe(&self) -> Result<TableInfo, RepoError>;
+    /// Counts memes created at or after `since`, expired ones included. Memes stored before
+    /// upload times were recorded are not counted.
+    async fn count_created_since(&self, since: DateTime<Utc>) -> Result<u64, RepoError>;
+    /// Counts the memes that have not expired, of every visibility, without reading them.
+    async fn count(&self) -> Result<u64, RepoError>;
+    /// Checks that the table (or collection) is reachable, for `/health`. Decorators pass it
+    /// straight through, like [`FileStorage::ping`].
+    async fn ping(&self) -> Result<(), RepoError>;
+}
+
+/// Lets the repository chosen at runtime ([`crate::backends::meme_repository`])
+/// go through the same generic decorators as a concrete backend.
+#[async_trait]
+impl MemeRepository for Box<dyn MemeRepository> {
+    async fn create(&self, meme: &Meme) -> Result<(), RepoError> {
+        (**self).create(meme).await
+    }
+
+    async fn get_by_id(&self, id: Uuid, consistency: ConsistencyLevel) -> Result<Option<Meme>, RepoError> {
+        (**self).get_by_id(id, consistency).await
+    }
+
+    async fn exists(&self, id: Uuid) -> Result<bool, RepoError> {
+        (**self).exists(id).await
+    }
+
+    async fn list_all(&self) -> Result<Vec<Meme>, RepoError> {
+        (**self).list_all().await
+    }
+
+    async fn list_sorted(&self, order: SortOrder) -> Result<Vec<Meme>, RepoError> {
+        (**self).list_sorted(order).await
+    }
+
+    async fn list_expired(&self, now: DateTime<Utc>) -> Result<Vec<Meme>, RepoError> {
+        (**self).list_expired(now).await
+    }
+
+    async fn list_due_for_publishing(&self, now: DateTime<Utc>) -> Result<Vec<Meme>, RepoError> {
+        (**self).list_due_for_publishing(now).await
+    }
+
+    async fn update(&self, meme: &Meme, expected_version: u64) -> Result<(), RepoError> {
+        (**self).update(meme, expected_version).await
+    }
+
+    async fn add_like(&self, id: Uuid) -> Result<u64, RepoError> {
+        (**self).add_like(id).await
+    }
+
+    async fn add_views(&self, id: Uuid, views: u64) -> Result<(), RepoError> {
+        (**self).add_views(id, views).await
+    }
+
+    async fn delete(&self, id: Uuid) -> Result<(), RepoError> {
+        (**self).delete(id).await
+    }
+
+    async fn describe(&self) -> Result<TableInfo, RepoError> {
+        (**self).describe().await
+    }
+
+    async fn count_created_since(&self, since: DateTime<Utc>) -> Result<u64, RepoError> {
+        (**self).count_created_since(since).await
+    }
+
+    async fn count(&self) -> Result<u64, RepoError> {
+        (**self).count().await
+    }
+
+    async fn ping(&self) -> Result<(), RepoError> {
+        (**self).ping().await
+    }
+}
+
+/// Persistent store for admin-managed content filter terms.
+#[async_trait]
+pub trait BlocklistRepository: Send + Sync + 'static {
+    async fn list_terms(&self) -> Result<Vec<String>, RepoError>;
+    /// Adds a term. Adding an existing term is a no-op.
+    async fn add_term(&self, term: &str) -> Result<(), RepoError>;
+    /// Removes a term. Removing a missing term is a no-op.
+    async fn remove_term(&self, term: &str) -> Result<(), RepoError>;
+}
+
+#[async_trait]
+impl BlocklistRepository for Box<dyn BlocklistRepository> {
+    async fn list_terms(&self) -> Result<Vec<String>, RepoError> {
+        (**self).list_terms().await
+    }
+
+    async fn add_term(&self, term: &str) -> Result<(), RepoError> {
+        (**self).add_term(term).await
+    }
+
+    async fn remove_term(&self, term: &str) -> Result<(), RepoError> {
+        (**self).remove_term(term).await
+    }
+}
+
+/// Settings a tenant has changed from the deployment's. Unset fields keep the deployment value.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TenantOverrides {
+    pub max_upload_bytes: Option<usize>,
+    pub max_uploads_per_day: Option<u64>,
+    /// Replaces the deployment's list; an empty list allows any image type.
+    pub allowed_image_types: Option<Vec<String>>,
+}
+
+/// Persistent store for per-tenant setting overrides.
+#[async_trait]
+pub trait TenantConfigRepository: Send + Sync + 'static {
+    /// The tenant's overrides; `None` if it has none stored.
+    async fn get_overrides(&self, tenant: &str) -> Result<Option<TenantOverrides>, RepoError>;
+    /// Replaces the tenant's overrides.
+    async fn put_overrides(&self, tenant: &str, overrides: &TenantOverrides) -> Result<(), RepoError>;
+}
+
+/// Earlier versions of memes' metadata, kept when a meme is updated.
+#[async_trait]
+pub trait MemeHistoryRepository: Send + Sync + 'static {
+    /// Keeps `meme` as it is at its version. Saving a version again replaces it; versions
+    /// are never reused, so the content is the same.
+    async fn save_version(&self, meme: &Meme) -> Result<(), RepoError>;
+    /// The meme's kept versions, newest first.
+    async fn list_versions(&self, id: Uuid) -> Result<Vec<Meme>, RepoError>;
+    /// One kept version, if there is one.
+    async fn get_version(&self, id: Uuid, version: u64) -> Result<Option<Meme>, RepoError>;
+    /// Removes every kept version, once the meme itself is deleted.
+    async fn delete_versions(&self, id: Uuid) -> Result<(), RepoError>;
+}
+
+/// Kind of change recorded in the audit log.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AuditAction {
+    Created,
+    Updated,
+    Deleted,
+}
+
+/// A field's value before and after a change; absent on the side where the meme did not exist.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FieldChange {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub before: Option<serde_json::Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub after: Option<serde_json::Value>,
+}
+
+/// One change to a meme, as kept in the audit log.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub id: Uuid,
+    pub recorded_at: DateTime<Utc>,
+    pub action: AuditAction,
+    pub meme_id: Uuid,
+    /// Who made the change: `owner`, `anonymous` or `seed` (see [`crate::services::Actor`]).
+    pub actor: String,
+    /// `X-Request-Id` of the request that made the change.
+    pub request_id: Option<String>,
+    /// Address of the client that made the change (see [`crate::client_ip`]); not recorded
+    /// for entries written before it was.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_ip: Option<IpAddr>,
+    /// The fields that changed, by name.
+    pub changes: BTreeMap<String, FieldChange>,
+}
+
+/// Append-only store for the audit log.
+#[async_trait]
+pub trait AuditRepository: Send + Sync + 'static {
+    /// Appends an entry. Entries are never changed or removed.
+    async fn append(&self, entry: &AuditEntry) -> Result<(), RepoError>;
+    /// Lists up to `limit` entries recorded at or after `since`, oldest first.
+    async fn list_since(&self, since: DateTime<Utc>, limit: usize) -> Result<Vec<AuditEntry>, RepoError>;
+}
+
+/// A schema migration applied to a deployment's tables (see [`crate::migrations`]).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AppliedMigration {
+    pub id: String,
+    pub applied_at: DateTime<Utc>,
+}
+
+/// Record of the schema migrations applied, so each runs once.
+#[async_trait]
+pub trait MigrationRepository: Send + Sync + 'static {
+    /// The migrations applied so far, in no particular order.
+    async fn list_applied(&self) -> Result<Vec<AppliedMigration>, RepoError>;
+    /// Records a migration as applied; recording it again only updates the time.
+    async fn record(&self, migration: &AppliedMigration) -> Result<(), RepoError>;
+}
+
+/// Where a backfill run stands.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BackfillState {
+    Running,
+    /// Stopped before the end, by a shutdown or an error; the next run resumes it.
+    Interrupted,
+    Completed,
+}
+
+/// Progress of a backfill of one meme attribute (see [`crate::backfill`]), saved as it
+/// goes so a run picks up after the last meme it got to.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BackfillProgress {
+    /// The attribute filled in, e.g. `content_hash`.
+    pub field: String,
+    pub state: BackfillState,
+    pub started_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    /// Memes are visited in ID order; the next run starts after this one.
+    pub last_meme_id: Option<Uuid>,
+    /// Memes the run started with, so `scanned` tells how far along it is.
+    pub total: u64,
+    pub scanned: u64,
+    pub updated: u64,
+    /// Memes whose value could not be worked out or saved; they are not retried.
+    pub failed: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Where backfill progress is kept, one record per attribute.
+#[async_trait]
+pub trait BackfillRepository: Send + Sync + 'static {
+    async fn load(&self, field: &str) -> Result<Option<BackfillProgress>, RepoError>;
+    /// Every backfill's latest progress, in field order.
+    async fn list(&self) -> Result<Vec<BackfillProgress>, RepoError>;
+    async fn save(&self, progress: &BackfillProgress) -> Result<(), RepoError>;
+}
+
+/// A meme in a trending ranking, as it was when the ranking was computed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrendingMeme {
+    pub score: f64,
+    pub meme: Meme,
+}
+
+/// The rankings computed by the trending job, best first in every window.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrendingSnapshot {
+    pub computed_at: DateTime<Utc>,
+    pub rankings: BTreeMap<TrendingWindow, Vec<TrendingMeme>>,
+}
+
+/// Where the latest trending rankings are kept, as one item, so serving them takes a
+/// single read.
+#[async_trait]
+pub trait TrendingRepository: Send + Sync + 'static {
+    /// The latest rankings; `None` before the job first ran.
+    async fn load(&self) -> Result<Option<TrendingSnapshot>, RepoError>;
+    /// Replaces the rankings.
+    async fn save(&self, snapshot: &TrendingSnapshot) -> Result<(), RepoError>;
+}
+
+/// What a shortlink redirects to.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ShortlinkTarget {
+    /// The meme, `GET /meme/{id}`.
+    #[default]
+    Page,
+    /// The meme's image, at the URL the API would hand out at the time of the redirect.
+    Image,
+}
+
+impl ShortlinkTarget {
+    /// Name as serialized and stored, e.g. `"image"`.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ShortlinkTarget::Page => "page",
+            ShortlinkTarget::Image => "image",
+        }
+    }
+}
+
+/// A short code that redirects to a meme (see [`crate::shortlinks`]).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Shortlink {
+    pub code: String,
+    pub meme_id: Uuid,
+    pub target: ShortlinkTarget,
+    pub created_at: DateTime<Utc>,
+    /// Redirects served so far.
+    pub hits: u64,
+}
+
+/// Where shortlinks are kept, one record per code.
+#[async_trait]
+pub trait ShortlinkRepository: Send + Sync + 'static {
+    /// Stores a new shortlink. Returns `false`, storing nothing, when its code is taken.
+    async fn create(&self, link: &Shortlink) -> Result<bool, RepoError>;
+    async fn get(&self, code: &str) -> Result<Option<Shortlink>, RepoError>;
+    /// Counts a redirect, atomically; counting a missing code is not an error.
+    async fn add_hit(&self, code: &str) -> Result<(), RepoError>;
+}
+
+/// A fediverse account following the instance's ActivityPub actor (see
+/// [`crate::activitypub`]).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Follower {
+    /// The follower's actor ID, e.g. `https://mastodon.social/users/alice`.
+    pub actor: String,
+    /// Where new memes are delivered: the follower's shared inbox when its server has one.
+    pub inbox: String,
+    pub followed_at: DateTime<Utc>,
+}
+
+/// Where followers are kept, one record per actor.
+#[async_trait]
+pub trait FollowerRepository: Send + Sync + 'static {
+    /// Stores a follower, replacing an earlier follow by the same actor.
+    async fn add(&self, follower: &Follower) -> Result<(), RepoError>;
+    /// Removes a follower. Removing one that is not following is a no-op.
+    async fn remove(&self, actor: &str) -> Result<(), RepoError>;
+    async fn list(&self) -> Result<Vec<Follower>, RepoError>;
+}
+
+/// Embedding vectors of memes, searched by cosine similarity (see [`crate::embeddings`]).
+#[async_trait]
+pub trait VectorIndex: Send + Sync + 'static {
+    /// Stores the meme's vector, replacing any earlier one.
+    async fn upsert(&self, id: Uuid, vector: &[f32]) -> Result<(), RepoError>;
+    /// Forgets the meme's vector; forgetting a meme without one is not an error.
+    async fn remove(&self, id: Uuid) -> Result<(), RepoError>;
+    /// The `limit` memes whose vectors are most similar to `query`, most similar first,
+    /// with their cosine similarity. Vectors of another length (from another model) are
+    /// skipped.
+    async fn nearest(&self, query: &[f32], limit: usize) -> Result<Vec<(Uuid, f32)>, RepoError>;
+}
+
+/// Progress of the change stream consumer per shard, so it resumes where it stopped.
+#[cfg_attr(feature = "lambda", allow(dead_code))] // The stream consumer does not run on Lambda
+#[async_trait]
+pub trait CheckpointRepository: Send + Sync + 'static {
+    /// Sequence number of the last processed record in `shard_id`, if any.
+    async fn get_checkpoint(&self, shard_id: &str) -> Result<Option<String>, RepoError>;
+    async fn save_checkpoint(&self, shard_id: &str, sequence_number: &str) -> Result<(), RepoError>;
+}
+
+/// What is stored alongside an uploaded file besides its bytes.
+#[derive(Debug, Clone, Default)]
+pub struct UploadOptions {
+    pub content_type: Option<String>,
+    /// Object tags, for lifecycle rules and cost allocation. Backends may drop them when
+    /// tagging is turned off.
+    pub tags: Vec<(String, String)>,
+    /// User metadata returned with the file (`x-amz-meta-*` on S3). Values must be ASCII.
+    pub metadata: Vec<(String, String)>,
+}
+
+impl UploadOptions {
+    pub fn with_content_type(content_type: impl Into<String>) -> Self {
+        Self { content_type: Some(content_type.into()), ..Self::default() }
+    }
+
+    pub fn tag(mut self, key: &str, value: impl Into<String>) -> Self {
+        self.tags.push((key.to_string(), value.into()));
+        self
+    }
+
+    pub fn metadata(mut self, key: &str, value: impl Into<String>) -> Self {
+        self.metadata.push((key.to_string(), value.into()));
+        self
+    }
+}
+
+/// What the storage backend reports about a stored file.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ObjectMetadata {
+    pub content_type: Option<String>,
+    pub content_length: Option<u64>,
+    /// Entity tag assigned by the backend, quotes included.
+    pub etag: Option<String>,
+    /// When the file was last written.
+    pub last_modified: Option<DateTime<Utc>>,
+}
+
+/// A stored file as reported by a listing.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StoredObject {
+    pub key: String,
+    pub size: u64,
 }
 
 #[async_trait]
 pub trait FileStorage: Send + Sync + 'static {
-    async fn upload(&self, key: &str, data: Vec<u8>, content_type: Option<String>) -> Result<(), StorageError>;
-    async fn download(&self, key: &str) -> Result<(ByteStream, Option<String>), StorageError>;
+    async fn upload(&self, key: &str, data: Bytes, options: UploadOptions) -> Result<(), StorageError>;
+    async fn download(&self, key: &str) -> Result<(ByteStream, ObjectMetadata), StorageError>;
+    /// Reads a file's metadata without its contents. Fails with `StorageError::NotFound` if
+    /// there is no such file.
+    async fn head(&self, key: &str) -> Result<ObjectMetadata, StorageError>;
+    /// Lists every stored file with its size.
+    async fn list(&self) -> Result<Vec<StoredObject>, StorageError>;
+    /// A URL that downloads the file without other credentials until `expires_in` passes.
+    /// Signed locally, so it does not check that the file exists.
+    async fn presigned_url(&self, key: &str, expires_in: Duration) -> Result<String, StorageError>;
     /// Deletes a file by its key.
     /// Should typically succeed even if the file doesn't exist, unless there's a backend error.
     async fn delete(&self, key: &str) -> Result<(), StorageError>;
+    /// Copies a file to another key of the same storage, with its content type and metadata,
+    /// replacing any file at `to`. Fails with `StorageError::NotFound` if there is no file at
+    /// `from`.
+    async fn copy(&self, from: &str, to: &str) -> Result<(), StorageError>;
+    /// Moves a file to another key, like [`FileStorage::copy`] followed by deleting `from`,
+    /// which is what it does unless the backend can move files itself. If the delete fails,
+    /// the file is left under both keys and the error is returned.
+    async fn rename(&self, from: &str, to: &str) -> Result<(), StorageError> {
+        self.copy(from, to).await?;
+        self.delete(from).await
+    }
+    /// Checks that the bucket (or container) is reachable, for `/health`. Decorators pass it
+    /// straight through, so it reflects the backend rather than retries or breakers.
+    async fn ping(&self) -> Result<(), StorageError>;
+}
+
+/// Lets the storage chosen at runtime ([`crate::backends::file_storage`]) go through
+/// the same generic decorators as a concrete backend.
+#[async_trait]
+impl FileStorage for Box<dyn FileStorage> {
+    async fn upload(&self, key: &str, data: Bytes, options: UploadOptions) -> Result<(), StorageError> {
+        (**self).upload(key, data, options).await
+    }
+
+    async fn download(&self, key: &str) -> Result<(ByteStream, ObjectMetadata), StorageError> {
+        (**self).download(key).await
+    }
+
+    async fn head(&self, key: &str) -> Result<ObjectMetadata, StorageError> {
+        (**self).head(key).await
+    }
+
+    async fn list(&self) -> Result<Vec<StoredObject>, StorageError> {
+        (**self).list().await
+    }
+
+    async fn presigned_url(&self, key: &str, expires_in: Duration) -> Result<String, StorageError> {
+        (**self).presigned_url(key, expires_in).await
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), StorageError> {
+        (**self).delete(key).await
+    }
+
+    async fn copy(&self, from: &str, to: &str) -> Result<(), StorageError> {
+        (**self).copy(from, to).await
+    }
+
+    async fn rename(&self, from: &str, to: &str) -> Result<(), StorageError> {
+        (**self).rename(from, to).await
+    }
+
+    async fn ping(&self) -> Result<(), StorageError> {
+        (**self).ping().await
+    }
 }
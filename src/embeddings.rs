@@ -0,0 +1,247 @@
+use crate::{aws_clients, config::Config, domain::ConsistencyLevel, errors::AppError, models::Meme, AppState};
+use anyhow::Context;
+use async_trait::async_trait;
+use aws_sdk_bedrockruntime::{primitives::Blob, Client as BedrockRuntimeClient};
+use serde::{Deserialize, Serialize};
+use std::{str::FromStr, sync::Arc, time::Duration};
+use uuid::Uuid;
+
+/// Longest semantic search query, in characters.
+pub const MAX_SEMANTIC_QUERY_CHARS: usize = 500;
+
+/// Most memes one semantic search returns.
+pub const MAX_SEMANTIC_RESULTS: usize = 20;
+
+/// Nearest vectors looked at per search, per result wanted; the surplus makes up for drafts,
+/// private memes and deleted memes, which are skipped.
+const CANDIDATES_PER_RESULT: usize = 4;
+
+/// Length of the vectors made by the `hashing` embedder.
+pub const HASHING_DIMENSIONS: usize = 256;
+
+/// Which model turns memes and queries into vectors (`APP_EMBEDDING_BACKEND`).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EmbeddingBackend {
+    /// Semantic search is off and no vectors are stored.
+    #[default]
+    None,
+    /// An Amazon Bedrock Titan embeddings model (`APP_EMBEDDING_MODEL`).
+    Bedrock,
+    /// Hashed character trigrams, computed locally. Finds spelling variants and shared word
+    /// parts rather than meaning; for development and tests.
+    Hashing,
+}
+
+impl FromStr for EmbeddingBackend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "none" => Ok(EmbeddingBackend::None),
+            "bedrock" => Ok(EmbeddingBackend::Bedrock),
+            "hashing" => Ok(EmbeddingBackend::Hashing),
+            other => Err(format!("unknown embedding backend '{}' (expected none, bedrock or hashing)", other)),
+        }
+    }
+}
+
+/// Turns text into a vector whose cosine similarity to another text's vector measures how
+/// alike the two are.
+#[async_trait]
+pub trait Embedder: Send + Sync + 'static {
+    async fn embed(&self, text: &str) -> anyhow::Result<Vec<f32>>;
+}
+
+/// Calls InvokeModel on a Titan embeddings model, whose requests take `inputText` and whose
+/// responses carry `embedding`.
+pub struct BedrockEmbedder {
+    client: BedrockRuntimeClient,
+    model_id: String,
+}
+
+impl BedrockEmbedder {
+    pub fn new(client: BedrockRuntimeClient, model_id: String) -> Self {
+        Self { client, model_id }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct TitanRequest<'a> {
+    input_text: &'a str,
+}
+
+#[derive(Deserialize)]
+struct TitanResponse {
+    embedding: Vec<f32>,
+}
+
+#[async_trait]
+impl Embedder for BedrockEmbedder {
+    async fn embed(&self, text: &str) -> anyhow::Result<Vec<f32>> {
+        let body = serde_json::to_vec(&TitanRequest { input_text: text })?;
+        let output = self.client
+            .invoke_model()
+            .model_id(&self.model_id)
+            .content_type("application/json")
+            .accept("application/json")
+            .body(Blob::new(body))
+            .send()
+            .await
+            .map_err(|e| anyhow::Error::new(e).context(format!("Bedrock (model: {}): Failed to embed text", self.model_id)))?;
+        let response: TitanResponse = serde_json::from_slice(output.body().as_ref())
+            .with_context(|| format!("Bedrock (model: {}): Unexpected embedding response", self.model_id))?;
+        Ok(response.embedding)
+    }
+}
+
+/// Feature hashing of each word's character trigrams (with word boundaries, so "cat"
+/// yields "#ca", "cat" and "at#") into [`HASHING_DIMENSIONS`] signed buckets, normalized to
+/// unit length. Uses FNV-1a, whose output is fixed, so stored vectors stay comparable
+/// across builds.
+#[derive(Debug, Default)]
+pub struct HashingEmbedder;
+
+impl HashingEmbedder {
+    fn fnv1a(bytes: &[u8]) -> u64 {
+        bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| (hash ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3))
+    }
+}
+
+#[async_trait]
+impl Embedder for HashingEmbedder {
+    async fn embed(&self, text: &str) -> anyhow::Result<Vec<f32>> {
+        let mut vector = vec![0.0_f32; HASHING_DIMENSIONS];
+        for word in text.split(|c: char| !c.is_alphanumeric()).filter(|word| !word.is_empty()) {
+            let chars: Vec<char> = std::iter::once('#').chain(word.to_lowercase().chars()).chain(std::iter::once('#')).collect();
+            for trigram in chars.windows(3) {
+                let hash = Self::fnv1a(trigram.iter().collect::<String>().as_bytes());
+                let sign = if hash >> 63 == 0 { 1.0 } else { -1.0 };
+                vector[(hash % HASHING_DIMENSIONS as u64) as usize] += sign;
+            }
+        }
+        let norm = vector.iter().map(|x| x * x).sum::<f32>().sqrt();
+        if norm > 0.0 {
+            vector.iter_mut().for_each(|x| *x /= norm);
+        }
+        Ok(vector)
+    }
+}
+
+/// Builds the configured embedder; `None` when semantic search is off.
+pub async fn build_embedder(config: &Config) -> Result<Option<Arc<dyn Embedder>>, AppError> {
+    match config.embedding_backend {
+        EmbeddingBackend::None => Ok(None),
+        EmbeddingBackend::Bedrock => {
+            let sdk_config = aws_clients::create_sdk_config(config).await?;
+            let client = aws_clients::create_bedrock_runtime_client(&sdk_config);
+            Ok(Some(Arc::new(BedrockEmbedder::new(client, config.embedding_model.clone()))))
+        }
+        EmbeddingBackend::Hashing => Ok(Some(Arc::new(HashingEmbedder))),
+    }
+}
+
+/// Cosine similarity of two vectors, from -1 to 1; `None` if their lengths differ or
+/// either is all zeros.
+pub fn cosine_similarity(a: &[f32], b: &[f32]) -> Option<f32> {
+    if a.len() != b.len() {
+        return None;
+    }
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norms = a.iter().map(|x| x * x).sum::<f32>().sqrt() * b.iter().map(|x| x * x).sum::<f32>().sqrt();
+    (norms > 0.0).then(|| dot / norms)
+}
+
+/// The `limit` entries most similar to `query`, most similar first. Shared by the
+/// [`crate::domain::VectorIndex`] implementations, which compare every stored vector.
+pub fn nearest(vectors: impl IntoIterator<Item = (Uuid, Vec<f32>)>, query: &[f32], limit: usize) -> Vec<(Uuid, f32)> {
+    let mut scored: Vec<_> = vectors
+        .into_iter()
+        .filter_map(|(id, vector)| Some((id, cosine_similarity(&vector, query)?)))
+        .collect();
+    scored.sort_by(|(_, a), (_, b)| b.total_cmp(a));
+    scored.truncate(limit);
+    scored
+}
+
+/// The text a meme is embedded from: its title, tags, description and caption text.
+pub fn meme_text(meme: &Meme) -> String {
+    [meme.title.as_str(), &meme.tags.join(" "), &meme.description, meme.caption_text.as_deref().unwrap_or_default()]
+        .into_iter()
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Embeds `text`, giving up after `APP_EMBEDDING_TIMEOUT_SECS`.
+pub async fn embed(embedder: &dyn Embedder, text: &str, config: &Config) -> anyhow::Result<Vec<f32>> {
+    let timeout = Duration::from_secs(config.embedding_timeout_secs);
+    tokio::time::timeout(timeout, embedder.embed(text))
+        .await
+        .map_err(|_| anyhow::anyhow!("Embedding timed out after {}s", timeout.as_secs()))?
+}
+
+/// Stores the vector of a new or changed meme. Like the caption, the vector is a search aid:
+/// failures are logged and counted in `embedding_failures_total`, and the meme is simply
+/// not found by semantic search until its next update.
+pub async fn index_meme(state: &AppState, meme: &Meme) {
+    let Some(embedder) = &state.embedder else {
+        return;
+    };
+    let result = match embed(embedder.as_ref(), &meme_text(meme), &state.config).await {
+        Ok(vector) => state.vector_index.upsert(meme.meme_id, &vector).await.map_err(anyhow::Error::new),
+        Err(e) => Err(e),
+    };
+    if let Err(e) = result {
+        metrics::counter!("embedding_failures_total").increment(1);
+        tracing::warn!(meme_id = %meme.meme_id, error = %e, "Failed to index the meme for semantic search");
+    }
+}
+
+/// Forgets the vector of a deleted meme. A leftover vector is harmless: searches skip memes
+/// that no longer exist and remove their vectors then.
+pub async fn unindex_meme(state: &AppState, id: Uuid) {
+    if state.embedder.is_none() {
+        return;
+    }
+    if let Err(e) = state.vector_index.remove(id).await {
+        tracing::warn!(meme_id = %id, error = %e, "Failed to remove the meme's vector");
+    }
+}
+
+/// The listed memes most similar to `query`, most similar first, at most
+/// [`MAX_SEMANTIC_RESULTS`]. Vectors of memes that no longer exist are removed on the way.
+pub async fn semantic_search(state: &AppState, query: &str) -> Result<Vec<Meme>, AppError> {
+    let Some(embedder) = &state.embedder else {
+        return Err(AppError::InvalidInput("Semantic search is not enabled (APP_EMBEDDING_BACKEND)".to_string()));
+    };
+    let query = query.trim();
+    if query.is_empty() || query.chars().count() > MAX_SEMANTIC_QUERY_CHARS {
+        return Err(AppError::InvalidInput(format!("q must have between 1 and {} characters", MAX_SEMANTIC_QUERY_CHARS)));
+    }
+    let vector = embed(embedder.as_ref(), query, &state.config).await.map_err(|e| {
+        metrics::counter!("embedding_failures_total").increment(1);
+        tracing::warn!(error = %e, "Failed to embed a search query");
+        AppError::ServiceUnavailable("Semantic search is unavailable".to_string())
+    })?;
+    let candidates = state.vector_index.nearest(&vector, MAX_SEMANTIC_RESULTS * CANDIDATES_PER_RESULT).await?;
+    let now = chrono::Utc::now();
+    let mut memes = Vec::new();
+    for (id, _) in candidates {
+        match state.meme_repo.get_by_id(id, ConsistencyLevel::Eventual).await? {
+            Some(meme) if meme.is_listed(now) => memes.push(meme),
+            Some(_) => {}
+            None => {
+                if let Err(e) = state.vector_index.remove(id).await {
+                    tracing::warn!(meme_id = %id, error = %e, "Failed to remove the vector of a deleted meme");
+                }
+            }
+        }
+        if memes.len() == MAX_SEMANTIC_RESULTS {
+            break;
+        }
+    }
+    tracing::debug!(matches = memes.len(), "Searched memes semantically");
+    Ok(memes)
+}
@@ -0,0 +1,241 @@
+//! Rich previews of pasted meme links, for chat apps and social sites.
+//!
+//! - `GET /meme/{id}` answers browsers and link preview crawlers, whose `Accept` header asks
+//!   for HTML, with a small page carrying Open Graph and Twitter card tags and a link to the
+//!   oEmbed endpoint.
+//! - `GET /oembed?url=...` answers with the meme as an oEmbed `photo` (or a `link` when the
+//!   image's size is unknown).
+//!
+//! Both only describe memes anyone may see, and only with `APP_PUBLIC_URL` set: their links
+//! are absolute, and the request's `Host` header is up to the client.
+
+use crate::{
+    announcements::{has_absolute_image_url, linked_meme},
+    config::ReadEndpoint,
+    errors::AppError,
+    handlers,
+    models::{Meme, MemeView},
+    AppState,
+};
+use axum::{
+    extract::{Query, State},
+    http::header,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+/// Query parameters for GET /oembed, as defined by the oEmbed spec.
+#[derive(Deserialize)]
+pub struct OEmbedQuery {
+    /// Link to the meme, `.../meme/{id}`.
+    pub url: String,
+    pub maxwidth: Option<u32>,
+    pub maxheight: Option<u32>,
+    /// Only `json` is supported.
+    pub format: Option<String>,
+}
+
+/// An oEmbed response.
+#[derive(Serialize)]
+pub struct OEmbed {
+    pub version: &'static str,
+    #[serde(rename = "type")]
+    pub kind: &'static str,
+    pub title: String,
+    pub provider_url: String,
+    /// Seconds the response may be cached: image URLs may be presigned for no longer.
+    pub cache_age: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub width: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub height: Option<u32>,
+}
+
+/// Handler for GET /oembed. The image is described at its stored size, scaled down to fit
+/// `maxwidth` and `maxheight`.
+pub async fn get_oembed(State(state): State<Arc<AppState>>, Query(query): Query<OEmbedQuery>) -> Result<Json<OEmbed>, AppError> {
+    if query.format.as_deref().is_some_and(|format| format != "json") {
+        return Err(AppError::NotImplemented("Only the json oEmbed format is supported".to_string()));
+    }
+    let base = public_base(&state).expect("/oembed is only routed with a public URL");
+    let meme_id =
+        linked_meme(&query.url, base).ok_or_else(|| AppError::InvalidInput(format!("Not a link to a meme: {}", query.url)))?;
+    let meme = handlers::find_visible_meme(&state, meme_id, false, ReadEndpoint::GetMeme).await?;
+    let view = handlers::meme_view(&state, meme).await?;
+    Ok(Json(oembed(base, &view, state.config.image_url_ttl_secs, query.maxwidth, query.maxheight)))
+}
+
+/// The oEmbed description of `view`: a `photo` fitting `max_width` and `max_height` when the
+/// image's size is known, else a `link`.
+fn oembed(base: &str, view: &MemeView, cache_age: u64, max_width: Option<u32>, max_height: Option<u32>) -> OEmbed {
+    let mut oembed = OEmbed {
+        version: "1.0",
+        kind: "link",
+        title: view.meme.title.clone(),
+        provider_url: format!("{}/", base),
+        cache_age,
+        url: None,
+        width: None,
+        height: None,
+    };
+    if let Some((width, height)) = known_size(&view.meme) {
+        let (width, height) = fit(width, height, max_width, max_height);
+        oembed.kind = "photo";
+        oembed.url = Some(absolute_image_url(base, view));
+        oembed.width = Some(width);
+        oembed.height = Some(height);
+    }
+    oembed
+}
+
+/// The HTML page of a meme the caller may see, for GET /meme/{id} from browsers and crawlers.
+/// Links start with `base`, the instance's public URL. Memes left out of the public listing
+/// are marked `noindex`.
+pub(crate) fn meme_page(base: &str, view: &MemeView) -> Response {
+    let meme = &view.meme;
+    let page_url = format!("{}/meme/{}", base, meme.meme_id);
+    let image_url = absolute_image_url(base, view);
+    let oembed_url = format!("{}/oembed?url={}", base, url::form_urlencoded::byte_serialize(page_url.as_bytes()).collect::<String>());
+    let (title, description) = (escape(&meme.title), escape(&meme.description));
+
+    let mut head = vec![
+        format!("<title>{}</title>", title),
+        format!("<meta name=\"description\" content=\"{}\">", description),
+        "<meta property=\"og:type\" content=\"website\">".to_string(),
+        format!("<meta property=\"og:title\" content=\"{}\">", title),
+        format!("<meta property=\"og:description\" content=\"{}\">", description),
+        format!("<meta property=\"og:url\" content=\"{}\">", escape(&page_url)),
+        format!("<meta property=\"og:image\" content=\"{}\">", escape(&image_url)),
+        format!("<meta property=\"og:image:alt\" content=\"{}\">", title),
+        "<meta name=\"twitter:card\" content=\"summary_large_image\">".to_string(),
+        format!("<meta name=\"twitter:title\" content=\"{}\">", title),
+        format!("<meta name=\"twitter:description\" content=\"{}\">", description),
+        format!("<meta name=\"twitter:image\" content=\"{}\">", escape(&image_url)),
+        format!("<link rel=\"canonical\" href=\"{}\">", escape(&page_url)),
+        format!(
+            "<link rel=\"alternate\" type=\"application/json+oembed\" href=\"{}\" title=\"{}\">",
+            escape(&oembed_url),
+            title
+        ),
+    ];
+    let mut size = String::new();
+    if let Some((width, height)) = known_size(meme) {
+        head.push(format!("<meta property=\"og:image:width\" content=\"{}\">", width));
+        head.push(format!("<meta property=\"og:image:height\" content=\"{}\">", height));
+        size = format!(" width=\"{}\" height=\"{}\"", width, height);
+    }
+    if !meme.is_listed(chrono::Utc::now()) {
+        head.push("<meta name=\"robots\" content=\"noindex\">".to_string());
+    }
+
+    let page = format!(
+        "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n<meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n{}\n</head>\n<body>\n<h1>{}</h1>\n<img src=\"{}\" alt=\"{}\"{}>\n<p>{}</p>\n</body>\n</html>\n",
+        head.join("\n"),
+        title,
+        escape(&image_url),
+        title,
+        size,
+        description,
+    );
+    (
+        // The JSON representation is served at the same URL
+        [(header::CONTENT_TYPE, "text/html; charset=utf-8"), (header::VARY, "accept")],
+        page,
+    )
+        .into_response()
+}
+
+/// The URL this server is reached at, without a trailing slash: `APP_PUBLIC_URL`. `None`
+/// when it is unset; links are not made from the `Host` header, which the client chooses.
+pub(crate) fn public_base(state: &AppState) -> Option<&str> {
+    state.config.public_url.as_deref()
+}
+
+/// The meme's image URL, made absolute when the image is served by this server.
+pub(crate) fn absolute_image_url(base: &str, view: &MemeView) -> String {
+    if has_absolute_image_url(view) {
+        view.image_url.clone()
+    } else {
+        format!("{}{}", base, view.image_url)
+    }
+}
+
+/// `width` x `height` scaled down, keeping the aspect ratio, to fit the given limits.
+fn fit(width: u32, height: u32, max_width: Option<u32>, max_height: Option<u32>) -> (u32, u32) {
+    let scale = [max_width.map(|max| max as f64 / width as f64), max_height.map(|max| max as f64 / height as f64)]
+        .into_iter()
+        .flatten()
+        .fold(1.0, f64::min);
+    (((width as f64 * scale).round() as u32).max(1), ((height as f64 * scale).round() as u32).max(1))
+}
+
+/// The image's size when it is known; memes stored before sizes were read have none.
+fn known_size(meme: &Meme) -> Option<(u32, u32)> {
+    meme.width.zip(meme.height).filter(|&(width, height)| width > 0 && height > 0)
+}
+
+/// Text escaped for HTML content and attribute values.
+pub(crate) fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generators::MemeBuilder;
+
+    const BASE: &str = "https://memes.example.com";
+
+    fn view(meme: Meme) -> MemeView {
+        let image_url = format!("/images/{}", meme.image_key);
+        MemeView { meme, image_url }
+    }
+
+    #[test]
+    fn sizes_are_scaled_down_to_fit_every_limit() {
+        assert_eq!(fit(800, 600, None, None), (800, 600));
+        assert_eq!(fit(800, 600, Some(400), None), (400, 300));
+        assert_eq!(fit(800, 600, None, Some(150)), (200, 150));
+        assert_eq!(fit(800, 600, Some(400), Some(150)), (200, 150));
+        assert_eq!(fit(800, 600, Some(2000), Some(2000)), (800, 600));
+        assert_eq!(fit(1000, 10, Some(10), None), (10, 1));
+    }
+
+    #[test]
+    fn memes_of_known_size_are_photos_fitting_maxwidth_and_maxheight() {
+        let view = view(MemeBuilder::new().title("Cat").dimensions(640, 480).build());
+        let photo = oembed(BASE, &view, 300, Some(320), Some(120));
+        assert_eq!(photo.kind, "photo");
+        assert_eq!((photo.width, photo.height), (Some(160), Some(120)));
+        assert_eq!(photo.url, Some(format!("{}{}", BASE, view.image_url)));
+        assert_eq!(photo.provider_url, "https://memes.example.com/");
+        assert_eq!(photo.cache_age, 300);
+
+        let unbounded = oembed(BASE, &view, 300, None, None);
+        assert_eq!((unbounded.width, unbounded.height), (Some(640), Some(480)));
+    }
+
+    #[test]
+    fn memes_of_unknown_size_are_links() {
+        let mut meme = MemeBuilder::new().title("Cat").build();
+        (meme.width, meme.height) = (None, None);
+        let link = oembed(BASE, &view(meme), 300, Some(320), None);
+        assert_eq!((link.kind, link.title.as_str()), ("link", "Cat"));
+        assert_eq!((link.url, link.width, link.height), (None, None, None));
+    }
+}
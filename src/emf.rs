@@ -0,0 +1,191 @@
+//! CloudWatch Embedded Metric Format (EMF) sink for the metrics recorded through the
+//! `metrics` facade (`APP_METRICS_SINK=emf`). Metrics are aggregated in memory and flushed
+//! periodically as one JSON document per metric and label set, written to stdout (where
+//! Lambda and the awslogs driver hand them to CloudWatch Logs) or sent to a CloudWatch agent.
+
+use crate::errors::AppError;
+use metrics::{Counter, Gauge, Histogram, Key, KeyName, Metadata, Recorder, SharedString, Unit};
+use metrics_util::registry::{AtomicStorage, Registry};
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+use std::{
+    sync::{atomic::Ordering, Arc},
+    time::Duration,
+};
+use tokio::{
+    io::{AsyncWrite, AsyncWriteExt},
+    net::{TcpStream, UdpSocket},
+};
+
+/// CloudWatch accepts at most 100 values per metric in one document.
+const MAX_VALUES_PER_DOCUMENT: usize = 100;
+
+/// Where EMF documents go: stdout, or a CloudWatch agent listening for EMF
+/// (`tcp://host:port` or `udp://host:port`, usually port 25888).
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EmfDestination {
+    Stdout,
+    Tcp(String),
+    Udp(String),
+}
+
+impl EmfDestination {
+    /// Parses an agent endpoint; stdout is used when none is configured.
+    pub fn parse(endpoint: Option<&str>) -> Result<Self, String> {
+        let Some(endpoint) = endpoint else {
+            return Ok(EmfDestination::Stdout);
+        };
+        match endpoint.split_once("://") {
+            Some(("tcp", address)) if !address.is_empty() => Ok(EmfDestination::Tcp(address.to_string())),
+            Some(("udp", address)) if !address.is_empty() => Ok(EmfDestination::Udp(address.to_string())),
+            _ => Err(format!("expected tcp://host:port or udp://host:port, got '{}'", endpoint)),
+        }
+    }
+}
+
+/// Recorder keeping counters, gauges and histograms until the next flush.
+struct EmfRecorder {
+    registry: Arc<Registry<Key, AtomicStorage>>,
+}
+
+impl Recorder for EmfRecorder {
+    // EMF documents carry no descriptions; units are derived from metric names
+    fn describe_counter(&self, _key: KeyName, _unit: Option<Unit>, _description: SharedString) {}
+    fn describe_gauge(&self, _key: KeyName, _unit: Option<Unit>, _description: SharedString) {}
+    fn describe_histogram(&self, _key: KeyName, _unit: Option<Unit>, _description: SharedString) {}
+
+    fn register_counter(&self, key: &Key, _metadata: &Metadata<'_>) -> Counter {
+        self.registry.get_or_create_counter(key, |counter| Counter::from_arc(counter.clone()))
+    }
+
+    fn register_gauge(&self, key: &Key, _metadata: &Metadata<'_>) -> Gauge {
+        self.registry.get_or_create_gauge(key, |gauge| Gauge::from_arc(gauge.clone()))
+    }
+
+    fn register_histogram(&self, key: &Key, _metadata: &Metadata<'_>) -> Histogram {
+        self.registry.get_or_create_histogram(key, |histogram| Histogram::from_arc(histogram.clone()))
+    }
+}
+
+/// Installs the EMF recorder as the global recorder and spawns the task flushing it every
+/// `flush_interval` under `namespace`. Must be called from within the Tokio runtime.
+pub fn install(namespace: String, flush_interval: Duration, destination: EmfDestination) -> Result<(), AppError> {
+    let registry = Arc::new(Registry::atomic());
+    metrics::set_global_recorder(EmfRecorder { registry: registry.clone() })
+        .map_err(|e| AppError::InitError(format!("Failed to install EMF metrics recorder: {}", e)))?;
+
+    tokio::spawn(async move {
+        let mut sink = Sink { destination, tcp: None };
+        let mut ticker = tokio::time::interval(flush_interval);
+        ticker.tick().await; // The first tick is immediate
+        loop {
+            ticker.tick().await;
+            let documents = drain(&registry, &namespace, chrono::Utc::now().timestamp_millis());
+            if let Err(e) = sink.send(&documents).await {
+                tracing::warn!(error = %e, documents = documents.len(), "Failed to emit EMF metrics; dropping them");
+            }
+        }
+    });
+    Ok(())
+}
+
+/// Takes what was recorded since the last flush: counter increments, current gauge values
+/// and histogram samples. Counters without increments and empty histograms are left out.
+fn drain(registry: &Registry<Key, AtomicStorage>, namespace: &str, timestamp: i64) -> Vec<String> {
+    let mut documents = Vec::new();
+    registry.visit_counters(|key, counter| {
+        let delta = counter.swap(0, Ordering::AcqRel);
+        if delta > 0 {
+            documents.push(document(namespace, timestamp, key, json!(delta)));
+        }
+    });
+    registry.visit_gauges(|key, gauge| {
+        let value = f64::from_bits(gauge.load(Ordering::Acquire));
+        documents.push(document(namespace, timestamp, key, json!(value)));
+    });
+    registry.visit_histograms(|key, histogram| {
+        let mut samples = Vec::new();
+        histogram.clear_with(|values| samples.extend_from_slice(values));
+        for chunk in samples.chunks(MAX_VALUES_PER_DOCUMENT) {
+            documents.push(document(namespace, timestamp, key, json!(chunk)));
+        }
+    });
+    documents
+}
+
+/// One EMF document: the metric's labels become its dimensions.
+fn document(namespace: &str, timestamp: i64, key: &Key, value: Value) -> String {
+    let name = key.name();
+    let dimensions: Vec<&str> = key.labels().map(|label| label.key()).collect();
+    let mut metric = json!({ "Name": name });
+    if let Some(unit) = unit_of(name) {
+        metric["Unit"] = json!(unit);
+    }
+
+    let mut fields = Map::new();
+    fields.insert(
+        "_aws".to_string(),
+        json!({
+            "Timestamp": timestamp,
+            "CloudWatchMetrics": [{ "Namespace": namespace, "Dimensions": [dimensions], "Metrics": [metric] }],
+        }),
+    );
+    for label in key.labels() {
+        fields.insert(label.key().to_string(), json!(label.value()));
+    }
+    fields.insert(name.to_string(), value);
+    Value::Object(fields).to_string()
+}
+
+/// CloudWatch unit of a metric, going by the Prometheus naming conventions used here.
+fn unit_of(name: &str) -> Option<&'static str> {
+    if name.ends_with("_seconds") {
+        Some("Seconds")
+    } else if name.ends_with("_bytes") {
+        Some("Bytes")
+    } else if name.ends_with("_total") {
+        Some("Count")
+    } else {
+        None
+    }
+}
+
+struct Sink {
+    destination: EmfDestination,
+    /// Connection to the agent, reopened after a failed write.
+    tcp: Option<TcpStream>,
+}
+
+impl Sink {
+    async fn send(&mut self, documents: &[String]) -> std::io::Result<()> {
+        match &self.destination {
+            EmfDestination::Stdout => write_lines(&mut tokio::io::stdout(), documents).await,
+            EmfDestination::Tcp(address) => {
+                let mut stream = match self.tcp.take() {
+                    Some(stream) => stream,
+                    None => TcpStream::connect(address.as_str()).await?,
+                };
+                write_lines(&mut stream, documents).await?;
+                self.tcp = Some(stream);
+                Ok(())
+            }
+            EmfDestination::Udp(address) => {
+                let socket = UdpSocket::bind("0.0.0.0:0").await?;
+                socket.connect(address.as_str()).await?;
+                for document in documents {
+                    socket.send(format!("{}\n", document).as_bytes()).await?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+async fn write_lines(writer: &mut (impl AsyncWrite + Unpin), documents: &[String]) -> std::io::Result<()> {
+    for document in documents {
+        writer.write_all(document.as_bytes()).await?;
+        writer.write_all(b"\n").await?;
+    }
+    writer.flush().await
+}
@@ -0,0 +1,78 @@
+//! Error reporting to Sentry (`APP_SENTRY_DSN`). Errors answered with a 5xx are captured
+//! together with the request ID and the matched route; 4xx client errors are not reported.
+//! Events carry no headers, query strings, bodies or raw paths, which may hold tokens
+//! (e.g. share links) or user content.
+
+use crate::{config::Config, errors::AppError};
+use axum::{
+    extract::{MatchedPath, Request},
+    middleware::Next,
+    response::Response,
+};
+use sentry::{protocol::Context, ClientInitGuard, ClientOptions};
+use std::{collections::BTreeMap, sync::Mutex, time::Duration};
+
+/// Reporting is process-global; tests build several app states in one process.
+static CLIENT: Mutex<Option<ClientInitGuard>> = Mutex::new(None);
+
+/// Starts reporting to the configured DSN on first use; later calls keep the first client.
+/// Nothing is reported when no DSN is configured.
+pub fn init(config: &Config) {
+    let Some(dsn) = &config.sentry_dsn else {
+        return;
+    };
+    let mut client = CLIENT.lock().expect("error reporting lock poisoned");
+    if client.is_some() {
+        return;
+    }
+    let guard = sentry::init((
+        dsn.as_str(),
+        ClientOptions {
+            release: sentry::release_name!(),
+            environment: config.sentry_environment.clone().map(Into::into),
+            send_default_pii: false,
+            ..Default::default()
+        },
+    ));
+    if guard.is_enabled() {
+        tracing::info!("Reporting server errors to Sentry");
+        *client = Some(guard);
+    }
+}
+
+/// Sends events still queued, waiting at most `timeout`. Called before the process exits.
+pub fn flush(timeout: Duration) {
+    if let Some(client) = sentry::Hub::current().client() {
+        client.flush(Some(timeout));
+    }
+}
+
+/// Reports an error about to be answered with a 5xx status. A no-op unless [`init`] set
+/// up a client.
+pub fn report(error: &AppError) {
+    sentry::capture_error(error);
+}
+
+/// Route middleware tagging the request's Sentry scope with its request ID and matched
+/// route (e.g. `/meme/{id}`), which also names the transaction. Runs inside the per-request
+/// hub of `sentry::integrations::tower::NewSentryLayer`, so tags do not leak between
+/// concurrent requests.
+pub async fn tag_request(request: Request, next: Next) -> Response {
+    let route = request.extensions().get::<MatchedPath>().map(|path| path.as_str().to_string());
+    let request_id = request.headers().get("x-request-id").and_then(|value| value.to_str().ok()).map(str::to_string);
+    let method = request.method().to_string();
+    sentry::configure_scope(|scope| {
+        let mut context = BTreeMap::new();
+        context.insert("method".to_string(), method.into());
+        if let Some(route) = &route {
+            scope.set_tag("route", route);
+            scope.set_transaction(Some(route));
+            context.insert("route".to_string(), route.clone().into());
+        }
+        if let Some(request_id) = &request_id {
+            scope.set_tag("request_id", request_id);
+        }
+        scope.set_context("request", Context::Other(context));
+    });
+    next.run(request).await
+}
@@ -1,11 +1,12 @@
 use crate::config;
+use crate::validation::ValidationErrors;
 use axum::{
-    http::StatusCode,
+    http::{Method, StatusCode},
     response::{IntoResponse, Response},
     Json,
 };
+use serde::{Deserialize, Serialize};
 use thiserror::Error;
-use tracing;
 use uuid::Uuid;
 
 // --- Domain/Infrastructure Errors ---
@@ -14,10 +15,16 @@ use uuid::Uuid;
 pub enum RepoError {
     #[error("Meme metadata not found with ID: {0}")] // Clarify metadata
     NotFound(Uuid),
+    #[error("Meme {id} was modified concurrently (expected version {expected}, found {actual})")]
+    VersionConflict { id: Uuid, expected: u64, actual: u64 }, // Conditional update lost the race
+    #[error("Meme already exists with ID: {0}")]
+    AlreadyExists(Uuid), // Conditional create found an item with the same ID
     #[error("Database backend error: {0}")]
     BackendError(#[from] anyhow::Error), // Allows easy conversion from SDK/other errors via context()
-    #[error("Data corruption detected: {0}")] // Error for unparseable data from DB
-    DataCorruption(String),
+    #[error("Data corruption detected in '{field}': {reason}")] // Error for unparseable data from DB
+    DataCorruption { field: String, reason: String }, // `field` names the attribute (or column) that failed
+    #[error("Database backend unavailable: circuit breaker '{0}' is open")]
+    Unavailable(&'static str), // Fast failure while the backend is considered down
 }
 
 #[derive(Error, Debug)]
@@ -28,6 +35,8 @@ pub enum StorageError {
     NotFound(String), // Specific variant for file not found
     #[error("Storage backend error: {0}")]
     BackendError(#[from] anyhow::Error), // Catch-all for SDK/backend issues
+    #[error("Storage backend unavailable: circuit breaker '{0}' is open")]
+    Unavailable(&'static str), // Fast failure while the backend is considered down
 }
 
 // --- Web Layer Error ---
@@ -37,24 +46,70 @@ pub enum AppError {
     // Input errors (4xx)
     #[error("Invalid input: {0}")]
     InvalidInput(String),
-    #[error("Missing form field: {0}")]
-    MissingFormField(String),
+    #[error("Validation failed: {0}")]
+    ValidationFailed(ValidationErrors), // Aggregated per-field errors (422)
     #[error("Error processing multipart form data: {0}")]
     MultipartError(#[from] axum::extract::multipart::MultipartError),
     #[error("Invalid meme ID format: {0}")]
     InvalidUuid(#[from] uuid::Error),
+    #[error("Payload too large: {0}")]
+    PayloadTooLarge(String), // Declared or sent size over the limit (413)
+    #[error("Unsupported media type: {0}")]
+    UnsupportedMediaType(String), // Request body of the wrong type (415)
+
+    // Auth errors (401/403)
+    #[error("Unauthorized: {0}")]
+    Unauthorized(String),
+    #[error("Forbidden: {0}")]
+    Forbidden(String),
 
     // Not Found Errors (404)
     #[error("Meme metadata not found with ID: {0}")]
     MemeNotFound(Uuid), // Specific for metadata from repo
     #[error("Image file not found with key: {0}")]
     ImageNotFound(String), // Specific for image file from storage
+    #[error("No route for path: {0}")]
+    RouteNotFound(String), // Router fallback for unknown paths
+    #[error("Unknown tenant: {0}")]
+    TenantNotFound(String), // X-Tenant-Id or subdomain names no configured tenant
+    #[error("Upload not found with ID: {0}")]
+    UploadNotFound(Uuid), // Resumable upload that finished, was cancelled or never existed
+    #[error("Shortlink not found: {0}")]
+    ShortlinkNotFound(String),
+    #[error("Unknown account: {0}")]
+    ActorNotFound(String), // WebFinger resource other than the ActivityPub actor
+    #[error("Meme {id} has no version {version}")]
+    VersionNotFound { id: Uuid, version: u64 }, // Not kept in the meme's history
+
+    // Method errors (405)
+    #[error("Method {method} not allowed for path: {path}")]
+    MethodNotAllowed { method: Method, path: String }, // The router adds the `Allow` header
+
+    // Conflict errors (409)
+    #[error("Conflict: {0}")]
+    Conflict(String),
+
+    // Conditional request errors (412)
+    #[error("Precondition failed: {0}")]
+    PreconditionFailed(String),
+
+    // Quota errors (429)
+    #[error("Quota exceeded: {0}")]
+    QuotaExceeded(String),
+
+    // Unsupported requests (501)
+    #[error("Not implemented: {0}")]
+    NotImplemented(String), // A standard's optional variant this server does not offer
 
     // Domain/Service level errors (5xx)
     #[error("Could not process meme data")] // User-friendly message
     RepositoryError(#[source] RepoError), // Wraps underlying RepoError
     #[error("Could not perform file storage operation")] // User-friendly message
     StorageError(#[source] StorageError), // Wraps underlying StorageError
+    #[error("Backend temporarily unavailable: {0}")]
+    ServiceUnavailable(String), // A circuit breaker is open (503)
+    #[error("Request timed out after {0:?}")]
+    Timeout(std::time::Duration), // Handler exceeded its time budget (504)
 
     // Configuration / Startup errors (5xx)
     #[error("Configuration error: {0}")]
@@ -65,6 +120,8 @@ pub enum AppError {
     // Generic Internal Server Error (5xx)
     #[error("Internal server error: {0}")]
     InternalServerError(String),
+    #[error("Handler panicked")]
+    Panicked { request_id: Option<String> }, // Caught by `panics::catch_panics`, which logged it
 }
 
 // --- Conversions from Domain Errors to AppError ---
@@ -73,13 +130,16 @@ impl From<RepoError> for AppError {
     fn from(err: RepoError) -> Self {
         match err {
             RepoError::NotFound(id) => AppError::MemeNotFound(id),
+            e @ RepoError::VersionConflict { .. } => AppError::PreconditionFailed(e.to_string()),
+            e @ RepoError::AlreadyExists(_) => AppError::Conflict(e.to_string()),
             // Map DataCorruption to the generic RepositoryError for handling
-            e @ RepoError::DataCorruption(_) => {
+            e @ RepoError::DataCorruption { .. } => {
                  tracing::error!(error.source = ?e, "Repository data corruption occurred");
                  AppError::RepositoryError(e) // Wrap the specific error
             }
             // Map other backend errors from repo -> generic repository error
             e @ RepoError::BackendError(_) => AppError::RepositoryError(e), // Use '@' binding
+            RepoError::Unavailable(backend) => AppError::ServiceUnavailable(backend.to_string()),
         }
     }
 }
@@ -89,6 +149,7 @@ impl From<StorageError> for AppError {
         match err {
             // Map storage NotFound -> specific AppError ImageNotFound
             StorageError::NotFound(key) => AppError::ImageNotFound(key),
+            StorageError::Unavailable(backend) => AppError::ServiceUnavailable(backend.to_string()),
             // Map other storage errors (UploadFailed, BackendError) -> generic storage error
             e => AppError::StorageError(e), // Wrap the specific error
         }
@@ -106,19 +167,37 @@ impl From<config::ConfigError> for AppError {
 
 // --- Axum Response Implementation ---
 
+/// The JSON body of every error response. Public so [`crate::client`] reads what the
+/// server writes.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ErrorBody {
+    pub error: String,
+    /// What failed per field, for 422 responses.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fields: Option<ValidationErrors>,
+    /// The ID to quote when reporting a server failure.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+}
+
 impl IntoResponse for AppError {
     fn into_response(self) -> Response {
         let (status, error_message) = match &self {
             // 4xx Client Errors
             AppError::InvalidInput(msg) => (StatusCode::BAD_REQUEST, msg.clone()),
-            AppError::MissingFormField(field) => {
-                (StatusCode::BAD_REQUEST, format!("Missing form field: {}", field))
-            }
+            AppError::ValidationFailed(_) => (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                "Validation failed".to_string(),
+            ),
             AppError::MultipartError(e) => (
-                StatusCode::BAD_REQUEST,
+                e.status(), // 413 for bodies over the limit, else 400
                 format!("Invalid multipart form data: {}", e),
             ),
             AppError::InvalidUuid(e) => (StatusCode::BAD_REQUEST, format!("Invalid ID format: {}", e)),
+            AppError::PayloadTooLarge(msg) => (StatusCode::PAYLOAD_TOO_LARGE, msg.clone()),
+            AppError::UnsupportedMediaType(msg) => (StatusCode::UNSUPPORTED_MEDIA_TYPE, msg.clone()),
+            AppError::Unauthorized(msg) => (StatusCode::UNAUTHORIZED, msg.clone()),
+            AppError::Forbidden(msg) => (StatusCode::FORBIDDEN, msg.clone()),
             AppError::MemeNotFound(id) => (
                 StatusCode::NOT_FOUND,
                 format!("Meme metadata not found with ID: {}", id),
@@ -126,6 +205,22 @@ impl IntoResponse for AppError {
             AppError::ImageNotFound(key) => {
                 (StatusCode::NOT_FOUND, format!("Image not found with key: {}", key))
             }
+            AppError::RouteNotFound(path) => (StatusCode::NOT_FOUND, format!("No route for path: {}", path)),
+            AppError::TenantNotFound(tenant) => (StatusCode::NOT_FOUND, format!("Unknown tenant: {}", tenant)),
+            AppError::UploadNotFound(id) => (StatusCode::NOT_FOUND, format!("Upload not found with ID: {}", id)),
+            AppError::ShortlinkNotFound(code) => (StatusCode::NOT_FOUND, format!("Shortlink not found: {}", code)),
+            AppError::ActorNotFound(resource) => (StatusCode::NOT_FOUND, format!("Unknown account: {}", resource)),
+            AppError::VersionNotFound { id, version } => {
+                (StatusCode::NOT_FOUND, format!("Meme {} has no version {}", id, version))
+            }
+            AppError::MethodNotAllowed { method, path } => (
+                StatusCode::METHOD_NOT_ALLOWED,
+                format!("Method {} not allowed for path: {}", method, path),
+            ),
+            AppError::Conflict(msg) => (StatusCode::CONFLICT, msg.clone()),
+            AppError::PreconditionFailed(msg) => (StatusCode::PRECONDITION_FAILED, msg.clone()),
+            AppError::QuotaExceeded(msg) => (StatusCode::TOO_MANY_REQUESTS, msg.clone()),
+            AppError::NotImplemented(msg) => (StatusCode::NOT_IMPLEMENTED, msg.clone()),
 
             // 5xx Server Errors
             AppError::RepositoryError(e) => {
@@ -146,6 +241,17 @@ impl IntoResponse for AppError {
                     "File storage operation failed".to_string(),
                 )
             }
+            AppError::ServiceUnavailable(backend) => {
+                tracing::warn!(backend = %backend, "Rejecting request while circuit breaker is open");
+                (
+                    StatusCode::SERVICE_UNAVAILABLE,
+                    "Service temporarily unavailable, please retry later".to_string(),
+                )
+            }
+            AppError::Timeout(limit) => (
+                StatusCode::GATEWAY_TIMEOUT,
+                format!("Request timed out after {} seconds", limit.as_secs()),
+            ),
             AppError::ConfigError(_msg) => {
                 // Config error was already logged in From trait impl
                 (
@@ -167,13 +273,29 @@ impl IntoResponse for AppError {
                     "An internal server error occurred".to_string(),
                 )
             }
+            AppError::Panicked { .. } => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "An internal server error occurred".to_string(),
+            ),
         };
 
+        // Server errors go to Sentry when configured; client errors are the caller's to fix.
+        // Panics were already reported by Sentry's panic hook, with the panic's stack trace.
+        if status.is_server_error() && !matches!(self, AppError::Panicked { .. } | AppError::NotImplemented(_)) {
+            crate::error_reporting::report(&self);
+        }
+
         // Log the final error response details (excluding sensitive source details logged above)
         tracing::warn!(status = %status, error.message = %error_message, "Responding with error");
 
-        // Format the response body as JSON
-        let body = Json(serde_json::json!({ "error": error_message }));
-        (status, body).into_response()
+        // Format the response body as JSON, including the per-field map for validation errors
+        let mut body = ErrorBody { error: error_message, fields: None, request_id: None };
+        match self {
+            AppError::ValidationFailed(fields) => body.fields = Some(fields),
+            // The ID to quote when reporting the failure
+            AppError::Panicked { request_id } => body.request_id = request_id,
+            _ => {}
+        }
+        (status, Json(body)).into_response()
     }
 }
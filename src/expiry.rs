@@ -0,0 +1,132 @@
+#[cfg(not(feature = "lambda"))]
+use crate::change_stream::{ChangeHandler, ChangeKind, MemeChange};
+use crate::{
+    domain::{FileStorage, MemeHistoryRepository, MemeRepository},
+    errors::{AppError, StorageError},
+    notifications,
+    AppState,
+};
+use serde::Serialize;
+use std::{sync::Arc, time::Duration};
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+
+/// Result of one expired-meme cleanup pass.
+#[derive(Serialize, Debug)]
+pub struct PurgeSummary {
+    /// Memes whose image and metadata were deleted.
+    pub purged: usize,
+    /// Memes that could not be deleted; they are retried on the next pass.
+    pub failed: usize,
+}
+
+/// Deletes the image and then the metadata of every meme past its expiry time. Deleting the
+/// image first means a failed pass leaves the item in place, so the next pass finds it again
+/// instead of orphaning the image. The history of a purged meme goes with it.
+pub async fn purge_expired(
+    repo: &dyn MemeRepository,
+    storage: &dyn FileStorage,
+    history: &dyn MemeHistoryRepository,
+) -> Result<PurgeSummary, AppError> {
+    let expired = repo.list_expired(chrono::Utc::now()).await?;
+    let mut summary = PurgeSummary { purged: 0, failed: 0 };
+
+    for meme in expired {
+        let image_deleted = match storage.delete(&meme.image_key).await {
+            Ok(()) | Err(StorageError::NotFound(_)) => true,
+            Err(e) => {
+                tracing::warn!(meme_id = %meme.meme_id, image_key = %meme.image_key, error = %e, "Failed to delete image of expired meme");
+                false
+            }
+        };
+        if !image_deleted {
+            summary.failed += 1;
+            continue;
+        }
+        match repo.delete(meme.meme_id).await {
+            Ok(()) => {
+                tracing::debug!(meme_id = %meme.meme_id, "Expired meme purged");
+                summary.purged += 1;
+                if let Err(e) = history.delete_versions(meme.meme_id).await {
+                    tracing::warn!(meme_id = %meme.meme_id, error = %e, "Failed to delete history of expired meme");
+                }
+            }
+            Err(e) => {
+                tracing::warn!(meme_id = %meme.meme_id, error = %e, "Failed to delete expired meme");
+                summary.failed += 1;
+            }
+        }
+    }
+
+    if summary.purged > 0 || summary.failed > 0 {
+        tracing::info!(purged = summary.purged, failed = summary.failed, "Expired meme cleanup finished");
+    }
+    Ok(summary)
+}
+
+/// Spawns a task that purges expired memes every `interval`, and emails about memes that
+/// come within `APP_EXPIRY_NOTICE_SECS` of their expiry since the previous pass. Failures
+/// are logged and retried on the next tick. The task exits once `shutdown` is cancelled,
+/// after finishing any pass that is already running.
+#[cfg_attr(feature = "lambda", allow(dead_code))] // Not scheduled on Lambda
+pub fn spawn_expiry_cleanup(
+    state: Arc<AppState>,
+    interval: Duration,
+    shutdown: CancellationToken,
+) -> JoinHandle<()> {
+    tracing::info!(interval_secs = interval.as_secs(), "Scheduling expired meme cleanup");
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        // The first pass also covers the interval before startup, as if the server had been running
+        let mut notified_until = chrono::Utc::now() - interval;
+        loop {
+            tokio::select! {
+                _ = ticker.tick() => {}
+                _ = shutdown.cancelled() => break,
+            }
+            if let Err(e) = purge_expired(state.meme_repo.as_ref(), state.file_storage.as_ref(), state.meme_history.as_ref()).await {
+                tracing::error!(error = %e, "Expired meme cleanup failed");
+            }
+            let now = chrono::Utc::now();
+            match notifications::notify_expiring(&state, notified_until, now).await {
+                Ok(_) => notified_until = now,
+                Err(e) => tracing::error!(error = %e, "Notifying about expiring memes failed"),
+            }
+        }
+    })
+}
+
+/// Deletes the image of a meme that DynamoDB TTL removed before the cleanup job got to it,
+/// which would otherwise leave the image orphaned in storage.
+#[cfg(not(feature = "lambda"))]
+pub struct ExpiredImageCleanup {
+    storage: Arc<dyn FileStorage>,
+}
+
+#[cfg(not(feature = "lambda"))]
+impl ExpiredImageCleanup {
+    pub fn new(storage: Arc<dyn FileStorage>) -> Self {
+        Self { storage }
+    }
+}
+
+#[cfg(not(feature = "lambda"))]
+#[async_trait::async_trait]
+impl ChangeHandler for ExpiredImageCleanup {
+    fn name(&self) -> &'static str {
+        "expired_images"
+    }
+
+    async fn handle(&self, change: &MemeChange) -> anyhow::Result<()> {
+        let Some(meme) = change.old.as_ref().filter(|_| change.kind == ChangeKind::Removed && change.removed_by_ttl) else {
+            return Ok(());
+        };
+        match self.storage.delete(&meme.image_key).await {
+            Ok(()) | Err(StorageError::NotFound(_)) => {
+                tracing::debug!(meme_id = %meme.meme_id, image_key = %meme.image_key, "Deleted image of meme removed by TTL");
+                Ok(())
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+}
@@ -0,0 +1,98 @@
+use crate::{domain::FileStorage, errors::StorageError, models::Meme};
+use async_zip::{tokio::write::ZipFileWriter, Compression, ZipEntryBuilder};
+use axum::body::Body;
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use std::{io, sync::Arc};
+use tokio::io::DuplexStream;
+use tokio_util::{compat::TokioAsyncReadCompatExt, io::ReaderStream};
+
+/// Layout version of the archive, bumped whenever the structure changes incompatibly.
+pub const ARCHIVE_VERSION: u32 = 1;
+/// Name of the metadata entry inside the archive.
+pub const MANIFEST_ENTRY: &str = "manifest.json";
+/// Directory inside the archive that holds image files, named by their image key.
+pub const IMAGES_DIR: &str = "images";
+
+/// Metadata written as `manifest.json` at the end of an export archive.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ExportManifest {
+    pub version: u32,
+    pub memes: Vec<Meme>,
+    /// Image keys referenced by exported memes that were missing from storage.
+    #[serde(default)]
+    pub missing_images: Vec<String>,
+}
+
+/// Returns a response body that streams a ZIP archive of `memes` and their images.
+///
+/// The archive is produced on a background task and piped through a small in-memory
+/// buffer, so images are copied from storage to the client without being held in full.
+/// If the task fails part-way, the body ends with an error so the client sees a broken
+/// transfer rather than a silently truncated archive.
+pub fn export_archive(storage: Arc<dyn FileStorage>, memes: Vec<Meme>) -> Body {
+    let (reader, writer) = tokio::io::duplex(64 * 1024);
+    let task = tokio::spawn(write_archive(storage, memes, writer));
+
+    let outcome = futures::stream::once(async move {
+        match task.await {
+            Ok(Ok(())) => None,
+            Ok(Err(e)) => {
+                tracing::error!(error = %e, "Export archive generation failed");
+                Some(Err(io::Error::other(e)))
+            }
+            Err(e) => Some(Err(io::Error::other(e))),
+        }
+    })
+    .filter_map(futures::future::ready);
+
+    Body::from_stream(ReaderStream::new(reader).chain(outcome))
+}
+
+async fn write_archive(
+    storage: Arc<dyn FileStorage>,
+    memes: Vec<Meme>,
+    writer: DuplexStream,
+) -> anyhow::Result<()> {
+    let mut zip = ZipFileWriter::with_tokio(writer);
+    let mut missing_images = Vec::new();
+
+    for meme in &memes {
+        let (byte_stream, _content_type) = match storage.download(&meme.image_key).await {
+            Ok(download) => download,
+            Err(StorageError::NotFound(key)) => {
+                tracing::warn!(meme_id = %meme.meme_id, image_key = %key, "Image missing during export, skipping file");
+                missing_images.push(key);
+                continue;
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        // Streamed entries are written with a data descriptor, which stream readers can only
+        // handle for compressed entries, so deflate them even though images rarely shrink.
+        let entry = ZipEntryBuilder::new(
+            format!("{}/{}", IMAGES_DIR, meme.image_key).into(),
+            Compression::Deflate,
+        );
+        let mut entry_writer = zip.write_entry_stream(entry).await?;
+        let mut image_reader = byte_stream.into_async_read().compat();
+        futures::io::copy(&mut image_reader, &mut entry_writer).await?;
+        entry_writer.close().await?;
+    }
+
+    let manifest = ExportManifest {
+        version: ARCHIVE_VERSION,
+        memes,
+        missing_images,
+    };
+    let manifest_json = serde_json::to_vec_pretty(&manifest)?;
+    zip.write_entry_whole(
+        ZipEntryBuilder::new(MANIFEST_ENTRY.into(), Compression::Stored),
+        &manifest_json,
+    )
+    .await?;
+    zip.close().await?;
+
+    tracing::info!(memes = manifest.memes.len(), "Export archive written");
+    Ok(())
+}
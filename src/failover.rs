@@ -0,0 +1,240 @@
+//! Failover of the meme table to a second region (`APP_DYNAMODB_SECONDARY_REGION`), for
+//! tables replicated there by DynamoDB Global Tables. A background job pings the primary
+//! region; after `APP_FAILOVER_THRESHOLD` failed checks in a row reads go to the secondary,
+//! and after as many good ones they come back. Writes follow only with
+//! `APP_FAILOVER_WRITES`, since Global Tables resolve conflicting writes in two regions by
+//! last writer wins. While the primary counts as up, a read it fails on is retried in the
+//! secondary, so reads also fail over where the job does not run (Lambda).
+
+use crate::{
+    domain::{ConsistencyLevel, MemeRepository, TableInfo},
+    errors::RepoError,
+    models::{Meme, SortOrder},
+};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use std::{
+    future::Future,
+    sync::{
+        atomic::{AtomicBool, AtomicU32, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+use uuid::Uuid;
+
+/// Which region serves the meme table, decided by health checks of the primary.
+pub struct RegionFailover {
+    primary_region: String,
+    secondary_region: String,
+    primary: Arc<dyn MemeRepository>,
+    primary_up: AtomicBool,
+    threshold: u32,
+    // Checks in a row whose outcome disagrees with `primary_up`
+    streak: AtomicU32,
+}
+
+impl RegionFailover {
+    pub fn new(primary_region: String, secondary_region: String, primary: Arc<dyn MemeRepository>, threshold: u32) -> Self {
+        metrics::gauge!("dynamodb_primary_region_up", "region" => primary_region.clone()).set(1.0);
+        Self {
+            primary_region,
+            secondary_region,
+            primary,
+            primary_up: AtomicBool::new(true),
+            threshold,
+            streak: AtomicU32::new(0),
+        }
+    }
+
+    /// Whether the primary region passed its recent health checks.
+    pub fn primary_up(&self) -> bool {
+        self.primary_up.load(Ordering::Relaxed)
+    }
+
+    /// The region reads go to.
+    pub fn active_region(&self) -> &str {
+        if self.primary_up() { &self.primary_region } else { &self.secondary_region }
+    }
+
+    /// Pings the primary region's table once, failing over or back when enough checks in a
+    /// row disagree with the current state.
+    pub async fn check(&self) {
+        let healthy = match self.primary.ping().await {
+            Ok(()) => true,
+            Err(e) => {
+                tracing::debug!(region = %self.primary_region, error = %e, "Primary region health check failed");
+                false
+            }
+        };
+        if healthy == self.primary_up() {
+            self.streak.store(0, Ordering::Relaxed);
+            return;
+        }
+        if self.streak.fetch_add(1, Ordering::Relaxed) + 1 < self.threshold {
+            return;
+        }
+        self.streak.store(0, Ordering::Relaxed);
+        self.primary_up.store(healthy, Ordering::Relaxed);
+        metrics::gauge!("dynamodb_primary_region_up", "region" => self.primary_region.clone()).set(if healthy { 1.0 } else { 0.0 });
+        if healthy {
+            metrics::counter!("dynamodb_region_failovers_total", "to" => "primary").increment(1);
+            tracing::info!(region = %self.primary_region, "Primary region is healthy again; failing back");
+        } else {
+            metrics::counter!("dynamodb_region_failovers_total", "to" => "secondary").increment(1);
+            tracing::warn!(
+                primary_region = %self.primary_region,
+                secondary_region = %self.secondary_region,
+                failed_checks = self.threshold,
+                "Primary region is unhealthy; failing over to the secondary region"
+            );
+        }
+    }
+}
+
+/// Spawns a task checking the primary region every `interval`. The task exits once
+/// `shutdown` is cancelled.
+#[cfg_attr(feature = "lambda", allow(dead_code))] // Not scheduled on Lambda
+pub fn spawn_health_checks(failover: Arc<RegionFailover>, interval: Duration, shutdown: CancellationToken) -> JoinHandle<()> {
+    tracing::info!(
+        interval_secs = interval.as_secs(),
+        primary_region = %failover.primary_region,
+        secondary_region = %failover.secondary_region,
+        "Scheduling health checks of the primary DynamoDB region"
+    );
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            tokio::select! {
+                _ = ticker.tick() => {}
+                _ = shutdown.cancelled() => break,
+            }
+            failover.check().await;
+        }
+    })
+}
+
+/// Whether a call failed because its region could not serve it, rather than on the data.
+fn region_failure(e: &RepoError) -> bool {
+    matches!(e, RepoError::BackendError(_) | RepoError::Unavailable(_))
+}
+
+/// Sends each call to the region [`RegionFailover`] picks. Both repositories are decorated
+/// on their own, with their own breakers.
+pub struct FailoverMemeRepository {
+    primary: Arc<dyn MemeRepository>,
+    secondary: Arc<dyn MemeRepository>,
+    failover: Arc<RegionFailover>,
+    fail_over_writes: bool,
+}
+
+impl FailoverMemeRepository {
+    pub fn new(
+        primary: Arc<dyn MemeRepository>,
+        secondary: Arc<dyn MemeRepository>,
+        failover: Arc<RegionFailover>,
+        fail_over_writes: bool,
+    ) -> Self {
+        Self { primary, secondary, failover, fail_over_writes }
+    }
+
+    /// Reads from the active region; a read the primary fails on is retried in the secondary.
+    async fn read<T, F, Fut>(&self, operation: &'static str, call: F) -> Result<T, RepoError>
+    where
+        F: Fn(Arc<dyn MemeRepository>) -> Fut,
+        Fut: Future<Output = Result<T, RepoError>>,
+    {
+        if !self.failover.primary_up() {
+            return call(self.secondary.clone()).await;
+        }
+        match call(self.primary.clone()).await {
+            Err(e) if region_failure(&e) => {
+                metrics::counter!("dynamodb_failover_reads_total", "operation" => operation).increment(1);
+                tracing::warn!(
+                    operation,
+                    region = %self.failover.secondary_region,
+                    error = %e,
+                    "Primary region failed a read; retrying in the secondary region"
+                );
+                call(self.secondary.clone()).await
+            }
+            result => result,
+        }
+    }
+
+    /// The repository writes go to: the secondary only while failed over with
+    /// `APP_FAILOVER_WRITES`.
+    fn writer(&self) -> &dyn MemeRepository {
+        if self.fail_over_writes && !self.failover.primary_up() {
+            self.secondary.as_ref()
+        } else {
+            self.primary.as_ref()
+        }
+    }
+}
+
+#[async_trait]
+impl MemeRepository for FailoverMemeRepository {
+    async fn create(&self, meme: &Meme) -> Result<(), RepoError> {
+        self.writer().create(meme).await
+    }
+
+    async fn get_by_id(&self, id: Uuid, consistency: ConsistencyLevel) -> Result<Option<Meme>, RepoError> {
+        self.read("get_by_id", |repo| async move { repo.get_by_id(id, consistency).await }).await
+    }
+
+    async fn exists(&self, id: Uuid) -> Result<bool, RepoError> {
+        self.read("exists", |repo| async move { repo.exists(id).await }).await
+    }
+
+    async fn list_all(&self) -> Result<Vec<Meme>, RepoError> {
+        self.read("list_all", |repo| async move { repo.list_all().await }).await
+    }
+
+    async fn list_sorted(&self, order: SortOrder) -> Result<Vec<Meme>, RepoError> {
+        self.read("list_sorted", |repo| async move { repo.list_sorted(order).await }).await
+    }
+
+    async fn list_expired(&self, now: DateTime<Utc>) -> Result<Vec<Meme>, RepoError> {
+        self.read("list_expired", |repo| async move { repo.list_expired(now).await }).await
+    }
+
+    async fn list_due_for_publishing(&self, now: DateTime<Utc>) -> Result<Vec<Meme>, RepoError> {
+        self.read("list_due_for_publishing", |repo| async move { repo.list_due_for_publishing(now).await }).await
+    }
+
+    async fn update(&self, meme: &Meme, expected_version: u64) -> Result<(), RepoError> {
+        self.writer().update(meme, expected_version).await
+    }
+
+    async fn add_like(&self, id: Uuid) -> Result<u64, RepoError> {
+        self.writer().add_like(id).await
+    }
+
+    async fn add_views(&self, id: Uuid, views: u64) -> Result<(), RepoError> {
+        self.writer().add_views(id, views).await
+    }
+
+    async fn delete(&self, id: Uuid) -> Result<(), RepoError> {
+        self.writer().delete(id).await
+    }
+
+    async fn describe(&self) -> Result<TableInfo, RepoError> {
+        self.read("describe", |repo| async move { repo.describe().await }).await
+    }
+
+    async fn count_created_since(&self, since: DateTime<Utc>) -> Result<u64, RepoError> {
+        self.read("count_created_since", |repo| async move { repo.count_created_since(since).await }).await
+    }
+
+    async fn count(&self) -> Result<u64, RepoError> {
+        self.read("count", |repo| async move { repo.count().await }).await
+    }
+
+    /// Checks the region reads go to, so `/health` stays ok while the secondary serves.
+    async fn ping(&self) -> Result<(), RepoError> {
+        if self.failover.primary_up() { self.primary.ping().await } else { self.secondary.ping().await }
+    }
+}
@@ -0,0 +1,290 @@
+use reqwest::{
+    dns::{Addrs, Name, Resolve, Resolving},
+    redirect, Client, Url,
+};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum FetchError {
+    #[error("invalid URL: {0}")]
+    InvalidUrl(String),
+    #[error("URL points to a disallowed address")]
+    BlockedAddress,
+    #[error("remote image exceeds the {0} byte limit")]
+    TooLarge(usize),
+    #[error("remote server responded with status {0}")]
+    HttpStatus(u16),
+    #[error("remote content is not a supported image (declared type: {0})")]
+    UnsupportedContent(String),
+    #[error("request timed out")]
+    Timeout,
+    #[error("request failed: {0}")]
+    Request(reqwest::Error),
+}
+
+impl From<reqwest::Error> for FetchError {
+    fn from(err: reqwest::Error) -> Self {
+        if err.is_timeout() {
+            return FetchError::Timeout;
+        }
+        // Surface addresses refused by `PublicOnlyResolver` as their own error
+        let mut source = std::error::Error::source(&err);
+        while let Some(cause) = source {
+            if cause.is::<BlockedAddressError>() {
+                return FetchError::BlockedAddress;
+            }
+            source = cause.source();
+        }
+        FetchError::Request(err)
+    }
+}
+
+/// Image downloaded from a remote URL. `content_type` is sniffed from the bytes,
+/// not taken from the remote server's headers.
+#[derive(Debug)]
+pub struct FetchedImage {
+    pub data: Vec<u8>,
+    pub content_type: String,
+    pub filename: String,
+}
+
+/// Downloads images from client-supplied URLs with size and time limits.
+///
+/// Only `http`/`https` URLs are accepted and redirects are not followed. Host names are
+/// resolved through [`PublicOnlyResolver`], so the addresses actually connected to are
+/// the ones that were checked, and loopback/private/link-local targets are refused.
+#[derive(Debug, Clone)]
+pub struct UrlFetcher {
+    client: Client,
+    max_bytes: usize,
+}
+
+impl UrlFetcher {
+    pub fn new(timeout: Duration, max_bytes: usize) -> Result<Self, reqwest::Error> {
+        let client = Client::builder()
+            .timeout(timeout)
+            .redirect(redirect::Policy::none())
+            .dns_resolver(Arc::new(PublicOnlyResolver))
+            // A proxy would resolve hosts itself, past the resolver's checks
+            .no_proxy()
+            .build()?;
+        Ok(Self { client, max_bytes })
+    }
+
+    /// Fetches `url`, streaming the body and aborting once it exceeds the size cap.
+    pub async fn fetch(&self, url: &str) -> Result<FetchedImage, FetchError> {
+        let url = Url::parse(url).map_err(|e| FetchError::InvalidUrl(e.to_string()))?;
+        check_url(&url)?;
+
+        tracing::debug!(%url, "Fetching remote image");
+        let mut response = self.client.get(url.clone()).send().await?;
+        if !response.status().is_success() {
+            return Err(FetchError::HttpStatus(response.status().as_u16()));
+        }
+        if response.content_length().is_some_and(|len| len > self.max_bytes as u64) {
+            return Err(FetchError::TooLarge(self.max_bytes));
+        }
+
+        let declared_type = response
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or("none")
+            .to_string();
+
+        let mut data = Vec::new();
+        while let Some(chunk) = response.chunk().await? {
+            if data.len() + chunk.len() > self.max_bytes {
+                return Err(FetchError::TooLarge(self.max_bytes));
+            }
+            data.extend_from_slice(&chunk);
+        }
+
+        // Trust the bytes, not the remote headers
+        let Some((content_type, extension)) = sniff_image_type(&data) else {
+            return Err(FetchError::UnsupportedContent(declared_type));
+        };
+        let stem = url
+            .path_segments()
+            .and_then(|mut segments| segments.next_back())
+            .and_then(|name| name.split('.').next())
+            .filter(|stem| !stem.is_empty())
+            .unwrap_or("image");
+
+        Ok(FetchedImage {
+            data,
+            content_type: content_type.to_string(),
+            filename: format!("{}.{}", stem, extension),
+        })
+    }
+}
+
+/// Identifies common image formats by their magic bytes. Returns the MIME type and extension.
+pub fn sniff_image_type(data: &[u8]) -> Option<(&'static str, &'static str)> {
+    match data {
+        [0xFF, 0xD8, 0xFF, ..] => Some(("image/jpeg", "jpg")),
+        [0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A, ..] => Some(("image/png", "png")),
+        [b'G', b'I', b'F', b'8', b'7' | b'9', b'a', ..] => Some(("image/gif", "gif")),
+        [b'R', b'I', b'F', b'F', _, _, _, _, b'W', b'E', b'B', b'P', ..] => Some(("image/webp", "webp")),
+        _ => None,
+    }
+}
+
+/// Marker error returned by [`PublicOnlyResolver`] when a host only resolves to blocked addresses.
+#[derive(Error, Debug)]
+#[error("host resolves only to disallowed addresses")]
+struct BlockedAddressError;
+
+/// DNS resolver that drops non-public addresses from lookups, preventing DNS-based SSRF.
+pub(crate) struct PublicOnlyResolver;
+
+impl Resolve for PublicOnlyResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        Box::pin(async move {
+            let host = name.as_str().to_string();
+            let resolved: Vec<SocketAddr> = tokio::net::lookup_host((host.as_str(), 0))
+                .await?
+                .collect();
+            let allowed: Vec<SocketAddr> = resolved
+                .iter()
+                .copied()
+                .filter(|addr| check_ip(addr.ip()).is_ok())
+                .collect();
+            if allowed.is_empty() {
+                tracing::warn!(%host, ?resolved, "Refusing to fetch from host resolving to disallowed addresses");
+                return Err(Box::new(BlockedAddressError) as Box<dyn std::error::Error + Send + Sync>);
+            }
+            Ok(Box::new(allowed.into_iter()) as Addrs)
+        })
+    }
+}
+
+/// Rejects non-HTTP schemes and hosts that obviously point at internal services.
+pub(crate) fn check_url(url: &Url) -> Result<(), FetchError> {
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(FetchError::InvalidUrl(format!("unsupported scheme '{}'", url.scheme())));
+    }
+    match url.host() {
+        None => Err(FetchError::InvalidUrl("missing host".to_string())),
+        Some(url::Host::Domain(domain)) => {
+            let domain = domain.trim_end_matches('.').to_ascii_lowercase();
+            if domain == "localhost" || domain.ends_with(".localhost") {
+                Err(FetchError::BlockedAddress)
+            } else {
+                Ok(())
+            }
+        }
+        Some(url::Host::Ipv4(ip)) => check_ip(IpAddr::V4(ip)),
+        Some(url::Host::Ipv6(ip)) => check_ip(IpAddr::V6(ip)),
+    }
+}
+
+/// Returns an error for loopback, private, link-local, multicast, reserved and other
+/// non-public addresses, including IPv6 addresses that embed one.
+fn check_ip(ip: IpAddr) -> Result<(), FetchError> {
+    let blocked = match ip {
+        IpAddr::V4(v4) => {
+            v4.octets()[0] == 0 // 0.0.0.0/8, "this network"
+                || v4.is_loopback()
+                || v4.is_private()
+                || v4.is_link_local()
+                || v4.is_unspecified()
+                || v4.is_multicast() // 224.0.0.0/4
+                || v4.octets()[0] >= 240 // 240.0.0.0/4 reserved, and the broadcast address
+                || v4.is_documentation()
+                || v4.octets()[0] == 100 && (v4.octets()[1] & 0xc0) == 64 // 100.64.0.0/10 (CGNAT)
+                || v4.octets()[0] == 198 && (v4.octets()[1] & 0xfe) == 18 // 198.18.0.0/15 benchmarking
+        }
+        IpAddr::V6(v6) => {
+            if let Some(v4) = v6.to_ipv4_mapped() {
+                return check_ip(IpAddr::V4(v4));
+            }
+            // IPv4-compatible (::a.b.c.d) and NAT64 (64:ff9b::a.b.c.d) addresses reach the
+            // embedded IPv4 address
+            let prefix = &v6.segments()[..6];
+            let compatible = prefix == [0; 6] && !v6.is_loopback() && !v6.is_unspecified();
+            if compatible || prefix == [0x64, 0xff9b, 0, 0, 0, 0] {
+                let octets = v6.octets();
+                return check_ip(IpAddr::V4(Ipv4Addr::new(octets[12], octets[13], octets[14], octets[15])));
+            }
+            let octets = v6.octets();
+            // 6to4 (2002:a.b.c.d::/48) reaches the IPv4 address after the prefix
+            if v6.segments()[0] == 0x2002 {
+                return check_ip(IpAddr::V4(Ipv4Addr::new(octets[2], octets[3], octets[4], octets[5])));
+            }
+            // Teredo (2001:0::/32) names its server's IPv4 address after the prefix and the
+            // client's, inverted, in the last 32 bits
+            if v6.segments()[..2] == [0x2001, 0] {
+                let server = Ipv4Addr::new(octets[4], octets[5], octets[6], octets[7]);
+                let client = Ipv4Addr::new(!octets[12], !octets[13], !octets[14], !octets[15]);
+                return check_ip(IpAddr::V4(server)).and_then(|()| check_ip(IpAddr::V4(client)));
+            }
+            v6.is_loopback()
+                || v6.is_multicast() // ff00::/8
+                || v6.is_unspecified()
+                || (v6.segments()[0] & 0xfe00) == 0xfc00 // fc00::/7 unique local
+                || (v6.segments()[0] & 0xffc0) == 0xfe80 // fe80::/10 link-local
+        }
+    };
+    if blocked { Err(FetchError::BlockedAddress) } else { Ok(()) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn blocked(ip: &str) -> bool {
+        matches!(check_ip(ip.parse().unwrap()), Err(FetchError::BlockedAddress))
+    }
+
+    #[test]
+    fn check_ip_blocks_non_public_ipv4() {
+        for ip in ["0.0.0.0", "0.1.2.3", "127.0.0.1", "10.0.0.1", "172.16.0.1", "192.168.1.1", "169.254.169.254", "100.64.0.1", "255.255.255.255"] {
+            assert!(blocked(ip), "{} should be blocked", ip);
+        }
+        for ip in ["224.0.0.1", "239.255.255.250", "240.0.0.1", "254.1.2.3", "198.18.0.1", "198.19.255.254"] {
+            assert!(blocked(ip), "{} should be blocked", ip);
+        }
+        for ip in ["93.184.216.34", "198.17.255.255", "198.20.0.1", "223.255.255.255"] {
+            assert!(!blocked(ip), "{} should be allowed", ip);
+        }
+    }
+
+    #[test]
+    fn check_ip_blocks_ipv6_wrapping_private_ipv4() {
+        for ip in ["::ffff:127.0.0.1", "::10.0.0.1", "::169.254.169.254", "64:ff9b::192.168.0.1", "64:ff9b::7f00:1"] {
+            assert!(blocked(ip), "{} should be blocked", ip);
+        }
+        // 6to4, and Teredo with a private server or (inverted) client address
+        for ip in ["2002:7f00:1::", "2002:a00:1::1", "2002:c0a8:101:1::1", "2001:0:a00:1::5db8:2bdd", "2001:0:5db8:d822::f5ff:fffe"] {
+            assert!(blocked(ip), "{} should be blocked", ip);
+        }
+        assert!(!blocked("::ffff:93.184.216.34"));
+        assert!(!blocked("64:ff9b::93.184.216.34"));
+        assert!(!blocked("2002:5db8:d822::1"));
+        assert!(!blocked("2001:0:5db8:d822::a247:27dd"));
+    }
+
+    #[test]
+    fn check_ip_blocks_non_public_ipv6() {
+        for ip in ["::", "::1", "fc00::1", "fd12:3456::1", "fe80::1", "ff02::1", "ff0e::101"] {
+            assert!(blocked(ip), "{} should be blocked", ip);
+        }
+        assert!(!blocked("2606:4700::1111"));
+    }
+
+    #[test]
+    fn check_url_rejects_other_schemes_and_internal_hosts() {
+        let check = |url: &str| check_url(&Url::parse(url).unwrap());
+        assert!(matches!(check("ftp://example.com/a.png"), Err(FetchError::InvalidUrl(_))));
+        assert!(matches!(check("file:///etc/passwd"), Err(FetchError::InvalidUrl(_))));
+        for url in ["http://localhost/a.png", "http://api.localhost./a.png", "http://127.0.0.1/", "http://[::1]/", "http://[::ffff:10.0.0.1]/", "http://0.0.0.0:8080/"] {
+            assert!(matches!(check(url), Err(FetchError::BlockedAddress)), "{} should be blocked", url);
+        }
+        assert!(check("https://example.com/a.png").is_ok());
+        assert!(check("http://93.184.216.34/a.png").is_ok());
+    }
+}
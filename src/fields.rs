@@ -0,0 +1,67 @@
+use crate::errors::AppError;
+use serde::{ser::Error as _, Deserialize, Serialize, Serializer};
+use std::collections::BTreeSet;
+
+/// Query string of endpoints that support sparse fieldsets, e.g. `?fields=meme_id,title`.
+#[derive(Deserialize, Debug, Default)]
+pub struct FieldsQuery {
+    pub fields: Option<String>,
+}
+
+impl FieldsQuery {
+    /// Parses `fields` against the fields of the resource. `None` means all fields.
+    pub fn parse(&self, available: &'static [&'static str]) -> Result<Option<FieldSet>, AppError> {
+        self.fields.as_deref().map(|raw| FieldSet::parse(raw, available)).transpose()
+    }
+}
+
+/// A validated selection of top-level fields to include in a response.
+#[derive(Debug, Clone)]
+pub struct FieldSet(BTreeSet<&'static str>);
+
+impl FieldSet {
+    /// Parses a comma-separated list, rejecting names that are not in `available`.
+    pub fn parse(raw: &str, available: &'static [&'static str]) -> Result<Self, AppError> {
+        let mut selected = BTreeSet::new();
+        for name in raw.split(',').map(str::trim).filter(|name| !name.is_empty()) {
+            let field = available.iter().find(|field| **field == name).ok_or_else(|| {
+                AppError::InvalidInput(format!("Unknown field '{}'; available fields: {}", name, available.join(", ")))
+            })?;
+            selected.insert(*field);
+        }
+        if selected.is_empty() {
+            return Err(AppError::InvalidInput("fields must name at least one field".to_string()));
+        }
+        Ok(Self(selected))
+    }
+
+    pub fn contains(&self, field: &str) -> bool {
+        self.0.contains(field)
+    }
+}
+
+/// Serializes `value` with only the selected top-level fields, or in full without a selection.
+/// Works for any struct that serializes to a map, so no per-combination types are needed.
+pub struct Sparse<'a, T> {
+    value: &'a T,
+    fields: Option<&'a FieldSet>,
+}
+
+impl<'a, T> Sparse<'a, T> {
+    pub fn new(value: &'a T, fields: Option<&'a FieldSet>) -> Self {
+        Self { value, fields }
+    }
+}
+
+impl<T: Serialize> Serialize for Sparse<'_, T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let Some(fields) = self.fields else {
+            return self.value.serialize(serializer);
+        };
+        let mut value = serde_json::to_value(self.value).map_err(S::Error::custom)?;
+        if let serde_json::Value::Object(map) = &mut value {
+            map.retain(|key, _| fields.contains(key));
+        }
+        value.serialize(serializer)
+    }
+}
@@ -0,0 +1,259 @@
+use crate::{
+    config::Config,
+    domain::{FileStorage, ObjectMetadata, StoredObject, UploadOptions},
+    errors::{AppError, StorageError},
+};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use aws_sdk_s3::primitives::ByteStream;
+use axum::body::Bytes;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    io::ErrorKind,
+    path::{Component, Path, PathBuf},
+    time::Duration,
+};
+use tracing::info;
+use uuid::Uuid;
+
+/// Suffix of the file next to each image holding its content type, metadata and ETag.
+const SIDECAR_SUFFIX: &str = ".meta.json";
+/// Suffix of files still being written; renamed into place once complete.
+const TEMP_SUFFIX: &str = ".tmp";
+
+/// What S3 would keep with the object, stored in the sidecar file.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Sidecar {
+    content_type: Option<String>,
+    #[serde(default)]
+    metadata: Vec<(String, String)>,
+    etag: Option<String>,
+}
+
+/// Implements `FileStorage` on a local directory, for runs without any cloud storage.
+/// Keys are paths under `APP_FILESYSTEM_ROOT` (plus the tenant prefix); files are written
+/// to a temporary name and renamed, so readers never see half an image.
+#[derive(Debug, Clone)]
+pub struct FilesystemStorage {
+    root: PathBuf, // Includes the tenant prefix
+}
+
+impl FilesystemStorage {
+    pub fn from_config(config: &Config) -> Self {
+        let root = config.filesystem_root.join(config.s3_key_prefix());
+        info!(root = %root.display(), "Initializing FilesystemStorage");
+        Self { root }
+    }
+
+    /// The file of `key`, or `None` if the key would leave the root (`..`, absolute paths)
+    /// or collide with the sidecar and temporary files.
+    fn path_of(&self, key: &str) -> Option<PathBuf> {
+        let path = Path::new(key);
+        let is_plain = !key.is_empty() && path.components().all(|component| matches!(component, Component::Normal(_)));
+        let is_reserved = key.ends_with(SIDECAR_SUFFIX) || key.ends_with(TEMP_SUFFIX);
+        (is_plain && !is_reserved).then(|| self.root.join(path))
+    }
+
+    fn existing_path_of(&self, key: &str) -> Result<PathBuf, StorageError> {
+        self.path_of(key).ok_or_else(|| StorageError::NotFound(key.to_string()))
+    }
+
+    async fn read_sidecar(&self, path: &Path) -> Sidecar {
+        // Images copied into the directory by hand have no sidecar
+        match tokio::fs::read(sidecar_path(path)).await {
+            Ok(data) => serde_json::from_slice(&data).unwrap_or_default(),
+            Err(_) => Sidecar::default(),
+        }
+    }
+}
+
+fn sidecar_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(SIDECAR_SUFFIX);
+    PathBuf::from(name)
+}
+
+fn io_error(error: std::io::Error, key: &str, context: &str) -> StorageError {
+    if error.kind() == ErrorKind::NotFound {
+        StorageError::NotFound(key.to_string())
+    } else {
+        StorageError::BackendError(anyhow::Error::new(error).context(format!("Filesystem: {} '{}'", context, key)))
+    }
+}
+
+/// Writes `data` next to `path` and renames it into place.
+async fn write_atomically(path: &Path, data: &[u8]) -> std::io::Result<()> {
+    let mut temp = path.as_os_str().to_owned();
+    temp.push(format!(".{}{}", Uuid::new_v4().simple(), TEMP_SUFFIX));
+    let temp = PathBuf::from(temp);
+    tokio::fs::write(&temp, data).await?;
+    if let Err(e) = tokio::fs::rename(&temp, path).await {
+        let _ = tokio::fs::remove_file(&temp).await;
+        return Err(e);
+    }
+    Ok(())
+}
+
+/// Creates the storage directory in `create` mode, or checks it exists.
+pub async fn ensure_root(config: &Config, create: bool) -> Result<(), AppError> {
+    let root = FilesystemStorage::from_config(config).root;
+    if create {
+        tokio::fs::create_dir_all(&root)
+            .await
+            .map_err(|e| AppError::InitError(format!("Failed to create image directory '{}': {}", root.display(), e)))?;
+    } else if !tokio::fs::metadata(&root).await.is_ok_and(|metadata| metadata.is_dir()) {
+        return Err(AppError::InitError(format!(
+            "Image directory '{}' does not exist. Create it, or set APP_RESOURCE_INIT=create",
+            root.display()
+        )));
+    }
+    info!(root = %root.display(), "Image directory {}.", if create { "created" } else { "verified" });
+    Ok(())
+}
+
+#[async_trait]
+impl FileStorage for FilesystemStorage {
+    /// Writes the image, then its sidecar. The ETag is the SHA-256 of the contents. Tags
+    /// have no place on a filesystem and are dropped.
+    async fn upload(&self, key: &str, data: Bytes, options: UploadOptions) -> Result<(), StorageError> {
+        let path = self.path_of(key).ok_or_else(|| StorageError::UploadFailed(format!("Filesystem: Invalid key '{}'", key)))?;
+        let upload_error = |e: std::io::Error| StorageError::UploadFailed(format!("Filesystem: Failed to write '{}': {}", key, e));
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await.map_err(upload_error)?;
+        }
+        let sidecar = Sidecar {
+            content_type: options.content_type,
+            metadata: options.metadata,
+            etag: Some(format!("\"{}\"", hex::encode(Sha256::digest(&data)))),
+        };
+        let sidecar = serde_json::to_vec(&sidecar).map_err(|e| StorageError::UploadFailed(format!("Filesystem: {}", e)))?;
+        write_atomically(&path, &data).await.map_err(upload_error)?;
+        write_atomically(&sidecar_path(&path), &sidecar).await.map_err(upload_error)?;
+        tracing::debug!(key = %key, "Filesystem: Upload successful");
+        Ok(())
+    }
+
+    async fn download(&self, key: &str) -> Result<(ByteStream, ObjectMetadata), StorageError> {
+        let path = self.existing_path_of(key)?;
+        let data = tokio::fs::read(&path).await.map_err(|e| io_error(e, key, "Failed to read"))?;
+        let modified = tokio::fs::metadata(&path).await.and_then(|file| file.modified()).ok();
+        let sidecar = self.read_sidecar(&path).await;
+        let metadata = ObjectMetadata {
+            content_type: sidecar.content_type,
+            content_length: Some(data.len() as u64),
+            etag: sidecar.etag,
+            last_modified: modified.map(DateTime::<Utc>::from),
+        };
+        Ok((ByteStream::from(data), metadata))
+    }
+
+    async fn head(&self, key: &str) -> Result<ObjectMetadata, StorageError> {
+        let path = self.existing_path_of(key)?;
+        let file = tokio::fs::metadata(&path).await.map_err(|e| io_error(e, key, "Failed to read metadata of"))?;
+        if !file.is_file() {
+            return Err(StorageError::NotFound(key.to_string()));
+        }
+        let sidecar = self.read_sidecar(&path).await;
+        Ok(ObjectMetadata {
+            content_type: sidecar.content_type,
+            content_length: Some(file.len()),
+            etag: sidecar.etag,
+            last_modified: file.modified().ok().map(DateTime::<Utc>::from),
+        })
+    }
+
+    /// Walks the directory tree, leaving out sidecar and temporary files.
+    async fn list(&self) -> Result<Vec<StoredObject>, StorageError> {
+        let mut objects = Vec::new();
+        let mut directories = vec![self.root.clone()];
+        while let Some(directory) = directories.pop() {
+            let mut entries = match tokio::fs::read_dir(&directory).await {
+                Ok(entries) => entries,
+                // Nothing was uploaded yet
+                Err(e) if e.kind() == ErrorKind::NotFound => continue,
+                Err(e) => return Err(io_error(e, &directory.display().to_string(), "Failed to list")),
+            };
+            let list_error = |e| io_error(e, &directory.display().to_string(), "Failed to list");
+            while let Some(entry) = entries.next_entry().await.map_err(list_error)? {
+                let file_type = entry.file_type().await.map_err(list_error)?;
+                let path = entry.path();
+                if file_type.is_dir() {
+                    directories.push(path);
+                    continue;
+                }
+                let Some(key) = path.strip_prefix(&self.root).ok().and_then(Path::to_str) else { continue };
+                if key.ends_with(SIDECAR_SUFFIX) || key.ends_with(TEMP_SUFFIX) {
+                    continue;
+                }
+                let size = entry.metadata().await.map_err(list_error)?.len();
+                objects.push(StoredObject { key: key.replace(std::path::MAIN_SEPARATOR, "/"), size });
+            }
+        }
+        Ok(objects)
+    }
+
+    /// There is nothing to sign: the image is served by the app's own `/images/{key}` route,
+    /// which applies the usual access checks.
+    async fn presigned_url(&self, key: &str, _expires_in: Duration) -> Result<String, StorageError> {
+        Ok(format!("/images/{}", key))
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), StorageError> {
+        let Some(path) = self.path_of(key) else { return Ok(()) };
+        for path in [sidecar_path(&path), path] {
+            match tokio::fs::remove_file(&path).await {
+                // Deleting a missing object succeeds on S3; match that
+                Err(e) if e.kind() != ErrorKind::NotFound => return Err(io_error(e, key, "Failed to delete")),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Copies the image and its sidecar, each written to a temporary name and renamed.
+    async fn copy(&self, from: &str, to: &str) -> Result<(), StorageError> {
+        let source = self.existing_path_of(from)?;
+        let target = self.path_of(to).ok_or_else(|| StorageError::UploadFailed(format!("Filesystem: Invalid key '{}'", to)))?;
+        let data = tokio::fs::read(&source).await.map_err(|e| io_error(e, from, "Failed to read"))?;
+        let sidecar = tokio::fs::read(sidecar_path(&source)).await.ok();
+        let copy_error = |e: std::io::Error| StorageError::UploadFailed(format!("Filesystem: Failed to write '{}': {}", to, e));
+        if let Some(parent) = target.parent() {
+            tokio::fs::create_dir_all(parent).await.map_err(copy_error)?;
+        }
+        write_atomically(&target, &data).await.map_err(copy_error)?;
+        if let Some(sidecar) = sidecar {
+            write_atomically(&sidecar_path(&target), &sidecar).await.map_err(copy_error)?;
+        }
+        Ok(())
+    }
+
+    /// Renames the image, then its sidecar. Each rename is atomic, so the image is always
+    /// under one of the two keys.
+    async fn rename(&self, from: &str, to: &str) -> Result<(), StorageError> {
+        let source = self.existing_path_of(from)?;
+        let target = self.path_of(to).ok_or_else(|| StorageError::UploadFailed(format!("Filesystem: Invalid key '{}'", to)))?;
+        if let Some(parent) = target.parent() {
+            tokio::fs::create_dir_all(parent)
+                .await
+                .map_err(|e| StorageError::UploadFailed(format!("Filesystem: Failed to write '{}': {}", to, e)))?;
+        }
+        tokio::fs::rename(&source, &target).await.map_err(|e| io_error(e, from, "Failed to move"))?;
+        match tokio::fs::rename(sidecar_path(&source), sidecar_path(&target)).await {
+            // Images copied into the directory by hand have no sidecar
+            Err(e) if e.kind() != ErrorKind::NotFound => Err(io_error(e, from, "Failed to move the sidecar of")),
+            _ => Ok(()),
+        }
+    }
+
+    /// Checks the directory still exists.
+    async fn ping(&self) -> Result<(), StorageError> {
+        match tokio::fs::metadata(&self.root).await {
+            Ok(metadata) if metadata.is_dir() => Ok(()),
+            _ => Err(StorageError::BackendError(anyhow::anyhow!(
+                "Filesystem: Image directory '{}' is not accessible",
+                self.root.display()
+            ))),
+        }
+    }
+}
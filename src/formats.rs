@@ -0,0 +1,203 @@
+use crate::errors::AppError;
+use axum::{
+    body::{to_bytes, Body, Bytes},
+    extract::{FromRequest, Request},
+    http::{header, HeaderMap, HeaderValue},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::de::DeserializeOwned;
+
+pub const MSGPACK: &str = "application/msgpack";
+pub const CBOR: &str = "application/cbor";
+
+/// Body formats understood by the JSON endpoints.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    Json,
+    MessagePack,
+    Cbor,
+}
+
+impl Format {
+    /// Maps a media type (without parameters) to a format. Wildcards mean the JSON default.
+    fn from_media_type(media_type: &str) -> Option<Self> {
+        match media_type.trim().to_ascii_lowercase().as_str() {
+            "application/json" | "application/*" | "*/*" => Some(Format::Json),
+            MSGPACK | "application/x-msgpack" | "application/vnd.msgpack" => Some(Format::MessagePack),
+            CBOR => Some(Format::Cbor),
+            _ => None,
+        }
+    }
+
+    /// The format of a request body, from its `Content-Type` header.
+    fn of_request(headers: &HeaderMap) -> Option<Self> {
+        let content_type = headers.get(header::CONTENT_TYPE)?.to_str().ok()?;
+        let media_type = content_type.split(';').next().unwrap_or_default();
+        // Only concrete types describe a body
+        Self::from_media_type(media_type).filter(|_| !media_type.contains('*'))
+    }
+
+    /// The format preferred by the `Accept` header: the supported media type with the highest
+    /// quality, the earliest on ties. JSON when the header is absent or names nothing we speak.
+    pub fn preferred(headers: &HeaderMap) -> Self {
+        let mut best: Option<(Format, f32)> = None;
+        for (media_type, quality) in accepted(headers) {
+            let Some(format) = Self::from_media_type(media_type) else {
+                continue;
+            };
+            if quality > 0.0 && best.is_none_or(|(_, best_quality)| quality > best_quality) {
+                best = Some((format, quality));
+            }
+        }
+        best.map_or(Format::Json, |(format, _)| format)
+    }
+}
+
+/// Whether the `Accept` header asks for HTML at least as much as for any body format, as
+/// browsers and link preview crawlers do. `*/*` alone means JSON.
+pub fn prefers_html(headers: &HeaderMap) -> bool {
+    let (mut html, mut other) = (0.0f32, 0.0f32);
+    for (media_type, quality) in accepted(headers) {
+        if matches!(media_type.trim().to_ascii_lowercase().as_str(), "text/html" | "application/xhtml+xml") {
+            html = html.max(quality);
+        } else if Format::from_media_type(media_type).is_some() {
+            other = other.max(quality);
+        }
+    }
+    html > 0.0 && html >= other
+}
+
+/// The media ranges of the `Accept` header with their quality (1 when not given).
+fn accepted(headers: &HeaderMap) -> Vec<(&str, f32)> {
+    let Some(accept) = headers.get(header::ACCEPT).and_then(|v| v.to_str().ok()) else {
+        return Vec::new();
+    };
+    accept
+        .split(',')
+        .map(|range| {
+            let mut params = range.split(';');
+            let media_type = params.next().unwrap_or_default();
+            let quality = params
+                .find_map(|param| param.trim().strip_prefix("q="))
+                .and_then(|q| q.trim().parse::<f32>().ok())
+                .unwrap_or(1.0);
+            (media_type, quality)
+        })
+        .collect()
+}
+
+/// Request body extractor for the JSON endpoints that also accepts MessagePack and CBOR,
+/// chosen by `Content-Type`. Anything else is handled (and rejected) exactly like `Json<T>`.
+pub struct Payload<T>(pub T);
+
+impl<T, S> FromRequest<S> for Payload<T>
+where
+    T: DeserializeOwned,
+    S: Send + Sync,
+{
+    type Rejection = Response;
+
+    async fn from_request(request: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let format = Format::of_request(request.headers()).unwrap_or(Format::Json);
+        if format == Format::Json {
+            let Json(value) = Json::<T>::from_request(request, state).await.map_err(IntoResponse::into_response)?;
+            return Ok(Payload(value));
+        }
+
+        let bytes = Bytes::from_request(request, state).await.map_err(IntoResponse::into_response)?;
+        // Decoded via a JSON value so IDs and timestamps are read from strings, as in JSON bodies
+        let value: Result<serde_json::Value, String> = match format {
+            Format::MessagePack => rmp_serde::from_slice(&bytes).map_err(|e| e.to_string()),
+            Format::Cbor => ciborium::from_reader(bytes.as_ref()).map_err(|e| e.to_string()),
+            Format::Json => unreachable!("handled above"),
+        };
+        value
+            .and_then(|value| serde_json::from_value(value).map_err(|e| e.to_string()))
+            .map(Payload)
+            .map_err(|e| AppError::InvalidInput(format!("Failed to decode request body: {}", e)).into_response())
+    }
+}
+
+/// Middleware that re-encodes JSON responses as MessagePack or CBOR when the client's
+/// `Accept` header prefers them. Other responses (images, exports, metrics) pass through, and
+/// JSON responses gain `Vary: Accept` so caches keep the representations apart.
+pub async fn negotiate_response_format(request: Request, next: Next) -> Response {
+    let format = Format::preferred(request.headers());
+    let mut response = next.run(request).await;
+
+    let is_json = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("application/json"));
+    if !is_json {
+        return response;
+    }
+    let varies_by_accept = response
+        .headers()
+        .get_all(header::VARY)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .any(|name| name.trim().eq_ignore_ascii_case("accept"));
+    if !varies_by_accept {
+        response.headers_mut().append(header::VARY, HeaderValue::from_static("accept"));
+    }
+    if format == Format::Json {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    // JSON bodies are built in memory by the handlers, so buffering them costs little
+    let bytes = match to_bytes(body, usize::MAX).await {
+        Ok(bytes) => bytes,
+        Err(e) => return AppError::InternalServerError(format!("Failed to read response body: {}", e)).into_response(),
+    };
+    let value: serde_json::Value = match serde_json::from_slice(&bytes) {
+        Ok(value) => value,
+        Err(_) => return Response::from_parts(parts, Body::from(bytes)), // Not actually JSON; leave it be
+    };
+    let (encoded, content_type) = match format {
+        Format::MessagePack => (rmp_serde::to_vec_named(&value).map_err(|e| e.to_string()), MSGPACK),
+        Format::Cbor => {
+            let mut buffer = Vec::new();
+            (ciborium::into_writer(&value, &mut buffer).map(|_| buffer).map_err(|e| e.to_string()), CBOR)
+        }
+        Format::Json => unreachable!("returned above"),
+    };
+    match encoded {
+        Ok(encoded) => {
+            parts.headers.insert(header::CONTENT_TYPE, HeaderValue::from_static(content_type));
+            parts.headers.remove(header::CONTENT_LENGTH);
+            Response::from_parts(parts, Body::from(encoded))
+        }
+        Err(e) => AppError::InternalServerError(format!("Failed to encode response as {}: {}", content_type, e)).into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn accepting(accept: &str) -> HeaderMap {
+        HeaderMap::from_iter([(header::ACCEPT, accept.parse().unwrap())])
+    }
+
+    #[test]
+    fn browsers_and_crawlers_get_html() {
+        assert!(prefers_html(&accepting("text/html,application/xhtml+xml,application/xml;q=0.9,*/*;q=0.8")));
+        assert!(prefers_html(&accepting("application/xhtml+xml")));
+        assert!(prefers_html(&accepting("TEXT/HTML;q=0.5, application/json;q=0.5")));
+    }
+
+    #[test]
+    fn api_clients_get_a_body_format() {
+        assert!(!prefers_html(&HeaderMap::new()));
+        assert!(!prefers_html(&accepting("*/*")));
+        assert!(!prefers_html(&accepting("application/json")));
+        assert!(!prefers_html(&accepting("text/html;q=0.5, application/json")));
+        assert!(!prefers_html(&accepting("text/html;q=0")));
+    }
+}
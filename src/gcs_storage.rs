@@ -0,0 +1,305 @@
+use crate::{
+    config::Config,
+    domain::{FileStorage, ObjectMetadata, StoredObject, UploadOptions},
+    errors::{AppError, StorageError},
+};
+use async_trait::async_trait;
+use axum::body::Bytes;
+use chrono::{DateTime, Utc};
+use aws_sdk_s3::primitives::ByteStream;
+use google_cloud_auth::{
+    credentials::{self, Credentials},
+    signer::Signer,
+};
+use google_cloud_gax::{error::rpc::Code, paginator::ItemPaginator};
+use google_cloud_storage::{
+    builder::storage::SignedUrlBuilder,
+    client::{Storage, StorageControl},
+    http::Method,
+    model::{Bucket, Object},
+};
+use std::time::Duration;
+use tracing::info;
+
+/// Implements `FileStorage` on a Google Cloud Storage bucket. Objects are written and read
+/// through the JSON API client and listed, inspected and deleted through the control
+/// client; names are the storage keys under the same tenant prefix S3 uses.
+#[derive(Debug, Clone)]
+pub struct GcsFileStorage {
+    storage: Storage,
+    control: StorageControl,
+    signer: Option<Signer>, // None against an emulator without a key; URLs are then unsigned
+    bucket: String,         // Resource name, `projects/_/buckets/<name>`
+    endpoint: Option<String>,
+    key_prefix: String,
+}
+
+impl GcsFileStorage {
+    pub async fn from_config(config: &Config) -> Result<Self, AppError> {
+        let credentials = gcs_credentials(config)?;
+        let mut storage = Storage::builder().with_credentials(credentials.clone());
+        if let Some(endpoint) = &config.gcs_endpoint {
+            storage = storage.with_endpoint(endpoint.clone());
+        }
+        let storage = storage
+            .build()
+            .await
+            .map_err(|e| AppError::InitError(format!("Failed to create the GCS client: {}", e)))?;
+        Ok(Self {
+            storage,
+            control: control_client(config, credentials).await?,
+            signer: gcs_signer(config)?,
+            bucket: bucket_resource(&config.gcs_bucket_name),
+            endpoint: config.gcs_endpoint.clone(),
+            key_prefix: config.s3_key_prefix(),
+        })
+    }
+
+    fn object_name(&self, key: &str) -> String {
+        format!("{}{}", self.key_prefix, key)
+    }
+}
+
+fn bucket_resource(name: &str) -> String {
+    format!("projects/_/buckets/{}", name)
+}
+
+/// The service account key when one is configured; otherwise anonymous against an emulator
+/// and application default credentials against Google.
+fn gcs_credentials(config: &Config) -> Result<Credentials, AppError> {
+    match (&config.gcs_service_account_key, &config.gcs_endpoint) {
+        (Some(key), _) => credentials::service_account::Builder::new(service_account_json(key)?)
+            .build()
+            .map_err(|e| AppError::InitError(format!("Invalid APP_GCS_SERVICE_ACCOUNT_KEY: {}", e))),
+        (None, Some(_)) => Ok(credentials::anonymous::Builder::new().build()),
+        (None, None) => credentials::Builder::default()
+            .build()
+            .map_err(|e| AppError::InitError(format!("No Google Cloud credentials found: {}", e))),
+    }
+}
+
+fn gcs_signer(config: &Config) -> Result<Option<Signer>, AppError> {
+    let signer = match (&config.gcs_service_account_key, &config.gcs_endpoint) {
+        (Some(key), _) => credentials::service_account::Builder::new(service_account_json(key)?).build_signer(),
+        (None, Some(_)) => return Ok(None),
+        (None, None) => credentials::Builder::default().build_signer(),
+    };
+    signer
+        .map(Some)
+        .map_err(|e| AppError::InitError(format!("Failed to set up GCS URL signing: {}", e)))
+}
+
+fn service_account_json(key: &str) -> Result<serde_json::Value, AppError> {
+    serde_json::from_str(key).map_err(|e| AppError::InitError(format!("Invalid APP_GCS_SERVICE_ACCOUNT_KEY: {}", e)))
+}
+
+async fn control_client(config: &Config, credentials: Credentials) -> Result<StorageControl, AppError> {
+    let mut control = StorageControl::builder().with_credentials(credentials);
+    if let Some(endpoint) = &config.gcs_endpoint {
+        control = control.with_endpoint(endpoint.clone());
+    }
+    control
+        .build()
+        .await
+        .map_err(|e| AppError::InitError(format!("Failed to create the GCS control client: {}", e)))
+}
+
+fn is_not_found(error: &google_cloud_storage::Error) -> bool {
+    error.status().is_some_and(|status| status.code == Code::NotFound) || error.http_status_code() == Some(404)
+}
+
+fn backend_error(error: impl std::error::Error + Send + Sync + 'static, context: String) -> StorageError {
+    StorageError::BackendError(anyhow::Error::new(error).context(context))
+}
+
+/// When the object was last written: its update time, which GCS sets on creation too.
+fn updated_at(object: &Object) -> Option<DateTime<Utc>> {
+    let updated = object.update_time.as_ref()?;
+    DateTime::from_timestamp(updated.seconds(), u32::try_from(updated.nanos()).ok()?)
+}
+
+fn object_metadata(object: &Object) -> ObjectMetadata {
+    ObjectMetadata {
+        content_type: Some(object.content_type.clone()).filter(|content_type| !content_type.is_empty()),
+        content_length: Some(object.size as u64),
+        etag: Some(object.etag.clone()).filter(|etag| !etag.is_empty()),
+        last_modified: updated_at(object),
+    }
+}
+
+/// Checks the bucket exists, creating it in `create` mode when `APP_GCS_PROJECT_ID` says
+/// which project owns it. Startup only, like the S3 bucket checks.
+pub async fn ensure_bucket(config: &Config, create: bool) -> Result<(), AppError> {
+    let control = control_client(config, gcs_credentials(config)?).await?;
+    let name = &config.gcs_bucket_name;
+    match control.get_bucket().set_name(bucket_resource(name)).send().await {
+        Ok(_) => {
+            info!(bucket = %name, "GCS bucket verified.");
+            return Ok(());
+        }
+        Err(e) if is_not_found(&e) => {}
+        Err(e) => return Err(AppError::InitError(format!("Failed to check GCS bucket '{}': {}", name, e))),
+    }
+    let (true, Some(project_id)) = (create, &config.gcs_project_id) else {
+        return Err(AppError::InitError(format!(
+            "GCS bucket '{}' does not exist. Create it, or set APP_RESOURCE_INIT=create and APP_GCS_PROJECT_ID",
+            name
+        )));
+    };
+    let created = control
+        .create_bucket()
+        .set_parent("projects/_")
+        .set_bucket_id(name.clone())
+        .set_bucket(Bucket::new().set_project(format!("projects/{}", project_id)))
+        .send()
+        .await;
+    match created {
+        Ok(_) => {}
+        // Another instance created it in the meantime
+        Err(e) if e.status().is_some_and(|status| status.code == Code::AlreadyExists) => {}
+        Err(e) => return Err(AppError::InitError(format!("Failed to create GCS bucket '{}': {}", name, e))),
+    }
+    info!(bucket = %name, "GCS bucket created.");
+    Ok(())
+}
+
+#[async_trait]
+impl FileStorage for GcsFileStorage {
+    /// Uploads the object with the content type and custom metadata. GCS objects have no
+    /// tags, so `options.tags` is dropped; the same values are in the metadata.
+    async fn upload(&self, key: &str, data: Bytes, options: UploadOptions) -> Result<(), StorageError> {
+        let content_type = options.content_type.unwrap_or_else(|| "application/octet-stream".to_string());
+        self.storage
+            .write_object(&self.bucket, self.object_name(key), data)
+            .set_content_type(content_type)
+            .set_metadata(options.metadata)
+            .send_unbuffered()
+            .await
+            .map_err(|e| StorageError::UploadFailed(format!("GCS: Failed to upload object '{}': {}", key, e)))?;
+        tracing::debug!(object = %key, "GCS: Upload successful");
+        Ok(())
+    }
+
+    /// Reads the object in chunks and hands it over as one buffer, like the Azure backend.
+    async fn download(&self, key: &str) -> Result<(ByteStream, ObjectMetadata), StorageError> {
+        let mut response = self.storage.read_object(&self.bucket, self.object_name(key)).send().await.map_err(|e| {
+            if is_not_found(&e) {
+                StorageError::NotFound(key.to_string())
+            } else {
+                backend_error(e, format!("GCS: Failed to download object '{}'", key))
+            }
+        })?;
+        let object = response.object();
+        let mut data = Vec::with_capacity(object.size.max(0) as usize);
+        while let Some(chunk) = response.next().await {
+            let chunk = chunk.map_err(|e| backend_error(e, format!("GCS: Failed to read object '{}'", key)))?;
+            data.extend_from_slice(&chunk);
+        }
+        let metadata = ObjectMetadata {
+            // Reads only describe the object's highlights, which have no update time; `head` has it
+            last_modified: None,
+            content_type: Some(object.content_type).filter(|content_type| !content_type.is_empty()),
+            content_length: Some(data.len() as u64),
+            etag: Some(object.etag).filter(|etag| !etag.is_empty()),
+        };
+        Ok((ByteStream::from(data), metadata))
+    }
+
+    async fn head(&self, key: &str) -> Result<ObjectMetadata, StorageError> {
+        let object = self
+            .control
+            .get_object()
+            .set_bucket(&self.bucket)
+            .set_object(self.object_name(key))
+            .send()
+            .await
+            .map_err(|e| {
+                if is_not_found(&e) {
+                    StorageError::NotFound(key.to_string())
+                } else {
+                    backend_error(e, format!("GCS: Failed to read metadata of object '{}'", key))
+                }
+            })?;
+        Ok(object_metadata(&object))
+    }
+
+    async fn list(&self) -> Result<Vec<StoredObject>, StorageError> {
+        let mut items = self.control.list_objects().set_parent(&self.bucket).set_prefix(&self.key_prefix).by_item();
+        let mut objects = Vec::new();
+        while let Some(object) = items.next().await {
+            let object = object.map_err(|e| backend_error(e, format!("GCS: Failed to list bucket '{}'", self.bucket)))?;
+            let Some(key) = object.name.strip_prefix(self.key_prefix.as_str()) else { continue };
+            objects.push(StoredObject { key: key.to_string(), size: object.size as u64 });
+        }
+        Ok(objects)
+    }
+
+    /// A V4 signed GET URL. Against an emulator without a key, which does not check
+    /// signatures, the plain object URL.
+    async fn presigned_url(&self, key: &str, expires_in: Duration) -> Result<String, StorageError> {
+        let Some(signer) = &self.signer else {
+            let endpoint = self.endpoint.as_deref().unwrap_or("https://storage.googleapis.com");
+            let bucket = self.bucket.trim_start_matches("projects/_/buckets/");
+            return Ok(format!("{}/{}/{}", endpoint, bucket, self.object_name(key)));
+        };
+        let mut builder = SignedUrlBuilder::for_object(&self.bucket, self.object_name(key))
+            .with_method(Method::GET)
+            .with_expiration(expires_in);
+        if let Some(endpoint) = &self.endpoint {
+            builder = builder.with_endpoint(endpoint.clone());
+        }
+        builder
+            .sign_with(signer)
+            .await
+            .map_err(|e| backend_error(e, format!("GCS: Failed to sign object '{}'", key)))
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), StorageError> {
+        match self.control.delete_object().set_bucket(&self.bucket).set_object(self.object_name(key)).send().await {
+            Ok(()) => Ok(()),
+            // Deleting a missing object succeeds on S3; match that
+            Err(e) if is_not_found(&e) => Ok(()),
+            Err(e) => Err(backend_error(e, format!("GCS: Failed to delete object '{}'", key))),
+        }
+    }
+
+    /// Copies within the bucket with RewriteObject, which keeps the content type and custom
+    /// metadata. Large objects can take several calls, each resuming with the last token.
+    async fn copy(&self, from: &str, to: &str) -> Result<(), StorageError> {
+        let mut rewrite_token = String::new();
+        loop {
+            let response = self
+                .control
+                .rewrite_object()
+                .set_source_bucket(&self.bucket)
+                .set_source_object(self.object_name(from))
+                .set_destination_bucket(&self.bucket)
+                .set_destination_name(self.object_name(to))
+                .set_rewrite_token(rewrite_token)
+                .send()
+                .await
+                .map_err(|e| {
+                    if is_not_found(&e) {
+                        StorageError::NotFound(from.to_string())
+                    } else {
+                        backend_error(e, format!("GCS: Failed to copy object '{}' to '{}'", from, to))
+                    }
+                })?;
+            if response.done {
+                return Ok(());
+            }
+            rewrite_token = response.rewrite_token;
+        }
+    }
+
+    /// Reads the bucket's metadata.
+    async fn ping(&self) -> Result<(), StorageError> {
+        self.control
+            .get_bucket()
+            .set_name(&self.bucket)
+            .send()
+            .await
+            .map_err(|e| backend_error(e, format!("GCS: Bucket '{}' is not reachable", self.bucket)))?;
+        Ok(())
+    }
+}
@@ -0,0 +1,387 @@
+//! Test data for property-based tests (only with the `testing` feature).
+//!
+//! [`MemeBuilder`] builds a stored [`Meme`] with sensible defaults for the fields a test
+//! does not care about. [`Meme`] and [`UploadPayload`] implement [`Arbitrary`], leaning on
+//! edge cases: Unicode and right-to-left titles, whitespace and control characters,
+//! descriptions far over the length limits, and odd filenames and extensions.
+//!
+//! Generation is seeded. [`seed`] picks a random seed per run and prints it, or reads
+//! `APP_TEST_SEED` to reproduce a failing run:
+//!
+//! ```ignore
+//! let mut rng = generators::rng();
+//! for _ in 0..100 {
+//!     let payload: UploadPayload = generators::generate(&mut rng);
+//!     assert!(!app.upload(&payload).await.status().is_server_error(), "{:?}", payload);
+//! }
+//! ```
+
+use crate::{
+    models::{Meme, MemeStatus, Visibility, INITIAL_VERSION},
+    testing::sample_png,
+};
+use arbitrary::{Arbitrary, Result, Unstructured};
+use base64::{prelude::BASE64_STANDARD, Engine};
+use chrono::{DateTime, Duration, Utc};
+use fastrand::Rng;
+use reqwest::multipart::{Form, Part};
+use uuid::Uuid;
+
+/// Reproduces a run of generated data with the seed it printed.
+pub const SEED_VAR: &str = "APP_TEST_SEED";
+
+/// Bytes of generator output each value is generated from; plenty for the longest text.
+const INPUT_BYTES: usize = 64 * 1024;
+
+/// The seed of this run: `APP_TEST_SEED` when set, a random one otherwise. Printed to
+/// stderr, which the test harness shows for a failing test, so the run can be repeated.
+pub fn seed() -> u64 {
+    let seed = match std::env::var(SEED_VAR) {
+        Ok(value) => value.parse().unwrap_or_else(|_| panic!("{} must be a number, got '{}'", SEED_VAR, value)),
+        Err(_) => fastrand::u64(..),
+    };
+    eprintln!("Generating test data with {}={}", SEED_VAR, seed);
+    seed
+}
+
+/// A generator seeded by [`seed`].
+pub fn rng() -> Rng {
+    Rng::with_seed(seed())
+}
+
+/// Generates a `T` from the output of `rng`.
+pub fn generate<T: for<'a> Arbitrary<'a>>(rng: &mut Rng) -> T {
+    let mut input = vec![0; INPUT_BYTES];
+    rng.fill(&mut input);
+    T::arbitrary(&mut Unstructured::new(&input)).expect("generated values fall back to defaults when input runs out")
+}
+
+/// Builds a [`Meme`] as stored after a successful upload; every field can be overridden.
+#[derive(Debug, Clone)]
+pub struct MemeBuilder {
+    meme: Meme,
+}
+
+impl Default for MemeBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MemeBuilder {
+    /// A published, public meme with a fresh ID, created now, with a 1x1 PNG image.
+    pub fn new() -> Self {
+        let meme_id = Uuid::new_v4();
+        Self {
+            meme: Meme {
+                meme_id,
+                title: "Test meme".to_string(),
+                description: "Built for a test".to_string(),
+                image_key: format!("{}.png", meme_id),
+                tags: Vec::new(),
+                source_url: None,
+                expires_at: None,
+                created_at: Some(Utc::now()),
+                version: INITIAL_VERSION,
+                like_count: 0,
+                visibility: Visibility::Public,
+                publish_at: None,
+                status: MemeStatus::Published,
+                view_count: 0,
+                caption_text: None,
+                dominant_color: None,
+                palette: Vec::new(),
+                width: Some(1),
+                height: Some(1),
+                size_bytes: Some(sample_png().len() as u64),
+                content_hash: None,
+                updated_at: None,
+            },
+        }
+    }
+
+    /// Sets the ID, and the image key to match.
+    pub fn id(mut self, meme_id: Uuid) -> Self {
+        self.meme.image_key = format!("{}.png", meme_id);
+        self.meme.meme_id = meme_id;
+        self
+    }
+
+    pub fn title(mut self, title: impl Into<String>) -> Self {
+        self.meme.title = title.into();
+        self
+    }
+
+    pub fn description(mut self, description: impl Into<String>) -> Self {
+        self.meme.description = description.into();
+        self
+    }
+
+    pub fn image_key(mut self, image_key: impl Into<String>) -> Self {
+        self.meme.image_key = image_key.into();
+        self
+    }
+
+    pub fn tags<I, S>(mut self, tags: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.meme.tags = tags.into_iter().map(Into::into).collect();
+        self
+    }
+
+    pub fn source_url(mut self, source_url: impl Into<String>) -> Self {
+        self.meme.source_url = Some(source_url.into());
+        self
+    }
+
+    pub fn expires_at(mut self, expires_at: DateTime<Utc>) -> Self {
+        self.meme.expires_at = Some(expires_at);
+        self
+    }
+
+    pub fn created_at(mut self, created_at: DateTime<Utc>) -> Self {
+        self.meme.created_at = Some(created_at);
+        self
+    }
+
+    pub fn version(mut self, version: u64) -> Self {
+        self.meme.version = version;
+        self
+    }
+
+    pub fn likes(mut self, like_count: u64) -> Self {
+        self.meme.like_count = like_count;
+        self
+    }
+
+    pub fn views(mut self, view_count: u64) -> Self {
+        self.meme.view_count = view_count;
+        self
+    }
+
+    pub fn visibility(mut self, visibility: Visibility) -> Self {
+        self.meme.visibility = visibility;
+        self
+    }
+
+    /// Makes the meme a draft that the publishing job publishes at `publish_at`.
+    pub fn scheduled(mut self, publish_at: DateTime<Utc>) -> Self {
+        self.meme.status = MemeStatus::Draft;
+        self.meme.publish_at = Some(publish_at);
+        self
+    }
+
+    pub fn status(mut self, status: MemeStatus) -> Self {
+        self.meme.status = status;
+        self
+    }
+
+    pub fn caption_text(mut self, caption_text: impl Into<String>) -> Self {
+        self.meme.caption_text = Some(caption_text.into());
+        self
+    }
+
+    /// Sets the palette, the first color being the dominant one.
+    pub fn palette<I, S>(mut self, palette: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.meme.palette = palette.into_iter().map(Into::into).collect();
+        self.meme.dominant_color = self.meme.palette.first().cloned();
+        self
+    }
+
+    pub fn dimensions(mut self, width: u32, height: u32) -> Self {
+        self.meme.width = Some(width);
+        self.meme.height = Some(height);
+        self
+    }
+
+    pub fn content_hash(mut self, content_hash: impl Into<String>) -> Self {
+        self.meme.content_hash = Some(content_hash.into());
+        self
+    }
+
+    pub fn build(self) -> Meme {
+        self.meme
+    }
+}
+
+impl<'a> Arbitrary<'a> for Meme {
+    /// A meme as a backend might hold it: any text, including text validation would reject
+    /// today, every optional field sometimes unset, and expiry and publishing times to the
+    /// second, as they are indexed.
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let now = Utc::now();
+        let whole_seconds = DateTime::from_timestamp(now.timestamp(), 0).expect("now is in range");
+        let meme_id = uuid::Builder::from_random_bytes(u.arbitrary()?).into_uuid();
+        let tag_count = u.int_in_range(0..=7)?;
+        let mut builder = MemeBuilder::new()
+            .id(meme_id)
+            .image_key(format!("{}.{}", meme_id, u.choose(&["png", "jpg", "gif", "webp"])?))
+            .title(text(u, 200)?)
+            .description(text(u, 5000)?)
+            .tags((0..tag_count).map(|_| tag(u)).collect::<Result<Vec<_>>>()?)
+            .created_at(now - Duration::seconds(u.int_in_range(0..=10 * 365 * 86_400)?))
+            .version(u.int_in_range(1..=999)?)
+            .likes(if u.arbitrary()? { 0 } else { u.int_in_range(0..=i64::MAX as u64 - 1)? })
+            .views(if u.arbitrary()? { 0 } else { u.int_in_range(0..=u64::MAX / 2 - 1)? })
+            .visibility(*u.choose(&[Visibility::Public, Visibility::Unlisted, Visibility::Private])?)
+            .dimensions(u.int_in_range(1..=16_384)?, u.int_in_range(1..=16_384)?);
+        if u.ratio(1, 4)? {
+            builder = builder.expires_at(whole_seconds + Duration::seconds(u.int_in_range(-86_400..=30 * 86_400 - 1)?));
+        }
+        if u.ratio(1, 4)? {
+            builder = builder.scheduled(whole_seconds + Duration::seconds(u.int_in_range(-86_400..=30 * 86_400 - 1)?));
+        }
+        if u.arbitrary()? {
+            builder = builder.caption_text(text(u, 500)?);
+        }
+        if u.arbitrary()? {
+            let colors = u.int_in_range(1..=5)?;
+            builder = builder.palette(
+                (0..colors).map(|_| Ok(format!("#{:06x}", u.int_in_range(0..=0x00ff_ffff_u32)?))).collect::<Result<Vec<_>>>()?,
+            );
+        }
+        if u.arbitrary()? {
+            builder = builder.source_url(format!("https://example.com/{}", percent_free_text(u)?));
+        }
+        if u.arbitrary()? {
+            let hash: [u8; 32] = u.arbitrary()?;
+            builder = builder.content_hash(hash.iter().map(|byte| format!("{:02x}", byte)).collect::<String>());
+        }
+        Ok(builder.build())
+    }
+}
+
+/// A meme upload as a client sends it, to `POST /upload_meme` ([`UploadPayload::form`]) or
+/// `POST /memes` ([`UploadPayload::json`]). The image is a valid PNG whatever the filename
+/// and content type claim.
+#[derive(Debug, Clone)]
+pub struct UploadPayload {
+    pub title: String,
+    pub description: String,
+    pub tags: Vec<String>,
+    pub filename: String,
+    pub content_type: String,
+    pub image: Vec<u8>,
+}
+
+impl Default for UploadPayload {
+    fn default() -> Self {
+        Self {
+            title: "Test meme".to_string(),
+            description: "Uploaded by a test".to_string(),
+            tags: Vec::new(),
+            filename: "meme.png".to_string(),
+            content_type: "image/png".to_string(),
+            image: sample_png(),
+        }
+    }
+}
+
+impl UploadPayload {
+    /// The multipart form of `POST /upload_meme`.
+    pub fn form(&self) -> Form {
+        let image = Part::bytes(self.image.clone())
+            .file_name(self.filename.clone())
+            .mime_str(&self.content_type)
+            .unwrap_or_else(|_| Part::bytes(self.image.clone()).file_name(self.filename.clone()));
+        let form = Form::new()
+            .text("title", self.title.clone())
+            .text("description", self.description.clone())
+            .part("image", image);
+        self.tags.iter().fold(form, |form, tag| form.text("tags", tag.clone()))
+    }
+
+    /// The JSON body of `POST /memes`.
+    pub fn json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "title": self.title,
+            "description": self.description,
+            "tags": self.tags,
+            "image_base64": BASE64_STANDARD.encode(&self.image),
+            "filename": self.filename,
+            "content_type": self.content_type,
+        })
+    }
+}
+
+impl<'a> Arbitrary<'a> for UploadPayload {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let tag_count = u.int_in_range(0..=14)?;
+        Ok(Self {
+            title: text(u, 200)?,
+            description: text(u, 5000)?,
+            tags: (0..tag_count).map(|_| tag(u)).collect::<Result<_>>()?,
+            filename: filename(u)?,
+            content_type: u.choose(&["image/png", "image/jpeg", "application/octet-stream", "text/plain", "image/PNG"])?.to_string(),
+            image: sample_png(),
+        })
+    }
+}
+
+/// Pieces of text that tend to break things: multi-byte and combining characters, emoji
+/// sequences, right-to-left text, invisible characters and control characters.
+const TRICKY: &[&str] = &[
+    "é", "e\u{301}", "ß", "İ", "Ω", "日本語", "ミーム", "😀", "👨‍👩‍👧‍👦", "🏳️‍🌈", "🇨🇦", "مرحبا", "שלום", "\u{202e}txt.exe",
+    "\u{200b}", "\u{feff}", "\u{a0}", "\t", "\n", "\r\n", "\u{0}", "\u{7}", "<script>alert(1)</script>", "' OR 1=1 --", "%s%n",
+    "../", "\\", "\"", "{{title}}", "${HOME}", "NaN", "null",
+];
+
+/// Text of up to about `max_chars` characters; sometimes empty, blank, padded with
+/// whitespace or much longer than any length limit.
+fn text(u: &mut Unstructured<'_>, max_chars: usize) -> Result<String> {
+    Ok(match u.int_in_range(0..=9)? {
+        0 => String::new(),
+        1 => " ".repeat(u.int_in_range(1..=9)?),
+        2 => "x".repeat(u.int_in_range(max_chars..=max_chars * 20 + 1)?),
+        _ => {
+            let mut text = String::new();
+            let target = u.int_in_range(1..=max_chars.max(1))?;
+            // Stops early once the input runs out, as every choice then repeats
+            while text.chars().count() < target && !u.is_empty() {
+                let piece = if u.ratio(1, 3)? {
+                    u.choose(TRICKY)?
+                } else {
+                    u.choose(&["meme", "when", "the", "build", "passes", " ", "Ça", "1337", "-", "!"])?
+                };
+                text.push_str(piece);
+            }
+            if u.ratio(1, 4)? {
+                text = format!("  {}\n", text);
+            }
+            text
+        }
+    })
+}
+
+/// A tag, sometimes one validation rejects (spaces, punctuation, too long).
+fn tag(u: &mut Unstructured<'_>) -> Result<String> {
+    Ok(match u.int_in_range(0..=5)? {
+        0 => u.choose(TRICKY)?.to_string(),
+        1 => "t".repeat(u.int_in_range(30..=199)?),
+        2 => " Funny ".to_string(),
+        _ => u.choose(&["funny", "cats", "programming", "ミーム", "rust-lang", "snake_case", "über", "2024"])?.to_string(),
+    })
+}
+
+/// A filename with an unusual name or extension.
+fn filename(u: &mut Unstructured<'_>) -> Result<String> {
+    let stem = match u.int_in_range(0..=9)? {
+        0 => "x".repeat(u.int_in_range(200..=999)?),
+        _ => u.choose(&["meme", "Mème 😀", "IMG_0001", "", ".hidden", "../../etc/passwd", "a b\tc", "con"])?.to_string(),
+    };
+    let extension = u.choose(&[".png", ".PNG", ".jpeg", ".JpG", ".gif", ".webp", "", ".", ".png.exe", ".tar.gz", ".svg", ".txt", ".🖼"])?;
+    Ok(format!("{}{}", stem, extension))
+}
+
+/// Text safe to put in a URL path.
+fn percent_free_text(u: &mut Unstructured<'_>) -> Result<String> {
+    const ALPHANUMERIC: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789";
+    let length = u.int_in_range(1..=39)?;
+    (0..length).map(|_| Ok(char::from(*u.choose(ALPHANUMERIC)?))).collect()
+}
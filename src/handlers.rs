@@ -1,75 +1,109 @@
 use crate::{
-    config::Config,
+    audit,
+    auth::OwnerAccess,
+    cdn,
+    circuit_breaker::BreakerState,
+    config::{Config, ReadEndpoint},
+    domain::{AuditAction, ConsistencyLevel, ObjectMetadata},
+    embeddings,
+    embeds,
     errors::{AppError, StorageError},
-    models::Meme,
+    export,
+    fields::{FieldSet, FieldsQuery, Sparse},
+    formats::{self, Payload},
+    imaging::{self, Rgb},
+    keys,
+    models::{Meme, MemeStatus, MemeView, SortOrder, TrendingWindow, Visibility},
+    notifications::{self, Notice},
+    search::{self, SearchMode},
+    services::{self, Caller, ImageInput, ImageUpload, MemePatch},
+    share::ShareGrant,
+    stats::{self, MemeStats},
+    trending,
+    validation::{self, MemeSubmission},
     AppState,
 };
-use aws_sdk_dynamodb::Client as DynamoDbClient;
-use aws_sdk_s3::Client as S3Client;
 use axum::{
     body::Body,
-    extract::{Multipart, Path, State},
-    http::{header, StatusCode},
+    extract::{Extension, Multipart, Path, Query, State},
+    http::{header, HeaderMap, StatusCode},
     response::{IntoResponse, Response},
     Json,
 };
-use mime_guess;
-use std::sync::Arc;
-use tracing;
+use base64::{prelude::BASE64_STANDARD, Engine};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, sync::Arc};
+use tokio_util::io::ReaderStream;
 use uuid::Uuid;
 
-/// Verifies connectivity to DynamoDB and S3 backend services.
-pub async fn health_check(State(state): State<Arc<AppState>>) -> StatusCode {
-    let db_client: &DynamoDbClient = &state.db_client;
-    let s3_client: &S3Client = &state.s3_client;
+/// Body of the `/health` response.
+#[derive(Serialize)]
+pub struct HealthReport {
+    pub status: &'static str,
+    pub dynamodb: &'static str,
+    /// Region serving the meme table, with a secondary region configured.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dynamodb_region: Option<String>,
+    pub s3: &'static str,
+    /// State of each backend's circuit breaker, keyed by backend name.
+    pub circuit_breakers: BTreeMap<&'static str, BreakerState>,
+}
+
+/// Verifies connectivity to the meme store and the image store and reports circuit breaker
+/// states. The connectivity checks bypass the breakers so they reflect the backends' actual
+/// health. The `dynamodb` and `s3` entries report the meme and image stores, whichever
+/// backends hold them.
+pub async fn health_check(State(state): State<Arc<AppState>>) -> (StatusCode, Json<HealthReport>) {
     let config: &Config = &state.config; // Get config from state
 
-    // Check DynamoDB using table name from config
-    let db_check_result = db_client
-        .describe_table()
-        .table_name(&config.dynamodb_table_name) // Use config value
-        .send()
-        .await;
-
-    if let Err(e) = db_check_result {
-        tracing::error!(
-            error = ?e,
-            table_name = %config.dynamodb_table_name, // Log config value
-            "Health check failed: DynamoDB connectivity error."
-        );
-        return StatusCode::SERVICE_UNAVAILABLE;
-    }
+    // Check the table (or collection) the memes live in
+    let db_ok = match state.meme_repo.ping().await {
+        Ok(()) => true,
+        Err(e) => {
+            tracing::error!(
+                error = ?e,
+                repository_backend = ?config.repository_backend,
+                "Health check failed: meme store connectivity error."
+            );
+            false
+        }
+    };
 
-    // Check S3 using bucket name from config
-    let s3_check_result = s3_client
-        .head_bucket()
-        .bucket(&config.meme_bucket_name) // Use config value
-        .send()
-        .await;
+    // Check the bucket (or container) the images live in
+    let s3_ok = match state.file_storage.ping().await {
+        Ok(()) => true,
+        Err(e) => {
+            tracing::error!(
+                error = ?e,
+                storage_backend = ?config.storage_backend,
+                "Health check failed: storage connectivity error."
+            );
+            false
+        }
+    };
 
-    if let Err(e) = s3_check_result {
-         tracing::error!(
-            error = ?e,
-            bucket_name = %config.meme_bucket_name, // Log config value
-            "Health check failed: S3 connectivity error."
-         );
-        return StatusCode::SERVICE_UNAVAILABLE;
+    let report = HealthReport {
+        status: if db_ok && s3_ok { "ok" } else { "unavailable" },
+        dynamodb: if db_ok { "ok" } else { "error" },
+        dynamodb_region: state.region_failover.as_ref().map(|failover| failover.active_region().to_string()),
+        s3: if s3_ok { "ok" } else { "error" },
+        circuit_breakers: state.circuit_breakers.iter().map(|b| (b.name(), b.state())).collect(),
+    };
+    if db_ok && s3_ok {
+        tracing::debug!("Health check passed: DynamoDB and S3 connectivity OK.");
+        (StatusCode::OK, Json(report))
+    } else {
+        (StatusCode::SERVICE_UNAVAILABLE, Json(report))
     }
-
-    tracing::debug!("Health check passed: DynamoDB and S3 connectivity OK.");
-    StatusCode::OK
 }
 
 pub async fn upload_meme(
     State(state): State<Arc<AppState>>,
+    caller: Caller,
     mut multipart: Multipart,
 ) -> Result<impl IntoResponse, AppError> {
-     let meme_id = Uuid::new_v4();
-    let mut title = None;
-    let mut description = None;
-    let mut image_data: Option<Vec<u8>> = None;
-    let mut image_filename: Option<String> = None;
-    let mut image_content_type: Option<String> = None;
+    let mut submission = MemeSubmission::default();
+    let mut image = ImageInput::Missing;
 
     while let Some(field) = multipart.next_field().await? {
         let field_name = match field.name() {
@@ -77,89 +111,521 @@ pub async fn upload_meme(
             None => continue,
         };
         match field_name.as_str() {
-            "title" => title = Some(field.text().await.map_err(|e| AppError::InvalidInput(format!("Failed to read title: {}", e)))?),
-            "description" => description = Some(field.text().await.map_err(|e| AppError::InvalidInput(format!("Failed to read description: {}", e)))?),
+            "title" => submission.title = Some(field.text().await.map_err(|e| AppError::InvalidInput(format!("Failed to read title: {}", e)))?),
+            "description" => submission.description = Some(field.text().await.map_err(|e| AppError::InvalidInput(format!("Failed to read description: {}", e)))?),
+            "tags" => {
+                let raw = field.text().await.map_err(|e| AppError::InvalidInput(format!("Failed to read tags: {}", e)))?;
+                submission.tags.extend(validation::split_tags(&raw));
+            }
+            "expires_in" => submission.expires_in = Some(field.text().await.map_err(|e| AppError::InvalidInput(format!("Failed to read expires_in: {}", e)))?),
+            "publish_at" => submission.publish_at = Some(field.text().await.map_err(|e| AppError::InvalidInput(format!("Failed to read publish_at: {}", e)))?),
+            "status" => submission.status = Some(field.text().await.map_err(|e| AppError::InvalidInput(format!("Failed to read status: {}", e)))?),
             "image" => {
-                image_filename = field.file_name().map(|s| s.to_string());
-                image_content_type = field.content_type().map(|m| m.to_string());
-                image_data = Some(field.bytes().await?.to_vec());
+                let filename = field.file_name().map(|s| s.to_string());
+                let content_type = field.content_type().map(|m| m.to_string());
+                let data = field.bytes().await?;
+                state.config.upload_body_limits.check_file(content_type.as_deref(), &data)?;
+                image = ImageInput::Provided(ImageUpload { data, filename, content_type, source_url: None });
             }
             _ => tracing::debug!("Ignoring unknown multipart field: {}", field_name),
         }
     }
 
-    let title = title.ok_or_else(|| AppError::MissingFormField("title".to_string()))?;
-    let description = description.ok_or_else(|| AppError::MissingFormField("description".to_string()))?;
-    let image_data = image_data.ok_or_else(|| AppError::MissingFormField("image".to_string()))?;
-    if image_data.is_empty() {
-        return Err(AppError::InvalidInput("image data cannot be empty".to_string()));
-    }
+    let meme = services::create_meme(&state, submission, image, &caller).await?;
+    let etag = meme.etag();
+    Ok((StatusCode::CREATED, [(header::ETAG, etag)], Json(meme_view(&state, meme).await?)))
+}
 
-    let extension = image_filename.as_ref()
-        .and_then(|name| name.split('.').last().map(|ext| ext.to_lowercase()))
-        .unwrap_or_else(|| "bin".to_string());
-    let image_key = format!("{}.{}", meme_id, extension);
+/// JSON body for POST /memes. Exactly one of `image_base64` or `source_url` must be set.
+#[derive(Deserialize)]
+pub struct CreateMemeRequest {
+    pub title: Option<String>,
+    pub description: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Base64-encoded (standard alphabet) image bytes.
+    pub image_base64: Option<String>,
+    /// Original filename, used to derive the stored extension for base64 images.
+    pub filename: Option<String>,
+    pub content_type: Option<String>,
+    /// URL the server downloads the image from.
+    pub source_url: Option<String>,
+    /// Seconds until the meme expires; the meme is permanent when unset.
+    pub expires_in: Option<u64>,
+    /// RFC 3339 time before which the meme is only visible to its owner.
+    pub publish_at: Option<String>,
+    /// Drafts are only visible to their owner until published.
+    pub status: Option<MemeStatus>,
+}
 
-    // Guess content type more reliably for upload if not provided
-    let final_content_type = image_content_type
-         .or_else(|| mime_guess::from_path(&image_key).first_raw().map(|s| s.to_string()))
-         .unwrap_or_else(|| "application/octet-stream".to_string());
+/// Handler for POST /memes: JSON alternative to the multipart upload for programmatic clients.
+pub async fn create_meme_json(
+    State(state): State<Arc<AppState>>,
+    caller: Caller,
+    Payload(request): Payload<CreateMemeRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    let image = match (request.image_base64, request.source_url) {
+        (Some(_), Some(_)) => ImageInput::Invalid {
+            field: "image",
+            message: "provide either image_base64 or source_url, not both".to_string(),
+        },
+        (None, None) => ImageInput::Missing,
+        (Some(encoded), None) => match BASE64_STANDARD.decode(encoded.trim()) {
+            Ok(data) => ImageInput::Provided(ImageUpload {
+                data: data.into(),
+                filename: request.filename,
+                content_type: request.content_type,
+                source_url: None,
+            }),
+            Err(e) => ImageInput::Invalid {
+                field: "image_base64",
+                message: format!("is not valid base64: {}", e),
+            },
+        },
+        (None, Some(url)) => match state.url_fetcher.fetch(&url).await {
+            // Sniffed type and derived filename win over client-declared values
+            Ok(fetched) => ImageInput::Provided(ImageUpload {
+                data: fetched.data.into(),
+                filename: Some(fetched.filename),
+                content_type: Some(fetched.content_type),
+                source_url: Some(url),
+            }),
+            Err(e) => {
+                tracing::warn!(source_url = %url, error = %e, "Failed to fetch remote image");
+                ImageInput::Invalid { field: "source_url", message: e.to_string() }
+            }
+        },
+    };
 
-    // Use the FileStorage trait object from state
-    // Pass the determined content type
-    state.file_storage
-         .upload(&image_key, image_data, Some(final_content_type))
-         .await?;
+    if let ImageInput::Provided(upload) = &image {
+        state.config.upload_body_limits.check_file(upload.content_type.as_deref(), &upload.data)?;
+    }
 
-    // Create and Store Meme Metadata
-    let meme = Meme {
-        meme_id,
-        title,
-        description,
-        image_key,
+    let submission = MemeSubmission {
+        title: request.title,
+        description: request.description,
+        tags: request.tags,
+        expires_in: request.expires_in.map(|secs| secs.to_string()),
+        publish_at: request.publish_at,
+        status: request.status.map(|status| status.as_str().to_string()),
     };
-    state.meme_repo.create(&meme).await?;
+    let meme = services::create_meme(&state, submission, image, &caller).await?;
+    let etag = meme.etag();
+    Ok((StatusCode::CREATED, [(header::ETAG, etag)], Json(meme_view(&state, meme).await?)))
+}
 
-    tracing::info!(meme_id = %meme_id, "Meme created successfully via handler");
-    Ok((StatusCode::CREATED, Json(meme)))
+/// Pairs a meme with the URL clients should fetch its image from.
+pub(crate) async fn meme_view(state: &AppState, meme: Meme) -> Result<MemeView, AppError> {
+    let image_url = cdn::image_url(state, &meme).await?;
+    Ok(MemeView { meme, image_url })
 }
 
+/// Loads a meme as seen by the caller: private memes are reported missing to anyone but the
+/// owner, so their existence is not revealed. `endpoint` decides how fresh the read is.
+pub(crate) async fn find_visible_meme(
+    state: &AppState,
+    meme_id: Uuid,
+    is_owner: bool,
+    endpoint: ReadEndpoint,
+) -> Result<Meme, AppError> {
+    state.meme_repo.get_by_id(meme_id, state.config.read_consistency(endpoint)).await?
+        .filter(|meme| meme.is_visible_to(is_owner))
+        .ok_or(AppError::MemeNotFound(meme_id))
+}
 
+/// Handler for GET /meme/{id}. `?fields=` limits the response to the named fields. Served
+/// memes count as viewed. Browsers and link preview crawlers asking for HTML get the meme's
+/// page instead (see [`embeds`]).
+/// Private memes need owner credentials.
 pub async fn get_meme(
     State(state): State<Arc<AppState>>,
+    OwnerAccess(is_owner): OwnerAccess,
     Path(id_str): Path<String>,
-) -> Result<impl IntoResponse, AppError> {
+    Query(query): Query<FieldsQuery>,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
     let meme_id = Uuid::parse_str(&id_str)?;
+    let fields = query.parse(Meme::FIELDS)?;
     tracing::debug!(%meme_id, "Fetching meme details via handler");
-    let maybe_meme = state.meme_repo.get_by_id(meme_id).await?;
+    let consistency = state.config.read_consistency(ReadEndpoint::GetMeme);
+    let maybe_meme = state.meme_repo.get_by_id(meme_id, consistency).await?.filter(|meme| meme.is_visible_to(is_owner));
     match maybe_meme {
-        Some(meme) => Ok(Json(meme)),
+        Some(meme) => {
+            state.views.record(meme_id);
+            meme_representation(&state, meme, fields.as_ref(), &headers).await
+        }
         None => Err(AppError::MemeNotFound(meme_id)),
     }
 }
 
+/// Handler for HEAD /meme/{id}. Answers with the headers GET would send for the same
+/// `Accept` header and `?fields=`, `Content-Length` included, so clients can check for
+/// changes without a body. Counts no view. The body is built and dropped by the router.
+pub async fn head_meme(
+    State(state): State<Arc<AppState>>,
+    OwnerAccess(is_owner): OwnerAccess,
+    Path(id_str): Path<String>,
+    Query(query): Query<FieldsQuery>,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    let meme_id = Uuid::parse_str(&id_str)?;
+    let fields = query.parse(Meme::FIELDS)?;
+    let meme = find_visible_meme(&state, meme_id, is_owner, ReadEndpoint::GetMeme).await?;
+    meme_representation(&state, meme, fields.as_ref(), &headers).await
+}
+
+/// The representation of a meme GET /meme/{id} answers with: its HTML page for browsers and
+/// crawlers (see [`embeds`]), else its JSON with the `ETag`. Both vary by `Accept`.
+async fn meme_representation(
+    state: &AppState,
+    meme: Meme,
+    fields: Option<&FieldSet>,
+    headers: &HeaderMap,
+) -> Result<Response, AppError> {
+    let etag = meme.etag();
+    let view = meme_view(state, meme).await?;
+    if let Some(base) = embeds::public_base(state).filter(|_| formats::prefers_html(headers)) {
+        return Ok(embeds::meme_page(base, &view));
+    }
+    // The HTML page is served at the same URL
+    Ok(([(header::ETAG, etag), (header::VARY, "accept".to_string())], Json(Sparse::new(&view, fields))).into_response())
+}
+
+/// JSON body for PATCH /meme/{id}. Omitted fields are left unchanged.
+#[derive(Deserialize)]
+pub struct UpdateMemeRequest {
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub tags: Option<Vec<String>>,
+    pub visibility: Option<Visibility>,
+}
+
+/// Handler for PATCH /meme/{id}. With an `If-Match` header the update only applies to that
+/// version (412 otherwise); without one it applies to the version read here. Either way a
+/// concurrent update between the read and the write is rejected with 412, never lost.
+/// Making a meme private, or editing one that is, needs owner credentials.
+pub async fn update_meme(
+    State(state): State<Arc<AppState>>,
+    caller: Caller,
+    Path(id_str): Path<String>,
+    headers: HeaderMap,
+    Payload(request): Payload<UpdateMemeRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    let meme_id = Uuid::parse_str(&id_str)?;
+    tracing::debug!(%meme_id, "Updating meme via handler");
+    let is_owner = caller.is_owner();
+
+    let current = find_visible_meme(&state, meme_id, is_owner, ReadEndpoint::Writes).await?;
+    if request.visibility == Some(Visibility::Private) && !is_owner {
+        return Err(AppError::Forbidden("Only the owner can make a meme private".to_string()));
+    }
+    check_if_match(&headers, &current)?;
+
+    let patch = MemePatch {
+        title: request.title,
+        description: request.description,
+        tags: request.tags,
+        visibility: request.visibility,
+    };
+    let meme = services::update_meme(&state, current, patch, &caller).await?;
+    let etag = meme.etag();
+    Ok(([(header::ETAG, etag)], Json(meme_view(&state, meme).await?)))
+}
+
+/// Rejects the request with 412 if it has an `If-Match` header that `current` does not match.
+fn check_if_match(headers: &HeaderMap, current: &Meme) -> Result<(), AppError> {
+    let Some(if_match) = headers.get(header::IF_MATCH) else { return Ok(()) };
+    let if_match = if_match.to_str()
+        .map_err(|_| AppError::InvalidInput("If-Match header is not valid text".to_string()))?;
+    if !etag_matches(if_match, &current.etag()) {
+        return Err(AppError::PreconditionFailed(format!(
+            "Meme {} is at version {}, which does not match If-Match",
+            current.meme_id, current.version
+        )));
+    }
+    Ok(())
+}
+
+/// Body of the GET /meme/{id}/history response.
+#[derive(Serialize)]
+pub struct MemeHistory {
+    pub meme_id: Uuid,
+    pub current_version: u64,
+    pub versions: Vec<Meme>, // Earlier versions, newest first
+}
+
+/// Handler for GET /meme/{id}/history. Lists the kept earlier versions of a meme's metadata;
+/// versions that were private are left out for anyone but the owner.
+pub async fn get_meme_history(
+    State(state): State<Arc<AppState>>,
+    OwnerAccess(is_owner): OwnerAccess,
+    Path(id_str): Path<String>,
+) -> Result<Json<MemeHistory>, AppError> {
+    let meme_id = Uuid::parse_str(&id_str)?;
+    let current = find_visible_meme(&state, meme_id, is_owner, ReadEndpoint::GetMeme).await?;
+    let mut versions = state.meme_history.list_versions(meme_id).await?;
+    versions.retain(|version| version.is_visible_to(is_owner));
+    Ok(Json(MemeHistory { meme_id, current_version: current.version, versions }))
+}
+
+/// Handler for POST /meme/{id}/revert/{version}. Stores the metadata of an earlier version as
+/// the meme's next version; `If-Match` applies as for PATCH. Reverting to a private version,
+/// or reverting a private meme, needs owner credentials.
+pub async fn revert_meme(
+    State(state): State<Arc<AppState>>,
+    caller: Caller,
+    Path((id_str, version)): Path<(String, u64)>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, AppError> {
+    let meme_id = Uuid::parse_str(&id_str)?;
+    tracing::debug!(%meme_id, version, "Reverting meme via handler");
+    let is_owner = caller.is_owner();
+
+    let current = find_visible_meme(&state, meme_id, is_owner, ReadEndpoint::Writes).await?;
+    check_if_match(&headers, &current)?;
+    if !is_owner && version != current.version {
+        let earlier = state.meme_history.get_version(meme_id, version).await?;
+        if earlier.is_some_and(|earlier| !earlier.is_visible_to(false)) {
+            return Err(AppError::Forbidden("Only the owner can make a meme private".to_string()));
+        }
+    }
+
+    let meme = services::revert_meme(&state, current, version, &caller).await?;
+    let etag = meme.etag();
+    Ok(([(header::ETAG, etag)], Json(meme_view(&state, meme).await?)))
+}
+
+/// Handler for POST /meme/{id}/publish. Publishes a draft right away, whether or not it has a
+/// publish time; `If-Match` applies as for PATCH. Drafts are only visible to their owner, so
+/// anyone else gets 404.
+pub async fn publish_meme(
+    State(state): State<Arc<AppState>>,
+    caller: Caller,
+    Path(id_str): Path<String>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, AppError> {
+    let meme_id = Uuid::parse_str(&id_str)?;
+    tracing::debug!(%meme_id, "Publishing meme via handler");
+    let current = find_visible_meme(&state, meme_id, caller.is_owner(), ReadEndpoint::Writes).await?;
+    check_if_match(&headers, &current)?;
+
+    let meme = services::publish_meme(&state, current, &caller).await?;
+    let etag = meme.etag();
+    Ok(([(header::ETAG, etag)], Json(meme_view(&state, meme).await?)))
+}
+
+/// Evaluates an `If-Match` header value (`*` or a list of entity tags) against `etag` using
+/// strong comparison, so weak tags never match.
+fn etag_matches(if_match: &str, etag: &str) -> bool {
+    if_match.split(',').map(str::trim).any(|candidate| candidate == "*" || candidate == etag)
+}
+
+/// Query parameters for GET /memes besides `fields`.
+#[derive(Deserialize)]
+pub struct ListMemesQuery {
+    pub sort: Option<SortOrder>,
+    pub status: Option<MemeStatus>,
+    pub color: Option<String>,
+}
+
+/// Handler for GET /memes. `?sort=` orders the memes (unordered without it) and `?fields=`
+/// limits every meme to the named fields. Only published public memes are listed, unless
+/// `?status=draft` asks for the drafts not shown yet, which needs owner credentials.
+/// `?color=rrggbb` keeps the memes whose dominant color is within
+/// [`imaging::COLOR_MATCH_DISTANCE`] of it.
 pub async fn list_memes(
     State(state): State<Arc<AppState>>,
+    OwnerAccess(is_owner): OwnerAccess,
+    Query(query): Query<FieldsQuery>,
+    Query(list_query): Query<ListMemesQuery>,
 ) -> Result<impl IntoResponse, AppError> {
-    tracing::debug!("Listing all memes via handler");
-    let memes = state.meme_repo.list_all().await?;
+    let fields = query.parse(Meme::FIELDS)?;
+    let status = list_query.status.unwrap_or_default();
+    if status == MemeStatus::Draft && !is_owner {
+        return Err(AppError::Forbidden("Only the owner can list drafts".to_string()));
+    }
+    let color = list_query.color.as_deref().map(str::parse::<Rgb>).transpose().map_err(AppError::InvalidInput)?;
+    tracing::debug!(sort = ?list_query.sort, status = status.as_str(), "Listing all memes via handler");
+    let mut memes = match list_query.sort {
+        Some(order) => state.meme_repo.list_sorted(order).await?,
+        None => state.meme_repo.list_all().await?,
+    };
+    let now = chrono::Utc::now();
+    match status {
+        MemeStatus::Published => memes.retain(|meme| meme.is_listed(now)),
+        MemeStatus::Draft => memes.retain(|meme| !meme.is_published(now)),
+    }
+    if let Some(color) = color {
+        memes.retain(|meme| {
+            let dominant = meme.dominant_color.as_deref().and_then(|dominant| dominant.parse::<Rgb>().ok());
+            dominant.is_some_and(|dominant| dominant.distance(color) <= imaging::COLOR_MATCH_DISTANCE)
+        });
+    }
     tracing::info!("Handler successfully retrieved {} memes", memes.len());
-    Ok(Json(memes))
+    let mut views = Vec::with_capacity(memes.len());
+    for meme in memes {
+        views.push(meme_view(&state, meme).await?);
+    }
+    let views: Vec<_> = views.iter().map(|view| Sparse::new(view, fields.as_ref())).collect();
+    Ok(Json(views).into_response())
+}
+
+
+/// Query parameters for GET /memes/search besides `fields`.
+#[derive(Deserialize)]
+pub struct SearchQuery {
+    pub q: String,
+    #[serde(default)]
+    pub mode: SearchMode,
+}
+
+/// Handler for GET /memes/search. Lists published public memes matching `?q=`:
+///
+/// - `mode=keyword` (the default): memes whose title, tags, description or caption text
+///   (the text on the image, see [`crate::ocr`]) contain every word, best matches first and
+///   newest first among equals. Reads every meme, like an unsorted listing.
+/// - `mode=semantic`: the memes whose embedding is most similar to the query's, most
+///   similar first (see [`crate::embeddings`]); 400 when `APP_EMBEDDING_BACKEND` is `none`.
+///
+/// `?fields=` works as for GET /memes.
+pub async fn search_memes(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<FieldsQuery>,
+    Query(search_query): Query<SearchQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    let fields = query.parse(Meme::FIELDS)?;
+    let memes = match search_query.mode {
+        SearchMode::Keyword => keyword_search(&state, &search_query.q).await?,
+        SearchMode::Semantic => embeddings::semantic_search(&state, &search_query.q).await?,
+    };
+    let mut views = Vec::with_capacity(memes.len());
+    for meme in memes {
+        views.push(meme_view(&state, meme).await?);
+    }
+    let views: Vec<_> = views.iter().map(|view| Sparse::new(view, fields.as_ref())).collect();
+    Ok(Json(views).into_response())
+}
+
+/// The listed memes containing every word of `q`, ranked by [`search::relevance`].
+async fn keyword_search(state: &AppState, q: &str) -> Result<Vec<Meme>, AppError> {
+    let terms = search::query_terms(q);
+    if terms.is_empty() || terms.len() > search::MAX_QUERY_TERMS {
+        return Err(AppError::InvalidInput(format!("q must have between 1 and {} words", search::MAX_QUERY_TERMS)));
+    }
+    let now = chrono::Utc::now();
+    let mut matches: Vec<_> = state.meme_repo.list_all().await?
+        .into_iter()
+        .filter(|meme| meme.is_listed(now))
+        .filter_map(|meme| Some((search::relevance(&meme, &terms)?, meme)))
+        .collect();
+    matches.sort_by(|(a_score, a), (b_score, b)| b_score.cmp(a_score).then(b.created_at.cmp(&a.created_at)));
+    tracing::debug!(terms = terms.len(), matches = matches.len(), "Searched memes");
+    Ok(matches.into_iter().map(|(_, meme)| meme).collect())
+}
+
+/// Handler for GET /stats. Serves the latest aggregation, which is at most
+/// `APP_STATS_INTERVAL_SECS` old, so the endpoint stays cheap however many memes exist.
+pub async fn get_stats(State(state): State<Arc<AppState>>) -> Result<Json<MemeStats>, AppError> {
+    let stats = stats::current_stats(&state).await?;
+    Ok(Json(stats.as_ref().clone()))
+}
+
+/// Query parameters for GET /memes/trending.
+#[derive(Deserialize)]
+pub struct TrendingQuery {
+    pub window: Option<TrendingWindow>,
+}
+
+/// A meme in the GET /memes/trending response, with the score it was ranked by.
+#[derive(Serialize)]
+pub struct TrendingMemeView {
+    #[serde(flatten)]
+    pub view: MemeView,
+    pub score: f64,
+}
+
+/// Body of the GET /memes/trending response.
+#[derive(Serialize)]
+pub struct TrendingMemes {
+    pub window: TrendingWindow,
+    pub computed_at: chrono::DateTime<chrono::Utc>,
+    pub memes: Vec<TrendingMemeView>,
+}
+
+/// Handler for GET /memes/trending. Serves the latest rankings of the trending job (`?window=`
+/// `day`, `week` or `month`, default `day`), which are at most `APP_TRENDING_INTERVAL_SECS`
+/// old, so the endpoint costs no scan. The ranked memes (at most `APP_TRENDING_LIMIT`) are
+/// read again concurrently, one eventually consistent point read each, so memes deleted,
+/// made private or unpublished since are left out, as are those that expired.
+pub async fn trending_memes(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<TrendingQuery>,
+) -> Result<Json<TrendingMemes>, AppError> {
+    let window = query.window.unwrap_or_default();
+    let snapshot = trending::current_trending(&state).await?;
+    let now = chrono::Utc::now();
+    let current = snapshot.rankings.get(&window).into_iter().flatten().map(|ranked| async {
+        let meme = state.meme_repo.get_by_id(ranked.meme.meme_id, ConsistencyLevel::Eventual).await?;
+        match meme.filter(|meme| meme.is_listed(now) && !meme.is_expired(now)) {
+            Some(meme) => Ok(Some(TrendingMemeView { view: meme_view(&state, meme).await?, score: ranked.score })),
+            None => Ok::<_, AppError>(None),
+        }
+    });
+    let memes = futures::future::try_join_all(current).await?.into_iter().flatten().collect();
+    Ok(Json(TrendingMemes { window, computed_at: snapshot.computed_at, memes }))
+}
+
+/// Body of the POST /meme/{id}/like response.
+#[derive(Serialize)]
+pub struct LikeResponse {
+    pub meme_id: Uuid,
+    pub like_count: u64,
+}
+
+/// Handler for POST /meme/{id}/like. Likes are counted atomically and do not change the
+/// meme's version, so they never conflict with edits. Only memes the caller may see can be
+/// liked; others answer 404, as for GET.
+pub async fn like_meme(
+    State(state): State<Arc<AppState>>,
+    OwnerAccess(is_owner): OwnerAccess,
+    Path(id_str): Path<String>,
+) -> Result<Json<LikeResponse>, AppError> {
+    let meme_id = Uuid::parse_str(&id_str)?;
+    find_visible_meme(&state, meme_id, is_owner, ReadEndpoint::Writes).await?;
+    let like_count = state.meme_repo.add_like(meme_id).await?;
+    tracing::debug!(%meme_id, like_count, "Meme liked");
+    Ok(Json(LikeResponse { meme_id, like_count }))
 }
 
+/// Serves only meme images, never quarantined ones, and hides the images of private memes
+/// from everyone but the owner. Image keys end in `<meme_id>.<ext>` whatever the layout, so
+/// the meme is found without an index; keys that name no meme (backups, staged uploads)
+/// answer 404 like missing images, as do the images of expired memes until they are purged.
+async fn check_image_access(state: &AppState, key: &str, is_owner: bool) -> Result<(), AppError> {
+    let Some(meme_id) = keys::servable_meme_id(key) else {
+        return Err(AppError::ImageNotFound(key.to_string()));
+    };
+    if is_owner {
+        return Ok(());
+    }
+    match state.meme_repo.get_by_id(meme_id, state.config.read_consistency(ReadEndpoint::Images)).await? {
+        Some(meme) if meme.is_visible_to(false) => Ok(()),
+        _ => Err(AppError::ImageNotFound(key.to_string())),
+    }
+}
 
-/// Handler for GET /images/{key}
+/// Handler for GET /images/{key}. Images of private memes need owner credentials.
+/// Quarantined images are never served; moderators download them with
+/// GET /meme/{id}/download.
 pub async fn get_image(
     State(state): State<Arc<AppState>>,
+    OwnerAccess(is_owner): OwnerAccess,
     Path(key): Path<String>,
 ) -> Result<Response, AppError> {
     tracing::debug!(image_key = %key, "Fetching image file via handler");
+    check_image_access(&state, &key, is_owner).await?;
 
-    let (byte_stream, content_type) = state.file_storage.download(&key).await?;
-
-    let content_type_header = content_type
-        .as_deref()
-        .unwrap_or("application/octet-stream");
+    let (byte_stream, metadata) = state.file_storage.download(&key).await?;
 
     // --- WORKAROUND: Collect the stream into memory ---
     let data = byte_stream
@@ -174,28 +640,270 @@ pub async fn get_image(
     let body = Body::from(bytes);
     // -------------------------------------------------
 
-    let response = Response::builder()
-        .status(StatusCode::OK)
-        .header(header::CONTENT_TYPE, content_type_header)
+    let response = image_response(&metadata)
         .body(body) // Use body created from collected Bytes
         .map_err(|e| AppError::InternalServerError(format!("Failed to build image response: {}", e)))?;
 
     Ok(response)
 }
 
+/// Handler for HEAD /images/{key}. Answers with the headers a GET would send, read with
+/// S3 HeadObject, so clients can check existence and size without downloading the image.
+pub async fn head_image(
+    State(state): State<Arc<AppState>>,
+    OwnerAccess(is_owner): OwnerAccess,
+    Path(key): Path<String>,
+) -> Result<Response, AppError> {
+    check_image_access(&state, &key, is_owner).await?;
+    let metadata = state.file_storage.head(&key).await?;
+    let mut response = image_response(&metadata);
+    if let Some(content_length) = metadata.content_length {
+        response = response.header(header::CONTENT_LENGTH, content_length);
+    }
+    response
+        .body(Body::empty())
+        .map_err(|e| AppError::InternalServerError(format!("Failed to build image response: {}", e)))
+}
+
+/// Handler for GET /meme/{id}/download. Streams the meme's image as an attachment named
+/// after its title, so browsers save it as e.g. `Distracted boyfriend.png`.
+pub async fn download_meme(
+    State(state): State<Arc<AppState>>,
+    OwnerAccess(is_owner): OwnerAccess,
+    Path(id_str): Path<String>,
+) -> Result<Response, AppError> {
+    let meme_id = Uuid::parse_str(&id_str)?;
+    let meme = find_visible_meme(&state, meme_id, is_owner, ReadEndpoint::Images).await?;
+    let (byte_stream, metadata) = state.file_storage.download(&meme.image_key).await?;
+
+    let mut response = image_response(&metadata)
+        .header(header::CONTENT_DISPOSITION, attachment_disposition(&meme.title, &meme.image_key));
+    if let Some(content_length) = metadata.content_length {
+        response = response.header(header::CONTENT_LENGTH, content_length);
+    }
+    response
+        .body(Body::from_stream(ReaderStream::new(byte_stream.into_async_read())))
+        .map_err(|e| AppError::InternalServerError(format!("Failed to build download response: {}", e)))
+}
+
+/// Handler for GET /meme/{id}/image. Redirects to the meme's current image URL, so others can
+/// keep a link that outlives presigned and signed URLs.
+pub async fn redirect_to_image(
+    State(state): State<Arc<AppState>>,
+    OwnerAccess(is_owner): OwnerAccess,
+    Path(id_str): Path<String>,
+) -> Result<Response, AppError> {
+    let meme_id = Uuid::parse_str(&id_str)?;
+    let meme = find_visible_meme(&state, meme_id, is_owner, ReadEndpoint::Images).await?;
+    let location = meme_view(&state, meme).await?.image_url;
+    // Not cached, since the image URL may expire
+    Ok((StatusCode::FOUND, [(header::LOCATION, location), (header::CACHE_CONTROL, "no-store".to_string())]).into_response())
+}
+
+/// Query parameters for POST /meme/{id}/share.
+#[derive(Deserialize)]
+pub struct ShareQuery {
+    /// Link lifetime in seconds; `APP_SHARE_LINK_TTL_SECS` when omitted.
+    pub expires_in: Option<u64>,
+}
+
+/// Body of the POST /meme/{id}/share response. URLs are relative to this server.
+#[derive(Serialize)]
+pub struct ShareLink {
+    pub url: String,
+    pub image_url: String,
+    pub expires_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// Handler for POST /meme/{id}/share (admin only). Returns a signed link that lets anyone
+/// holding it view the meme and its image until it expires, without other credentials.
+pub async fn create_share_link(
+    State(state): State<Arc<AppState>>,
+    Path(id_str): Path<String>,
+    Query(query): Query<ShareQuery>,
+) -> Result<(StatusCode, Json<ShareLink>), AppError> {
+    let meme_id = Uuid::parse_str(&id_str)?;
+    let Some(secret) = state.config.share_secret.as_deref() else {
+        return Err(AppError::Forbidden("Share links are disabled".to_string()));
+    };
+    let lifetime = query.expires_in.unwrap_or(state.config.share_link_ttl_secs);
+    if lifetime == 0 || lifetime > state.config.share_link_max_ttl_secs {
+        return Err(AppError::InvalidInput(format!(
+            "expires_in must be between 1 and {} seconds",
+            state.config.share_link_max_ttl_secs
+        )));
+    }
+    if !state.meme_repo.exists(meme_id).await? {
+        return Err(AppError::MemeNotFound(meme_id));
+    }
+
+    let grant = ShareGrant {
+        meme_id,
+        // Whole seconds, as encoded in the token
+        expires_at: chrono::DateTime::from_timestamp(chrono::Utc::now().timestamp() + lifetime as i64, 0)
+            .ok_or_else(|| AppError::InvalidInput("expires_in is out of range".to_string()))?,
+    };
+    let url = format!("/shared/{}", grant.sign(secret));
+    tracing::info!(%meme_id, expires_at = %grant.expires_at, "Share link created");
+    Ok((
+        StatusCode::CREATED,
+        Json(ShareLink { image_url: format!("{}/image", url), url, expires_at: grant.expires_at }),
+    ))
+}
+
+/// Handler for GET /shared/{token}. The token was verified by `share::require_share_token`.
+/// Memes awaiting approval answer 404, as their image URL would lead into quarantine.
+pub async fn get_shared_meme(
+    State(state): State<Arc<AppState>>,
+    Extension(grant): Extension<ShareGrant>,
+) -> Result<impl IntoResponse, AppError> {
+    let meme = state.meme_repo.get_by_id(grant.meme_id, state.config.read_consistency(ReadEndpoint::Shared)).await?
+        .filter(|meme| !meme.is_awaiting_approval())
+        .ok_or(AppError::MemeNotFound(grant.meme_id))?;
+    let etag = meme.etag();
+    Ok(([(header::ETAG, etag)], Json(meme_view(&state, meme).await?)))
+}
+
+/// Handler for GET /shared/{token}/image. Streams the shared meme's image, unless it awaits
+/// approval.
+pub async fn get_shared_image(
+    State(state): State<Arc<AppState>>,
+    Extension(grant): Extension<ShareGrant>,
+) -> Result<Response, AppError> {
+    let meme = state.meme_repo.get_by_id(grant.meme_id, state.config.read_consistency(ReadEndpoint::Shared)).await?
+        .ok_or(AppError::MemeNotFound(grant.meme_id))?;
+    if meme.is_awaiting_approval() {
+        return Err(AppError::ImageNotFound(meme.image_key));
+    }
+    let (byte_stream, metadata) = state.file_storage.download(&meme.image_key).await?;
+    image_response(&metadata)
+        .body(Body::from_stream(ReaderStream::new(byte_stream.into_async_read())))
+        .map_err(|e| AppError::InternalServerError(format!("Failed to build image response: {}", e)))
+}
+
+/// Longest filename stem offered for downloads, in characters.
+const MAX_FILENAME_CHARS: usize = 100;
+
+/// `Content-Disposition` value for saving an image under the meme's title. Characters that are
+/// unsafe in filenames are dropped. `filename` carries an ASCII-only fallback; non-ASCII
+/// titles are also given as an RFC 5987 `filename*`.
+fn attachment_disposition(title: &str, image_key: &str) -> String {
+    let stem: String = title
+        .chars()
+        .filter(|c| !c.is_control() && !matches!(c, '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|'))
+        .collect::<String>()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .trim_start_matches('.')
+        .chars()
+        .take(MAX_FILENAME_CHARS)
+        .collect();
+    let stem = if stem.is_empty() { "meme".to_string() } else { stem };
+    let filename = match image_key.rsplit_once('.') {
+        Some((_, extension)) if !extension.is_empty() && extension.chars().all(|c| c.is_ascii_alphanumeric()) => {
+            format!("{}.{}", stem, extension.to_ascii_lowercase())
+        }
+        _ => stem,
+    };
+
+    let ascii: String = filename.chars().map(|c| if c.is_ascii() { c } else { '_' }).collect();
+    if ascii == filename {
+        return format!("attachment; filename=\"{}\"", filename);
+    }
+    // RFC 5987 attr-chars are sent as they are; every other byte is percent-encoded
+    let encoded: String = filename
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'!' | b'#' | b'$' | b'&' | b'+' | b'-' | b'.' | b'^' | b'_' | b'`' | b'|' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect();
+    format!("attachment; filename=\"{}\"; filename*=UTF-8''{}", ascii, encoded)
+}
+
+/// Status and headers shared by GET and HEAD on an image.
+fn image_response(metadata: &ObjectMetadata) -> axum::http::response::Builder {
+    let content_type = metadata.content_type.as_deref().unwrap_or("application/octet-stream");
+    let mut response = Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, content_type);
+    if let Some(etag) = &metadata.etag {
+        response = response.header(header::ETAG, etag);
+    }
+    response
+}
+
+
+/// Query parameters for GET /export. `ids` is a comma-separated list of meme IDs.
+#[derive(Deserialize)]
+pub struct ExportQuery {
+    pub ids: Option<String>,
+    pub tag: Option<String>,
+}
+
+/// Handler for GET /export. Streams a ZIP of the selected memes' images plus a `manifest.json`.
+/// With neither `ids` nor `tag`, every listed meme is exported, and with owner credentials every
+/// meme, so the archive can serve as a backup. Memes named in `ids` may also be unlisted, or
+/// private with owner credentials.
+pub async fn export_memes(
+    State(state): State<Arc<AppState>>,
+    OwnerAccess(is_owner): OwnerAccess,
+    Query(query): Query<ExportQuery>,
+) -> Result<Response, AppError> {
+    let mut memes: Vec<Meme> = match &query.ids {
+        Some(ids) => {
+            let consistency = state.config.read_consistency(ReadEndpoint::Export);
+            let mut selected = Vec::new();
+            for id_str in ids.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+                let meme_id = Uuid::parse_str(id_str)?;
+                match state.meme_repo.get_by_id(meme_id, consistency).await?.filter(|meme| meme.is_visible_to(is_owner)) {
+                    Some(meme) => selected.push(meme),
+                    None => tracing::debug!(%meme_id, "Requested meme not found, omitting from export"),
+                }
+            }
+            selected
+        }
+        None => {
+            let mut memes = state.meme_repo.list_all().await?;
+            // The owner's private, unlisted and unpublished memes belong in a backup too
+            let now = chrono::Utc::now();
+            memes.retain(|meme| is_owner || meme.is_listed(now));
+            memes
+        }
+    };
+    if let Some(tag) = &query.tag {
+        let tag = tag.trim().to_lowercase();
+        memes.retain(|meme| meme.tags.contains(&tag));
+    }
+    tracing::info!(count = memes.len(), "Starting meme export");
+
+    let body = export::export_archive(state.file_storage.clone(), memes);
+    Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, "application/zip")
+        .header(header::CONTENT_DISPOSITION, "attachment; filename=\"memes-export.zip\"")
+        .body(body)
+        .map_err(|e| AppError::InternalServerError(format!("Failed to build export response: {}", e)))
+}
+
 
 /// Deletes the meme metadata and its corresponding image file.
+/// Private memes can only be deleted by their owner.
 pub async fn delete_meme(
     State(state): State<Arc<AppState>>,
+    caller: Caller,
     Path(id_str): Path<String>,
 ) -> Result<StatusCode, AppError> { // Return only status code on success
     // Validate UUID format
     let meme_id = Uuid::parse_str(&id_str)?;
     tracing::debug!(%meme_id, "Deleting meme via handler");
+    let is_owner = caller.is_owner();
 
     // 1. Get the meme metadata first to ensure it exists and to get the image_key
-    let meme_to_delete = state.meme_repo.get_by_id(meme_id).await? // -> RepoError -> AppError
-        .ok_or(AppError::MemeNotFound(meme_id))?; // If None, map to AppError::MemeNotFound (404)
+    let meme_to_delete = find_visible_meme(&state, meme_id, is_owner, ReadEndpoint::Writes).await?; // Missing or hidden -> 404
 
     // 2. Delete the image file from S3 storage
     // We proceed even if S3 delete fails for "not found", but fail on other errors.
@@ -214,9 +922,55 @@ pub async fn delete_meme(
 
     // 3. Delete the meme metadata from the repository
     state.meme_repo.delete(meme_id).await?; // Propagate RepoError -> AppError
+    audit::record(&state, &caller, AuditAction::Deleted, Some(&meme_to_delete), None).await;
+    // The meme is gone either way; leftover versions are only unreachable
+    if let Err(e) = state.meme_history.delete_versions(meme_id).await {
+        tracing::warn!(%meme_id, error = %e, "Failed to delete the meme's history");
+    }
+    embeddings::unindex_meme(&state, meme_id).await;
+    // Deleting a meme awaiting approval is how moderators reject it
+    if meme_to_delete.is_awaiting_approval() {
+        notifications::notify(&state, Notice::Rejected, &meme_to_delete).await;
+    }
 
     tracing::info!(%meme_id, "Meme deleted successfully via handler");
 
     // 4. Return 204 No Content on successful deletion
     Ok(StatusCode::NO_CONTENT)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn if_match_compares_strongly() {
+        assert!(etag_matches("\"3\"", "\"3\""));
+        assert!(etag_matches("\"1\", \"3\"", "\"3\""));
+        assert!(etag_matches("*", "\"3\""));
+        assert!(!etag_matches("W/\"3\"", "\"3\""));
+        assert!(!etag_matches("\"2\"", "\"3\""));
+    }
+
+    #[test]
+    fn attachment_names_follow_the_title() {
+        assert_eq!(attachment_disposition("Funny cat", "abc.PNG"), "attachment; filename=\"Funny cat.png\"");
+        assert_eq!(attachment_disposition(" ../a/b:c?  \"d\" ", "abc.jpg"), "attachment; filename=\"abc d.jpg\"");
+        assert_eq!(attachment_disposition("???", "abc"), "attachment; filename=\"meme\"");
+        assert_eq!(attachment_disposition("Title", "abc.p-g"), "attachment; filename=\"Title\"");
+    }
+
+    #[test]
+    fn non_ascii_titles_get_an_encoded_filename() {
+        assert_eq!(
+            attachment_disposition("Café meme", "abc.gif"),
+            "attachment; filename=\"Caf_ meme.gif\"; filename*=UTF-8''Caf%C3%A9%20meme.gif"
+        );
+    }
+
+    #[test]
+    fn long_titles_are_cut() {
+        let disposition = attachment_disposition(&"x".repeat(150), "abc.png");
+        assert_eq!(disposition, format!("attachment; filename=\"{}.png\"", "x".repeat(MAX_FILENAME_CHARS)));
+    }
+}
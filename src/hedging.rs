@@ -0,0 +1,204 @@
+//! Hedged meme lookups (`APP_HEDGED_READS`). When a `get_by_id` has not answered within the
+//! `APP_HEDGE_PERCENTILE` latency of recent lookups, the same read is sent a second time and
+//! the first successful answer wins; the other request is dropped. Only the slowest few
+//! percent of lookups are sent twice, which cuts tail latency from a slow storage node or
+//! connection at little extra read capacity. Writes and listings are never hedged.
+
+use crate::{
+    domain::{ConsistencyLevel, MemeRepository, TableInfo},
+    errors::RepoError,
+    models::{Meme, SortOrder},
+};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use uuid::Uuid;
+
+/// Latencies of this many recent lookups make up the percentile.
+const WINDOW: usize = 1000;
+
+/// Nothing is hedged before this many lookups were measured.
+const MIN_SAMPLES: usize = 100;
+
+/// The hedge delay is worked out again after this many lookups.
+const RECOMPUTE_EVERY: u64 = 50;
+
+/// When to send a hedge: the configured percentile of recent lookup latencies, but never
+/// sooner than `min_delay`.
+pub struct HedgePolicy {
+    backend: &'static str,
+    percentile: f64,
+    min_delay: Duration,
+    window: Mutex<LatencyWindow>,
+}
+
+#[derive(Default)]
+struct LatencyWindow {
+    samples: VecDeque<Duration>,
+    recorded: u64,
+    delay: Option<Duration>,
+}
+
+impl HedgePolicy {
+    pub fn new(backend: &'static str, percentile: f64, min_delay: Duration) -> Self {
+        Self { backend, percentile, min_delay, window: Mutex::new(LatencyWindow::default()) }
+    }
+
+    /// How long a lookup may take before it is hedged; `None` until enough were measured.
+    fn delay(&self) -> Option<Duration> {
+        self.window.lock().expect("hedge window lock poisoned").delay
+    }
+
+    /// Adds a lookup's latency to the window. A request dropped because the other one won
+    /// is recorded with how long it had run, a lower bound of its latency.
+    fn record(&self, latency: Duration) {
+        let mut window = self.window.lock().expect("hedge window lock poisoned");
+        if window.samples.len() == WINDOW {
+            window.samples.pop_front();
+        }
+        window.samples.push_back(latency);
+        window.recorded += 1;
+        let due = window.delay.is_none() || window.recorded.is_multiple_of(RECOMPUTE_EVERY);
+        if window.samples.len() < MIN_SAMPLES || !due {
+            return;
+        }
+        let mut sorted: Vec<Duration> = window.samples.iter().copied().collect();
+        sorted.sort_unstable();
+        let rank = ((sorted.len() as f64 * self.percentile / 100.0).ceil() as usize).clamp(1, sorted.len());
+        let delay = sorted[rank - 1].max(self.min_delay);
+        window.delay = Some(delay);
+        metrics::gauge!("hedge_delay_seconds", "backend" => self.backend).set(delay.as_secs_f64());
+    }
+}
+
+/// Hedges `get_by_id` on the inner repository per its [`HedgePolicy`]; every other call
+/// goes straight through.
+pub struct HedgedMemeRepository {
+    inner: Arc<dyn MemeRepository>,
+    policy: HedgePolicy,
+}
+
+impl HedgedMemeRepository {
+    pub fn new(inner: Arc<dyn MemeRepository>, policy: HedgePolicy) -> Self {
+        Self { inner, policy }
+    }
+
+    /// Counts a hedged lookup by the request that answered it, in `hedged_reads_total`.
+    fn won(&self, winner: &'static str) {
+        metrics::counter!("hedged_reads_total", "backend" => self.policy.backend, "winner" => winner).increment(1);
+    }
+}
+
+#[async_trait]
+impl MemeRepository for HedgedMemeRepository {
+    async fn create(&self, meme: &Meme) -> Result<(), RepoError> {
+        self.inner.create(meme).await
+    }
+
+    /// Sends the lookup again once it outlasts the hedge delay, and returns the first
+    /// successful answer; the error of the first request when both fail.
+    async fn get_by_id(&self, id: Uuid, consistency: ConsistencyLevel) -> Result<Option<Meme>, RepoError> {
+        let started = Instant::now();
+        let first = self.inner.get_by_id(id, consistency);
+        tokio::pin!(first);
+        let Some(delay) = self.policy.delay() else {
+            let result = first.await;
+            self.policy.record(started.elapsed());
+            return result;
+        };
+        tokio::select! {
+            result = &mut first => {
+                self.policy.record(started.elapsed());
+                return result;
+            }
+            _ = tokio::time::sleep(delay) => {}
+        }
+
+        tracing::debug!(%id, delay_ms = delay.as_millis() as u64, "Meme lookup is slow; sending a hedged request");
+        let hedged = Instant::now();
+        let second = self.inner.get_by_id(id, consistency);
+        tokio::pin!(second);
+        tokio::select! {
+            result = &mut first => {
+                self.policy.record(started.elapsed());
+                if result.is_ok() {
+                    self.policy.record(hedged.elapsed());
+                    self.won("first");
+                    return result;
+                }
+                let hedge_result = second.await;
+                self.policy.record(hedged.elapsed());
+                self.won(if hedge_result.is_ok() { "hedge" } else { "none" });
+                hedge_result.or(result)
+            }
+            hedge_result = &mut second => {
+                self.policy.record(hedged.elapsed());
+                if hedge_result.is_ok() {
+                    self.policy.record(started.elapsed());
+                    self.won("hedge");
+                    return hedge_result;
+                }
+                let result = first.await;
+                self.policy.record(started.elapsed());
+                self.won(if result.is_ok() { "first" } else { "none" });
+                result
+            }
+        }
+    }
+
+    async fn exists(&self, id: Uuid) -> Result<bool, RepoError> {
+        self.inner.exists(id).await
+    }
+
+    async fn list_all(&self) -> Result<Vec<Meme>, RepoError> {
+        self.inner.list_all().await
+    }
+
+    async fn list_sorted(&self, order: SortOrder) -> Result<Vec<Meme>, RepoError> {
+        self.inner.list_sorted(order).await
+    }
+
+    async fn list_expired(&self, now: DateTime<Utc>) -> Result<Vec<Meme>, RepoError> {
+        self.inner.list_expired(now).await
+    }
+
+    async fn list_due_for_publishing(&self, now: DateTime<Utc>) -> Result<Vec<Meme>, RepoError> {
+        self.inner.list_due_for_publishing(now).await
+    }
+
+    async fn update(&self, meme: &Meme, expected_version: u64) -> Result<(), RepoError> {
+        self.inner.update(meme, expected_version).await
+    }
+
+    async fn add_like(&self, id: Uuid) -> Result<u64, RepoError> {
+        self.inner.add_like(id).await
+    }
+
+    async fn add_views(&self, id: Uuid, views: u64) -> Result<(), RepoError> {
+        self.inner.add_views(id, views).await
+    }
+
+    async fn delete(&self, id: Uuid) -> Result<(), RepoError> {
+        self.inner.delete(id).await
+    }
+
+    async fn describe(&self) -> Result<TableInfo, RepoError> {
+        self.inner.describe().await
+    }
+
+    async fn count_created_since(&self, since: DateTime<Utc>) -> Result<u64, RepoError> {
+        self.inner.count_created_since(since).await
+    }
+
+    async fn count(&self) -> Result<u64, RepoError> {
+        self.inner.count().await
+    }
+
+    async fn ping(&self) -> Result<(), RepoError> {
+        self.inner.ping().await
+    }
+}
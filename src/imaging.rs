@@ -0,0 +1,113 @@
+use axum::body::Bytes;
+use image::{ImageReader, Limits};
+use std::{collections::HashMap, fmt, io::Cursor, str::FromStr};
+
+/// Most colors kept in a meme's palette, the dominant one first.
+pub const PALETTE_SIZE: usize = 5;
+
+/// Largest distance between two colors (Euclidean, in RGB units) at which `?color=` still
+/// matches a meme's dominant color: about a quarter of the way from a primary color to black.
+pub const COLOR_MATCH_DISTANCE: f64 = 64.0;
+
+/// Side of the thumbnail colors are counted on, in pixels. Small enough to count quickly,
+/// large enough to keep every color covering a noticeable part of the image.
+const SAMPLE_SIZE: u32 = 64;
+
+/// Largest image decoded for its palette, in bytes of decoded pixels; bigger images get none.
+const MAX_DECODED_BYTES: u64 = 256 * 1024 * 1024;
+
+/// An sRGB color, written as `#rrggbb`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Rgb(pub [u8; 3]);
+
+impl Rgb {
+    /// Euclidean distance to `other` in RGB space, from 0 to about 441.
+    pub fn distance(self, other: Rgb) -> f64 {
+        self.0
+            .iter()
+            .zip(other.0)
+            .map(|(a, b)| (f64::from(*a) - f64::from(b)).powi(2))
+            .sum::<f64>()
+            .sqrt()
+    }
+}
+
+impl fmt::Display for Rgb {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let [r, g, b] = self.0;
+        write!(f, "#{:02x}{:02x}{:02x}", r, g, b)
+    }
+}
+
+impl FromStr for Rgb {
+    type Err = String;
+
+    /// Parses six hex digits, with or without a leading `#`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let hex = s.strip_prefix('#').unwrap_or(s);
+        let invalid = || format!("'{}' is not a color (expected six hex digits, like ff0000)", s);
+        if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(invalid());
+        }
+        let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).map_err(|_| invalid());
+        Ok(Rgb([channel(0)?, channel(2)?, channel(4)?]))
+    }
+}
+
+/// The colors of an image: the one covering most of it, and up to [`PALETTE_SIZE`] most
+/// common ones (the dominant color first).
+#[derive(Clone, Debug, PartialEq)]
+pub struct ImageColors {
+    pub dominant: Rgb,
+    pub palette: Vec<Rgb>,
+}
+
+/// Width and height of the image in pixels, read from its header without decoding it.
+/// `None` for formats the decoder does not read and for unreadable headers.
+pub fn dimensions(data: &[u8]) -> Option<(u32, u32)> {
+    ImageReader::new(Cursor::new(data)).with_guessed_format().ok()?.into_dimensions().ok()
+}
+
+/// Decodes the image and counts the colors of a thumbnail, grouped into 4096 bins (16
+/// levels per channel) and averaged within each bin. Transparent pixels are ignored.
+/// `None` for formats the decoder does not read (anything but JPEG, PNG, GIF and WebP),
+/// undecodable or oversized images, and fully transparent ones. CPU-bound: run it off the
+/// async workers.
+pub fn extract_colors(data: &[u8]) -> Option<ImageColors> {
+    let mut reader = ImageReader::new(Cursor::new(data)).with_guessed_format().ok()?;
+    let mut limits = Limits::default();
+    limits.max_alloc = Some(MAX_DECODED_BYTES);
+    reader.limits(limits);
+    let thumbnail = reader.decode().ok()?.thumbnail(SAMPLE_SIZE, SAMPLE_SIZE).to_rgba8();
+
+    let mut bins: HashMap<[u8; 3], (u64, [u64; 3])> = HashMap::new();
+    for pixel in thumbnail.pixels().filter(|pixel| pixel.0[3] >= 128) {
+        let [r, g, b, _] = pixel.0;
+        let (count, sums) = bins.entry([r >> 4, g >> 4, b >> 4]).or_default();
+        *count += 1;
+        for (sum, channel) in sums.iter_mut().zip([r, g, b]) {
+            *sum += u64::from(channel);
+        }
+    }
+    let mut bins: Vec<_> = bins.into_iter().collect();
+    // Ties broken by bin, so the same image always gets the same palette
+    bins.sort_by(|(a_bin, (a_count, _)), (b_bin, (b_count, _))| b_count.cmp(a_count).then(a_bin.cmp(b_bin)));
+    let palette: Vec<Rgb> = bins
+        .into_iter()
+        .take(PALETTE_SIZE)
+        .map(|(_, (count, sums))| Rgb(sums.map(|sum| (sum / count) as u8)))
+        .collect();
+    Some(ImageColors { dominant: *palette.first()?, palette })
+}
+
+/// [`extract_colors`] on a blocking thread. Colors only help clients render placeholders
+/// and filter listings, so images without them are stored anyway.
+pub async fn colors_of(data: Bytes) -> Option<ImageColors> {
+    match tokio::task::spawn_blocking(move || extract_colors(&data)).await {
+        Ok(colors) => colors,
+        Err(e) => {
+            tracing::warn!(error = %e, "Extracting the colors of an uploaded image failed");
+            None
+        }
+    }
+}
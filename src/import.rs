@@ -0,0 +1,315 @@
+use crate::{
+    content_filter::ContentFilter,
+    domain::{FileStorage, MemeRepository, UploadOptions},
+    errors::{AppError, RepoError, StorageError},
+    export::{ExportManifest, IMAGES_DIR, MANIFEST_ENTRY},
+    models::Meme,
+    validation::{self, MemeSubmission, ValidationErrors, ValidationLimits},
+};
+use async_zip::base::read::stream::ZipFileReader;
+use futures::{stream, AsyncReadExt, StreamExt};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use tokio::io::AsyncBufRead;
+use uuid::Uuid;
+
+/// Largest single file accepted from an archive, matching the upload body limit.
+const MAX_ENTRY_BYTES: u64 = 10 * 1024 * 1024;
+/// Memes created at once; each create is a conditional write of its own.
+const CREATE_CONCURRENCY: usize = 16;
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportStatus {
+    Created,
+    /// A meme with this ID already exists; nothing was changed.
+    Skipped,
+    /// The meme failed validation, its image was missing or rejected, or the write failed.
+    Failed,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ImportItemResult {
+    pub meme_id: Uuid,
+    pub status: ImportStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Per-item outcome of an import, returned to the client.
+#[derive(Serialize, Debug, Default)]
+pub struct ImportReport {
+    pub created: usize,
+    pub skipped: usize,
+    pub failed: usize,
+    pub items: Vec<ImportItemResult>,
+}
+
+impl ImportReport {
+    fn record(&mut self, meme_id: Uuid, status: ImportStatus, error: Option<String>) {
+        match status {
+            ImportStatus::Created => self.created += 1,
+            ImportStatus::Skipped => self.skipped += 1,
+            ImportStatus::Failed => self.failed += 1,
+        }
+        self.items.push(ImportItemResult { meme_id, status, error });
+    }
+}
+
+/// Restores memes from an archive produced by the export endpoint.
+///
+/// The archive is read as a stream, one entry at a time. Images already present in storage
+/// are left untouched, even when the meme using them has expired; other images are checked
+/// against `limits`, uploaded as they arrive and removed again if their meme ends up not
+/// being created.
+pub async fn import_archive<R>(
+    repo: &dyn MemeRepository,
+    storage: &dyn FileStorage,
+    limits: &ValidationLimits,
+    filter: &ContentFilter,
+    reader: R,
+) -> Result<ImportReport, AppError>
+where
+    R: AsyncBufRead + Unpin,
+{
+    let mut images = ArchiveImages::default();
+    let result = read_entries(storage, limits, reader, &mut images).await;
+    let manifest = match result {
+        Ok(Some(manifest)) => manifest,
+        Ok(None) => {
+            remove_images(storage, images.uploaded.iter()).await;
+            return Err(AppError::InvalidInput(format!("Archive has no {}", MANIFEST_ENTRY)));
+        }
+        Err(e) => {
+            remove_images(storage, images.uploaded.iter()).await;
+            return Err(e);
+        }
+    };
+
+    let mut keys_by_id: HashMap<Uuid, String> = HashMap::new();
+    for meme in &manifest.memes {
+        keys_by_id.entry(meme.meme_id).or_insert_with(|| meme.image_key.clone());
+    }
+    let report = create_missing(repo, manifest.memes, limits, filter, Some(&images)).await?;
+
+    // Drop uploaded images that did not end up attached to a newly created meme
+    let attached: HashSet<&str> = report
+        .items
+        .iter()
+        .filter(|item| item.status == ImportStatus::Created)
+        .filter_map(|item| keys_by_id.get(&item.meme_id).map(String::as_str))
+        .collect();
+    remove_images(storage, images.uploaded.iter().filter(|key| !attached.contains(key.as_str()))).await;
+
+    Ok(report)
+}
+
+/// Restores memes from a bare manifest whose images are already present in storage.
+pub async fn import_manifest(
+    repo: &dyn MemeRepository,
+    limits: &ValidationLimits,
+    filter: &ContentFilter,
+    manifest: ExportManifest,
+) -> Result<ImportReport, AppError> {
+    create_missing(repo, manifest.memes, limits, filter, None).await
+}
+
+/// Images found in an archive, by what became of them.
+#[derive(Default)]
+struct ArchiveImages {
+    /// Uploaded by this import.
+    uploaded: HashSet<String>,
+    /// Already in storage, so not uploaded again.
+    present: HashSet<String>,
+    /// Refused by validation, with the reason.
+    rejected: HashMap<String, String>,
+}
+
+impl ArchiveImages {
+    /// Why a meme using `key` cannot be created, if it cannot.
+    fn problem(&self, key: &str) -> Option<String> {
+        if let Some(reason) = self.rejected.get(key) {
+            Some(reason.clone())
+        } else if self.uploaded.contains(key) || self.present.contains(key) {
+            None
+        } else {
+            Some("missing from archive".to_string())
+        }
+    }
+}
+
+/// Validates and creates every meme of the manifest. Creates are conditional, so a meme
+/// whose ID is already stored (expired ones included) is reported as skipped and left as
+/// it is. When `images` is given, memes whose image was not stored from the archive are
+/// reported as failed instead of being created.
+async fn create_missing(
+    repo: &dyn MemeRepository,
+    memes: Vec<Meme>,
+    limits: &ValidationLimits,
+    filter: &ContentFilter,
+    images: Option<&ArchiveImages>,
+) -> Result<ImportReport, AppError> {
+    let mut report = ImportReport::default();
+    let mut to_create = Vec::new();
+    let mut seen = HashSet::new();
+
+    for meme in memes {
+        let meme_id = meme.meme_id;
+        if !seen.insert(meme_id) {
+            report.record(meme_id, ImportStatus::Skipped, None);
+            continue;
+        }
+        if let Some(problem) = images.and_then(|images| images.problem(&meme.image_key)) {
+            // A meme that is already stored keeps its image, so it is skipped as usual
+            if repo.exists(meme_id).await? {
+                report.record(meme_id, ImportStatus::Skipped, None);
+            } else {
+                report.record(meme_id, ImportStatus::Failed, Some(format!("image '{}' {}", meme.image_key, problem)));
+            }
+            continue;
+        }
+        match validate_item(meme, limits, filter) {
+            Ok(meme) => to_create.push(meme),
+            Err(errors) => report.record(meme_id, ImportStatus::Failed, Some(describe(&errors))),
+        }
+    }
+
+    let mut outcomes = stream::iter(to_create)
+        .map(|meme| async move { (meme.meme_id, repo.create(&meme).await) })
+        .buffer_unordered(CREATE_CONCURRENCY);
+    while let Some((meme_id, outcome)) = outcomes.next().await {
+        match outcome {
+            Ok(()) => report.record(meme_id, ImportStatus::Created, None),
+            Err(RepoError::AlreadyExists(_)) => report.record(meme_id, ImportStatus::Skipped, None),
+            Err(e) => {
+                tracing::warn!(%meme_id, error = ?e, "Failed to import meme");
+                report.record(meme_id, ImportStatus::Failed, Some(e.to_string()));
+            }
+        }
+    }
+
+    tracing::info!(created = report.created, skipped = report.skipped, failed = report.failed, "Import finished");
+    Ok(report)
+}
+
+/// Holds an imported meme to the rules of the upload API, applying the same normalization
+/// (trimmed text, lowercased tags, masked terms).
+fn validate_item(meme: Meme, limits: &ValidationLimits, filter: &ContentFilter) -> Result<Meme, ValidationErrors> {
+    let submission = MemeSubmission {
+        title: Some(meme.title.clone()),
+        description: Some(meme.description.clone()),
+        tags: meme.tags.clone(),
+        ..MemeSubmission::default()
+    };
+    let (fields, mut errors) = match validation::validate_submission(submission, limits, filter) {
+        Ok(fields) => (Some(fields), ValidationErrors::new()),
+        Err(errors) => (None, errors),
+    };
+    if let Some(size) = meme.size_bytes.filter(|size| *size > limits.max_upload_bytes as u64) {
+        errors.add("image", format!("must be at most {} bytes (got {})", limits.max_upload_bytes, size));
+    }
+    match fields {
+        Some(fields) if errors.is_empty() => {
+            Ok(Meme { title: fields.title, description: fields.description, tags: fields.tags, ..meme })
+        }
+        _ => Err(errors),
+    }
+}
+
+/// One line listing every failing field with its messages.
+fn describe(errors: &ValidationErrors) -> String {
+    errors
+        .iter()
+        .map(|(field, messages)| format!("{}: {}", field, messages.join(", ")))
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+/// Walks the archive, uploading new images and returning the parsed manifest if present.
+async fn read_entries<R>(
+    storage: &dyn FileStorage,
+    limits: &ValidationLimits,
+    reader: R,
+    images: &mut ArchiveImages,
+) -> Result<Option<ExportManifest>, AppError>
+where
+    R: AsyncBufRead + Unpin,
+{
+    let invalid = |e: async_zip::error::ZipError| AppError::InvalidInput(format!("Invalid archive: {}", e));
+    let images_prefix = format!("{}/", IMAGES_DIR);
+    let mut manifest = None;
+    let mut zip = ZipFileReader::with_tokio(reader);
+
+    while let Some(mut entry) = zip.next_with_entry().await.map_err(invalid)? {
+        let name = entry.reader().entry().filename().as_str().map_err(invalid)?.to_string();
+
+        let wanted = if name == MANIFEST_ENTRY {
+            true
+        } else if let Some(key) = name.strip_prefix(&images_prefix).filter(|key| !key.is_empty()) {
+            // Never overwrite a stored image: it may belong to a meme that is not listed,
+            // such as an expired one awaiting cleanup
+            let known = images.uploaded.contains(key) || images.present.contains(key) || images.rejected.contains_key(key);
+            !known && !is_stored(storage, key, &mut images.present).await?
+        } else {
+            false
+        };
+        if !wanted {
+            tracing::debug!(entry = %name, "Skipping archive entry");
+            zip = entry.skip().await.map_err(invalid)?;
+            continue;
+        }
+
+        let mut data = Vec::new();
+        (&mut entry.reader_mut())
+            .take(MAX_ENTRY_BYTES + 1)
+            .read_to_end(&mut data)
+            .await
+            .map_err(|e| AppError::InvalidInput(format!("Invalid archive entry '{}': {}", name, e)))?;
+        if data.len() as u64 > MAX_ENTRY_BYTES {
+            return Err(AppError::InvalidInput(format!("Archive entry '{}' is too large", name)));
+        }
+        zip = entry.done().await.map_err(invalid)?;
+
+        if name == MANIFEST_ENTRY {
+            let parsed: ExportManifest = serde_json::from_slice(&data)
+                .map_err(|e| AppError::InvalidInput(format!("Invalid {}: {}", MANIFEST_ENTRY, e)))?;
+            manifest = Some(parsed);
+        } else if let Some(key) = name.strip_prefix(&images_prefix) {
+            let mut errors = ValidationErrors::new();
+            if !validation::validate_image(&mut errors, &data, limits) {
+                images.rejected.insert(key.to_string(), errors.get("image").join(", "));
+                continue;
+            }
+            let options = UploadOptions {
+                content_type: mime_guess::from_path(key).first_raw().map(|s| s.to_string()),
+                ..UploadOptions::default()
+            };
+            storage.upload(key, data.into(), options).await?;
+            images.uploaded.insert(key.to_string());
+        }
+    }
+
+    Ok(manifest)
+}
+
+/// Whether `key` is already in storage, remembering it in `present` if so.
+async fn is_stored(storage: &dyn FileStorage, key: &str, present: &mut HashSet<String>) -> Result<bool, AppError> {
+    match storage.head(key).await {
+        Ok(_) => {
+            present.insert(key.to_string());
+            Ok(true)
+        }
+        Err(StorageError::NotFound(_)) => Ok(false),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Best-effort removal of images uploaded during a failed or partial import.
+async fn remove_images<'a>(storage: &dyn FileStorage, keys: impl Iterator<Item = &'a String>) {
+    for key in keys {
+        if let Err(e) = storage.delete(key).await {
+            tracing::warn!(image_key = %key, error = ?e, "Failed to remove orphaned import image");
+        }
+    }
+}
+
@@ -0,0 +1,243 @@
+use crate::{
+    domain::{BlocklistRepository, ConsistencyLevel, FileStorage, MemeRepository, ObjectMetadata, StoredObject, TableInfo, UploadOptions},
+    errors::{RepoError, StorageError},
+    models::{Meme, SortOrder},
+};
+use async_trait::async_trait;
+use aws_sdk_s3::primitives::ByteStream;
+use axum::body::Bytes;
+use chrono::{DateTime, Utc};
+use std::{
+    future::Future,
+    time::{Duration, Instant},
+};
+use tracing::Instrument;
+use uuid::Uuid;
+
+/// The backend a decorator measures, and the duration from which its calls are logged as slow.
+#[derive(Clone, Copy, Debug)]
+struct Probe {
+    backend: &'static str,
+    slow_call_threshold: Option<Duration>,
+}
+
+/// Times one backend call inside a `backend_call` span and records:
+/// - `backend_call_duration_seconds{backend, operation, outcome}` for every call,
+/// - `backend_call_errors_total{backend, operation, error}` for failed calls,
+/// - `backend_call_items{backend, operation}` for calls that `items` can count.
+///
+/// Calls reaching the probe's slow-call threshold are also logged as a warning.
+async fn observe<T, E, Fut>(
+    probe: Probe,
+    operation: &'static str,
+    call: Fut,
+    items: impl FnOnce(&T) -> Option<usize>,
+    error_kind: fn(&E) -> &'static str,
+) -> Result<T, E>
+where
+    Fut: Future<Output = Result<T, E>>,
+{
+    let backend = probe.backend;
+    let span = tracing::debug_span!("backend_call", backend, operation);
+    let started = Instant::now();
+    let result = call.instrument(span).await;
+    let elapsed = started.elapsed();
+
+    let outcome = match &result {
+        Ok(value) => {
+            if let Some(count) = items(value) {
+                metrics::histogram!("backend_call_items", "backend" => backend, "operation" => operation).record(count as f64);
+            }
+            "ok"
+        }
+        Err(e) => {
+            metrics::counter!("backend_call_errors_total", "backend" => backend, "operation" => operation, "error" => error_kind(e))
+                .increment(1);
+            "error"
+        }
+    };
+    metrics::histogram!("backend_call_duration_seconds", "backend" => backend, "operation" => operation, "outcome" => outcome)
+        .record(elapsed.as_secs_f64());
+    tracing::debug!(backend, operation, outcome, elapsed_ms = elapsed.as_millis() as u64, "Backend call finished");
+    if let Some(threshold) = probe.slow_call_threshold.filter(|threshold| elapsed >= *threshold) {
+        tracing::warn!(
+            backend,
+            operation,
+            outcome,
+            elapsed_ms = elapsed.as_millis() as u64,
+            threshold_ms = threshold.as_millis() as u64,
+            "Slow backend call"
+        );
+    }
+    result
+}
+
+fn repo_error_kind(e: &RepoError) -> &'static str {
+    match e {
+        RepoError::NotFound(_) => "not_found",
+        RepoError::VersionConflict { .. } => "version_conflict",
+        RepoError::AlreadyExists(_) => "already_exists",
+        RepoError::BackendError(_) => "backend",
+        RepoError::DataCorruption { .. } => "data_corruption",
+        RepoError::Unavailable(_) => "unavailable",
+    }
+}
+
+fn storage_error_kind(e: &StorageError) -> &'static str {
+    match e {
+        StorageError::UploadFailed(_) => "upload_failed",
+        StorageError::NotFound(_) => "not_found",
+        StorageError::BackendError(_) => "backend",
+        StorageError::Unavailable(_) => "unavailable",
+    }
+}
+
+/// Records latency, errors and item counts for every call of the wrapped repository, and
+/// logs calls taking `slow_call_threshold` or longer.
+///
+/// Layered innermost, below retries and the circuit breaker, so each attempt against the
+/// backend is measured on its own.
+pub struct InstrumentedRepository<R> {
+    inner: R,
+    probe: Probe,
+}
+
+impl<R> InstrumentedRepository<R> {
+    pub fn new(inner: R, backend: &'static str, slow_call_threshold: Option<Duration>) -> Self {
+        Self { inner, probe: Probe { backend, slow_call_threshold } }
+    }
+}
+
+#[async_trait]
+impl<R: MemeRepository> MemeRepository for InstrumentedRepository<R> {
+    async fn create(&self, meme: &Meme) -> Result<(), RepoError> {
+        observe(self.probe, "create", self.inner.create(meme), |_| None, repo_error_kind).await
+    }
+
+    async fn get_by_id(&self, id: Uuid, consistency: ConsistencyLevel) -> Result<Option<Meme>, RepoError> {
+        observe(self.probe, "get_by_id", self.inner.get_by_id(id, consistency), |_| None, repo_error_kind).await
+    }
+
+    async fn exists(&self, id: Uuid) -> Result<bool, RepoError> {
+        observe(self.probe, "exists", self.inner.exists(id), |_| None, repo_error_kind).await
+    }
+
+    async fn list_all(&self) -> Result<Vec<Meme>, RepoError> {
+        observe(self.probe, "list_all", self.inner.list_all(), |memes| Some(memes.len()), repo_error_kind).await
+    }
+
+    async fn list_sorted(&self, order: SortOrder) -> Result<Vec<Meme>, RepoError> {
+        observe(self.probe, "list_sorted", self.inner.list_sorted(order), |memes| Some(memes.len()), repo_error_kind).await
+    }
+
+    async fn list_expired(&self, now: DateTime<Utc>) -> Result<Vec<Meme>, RepoError> {
+        observe(self.probe, "list_expired", self.inner.list_expired(now), |memes| Some(memes.len()), repo_error_kind).await
+    }
+
+    async fn list_due_for_publishing(&self, now: DateTime<Utc>) -> Result<Vec<Meme>, RepoError> {
+        let fut = self.inner.list_due_for_publishing(now);
+        observe(self.probe, "list_due_for_publishing", fut, |memes| Some(memes.len()), repo_error_kind).await
+    }
+
+    async fn update(&self, meme: &Meme, expected_version: u64) -> Result<(), RepoError> {
+        observe(self.probe, "update", self.inner.update(meme, expected_version), |_| None, repo_error_kind).await
+    }
+
+    async fn add_like(&self, id: Uuid) -> Result<u64, RepoError> {
+        observe(self.probe, "add_like", self.inner.add_like(id), |_| None, repo_error_kind).await
+    }
+
+    async fn add_views(&self, id: Uuid, views: u64) -> Result<(), RepoError> {
+        observe(self.probe, "add_views", self.inner.add_views(id, views), |_| None, repo_error_kind).await
+    }
+
+    async fn delete(&self, id: Uuid) -> Result<(), RepoError> {
+        observe(self.probe, "delete", self.inner.delete(id), |_| None, repo_error_kind).await
+    }
+
+    async fn describe(&self) -> Result<TableInfo, RepoError> {
+        observe(self.probe, "describe", self.inner.describe(), |_| None, repo_error_kind).await
+    }
+
+    async fn count_created_since(&self, since: DateTime<Utc>) -> Result<u64, RepoError> {
+        observe(self.probe, "count_created_since", self.inner.count_created_since(since), |_| None, repo_error_kind).await
+    }
+
+    async fn count(&self) -> Result<u64, RepoError> {
+        observe(self.probe, "count", self.inner.count(), |_| None, repo_error_kind).await
+    }
+
+    async fn ping(&self) -> Result<(), RepoError> {
+        self.inner.ping().await
+    }
+}
+
+#[async_trait]
+impl<R: BlocklistRepository> BlocklistRepository for InstrumentedRepository<R> {
+    async fn list_terms(&self) -> Result<Vec<String>, RepoError> {
+        observe(self.probe, "list_terms", self.inner.list_terms(), |terms| Some(terms.len()), repo_error_kind).await
+    }
+
+    async fn add_term(&self, term: &str) -> Result<(), RepoError> {
+        observe(self.probe, "add_term", self.inner.add_term(term), |_| None, repo_error_kind).await
+    }
+
+    async fn remove_term(&self, term: &str) -> Result<(), RepoError> {
+        observe(self.probe, "remove_term", self.inner.remove_term(term), |_| None, repo_error_kind).await
+    }
+}
+
+/// Records latency and errors for every call of the wrapped file storage, plus upload sizes
+/// in `storage_upload_bytes{backend}`. Layered like [`InstrumentedRepository`].
+pub struct InstrumentedStorage<S> {
+    inner: S,
+    probe: Probe,
+}
+
+impl<S> InstrumentedStorage<S> {
+    pub fn new(inner: S, backend: &'static str, slow_call_threshold: Option<Duration>) -> Self {
+        Self { inner, probe: Probe { backend, slow_call_threshold } }
+    }
+}
+
+#[async_trait]
+impl<S: FileStorage> FileStorage for InstrumentedStorage<S> {
+    async fn upload(&self, key: &str, data: Bytes, options: UploadOptions) -> Result<(), StorageError> {
+        metrics::histogram!("storage_upload_bytes", "backend" => self.probe.backend).record(data.len() as f64);
+        observe(self.probe, "upload", self.inner.upload(key, data, options), |_| None, storage_error_kind).await
+    }
+
+    async fn download(&self, key: &str) -> Result<(ByteStream, ObjectMetadata), StorageError> {
+        // Measures the time to the start of the body; streaming it is up to the caller
+        observe(self.probe, "download", self.inner.download(key), |_| None, storage_error_kind).await
+    }
+
+    async fn head(&self, key: &str) -> Result<ObjectMetadata, StorageError> {
+        observe(self.probe, "head", self.inner.head(key), |_| None, storage_error_kind).await
+    }
+
+    async fn list(&self) -> Result<Vec<StoredObject>, StorageError> {
+        observe(self.probe, "list", self.inner.list(), |objects| Some(objects.len()), storage_error_kind).await
+    }
+
+    /// Not measured: signing makes no backend call.
+    async fn presigned_url(&self, key: &str, expires_in: Duration) -> Result<String, StorageError> {
+        self.inner.presigned_url(key, expires_in).await
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), StorageError> {
+        observe(self.probe, "delete", self.inner.delete(key), |_| None, storage_error_kind).await
+    }
+
+    async fn copy(&self, from: &str, to: &str) -> Result<(), StorageError> {
+        observe(self.probe, "copy", self.inner.copy(from, to), |_| None, storage_error_kind).await
+    }
+
+    async fn rename(&self, from: &str, to: &str) -> Result<(), StorageError> {
+        observe(self.probe, "rename", self.inner.rename(from, to), |_| None, storage_error_kind).await
+    }
+
+    async fn ping(&self) -> Result<(), StorageError> {
+        self.inner.ping().await
+    }
+}
@@ -0,0 +1,112 @@
+use chrono::Utc;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::{str::FromStr, sync::Arc};
+use uuid::Uuid;
+
+/// Decides where a new meme's image is stored. Every layout ends keys in
+/// `<meme_id>.<extension>`: image access checks find the meme from that file name, and
+/// each meme keeps its own object even when two uploads are identical.
+pub trait KeyStrategy: Send + Sync + 'static {
+    fn image_key(&self, meme_id: Uuid, extension: &str, data: &[u8]) -> String;
+}
+
+/// `<meme_id>.<ext>` at the top of the bucket (or tenant prefix), as keys have always been.
+pub struct FlatKeys;
+
+impl KeyStrategy for FlatKeys {
+    fn image_key(&self, meme_id: Uuid, extension: &str, _data: &[u8]) -> String {
+        format!("{}.{}", meme_id, extension)
+    }
+}
+
+/// `<year>/<month>/<meme_id>.<ext>` by upload time (UTC), so listings and lifecycle rules
+/// can work month by month.
+pub struct DatePrefixedKeys;
+
+impl KeyStrategy for DatePrefixedKeys {
+    fn image_key(&self, meme_id: Uuid, extension: &str, _data: &[u8]) -> String {
+        format!("{}/{}.{}", Utc::now().format("%Y/%m"), meme_id, extension)
+    }
+}
+
+/// `<aa>/<bb>/<meme_id>.<ext>`, where `aabb` starts the SHA-256 of the image. Spreads keys
+/// evenly over 65536 prefixes whatever the upload pattern.
+pub struct ContentHashKeys;
+
+impl KeyStrategy for ContentHashKeys {
+    fn image_key(&self, meme_id: Uuid, extension: &str, data: &[u8]) -> String {
+        let digest = hex::encode(Sha256::digest(data));
+        format!("{}/{}/{}.{}", &digest[..2], &digest[2..4], meme_id, extension)
+    }
+}
+
+/// Key layout for new images (`APP_IMAGE_KEY_LAYOUT`). Existing images keep their keys,
+/// so the layout can be changed at any time.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum KeyLayout {
+    #[default]
+    Flat,
+    Date,
+    ContentHash,
+}
+
+impl KeyLayout {
+    pub fn strategy(self) -> Arc<dyn KeyStrategy> {
+        match self {
+            KeyLayout::Flat => Arc::new(FlatKeys),
+            KeyLayout::Date => Arc::new(DatePrefixedKeys),
+            KeyLayout::ContentHash => Arc::new(ContentHashKeys),
+        }
+    }
+}
+
+impl FromStr for KeyLayout {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "flat" => Ok(KeyLayout::Flat),
+            "date" => Ok(KeyLayout::Date),
+            "content-hash" => Ok(KeyLayout::ContentHash),
+            other => Err(format!("unknown image key layout '{}' (expected flat, date or content-hash)", other)),
+        }
+    }
+}
+
+/// The meme an image key belongs to, from its `<meme_id>.<ext>` file name. `None` for keys
+/// that do not name a meme, such as backups.
+pub fn meme_id_of(key: &str) -> Option<Uuid> {
+    let file_name = key.rsplit('/').next()?;
+    let stem = file_name.split('.').next()?;
+    Uuid::parse_str(stem).ok()
+}
+
+/// Prefix under which new images wait while moderation or malware scanning is on. Images
+/// under it are never served; approval copies them to their own key.
+pub const QUARANTINE_PREFIX: &str = "quarantine/";
+
+/// Where an image with key `key` waits for approval.
+pub fn quarantine_key(key: &str) -> String {
+    format!("{}{}", QUARANTINE_PREFIX, key)
+}
+
+/// The key a quarantined image is released to; `None` for keys outside the quarantine.
+pub fn released_key(key: &str) -> Option<&str> {
+    key.strip_prefix(QUARANTINE_PREFIX)
+}
+
+/// Whether `key` is in the quarantine.
+pub fn is_quarantined(key: &str) -> bool {
+    key.starts_with(QUARANTINE_PREFIX)
+}
+
+/// The meme whose image `key` is, when it may be served at GET /images: neither quarantined
+/// nor a staged tus upload. `None` for backups and anything else kept in the bucket.
+pub fn servable_meme_id(key: &str) -> Option<Uuid> {
+    if is_quarantined(key) || key.starts_with(crate::tus::STAGING_PREFIX) {
+        return None;
+    }
+    meme_id_of(key)
+}
@@ -0,0 +1,496 @@
+//! Meme posting API backed by DynamoDB and S3.
+//!
+//! The binary (`main.rs`) adds the command line and the server lifecycle on top of this
+//! library; integration tests build the same [`AppState`] through [`build_app_state`].
+
+use crate::{
+    access_log::AccessLog,
+    backends::{RepositoryBackend, Resilience, StorageBackend},
+    cdn::CdnSigner,
+    circuit_breaker::CircuitBreaker,
+    config::Config,
+    shutdown::InFlightRequests,
+    stats::MemeStats,
+    content_filter::ContentFilter,
+    domain::{
+        AuditRepository, BackfillRepository, BlocklistRepository, FileStorage, MemeHistoryRepository, MemeRepository,
+        ShortlinkRepository, TrendingRepository, TrendingSnapshot, VectorIndex,
+    },
+    embeddings::Embedder,
+    errors::AppError,
+    failover::RegionFailover,
+    fetcher::UrlFetcher,
+    ocr::TextExtractor,
+    progress::ProgressRegistry,
+    scanning::Scanner,
+    sitemap::Sitemap,
+    keys::KeyStrategy,
+    models::Meme,
+    startup::{init_resources, verify_resources, verify_upload_kms_key, BucketSettings, ResourceInitMode},
+    tenant::TenantSettings,
+    trending::ViewCounter,
+    xray::XrayEmitter,
+};
+use aws_config::SdkConfig;
+use aws_sdk_dynamodb::Client as DynamoDbClient;
+use metrics_exporter_prometheus::PrometheusHandle;
+use aws_sdk_s3::Client as S3Client;
+use tracing::info;
+use std::collections::BTreeMap;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use tokio::sync::{broadcast, Mutex};
+
+/// Memes a slow subscriber of [`AppState::new_memes`] can fall behind by before it misses some.
+const NEW_MEMES_CAPACITY: usize = 64;
+
+// --- Modules ---
+pub mod access_log;
+pub mod activitypub;
+pub mod admin;
+pub mod announcements;
+pub mod audit;
+pub mod auth;
+pub mod aws_clients;
+#[cfg(feature = "azure")]
+pub mod azure_blob;
+pub mod backends;
+pub mod backfill;
+pub mod backup;
+pub mod body_limit;
+pub mod body_logging;
+pub mod cdn;
+#[cfg(feature = "chaos")]
+pub mod chaos;
+#[cfg(not(feature = "lambda"))]
+pub mod change_stream;
+pub mod circuit_breaker;
+#[cfg(feature = "client")]
+pub mod client;
+pub mod client_ip;
+pub mod config;
+pub mod content_filter;
+#[cfg(feature = "discord")]
+pub mod discord;
+pub mod domain;
+pub mod embeddings;
+pub mod embeds;
+pub mod emf;
+pub mod error_reporting;
+pub mod errors;
+pub mod expiry;
+pub mod export;
+pub mod failover;
+pub mod fetcher;
+#[cfg(feature = "testing")]
+pub mod generators;
+pub mod fields;
+pub mod filesystem_storage;
+pub mod formats;
+#[cfg(feature = "gcs")]
+pub mod gcs_storage;
+pub mod handlers;
+pub mod hedging;
+pub mod imaging;
+pub mod import;
+pub mod instrumentation;
+pub mod keys;
+pub mod log_level;
+pub mod migrations;
+pub mod models;
+pub mod notifications;
+pub mod ocr;
+pub mod panics;
+#[cfg(feature = "mongodb")]
+pub mod mongo_repository;
+pub mod progress;
+pub mod publishing;
+#[cfg(feature = "testing")]
+pub mod recording;
+pub mod remote_config;
+pub mod repositories;
+pub mod retry;
+pub mod routes;
+pub mod runtime_metrics;
+pub mod scanning;
+pub mod sdk_metrics;
+pub mod search;
+pub mod seed;
+pub mod services;
+pub mod share;
+pub mod shortlinks;
+pub mod shutdown;
+pub mod sitemap;
+pub mod slack;
+pub mod slow_requests;
+#[cfg(feature = "sqlite")]
+pub mod sqlite_repository;
+pub mod startup;
+pub mod stats;
+pub mod storage;
+#[cfg(feature = "telegram")]
+pub mod telegram;
+pub mod telemetry;
+pub mod tenant;
+#[cfg(feature = "testing")]
+pub mod testing;
+pub mod timeout;
+#[cfg(not(feature = "lambda"))]
+pub mod tls;
+pub mod trending;
+pub mod tus;
+pub mod validation;
+#[cfg(not(feature = "lambda"))]
+pub mod webhooks;
+pub mod xray;
+
+//-----------------------------------------------------------------------------
+// Application State - Define ALL shared state components here
+//-----------------------------------------------------------------------------
+#[derive(Clone)] // Needed for Axum state extension
+pub struct AppState {
+    // Concrete clients might be needed for direct use or specific configs
+    pub db_client: DynamoDbClient,
+    pub s3_client: S3Client,
+    // Trait objects for dependency injection via interfaces
+    pub meme_repo: Arc<dyn MemeRepository>,
+    pub file_storage: Arc<dyn FileStorage>,
+    pub blocklist_repo: Arc<dyn BlocklistRepository>,
+    // Earlier versions of memes' metadata, kept on every update
+    pub meme_history: Arc<dyn MemeHistoryRepository>,
+    // Append-only record of meme changes, per tenant
+    pub audit_log: Arc<dyn AuditRepository>,
+    // Progress of attribute backfills (`/admin/backfills`), shared by all instances
+    pub backfill_repo: Arc<dyn BackfillRepository>,
+    // Short codes redirecting to memes, with their hit counts
+    pub shortlink_repo: Arc<dyn ShortlinkRepository>,
+    // Active content filter; swapped out when admins edit the blocklist
+    pub content_filter: Arc<RwLock<Arc<ContentFilter>>>,
+    // Signs CloudFront URLs and cookies; `None` without a CDN key pair
+    pub cdn_signer: Option<Arc<CdnSigner>>,
+    // Chooses the storage key of each new image (`APP_IMAGE_KEY_LAYOUT`)
+    pub key_strategy: Arc<dyn KeyStrategy>,
+    // HTTP client for JSON uploads that reference a remote image
+    pub url_fetcher: Arc<UrlFetcher>,
+    // Reads the text on uploaded images; `None` when OCR is off
+    pub text_extractor: Option<Arc<dyn TextExtractor>>,
+    // Checks uploaded images for malware before they leave quarantine; `None` when scanning is off
+    pub scanner: Option<Arc<dyn Scanner>>,
+    // Embeds memes and queries for semantic search; `None` when it is off
+    pub embedder: Option<Arc<dyn Embedder>>,
+    // Vectors of this state's memes, searched by `GET /memes/search?mode=semantic`
+    pub vector_index: Arc<dyn VectorIndex>,
+    // Which region serves the meme table; `None` without a secondary region
+    pub region_failover: Option<Arc<RegionFailover>>,
+    // Shared application configuration
+    pub config: Arc<Config>,
+    // Breakers guarding the backends, reported on /health
+    pub circuit_breakers: Vec<Arc<CircuitBreaker>>,
+    // Renders the Prometheus /metrics endpoint; `None` with another metrics sink
+    pub metrics: Option<PrometheusHandle>,
+    // Requests still being served, drained on shutdown
+    pub in_flight: Arc<InFlightRequests>,
+    // Latest /stats aggregation; refreshed by a background job
+    pub stats: Arc<RwLock<Option<Arc<MemeStats>>>>,
+    // Where the trending job stores its rankings, shared by all instances
+    pub trending_repo: Arc<dyn TrendingRepository>,
+    // Latest trending rankings, computed here or loaded from `trending_repo`
+    pub trending: Arc<RwLock<Option<Arc<TrendingSnapshot>>>>,
+    // Latest /sitemap.xml, generated on request and kept for `APP_SITEMAP_TTL_SECS`; held
+    // while it is rebuilt, so only one request lists the memes
+    pub sitemap: Arc<Mutex<Option<Arc<Sitemap>>>>,
+    // Views of memes not yet added to their `view_count`
+    pub views: Arc<ViewCounter>,
+    // Progress of uploads sent with `X-Upload-Id`, streamed at /uploads/{id}/progress
+    pub upload_progress: Arc<ProgressRegistry>,
+    // Memes as they become publicly listed on this instance, for in-process subscribers
+    // (see `announcements`)
+    pub new_memes: broadcast::Sender<Meme>,
+    // Emails about approvals, rejections and expiring memes; `None` without recipients
+    pub notifier: Option<Arc<notifications::Notifier>>,
+    // Slash commands, link unfurls and notifications; `None` when Slack is not configured
+    pub slack: Option<Arc<slack::SlackApp>>,
+    // The instance's ActivityPub actor, delivering new memes to its followers; `None`
+    // without a key and on tenants' states
+    pub activitypub: Option<Arc<activitypub::InstanceActor>>,
+    // Posts new memes and answers slash commands; `None` when Discord is not configured
+    #[cfg(feature = "discord")]
+    pub discord: Option<Arc<discord::DiscordBot>>,
+    // Uploads photos and answers commands sent to the bot; `None` without a bot token
+    #[cfg(feature = "telegram")]
+    pub telegram: Option<Arc<telegram::TelegramBot>>,
+    // Sends request segments to the X-Ray daemon; `None` when X-Ray is off
+    pub xray: Option<Arc<XrayEmitter>>,
+    // Writes a line per request; `None` when the access log is off
+    pub access_log: Option<Arc<AccessLog>>,
+    // State of each configured tenant, by ID; empty for single-tenant deployments and on
+    // the tenants' own states
+    pub tenants: BTreeMap<String, Arc<AppState>>,
+    // Overrides of the tenant this state serves; `None` outside tenants
+    pub tenant_settings: Option<Arc<TenantSettings>>,
+}
+
+//-----------------------------------------------------------------------------
+// Application Setup
+//-----------------------------------------------------------------------------
+
+// --- Create AWS SDK Config and Clients ---
+pub async fn create_clients(config: &Config) -> Result<(DynamoDbClient, S3Client), AppError> {
+    info!("Initializing AWS SDK config and clients...");
+    let sdk_config = aws_clients::create_sdk_config(config).await?; // Create base SDK config from App Config
+
+    let db_client = aws_clients::create_dynamodb_client(&sdk_config); // Create DynamoDB client
+    let s3_client = aws_clients::create_s3_client(&sdk_config, config); // Create S3 client
+    info!("AWS clients initialized.");
+    Ok((db_client, s3_client))
+}
+
+// --- Prepare AWS Resources (DynamoDB Tables, S3 Bucket) ---
+/// Creates or verifies the tables and bucket, including every tenant's meme table.
+pub async fn initialize_resources(
+    db_client: &DynamoDbClient,
+    s3_client: &S3Client,
+    config: &Config,
+    mode: ResourceInitMode,
+) -> Result<(), AppError> {
+    initialize_scoped_resources(db_client, s3_client, config, mode).await?;
+    for tenant in &config.tenants {
+        initialize_scoped_resources(db_client, s3_client, &config.for_tenant(tenant), mode).await?;
+    }
+    Ok(())
+}
+
+async fn initialize_scoped_resources(
+    db_client: &DynamoDbClient,
+    s3_client: &S3Client,
+    config: &Config,
+    mode: ResourceInitMode,
+) -> Result<(), AppError> {
+    // The tables and bucket are only created or checked when DynamoDB and S3 hold them
+    let table_name = (config.repository_backend == RepositoryBackend::DynamoDb).then_some(config.dynamodb_table_name.as_str());
+    let meta_table_name = (config.repository_backend != RepositoryBackend::Sqlite).then_some(config.meta_table_name.as_str());
+    let bucket_name = (config.storage_backend == StorageBackend::S3).then_some(config.meme_bucket_name.as_str());
+    let uses_aws = table_name.is_some() || meta_table_name.is_some() || bucket_name.is_some();
+    // Bucket configuration calls are not served by accelerated endpoints
+    let bucket_admin_client = aws_clients::without_acceleration(s3_client);
+    match mode {
+        ResourceInitMode::Create if uses_aws => {
+            init_resources(
+                db_client,
+                &bucket_admin_client,
+                table_name,
+                meta_table_name,
+                bucket_name,
+                config.s3_region(),
+                &BucketSettings {
+                    block_public_access: config.s3_block_public_access,
+                    encryption: config.s3_encryption,
+                    kms_key_id: config.s3_kms_key_id.clone(),
+                    versioning: config.s3_versioning,
+                    abort_incomplete_upload_days: config.s3_abort_incomplete_upload_days,
+                    transfer_acceleration: config.s3_accelerate,
+                },
+                config.stream_consumer_enabled,
+                &config.dynamodb_capacity,
+                &config.dynamodb_meta_capacity,
+            )
+            .await?; // Propagate errors
+            info!("AWS resources initialized successfully.");
+            if table_name.is_some() {
+                let applied = migrations::run_pending(db_client, config).await?;
+                if !applied.is_empty() {
+                    info!(?applied, "Migrations applied.");
+                }
+            }
+        }
+        ResourceInitMode::Verify if uses_aws => {
+            verify_resources(
+                db_client,
+                &bucket_admin_client,
+                table_name,
+                meta_table_name,
+                bucket_name,
+                config.s3_region(),
+                config.s3_accelerate,
+                config.stream_consumer_enabled,
+                &config.dynamodb_capacity,
+                &config.dynamodb_meta_capacity,
+            )
+            .await?;
+            if table_name.is_some() {
+                migrations::warn_pending(db_client, config).await?;
+            }
+        }
+        ResourceInitMode::Skip => {
+            info!("Skipping AWS resource initialization; table and bucket are expected to exist.");
+            return Ok(());
+        }
+        // Nothing is stored in AWS
+        ResourceInitMode::Create | ResourceInitMode::Verify => {}
+    }
+    #[cfg(feature = "azure")]
+    if config.storage_backend == StorageBackend::Azure {
+        azure_blob::ensure_container(config, mode == ResourceInitMode::Create).await?;
+    }
+    #[cfg(feature = "gcs")]
+    if config.storage_backend == StorageBackend::Gcs {
+        gcs_storage::ensure_bucket(config, mode == ResourceInitMode::Create).await?;
+    }
+    #[cfg(feature = "mongodb")]
+    if config.repository_backend == RepositoryBackend::MongoDb {
+        mongo_repository::ensure_indexes(config, mode == ResourceInitMode::Create).await?;
+    }
+    #[cfg(feature = "sqlite")]
+    if config.repository_backend == RepositoryBackend::Sqlite {
+        sqlite_repository::ensure_tables(config, mode == ResourceInitMode::Create).await?;
+    }
+    if config.storage_backend == StorageBackend::Filesystem {
+        filesystem_storage::ensure_root(config, mode == ResourceInitMode::Create).await?;
+    }
+    if let Some(kms_key_id) = &config.s3_upload_kms_key_id {
+        verify_upload_kms_key(s3_client, &config.meme_bucket_name, &config.s3_key_prefix(), kms_key_id).await?;
+    }
+    Ok(())
+}
+
+/// Connects to AWS, prepares resources according to `APP_RESOURCE_INIT`, and wires up the shared
+/// application state. Used by every subcommand that talks to the backends through the repositories.
+pub async fn build_app_state(config: Config) -> Result<Arc<AppState>, AppError> {
+    let sdk_config = aws_clients::create_sdk_config(&config).await?;
+    build_app_state_with_sdk_config(config, &sdk_config).await
+}
+
+/// Like [`build_app_state`], but with an SDK configuration built by the caller, e.g. with
+/// explicit credentials for a throwaway LocalStack in tests. Every AWS client is made from it.
+pub async fn build_app_state_with_sdk_config(config: Config, sdk_config: &SdkConfig) -> Result<Arc<AppState>, AppError> {
+    let db_client = aws_clients::create_dynamodb_client(sdk_config);
+    let s3_client = aws_clients::create_s3_client(sdk_config, &config);
+    // Ensure backend resources are ready before using them
+    initialize_resources(&db_client, &s3_client, &config, config.resource_init).await?;
+
+    let tenant_configs: Vec<Config> = config.tenants.iter().map(|tenant| config.for_tenant(tenant)).collect();
+    let tenant_config_repo = backends::build_tenant_config_repository(&config, &db_client)?;
+    let tenant_config_ttl = Duration::from_secs(config.tenant_config_ttl_secs);
+    let mut app_state = build_scoped_state(config, sdk_config, db_client.clone(), s3_client.clone()).await?;
+    for tenant_config in tenant_configs {
+        let tenant = tenant_config.tenant.clone().expect("tenant config names its tenant");
+        let tenant_state = build_scoped_state(tenant_config, sdk_config, db_client.clone(), s3_client.clone()).await?;
+        // One blocklist and one drain on shutdown for the whole deployment
+        let tenant_state = AppState {
+            content_filter: app_state.content_filter.clone(),
+            in_flight: app_state.in_flight.clone(),
+            xray: app_state.xray.clone(),
+            access_log: app_state.access_log.clone(),
+            tenant_settings: Some(Arc::new(TenantSettings::new(tenant.clone(), tenant_config_repo.clone(), tenant_config_ttl))),
+            ..tenant_state
+        };
+        info!(%tenant, "Tenant state created.");
+        app_state.tenants.insert(tenant, Arc::new(tenant_state));
+    }
+    Ok(Arc::new(app_state))
+}
+
+/// Builds the state for one configuration: the deployment's own, or a tenant's from
+/// [`Config::for_tenant`].
+async fn build_scoped_state(
+    config: Config,
+    sdk_config: &SdkConfig,
+    db_client: DynamoDbClient,
+    s3_client: S3Client,
+) -> Result<AppState, AppError> {
+    let metrics = telemetry::install_metrics_recorder(&config)?;
+    error_reporting::init(&config);
+    panics::install_hook();
+    let xray = XrayEmitter::from_config(&config).await?;
+    let access_log = AccessLog::from_config(&config)?;
+
+    // --- Create Repository and Storage Implementations ---
+    // The configured backends, each wrapped in its backend's breaker and retries
+    let resilience = Resilience::from_config(&config);
+    let (meme_repo, region_failover) = backends::meme_repository(&config, &db_client, &resilience).await?;
+    let file_storage = backends::file_storage(&config, &s3_client, &resilience).await?;
+    let blocklist_repo_impl = backends::build_blocklist_repository(&config, &db_client)?;
+    let meme_history = backends::build_history_repository(&config, &db_client)?;
+    let audit_log = backends::build_audit_repository(&config, &db_client)?;
+    let trending_repo = backends::build_trending_repository(&config, &db_client)?;
+    let backfill_repo = backends::build_backfill_repository(&config, &db_client)?;
+    let shortlink_repo = backends::build_shortlink_repository(&config, &db_client)?;
+    let vector_index = backends::build_vector_index(&config, &db_client)?;
+    info!("Repository and Storage implementations created.");
+
+    // --- Load Content Filter (configured terms + admin-managed terms) ---
+    // Through the undecorated repository, so fault injection cannot fail startup
+    let content_filter = content_filter::load(&config, &blocklist_repo_impl).await?;
+    let blocklist_repo = resilience.blocklist_repository(blocklist_repo_impl);
+
+    let cdn_signer = CdnSigner::from_config(&config)?.map(Arc::new);
+    let text_extractor = ocr::build_text_extractor(&config).await?;
+    let scanner = scanning::build_scanner(&config);
+    let embedder = embeddings::build_embedder(&config).await?;
+
+    let notifier = notifications::Notifier::from_config(&config, sdk_config).map(Arc::new);
+    let slack = slack::SlackApp::from_config(&config)?.map(Arc::new);
+    let activitypub = match config.tenant {
+        Some(_) => None,
+        None => activitypub::InstanceActor::from_config(&config, backends::build_follower_repository(&config, &db_client)?)?.map(Arc::new),
+    };
+    #[cfg(feature = "discord")]
+    let discord = discord::DiscordBot::from_config(&config)?.map(Arc::new);
+    #[cfg(feature = "telegram")]
+    let telegram = telegram::TelegramBot::from_config(&config)?.map(Arc::new);
+    let url_fetcher = UrlFetcher::new(
+        Duration::from_secs(config.fetch_timeout_secs),
+        config.fetch_max_bytes,
+    )
+    .map_err(|e| AppError::InitError(format!("Failed to build HTTP client: {}", e)))?;
+
+    // --- Create Application State ---
+    // Bundle all shared components into an Arc<AppState>
+    let app_state = AppState {
+        db_client, // Move clients into state
+        s3_client,
+        // Trait objects (Arc<dyn Trait>) wrapping the decorated concrete impls
+        meme_repo,
+        file_storage,
+        blocklist_repo,
+        meme_history,
+        audit_log,
+        backfill_repo,
+        shortlink_repo,
+        content_filter: Arc::new(RwLock::new(Arc::new(content_filter))),
+        cdn_signer,
+        key_strategy: config.image_key_layout.strategy(),
+        url_fetcher: Arc::new(url_fetcher),
+        text_extractor,
+        scanner,
+        embedder,
+        vector_index,
+        region_failover,
+        // Share config using Arc
+        config: Arc::new(config),
+        circuit_breakers: resilience.circuit_breakers(),
+        metrics,
+        in_flight: Arc::new(InFlightRequests::default()),
+        stats: Arc::new(RwLock::new(None)),
+        trending_repo,
+        trending: Arc::new(RwLock::new(None)),
+        sitemap: Arc::new(Mutex::new(None)),
+        views: Arc::new(ViewCounter::default()),
+        upload_progress: Arc::new(ProgressRegistry::default()),
+        new_memes: broadcast::channel(NEW_MEMES_CAPACITY).0,
+        notifier,
+        slack,
+        activitypub,
+        #[cfg(feature = "discord")]
+        discord,
+        #[cfg(feature = "telegram")]
+        telegram,
+        xray,
+        access_log,
+        tenants: BTreeMap::new(),
+        tenant_settings: None,
+    };
+    info!("Application state created.");
+    Ok(app_state)
+}
+
@@ -0,0 +1,146 @@
+//! Runtime control of the log filter (`GET`/`PUT /admin/log_level`). The filter the server
+//! starts with (`RUST_LOG`) is wrapped in a reload layer, so operators can turn on debug
+//! logging for a module such as `storage` or `repositories` without a restart. Changes apply
+//! to the instance that receives them and are lost when it restarts.
+
+use crate::errors::AppError;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    sync::{Mutex, OnceLock},
+    time::Duration,
+};
+use tracing_subscriber::{filter::LevelFilter, reload, EnvFilter, Registry};
+
+/// Prefix of this crate's targets: module `storage` logs as `axum_meme_posting_example::storage`.
+const CRATE_TARGET: &str = env!("CARGO_CRATE_NAME");
+
+/// Longest `reset_after_secs`: changes are meant to be temporary.
+const MAX_RESET_AFTER_SECS: u64 = 24 * 60 * 60;
+
+/// The reloadable filter; logging is process-global, so it is too.
+static FILTER: OnceLock<LogFilter> = OnceLock::new();
+
+struct LogFilter {
+    handle: reload::Handle<EnvFilter, Registry>,
+    startup: String,
+    reset: Mutex<PendingReset>,
+}
+
+/// The reset scheduled by the latest change; each change replaces the previous one's.
+#[derive(Default)]
+struct PendingReset {
+    generation: u64,
+    at: Option<DateTime<Utc>>,
+}
+
+/// Wraps the log filter so it can be changed at runtime. Only the first call's layer is
+/// controlled by the endpoint.
+pub fn reloadable(filter: EnvFilter) -> reload::Layer<EnvFilter, Registry> {
+    let startup = filter.to_string();
+    let (layer, handle) = reload::Layer::new(filter);
+    let _ = FILTER.set(LogFilter { handle, startup, reset: Mutex::new(PendingReset::default()) });
+    layer
+}
+
+/// Body of PUT /admin/log_level. At least one of `filter` and `modules` is needed.
+#[derive(Debug, Deserialize)]
+pub struct LogLevelChange {
+    /// Replaces the whole filter, in `RUST_LOG` syntax.
+    pub filter: Option<String>,
+    /// Levels for this crate's modules, e.g. `{"storage": "debug"}`, added to the filter.
+    #[serde(default)]
+    pub modules: BTreeMap<String, String>,
+    /// Goes back to the startup filter after this many seconds.
+    pub reset_after_secs: Option<u64>,
+}
+
+/// Body of the GET/PUT /admin/log_level responses.
+#[derive(Debug, Serialize)]
+pub struct LogLevelReport {
+    pub filter: String,
+    pub startup_filter: String,
+    /// When the startup filter comes back, if a change asked for it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub resets_at: Option<DateTime<Utc>>,
+}
+
+fn installed() -> Result<&'static LogFilter, AppError> {
+    FILTER
+        .get()
+        .ok_or_else(|| AppError::Conflict("The log filter cannot be changed: logging was not set up by the server".to_string()))
+}
+
+/// The filter in effect.
+pub fn current() -> Result<LogLevelReport, AppError> {
+    let log_filter = installed()?;
+    report(log_filter)
+}
+
+fn report(log_filter: &LogFilter) -> Result<LogLevelReport, AppError> {
+    let filter = log_filter
+        .handle
+        .with_current(|filter| filter.to_string())
+        .map_err(|e| AppError::InternalServerError(format!("Failed to read the log filter: {}", e)))?;
+    let resets_at = log_filter.reset.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).at;
+    Ok(LogLevelReport { filter, startup_filter: log_filter.startup.clone(), resets_at })
+}
+
+/// Applies a change, scheduling the reset it asks for. The filter is validated as a whole
+/// before anything changes.
+pub fn change(change: LogLevelChange) -> Result<LogLevelReport, AppError> {
+    let log_filter = installed()?;
+    if change.filter.is_none() && change.modules.is_empty() {
+        return Err(AppError::InvalidInput("Give a filter, modules or both".to_string()));
+    }
+    if change.reset_after_secs.is_some_and(|secs| secs > MAX_RESET_AFTER_SECS) {
+        return Err(AppError::InvalidInput(format!("reset_after_secs must be at most {}", MAX_RESET_AFTER_SECS)));
+    }
+    let mut directives = match change.filter {
+        Some(filter) => filter,
+        None => report(log_filter)?.filter,
+    };
+    for (module, level) in &change.modules {
+        let is_path = module.split("::").all(|part| !part.is_empty() && part.chars().all(|c| c.is_ascii_alphanumeric() || c == '_'));
+        if !is_path {
+            return Err(AppError::InvalidInput(format!("'{}' is not a module path", module)));
+        }
+        let level: LevelFilter = level
+            .parse()
+            .map_err(|_| AppError::InvalidInput(format!("'{}' is not a log level (off, error, warn, info, debug or trace)", level)))?;
+        directives.push_str(&format!(",{}::{}={}", CRATE_TARGET, module, level));
+    }
+    let filter = EnvFilter::builder()
+        .parse(directives.trim_start_matches(','))
+        .map_err(|e| AppError::InvalidInput(format!("Invalid log filter: {}", e)))?;
+    let filter_string = filter.to_string();
+    log_filter
+        .handle
+        .reload(filter)
+        .map_err(|e| AppError::InternalServerError(format!("Failed to change the log filter: {}", e)))?;
+
+    let mut reset = log_filter.reset.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    reset.generation += 1;
+    reset.at = change.reset_after_secs.map(|secs| Utc::now() + Duration::from_secs(secs));
+    if let Some(secs) = change.reset_after_secs {
+        tokio::spawn(reset_later(log_filter, reset.generation, Duration::from_secs(secs)));
+    }
+    drop(reset);
+    tracing::warn!(filter = %filter_string, reset_after_secs = ?change.reset_after_secs, "Log filter changed");
+    report(log_filter)
+}
+
+/// Restores the startup filter after `delay`, unless another change came in meanwhile.
+async fn reset_later(log_filter: &'static LogFilter, generation: u64, delay: Duration) {
+    tokio::time::sleep(delay).await;
+    let mut reset = log_filter.reset.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    if reset.generation != generation {
+        return;
+    }
+    reset.at = None;
+    match log_filter.handle.reload(EnvFilter::new(&log_filter.startup)) {
+        Ok(()) => tracing::warn!(filter = %log_filter.startup, "Log filter reset"),
+        Err(e) => tracing::error!(error = %e, "Failed to reset the log filter"),
+    }
+}
@@ -1,44 +1,146 @@
-use crate::{
+use axum_meme_posting_example::{
+    backup,
+    build_app_state,
     config::Config,
-    domain::{FileStorage, MemeRepository},
+    create_clients,
     errors::AppError,
-    repositories::DynamoDbMemeRepository,
+    initialize_resources,
+    log_level,
+    migrations,
     routes::create_router,
-    startup::init_resources,
-    storage::S3FileStorage,
+    seed,
+    startup::ResourceInitMode,
+    AppState,
 };
-use aws_sdk_dynamodb::Client as DynamoDbClient;
-use aws_sdk_s3::Client as S3Client;
+#[cfg(not(feature = "lambda"))]
+use axum_meme_posting_example::{
+    announcements,
+    aws_clients,
+    change_stream,
+    client_ip,
+    config,
+    error_reporting,
+    expiry,
+    failover,
+    publishing,
+    repositories::DynamoDbCheckpointRepository,
+    routes,
+    stats,
+    tls,
+    trending,
+    webhooks,
+};
+#[cfg(feature = "client")]
+use axum_meme_posting_example::client::{MemeClient, NewMeme};
+#[cfg(all(feature = "telegram", not(feature = "lambda")))]
+use axum_meme_posting_example::telegram;
+#[cfg(feature = "client")]
+use anyhow::Context;
+#[cfg(feature = "client")]
+use std::io::Write;
+#[cfg(feature = "client")]
+use clap::Args;
+use clap::{Parser, Subcommand};
+#[cfg(not(feature = "lambda"))]
 use tokio::signal;
+#[cfg(not(feature = "lambda"))]
+use tokio_util::sync::CancellationToken;
 use tracing::info;
+use std::path::PathBuf;
 use std::sync::Arc;
-use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
-
-// --- Modules ---
-mod aws_clients;
-mod config;
-mod domain;
-mod errors;
-mod handlers;
-mod models;
-mod repositories;
-mod routes;
-mod startup;
-mod storage;
+#[cfg(not(feature = "lambda"))]
+use std::time::Duration;
+use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilter, Layer};
 
 //-----------------------------------------------------------------------------
-// Application State - Define ALL shared state components here
+// Command Line Interface
 //-----------------------------------------------------------------------------
-#[derive(Clone)] // Needed for Axum state extension
-pub struct AppState {
-    // Concrete clients might be needed for direct use or specific configs
-    db_client: DynamoDbClient,
-    s3_client: S3Client,
-    // Trait objects for dependency injection via interfaces
-    meme_repo: Arc<dyn MemeRepository>,
-    file_storage: Arc<dyn FileStorage>,
-    // Shared application configuration
-    config: Arc<Config>,
+#[derive(Parser, Debug)]
+#[command(version, about = "Meme posting API backed by DynamoDB and S3")]
+struct Cli {
+    /// TOML config file to layer under environment variables (overrides APP_CONFIG_FILE)
+    #[arg(long, global = true, value_name = "PATH")]
+    config: Option<PathBuf>,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug, Default)]
+enum Command {
+    /// Run the HTTP server (default when no subcommand is given)
+    #[default]
+    Serve,
+    /// Create the DynamoDB tables and S3 bucket if missing, then exit
+    InitResources,
+    /// Load fixture memes through the regular upload path, then exit
+    Seed {
+        /// JSON fixture file; image paths inside it are relative to this file
+        #[arg(long, value_name = "PATH", default_value = "fixtures/memes.json")]
+        file: PathBuf,
+    },
+    /// Validate the configuration, print the effective settings and exit
+    CheckConfig,
+    /// Recreate memes missing from the table from a metadata backup, then exit
+    RestoreBackup {
+        /// Storage key of the backup file, as returned when it was created
+        #[arg(long)]
+        key: String,
+    },
+    /// Apply pending schema migrations to the meme tables, then exit
+    Migrate {
+        /// Only list each migration and when it was applied
+        #[arg(long)]
+        status: bool,
+    },
+    /// Talk to a running server: upload, list or delete memes
+    #[cfg(feature = "client")]
+    Client(ClientArgs),
+}
+
+#[cfg(feature = "client")]
+#[derive(Args, Debug)]
+struct ClientArgs {
+    /// Base URL of the server
+    #[arg(long, global = true, default_value = "http://127.0.0.1:3000")]
+    url: String,
+
+    /// Bearer token acting as the owner; defaults to APP_ADMIN_TOKEN
+    #[arg(long, global = true)]
+    token: Option<String>,
+
+    #[command(subcommand)]
+    command: ClientCommand,
+}
+
+#[cfg(feature = "client")]
+#[derive(Subcommand, Debug)]
+enum ClientCommand {
+    /// Upload an image as a new meme and print its ID
+    Upload {
+        /// The image file
+        file: PathBuf,
+        #[arg(long)]
+        title: String,
+        #[arg(long)]
+        description: String,
+        /// Comma-separated tags
+        #[arg(long, value_delimiter = ',')]
+        tags: Vec<String>,
+        /// Seconds until the meme expires
+        #[arg(long)]
+        expires_in: Option<u64>,
+    },
+    /// List the published public memes, one per line: ID, title and tags
+    List {
+        /// Print the memes as JSON instead
+        #[arg(long)]
+        json: bool,
+    },
+    /// Delete a meme and its image
+    Delete {
+        id: uuid::Uuid,
+    },
 }
 
 //-----------------------------------------------------------------------------
@@ -46,89 +148,475 @@ pub struct AppState {
 //-----------------------------------------------------------------------------
 #[tokio::main]
 async fn main() -> Result<(), AppError> {
+    let cli = Cli::parse();
+    // Needs no server configuration, and keeps stdout free of logs for scripts
+    #[cfg(feature = "client")]
+    if let Some(Command::Client(args)) = cli.command {
+        if let Err(e) = run_client(args).await {
+            eprintln!("❌ {:#}", e);
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
     // --- Initialize Tracing ---
-    tracing_subscriber::registry()
-        .with(EnvFilter::try_from_default_env().unwrap_or_else(|_| {
-            // Define default log levels if RUST_LOG isn't set
-            "axum_meme_posting_example=debug,tower_http=debug,info".into()
-        }))
-        .with(tracing_subscriber::fmt::layer())
-        .init();
+    let log_filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| {
+        // Define default log levels if RUST_LOG isn't set
+        "axum_meme_posting_example=debug,tower_http=debug,info".into()
+    });
+    let registry = tracing_subscriber::registry().with(
+        tracing_subscriber::fmt::layer()
+            .with_ansi(!cfg!(feature = "lambda")) // CloudWatch shows raw escape codes
+            // Only filters logs, so tokio-console still sees every task; PUT /admin/log_level changes it
+            .with_filter(log_level::reloadable(log_filter)),
+    );
+    // Serves tokio-console on 127.0.0.1:6669 (TOKIO_CONSOLE_BIND to change)
+    #[cfg(feature = "console-subscriber")]
+    let registry = registry.with(console_subscriber::spawn());
+    registry.init();
     info!("Tracing initialized.");
 
     // --- Load Configuration ---
     // Load config early, fail fast if required vars are missing
-    let config = Config::load().map_err(|e| {
+    let config = Config::load(cli.config.as_deref()).await.map_err(|e| {
         eprintln!("❌ Configuration Error: {}", e); // Log critical error to stderr
         AppError::InitError(format!("Configuration loading failed: {}", e))
     })?;
     // Config is logged within Config::load now
 
-    // --- Create AWS SDK Config and Clients ---
-    info!("Initializing AWS SDK config and clients...");
-    let sdk_config = aws_clients::create_sdk_config(&config).await?; // Create base SDK config from App Config
-
-    let db_client = aws_clients::create_dynamodb_client(&sdk_config); // Create DynamoDB client
-    let s3_client = aws_clients::create_s3_client(&sdk_config); // Create S3 client
-    info!("AWS clients initialized.");
-
-    // --- Initialize AWS Resources (DynamoDB Table, S3 Bucket) ---
-    // Ensure backend resources are ready before starting the server
-    init_resources(
-        &db_client,
-        &s3_client,
-        &config.dynamodb_table_name, // Pass table name from config
-        &config.meme_bucket_name,    // Pass bucket name from config
-        &config.aws_region,
-    )
-    .await?; // Propagate errors
-    info!("AWS resources initialized successfully.");
-
-    // --- Create Repository and Storage Implementations ---
-    // Instantiate concrete types, passing clients and required config
-    let meme_repo_impl = DynamoDbMemeRepository::new(
-        db_client.clone(), // Clone client needed for repo
-        config.dynamodb_table_name.clone(), // Pass table name
-    );
-    let file_storage_impl = S3FileStorage::new(
-        s3_client.clone(), // Clone client needed for storage
-        config.meme_bucket_name.clone(), // Pass bucket name
-    );
-    info!("Repository and Storage implementations created.");
-
-    // --- Create Application State ---
-    // Bundle all shared components into an Arc<AppState>
-    let app_state = Arc::new(AppState {
-        db_client, // Move clients into state
-        s3_client,
-        // Convert concrete impls to trait objects (Arc<dyn Trait>)
-        meme_repo: Arc::new(meme_repo_impl),
-        file_storage: Arc::new(file_storage_impl),
-        // Share config using Arc
-        config: Arc::new(config),
-    });
-    info!("Application state created.");
+    match cli.command.unwrap_or_default() {
+        Command::Serve => {
+            let app_state = build_app_state(config).await?;
+            serve(app_state).await
+        }
+        Command::InitResources => {
+            let (db_client, s3_client) = create_clients(&config).await?;
+            initialize_resources(&db_client, &s3_client, &config, ResourceInitMode::Create).await?;
+            println!("Resources are ready.");
+            Ok(())
+        }
+        Command::Seed { file } => {
+            let app_state = build_app_state(config).await?;
+            let created = seed::seed_from_file(&app_state, &file).await?;
+            println!("Seeded {} meme(s) from {}.", created, file.display());
+            Ok(())
+        }
+        Command::CheckConfig => {
+            // Secrets are redacted in the serialized form, as for GET /admin/resources
+            let shown = serde_json::to_string_pretty(&config)
+                .map_err(|e| AppError::InitError(format!("Failed to serialize the configuration: {}", e)))?;
+            println!("{}", shown);
+            println!("Configuration is valid.");
+            Ok(())
+        }
+        Command::RestoreBackup { key } => {
+            let app_state = build_app_state(config).await?;
+            let report = backup::restore(&app_state, &key).await?;
+            println!(
+                "Restored {} meme(s) from {} ({} already present, {} failed).",
+                report.created, key, report.skipped, report.failed
+            );
+            for item in &report.items {
+                if let Some(error) = &item.error {
+                    eprintln!("{}: {}", item.meme_id, error);
+                }
+            }
+            Ok(())
+        }
+        Command::Migrate { status } => {
+            let (db_client, _) = create_clients(&config).await?;
+            let scopes = std::iter::once(config.clone()).chain(config.tenants.iter().map(|tenant| config.for_tenant(tenant)));
+            for scope in scopes {
+                let table_name = &scope.dynamodb_table_name;
+                if status {
+                    for migration in migrations::status(&db_client, &scope).await? {
+                        let applied = migration.applied_at.map_or_else(|| "pending".to_string(), |at| format!("applied {}", at));
+                        println!("{} {} ({}): {}", table_name, migration.id, applied, migration.description);
+                    }
+                } else {
+                    let applied = migrations::run_pending(&db_client, &scope).await?;
+                    println!("{}: {} migration(s) applied.", table_name, applied.len());
+                }
+            }
+            Ok(())
+        }
+        #[cfg(feature = "client")]
+        Command::Client(_) => unreachable!("client commands run before configuration is loaded"),
+    }
+}
+
+/// Runs a `client` subcommand against the server at `args.url`.
+#[cfg(feature = "client")]
+async fn run_client(args: ClientArgs) -> anyhow::Result<()> {
+    let mut client = MemeClient::new(args.url);
+    if let Some(token) = args.token.or_else(|| std::env::var("APP_ADMIN_TOKEN").ok()) {
+        client = client.with_token(token);
+    }
+    match args.command {
+        ClientCommand::Upload { file, title, description, tags, expires_in } => {
+            let image = std::fs::read(&file).with_context(|| format!("Failed to read {}", file.display()))?;
+            let filename = file.file_name().map_or_else(|| "image".to_string(), |name| name.to_string_lossy().into_owned());
+            let mut meme = NewMeme::new(title, description, image, filename).tags(tags);
+            if let Some(expires_in) = expires_in {
+                meme = meme.expires_in(expires_in);
+            }
+            let created = client.upload(meme).await?;
+            println!("{}", created.meme.meme_id);
+        }
+        ClientCommand::List { json } => {
+            let memes = client.list().await?;
+            // Write errors (such as a closed pipe) end the command rather than panic
+            let mut out = std::io::stdout().lock();
+            if json {
+                serde_json::to_writer_pretty(&mut out, &memes)?;
+                writeln!(out)?;
+            } else {
+                for view in memes {
+                    writeln!(out, "{}\t{}\t{}", view.meme.meme_id, view.meme.title, view.meme.tags.join(","))?;
+                }
+            }
+        }
+        ClientCommand::Delete { id } => {
+            client.delete(id).await?;
+            eprintln!("Deleted {}.", id);
+        }
+    }
+    Ok(())
+}
+
+/// Starts background jobs and runs the HTTP server until a shutdown signal arrives.
+///
+/// On shutdown the listener stops accepting connections, then in-flight requests and
+/// background jobs get `APP_SHUTDOWN_GRACE_SECS` to finish before they are cut off.
+#[cfg(not(feature = "lambda"))]
+async fn serve(app_state: Arc<AppState>) -> Result<(), AppError> {
+    let shutdown = CancellationToken::new();
+
+    // --- Background Jobs ---
+    let mut background_jobs = Vec::new();
+    // Per-table jobs run for the deployment's own table and every tenant's
+    for scoped_state in std::iter::once(&app_state).chain(app_state.tenants.values()) {
+        if let Some(interval_secs) = scoped_state.config.backup_interval_secs {
+            background_jobs.push(backup::spawn_scheduled_backups(
+                scoped_state.clone(),
+                Duration::from_secs(interval_secs),
+                shutdown.clone(),
+            ));
+        }
+        if scoped_state.config.expiry_cleanup_interval_secs > 0 {
+            background_jobs.push(expiry::spawn_expiry_cleanup(
+                scoped_state.clone(),
+                Duration::from_secs(scoped_state.config.expiry_cleanup_interval_secs),
+                shutdown.clone(),
+            ));
+        }
+        if scoped_state.config.publish_interval_secs > 0 {
+            background_jobs.push(publishing::spawn_publishing(
+                scoped_state.clone(),
+                Duration::from_secs(scoped_state.config.publish_interval_secs),
+                shutdown.clone(),
+            ));
+        }
+        background_jobs.push(stats::spawn_stats_aggregation(
+            scoped_state.clone(),
+            Duration::from_secs(scoped_state.config.stats_interval_secs),
+            shutdown.clone(),
+        ));
+        background_jobs.push(trending::spawn_trending(
+            scoped_state.clone(),
+            Duration::from_secs(scoped_state.config.trending_interval_secs),
+            shutdown.clone(),
+        ));
+        if let Some(region_failover) = &scoped_state.region_failover {
+            background_jobs.push(failover::spawn_health_checks(
+                region_failover.clone(),
+                Duration::from_secs(scoped_state.config.failover_check_interval_secs),
+                shutdown.clone(),
+            ));
+        }
+    }
+    if app_state.config.stream_consumer_enabled {
+        background_jobs.push(start_change_stream(&app_state, shutdown.clone()).await?);
+    }
+    #[cfg(feature = "discord")]
+    if let Some(bot) = app_state.discord.clone() {
+        bot.register_commands().await;
+        if bot.posts_memes() {
+            background_jobs.push(announcements::spawn_announcer(app_state.clone(), bot, shutdown.clone()));
+        }
+    }
+    if let Some(slack) = app_state.slack.clone().filter(|slack| slack.sends_notifications()) {
+        background_jobs.push(announcements::spawn_announcer(app_state.clone(), slack, shutdown.clone()));
+    }
+    if let Some(actor) = app_state.activitypub.clone() {
+        background_jobs.push(announcements::spawn_announcer(app_state.clone(), actor, shutdown.clone()));
+    }
+    #[cfg(feature = "telegram")]
+    if let Some(bot) = app_state.telegram.clone() {
+        if bot.uses_webhook() {
+            bot.register_webhook().await;
+        } else {
+            background_jobs.push(telegram::spawn_polling(app_state.clone(), bot, shutdown.clone()));
+        }
+    }
 
     // --- Create Router ---
     let app = create_router(app_state.clone()); // Pass Arc<AppState> to router setup
     info!("Axum router created.");
 
-    // --- Start Server ---
-    let bind_address = app_state.config.bind_address; // Get bind address from config in state
-    info!("Server listening on http://{}", bind_address);
-    let listener = tokio::net::TcpListener::bind(bind_address)
-        .await
-        .map_err(|e| AppError::InitError(format!("Failed to bind to address {}: {}", bind_address, e)))?;
+    // --- Start Servers ---
+    let config = &app_state.config;
+    let bind_address = &config.bind_address; // Get bind address from config in state
+    let mut servers = Vec::new();
+    match (&config.tls_cert_path, &config.tls_key_path, bind_address) {
+        (Some(cert_path), Some(key_path), config::ListenAddress::Tcp(socket_address)) => {
+            let tls_config = tls::load_rustls_config(cert_path, key_path)?;
+            servers.push(spawn_tls_server(*socket_address, tls_config, app, shutdown.clone())?);
+            info!("Server listening on https://{}", socket_address);
+
+            if let Some(redirect_address) = config.tls_redirect_address {
+                let redirect_app = tls::redirect_router(socket_address.port());
+                let redirect_address = config::ListenAddress::Tcp(redirect_address);
+                servers.push(spawn_server(&redirect_address, config.server_socket_mode, None, redirect_app, shutdown.clone()).await?);
+                info!("Redirecting {} to HTTPS", redirect_address);
+            }
+        }
+        _ => {
+            let proxy_protocol = config.server_proxy_protocol.then(|| config.client_trusted_proxies.clone());
+            servers.push(spawn_server(bind_address, config.server_socket_mode, proxy_protocol, app, shutdown.clone()).await?);
+            info!("Server listening on {}", bind_address);
+        }
+    }
+
+    // Operator routes get their own listener when an admin address is configured
+    if let Some(admin_address) = &config.admin_address {
+        let admin_app = routes::create_admin_router(app_state.clone());
+        servers.push(spawn_server(admin_address, config.server_socket_mode, None, admin_app, shutdown.clone()).await?);
+        info!("Admin server listening on {}", admin_address);
+    }
+
+    tokio::select! {
+        (result, _, _) = futures::future::select_all(servers.iter_mut()) => {
+            // A server only stops by itself on error
+            return server_result(result);
+        }
+        _ = shutdown_signal() => {}
+    }
+
+    // --- Drain ---
+    shutdown.cancel(); // Stops accepting connections and tells background jobs to wrap up
+    let grace = Duration::from_secs(app_state.config.shutdown_grace_secs);
+    let deadline = tokio::time::Instant::now() + grace;
+    let in_flight_at_signal = app_state.in_flight.active();
+    info!(
+        in_flight = in_flight_at_signal,
+        grace_secs = grace.as_secs(),
+        "Stopped accepting connections, draining in-flight requests"
+    );
+
+    let mut server_outcome = Ok(());
+    let mut unfinished = Vec::new();
+    for mut server in servers {
+        match tokio::time::timeout_at(deadline, &mut server).await {
+            Ok(result) => server_outcome = server_outcome.and(server_result(result)),
+            Err(_) => unfinished.push(server),
+        }
+    }
+    // Work requests left running in the background, e.g. answers to chat platforms
+    let drained = unfinished.is_empty() && tokio::time::timeout_at(deadline, app_state.in_flight.wait_idle()).await.is_ok();
+    let requests_aborted = if drained { 0 } else { app_state.in_flight.active() };
+    if !drained {
+        tracing::warn!(still_active = requests_aborted, "Grace period expired, aborting remaining requests");
+        unfinished.iter().for_each(|server| server.abort());
+    }
+
+    let (mut jobs_flushed, mut jobs_aborted) = (0, 0);
+    for mut job in background_jobs {
+        if tokio::time::timeout_at(deadline, &mut job).await.is_ok() {
+            jobs_flushed += 1;
+        } else {
+            job.abort();
+            jobs_aborted += 1;
+        }
+    }
+
+    // Unix socket files would otherwise block the next start
+    #[cfg(unix)]
+    for address in std::iter::once(&config.bind_address).chain(&config.admin_address) {
+        if let config::ListenAddress::Unix(path) = address
+            && let Err(e) = std::fs::remove_file(path)
+        {
+            tracing::warn!(path = %path.display(), error = %e, "Failed to remove Unix socket file");
+        }
+    }
+
+    // Errors reported while draining are still queued
+    error_reporting::flush(Duration::from_secs(2));
+
+    info!(
+        in_flight_at_signal,
+        requests_drained = in_flight_at_signal.saturating_sub(requests_aborted),
+        requests_aborted,
+        background_jobs_flushed = jobs_flushed,
+        background_jobs_aborted = jobs_aborted,
+        requests_served = app_state.in_flight.completed(),
+        "Shutdown complete"
+    );
+    server_outcome
+}
+
+/// Binds `address` and serves `app` on a background task until `shutdown` is cancelled.
+/// Unix socket files get `socket_mode` permissions; a stale socket left by a previous run is replaced.
+/// With `proxy_protocol`, TCP connections start with a PROXY protocol header, whose source
+/// address is used for connections from the given trusted proxies.
+#[cfg(not(feature = "lambda"))]
+async fn spawn_server(
+    address: &config::ListenAddress,
+    #[cfg_attr(not(unix), allow(unused_variables))] socket_mode: u32,
+    proxy_protocol: Option<Vec<ipnet::IpNet>>,
+    app: axum::Router,
+    shutdown: CancellationToken,
+) -> Result<tokio::task::JoinHandle<std::io::Result<()>>, AppError> {
+    let bind_error = |e: std::io::Error| AppError::InitError(format!("Failed to bind to address {}: {}", address, e));
 
     // Run the server with graceful shutdown
-    axum::serve(listener, app.into_make_service()) // Use app directly if using Axum 0.7+
-        .with_graceful_shutdown(shutdown_signal())
-        .await
-        .map_err(|e| AppError::InternalServerError(format!("Server execution failed: {}", e)))?;
+    let task = match address {
+        config::ListenAddress::Tcp(socket_address) => {
+            let listener = tokio::net::TcpListener::bind(socket_address).await.map_err(bind_error)?;
+            // Peer addresses for client IP resolution
+            let app = app.into_make_service_with_connect_info::<client_ip::PeerAddr>();
+            match proxy_protocol {
+                Some(trusted_proxies) => {
+                    let listener = client_ip::ProxyProtocolListener::new(listener, trusted_proxies).map_err(bind_error)?;
+                    let server = axum::serve(listener, app).with_graceful_shutdown(shutdown.cancelled_owned());
+                    tokio::spawn(async move { server.await })
+                }
+                None => {
+                    let server = axum::serve(listener, app).with_graceful_shutdown(shutdown.cancelled_owned());
+                    tokio::spawn(async move { server.await })
+                }
+            }
+        }
+        #[cfg(unix)]
+        config::ListenAddress::Unix(path) => {
+            use std::os::unix::fs::{FileTypeExt, PermissionsExt};
 
-    Ok(())
+            match std::fs::symlink_metadata(path) {
+                Ok(meta) if meta.file_type().is_socket() => std::fs::remove_file(path).map_err(bind_error)?,
+                Ok(_) => {
+                    return Err(AppError::InitError(format!(
+                        "Refusing to replace {}: it exists and is not a socket",
+                        path.display()
+                    )));
+                }
+                Err(_) => {} // Nothing to clean up
+            }
+            let listener = tokio::net::UnixListener::bind(path).map_err(bind_error)?;
+            std::fs::set_permissions(path, std::fs::Permissions::from_mode(socket_mode)).map_err(bind_error)?;
+            let server = axum::serve(listener, app.into_make_service())
+                .with_graceful_shutdown(shutdown.cancelled_owned());
+            tokio::spawn(async move { server.await })
+        }
+        #[cfg(not(unix))]
+        config::ListenAddress::Unix(_) => unreachable!("rejected when the config is loaded"),
+    };
+    Ok(task)
+}
+
+/// Serves `app` over HTTPS on `address`, stopping gracefully once `shutdown` is cancelled.
+#[cfg(not(feature = "lambda"))]
+fn spawn_tls_server(
+    address: std::net::SocketAddr,
+    tls_config: axum_server::tls_rustls::RustlsConfig,
+    app: axum::Router,
+    shutdown: CancellationToken,
+) -> Result<tokio::task::JoinHandle<std::io::Result<()>>, AppError> {
+    // Bind up front so an address in use is reported at startup, like the plain listeners
+    let listener = std::net::TcpListener::bind(address)
+        .and_then(|listener| listener.set_nonblocking(true).map(|_| listener))
+        .map_err(|e| AppError::InitError(format!("Failed to bind to address https://{}: {}", address, e)))?;
+
+    let handle = axum_server::Handle::new();
+    let server = axum_server::from_tcp_rustls(listener, tls_config)
+        .handle(handle.clone())
+        .serve(app.into_make_service_with_connect_info::<client_ip::PeerAddr>());
+    tokio::spawn(async move {
+        shutdown.cancelled().await;
+        handle.graceful_shutdown(None); // The drain deadline in `serve` bounds the wait
+    });
+    Ok(tokio::spawn(server))
+}
+
+/// Wires the change stream consumer to its handlers: TTL image cleanup always, webhooks
+/// when `APP_WEBHOOK_URLS` is set.
+#[cfg(not(feature = "lambda"))]
+async fn start_change_stream(
+    app_state: &Arc<AppState>,
+    shutdown: CancellationToken,
+) -> Result<tokio::task::JoinHandle<()>, AppError> {
+    let config = &app_state.config;
+    let mut handlers: Vec<Arc<dyn change_stream::ChangeHandler>> =
+        vec![Arc::new(expiry::ExpiredImageCleanup::new(app_state.file_storage.clone()))];
+    if !config.webhook_urls.is_empty() {
+        let notifier = webhooks::WebhookNotifier::new(config.webhook_urls.clone(), config.webhook_secret.clone())
+            .map_err(|e| AppError::InitError(format!("Failed to build webhook HTTP client: {}", e)))?;
+        handlers.push(Arc::new(notifier));
+    }
+
+    let sdk_config = aws_clients::create_sdk_config(config).await?;
+    let checkpoints = DynamoDbCheckpointRepository::new(app_state.db_client.clone(), config.meta_table_name.clone());
+    let consumer = change_stream::StreamConsumer::connect(
+        &app_state.db_client,
+        aws_clients::create_dynamodb_streams_client(&sdk_config),
+        &config.dynamodb_table_name,
+        Arc::new(checkpoints),
+        handlers,
+        Duration::from_millis(config.stream_poll_interval_ms),
+    )
+    .await?;
+    Ok(consumer.spawn(shutdown))
+}
+
+#[cfg(not(feature = "lambda"))]
+fn server_result(result: Result<std::io::Result<()>, tokio::task::JoinError>) -> Result<(), AppError> {
+    match result {
+        Ok(Ok(())) => Ok(()),
+        Ok(Err(e)) => Err(AppError::InternalServerError(format!("Server execution failed: {}", e))),
+        Err(e) => Err(AppError::InternalServerError(format!("Server task failed: {}", e))),
+    }
+}
+
+/// Hands the router to the Lambda runtime, which feeds it API Gateway / function URL events.
+/// Lambda freezes the process between invocations, so periodic backups are not scheduled;
+/// trigger them through `POST /admin/backups` from an EventBridge schedule instead.
+#[cfg(feature = "lambda")]
+async fn serve(app_state: Arc<AppState>) -> Result<(), AppError> {
+    if app_state.config.backup_interval_secs.is_some() {
+        tracing::warn!("APP_BACKUP_INTERVAL_SECS is ignored when running on Lambda");
+    }
+    if app_state.config.admin_address.is_some() {
+        return Err(AppError::InitError(
+            "APP_ADMIN_ADDRESS is not supported on Lambda; unset it to serve admin routes on the main router".to_string(),
+        ));
+    }
+    if app_state.config.tls_cert_path.is_some() {
+        return Err(AppError::InitError(
+            "APP_TLS_CERT_PATH is not supported on Lambda; TLS is terminated by API Gateway or the function URL".to_string(),
+        ));
+    }
+    if app_state.config.stream_consumer_enabled {
+        return Err(AppError::InitError(
+            "APP_STREAM_CONSUMER is not supported on Lambda; the consumer polls continuously and needs a long-running server".to_string(),
+        ));
+    }
+    let app = create_router(app_state);
+    info!("Axum router created, starting Lambda runtime.");
+    lambda_http::run(app)
+        .await
+        .map_err(|e| AppError::InternalServerError(format!("Lambda runtime failed: {}", e)))
 }
 
+#[cfg(not(feature = "lambda"))]
 async fn shutdown_signal() {
     let ctrl_c = async {
         signal::ctrl_c()
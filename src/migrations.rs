@@ -0,0 +1,129 @@
+//! Schema migrations of the DynamoDB meme table: index additions, TTL enablement and
+//! backfills of attributes newer code relies on. Each step runs once per table; applied
+//! steps are recorded in the meta table (see [`DynamoDbMigrationRepository`]). Pending
+//! steps run at startup with `APP_RESOURCE_INIT=create` and through the `migrate` command.
+//! Steps are idempotent, so two instances starting together at worst repeat one.
+
+use crate::{
+    backends::RepositoryBackend,
+    config::Config,
+    domain::{AppliedMigration, MigrationRepository},
+    errors::AppError,
+    repositories::{self, DynamoDbMigrationRepository, MEME_TTL_ATTRIBUTE},
+    startup,
+};
+use aws_sdk_dynamodb::Client as DynamoDbClient;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use tracing::{info, warn};
+
+/// A schema change, identified by a sortable ID that never changes once released.
+pub struct Migration {
+    pub id: &'static str,
+    pub description: &'static str,
+    step: Step,
+}
+
+enum Step {
+    ListingIndexes,
+    ListingKeys,
+    ExpiryTtl,
+}
+
+/// Every migration, in the order they run. Append new ones; never reorder or remove.
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        id: "0001-listing-indexes",
+        description: "Add the global secondary indexes of sorted listings",
+        step: Step::ListingIndexes,
+    },
+    Migration {
+        id: "0002-listing-keys",
+        description: "Add listing index keys to memes stored before sorting existed",
+        step: Step::ListingKeys,
+    },
+    Migration {
+        id: "0003-expiry-ttl",
+        description: "Enable DynamoDB TTL on the expiry attribute",
+        step: Step::ExpiryTtl,
+    },
+];
+
+/// A migration and when it was applied to a table; `None` while pending.
+#[derive(Debug, Serialize)]
+pub struct MigrationStatus {
+    pub id: &'static str,
+    pub description: &'static str,
+    pub applied_at: Option<DateTime<Utc>>,
+}
+
+/// The meme table the configuration migrates, with where its record is kept; `None` when
+/// memes are stored outside DynamoDB.
+fn migrated_table(db_client: &DynamoDbClient, config: &Config) -> Option<DynamoDbMigrationRepository> {
+    (config.repository_backend == RepositoryBackend::DynamoDb).then(|| {
+        DynamoDbMigrationRepository::new(db_client.clone(), config.meta_table_name.clone(), config.tenant.as_deref())
+    })
+}
+
+/// Every migration with its state for the configuration's meme table.
+pub async fn status(db_client: &DynamoDbClient, config: &Config) -> Result<Vec<MigrationStatus>, AppError> {
+    let Some(record) = migrated_table(db_client, config) else {
+        return Ok(Vec::new());
+    };
+    let applied = record.list_applied().await?;
+    Ok(MIGRATIONS
+        .iter()
+        .map(|migration| MigrationStatus {
+            id: migration.id,
+            description: migration.description,
+            applied_at: applied.iter().find(|done| done.id == migration.id).map(|done| done.applied_at),
+        })
+        .collect())
+}
+
+/// Applies the pending migrations in order, recording each as it completes, so a failed
+/// step is retried on the next run and the ones after it wait. Returns the IDs applied.
+pub async fn run_pending(db_client: &DynamoDbClient, config: &Config) -> Result<Vec<&'static str>, AppError> {
+    let Some(record) = migrated_table(db_client, config) else {
+        return Ok(Vec::new());
+    };
+    let applied = record.list_applied().await?;
+    let table_name = &config.dynamodb_table_name;
+    let mut ran = Vec::new();
+    for migration in MIGRATIONS.iter().filter(|migration| !applied.iter().any(|done| done.id == migration.id)) {
+        info!(%table_name, migration = migration.id, "Applying migration: {}", migration.description);
+        apply(db_client, config, migration).await.map_err(|e| {
+            AppError::InitError(format!("Migration '{}' of DynamoDB table '{}' failed: {}", migration.id, table_name, e))
+        })?;
+        record.record(&AppliedMigration { id: migration.id.to_string(), applied_at: Utc::now() }).await?;
+        ran.push(migration.id);
+    }
+    Ok(ran)
+}
+
+/// Logs the migrations not yet applied, for deployments that do not run them at startup.
+pub async fn warn_pending(db_client: &DynamoDbClient, config: &Config) -> Result<(), AppError> {
+    let pending: Vec<_> = status(db_client, config)
+        .await?
+        .into_iter()
+        .filter(|migration| migration.applied_at.is_none())
+        .map(|migration| migration.id)
+        .collect();
+    if !pending.is_empty() {
+        warn!(table_name = %config.dynamodb_table_name, ?pending, "DynamoDB table has pending migrations; run the `migrate` command");
+    }
+    Ok(())
+}
+
+async fn apply(db_client: &DynamoDbClient, config: &Config, migration: &Migration) -> Result<(), AppError> {
+    let table_name = &config.dynamodb_table_name;
+    match migration.step {
+        Step::ListingIndexes => startup::create_listing_indexes(db_client, table_name, &config.dynamodb_capacity).await,
+        Step::ListingKeys => {
+            let updated = repositories::backfill_listing_keys(db_client, table_name).await?;
+            info!(%table_name, updated, "Listing keys backfilled.");
+            Ok(())
+        }
+        Step::ExpiryTtl => startup::ensure_ttl(db_client, table_name, MEME_TTL_ATTRIBUTE, true).await,
+    }
+}
@@ -1,3 +1,4 @@
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
@@ -7,11 +8,247 @@ use uuid::Uuid;
 /// - `meme_id`: A unique identifier (UUID) for the meme.
 /// - `title`: The meme's title.
 /// - `description`: A short description of the meme.
-/// - `image_key`: The key (i.e. filename) of the meme image stored in S3.
+/// - `image_key`: The key (i.e. filename) of the meme image stored in S3. Under
+///   [`crate::keys::QUARANTINE_PREFIX`] while the meme awaits approval.
+/// - `tags`: Normalized (lowercase, de-duplicated) tags describing the meme.
+/// - `source_url`: For images ingested by URL, where the image was fetched from.
+/// - `expires_at`: For ephemeral memes, when the meme stops being served and is cleaned up.
+/// - `created_at`: When the meme was uploaded; unknown for memes stored before it was recorded.
+/// - `version`: Incremented on every update; exposed as the `ETag` for optimistic concurrency.
+/// - `like_count`: Number of likes. Counted atomically and not part of the version.
+/// - `visibility`: Who can see the meme; see [`Visibility`].
+/// - `publish_at`: For scheduled memes, when the meme appears publicly.
+/// - `status`: Whether the meme is a draft or published; see [`MemeStatus`].
+/// - `view_count`: Approximate number of views. Counted like likes, in batches.
+/// - `caption_text`: The text on the image, read by OCR at upload when it is turned on.
+/// - `dominant_color`: The color covering most of the image, as `#rrggbb`.
+/// - `palette`: Up to five most common colors of the image, the dominant one first.
+/// - `width`, `height`: The image's size in pixels, read from its header at upload.
+/// - `size_bytes`: The size of the stored image file.
+/// - `content_hash`: SHA-256 of the image file, in hex; filled in for older memes by the
+///   `content_hash` backfill.
+/// - `updated_at`: When the meme's metadata was last changed by an update, revert, approval
+///   or publication; unset until then. Likes, views and backfills leave it alone.
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Meme {
     pub meme_id: Uuid,
     pub title: String,
     pub description: String,
     pub image_key: String,
-}
\ No newline at end of file
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source_url: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<DateTime<Utc>>,
+    #[serde(default = "initial_version")]
+    pub version: u64,
+    #[serde(default)]
+    pub like_count: u64,
+    #[serde(default)]
+    pub visibility: Visibility,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub publish_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub status: MemeStatus,
+    #[serde(default)]
+    pub view_count: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub caption_text: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dominant_color: Option<String>,
+    #[serde(default)]
+    pub palette: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub width: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub height: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size_bytes: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content_hash: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+/// Who can see a meme. Memes stored before visibility existed are public.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Visibility {
+    /// Listed and viewable by anyone.
+    #[default]
+    Public,
+    /// Viewable by anyone with its ID, but left out of listings and exports.
+    Unlisted,
+    /// Only viewable by its owner (or through a share link).
+    Private,
+}
+
+impl Visibility {
+    /// Name as serialized and stored, e.g. `"unlisted"`.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Visibility::Public => "public",
+            Visibility::Unlisted => "unlisted",
+            Visibility::Private => "private",
+        }
+    }
+
+    /// Parses a stored name; the inverse of [`Visibility::as_str`].
+    pub fn from_name(name: &str) -> Option<Self> {
+        [Visibility::Public, Visibility::Unlisted, Visibility::Private]
+            .into_iter()
+            .find(|visibility| visibility.as_str() == name)
+    }
+}
+
+/// Where a meme is in the publishing workflow. Memes stored before drafts existed are
+/// published.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum MemeStatus {
+    /// Only visible to its owner until published, either explicitly or, with a `publish_at`,
+    /// by the publishing job once that time passes.
+    Draft,
+    /// Shown according to its visibility.
+    #[default]
+    Published,
+}
+
+impl MemeStatus {
+    /// Name as serialized and stored, e.g. `"draft"`.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            MemeStatus::Draft => "draft",
+            MemeStatus::Published => "published",
+        }
+    }
+
+    /// Parses a stored name; the inverse of [`MemeStatus::as_str`].
+    pub fn from_name(name: &str) -> Option<Self> {
+        [MemeStatus::Draft, MemeStatus::Published].into_iter().find(|status| status.as_str() == name)
+    }
+}
+
+/// Version of a newly created meme, and of memes stored before versioning existed.
+pub const INITIAL_VERSION: u64 = 1;
+
+pub(crate) fn initial_version() -> u64 {
+    INITIAL_VERSION
+}
+
+
+impl Meme {
+    /// Names of the fields selectable with `?fields=`: the serialized fields, kept in sync
+    /// with the struct, plus `image_url` from [`MemeView`].
+    pub const FIELDS: &'static [&'static str] = &[
+        "meme_id",
+        "title",
+        "description",
+        "image_key",
+        "tags",
+        "source_url",
+        "expires_at",
+        "created_at",
+        "version",
+        "like_count",
+        "visibility",
+        "publish_at",
+        "status",
+        "view_count",
+        "caption_text",
+        "dominant_color",
+        "palette",
+        "width",
+        "height",
+        "size_bytes",
+        "content_hash",
+        "updated_at",
+        "image_url",
+    ];
+
+    /// Whether the meme's expiry time has passed (it may not have been cleaned up yet).
+    pub fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        self.expires_at.is_some_and(|expires_at| expires_at <= now)
+    }
+
+    /// Whether the meme is published, or a scheduled draft whose publish time has passed (the
+    /// publishing job may not have published it yet).
+    pub fn is_published(&self, now: DateTime<Utc>) -> bool {
+        self.status == MemeStatus::Published || self.publish_at.is_some_and(|publish_at| publish_at <= now)
+    }
+
+    /// Whether the meme's image is still quarantined, waiting for a moderator's approval.
+    pub fn is_awaiting_approval(&self) -> bool {
+        crate::keys::is_quarantined(&self.image_key)
+    }
+
+    /// Whether a caller may see the meme; private memes, drafts and memes awaiting approval
+    /// need owner credentials.
+    pub fn is_visible_to(&self, is_owner: bool) -> bool {
+        is_owner || (self.visibility != Visibility::Private && self.is_published(Utc::now()) && !self.is_awaiting_approval())
+    }
+
+    /// Whether the meme belongs in public listings, exports and statistics: public,
+    /// published and approved.
+    pub fn is_listed(&self, now: DateTime<Utc>) -> bool {
+        self.visibility == Visibility::Public && self.is_published(now) && !self.is_awaiting_approval()
+    }
+
+    /// Strong entity tag for this version of the meme, as sent in `ETag` headers.
+    pub fn etag(&self) -> String {
+        format!("\"{}\"", self.version)
+    }
+}
+
+/// A meme as the API returns it: the stored metadata plus a short-lived URL of its image
+/// (see [`crate::cdn::image_url`]). Kept out of [`Meme`] so the URL is never persisted.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct MemeView {
+    #[serde(flatten)]
+    pub meme: Meme,
+    pub image_url: String,
+}
+
+/// Orders offered by `GET /memes?sort=`, each served by an index rather than sorted in memory.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    /// Most recently uploaded first.
+    Newest,
+    /// Oldest upload first.
+    Oldest,
+    /// Alphabetical by title, ignoring case.
+    Title,
+    /// Most liked first.
+    Top,
+}
+
+/// Periods offered by `GET /memes/trending?window=`: memes uploaded within the period,
+/// ranked by their decayed popularity (see [`crate::trending`]).
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum TrendingWindow {
+    /// Uploaded in the last 24 hours.
+    #[default]
+    Day,
+    /// Uploaded in the last 7 days.
+    Week,
+    /// Uploaded in the last 30 days.
+    Month,
+}
+
+impl TrendingWindow {
+    pub const ALL: [TrendingWindow; 3] = [TrendingWindow::Day, TrendingWindow::Week, TrendingWindow::Month];
+
+    /// How far back uploads count.
+    pub fn duration(self) -> chrono::Duration {
+        match self {
+            TrendingWindow::Day => chrono::Duration::days(1),
+            TrendingWindow::Week => chrono::Duration::days(7),
+            TrendingWindow::Month => chrono::Duration::days(30),
+        }
+    }
+}
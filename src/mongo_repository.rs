@@ -0,0 +1,514 @@
+use crate::{
+    config::Config,
+    domain::{ConsistencyLevel, MemeRepository, TableInfo},
+    errors::{AppError, RepoError},
+    models::{Meme, MemeStatus, SortOrder, Visibility, INITIAL_VERSION},
+};
+use anyhow::Context;
+use async_trait::async_trait;
+use chrono::{DateTime, SecondsFormat, Utc};
+use futures::TryStreamExt;
+use mongodb::{
+    bson::{self, doc, Bson, Document},
+    error::{ErrorKind, WriteError, WriteFailure},
+    options::{IndexOptions, ReturnDocument},
+    Client, Collection, Database, IndexModel,
+};
+use std::time::Duration;
+use tracing::info;
+use uuid::Uuid;
+
+/// How long after `expires_at` the TTL index may delete a document, as with DynamoDB TTL.
+const TTL_DELAY_SECS: i64 = 24 * 60 * 60;
+/// Server error code for a duplicate `_id`.
+const DUPLICATE_KEY: i32 = 11000;
+
+/// Indexes the collection needs, by name: the three listing orders, expiry scans, and the
+/// TTL index on `ttl`.
+fn indexes() -> Vec<IndexModel> {
+    let index = |name: &str, keys: Document| {
+        IndexModel::builder().keys(keys).options(IndexOptions::builder().name(name.to_string()).build()).build()
+    };
+    vec![
+        index("by_created_at", doc! { "created_at": -1 }),
+        index("by_title", doc! { "title_key": 1 }),
+        index("by_like_count", doc! { "like_count": -1 }),
+        index("by_expires_at", doc! { "expires_at": 1 }),
+        IndexModel::builder()
+            .keys(doc! { "ttl": 1 })
+            .options(IndexOptions::builder().name("ttl".to_string()).expire_after(Duration::ZERO).build())
+            .build(),
+    ]
+}
+
+/// Filter hiding expired memes at `now`.
+fn not_expired(now: DateTime<Utc>) -> Document {
+    doc! { "$or": [{ "expires_at": { "$exists": false } }, { "expires_at": { "$gt": now.timestamp() } }] }
+}
+
+/// Implements `MemeRepository` on a MongoDB collection named like the DynamoDB meme table
+/// (so tenants get `<table>-<tenant>`), in the `APP_MONGODB_DATABASE` database. Documents
+/// are keyed by `_id` = meme ID and mirror the DynamoDB item attributes.
+#[derive(Debug, Clone)]
+pub struct MongoMemeRepository {
+    database: Database,
+    collection: Collection<Document>,
+}
+
+impl MongoMemeRepository {
+    pub async fn from_config(config: &Config) -> Result<Self, AppError> {
+        let database = connect(config).await?;
+        let collection = database.collection(&config.dynamodb_table_name);
+        info!(collection = %config.dynamodb_table_name, database = %config.mongodb_database, "Initializing MongoMemeRepository");
+        Ok(Self { database, collection })
+    }
+
+    /// Finds memes matching `filter` in `sort` order. Fails fast if a document is corrupt.
+    async fn find_memes(&self, filter: Document, sort: Option<Document>) -> Result<Vec<Meme>, RepoError> {
+        let documents: Vec<Document> = self
+            .collection
+            .find(filter)
+            .with_options(mongodb::options::FindOptions::builder().sort(sort).build())
+            .await
+            .context(format!("MongoDB: Failed to query collection '{}'", self.collection.name()))
+            .map_err(RepoError::BackendError)?
+            .try_collect()
+            .await
+            .context(format!("MongoDB: Failed to read results from collection '{}'", self.collection.name()))
+            .map_err(RepoError::BackendError)?;
+        documents.iter().map(|document| self.parse_document(document)).collect()
+    }
+
+    fn parse_document(&self, document: &Document) -> Result<Meme, RepoError> {
+        document_to_meme(document).ok_or_else(|| {
+            let id = document.get_str("_id").ok();
+            tracing::error!(meme_id = ?id, collection = %self.collection.name(), "MongoDB: Failed to parse document into Meme");
+            RepoError::DataCorruption {
+                field: "document".to_string(),
+                reason: format!("MongoDB: Failed to parse document {:?} of collection '{}'", id, self.collection.name()),
+            }
+        })
+    }
+}
+
+async fn connect(config: &Config) -> Result<Database, AppError> {
+    let uri = config
+        .mongodb_uri
+        .as_deref()
+        .ok_or_else(|| AppError::InitError("MongoDB needs APP_MONGODB_URI".to_string()))?;
+    let client = Client::with_uri_str(uri)
+        .await
+        .map_err(|e| AppError::InitError(format!("Invalid APP_MONGODB_URI: {}", e)))?;
+    Ok(client.database(&config.mongodb_database))
+}
+
+fn is_duplicate_key(error: &mongodb::error::Error) -> bool {
+    matches!(
+        error.kind.as_ref(),
+        ErrorKind::Write(WriteFailure::WriteError(WriteError { code: DUPLICATE_KEY, .. }))
+    )
+}
+
+/// Creates the collection's indexes in `create` mode, or checks they exist. Creating an
+/// index that already exists is a no-op, so this is safe on every start.
+pub async fn ensure_indexes(config: &Config, create: bool) -> Result<(), AppError> {
+    let collection: Collection<Document> = connect(config).await?.collection(&config.dynamodb_table_name);
+    let name = collection.name().to_string();
+    if create {
+        collection
+            .create_indexes(indexes())
+            .await
+            .map_err(|e| AppError::InitError(format!("Failed to create indexes on MongoDB collection '{}': {}", name, e)))?;
+        info!(collection = %name, "MongoDB indexes created.");
+        return Ok(());
+    }
+    // A collection that does not exist yet has no indexes
+    let existing = match collection.list_index_names().await {
+        Ok(names) => names,
+        Err(e) if matches!(e.kind.as_ref(), ErrorKind::Command(command) if command.code == 26) => Vec::new(),
+        Err(e) => return Err(AppError::InitError(format!("Failed to list indexes of MongoDB collection '{}': {}", name, e))),
+    };
+    let missing: Vec<String> = indexes()
+        .iter()
+        .filter_map(|index| index.options.as_ref()?.name.clone())
+        .filter(|index| !existing.contains(index))
+        .collect();
+    if !missing.is_empty() {
+        return Err(AppError::InitError(format!(
+            "MongoDB collection '{}' is missing indexes {}. Create them, or set APP_RESOURCE_INIT=create",
+            name,
+            missing.join(", ")
+        )));
+    }
+    info!(collection = %name, "MongoDB indexes verified.");
+    Ok(())
+}
+
+#[async_trait]
+impl MemeRepository for MongoMemeRepository {
+    /// Inserts the document. A duplicate `_id` holding this very meme is a retried insert
+    /// whose first attempt landed, not a collision.
+    async fn create(&self, meme: &Meme) -> Result<(), RepoError> {
+        let document = meme_to_document(meme);
+        let err = match self.collection.insert_one(document.clone()).await {
+            Ok(_) => return Ok(()),
+            Err(err) => err,
+        };
+        if !is_duplicate_key(&err) {
+            return Err(RepoError::BackendError(
+                anyhow::Error::new(err)
+                    .context(format!("MongoDB (collection: {}): Failed to insert meme (id: {})", self.collection.name(), meme.meme_id)),
+            ));
+        }
+        let existing = self
+            .collection
+            .find_one(doc! { "_id": meme.meme_id.to_string() })
+            .await
+            .context(format!("MongoDB (collection: {}): Failed to get meme (id: {})", self.collection.name(), meme.meme_id))
+            .map_err(RepoError::BackendError)?;
+        match existing {
+            Some(existing) if existing == document => Ok(()),
+            _ => Err(RepoError::AlreadyExists(meme.meme_id)),
+        }
+    }
+
+    /// Reads go to the primary, so they are always strong.
+    async fn get_by_id(&self, id: Uuid, _consistency: ConsistencyLevel) -> Result<Option<Meme>, RepoError> {
+        let document = self
+            .collection
+            .find_one(doc! { "_id": id.to_string() })
+            .await
+            .context(format!("MongoDB (collection: {}): Failed to get meme (id: {})", self.collection.name(), id))
+            .map_err(RepoError::BackendError)?;
+        match document {
+            // Expired memes are hidden until the cleanup job (or the TTL index) removes them
+            Some(document) => match self.parse_document(&document)? {
+                meme if meme.is_expired(Utc::now()) => Ok(None),
+                meme => Ok(Some(meme)),
+            },
+            None => Ok(None),
+        }
+    }
+
+    async fn exists(&self, id: Uuid) -> Result<bool, RepoError> {
+        let mut filter = not_expired(Utc::now());
+        filter.insert("_id", id.to_string());
+        let count = self
+            .collection
+            .count_documents(filter)
+            .limit(1)
+            .await
+            .context(format!("MongoDB (collection: {}): Failed to check meme (id: {})", self.collection.name(), id))
+            .map_err(RepoError::BackendError)?;
+        Ok(count > 0)
+    }
+
+    async fn list_all(&self) -> Result<Vec<Meme>, RepoError> {
+        self.find_memes(not_expired(Utc::now()), None).await
+    }
+
+    /// Sorts on the same attributes as the DynamoDB listing indexes, leaving out memes
+    /// without the sort attribute just as those sparse indexes do.
+    async fn list_sorted(&self, order: SortOrder) -> Result<Vec<Meme>, RepoError> {
+        let (field, direction) = match order {
+            SortOrder::Newest => ("created_at", -1),
+            SortOrder::Oldest => ("created_at", 1),
+            SortOrder::Title => ("title_key", 1),
+            SortOrder::Top => ("like_count", -1),
+        };
+        let filter = doc! { "$and": [not_expired(Utc::now()), { field: { "$exists": true } }] };
+        self.find_memes(filter, Some(doc! { field: direction })).await
+    }
+
+    async fn list_expired(&self, now: DateTime<Utc>) -> Result<Vec<Meme>, RepoError> {
+        self.find_memes(doc! { "expires_at": { "$lte": now.timestamp() } }, None).await
+    }
+
+    async fn list_due_for_publishing(&self, now: DateTime<Utc>) -> Result<Vec<Meme>, RepoError> {
+        self.find_memes(doc! { "status": MemeStatus::Draft.as_str(), "publish_at": { "$lte": now.timestamp() } }, None).await
+    }
+
+    /// Sets every field but the like and view counts while the stored version matches. When nothing
+    /// matched, reads the document back to tell a lost race from a deleted meme.
+    async fn update(&self, meme: &Meme, expected_version: u64) -> Result<(), RepoError> {
+        let id = meme.meme_id.to_string();
+        // Documents written before versioning have no `version` and read as the initial version
+        let version_filter = if expected_version == INITIAL_VERSION {
+            doc! { "$or": [{ "version": { "$exists": false } }, { "version": expected_version as i64 }] }
+        } else {
+            doc! { "version": expected_version as i64 }
+        };
+        let mut fields = meme_to_document(meme);
+        fields.remove("_id");
+        fields.remove("like_count");
+        fields.remove("view_count");
+        let mut update = doc! { "$set": fields };
+        // Optional attributes cleared on the meme are removed from the document
+        let unset: Document = [
+            "source_url", "expires_at", "ttl", "created_at", "publish_at", "caption_text", "dominant_color", "width", "height",
+            "size_bytes", "content_hash", "updated_at",
+        ]
+            .into_iter()
+            .filter(|field| !update.get_document("$set").is_ok_and(|set| set.contains_key(field)))
+            .map(|field| (field.to_string(), Bson::String(String::new())))
+            .collect();
+        if !unset.is_empty() {
+            update.insert("$unset", unset);
+        }
+        let result = self
+            .collection
+            .update_one(doc! { "$and": [{ "_id": &id }, version_filter] }, update)
+            .await
+            .context(format!("MongoDB (collection: {}): Failed to update meme (id: {})", self.collection.name(), id))
+            .map_err(RepoError::BackendError)?;
+        if result.matched_count > 0 {
+            return Ok(());
+        }
+        let current = self
+            .collection
+            .find_one(doc! { "_id": &id })
+            .await
+            .context(format!("MongoDB (collection: {}): Failed to get meme (id: {})", self.collection.name(), id))
+            .map_err(RepoError::BackendError)?;
+        match current {
+            None => Err(RepoError::NotFound(meme.meme_id)),
+            Some(current) => Err(RepoError::VersionConflict {
+                id: meme.meme_id,
+                expected: expected_version,
+                actual: self.parse_document(&current)?.version,
+            }),
+        }
+    }
+
+    /// Increments `like_count` with `$inc`, so concurrent likes are all counted.
+    async fn add_like(&self, id: Uuid) -> Result<u64, RepoError> {
+        let filter = doc! { "$and": [{ "_id": id.to_string() }, not_expired(Utc::now())] };
+        let updated = self
+            .collection
+            .find_one_and_update(filter, doc! { "$inc": { "like_count": 1_i64 } })
+            .return_document(ReturnDocument::After)
+            .await
+            .context(format!("MongoDB (collection: {}): Failed to like meme (id: {})", self.collection.name(), id))
+            .map_err(RepoError::BackendError)?
+            .ok_or(RepoError::NotFound(id))?;
+        count(&updated, "like_count").ok_or_else(|| {
+            RepoError::DataCorruption {
+                field: "like_count".to_string(),
+                reason: format!("MongoDB (collection: {}): Like of meme {} returned no like count", self.collection.name(), id),
+            }
+        })
+    }
+
+    /// Adds to `view_count` with `$inc`, so views counted by several instances all add up.
+    async fn add_views(&self, id: Uuid, views: u64) -> Result<(), RepoError> {
+        let filter = doc! { "$and": [{ "_id": id.to_string() }, not_expired(Utc::now())] };
+        let result = self
+            .collection
+            .update_one(filter, doc! { "$inc": { "view_count": views as i64 } })
+            .await
+            .context(format!("MongoDB (collection: {}): Failed to count views of meme (id: {})", self.collection.name(), id))
+            .map_err(RepoError::BackendError)?;
+        if result.matched_count == 0 {
+            return Err(RepoError::NotFound(id));
+        }
+        Ok(())
+    }
+
+    async fn delete(&self, id: Uuid) -> Result<(), RepoError> {
+        self.collection
+            .delete_one(doc! { "_id": id.to_string() })
+            .await
+            .context(format!("MongoDB (collection: {}): Failed to delete meme (id: {})", self.collection.name(), id))
+            .map_err(RepoError::BackendError)?;
+        Ok(())
+    }
+
+    /// Reports the estimated document count, data size from `collStats`, and the indexes.
+    /// MongoDB builds indexes before returning, so every listed index is reported active.
+    async fn describe(&self) -> Result<TableInfo, RepoError> {
+        let name = self.collection.name().to_string();
+        let item_count = self
+            .collection
+            .estimated_document_count()
+            .await
+            .context(format!("MongoDB: Failed to count documents of collection '{}'", name))
+            .map_err(RepoError::BackendError)?;
+        let stats = self
+            .database
+            .run_command(doc! { "collStats": &name })
+            .await
+            .context(format!("MongoDB: Failed to read statistics of collection '{}'", name))
+            .map_err(RepoError::BackendError)?;
+        let indexes = self
+            .collection
+            .list_index_names()
+            .await
+            .context(format!("MongoDB: Failed to list indexes of collection '{}'", name))
+            .map_err(RepoError::BackendError)?;
+        Ok(TableInfo {
+            name,
+            status: "ACTIVE".to_string(),
+            item_count: Some(item_count),
+            size_bytes: count(&stats, "size"),
+            indexes: indexes
+                .into_iter()
+                .filter(|index| index != "_id_")
+                .map(|index| (index, "ACTIVE".to_string()))
+                .collect(),
+        })
+    }
+
+    async fn count_created_since(&self, since: DateTime<Utc>) -> Result<u64, RepoError> {
+        let filter = doc! { "created_at": { "$gte": since.to_rfc3339_opts(SecondsFormat::Nanos, true) } };
+        self.collection
+            .count_documents(filter)
+            .await
+            .context(format!("MongoDB: Failed to count recent memes in collection '{}'", self.collection.name()))
+            .map_err(RepoError::BackendError)
+    }
+
+    async fn count(&self) -> Result<u64, RepoError> {
+        self.collection
+            .count_documents(not_expired(Utc::now()))
+            .await
+            .context(format!("MongoDB: Failed to count memes in collection '{}'", self.collection.name()))
+            .map_err(RepoError::BackendError)
+    }
+
+    async fn ping(&self) -> Result<(), RepoError> {
+        self.database
+            .run_command(doc! { "ping": 1 })
+            .await
+            .context(format!("MongoDB: Database '{}' is not reachable", self.database.name()))
+            .map_err(RepoError::BackendError)?;
+        Ok(())
+    }
+}
+
+/// A non-negative whole number, whichever BSON number type the server used.
+fn count(document: &Document, field: &str) -> Option<u64> {
+    match document.get(field)? {
+        Bson::Int32(value) => u64::try_from(*value).ok(),
+        Bson::Int64(value) => u64::try_from(*value).ok(),
+        Bson::Double(value) if *value >= 0.0 => Some(*value as u64),
+        _ => None,
+    }
+}
+
+fn meme_to_document(meme: &Meme) -> Document {
+    let mut document = doc! {
+        "_id": meme.meme_id.to_string(),
+        "title": &meme.title,
+        "description": &meme.description,
+        "image_key": &meme.image_key,
+        "tags": &meme.tags,
+        "palette": &meme.palette,
+        "version": meme.version as i64,
+        "like_count": meme.like_count as i64,
+        "view_count": meme.view_count as i64,
+        "visibility": meme.visibility.as_str(),
+        "status": meme.status.as_str(),
+        // The title is sorted without regard to case
+        "title_key": meme.title.to_lowercase(),
+    };
+    if let Some(created_at) = meme.created_at {
+        // Fixed-width UTC timestamps sort chronologically as strings, as in DynamoDB
+        document.insert("created_at", created_at.to_rfc3339_opts(SecondsFormat::Nanos, true));
+    }
+    if let Some(source_url) = &meme.source_url {
+        document.insert("source_url", source_url);
+    }
+    if let Some(publish_at) = meme.publish_at {
+        document.insert("publish_at", publish_at.timestamp());
+    }
+    if let Some(caption_text) = &meme.caption_text {
+        document.insert("caption_text", caption_text);
+    }
+    if let Some(dominant_color) = &meme.dominant_color {
+        document.insert("dominant_color", dominant_color);
+    }
+    if let Some(width) = meme.width {
+        document.insert("width", i64::from(width));
+    }
+    if let Some(height) = meme.height {
+        document.insert("height", i64::from(height));
+    }
+    if let Some(size_bytes) = meme.size_bytes {
+        document.insert("size_bytes", size_bytes as i64);
+    }
+    if let Some(content_hash) = &meme.content_hash {
+        document.insert("content_hash", content_hash);
+    }
+    if let Some(updated_at) = meme.updated_at {
+        document.insert("updated_at", updated_at.to_rfc3339_opts(SecondsFormat::Nanos, true));
+    }
+    if let Some(expires_at) = meme.expires_at {
+        document.insert("expires_at", expires_at.timestamp());
+        // TTL indexes only act on dates
+        let ttl = bson::DateTime::from_millis((expires_at.timestamp() + TTL_DELAY_SECS) * 1000);
+        document.insert("ttl", ttl);
+    }
+    document
+}
+
+fn document_to_meme(document: &Document) -> Option<Meme> {
+    let tags = match document.get_array("tags") {
+        Ok(tags) => tags.iter().map(|tag| tag.as_str().map(str::to_string)).collect::<Option<Vec<_>>>()?,
+        Err(_) => Vec::new(),
+    };
+    let palette = match document.get_array("palette") {
+        Ok(palette) => palette.iter().map(|color| color.as_str().map(str::to_string)).collect::<Option<Vec<_>>>()?,
+        Err(_) => Vec::new(),
+    };
+    let expires_at = match document.get("expires_at") {
+        Some(value) => Some(DateTime::from_timestamp(value.as_i64()?, 0)?),
+        None => None,
+    };
+    let created_at = match document.get("created_at") {
+        Some(value) => Some(DateTime::parse_from_rfc3339(value.as_str()?).ok()?.with_timezone(&Utc)),
+        None => None,
+    };
+    let updated_at = match document.get("updated_at") {
+        Some(value) => Some(DateTime::parse_from_rfc3339(value.as_str()?).ok()?.with_timezone(&Utc)),
+        None => None,
+    };
+    let publish_at = match document.get("publish_at") {
+        Some(value) => Some(DateTime::from_timestamp(value.as_i64()?, 0)?),
+        None => None,
+    };
+    let status = match document.get("status") {
+        Some(value) => MemeStatus::from_name(value.as_str()?)?,
+        None => MemeStatus::default(),
+    };
+    let visibility = match document.get("visibility") {
+        Some(value) => Visibility::from_name(value.as_str()?)?,
+        None => Visibility::default(),
+    };
+    Some(Meme {
+        meme_id: Uuid::parse_str(document.get_str("_id").ok()?).ok()?,
+        title: document.get_str("title").ok()?.to_string(),
+        description: document.get_str("description").ok()?.to_string(),
+        image_key: document.get_str("image_key").ok()?.to_string(),
+        tags,
+        source_url: document.get_str("source_url").ok().map(str::to_string),
+        expires_at,
+        created_at,
+        version: match document.get("version") {
+            Some(_) => count(document, "version")?,
+            None => INITIAL_VERSION,
+        },
+        like_count: count(document, "like_count").unwrap_or(0),
+        visibility,
+        publish_at,
+        status,
+        view_count: count(document, "view_count").unwrap_or(0),
+        caption_text: document.get_str("caption_text").ok().map(str::to_string),
+        dominant_color: document.get_str("dominant_color").ok().map(str::to_string),
+        palette,
+        width: count(document, "width").and_then(|width| u32::try_from(width).ok()),
+        height: count(document, "height").and_then(|height| u32::try_from(height).ok()),
+        size_bytes: count(document, "size_bytes"),
+        content_hash: document.get_str("content_hash").ok().map(str::to_string),
+        updated_at,
+    })
+}
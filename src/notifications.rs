@@ -0,0 +1,122 @@
+use crate::{aws_clients, config::Config, errors::AppError, models::Meme, AppState};
+use anyhow::Context;
+use aws_config::SdkConfig;
+use aws_sdk_sesv2::{
+    types::{Body, Content, Destination, EmailContent, Message},
+    Client as SesClient,
+};
+use chrono::{DateTime, SecondsFormat, Utc};
+
+/// What an email tells its recipients about a meme.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Notice {
+    /// Released from moderation.
+    Approved,
+    /// Deleted while awaiting approval.
+    Rejected,
+    /// Expires within `APP_EXPIRY_NOTICE_SECS`.
+    Expiring,
+}
+
+impl Notice {
+    /// Label used in logs and the `notification_failures_total` metric.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Notice::Approved => "approved",
+            Notice::Rejected => "rejected",
+            Notice::Expiring => "expiring",
+        }
+    }
+
+    /// Subject and body templates. `{title}`, `{id}` and `{expires_at}` stand for the meme's.
+    fn templates(self) -> (&'static str, &'static str) {
+        match self {
+            Notice::Approved => (
+                "Meme approved: {title}",
+                "The meme \"{title}\" ({id}) was approved and is now shown according to its status and visibility.",
+            ),
+            Notice::Rejected => (
+                "Meme rejected: {title}",
+                "The meme \"{title}\" ({id}) was rejected during moderation and has been deleted.",
+            ),
+            Notice::Expiring => (
+                "Meme expiring: {title}",
+                "The meme \"{title}\" ({id}) expires at {expires_at}. It will then be deleted with its image.",
+            ),
+        }
+    }
+}
+
+/// Emails the configured recipients (`APP_NOTIFICATION_EMAILS`) through Amazon SES.
+pub struct Notifier {
+    ses: SesClient,
+    sender: String,
+    recipients: Vec<String>,
+}
+
+impl Notifier {
+    /// The notifier of `config`, sending through SES with the shared `sdk_config`; `None`
+    /// without recipients.
+    pub fn from_config(config: &Config, sdk_config: &SdkConfig) -> Option<Self> {
+        let sender = config.notification_sender.clone().filter(|_| !config.notification_emails.is_empty())?;
+        Some(Self { ses: aws_clients::create_ses_client(sdk_config), sender, recipients: config.notification_emails.clone() })
+    }
+
+    /// Sends one plain text email about `meme` from the sender, an identity verified in SES,
+    /// to every recipient.
+    pub async fn send(&self, notice: Notice, meme: &Meme) -> anyhow::Result<()> {
+        let (subject, body) = notice.templates();
+        let text = |data: String| Content::builder().data(data).charset("UTF-8").build();
+        let message = Message::builder()
+            .subject(text(render(subject, meme))?)
+            .body(Body::builder().text(text(render(body, meme))?).build())
+            .build();
+        let output = self
+            .ses
+            .send_email()
+            .from_email_address(&self.sender)
+            .destination(Destination::builder().set_to_addresses(Some(self.recipients.clone())).build())
+            .content(EmailContent::builder().simple(message).build())
+            .send()
+            .await
+            .context("SES SendEmail failed")?;
+        let message_id = output.message_id().context("SES SendEmail returned no message ID")?;
+        tracing::debug!(notice = notice.as_str(), meme_id = %meme.meme_id, %message_id, "Notification sent");
+        Ok(())
+    }
+}
+
+/// Emails the recipients about `meme`, if notifications are on. Best effort: the change it
+/// reports is already stored, so a failed email is logged and counted in
+/// `notification_failures_total{notice}` rather than failing the request.
+pub async fn notify(state: &AppState, notice: Notice, meme: &Meme) {
+    let Some(notifier) = &state.notifier else { return };
+    if let Err(e) = notifier.send(notice, meme).await {
+        metrics::counter!("notification_failures_total", "notice" => notice.as_str()).increment(1);
+        tracing::warn!(notice = notice.as_str(), meme_id = %meme.meme_id, error = %e, "Failed to send notification");
+    }
+}
+
+/// Reports the memes that reach `APP_EXPIRY_NOTICE_SECS` before their expiry after `since`
+/// and up to `until`. Run with consecutive periods, each meme is reported once; memes
+/// uploaded to expire sooner than the notice period are not reported at all.
+pub async fn notify_expiring(state: &AppState, since: DateTime<Utc>, until: DateTime<Utc>) -> Result<usize, AppError> {
+    if state.notifier.is_none() || state.config.expiry_notice_secs == 0 {
+        return Ok(0);
+    }
+    let notice = chrono::Duration::seconds(state.config.expiry_notice_secs as i64);
+    let expiring = state.meme_repo.list_expired(until + notice).await?;
+    let mut notified = 0;
+    for meme in expiring.iter().filter(|meme| meme.expires_at.is_some_and(|expires_at| expires_at > since + notice)) {
+        notify(state, Notice::Expiring, meme).await;
+        notified += 1;
+    }
+    Ok(notified)
+}
+
+/// Fills the placeholders of `template` from `meme`. The title goes last, so placeholders
+/// in it are left alone.
+fn render(template: &str, meme: &Meme) -> String {
+    let expires_at = meme.expires_at.map_or_else(|| "never".to_string(), |at| at.to_rfc3339_opts(SecondsFormat::Secs, true));
+    template.replace("{id}", &meme.meme_id.to_string()).replace("{expires_at}", &expires_at).replace("{title}", &meme.title)
+}
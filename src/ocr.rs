@@ -0,0 +1,164 @@
+use crate::{aws_clients, config::Config, errors::AppError, AppState};
+use async_trait::async_trait;
+use aws_sdk_textract::{
+    primitives::Blob,
+    types::{BlockType, Document},
+    Client as TextractClient,
+};
+use serde::Serialize;
+use std::{str::FromStr, sync::Arc, time::Duration};
+
+/// Longest caption kept on a meme, in characters; text beyond it is dropped.
+pub const MAX_CAPTION_CHARS: usize = 2000;
+
+/// Which engine reads the text on uploaded images (`APP_OCR_BACKEND`).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OcrBackend {
+    /// Uploads are stored without a caption.
+    #[default]
+    None,
+    /// Amazon Textract's DetectDocumentText; reads JPEG and PNG images.
+    Textract,
+    /// The `tesseract` command line tool; needs the `tesseract` feature.
+    Tesseract,
+}
+
+impl FromStr for OcrBackend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "none" => Ok(OcrBackend::None),
+            "textract" => Ok(OcrBackend::Textract),
+            "tesseract" => Ok(OcrBackend::Tesseract),
+            other => Err(format!("unknown OCR backend '{}' (expected none, textract or tesseract)", other)),
+        }
+    }
+}
+
+/// Reads the text printed on an image.
+#[async_trait]
+pub trait TextExtractor: Send + Sync + 'static {
+    /// The image's text, one line per line found; empty if there is none. Images the engine
+    /// cannot read are reported as having no text rather than failing.
+    async fn extract_text(&self, image: &[u8], content_type: &str) -> anyhow::Result<String>;
+}
+
+/// Runs DetectDocumentText on the image bytes, so the image needs no S3 location.
+pub struct TextractExtractor {
+    client: TextractClient,
+}
+
+impl TextractExtractor {
+    pub fn new(client: TextractClient) -> Self {
+        Self { client }
+    }
+}
+
+#[async_trait]
+impl TextExtractor for TextractExtractor {
+    async fn extract_text(&self, image: &[u8], content_type: &str) -> anyhow::Result<String> {
+        // The only image formats synchronous Textract calls accept
+        if !matches!(content_type, "image/jpeg" | "image/png") {
+            return Ok(String::new());
+        }
+        let output = self.client
+            .detect_document_text()
+            .document(Document::builder().bytes(Blob::new(image)).build())
+            .send()
+            .await
+            .map_err(|e| anyhow::Error::new(e).context("Textract: Failed to detect text"))?;
+        let lines: Vec<&str> = output
+            .blocks()
+            .iter()
+            .filter(|block| block.block_type() == Some(&BlockType::Line))
+            .filter_map(|block| block.text())
+            .collect();
+        Ok(lines.join("\n"))
+    }
+}
+
+/// Pipes the image through `tesseract stdin stdout`, which reads any format Leptonica does.
+#[cfg(feature = "tesseract")]
+pub struct TesseractExtractor {
+    command: String,
+}
+
+#[cfg(feature = "tesseract")]
+impl TesseractExtractor {
+    pub fn new(command: String) -> Self {
+        Self { command }
+    }
+}
+
+#[cfg(feature = "tesseract")]
+#[async_trait]
+impl TextExtractor for TesseractExtractor {
+    async fn extract_text(&self, image: &[u8], _content_type: &str) -> anyhow::Result<String> {
+        use anyhow::Context;
+        use std::process::Stdio;
+        use tokio::io::AsyncWriteExt;
+
+        let mut child = tokio::process::Command::new(&self.command)
+            .args(["stdin", "stdout"])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .with_context(|| format!("Failed to run '{}'", self.command))?;
+        let mut stdin = child.stdin.take().expect("stdin is piped");
+        let image = image.to_vec();
+        // Written concurrently, so a large image cannot deadlock against a full stdout pipe
+        let writer = tokio::spawn(async move { stdin.write_all(&image).await });
+        let output = child.wait_with_output().await.with_context(|| format!("Failed to run '{}'", self.command))?;
+        // A write error only means tesseract stopped reading; its exit status tells why
+        let _ = writer.await;
+        if !output.status.success() {
+            anyhow::bail!("'{}' failed ({}): {}", self.command, output.status, String::from_utf8_lossy(&output.stderr).trim());
+        }
+        let text = String::from_utf8_lossy(&output.stdout);
+        Ok(text.lines().map(str::trim).filter(|line| !line.is_empty()).collect::<Vec<_>>().join("\n"))
+    }
+}
+
+/// Builds the configured extractor; `None` when OCR is off. Config loading already rejected
+/// `tesseract` in builds without its feature.
+pub async fn build_text_extractor(config: &Config) -> Result<Option<Arc<dyn TextExtractor>>, AppError> {
+    match config.ocr_backend {
+        OcrBackend::None => Ok(None),
+        OcrBackend::Textract => {
+            let sdk_config = aws_clients::create_sdk_config(config).await?;
+            Ok(Some(Arc::new(TextractExtractor::new(aws_clients::create_textract_client(&sdk_config)))))
+        }
+        #[cfg(feature = "tesseract")]
+        OcrBackend::Tesseract => Ok(Some(Arc::new(TesseractExtractor::new(config.tesseract_command.clone())))),
+        #[cfg(not(feature = "tesseract"))]
+        OcrBackend::Tesseract => Err(AppError::InitError("APP_OCR_BACKEND=tesseract needs the `tesseract` feature".to_string())),
+    }
+}
+
+/// The caption to store with a new meme: the text on its image, at most
+/// [`MAX_CAPTION_CHARS`] long, or `None` when OCR is off or finds nothing. The caption is a
+/// search aid, so an engine that fails or takes longer than `APP_OCR_TIMEOUT_SECS` is
+/// logged and the meme is stored without one.
+pub async fn caption_of(state: &AppState, image: &[u8], content_type: &str) -> Option<String> {
+    let extractor = state.text_extractor.as_ref()?;
+    let timeout = Duration::from_secs(state.config.ocr_timeout_secs);
+    let text = match tokio::time::timeout(timeout, extractor.extract_text(image, content_type)).await {
+        Ok(Ok(text)) => text,
+        Ok(Err(e)) => {
+            metrics::counter!("ocr_failures_total").increment(1);
+            tracing::warn!(error = %e, "Failed to read the text on an uploaded image");
+            return None;
+        }
+        Err(_) => {
+            metrics::counter!("ocr_failures_total").increment(1);
+            tracing::warn!(timeout_secs = timeout.as_secs(), "Reading the text on an uploaded image timed out");
+            return None;
+        }
+    };
+    let text = text.trim();
+    (!text.is_empty()).then(|| text.chars().take(MAX_CAPTION_CHARS).collect())
+}
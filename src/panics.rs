@@ -0,0 +1,72 @@
+//! Turns panicking handlers into 500 responses instead of dropped connections. The panic is
+//! logged with its message, the request ID and the stack trace of the panic (when
+//! `RUST_BACKTRACE` is set), and the client gets the usual JSON error with the request ID.
+
+use crate::{auth::REQUEST_ID_HEADER, errors::AppError};
+use axum::{
+    extract::Request,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::{
+    any::Any,
+    backtrace::Backtrace,
+    cell::RefCell,
+    sync::Once,
+};
+use tower_http::catch_panic::CatchPanicLayer;
+
+thread_local! {
+    /// Stack trace of the last panic on this thread, taken by the panic hook. The unwind is
+    /// caught on the thread that panicked, so the handler finds it here.
+    static PANIC_BACKTRACE: RefCell<Option<Backtrace>> = const { RefCell::new(None) };
+}
+
+tokio::task_local! {
+    /// ID of the request being handled, for the panic handler, which only gets the payload.
+    static REQUEST_ID: Option<String>;
+}
+
+/// Chains a panic hook keeping the panic's stack trace for [`catch_panics`]. The hook is
+/// process-global, so only the first call installs it.
+pub fn install_hook() {
+    static INSTALL: Once = Once::new();
+    INSTALL.call_once(|| {
+        let previous = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            // Only captured when RUST_BACKTRACE or RUST_LIB_BACKTRACE asks for it
+            PANIC_BACKTRACE.with(|backtrace| *backtrace.borrow_mut() = Some(Backtrace::capture()));
+            previous(info);
+        }));
+    });
+}
+
+/// Layer answering panics in the services it wraps with a 500. Must sit inside
+/// [`remember_request_id`] for responses and logs to carry the request ID.
+pub fn catch_panics() -> CatchPanicLayer<fn(Box<dyn Any + Send + 'static>) -> Response> {
+    CatchPanicLayer::custom(respond_to_panic as fn(Box<dyn Any + Send + 'static>) -> Response)
+}
+
+/// Middleware making the request ID (set by `SetRequestIdLayer`) available to the panic
+/// handler of [`catch_panics`].
+pub async fn remember_request_id(request: Request, next: Next) -> Response {
+    let request_id = request.headers().get(REQUEST_ID_HEADER).and_then(|value| value.to_str().ok()).map(str::to_string);
+    REQUEST_ID.scope(request_id, next.run(request)).await
+}
+
+fn respond_to_panic(payload: Box<dyn Any + Send + 'static>) -> Response {
+    let message = payload
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("non-string panic payload");
+    let request_id = REQUEST_ID.try_with(Clone::clone).ok().flatten();
+    let backtrace = PANIC_BACKTRACE.with(|backtrace| backtrace.borrow_mut().take());
+    tracing::error!(
+        request_id = request_id.as_deref(),
+        panic.message = %message,
+        panic.backtrace = %backtrace.map_or_else(|| "unavailable".to_string(), |backtrace| backtrace.to_string()),
+        "Handler panicked"
+    );
+    AppError::Panicked { request_id }.into_response()
+}
@@ -0,0 +1,180 @@
+//! Progress of uploads proxied through the server. A client names its upload with an
+//! `X-Upload-Id` header and follows it at `GET /uploads/{upload_id}/progress`, a stream of
+//! server-sent events. Progress is only known to the instance receiving the upload.
+
+use crate::{errors::AppError, AppState};
+use axum::{
+    body::Body,
+    extract::{Path, Request, State},
+    http::{header, StatusCode},
+    middleware::Next,
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        Response,
+    },
+};
+use futures::{stream, Stream, TryStreamExt};
+use serde::Serialize;
+use std::{
+    collections::HashMap,
+    convert::Infallible,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tokio::sync::watch;
+
+/// Request header naming an upload whose progress is published.
+pub const UPLOAD_ID_HEADER: &str = "x-upload-id";
+
+const MAX_UPLOAD_ID_LENGTH: usize = 64;
+
+/// A progress stream without news for this long ends; `EventSource` clients reconnect.
+const IDLE_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// How far an upload got, as sent in each event.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct UploadProgress {
+    /// Request body bytes received so far.
+    pub received: u64,
+    /// The request's `Content-Length`; `None` for chunked requests or before the upload started.
+    pub total: Option<u64>,
+    /// Status of the upload's response, once it was handled.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<u16>,
+}
+
+struct Channel {
+    sender: watch::Sender<UploadProgress>,
+    started: bool,
+}
+
+/// Progress of the uploads in flight, by upload ID. Subscribers may arrive before their
+/// upload starts; channels nobody uploads to or listens on are dropped.
+#[derive(Default)]
+pub struct ProgressRegistry {
+    channels: Mutex<HashMap<String, Channel>>,
+}
+
+impl ProgressRegistry {
+    /// Follows the upload `id`, started or not.
+    pub fn subscribe(&self, id: &str) -> watch::Receiver<UploadProgress> {
+        let mut channels = self.channels.lock().expect("progress registry lock poisoned");
+        Self::prune(&mut channels);
+        channels
+            .entry(id.to_string())
+            .or_insert_with(|| Channel { sender: watch::Sender::new(UploadProgress::default()), started: false })
+            .sender
+            .subscribe()
+    }
+
+    /// Marks the upload `id` as started, `None` if an upload with this ID already is.
+    fn start(&self, id: &str, total: Option<u64>) -> Option<watch::Sender<UploadProgress>> {
+        let mut channels = self.channels.lock().expect("progress registry lock poisoned");
+        Self::prune(&mut channels);
+        let channel = channels
+            .entry(id.to_string())
+            .or_insert_with(|| Channel { sender: watch::Sender::new(UploadProgress::default()), started: false });
+        if channel.started {
+            return None;
+        }
+        channel.started = true;
+        channel.sender.send_replace(UploadProgress { received: 0, total, status: None });
+        Some(channel.sender.clone())
+    }
+
+    /// Publishes the upload's outcome (none if it was cancelled) and forgets it. Subscribers
+    /// still get the last update before their stream ends.
+    fn finish(&self, id: &str, status: Option<StatusCode>) {
+        let channel = self.channels.lock().expect("progress registry lock poisoned").remove(id);
+        if let (Some(channel), Some(status)) = (channel, status) {
+            channel.sender.send_modify(|progress| progress.status = Some(status.as_u16()));
+        }
+    }
+
+    fn prune(channels: &mut HashMap<String, Channel>) {
+        channels.retain(|_, channel| channel.started || channel.sender.receiver_count() > 0);
+    }
+}
+
+/// Forgets a tracked upload when its request ends, including when the handler is cancelled
+/// (e.g. the client disconnected).
+struct Tracking {
+    state: Arc<AppState>,
+    id: String,
+    status: Option<StatusCode>,
+}
+
+impl Drop for Tracking {
+    fn drop(&mut self) {
+        self.state.upload_progress.finish(&self.id, self.status);
+    }
+}
+
+/// Middleware for the upload routes: with an `X-Upload-Id` header, counts the request body
+/// bytes as the handler reads them and publishes them to the upload's subscribers. Placed
+/// outside the upload timeout, so timed-out uploads are reported too.
+pub async fn track_upload(State(state): State<Arc<AppState>>, request: Request, next: Next) -> Result<Response, AppError> {
+    let Some(id) = request.headers().get(UPLOAD_ID_HEADER) else {
+        return Ok(next.run(request).await);
+    };
+    let id = parse_upload_id(id.to_str().unwrap_or_default())?;
+    let total = request
+        .headers()
+        .get(header::CONTENT_LENGTH)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse().ok());
+    let sender = state
+        .upload_progress
+        .start(&id, total)
+        .ok_or_else(|| AppError::Conflict(format!("Upload '{}' is already in progress", id)))?;
+    let mut tracking = Tracking { state: state.clone(), id, status: None };
+
+    let request = request.map(|body| {
+        Body::from_stream(body.into_data_stream().inspect_ok(move |chunk| {
+            sender.send_modify(|progress| progress.received += chunk.len() as u64);
+        }))
+    });
+    let response = next.run(request).await;
+    tracking.status = Some(response.status());
+    Ok(response)
+}
+
+/// Handler for GET /uploads/{upload_id}/progress: a `progress` event with the bytes received
+/// so far right away and whenever more arrive, then `complete` (2xx response) or `failed`
+/// (error or cancelled upload), after which the stream ends. Clients should close their
+/// `EventSource` on the last event; it would otherwise reconnect and wait for a new upload.
+pub async fn stream_progress(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, AppError> {
+    let id = parse_upload_id(&id)?;
+    let mut receiver = state.upload_progress.subscribe(&id);
+    receiver.mark_changed(); // Send the current state first
+
+    let events = stream::unfold(Some(receiver), |receiver| async move {
+        let mut receiver = receiver?;
+        let changed = tokio::time::timeout(IDLE_TIMEOUT, receiver.changed()).await.ok()?;
+        let progress = receiver.borrow_and_update().clone();
+        let (name, is_last) = match (changed, progress.status) {
+            (Ok(()), None) => ("progress", false),
+            (_, Some(status)) if (200..300).contains(&status) => ("complete", true),
+            _ => ("failed", true), // Error response, or the upload was cancelled
+        };
+        let event = Event::default().event(name).json_data(&progress).unwrap_or_default();
+        Some((Ok(event), (!is_last).then_some(receiver)))
+    });
+    Ok(Sse::new(events).keep_alive(KeepAlive::default()))
+}
+
+fn parse_upload_id(id: &str) -> Result<String, AppError> {
+    let is_valid = !id.is_empty()
+        && id.len() <= MAX_UPLOAD_ID_LENGTH
+        && id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if !is_valid {
+        return Err(AppError::InvalidInput(format!(
+            "Upload IDs are 1 to {} letters, digits, '-' or '_'",
+            MAX_UPLOAD_ID_LENGTH
+        )));
+    }
+    Ok(id.to_string())
+}
@@ -0,0 +1,42 @@
+use crate::{errors::AppError, services::{self, Caller}, AppState};
+use std::{sync::Arc, time::Duration};
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+
+/// Publishes every scheduled draft whose publish time has passed and returns how many were
+/// published. Such drafts are already shown from their publish time on; publishing them
+/// stores the change as a new version, so it is audited and reaches the change stream (and
+/// webhooks).
+pub async fn publish_due(state: &AppState) -> Result<usize, AppError> {
+    let due = state.meme_repo.list_due_for_publishing(chrono::Utc::now()).await?;
+    let mut published = 0;
+    for meme in due {
+        let meme_id = meme.meme_id;
+        match services::publish_meme(state, meme, &Caller::scheduler()).await {
+            Ok(_) => published += 1,
+            // Edited or deleted since it was listed; an edited draft is found again next time
+            Err(AppError::PreconditionFailed(_) | AppError::MemeNotFound(_)) => {}
+            Err(e) => tracing::warn!(%meme_id, error = %e, "Failed to publish scheduled meme"),
+        }
+    }
+    Ok(published)
+}
+
+/// Spawns a task that publishes due drafts every `interval`. Failures are logged and retried
+/// on the next tick. The task exits once `shutdown` is cancelled.
+#[cfg_attr(feature = "lambda", allow(dead_code))] // Not scheduled on Lambda
+pub fn spawn_publishing(state: Arc<AppState>, interval: Duration, shutdown: CancellationToken) -> JoinHandle<()> {
+    tracing::info!(interval_secs = interval.as_secs(), "Scheduling publication of scheduled memes");
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            tokio::select! {
+                _ = ticker.tick() => {}
+                _ = shutdown.cancelled() => break,
+            }
+            if let Err(e) = publish_due(&state).await {
+                tracing::error!(error = %e, "Publishing scheduled memes failed");
+            }
+        }
+    })
+}
@@ -0,0 +1,563 @@
+//! Record/replay of backend calls for offline tests (only with the `testing` feature).
+//!
+//! [`RecordingMemeRepository`] and [`RecordingFileStorage`] wrap the real repository and
+//! storage of a LocalStack run and write every call with its outcome to a [`Recorder`],
+//! which saves them as a JSON fixture (a "cassette"). [`ReplayMemeRepository`] and
+//! [`ReplayFileStorage`] implement the same traits from a [`Replayer`] holding that fixture,
+//! so the same test runs later without Docker, fast and deterministically.
+//!
+//! Calls replay in the recorded order per backend, and each must be the recorded operation.
+//! Meme IDs are random in every run: a UUID in a replayed request that differs from the
+//! recorded one at the same position is remembered, and the recorded UUID is swapped for
+//! the live one in every response after that.
+
+use crate::{
+    domain::{ConsistencyLevel, FileStorage, MemeRepository, ObjectMetadata, StoredObject, TableInfo, UploadOptions},
+    errors::{RepoError, StorageError},
+    models::{Meme, SortOrder},
+};
+use anyhow::{anyhow, Context};
+use async_trait::async_trait;
+use aws_sdk_s3::primitives::ByteStream;
+use axum::body::Bytes;
+use base64::{prelude::BASE64_STANDARD, Engine};
+use chrono::{DateTime, Utc};
+use regex::Regex;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::{
+    collections::{HashMap, VecDeque},
+    path::Path,
+    sync::{Arc, LazyLock, Mutex},
+    time::Duration,
+};
+use uuid::Uuid;
+
+/// The backend names in a cassette.
+const DYNAMODB: &str = "dynamodb";
+const S3: &str = "s3";
+
+static UUID_PATTERN: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new("[0-9a-f]{8}-[0-9a-f]{4}-[0-9a-f]{4}-[0-9a-f]{4}-[0-9a-f]{12}").expect("valid UUID pattern"));
+
+/// A recorded fixture: every call in the order it was made.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Cassette {
+    pub interactions: Vec<Interaction>,
+}
+
+/// One call to a backend and how it ended.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Interaction {
+    pub backend: String,
+    pub operation: String,
+    pub request: Value,
+    pub outcome: Outcome,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Outcome {
+    Ok(Value),
+    Err(RecordedError),
+}
+
+impl Outcome {
+    fn of<T: Serialize, E>(result: &Result<T, E>) -> Self
+    where
+        for<'a> &'a E: Into<RecordedError>,
+    {
+        match result {
+            Ok(value) => Outcome::Ok(serde_json::to_value(value).expect("recorded responses serialize")),
+            Err(e) => Outcome::Err(e.into()),
+        }
+    }
+}
+
+/// A repository or storage error as recorded. Backend errors keep only their message.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum RecordedError {
+    NotFound { key: String },
+    VersionConflict { id: Uuid, expected: u64, actual: u64 },
+    AlreadyExists { id: Uuid },
+    DataCorruption { field: String, reason: String },
+    UploadFailed { message: String },
+    Backend { message: String },
+    Unavailable { breaker: String },
+}
+
+impl From<&RepoError> for RecordedError {
+    fn from(e: &RepoError) -> Self {
+        match e {
+            RepoError::NotFound(id) => RecordedError::NotFound { key: id.to_string() },
+            RepoError::VersionConflict { id, expected, actual } => {
+                RecordedError::VersionConflict { id: *id, expected: *expected, actual: *actual }
+            }
+            RepoError::AlreadyExists(id) => RecordedError::AlreadyExists { id: *id },
+            RepoError::DataCorruption { field, reason } => {
+                RecordedError::DataCorruption { field: field.clone(), reason: reason.clone() }
+            }
+            RepoError::BackendError(e) => RecordedError::Backend { message: format!("{:#}", e) },
+            RepoError::Unavailable(breaker) => RecordedError::Unavailable { breaker: breaker.to_string() },
+        }
+    }
+}
+
+impl From<&StorageError> for RecordedError {
+    fn from(e: &StorageError) -> Self {
+        match e {
+            StorageError::UploadFailed(message) => RecordedError::UploadFailed { message: message.clone() },
+            StorageError::NotFound(key) => RecordedError::NotFound { key: key.clone() },
+            StorageError::BackendError(e) => RecordedError::Backend { message: format!("{:#}", e) },
+            StorageError::Unavailable(breaker) => RecordedError::Unavailable { breaker: breaker.to_string() },
+        }
+    }
+}
+
+impl From<RecordedError> for RepoError {
+    fn from(e: RecordedError) -> Self {
+        match e {
+            RecordedError::NotFound { key } => match key.parse() {
+                Ok(id) => RepoError::NotFound(id),
+                Err(_) => RepoError::BackendError(anyhow!("Recorded not found error for '{}', which is not a meme ID", key)),
+            },
+            RecordedError::VersionConflict { id, expected, actual } => RepoError::VersionConflict { id, expected, actual },
+            RecordedError::AlreadyExists { id } => RepoError::AlreadyExists(id),
+            RecordedError::DataCorruption { field, reason } => RepoError::DataCorruption { field, reason },
+            RecordedError::UploadFailed { message } | RecordedError::Backend { message } => RepoError::BackendError(anyhow!(message)),
+            RecordedError::Unavailable { breaker } => RepoError::Unavailable(breaker_name(&breaker)),
+        }
+    }
+}
+
+impl From<RecordedError> for StorageError {
+    fn from(e: RecordedError) -> Self {
+        match e {
+            RecordedError::NotFound { key } => StorageError::NotFound(key),
+            RecordedError::UploadFailed { message } => StorageError::UploadFailed(message),
+            RecordedError::Unavailable { breaker } => StorageError::Unavailable(breaker_name(&breaker)),
+            other => StorageError::BackendError(anyhow!("{:?}", other)),
+        }
+    }
+}
+
+/// The `&'static str` breaker names of the app, since errors carry those.
+fn breaker_name(name: &str) -> &'static str {
+    match name {
+        "dynamodb" => "dynamodb",
+        "dynamodb_secondary" => "dynamodb_secondary",
+        "s3" => "s3",
+        _ => "replay",
+    }
+}
+
+/// Collects the calls of recording decorators, for [`Recorder::save`].
+#[derive(Default)]
+pub struct Recorder {
+    interactions: Mutex<Vec<Interaction>>,
+}
+
+impl Recorder {
+    fn record(&self, backend: &str, operation: &str, request: Value, outcome: Outcome) {
+        self.interactions.lock().expect("recorder lock poisoned").push(Interaction {
+            backend: backend.to_string(),
+            operation: operation.to_string(),
+            request,
+            outcome,
+        });
+    }
+
+    /// Writes the calls recorded so far to `path` as pretty-printed JSON.
+    pub fn save(&self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        let path = path.as_ref();
+        let cassette = Cassette { interactions: self.interactions.lock().expect("recorder lock poisoned").clone() };
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).with_context(|| format!("Failed to create {}", parent.display()))?;
+        }
+        let json = serde_json::to_string_pretty(&cassette)?;
+        std::fs::write(path, json + "\n").with_context(|| format!("Failed to write cassette {}", path.display()))
+    }
+}
+
+/// Serves the calls of a cassette to replay decorators, in order per backend.
+pub struct Replayer {
+    state: Mutex<ReplayState>,
+}
+
+#[derive(Default)]
+struct ReplayState {
+    queues: HashMap<String, VecDeque<Interaction>>,
+    /// Recorded UUIDs and the live ones that took their place.
+    ids: HashMap<String, String>,
+    mismatches: Vec<String>,
+}
+
+impl Replayer {
+    pub fn new(cassette: Cassette) -> Self {
+        let mut state = ReplayState::default();
+        for interaction in cassette.interactions {
+            state.queues.entry(interaction.backend.clone()).or_default().push_back(interaction);
+        }
+        Self { state: Mutex::new(state) }
+    }
+
+    /// Loads the cassette at `path`.
+    pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let json = std::fs::read_to_string(path).with_context(|| format!("Failed to read cassette {}", path.display()))?;
+        let cassette = serde_json::from_str(&json).with_context(|| format!("Invalid cassette {}", path.display()))?;
+        Ok(Self::new(cassette))
+    }
+
+    /// The recorded outcome of the next call to `backend`, which must be `operation`.
+    fn replay<T: DeserializeOwned>(&self, backend: &str, operation: &str, request: Value) -> Result<T, RecordedError> {
+        let mut state = self.state.lock().expect("replayer lock poisoned");
+        let Some(interaction) = state.queues.get_mut(backend).and_then(VecDeque::pop_front) else {
+            return Err(state.mismatch(format!("{} {} was called after the recorded {} calls ran out", backend, operation, backend)));
+        };
+        if interaction.operation != operation {
+            return Err(state.mismatch(format!(
+                "{} {} was called where {} was recorded",
+                backend, operation, interaction.operation
+            )));
+        }
+        state.learn_ids(&interaction.request, &request);
+        let outcome = state.substitute_ids(&interaction.outcome);
+        match outcome {
+            Outcome::Ok(value) => serde_json::from_value(value).map_err(|e| {
+                state.mismatch(format!("Recorded response of {} {} does not parse: {}", backend, operation, e))
+            }),
+            Outcome::Err(e) => Err(e),
+        }
+    }
+
+    /// Panics when a call did not match the cassette or recorded calls were left unused.
+    pub fn assert_finished(&self) {
+        let state = self.state.lock().expect("replayer lock poisoned");
+        let mut problems = state.mismatches.clone();
+        for (backend, queue) in &state.queues {
+            if let Some(next) = queue.front() {
+                problems.push(format!("{} recorded {} calls that were not made, starting with {}", backend, queue.len(), next.operation));
+            }
+        }
+        assert!(problems.is_empty(), "Replay diverged from the cassette:\n{}", problems.join("\n"));
+    }
+}
+
+impl ReplayState {
+    fn mismatch(&mut self, message: String) -> RecordedError {
+        self.mismatches.push(message.clone());
+        RecordedError::Backend { message }
+    }
+
+    /// Pairs the UUIDs of the recorded and the live request by position.
+    fn learn_ids(&mut self, recorded: &Value, live: &Value) {
+        let (recorded, live) = (recorded.to_string(), live.to_string());
+        for (recorded, live) in UUID_PATTERN.find_iter(&recorded).zip(UUID_PATTERN.find_iter(&live)) {
+            if recorded.as_str() != live.as_str() {
+                self.ids.entry(recorded.as_str().to_string()).or_insert_with(|| live.as_str().to_string());
+            }
+        }
+    }
+
+    fn substitute_ids(&self, outcome: &Outcome) -> Outcome {
+        let json = serde_json::to_string(outcome).expect("outcomes serialize");
+        let json = UUID_PATTERN.replace_all(&json, |found: &regex::Captures| {
+            self.ids.get(&found[0]).cloned().unwrap_or_else(|| found[0].to_string())
+        });
+        serde_json::from_str(&json).expect("UUID substitution keeps the JSON valid")
+    }
+}
+
+/// Records every call of the inner repository to a [`Recorder`].
+pub struct RecordingMemeRepository {
+    inner: Arc<dyn MemeRepository>,
+    recorder: Arc<Recorder>,
+}
+
+impl RecordingMemeRepository {
+    pub fn new(inner: Arc<dyn MemeRepository>, recorder: Arc<Recorder>) -> Self {
+        Self { inner, recorder }
+    }
+
+    fn record<R: Serialize>(&self, operation: &str, request: Value, result: Result<R, RepoError>) -> Result<R, RepoError> {
+        self.recorder.record(DYNAMODB, operation, request, Outcome::of(&result));
+        result
+    }
+}
+
+#[async_trait]
+impl MemeRepository for RecordingMemeRepository {
+    async fn create(&self, meme: &Meme) -> Result<(), RepoError> {
+        self.record("create", json!({ "meme": meme }), self.inner.create(meme).await)
+    }
+
+    async fn get_by_id(&self, id: Uuid, consistency: ConsistencyLevel) -> Result<Option<Meme>, RepoError> {
+        let request = json!({ "id": id, "consistency": format!("{:?}", consistency) });
+        self.record("get_by_id", request, self.inner.get_by_id(id, consistency).await)
+    }
+
+    async fn exists(&self, id: Uuid) -> Result<bool, RepoError> {
+        self.record("exists", json!({ "id": id }), self.inner.exists(id).await)
+    }
+
+    async fn list_all(&self) -> Result<Vec<Meme>, RepoError> {
+        self.record("list_all", Value::Null, self.inner.list_all().await)
+    }
+
+    async fn list_sorted(&self, order: SortOrder) -> Result<Vec<Meme>, RepoError> {
+        self.record("list_sorted", json!({ "order": format!("{:?}", order) }), self.inner.list_sorted(order).await)
+    }
+
+    async fn list_expired(&self, now: DateTime<Utc>) -> Result<Vec<Meme>, RepoError> {
+        self.record("list_expired", Value::Null, self.inner.list_expired(now).await)
+    }
+
+    async fn list_due_for_publishing(&self, now: DateTime<Utc>) -> Result<Vec<Meme>, RepoError> {
+        self.record("list_due_for_publishing", Value::Null, self.inner.list_due_for_publishing(now).await)
+    }
+
+    async fn update(&self, meme: &Meme, expected_version: u64) -> Result<(), RepoError> {
+        let request = json!({ "meme": meme, "expected_version": expected_version });
+        self.record("update", request, self.inner.update(meme, expected_version).await)
+    }
+
+    async fn add_like(&self, id: Uuid) -> Result<u64, RepoError> {
+        self.record("add_like", json!({ "id": id }), self.inner.add_like(id).await)
+    }
+
+    async fn add_views(&self, id: Uuid, views: u64) -> Result<(), RepoError> {
+        self.record("add_views", json!({ "id": id, "views": views }), self.inner.add_views(id, views).await)
+    }
+
+    async fn delete(&self, id: Uuid) -> Result<(), RepoError> {
+        self.record("delete", json!({ "id": id }), self.inner.delete(id).await)
+    }
+
+    async fn describe(&self) -> Result<TableInfo, RepoError> {
+        self.record("describe", Value::Null, self.inner.describe().await)
+    }
+
+    async fn count_created_since(&self, since: DateTime<Utc>) -> Result<u64, RepoError> {
+        self.record("count_created_since", Value::Null, self.inner.count_created_since(since).await)
+    }
+
+    async fn count(&self) -> Result<u64, RepoError> {
+        self.record("count", Value::Null, self.inner.count().await)
+    }
+
+    async fn ping(&self) -> Result<(), RepoError> {
+        self.record("ping", Value::Null, self.inner.ping().await)
+    }
+}
+
+/// Records every call of the inner storage to a [`Recorder`]. Downloaded files are kept
+/// base64-encoded; uploaded ones only by size.
+pub struct RecordingFileStorage {
+    inner: Arc<dyn FileStorage>,
+    recorder: Arc<Recorder>,
+}
+
+impl RecordingFileStorage {
+    pub fn new(inner: Arc<dyn FileStorage>, recorder: Arc<Recorder>) -> Self {
+        Self { inner, recorder }
+    }
+
+    fn record<R: Serialize>(&self, operation: &str, request: Value, result: Result<R, StorageError>) -> Result<R, StorageError> {
+        self.recorder.record(S3, operation, request, Outcome::of(&result));
+        result
+    }
+}
+
+/// A download as recorded.
+#[derive(Serialize, Deserialize)]
+struct RecordedDownload {
+    body_base64: String,
+    metadata: ObjectMetadata,
+}
+
+#[async_trait]
+impl FileStorage for RecordingFileStorage {
+    async fn upload(&self, key: &str, data: Bytes, options: UploadOptions) -> Result<(), StorageError> {
+        let request = json!({ "key": key, "size": data.len(), "content_type": options.content_type });
+        self.record("upload", request, self.inner.upload(key, data, options).await)
+    }
+
+    async fn download(&self, key: &str) -> Result<(ByteStream, ObjectMetadata), StorageError> {
+        let request = json!({ "key": key });
+        let downloaded = match self.inner.download(key).await {
+            Ok((body, metadata)) => match body.collect().await {
+                Ok(body) => Ok((body.into_bytes(), metadata)),
+                Err(e) => Err(StorageError::BackendError(anyhow!(e).context("Failed to read the recorded download"))),
+            },
+            Err(e) => Err(e),
+        };
+        let outcome = match &downloaded {
+            Ok((body, metadata)) => {
+                let download = RecordedDownload { body_base64: BASE64_STANDARD.encode(body), metadata: metadata.clone() };
+                Outcome::Ok(serde_json::to_value(download).expect("recorded responses serialize"))
+            }
+            Err(e) => Outcome::Err(e.into()),
+        };
+        self.recorder.record(S3, "download", request, outcome);
+        downloaded.map(|(body, metadata)| (ByteStream::from(body), metadata))
+    }
+
+    async fn head(&self, key: &str) -> Result<ObjectMetadata, StorageError> {
+        self.record("head", json!({ "key": key }), self.inner.head(key).await)
+    }
+
+    async fn list(&self) -> Result<Vec<StoredObject>, StorageError> {
+        self.record("list", Value::Null, self.inner.list().await)
+    }
+
+    async fn presigned_url(&self, key: &str, expires_in: Duration) -> Result<String, StorageError> {
+        let request = json!({ "key": key, "expires_in_secs": expires_in.as_secs() });
+        self.record("presigned_url", request, self.inner.presigned_url(key, expires_in).await)
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), StorageError> {
+        self.record("delete", json!({ "key": key }), self.inner.delete(key).await)
+    }
+
+    async fn copy(&self, from: &str, to: &str) -> Result<(), StorageError> {
+        self.record("copy", json!({ "from": from, "to": to }), self.inner.copy(from, to).await)
+    }
+
+    async fn ping(&self) -> Result<(), StorageError> {
+        self.record("ping", Value::Null, self.inner.ping().await)
+    }
+}
+
+/// Serves the DynamoDB calls of a cassette.
+pub struct ReplayMemeRepository {
+    replayer: Arc<Replayer>,
+}
+
+impl ReplayMemeRepository {
+    pub fn new(replayer: Arc<Replayer>) -> Self {
+        Self { replayer }
+    }
+
+    fn replay<R: DeserializeOwned>(&self, operation: &str, request: Value) -> Result<R, RepoError> {
+        self.replayer.replay(DYNAMODB, operation, request).map_err(RepoError::from)
+    }
+}
+
+#[async_trait]
+impl MemeRepository for ReplayMemeRepository {
+    async fn create(&self, meme: &Meme) -> Result<(), RepoError> {
+        self.replay("create", json!({ "meme": meme }))
+    }
+
+    async fn get_by_id(&self, id: Uuid, consistency: ConsistencyLevel) -> Result<Option<Meme>, RepoError> {
+        self.replay("get_by_id", json!({ "id": id, "consistency": format!("{:?}", consistency) }))
+    }
+
+    async fn exists(&self, id: Uuid) -> Result<bool, RepoError> {
+        self.replay("exists", json!({ "id": id }))
+    }
+
+    async fn list_all(&self) -> Result<Vec<Meme>, RepoError> {
+        self.replay("list_all", Value::Null)
+    }
+
+    async fn list_sorted(&self, order: SortOrder) -> Result<Vec<Meme>, RepoError> {
+        self.replay("list_sorted", json!({ "order": format!("{:?}", order) }))
+    }
+
+    async fn list_expired(&self, _now: DateTime<Utc>) -> Result<Vec<Meme>, RepoError> {
+        self.replay("list_expired", Value::Null)
+    }
+
+    async fn list_due_for_publishing(&self, _now: DateTime<Utc>) -> Result<Vec<Meme>, RepoError> {
+        self.replay("list_due_for_publishing", Value::Null)
+    }
+
+    async fn update(&self, meme: &Meme, expected_version: u64) -> Result<(), RepoError> {
+        self.replay("update", json!({ "meme": meme, "expected_version": expected_version }))
+    }
+
+    async fn add_like(&self, id: Uuid) -> Result<u64, RepoError> {
+        self.replay("add_like", json!({ "id": id }))
+    }
+
+    async fn add_views(&self, id: Uuid, views: u64) -> Result<(), RepoError> {
+        self.replay("add_views", json!({ "id": id, "views": views }))
+    }
+
+    async fn delete(&self, id: Uuid) -> Result<(), RepoError> {
+        self.replay("delete", json!({ "id": id }))
+    }
+
+    async fn describe(&self) -> Result<TableInfo, RepoError> {
+        self.replay("describe", Value::Null)
+    }
+
+    async fn count_created_since(&self, _since: DateTime<Utc>) -> Result<u64, RepoError> {
+        self.replay("count_created_since", Value::Null)
+    }
+
+    async fn count(&self) -> Result<u64, RepoError> {
+        self.replay("count", Value::Null)
+    }
+
+    async fn ping(&self) -> Result<(), RepoError> {
+        self.replay("ping", Value::Null)
+    }
+}
+
+/// Serves the S3 calls of a cassette.
+pub struct ReplayFileStorage {
+    replayer: Arc<Replayer>,
+}
+
+impl ReplayFileStorage {
+    pub fn new(replayer: Arc<Replayer>) -> Self {
+        Self { replayer }
+    }
+
+    fn replay<R: DeserializeOwned>(&self, operation: &str, request: Value) -> Result<R, StorageError> {
+        self.replayer.replay(S3, operation, request).map_err(StorageError::from)
+    }
+}
+
+#[async_trait]
+impl FileStorage for ReplayFileStorage {
+    async fn upload(&self, key: &str, data: Bytes, options: UploadOptions) -> Result<(), StorageError> {
+        self.replay("upload", json!({ "key": key, "size": data.len(), "content_type": options.content_type }))
+    }
+
+    async fn download(&self, key: &str) -> Result<(ByteStream, ObjectMetadata), StorageError> {
+        let recorded: RecordedDownload = self.replay("download", json!({ "key": key }))?;
+        let body = BASE64_STANDARD
+            .decode(&recorded.body_base64)
+            .map_err(|e| StorageError::BackendError(anyhow!(e).context("Recorded download is not base64")))?;
+        Ok((ByteStream::from(body), recorded.metadata))
+    }
+
+    async fn head(&self, key: &str) -> Result<ObjectMetadata, StorageError> {
+        self.replay("head", json!({ "key": key }))
+    }
+
+    async fn list(&self) -> Result<Vec<StoredObject>, StorageError> {
+        self.replay("list", Value::Null)
+    }
+
+    async fn presigned_url(&self, key: &str, expires_in: Duration) -> Result<String, StorageError> {
+        self.replay("presigned_url", json!({ "key": key, "expires_in_secs": expires_in.as_secs() }))
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), StorageError> {
+        self.replay("delete", json!({ "key": key }))
+    }
+
+    async fn copy(&self, from: &str, to: &str) -> Result<(), StorageError> {
+        self.replay("copy", json!({ "from": from, "to": to }))
+    }
+
+    async fn ping(&self) -> Result<(), StorageError> {
+        self.replay("ping", Value::Null)
+    }
+}
@@ -0,0 +1,147 @@
+use crate::{
+    aws_clients,
+    config::{file_key, ConfigError, ConfigSource},
+};
+use std::{
+    collections::HashMap,
+    str::FromStr,
+    sync::{LazyLock, Mutex},
+    time::{Duration, Instant},
+};
+
+/// Where settings are loaded from, selected by `APP_CONFIG_SOURCE`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ConfigSourceKind {
+    /// Environment variables and the optional config file only.
+    #[default]
+    Env,
+    /// Additionally read SSM Parameter Store (and Secrets Manager, if configured).
+    Ssm,
+}
+
+impl FromStr for ConfigSourceKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "env" => Ok(Self::Env),
+            "ssm" => Ok(Self::Ssm),
+            other => Err(format!("unknown config source '{}', expected 'env' or 'ssm'", other)),
+        }
+    }
+}
+
+/// Default parameter path searched when `APP_SSM_PREFIX` is unset.
+const DEFAULT_SSM_PREFIX: &str = "/axum-meme/";
+/// How long fetched values are reused before asking AWS again.
+const DEFAULT_CACHE_TTL_SECS: u64 = 300;
+
+/// Settings fetched at one point in time.
+type CachedValues = (Instant, HashMap<String, String>);
+
+/// Fetched values keyed by `{prefix}|{secret id}`, so reloading configuration in the same
+/// process does not hit SSM/Secrets Manager again until the entry expires.
+static CACHE: LazyLock<Mutex<HashMap<String, CachedValues>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Loads settings from SSM Parameter Store and, when `APP_SECRETS_ID` is set, from a JSON
+/// secret in Secrets Manager. Returns values keyed like config file keys.
+///
+/// Parameters are read recursively under `APP_SSM_PREFIX` (default `/axum-meme/`), and the
+/// path below the prefix becomes the key: `/axum-meme/s3_bucket_name` sets
+/// `APP_S3_BUCKET_NAME`, `/axum-meme/content_filter/mode` sets `APP_CONTENT_FILTER_MODE`.
+/// The secret must be a JSON object whose keys are setting names in either form
+/// (`admin_token` or `APP_ADMIN_TOKEN`). Secret values win over parameters.
+pub async fn fetch(source: &ConfigSource) -> Result<HashMap<String, String>, ConfigError> {
+    let prefix = source.get("APP_SSM_PREFIX").unwrap_or_else(|| DEFAULT_SSM_PREFIX.to_string());
+    let prefix = format!("/{}/", prefix.trim_matches('/'));
+    let secret_id = source.get("APP_SECRETS_ID").filter(|id| !id.is_empty());
+    let ttl = Duration::from_secs(source.parse_or("APP_CONFIG_CACHE_TTL_SECS", DEFAULT_CACHE_TTL_SECS)?);
+
+    let cache_key = format!("{}|{}", prefix, secret_id.as_deref().unwrap_or_default());
+    if let Some((fetched_at, values)) = CACHE.lock().expect("config cache lock poisoned").get(&cache_key)
+        && fetched_at.elapsed() < ttl
+    {
+        tracing::debug!(%prefix, "Using cached remote configuration");
+        return Ok(values.clone());
+    }
+
+    let sdk_config =
+        aws_clients::load_sdk_config(&source.aws_region(), source.get("AWS_ENDPOINT_URL").as_deref()).await;
+
+    let mut values = fetch_parameters(&aws_sdk_ssm::Client::new(&sdk_config), &prefix).await?;
+    let parameter_count = values.len();
+    if let Some(secret_id) = &secret_id {
+        let secret = fetch_secret(&aws_sdk_secretsmanager::Client::new(&sdk_config), secret_id).await?;
+        values.extend(secret);
+    }
+    tracing::info!(
+        %prefix,
+        secret_id = ?secret_id,
+        parameters = parameter_count,
+        settings = values.len(),
+        "Remote configuration loaded"
+    );
+
+    CACHE
+        .lock()
+        .expect("config cache lock poisoned")
+        .insert(cache_key, (Instant::now(), values.clone()));
+    Ok(values)
+}
+
+async fn fetch_parameters(
+    client: &aws_sdk_ssm::Client,
+    prefix: &str,
+) -> Result<HashMap<String, String>, ConfigError> {
+    let remote_error = |e: String| ConfigError::RemoteError(format!("SSM path {}", prefix), e);
+    let mut values = HashMap::new();
+    let mut pages = client
+        .get_parameters_by_path()
+        .path(prefix)
+        .recursive(true)
+        .with_decryption(true) // SecureString parameters
+        .into_paginator()
+        .send();
+
+    while let Some(page) = pages.next().await {
+        let page = page.map_err(|e| remote_error(aws_sdk_ssm::error::DisplayErrorContext(e).to_string()))?;
+        for parameter in page.parameters() {
+            let (Some(name), Some(value)) = (parameter.name(), parameter.value()) else {
+                continue;
+            };
+            let relative = name.strip_prefix(prefix).unwrap_or(name);
+            values.insert(file_key(&relative.replace('/', "_")), value.to_string());
+        }
+    }
+    Ok(values)
+}
+
+async fn fetch_secret(
+    client: &aws_sdk_secretsmanager::Client,
+    secret_id: &str,
+) -> Result<HashMap<String, String>, ConfigError> {
+    let remote_error = |e: String| ConfigError::RemoteError(format!("secret {}", secret_id), e);
+    let output = client
+        .get_secret_value()
+        .secret_id(secret_id)
+        .send()
+        .await
+        .map_err(|e| remote_error(aws_sdk_secretsmanager::error::DisplayErrorContext(e).to_string()))?;
+    let secret = output
+        .secret_string()
+        .ok_or_else(|| remote_error("secret has no string value".to_string()))?;
+
+    let object: serde_json::Map<String, serde_json::Value> = serde_json::from_str(secret)
+        .map_err(|e| remote_error(format!("expected a JSON object: {}", e)))?;
+    Ok(object
+        .into_iter()
+        .map(|(key, value)| {
+            let value = match value {
+                serde_json::Value::String(s) => s,
+                other => other.to_string(),
+            };
+            (file_key(&key), value)
+        })
+        .collect())
+}
@@ -1,29 +1,255 @@
 use crate::{
-    domain::MemeRepository,
+    domain::{
+        AppliedMigration, AuditEntry, AuditRepository, BackfillProgress, BackfillRepository, BlocklistRepository, CheckpointRepository,
+        ConsistencyLevel, Follower, FollowerRepository, MemeHistoryRepository, MemeRepository, MigrationRepository, Shortlink, ShortlinkRepository,
+        ShortlinkTarget, TableInfo, TenantConfigRepository, TenantOverrides, TrendingRepository, TrendingSnapshot, VectorIndex,
+    },
+    embeddings,
     errors::RepoError,
-    models::Meme,
+    models::{Meme, MemeStatus, SortOrder, Visibility, INITIAL_VERSION},
 };
 use anyhow::Context;
 use async_trait::async_trait;
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
 use aws_sdk_dynamodb::{
-    types::AttributeValue,
+    operation::{put_item::PutItemError, update_item::UpdateItemError},
+    types::{AttributeValue, ReturnValue, ReturnValuesOnConditionCheckFailure, Select},
     Client as DynamoDbClient,
 };
 use std::collections::HashMap;
 use tracing::{self, info};
 use uuid::Uuid;
 
+/// Attribute DynamoDB TTL is enabled on for the meme table.
+pub const MEME_TTL_ATTRIBUTE: &str = "ttl";
+/// How long after `expires_at` DynamoDB TTL may delete an item. The cleanup job normally
+/// removes expired memes (and their images) well before then; TTL only catches leftovers.
+const TTL_DELAY_SECS: i64 = 24 * 60 * 60;
+
+/// Attribute every meme item carries with the same value, as the partition key of the
+/// listing indexes. One partition keeps each index globally ordered by its sort key.
+pub const LISTING_ATTRIBUTE: &str = "listing";
+const LISTING_PARTITION: &str = "all";
+
+/// A global secondary index over the listing partition, ordered by `sort_key`.
+#[derive(Debug, Clone, Copy)]
+pub struct ListingIndex {
+    pub name: &'static str,
+    pub sort_key: &'static str,
+    /// Whether `sort_key` is a number attribute (otherwise a string).
+    pub numeric: bool,
+}
+
+const CREATED_AT_INDEX: ListingIndex = ListingIndex { name: "by_created_at", sort_key: "created_at", numeric: false };
+const TITLE_INDEX: ListingIndex = ListingIndex { name: "by_title", sort_key: "title_key", numeric: false };
+const LIKES_INDEX: ListingIndex = ListingIndex { name: "by_like_count", sort_key: "like_count", numeric: true };
+
+/// Indexes the meme table needs for sorted listings; created by `init-resources`.
+pub const LISTING_INDEXES: &[ListingIndex] = &[CREATED_AT_INDEX, TITLE_INDEX, LIKES_INDEX];
+
+/// Index and direction serving a sort order.
+fn listing_index(order: SortOrder) -> (ListingIndex, bool) {
+    match order {
+        SortOrder::Newest => (CREATED_AT_INDEX, false),
+        SortOrder::Oldest => (CREATED_AT_INDEX, true),
+        SortOrder::Title => (TITLE_INDEX, true),
+        SortOrder::Top => (LIKES_INDEX, false),
+    }
+}
+
+/// Filter hiding expired memes, with `:now` bound to the current time in epoch seconds.
+const NOT_EXPIRED_FILTER: &str = "attribute_not_exists(expires_at) OR expires_at > :now";
+
+/// Filter finding scheduled drafts whose time has come, with `#status` naming the status
+/// attribute (a reserved word) and `:draft` bound to the draft status.
+const DUE_FOR_PUBLISHING_FILTER: &str = "#status = :draft AND publish_at <= :now";
+
+/// Binds `:now` to `now` in epoch seconds.
+fn now_values(now: DateTime<Utc>) -> HashMap<String, AttributeValue> {
+    HashMap::from([(":now".to_string(), AttributeValue::N(now.timestamp().to_string()))])
+}
+
 #[derive(Debug, Clone)]
 pub struct DynamoDbMemeRepository {
     client: DynamoDbClient,
     table_name: String, // Store the table name
+    scan_segments: u32, // Segments of a parallel scan; 1 scans sequentially
 }
 
 impl DynamoDbMemeRepository {
     /// Creates a new repository instance configured for a specific table.
     pub fn new(client: DynamoDbClient, table_name: String) -> Self {
         info!(%table_name, "Initializing DynamoDbMemeRepository");
-        Self { client, table_name }
+        Self { client, table_name, scan_segments: 1 }
+    }
+
+    /// Splits full-table scans (`list_all`, `list_expired`) into `segments` parallel scans.
+    /// Large tables are read that much faster, at the cost of as many concurrent requests
+    /// against the table's read capacity.
+    pub fn with_scan_segments(mut self, segments: u32) -> Self {
+        self.scan_segments = segments.max(1);
+        self
+    }
+
+    /// Scans the table for memes matching `filter_expression`, in which `:now` is bound to
+    /// `now` as epoch seconds. Segments are scanned concurrently and their results merged,
+    /// so the order is unspecified.
+    async fn scan_memes(&self, filter_expression: &str, now: DateTime<Utc>) -> Result<Vec<Meme>, RepoError> {
+        self.scan_memes_with(filter_expression, None, now_values(now)).await
+    }
+
+    /// Like [`Self::scan_memes`], with the attribute names in `filter_expression` bound by
+    /// `names` and every value by `values`.
+    async fn scan_memes_with(
+        &self,
+        filter_expression: &str,
+        names: Option<HashMap<String, String>>,
+        values: HashMap<String, AttributeValue>,
+    ) -> Result<Vec<Meme>, RepoError> {
+        tracing::debug!("DynamoDB: Scanning table '{}' (filter: {}, segments: {})", self.table_name, filter_expression, self.scan_segments);
+        let memes: Vec<Meme> = if self.scan_segments == 1 {
+            self.scan_segment(filter_expression, &names, &values, None).await?
+        } else {
+            let segments = (0..self.scan_segments)
+                .map(|segment| self.scan_segment(filter_expression, &names, &values, Some(segment as i32)));
+            futures::future::try_join_all(segments).await?.into_iter().flatten().collect()
+        };
+        tracing::info!("DynamoDB (table: {}): Successfully listed {} memes", self.table_name, memes.len());
+        Ok(memes)
+    }
+
+    /// Scans one segment of the table, or all of it when `segment` is `None`. Handles
+    /// pagination.
+    async fn scan_segment(
+        &self,
+        filter_expression: &str,
+        names: &Option<HashMap<String, String>>,
+        values: &HashMap<String, AttributeValue>,
+        segment: Option<i32>,
+    ) -> Result<Vec<Meme>, RepoError> {
+        let mut memes: Vec<Meme> = Vec::new();
+        let mut last_evaluated_key: Option<HashMap<String, AttributeValue>> = None;
+
+        loop {
+            let mut request_builder = self.client
+                .scan()
+                .table_name(&self.table_name) // Use stored table name
+                .filter_expression(filter_expression)
+                .set_expression_attribute_names(names.clone())
+                .set_expression_attribute_values(Some(values.clone()));
+            if let Some(segment) = segment {
+                request_builder = request_builder.segment(segment).total_segments(self.scan_segments as i32);
+            }
+
+            // Apply ExclusiveStartKey if paginating from previous response
+            if let Some(lek) = last_evaluated_key {
+                request_builder = request_builder.set_exclusive_start_key(Some(lek));
+            }
+
+            let resp = request_builder
+                .send()
+                .await
+                .context(format!("DynamoDB: Failed to scan table '{}' (segment: {:?})", self.table_name, segment))
+                .map_err(RepoError::BackendError)?;
+
+            if let Some(items) = resp.items {
+                tracing::debug!("DynamoDB Scan (table: {}, segment: {:?}): Returned {} items", self.table_name, segment, items.len());
+                for item in items {
+                    memes.push(self.parse_listed_item(&item, "scan")?);
+                }
+            } else {
+                tracing::debug!("DynamoDB Scan (table: {}): Returned no items in this page.", self.table_name);
+            }
+
+            // Check for next page
+            last_evaluated_key = resp.last_evaluated_key;
+            if last_evaluated_key.is_none() {
+                tracing::debug!("DynamoDB Scan (table: {}, segment: {:?}): Complete.", self.table_name, segment);
+                break; // Exit loop if no more pages
+            } else {
+                tracing::debug!("DynamoDB Scan (table: {}, segment: {:?}): Continuing with LastEvaluatedKey...", self.table_name, segment);
+            }
+        }
+
+        Ok(memes)
+    }
+
+    /// Counts the memes that have not expired in one segment of the table, or all of it when
+    /// `segment` is `None`. Handles pagination.
+    async fn count_segment(&self, now: DateTime<Utc>, segment: Option<i32>) -> Result<u64, RepoError> {
+        let mut count = 0u64;
+        let mut last_evaluated_key: Option<HashMap<String, AttributeValue>> = None;
+
+        loop {
+            let mut request_builder = self.client
+                .scan()
+                .table_name(&self.table_name)
+                .filter_expression(NOT_EXPIRED_FILTER)
+                .expression_attribute_values(":now", AttributeValue::N(now.timestamp().to_string()))
+                .select(Select::Count)
+                .set_exclusive_start_key(last_evaluated_key);
+            if let Some(segment) = segment {
+                request_builder = request_builder.segment(segment).total_segments(self.scan_segments as i32);
+            }
+            let resp = request_builder
+                .send()
+                .await
+                .context(format!("DynamoDB: Failed to count memes in table '{}' (segment: {:?})", self.table_name, segment))
+                .map_err(RepoError::BackendError)?;
+
+            count += u64::try_from(resp.count).unwrap_or_default();
+            last_evaluated_key = resp.last_evaluated_key;
+            if last_evaluated_key.is_none() {
+                break;
+            }
+        }
+        Ok(count)
+    }
+
+    /// Queries a listing index for memes that have not expired, in index order (descending
+    /// unless `forward`). Handles pagination.
+    async fn query_listing(&self, index: ListingIndex, forward: bool) -> Result<Vec<Meme>, RepoError> {
+        tracing::debug!(table_name = %self.table_name, index = index.name, forward, "DynamoDB: Querying listing index");
+        let mut memes: Vec<Meme> = Vec::new();
+        let mut last_evaluated_key: Option<HashMap<String, AttributeValue>> = None;
+
+        loop {
+            let resp = self.client
+                .query()
+                .table_name(&self.table_name)
+                .index_name(index.name)
+                .key_condition_expression("#listing = :listing")
+                .filter_expression(NOT_EXPIRED_FILTER)
+                .expression_attribute_names("#listing", LISTING_ATTRIBUTE)
+                .expression_attribute_values(":listing", AttributeValue::S(LISTING_PARTITION.to_string()))
+                .expression_attribute_values(":now", AttributeValue::N(Utc::now().timestamp().to_string()))
+                .scan_index_forward(forward)
+                .set_exclusive_start_key(last_evaluated_key)
+                .send()
+                .await
+                .context(format!("DynamoDB: Failed to query index '{}' of table '{}'", index.name, self.table_name))
+                .map_err(RepoError::BackendError)?;
+
+            for item in resp.items.unwrap_or_default() {
+                memes.push(self.parse_listed_item(&item, "query")?);
+            }
+            last_evaluated_key = resp.last_evaluated_key;
+            if last_evaluated_key.is_none() {
+                break;
+            }
+        }
+
+        tracing::info!(table_name = %self.table_name, index = index.name, count = memes.len(), "DynamoDB: Listed memes from index");
+        Ok(memes)
+    }
+
+    /// Parses an item returned by a listing. Fails fast if data in the table is corrupt.
+    fn parse_listed_item(&self, item: &HashMap<String, AttributeValue>, operation: &str) -> Result<Meme, RepoError> {
+        item_to_meme(item).inspect_err(|e| {
+            let item_id = item.get("meme_id").and_then(|v| v.as_s().ok());
+            tracing::error!(item.id = ?item_id, table_name = %self.table_name, error = %e, "DynamoDB: Failed to parse item from {} into Meme", operation);
+        })
     }
 }
 
@@ -31,139 +257,1693 @@ impl DynamoDbMemeRepository {
 impl MemeRepository for DynamoDbMemeRepository {
     /// Stores a `Meme` in the DynamoDB table using PutItem.
     async fn create(&self, meme: &Meme) -> Result<(), RepoError> {
-        self.client
+        let item = meme_to_item(meme);
+        let result = self.client
             .put_item()
             .table_name(&self.table_name) // Use stored table name
-            .item("meme_id", AttributeValue::S(meme.meme_id.to_string()))
-            .item("title", AttributeValue::S(meme.title.clone()))
-            .item("description", AttributeValue::S(meme.description.clone()))
-            .item("image_key", AttributeValue::S(meme.image_key.clone()))
+            .set_item(Some(item.clone()))
+            .condition_expression("attribute_not_exists(meme_id)") // Never overwrite an existing meme
+            .return_values_on_condition_check_failure(ReturnValuesOnConditionCheckFailure::AllOld)
             .send()
-            .await
-            .context(format!("DynamoDB (table: {}): Failed to put meme (id: {})", self.table_name, meme.meme_id))
-            .map_err(RepoError::BackendError)?; // Map anyhow::Error -> RepoError
-        Ok(())
+            .await;
+
+        let err = match result {
+            Ok(_) => return Ok(()),
+            Err(err) => err,
+        };
+        match err.as_service_error() {
+            // A retried write whose first attempt already landed finds its own item: not a collision
+            Some(PutItemError::ConditionalCheckFailedException(failed)) if failed.item() == Some(&item) => Ok(()),
+            Some(PutItemError::ConditionalCheckFailedException(_)) => Err(RepoError::AlreadyExists(meme.meme_id)),
+            _ => Err(RepoError::BackendError(
+                anyhow::Error::new(err)
+                    .context(format!("DynamoDB (table: {}): Failed to put meme (id: {})", self.table_name, meme.meme_id)),
+            )),
+        }
     }
 
     /// Retrieves a `Meme` from DynamoDB using GetItem.
-    async fn get_by_id(&self, id: Uuid) -> Result<Option<Meme>, RepoError> {
+    async fn get_by_id(&self, id: Uuid, consistency: ConsistencyLevel) -> Result<Option<Meme>, RepoError> {
         let id_str = id.to_string();
         let resp = self.client
             .get_item()
             .table_name(&self.table_name) // Use stored table name
             .key("meme_id", AttributeValue::S(id_str.clone()))
+            .consistent_read(consistency == ConsistencyLevel::Strong)
             .send()
             .await
             .context(format!("DynamoDB (table: {}): Failed to get meme (id: {})", self.table_name, id_str))
             .map_err(RepoError::BackendError)?;
 
         match resp.item {
+            // Expired memes are hidden until the cleanup job (or TTL) removes them
             Some(item) => match item_to_meme(&item) {
-                Some(meme) => Ok(Some(meme)),
-                None => {
-                    tracing::error!(meme_id = %id_str, table_name = %self.table_name, "DynamoDB: Retrieved item but failed to parse into Meme");
-                    // Return a RepoError indicating data inconsistency
-                    Err(RepoError::DataCorruption(format!(
-                        "Failed to parse meme data retrieved from DynamoDB table '{}' for id {}",
-                        self.table_name, id_str
-                    )))
+                Ok(meme) if meme.is_expired(Utc::now()) => Ok(None),
+                Ok(meme) => Ok(Some(meme)),
+                Err(e) => {
+                    tracing::error!(meme_id = %id_str, table_name = %self.table_name, error = %e, "DynamoDB: Retrieved item but failed to parse into Meme");
+                    Err(e)
                 }
             },
             None => Ok(None), // Item not found is not an error
         }
     }
 
-    /// Lists all memes using DynamoDB Scan. Handles pagination.
+    /// Fetches only the key and expiry time with a projected GetItem.
+    async fn exists(&self, id: Uuid) -> Result<bool, RepoError> {
+        let resp = self.client
+            .get_item()
+            .table_name(&self.table_name)
+            .key("meme_id", AttributeValue::S(id.to_string()))
+            .projection_expression("meme_id, expires_at")
+            .send()
+            .await
+            .context(format!("DynamoDB (table: {}): Failed to check meme (id: {})", self.table_name, id))
+            .map_err(RepoError::BackendError)?;
+
+        let Some(item) = resp.item else { return Ok(false) };
+        match item.get("expires_at") {
+            Some(value) => {
+                let expires_at: i64 = value.as_n().ok().and_then(|n| n.parse().ok()).ok_or_else(|| RepoError::DataCorruption {
+                    field: "expires_at".to_string(),
+                    reason: format!("DynamoDB (table: {}): Meme {} has a malformed expiry time", self.table_name, id),
+                })?;
+                Ok(expires_at > Utc::now().timestamp())
+            }
+            None => Ok(true),
+        }
+    }
+
+    /// Lists all memes that have not expired using DynamoDB Scan. Handles pagination.
     async fn list_all(&self) -> Result<Vec<Meme>, RepoError> {
-        tracing::debug!("DynamoDB: Scanning table '{}' for all memes", self.table_name);
-        let mut memes: Vec<Meme> = Vec::new();
+        self.scan_memes(NOT_EXPIRED_FILTER, Utc::now())
+            .await
+    }
+
+    /// Lists memes from the listing index serving `order`.
+    async fn list_sorted(&self, order: SortOrder) -> Result<Vec<Meme>, RepoError> {
+        let (index, forward) = listing_index(order);
+        self.query_listing(index, forward).await
+    }
+
+    /// Scans for memes past their expiry time that are still stored.
+    async fn list_expired(&self, now: DateTime<Utc>) -> Result<Vec<Meme>, RepoError> {
+        self.scan_memes("expires_at <= :now", now).await
+    }
+
+    /// Scans for scheduled drafts whose publish time has passed.
+    async fn list_due_for_publishing(&self, now: DateTime<Utc>) -> Result<Vec<Meme>, RepoError> {
+        let names = HashMap::from([("#status".to_string(), "status".to_string())]);
+        let mut values = now_values(now);
+        values.insert(":draft".to_string(), AttributeValue::S(MemeStatus::Draft.as_str().to_string()));
+        self.scan_memes_with(DUE_FOR_PUBLISHING_FILTER, Some(names), values).await
+    }
+
+    /// Writes the meme's attributes with a conditional UpdateItem that only succeeds while the
+    /// stored version matches. The like and view counts are not written, so concurrent likes
+    /// and views are kept.
+    /// On failure the current item is returned with the error, which tells a lost race apart
+    /// from a deleted meme, and a retry whose first attempt landed from either.
+    async fn update(&self, meme: &Meme, expected_version: u64) -> Result<(), RepoError> {
+        // Items written before versioning have no `version` attribute and read as the initial version
+        let condition = if expected_version == INITIAL_VERSION {
+            "attribute_exists(meme_id) AND (attribute_not_exists(#version) OR #version = :expected)"
+        } else {
+            "attribute_exists(meme_id) AND #version = :expected"
+        };
+        let mut attributes: Vec<_> = meme_to_item(meme)
+            .into_iter()
+            .filter(|(name, _)| !["meme_id", "like_count", "view_count"].contains(&name.as_str()))
+            .collect();
+        attributes.sort_by(|(a, _), (b, _)| a.cmp(b));
+        // Attributes the meme no longer has are removed rather than kept from the stored item
+        let removed: Vec<_> = OPTIONAL_ATTRIBUTES
+            .iter()
+            .filter(|name| !attributes.iter().any(|(present, _)| present == *name))
+            .collect();
+        // Items written before likes existed start counting from zero
+        let mut assignments = vec!["#like_count = if_not_exists(#like_count, :zero)".to_string()];
+        let mut request = self.client
+            .update_item()
+            .table_name(&self.table_name)
+            .key("meme_id", AttributeValue::S(meme.meme_id.to_string()))
+            .condition_expression(condition)
+            .expression_attribute_names("#version", "version")
+            .expression_attribute_names("#like_count", "like_count")
+            .expression_attribute_values(":expected", AttributeValue::N(expected_version.to_string()))
+            .expression_attribute_values(":zero", AttributeValue::N("0".to_string()))
+            .return_values_on_condition_check_failure(ReturnValuesOnConditionCheckFailure::AllOld);
+        for (position, (name, value)) in attributes.iter().enumerate() {
+            assignments.push(format!("#a{0} = :a{0}", position));
+            request = request
+                .expression_attribute_names(format!("#a{}", position), name)
+                .expression_attribute_values(format!(":a{}", position), value.clone());
+        }
+        let mut expression = format!("SET {}", assignments.join(", "));
+        if !removed.is_empty() {
+            let placeholders: Vec<_> = (0..removed.len()).map(|position| format!("#r{}", position)).collect();
+            expression.push_str(&format!(" REMOVE {}", placeholders.join(", ")));
+            for (placeholder, name) in placeholders.into_iter().zip(&removed) {
+                request = request.expression_attribute_names(placeholder, **name);
+            }
+        }
+        let result = request.update_expression(expression).send().await;
+
+        let err = match result {
+            Ok(_) => return Ok(()),
+            Err(err) => err,
+        };
+        let Some(UpdateItemError::ConditionalCheckFailedException(failed)) = err.as_service_error() else {
+            return Err(RepoError::BackendError(
+                anyhow::Error::new(err)
+                    .context(format!("DynamoDB (table: {}): Failed to update meme (id: {})", self.table_name, meme.meme_id)),
+            ));
+        };
+        match failed.item() {
+            None => Err(RepoError::NotFound(meme.meme_id)),
+            // A retried update whose first attempt already landed finds its own write: not a conflict
+            Some(item)
+                if attributes.iter().all(|(name, value)| item.get(name) == Some(value))
+                    && removed.iter().all(|name| !item.contains_key(**name)) =>
+            {
+                Ok(())
+            }
+            Some(item) => Err(RepoError::VersionConflict {
+                id: meme.meme_id,
+                expected: expected_version,
+                actual: item_to_meme(item)?.version,
+            }),
+        }
+    }
+
+    /// Increments `like_count` with an UpdateItem ADD, so concurrent likes are all counted.
+    async fn add_like(&self, id: Uuid) -> Result<u64, RepoError> {
+        let result = self.client
+            .update_item()
+            .table_name(&self.table_name)
+            .key("meme_id", AttributeValue::S(id.to_string()))
+            // Also puts memes stored before sorting existed into the listing partition
+            .update_expression("SET #listing = :listing ADD like_count :one")
+            .condition_expression(format!("attribute_exists(meme_id) AND ({})", NOT_EXPIRED_FILTER))
+            .expression_attribute_names("#listing", LISTING_ATTRIBUTE)
+            .expression_attribute_values(":listing", AttributeValue::S(LISTING_PARTITION.to_string()))
+            .expression_attribute_values(":one", AttributeValue::N("1".to_string()))
+            .expression_attribute_values(":now", AttributeValue::N(Utc::now().timestamp().to_string()))
+            .return_values(ReturnValue::UpdatedNew)
+            .send()
+            .await;
+
+        match result {
+            Ok(output) => output
+                .attributes()
+                .and_then(|attributes| attributes.get("like_count")?.as_n().ok()?.parse().ok())
+                .ok_or_else(|| RepoError::DataCorruption {
+                    field: "like_count".to_string(),
+                    reason: format!("DynamoDB (table: {}): Like of meme {} returned no like count", self.table_name, id),
+                }),
+            Err(err) if matches!(err.as_service_error(), Some(UpdateItemError::ConditionalCheckFailedException(_))) => {
+                Err(RepoError::NotFound(id))
+            }
+            Err(err) => Err(RepoError::BackendError(
+                anyhow::Error::new(err).context(format!("DynamoDB (table: {}): Failed to like meme (id: {})", self.table_name, id)),
+            )),
+        }
+    }
+
+    /// Adds to `view_count` with an UpdateItem ADD, so views counted by several instances all
+    /// add up.
+    async fn add_views(&self, id: Uuid, views: u64) -> Result<(), RepoError> {
+        let result = self.client
+            .update_item()
+            .table_name(&self.table_name)
+            .key("meme_id", AttributeValue::S(id.to_string()))
+            .update_expression("ADD view_count :views")
+            .condition_expression(format!("attribute_exists(meme_id) AND ({})", NOT_EXPIRED_FILTER))
+            .expression_attribute_values(":views", AttributeValue::N(views.to_string()))
+            .expression_attribute_values(":now", AttributeValue::N(Utc::now().timestamp().to_string()))
+            .send()
+            .await;
+
+        match result {
+            Ok(_) => Ok(()),
+            Err(err) if matches!(err.as_service_error(), Some(UpdateItemError::ConditionalCheckFailedException(_))) => {
+                Err(RepoError::NotFound(id))
+            }
+            Err(err) => Err(RepoError::BackendError(
+                anyhow::Error::new(err)
+                    .context(format!("DynamoDB (table: {}): Failed to count views of meme (id: {})", self.table_name, id)),
+            )),
+        }
+    }
+
+    /// Deletes an item from DynamoDB using DeleteItem.
+    async fn delete(&self, id: Uuid) -> Result<(), RepoError> {
+        let id_str = id.to_string();
+        tracing::debug!(meme_id = %id_str, table_name = %self.table_name, "DynamoDB: Deleting item");
+
+        self.client
+            .delete_item()
+            .table_name(&self.table_name) // Use stored table name
+            .key("meme_id", AttributeValue::S(id_str.clone()))
+            // DeleteItem succeeds even if item not found, so no need for ConditionExpression unless required
+            .send()
+            .await
+            .context(format!("DynamoDB (table: {}): Failed to delete meme (id: {})", self.table_name, id_str))
+            .map_err(RepoError::BackendError)?;
+
+        tracing::debug!(meme_id = %id_str, table_name = %self.table_name, "DynamoDB: Delete request sent");
+        Ok(())
+    }
+
+    /// Reads the table's status, size and index states with DescribeTable.
+    async fn describe(&self) -> Result<TableInfo, RepoError> {
+        let table = self.client
+            .describe_table()
+            .table_name(&self.table_name)
+            .send()
+            .await
+            .context(format!("DynamoDB: Failed to describe table '{}'", self.table_name))
+            .map_err(RepoError::BackendError)?
+            .table
+            .ok_or_else(|| RepoError::DataCorruption {
+                field: "Table".to_string(),
+                reason: format!("DynamoDB: No description returned for table '{}'", self.table_name),
+            })?;
+
+        Ok(TableInfo {
+            name: self.table_name.clone(),
+            status: table.table_status().map_or_else(|| "UNKNOWN".to_string(), |status| status.as_str().to_string()),
+            item_count: table.item_count().and_then(|count| u64::try_from(count).ok()),
+            size_bytes: table.table_size_bytes().and_then(|size| u64::try_from(size).ok()),
+            indexes: table
+                .global_secondary_indexes()
+                .iter()
+                .filter_map(|index| {
+                    let status = index.index_status().map_or("UNKNOWN", |status| status.as_str());
+                    Some((index.index_name()?.to_string(), status.to_string()))
+                })
+                .collect(),
+        })
+    }
+
+    /// Counts with `Select::Count` scans, so no items are transferred, split into segments
+    /// like [`Self::with_scan_segments`] lists.
+    async fn count(&self) -> Result<u64, RepoError> {
+        let now = Utc::now();
+        let segments = (0..self.scan_segments)
+            .map(|segment| self.count_segment(now, (self.scan_segments > 1).then_some(segment as i32)));
+        let count = futures::future::try_join_all(segments).await?.into_iter().sum();
+        tracing::debug!(table_name = %self.table_name, count, "DynamoDB: Counted memes");
+        Ok(count)
+    }
+
+    /// Counts items in the creation-time index from `since` on, with `Select::Count` so no
+    /// items are transferred. Handles pagination.
+    async fn count_created_since(&self, since: DateTime<Utc>) -> Result<u64, RepoError> {
+        let mut count = 0u64;
         let mut last_evaluated_key: Option<HashMap<String, AttributeValue>> = None;
 
         loop {
-            let mut request_builder = self.client.scan().table_name(&self.table_name); // Use stored table name
+            let resp = self.client
+                .query()
+                .table_name(&self.table_name)
+                .index_name(CREATED_AT_INDEX.name)
+                .key_condition_expression("#listing = :listing AND #created_at >= :since")
+                .expression_attribute_names("#listing", LISTING_ATTRIBUTE)
+                .expression_attribute_names("#created_at", CREATED_AT_INDEX.sort_key)
+                .expression_attribute_values(":listing", AttributeValue::S(LISTING_PARTITION.to_string()))
+                .expression_attribute_values(":since", AttributeValue::S(since.to_rfc3339_opts(SecondsFormat::Nanos, true)))
+                .select(Select::Count)
+                .set_exclusive_start_key(last_evaluated_key)
+                .send()
+                .await
+                .context(format!("DynamoDB: Failed to count recent memes in table '{}'", self.table_name))
+                .map_err(RepoError::BackendError)?;
 
-            // Apply ExclusiveStartKey if paginating from previous response
-            if let Some(lek) = last_evaluated_key {
-                request_builder = request_builder.set_exclusive_start_key(Some(lek));
+            count += u64::try_from(resp.count).unwrap_or_default();
+            last_evaluated_key = resp.last_evaluated_key;
+            if last_evaluated_key.is_none() {
+                break;
             }
+        }
 
-            let resp = request_builder
+        tracing::debug!(table_name = %self.table_name, %since, count, "DynamoDB: Counted recent memes");
+        Ok(count)
+    }
+
+    /// Describes the table; any answer means DynamoDB and the table are reachable.
+    async fn ping(&self) -> Result<(), RepoError> {
+        self.client
+            .describe_table()
+            .table_name(&self.table_name)
+            .send()
+            .await
+            .context(format!("DynamoDB: Table '{}' is not reachable", self.table_name))
+            .map_err(RepoError::BackendError)?;
+        Ok(())
+    }
+}
+
+/// Partition key under which blocklist terms are stored in the meta table.
+pub(crate) const BLOCKLIST_PK: &str = "blocklist";
+
+/// Stores content filter terms in the auxiliary meta table (pk = "blocklist", sk = term).
+#[derive(Debug, Clone)]
+pub struct DynamoDbBlocklistRepository {
+    client: DynamoDbClient,
+    table_name: String,
+}
+
+impl DynamoDbBlocklistRepository {
+    pub fn new(client: DynamoDbClient, table_name: String) -> Self {
+        info!(%table_name, "Initializing DynamoDbBlocklistRepository");
+        Self { client, table_name }
+    }
+}
+
+#[async_trait]
+impl BlocklistRepository for DynamoDbBlocklistRepository {
+    /// Queries all terms under the blocklist partition. Handles pagination.
+    async fn list_terms(&self) -> Result<Vec<String>, RepoError> {
+        let mut terms = Vec::new();
+        let mut last_evaluated_key: Option<HashMap<String, AttributeValue>> = None;
+
+        loop {
+            let resp = self.client
+                .query()
+                .table_name(&self.table_name)
+                .key_condition_expression("pk = :pk")
+                .expression_attribute_values(":pk", AttributeValue::S(BLOCKLIST_PK.to_string()))
+                .set_exclusive_start_key(last_evaluated_key)
                 .send()
                 .await
-                .context(format!("DynamoDB: Failed to scan table '{}'", self.table_name))
+                .context(format!("DynamoDB (table: {}): Failed to query blocklist terms", self.table_name))
                 .map_err(RepoError::BackendError)?;
 
-            if let Some(items) = resp.items {
-                tracing::debug!("DynamoDB Scan (table: {}): Returned {} items", self.table_name, items.len());
-                for item in items {
-                    match item_to_meme(&item) {
-                        Some(meme) => memes.push(meme),
-                        None => {
-                            let item_id = item.get("meme_id").and_then(|v| v.as_s().ok());
-                            tracing::error!(item.id = ?item_id, table_name = %self.table_name, "DynamoDB: Failed to parse item from scan into Meme");
-                            // Fail fast if data in the table is corrupt
-                            return Err(RepoError::DataCorruption(format!(
-                                "DynamoDB: Failed to parse item {:?} during scan of table '{}'",
-                                item_id, self.table_name
-                            )));
-                        }
+            for item in resp.items.unwrap_or_default() {
+                match item.get("sk").and_then(|v| v.as_s().ok()) {
+                    Some(term) => terms.push(term.clone()),
+                    None => {
+                        return Err(RepoError::DataCorruption {
+                            field: "sk".to_string(),
+                            reason: format!("Blocklist item without a term in table '{}'", self.table_name),
+                        });
                     }
                 }
-            } else {
-                tracing::debug!("DynamoDB Scan (table: {}): Returned no items in this page.", self.table_name);
             }
 
-            // Check for next page
             last_evaluated_key = resp.last_evaluated_key;
             if last_evaluated_key.is_none() {
-                tracing::debug!("DynamoDB Scan (table: {}): Complete.", self.table_name);
-                break; // Exit loop if no more pages
-            } else {
-                tracing::debug!("DynamoDB Scan (table: {}): Continuing with LastEvaluatedKey...", self.table_name);
+                break;
             }
         }
 
-        tracing::info!("DynamoDB (table: {}): Successfully listed {} memes", self.table_name, memes.len());
-        Ok(memes)
+        tracing::debug!(table_name = %self.table_name, count = terms.len(), "DynamoDB: Loaded blocklist terms");
+        Ok(terms)
     }
 
-    /// Deletes an item from DynamoDB using DeleteItem.
-    async fn delete(&self, id: Uuid) -> Result<(), RepoError> {
-        let id_str = id.to_string();
-        tracing::debug!(meme_id = %id_str, table_name = %self.table_name, "DynamoDB: Deleting item");
+    async fn add_term(&self, term: &str) -> Result<(), RepoError> {
+        self.client
+            .put_item()
+            .table_name(&self.table_name)
+            .item("pk", AttributeValue::S(BLOCKLIST_PK.to_string()))
+            .item("sk", AttributeValue::S(term.to_string()))
+            .send()
+            .await
+            .context(format!("DynamoDB (table: {}): Failed to add blocklist term", self.table_name))
+            .map_err(RepoError::BackendError)?;
+        Ok(())
+    }
 
+    async fn remove_term(&self, term: &str) -> Result<(), RepoError> {
         self.client
             .delete_item()
-            .table_name(&self.table_name) // Use stored table name
-            .key("meme_id", AttributeValue::S(id_str.clone()))
-            // DeleteItem succeeds even if item not found, so no need for ConditionExpression unless required
+            .table_name(&self.table_name)
+            .key("pk", AttributeValue::S(BLOCKLIST_PK.to_string()))
+            .key("sk", AttributeValue::S(term.to_string()))
             .send()
             .await
-            .context(format!("DynamoDB (table: {}): Failed to delete meme (id: {})", self.table_name, id_str))
+            .context(format!("DynamoDB (table: {}): Failed to remove blocklist term", self.table_name))
             .map_err(RepoError::BackendError)?;
+        Ok(())
+    }
+}
 
-        tracing::debug!(meme_id = %id_str, table_name = %self.table_name, "DynamoDB: Delete request sent");
+/// Partition key under which change stream checkpoints are stored in the meta table.
+const CHECKPOINT_PK: &str = "stream-checkpoint";
+
+/// Stores the change stream consumer's position in the auxiliary meta table
+/// (pk = "stream-checkpoint", sk = shard ID).
+#[cfg_attr(feature = "lambda", allow(dead_code))]
+#[derive(Debug, Clone)]
+pub struct DynamoDbCheckpointRepository {
+    client: DynamoDbClient,
+    table_name: String,
+}
+
+#[cfg_attr(feature = "lambda", allow(dead_code))]
+impl DynamoDbCheckpointRepository {
+    pub fn new(client: DynamoDbClient, table_name: String) -> Self {
+        info!(%table_name, "Initializing DynamoDbCheckpointRepository");
+        Self { client, table_name }
+    }
+}
+
+#[async_trait]
+impl CheckpointRepository for DynamoDbCheckpointRepository {
+    async fn get_checkpoint(&self, shard_id: &str) -> Result<Option<String>, RepoError> {
+        let resp = self.client
+            .get_item()
+            .table_name(&self.table_name)
+            .key("pk", AttributeValue::S(CHECKPOINT_PK.to_string()))
+            .key("sk", AttributeValue::S(shard_id.to_string()))
+            .consistent_read(true)
+            .send()
+            .await
+            .context(format!("DynamoDB (table: {}): Failed to get stream checkpoint (shard: {})", self.table_name, shard_id))
+            .map_err(RepoError::BackendError)?;
+
+        match resp.item {
+            Some(item) => match item.get("sequence_number").and_then(|v| v.as_s().ok()) {
+                Some(sequence_number) => Ok(Some(sequence_number.clone())),
+                None => Err(RepoError::DataCorruption {
+                    field: "sequence_number".to_string(),
+                    reason: format!("Stream checkpoint without a sequence number in table '{}' (shard: {})", self.table_name, shard_id),
+                }),
+            },
+            None => Ok(None),
+        }
+    }
+
+    async fn save_checkpoint(&self, shard_id: &str, sequence_number: &str) -> Result<(), RepoError> {
+        self.client
+            .put_item()
+            .table_name(&self.table_name)
+            .item("pk", AttributeValue::S(CHECKPOINT_PK.to_string()))
+            .item("sk", AttributeValue::S(shard_id.to_string()))
+            .item("sequence_number", AttributeValue::S(sequence_number.to_string()))
+            .send()
+            .await
+            .context(format!("DynamoDB (table: {}): Failed to save stream checkpoint (shard: {})", self.table_name, shard_id))
+            .map_err(RepoError::BackendError)?;
         Ok(())
     }
 }
 
-// Helper function to convert DynamoDB item map to Meme struct
-// Remains internal to this module.
-fn item_to_meme(item: &HashMap<String, AttributeValue>) -> Option<Meme> {
-    // Use flat_map style for conciseness and early exit on None/Err
-    let meme_id = item
-        .get("meme_id")?
-        .as_s()
-        .ok()
-        .and_then(|s| Uuid::parse_str(s).ok())?;
-    let title = item.get("title")?.as_s().ok()?.to_string();
-    let description = item.get("description")?.as_s().ok()?.to_string();
-    let image_key = item.get("image_key")?.as_s().ok()?.to_string();
+/// Partition key under which tenant overrides are stored in the meta table.
+pub(crate) const TENANT_CONFIG_PK: &str = "tenant-config";
 
-    Some(Meme {
-        meme_id,
-        title,
-        description,
-        image_key,
-    })
+/// Stores per-tenant setting overrides in the auxiliary meta table (pk = "tenant-config",
+/// sk = tenant ID). Operators may also write the items directly; `allowed_image_types` is
+/// read from either a list or a string set.
+#[derive(Debug, Clone)]
+pub struct DynamoDbTenantConfigRepository {
+    client: DynamoDbClient,
+    table_name: String,
+}
+
+impl DynamoDbTenantConfigRepository {
+    pub fn new(client: DynamoDbClient, table_name: String) -> Self {
+        info!(%table_name, "Initializing DynamoDbTenantConfigRepository");
+        Self { client, table_name }
+    }
+}
+
+#[async_trait]
+impl TenantConfigRepository for DynamoDbTenantConfigRepository {
+    async fn get_overrides(&self, tenant: &str) -> Result<Option<TenantOverrides>, RepoError> {
+        let resp = self.client
+            .get_item()
+            .table_name(&self.table_name)
+            .key("pk", AttributeValue::S(TENANT_CONFIG_PK.to_string()))
+            .key("sk", AttributeValue::S(tenant.to_string()))
+            .send()
+            .await
+            .context(format!("DynamoDB (table: {}): Failed to get overrides of tenant '{}'", self.table_name, tenant))
+            .map_err(RepoError::BackendError)?;
+
+        resp.item
+            .map(|item| {
+                item_to_overrides(&item).map_err(|field| RepoError::DataCorruption {
+                    field: field.to_string(),
+                    reason: format!("Malformed overrides of tenant '{}' in table '{}'", tenant, self.table_name),
+                })
+            })
+            .transpose()
+    }
+
+    async fn put_overrides(&self, tenant: &str, overrides: &TenantOverrides) -> Result<(), RepoError> {
+        let mut item = HashMap::from([
+            ("pk".to_string(), AttributeValue::S(TENANT_CONFIG_PK.to_string())),
+            ("sk".to_string(), AttributeValue::S(tenant.to_string())),
+        ]);
+        if let Some(max_upload_bytes) = overrides.max_upload_bytes {
+            item.insert("max_upload_bytes".to_string(), AttributeValue::N(max_upload_bytes.to_string()));
+        }
+        if let Some(max_uploads_per_day) = overrides.max_uploads_per_day {
+            item.insert("max_uploads_per_day".to_string(), AttributeValue::N(max_uploads_per_day.to_string()));
+        }
+        if let Some(types) = &overrides.allowed_image_types {
+            // A list rather than a string set, which could not be empty
+            item.insert("allowed_image_types".to_string(), AttributeValue::L(types.iter().cloned().map(AttributeValue::S).collect()));
+        }
+        self.client
+            .put_item()
+            .table_name(&self.table_name)
+            .set_item(Some(item))
+            .send()
+            .await
+            .context(format!("DynamoDB (table: {}): Failed to save overrides of tenant '{}'", self.table_name, tenant))
+            .map_err(RepoError::BackendError)?;
+        Ok(())
+    }
+}
+
+/// Reads a tenant's overrides; fails with the name of the first malformed attribute.
+fn item_to_overrides(item: &HashMap<String, AttributeValue>) -> Result<TenantOverrides, &'static str> {
+    let number = |name: &'static str| -> Result<Option<u64>, &'static str> {
+        match item.get(name) {
+            Some(value) => value.as_n().ok().and_then(|n| n.parse().ok()).map(Some).ok_or(name),
+            None => Ok(None),
+        }
+    };
+    let allowed_image_types = match item.get("allowed_image_types") {
+        Some(AttributeValue::Ss(types)) => Some(types.clone()),
+        Some(AttributeValue::L(types)) => Some(
+            types
+                .iter()
+                .map(|t| t.as_s().ok().cloned())
+                .collect::<Option<Vec<String>>>()
+                .ok_or("allowed_image_types")?,
+        ),
+        Some(_) => return Err("allowed_image_types"),
+        None => None,
+    };
+    Ok(TenantOverrides {
+        max_upload_bytes: number("max_upload_bytes")?
+            .map(usize::try_from)
+            .transpose()
+            .map_err(|_| "max_upload_bytes")?,
+        max_uploads_per_day: number("max_uploads_per_day")?,
+        allowed_image_types,
+    })
+}
+
+/// Prefix of the partition keys under which earlier meme versions are stored in the meta
+/// table, followed by the meme ID.
+pub(crate) const HISTORY_PK_PREFIX: &str = "meme-history#";
+
+/// Partition key of a meme's history.
+pub(crate) fn history_partition(id: Uuid) -> String {
+    format!("{}{}", HISTORY_PK_PREFIX, id)
+}
+
+/// Sort key of a version: zero-padded, so versions sort numerically.
+pub(crate) fn history_sort_key(version: u64) -> String {
+    format!("{:020}", version)
+}
+
+/// Stores earlier versions of memes in the auxiliary meta table (pk = "meme-history#<id>",
+/// sk = zero-padded version), with each version as JSON in `meme`.
+#[derive(Debug, Clone)]
+pub struct DynamoDbMemeHistoryRepository {
+    client: DynamoDbClient,
+    table_name: String,
+}
+
+impl DynamoDbMemeHistoryRepository {
+    pub fn new(client: DynamoDbClient, table_name: String) -> Self {
+        info!(%table_name, "Initializing DynamoDbMemeHistoryRepository");
+        Self { client, table_name }
+    }
+
+    fn parse_version(&self, item: &HashMap<String, AttributeValue>) -> Result<Meme, RepoError> {
+        item.get("meme")
+            .and_then(|value| value.as_s().ok())
+            .and_then(|json| serde_json::from_str(json).ok())
+            .ok_or_else(|| RepoError::DataCorruption {
+                field: "meme".to_string(),
+                reason: format!("Malformed meme version in table '{}'", self.table_name),
+            })
+    }
+
+    /// Queries a meme's history partition, newest version first. Handles pagination.
+    async fn query_versions(&self, id: Uuid) -> Result<Vec<HashMap<String, AttributeValue>>, RepoError> {
+        let mut items = Vec::new();
+        let mut last_evaluated_key: Option<HashMap<String, AttributeValue>> = None;
+
+        loop {
+            let resp = self.client
+                .query()
+                .table_name(&self.table_name)
+                .key_condition_expression("pk = :pk")
+                .expression_attribute_values(":pk", AttributeValue::S(history_partition(id)))
+                .scan_index_forward(false)
+                .set_exclusive_start_key(last_evaluated_key)
+                .send()
+                .await
+                .context(format!("DynamoDB (table: {}): Failed to query history of meme {}", self.table_name, id))
+                .map_err(RepoError::BackendError)?;
+
+            items.extend(resp.items.unwrap_or_default());
+            last_evaluated_key = resp.last_evaluated_key;
+            if last_evaluated_key.is_none() {
+                break;
+            }
+        }
+        Ok(items)
+    }
+}
+
+#[async_trait]
+impl MemeHistoryRepository for DynamoDbMemeHistoryRepository {
+    async fn save_version(&self, meme: &Meme) -> Result<(), RepoError> {
+        let json = serde_json::to_string(meme)
+            .context("Failed to encode meme version")
+            .map_err(RepoError::BackendError)?;
+        self.client
+            .put_item()
+            .table_name(&self.table_name)
+            .item("pk", AttributeValue::S(history_partition(meme.meme_id)))
+            .item("sk", AttributeValue::S(history_sort_key(meme.version)))
+            .item("meme", AttributeValue::S(json))
+            .send()
+            .await
+            .context(format!(
+                "DynamoDB (table: {}): Failed to save version {} of meme {}",
+                self.table_name, meme.version, meme.meme_id
+            ))
+            .map_err(RepoError::BackendError)?;
+        Ok(())
+    }
+
+    async fn list_versions(&self, id: Uuid) -> Result<Vec<Meme>, RepoError> {
+        self.query_versions(id).await?.iter().map(|item| self.parse_version(item)).collect()
+    }
+
+    async fn get_version(&self, id: Uuid, version: u64) -> Result<Option<Meme>, RepoError> {
+        let resp = self.client
+            .get_item()
+            .table_name(&self.table_name)
+            .key("pk", AttributeValue::S(history_partition(id)))
+            .key("sk", AttributeValue::S(history_sort_key(version)))
+            .send()
+            .await
+            .context(format!("DynamoDB (table: {}): Failed to get version {} of meme {}", self.table_name, version, id))
+            .map_err(RepoError::BackendError)?;
+        resp.item.map(|item| self.parse_version(&item)).transpose()
+    }
+
+    /// Deletes the versions one by one; a meme has few.
+    async fn delete_versions(&self, id: Uuid) -> Result<(), RepoError> {
+        for item in self.query_versions(id).await? {
+            let Some(sort_key) = item.get("sk").cloned() else { continue };
+            self.client
+                .delete_item()
+                .table_name(&self.table_name)
+                .key("pk", AttributeValue::S(history_partition(id)))
+                .key("sk", sort_key)
+                .send()
+                .await
+                .context(format!("DynamoDB (table: {}): Failed to delete history of meme {}", self.table_name, id))
+                .map_err(RepoError::BackendError)?;
+        }
+        Ok(())
+    }
+}
+
+/// Partition key under which the audit log is stored in the meta table; tenants append
+/// `#<tenant>`, since they share the meta table.
+pub(crate) const AUDIT_PK: &str = "audit";
+
+/// The audit log's partition key for `tenant`.
+pub(crate) fn audit_partition(tenant: Option<&str>) -> String {
+    match tenant {
+        Some(tenant) => format!("{}#{}", AUDIT_PK, tenant),
+        None => AUDIT_PK.to_string(),
+    }
+}
+
+/// Sort key of an audit entry: its fixed-width UTC time, which sorts chronologically,
+/// followed by its ID so entries recorded at the same instant do not collide.
+pub(crate) fn audit_sort_key(entry: &AuditEntry) -> String {
+    format!("{}#{}", entry.recorded_at.to_rfc3339_opts(SecondsFormat::Nanos, true), entry.id)
+}
+
+/// Stores the audit log in the auxiliary meta table (pk = "audit" or "audit#<tenant>",
+/// sk = time and entry ID), with each entry as JSON in `entry`.
+#[derive(Debug, Clone)]
+pub struct DynamoDbAuditRepository {
+    client: DynamoDbClient,
+    table_name: String,
+    partition: String,
+}
+
+impl DynamoDbAuditRepository {
+    pub fn new(client: DynamoDbClient, table_name: String, tenant: Option<&str>) -> Self {
+        info!(%table_name, ?tenant, "Initializing DynamoDbAuditRepository");
+        Self { client, table_name, partition: audit_partition(tenant) }
+    }
+}
+
+#[async_trait]
+impl AuditRepository for DynamoDbAuditRepository {
+    /// Puts the entry on the condition that its key is new, so nothing is overwritten.
+    async fn append(&self, entry: &AuditEntry) -> Result<(), RepoError> {
+        let json = serde_json::to_string(entry)
+            .context("Failed to encode audit entry")
+            .map_err(RepoError::BackendError)?;
+        self.client
+            .put_item()
+            .table_name(&self.table_name)
+            .item("pk", AttributeValue::S(self.partition.clone()))
+            .item("sk", AttributeValue::S(audit_sort_key(entry)))
+            .item("entry", AttributeValue::S(json))
+            .condition_expression("attribute_not_exists(pk)")
+            .send()
+            .await
+            .context(format!("DynamoDB (table: {}): Failed to append audit entry (meme: {})", self.table_name, entry.meme_id))
+            .map_err(RepoError::BackendError)?;
+        Ok(())
+    }
+
+    /// Queries the partition from `since` on in key order. Handles pagination.
+    async fn list_since(&self, since: DateTime<Utc>, limit: usize) -> Result<Vec<AuditEntry>, RepoError> {
+        let mut entries = Vec::new();
+        let mut last_evaluated_key: Option<HashMap<String, AttributeValue>> = None;
+
+        while entries.len() < limit {
+            let resp = self.client
+                .query()
+                .table_name(&self.table_name)
+                .key_condition_expression("pk = :pk AND sk >= :since")
+                .expression_attribute_values(":pk", AttributeValue::S(self.partition.clone()))
+                .expression_attribute_values(":since", AttributeValue::S(since.to_rfc3339_opts(SecondsFormat::Nanos, true)))
+                .limit(i32::try_from(limit - entries.len()).unwrap_or(i32::MAX))
+                .set_exclusive_start_key(last_evaluated_key)
+                .send()
+                .await
+                .context(format!("DynamoDB (table: {}): Failed to query the audit log", self.table_name))
+                .map_err(RepoError::BackendError)?;
+
+            for item in resp.items.unwrap_or_default() {
+                let entry = item
+                    .get("entry")
+                    .and_then(|value| value.as_s().ok())
+                    .and_then(|json| serde_json::from_str(json).ok())
+                    .ok_or_else(|| RepoError::DataCorruption {
+                        field: "entry".to_string(),
+                        reason: format!("Malformed audit entry in table '{}'", self.table_name),
+                    })?;
+                entries.push(entry);
+            }
+            last_evaluated_key = resp.last_evaluated_key;
+            if last_evaluated_key.is_none() {
+                break;
+            }
+        }
+        Ok(entries)
+    }
+}
+
+/// Partition key under which applied schema migrations are recorded in the meta table;
+/// tenants append `#<tenant>`, like the audit log.
+pub(crate) const MIGRATION_PK: &str = "schema-migrations";
+
+/// The applied migrations' partition key for `tenant`.
+pub(crate) fn migration_partition(tenant: Option<&str>) -> String {
+    match tenant {
+        Some(tenant) => format!("{}#{}", MIGRATION_PK, tenant),
+        None => MIGRATION_PK.to_string(),
+    }
+}
+
+/// Records applied schema migrations in the auxiliary meta table (pk = "schema-migrations"
+/// or "schema-migrations#<tenant>", sk = migration ID), with the time in `applied_at`.
+#[derive(Debug, Clone)]
+pub struct DynamoDbMigrationRepository {
+    client: DynamoDbClient,
+    table_name: String,
+    partition: String,
+}
+
+impl DynamoDbMigrationRepository {
+    pub fn new(client: DynamoDbClient, table_name: String, tenant: Option<&str>) -> Self {
+        info!(%table_name, ?tenant, "Initializing DynamoDbMigrationRepository");
+        Self { client, table_name, partition: migration_partition(tenant) }
+    }
+}
+
+#[async_trait]
+impl MigrationRepository for DynamoDbMigrationRepository {
+    /// Queries the whole partition, with consistent reads so a step just recorded by
+    /// another instance is seen. Handles pagination.
+    async fn list_applied(&self) -> Result<Vec<AppliedMigration>, RepoError> {
+        let mut applied = Vec::new();
+        let mut last_evaluated_key: Option<HashMap<String, AttributeValue>> = None;
+
+        loop {
+            let resp = self.client
+                .query()
+                .table_name(&self.table_name)
+                .key_condition_expression("pk = :pk")
+                .expression_attribute_values(":pk", AttributeValue::S(self.partition.clone()))
+                .consistent_read(true)
+                .set_exclusive_start_key(last_evaluated_key)
+                .send()
+                .await
+                .context(format!("DynamoDB (table: {}): Failed to query applied migrations", self.table_name))
+                .map_err(RepoError::BackendError)?;
+
+            for item in resp.items.unwrap_or_default() {
+                let id = item.get("sk").and_then(|value| value.as_s().ok());
+                let applied_at = item
+                    .get("applied_at")
+                    .and_then(|value| value.as_s().ok())
+                    .and_then(|time| DateTime::parse_from_rfc3339(time).ok());
+                match (id, applied_at) {
+                    (Some(id), Some(applied_at)) => {
+                        applied.push(AppliedMigration { id: id.clone(), applied_at: applied_at.with_timezone(&Utc) })
+                    }
+                    _ => {
+                        return Err(RepoError::DataCorruption {
+                            field: "applied_at".to_string(),
+                            reason: format!("Malformed migration record in table '{}'", self.table_name),
+                        })
+                    }
+                }
+            }
+            last_evaluated_key = resp.last_evaluated_key;
+            if last_evaluated_key.is_none() {
+                break;
+            }
+        }
+        Ok(applied)
+    }
+
+    async fn record(&self, migration: &AppliedMigration) -> Result<(), RepoError> {
+        self.client
+            .put_item()
+            .table_name(&self.table_name)
+            .item("pk", AttributeValue::S(self.partition.clone()))
+            .item("sk", AttributeValue::S(migration.id.clone()))
+            .item("applied_at", AttributeValue::S(migration.applied_at.to_rfc3339_opts(SecondsFormat::Secs, true)))
+            .send()
+            .await
+            .context(format!("DynamoDB (table: {}): Failed to record migration '{}'", self.table_name, migration.id))
+            .map_err(RepoError::BackendError)?;
+        Ok(())
+    }
+}
+
+/// Partition key under which backfill progress is stored in the meta table; tenants append
+/// `#<tenant>`, like the audit log.
+pub(crate) const BACKFILL_PK: &str = "backfill";
+
+/// The backfill progress partition key for `tenant`.
+pub(crate) fn backfill_partition(tenant: Option<&str>) -> String {
+    match tenant {
+        Some(tenant) => format!("{}#{}", BACKFILL_PK, tenant),
+        None => BACKFILL_PK.to_string(),
+    }
+}
+
+/// Stores backfill progress in the auxiliary meta table (pk = "backfill" or
+/// "backfill#<tenant>", sk = field), with the progress as JSON in `progress`.
+#[derive(Debug, Clone)]
+pub struct DynamoDbBackfillRepository {
+    client: DynamoDbClient,
+    table_name: String,
+    partition: String,
+}
+
+impl DynamoDbBackfillRepository {
+    pub fn new(client: DynamoDbClient, table_name: String, tenant: Option<&str>) -> Self {
+        info!(%table_name, ?tenant, "Initializing DynamoDbBackfillRepository");
+        Self { client, table_name, partition: backfill_partition(tenant) }
+    }
+
+    fn progress_of(&self, item: &HashMap<String, AttributeValue>) -> Result<BackfillProgress, RepoError> {
+        item.get("progress")
+            .and_then(|value| value.as_s().ok())
+            .and_then(|json| serde_json::from_str(json).ok())
+            .ok_or_else(|| RepoError::DataCorruption {
+                field: "progress".to_string(),
+                reason: format!("Malformed backfill progress in table '{}'", self.table_name),
+            })
+    }
+}
+
+#[async_trait]
+impl BackfillRepository for DynamoDbBackfillRepository {
+    async fn load(&self, field: &str) -> Result<Option<BackfillProgress>, RepoError> {
+        let resp = self.client
+            .get_item()
+            .table_name(&self.table_name)
+            .key("pk", AttributeValue::S(self.partition.clone()))
+            .key("sk", AttributeValue::S(field.to_string()))
+            .consistent_read(true)
+            .send()
+            .await
+            .context(format!("DynamoDB (table: {}): Failed to get backfill progress (field: {})", self.table_name, field))
+            .map_err(RepoError::BackendError)?;
+        resp.item.as_ref().map(|item| self.progress_of(item)).transpose()
+    }
+
+    /// Queries the whole partition; there is one item per backfilled field.
+    async fn list(&self) -> Result<Vec<BackfillProgress>, RepoError> {
+        let resp = self.client
+            .query()
+            .table_name(&self.table_name)
+            .key_condition_expression("pk = :pk")
+            .expression_attribute_values(":pk", AttributeValue::S(self.partition.clone()))
+            .send()
+            .await
+            .context(format!("DynamoDB (table: {}): Failed to query backfill progress", self.table_name))
+            .map_err(RepoError::BackendError)?;
+        resp.items().iter().map(|item| self.progress_of(item)).collect()
+    }
+
+    async fn save(&self, progress: &BackfillProgress) -> Result<(), RepoError> {
+        let json = serde_json::to_string(progress)
+            .context("Failed to encode backfill progress")
+            .map_err(RepoError::BackendError)?;
+        self.client
+            .put_item()
+            .table_name(&self.table_name)
+            .item("pk", AttributeValue::S(self.partition.clone()))
+            .item("sk", AttributeValue::S(progress.field.clone()))
+            .item("progress", AttributeValue::S(json))
+            .send()
+            .await
+            .context(format!("DynamoDB (table: {}): Failed to save backfill progress (field: {})", self.table_name, progress.field))
+            .map_err(RepoError::BackendError)?;
+        Ok(())
+    }
+}
+
+/// Partition key under which the trending rankings are stored in the meta table; tenants
+/// append `#<tenant>`, like the audit log.
+pub(crate) const TRENDING_PK: &str = "trending";
+
+/// Sort key of the one item holding the rankings.
+pub(crate) const TRENDING_SK: &str = "rankings";
+
+/// The trending rankings' partition key for `tenant`.
+pub(crate) fn trending_partition(tenant: Option<&str>) -> String {
+    match tenant {
+        Some(tenant) => format!("{}#{}", TRENDING_PK, tenant),
+        None => TRENDING_PK.to_string(),
+    }
+}
+
+/// Stores the trending rankings as one item of the auxiliary meta table (pk = "trending"
+/// or "trending#<tenant>", sk = "rankings"), with the snapshot as JSON in `snapshot`.
+#[derive(Debug, Clone)]
+pub struct DynamoDbTrendingRepository {
+    client: DynamoDbClient,
+    table_name: String,
+    partition: String,
+}
+
+impl DynamoDbTrendingRepository {
+    pub fn new(client: DynamoDbClient, table_name: String, tenant: Option<&str>) -> Self {
+        info!(%table_name, ?tenant, "Initializing DynamoDbTrendingRepository");
+        Self { client, table_name, partition: trending_partition(tenant) }
+    }
+}
+
+#[async_trait]
+impl TrendingRepository for DynamoDbTrendingRepository {
+    async fn load(&self) -> Result<Option<TrendingSnapshot>, RepoError> {
+        let resp = self.client
+            .get_item()
+            .table_name(&self.table_name)
+            .key("pk", AttributeValue::S(self.partition.clone()))
+            .key("sk", AttributeValue::S(TRENDING_SK.to_string()))
+            .send()
+            .await
+            .context(format!("DynamoDB (table: {}): Failed to get the trending rankings", self.table_name))
+            .map_err(RepoError::BackendError)?;
+        resp.item
+            .map(|item| {
+                item.get("snapshot")
+                    .and_then(|value| value.as_s().ok())
+                    .and_then(|json| serde_json::from_str(json).ok())
+                    .ok_or_else(|| RepoError::DataCorruption {
+                        field: "snapshot".to_string(),
+                        reason: format!("Malformed trending rankings in table '{}'", self.table_name),
+                    })
+            })
+            .transpose()
+    }
+
+    async fn save(&self, snapshot: &TrendingSnapshot) -> Result<(), RepoError> {
+        let json = serde_json::to_string(snapshot)
+            .context("Failed to encode the trending rankings")
+            .map_err(RepoError::BackendError)?;
+        self.client
+            .put_item()
+            .table_name(&self.table_name)
+            .item("pk", AttributeValue::S(self.partition.clone()))
+            .item("sk", AttributeValue::S(TRENDING_SK.to_string()))
+            .item("snapshot", AttributeValue::S(json))
+            .send()
+            .await
+            .context(format!("DynamoDB (table: {}): Failed to save the trending rankings", self.table_name))
+            .map_err(RepoError::BackendError)?;
+        Ok(())
+    }
+}
+
+/// Partition key under which meme vectors are stored in the meta table; tenants append
+/// `#<tenant>`, like the audit log.
+pub(crate) const EMBEDDING_PK: &str = "embedding";
+
+/// The meme vectors' partition key for `tenant`.
+pub(crate) fn embedding_partition(tenant: Option<&str>) -> String {
+    match tenant {
+        Some(tenant) => format!("{}#{}", EMBEDDING_PK, tenant),
+        None => EMBEDDING_PK.to_string(),
+    }
+}
+
+/// Stores meme vectors in the auxiliary meta table (pk = "embedding" or
+/// "embedding#<tenant>", sk = meme ID), each as a JSON array in `vector`. Searches read the
+/// whole partition and compare every vector, which suits the thousands of memes a
+/// deployment of this size holds; a dedicated vector store can implement the same trait.
+#[derive(Debug, Clone)]
+pub struct DynamoDbVectorIndex {
+    client: DynamoDbClient,
+    table_name: String,
+    partition: String,
+}
+
+impl DynamoDbVectorIndex {
+    pub fn new(client: DynamoDbClient, table_name: String, tenant: Option<&str>) -> Self {
+        info!(%table_name, ?tenant, "Initializing DynamoDbVectorIndex");
+        Self { client, table_name, partition: embedding_partition(tenant) }
+    }
+}
+
+#[async_trait]
+impl VectorIndex for DynamoDbVectorIndex {
+    async fn upsert(&self, id: Uuid, vector: &[f32]) -> Result<(), RepoError> {
+        let json = serde_json::to_string(vector)
+            .context("Failed to encode the meme's vector")
+            .map_err(RepoError::BackendError)?;
+        self.client
+            .put_item()
+            .table_name(&self.table_name)
+            .item("pk", AttributeValue::S(self.partition.clone()))
+            .item("sk", AttributeValue::S(id.to_string()))
+            .item("vector", AttributeValue::S(json))
+            .send()
+            .await
+            .context(format!("DynamoDB (table: {}): Failed to store the vector of meme {}", self.table_name, id))
+            .map_err(RepoError::BackendError)?;
+        Ok(())
+    }
+
+    async fn remove(&self, id: Uuid) -> Result<(), RepoError> {
+        self.client
+            .delete_item()
+            .table_name(&self.table_name)
+            .key("pk", AttributeValue::S(self.partition.clone()))
+            .key("sk", AttributeValue::S(id.to_string()))
+            .send()
+            .await
+            .context(format!("DynamoDB (table: {}): Failed to delete the vector of meme {}", self.table_name, id))
+            .map_err(RepoError::BackendError)?;
+        Ok(())
+    }
+
+    /// Queries the whole partition, handling pagination, and ranks it in memory.
+    async fn nearest(&self, query: &[f32], limit: usize) -> Result<Vec<(Uuid, f32)>, RepoError> {
+        let mut vectors = Vec::new();
+        let mut last_evaluated_key: Option<HashMap<String, AttributeValue>> = None;
+        loop {
+            let resp = self.client
+                .query()
+                .table_name(&self.table_name)
+                .key_condition_expression("pk = :pk")
+                .expression_attribute_values(":pk", AttributeValue::S(self.partition.clone()))
+                .set_exclusive_start_key(last_evaluated_key)
+                .send()
+                .await
+                .context(format!("DynamoDB (table: {}): Failed to query meme vectors", self.table_name))
+                .map_err(RepoError::BackendError)?;
+
+            for item in resp.items.unwrap_or_default() {
+                let id = item.get("sk").and_then(|value| value.as_s().ok()).and_then(|sk| sk.parse().ok());
+                let vector = item.get("vector").and_then(|value| value.as_s().ok()).and_then(|json| serde_json::from_str(json).ok());
+                let (Some(id), Some(vector)) = (id, vector) else {
+                    return Err(RepoError::DataCorruption {
+                        field: "vector".to_string(),
+                        reason: format!("Malformed meme vector in table '{}'", self.table_name),
+                    });
+                };
+                vectors.push((id, vector));
+            }
+            last_evaluated_key = resp.last_evaluated_key;
+            if last_evaluated_key.is_none() {
+                break;
+            }
+        }
+        Ok(embeddings::nearest(vectors, query, limit))
+    }
+}
+
+/// Partition key under which shortlinks are stored in the meta table; tenants append
+/// `#<tenant>`, like the audit log.
+pub(crate) const SHORTLINK_PK: &str = "shortlink";
+
+/// The shortlinks' partition key for `tenant`.
+pub(crate) fn shortlink_partition(tenant: Option<&str>) -> String {
+    match tenant {
+        Some(tenant) => format!("{}#{}", SHORTLINK_PK, tenant),
+        None => SHORTLINK_PK.to_string(),
+    }
+}
+
+/// Stores shortlinks in the auxiliary meta table (pk = "shortlink" or "shortlink#<tenant>",
+/// sk = code), with the meme, target and creation time as attributes and the redirects
+/// counted in a numeric `hits` attribute.
+#[derive(Debug, Clone)]
+pub struct DynamoDbShortlinkRepository {
+    client: DynamoDbClient,
+    table_name: String,
+    partition: String,
+}
+
+impl DynamoDbShortlinkRepository {
+    pub fn new(client: DynamoDbClient, table_name: String, tenant: Option<&str>) -> Self {
+        info!(%table_name, ?tenant, "Initializing DynamoDbShortlinkRepository");
+        Self { client, table_name, partition: shortlink_partition(tenant) }
+    }
+
+    fn shortlink_of(&self, code: &str, item: &HashMap<String, AttributeValue>) -> Result<Shortlink, RepoError> {
+        let text = |name: &str| item.get(name).and_then(|value| value.as_s().ok());
+        let meme_id = text("meme_id").and_then(|id| id.parse().ok());
+        let target = match text("target").map(String::as_str) {
+            Some("page") => Some(ShortlinkTarget::Page),
+            Some("image") => Some(ShortlinkTarget::Image),
+            _ => None,
+        };
+        let created_at = text("created_at").and_then(|at| DateTime::parse_from_rfc3339(at).ok());
+        let hits = item.get("hits").and_then(|value| value.as_n().ok()).and_then(|hits| hits.parse().ok());
+        let (Some(meme_id), Some(target), Some(created_at), Some(hits)) = (meme_id, target, created_at, hits) else {
+            return Err(RepoError::DataCorruption {
+                field: "shortlink".to_string(),
+                reason: format!("Malformed shortlink '{}' in table '{}'", code, self.table_name),
+            });
+        };
+        Ok(Shortlink { code: code.to_string(), meme_id, target, created_at: created_at.with_timezone(&Utc), hits })
+    }
+}
+
+#[async_trait]
+impl ShortlinkRepository for DynamoDbShortlinkRepository {
+    async fn create(&self, link: &Shortlink) -> Result<bool, RepoError> {
+        let result = self.client
+            .put_item()
+            .table_name(&self.table_name)
+            .item("pk", AttributeValue::S(self.partition.clone()))
+            .item("sk", AttributeValue::S(link.code.clone()))
+            .item("meme_id", AttributeValue::S(link.meme_id.to_string()))
+            .item("target", AttributeValue::S(link.target.as_str().to_string()))
+            .item("created_at", AttributeValue::S(link.created_at.to_rfc3339_opts(SecondsFormat::Millis, true)))
+            .item("hits", AttributeValue::N(link.hits.to_string()))
+            .condition_expression("attribute_not_exists(sk)")
+            .send()
+            .await;
+        match result {
+            Ok(_) => Ok(true),
+            Err(err) if matches!(err.as_service_error(), Some(PutItemError::ConditionalCheckFailedException(_))) => Ok(false),
+            Err(err) => Err(RepoError::BackendError(
+                anyhow::Error::new(err)
+                    .context(format!("DynamoDB (table: {}): Failed to create shortlink '{}'", self.table_name, link.code)),
+            )),
+        }
+    }
+
+    async fn get(&self, code: &str) -> Result<Option<Shortlink>, RepoError> {
+        let resp = self.client
+            .get_item()
+            .table_name(&self.table_name)
+            .key("pk", AttributeValue::S(self.partition.clone()))
+            .key("sk", AttributeValue::S(code.to_string()))
+            .send()
+            .await
+            .context(format!("DynamoDB (table: {}): Failed to get shortlink '{}'", self.table_name, code))
+            .map_err(RepoError::BackendError)?;
+        resp.item.as_ref().map(|item| self.shortlink_of(code, item)).transpose()
+    }
+
+    async fn add_hit(&self, code: &str) -> Result<(), RepoError> {
+        let result = self.client
+            .update_item()
+            .table_name(&self.table_name)
+            .key("pk", AttributeValue::S(self.partition.clone()))
+            .key("sk", AttributeValue::S(code.to_string()))
+            .update_expression("ADD hits :one")
+            // Without it, counting a deleted code would recreate it as a bare counter
+            .condition_expression("attribute_exists(sk)")
+            .expression_attribute_values(":one", AttributeValue::N("1".to_string()))
+            .send()
+            .await;
+        match result {
+            Ok(_) => Ok(()),
+            Err(err) if matches!(err.as_service_error(), Some(UpdateItemError::ConditionalCheckFailedException(_))) => Ok(()),
+            Err(err) => Err(RepoError::BackendError(
+                anyhow::Error::new(err)
+                    .context(format!("DynamoDB (table: {}): Failed to count a hit of shortlink '{}'", self.table_name, code)),
+            )),
+        }
+    }
+}
+
+/// Partition key under which the ActivityPub actor's followers are stored in the meta table.
+/// Only the deployment itself federates, so there are no tenant partitions.
+pub(crate) const FOLLOWER_PK: &str = "follower";
+
+/// Stores the ActivityPub actor's followers in the auxiliary meta table (pk = "follower",
+/// sk = actor ID), with their inbox and the time they followed as attributes.
+#[derive(Debug, Clone)]
+pub struct DynamoDbFollowerRepository {
+    client: DynamoDbClient,
+    table_name: String,
+}
+
+impl DynamoDbFollowerRepository {
+    pub fn new(client: DynamoDbClient, table_name: String) -> Self {
+        info!(%table_name, "Initializing DynamoDbFollowerRepository");
+        Self { client, table_name }
+    }
+}
+
+#[async_trait]
+impl FollowerRepository for DynamoDbFollowerRepository {
+    async fn add(&self, follower: &Follower) -> Result<(), RepoError> {
+        self.client
+            .put_item()
+            .table_name(&self.table_name)
+            .item("pk", AttributeValue::S(FOLLOWER_PK.to_string()))
+            .item("sk", AttributeValue::S(follower.actor.clone()))
+            .item("inbox", AttributeValue::S(follower.inbox.clone()))
+            .item("followed_at", AttributeValue::S(follower.followed_at.to_rfc3339_opts(SecondsFormat::Millis, true)))
+            .send()
+            .await
+            .context(format!("DynamoDB (table: {}): Failed to add follower '{}'", self.table_name, follower.actor))
+            .map_err(RepoError::BackendError)?;
+        Ok(())
+    }
+
+    async fn remove(&self, actor: &str) -> Result<(), RepoError> {
+        self.client
+            .delete_item()
+            .table_name(&self.table_name)
+            .key("pk", AttributeValue::S(FOLLOWER_PK.to_string()))
+            .key("sk", AttributeValue::S(actor.to_string()))
+            .send()
+            .await
+            .context(format!("DynamoDB (table: {}): Failed to remove follower '{}'", self.table_name, actor))
+            .map_err(RepoError::BackendError)?;
+        Ok(())
+    }
+
+    /// Queries all followers under the follower partition. Handles pagination.
+    async fn list(&self) -> Result<Vec<Follower>, RepoError> {
+        let mut followers = Vec::new();
+        let mut last_evaluated_key: Option<HashMap<String, AttributeValue>> = None;
+
+        loop {
+            let resp = self.client
+                .query()
+                .table_name(&self.table_name)
+                .key_condition_expression("pk = :pk")
+                .expression_attribute_values(":pk", AttributeValue::S(FOLLOWER_PK.to_string()))
+                .set_exclusive_start_key(last_evaluated_key)
+                .send()
+                .await
+                .context(format!("DynamoDB (table: {}): Failed to query followers", self.table_name))
+                .map_err(RepoError::BackendError)?;
+
+            for item in resp.items.unwrap_or_default() {
+                let text = |name: &str| item.get(name).and_then(|value| value.as_s().ok());
+                let followed_at = text("followed_at").and_then(|at| DateTime::parse_from_rfc3339(at).ok());
+                let (Some(actor), Some(inbox), Some(followed_at)) = (text("sk"), text("inbox"), followed_at) else {
+                    return Err(RepoError::DataCorruption {
+                        field: "follower".to_string(),
+                        reason: format!("Malformed follower in table '{}'", self.table_name),
+                    });
+                };
+                followers.push(Follower { actor: actor.clone(), inbox: inbox.clone(), followed_at: followed_at.with_timezone(&Utc) });
+            }
+
+            last_evaluated_key = resp.last_evaluated_key;
+            if last_evaluated_key.is_none() {
+                break;
+            }
+        }
+        Ok(followers)
+    }
+}
+
+/// A meme item as stored, (de)serialized with `serde_dynamo`. Mirrors [`Meme`] but for the
+/// timestamps: `expires_at` is epoch seconds (a number, as TTL needs) and `created_at` and
+/// `updated_at` fixed-width UTC strings, which sorts chronologically. Attributes added after the first
+/// release default, so older items still read.
+#[derive(Serialize, Deserialize)]
+struct MemeItem {
+    meme_id: Uuid,
+    title: String,
+    description: String,
+    image_key: String,
+    #[serde(default)]
+    tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    source_url: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none", with = "chrono::serde::ts_seconds_option")]
+    expires_at: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none", serialize_with = "fixed_width_timestamp")]
+    created_at: Option<DateTime<Utc>>,
+    #[serde(default = "crate::models::initial_version")]
+    version: u64,
+    #[serde(default)]
+    like_count: u64,
+    #[serde(default)]
+    visibility: Visibility,
+    #[serde(default, skip_serializing_if = "Option::is_none", with = "chrono::serde::ts_seconds_option")]
+    publish_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    status: MemeStatus,
+    #[serde(default)]
+    view_count: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    caption_text: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    dominant_color: Option<String>,
+    #[serde(default)]
+    palette: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    width: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    height: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    size_bytes: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    content_hash: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none", serialize_with = "fixed_width_timestamp")]
+    updated_at: Option<DateTime<Utc>>,
+}
+
+fn fixed_width_timestamp<S: serde::Serializer>(timestamp: &Option<DateTime<Utc>>, serializer: S) -> Result<S::Ok, S::Error> {
+    match timestamp {
+        Some(timestamp) => serializer.serialize_some(&timestamp.to_rfc3339_opts(SecondsFormat::Nanos, true)),
+        None => serializer.serialize_none(),
+    }
+}
+
+impl From<&Meme> for MemeItem {
+    fn from(meme: &Meme) -> Self {
+        Self {
+            meme_id: meme.meme_id,
+            title: meme.title.clone(),
+            description: meme.description.clone(),
+            image_key: meme.image_key.clone(),
+            tags: meme.tags.clone(),
+            source_url: meme.source_url.clone(),
+            expires_at: meme.expires_at,
+            created_at: meme.created_at,
+            version: meme.version,
+            like_count: meme.like_count,
+            visibility: meme.visibility,
+            publish_at: meme.publish_at,
+            status: meme.status,
+            view_count: meme.view_count,
+            caption_text: meme.caption_text.clone(),
+            dominant_color: meme.dominant_color.clone(),
+            palette: meme.palette.clone(),
+            width: meme.width,
+            height: meme.height,
+            size_bytes: meme.size_bytes,
+            content_hash: meme.content_hash.clone(),
+            updated_at: meme.updated_at,
+        }
+    }
+}
+
+impl From<MemeItem> for Meme {
+    fn from(item: MemeItem) -> Self {
+        Self {
+            meme_id: item.meme_id,
+            title: item.title,
+            description: item.description,
+            image_key: item.image_key,
+            tags: item.tags,
+            source_url: item.source_url,
+            expires_at: item.expires_at,
+            created_at: item.created_at,
+            version: item.version,
+            like_count: item.like_count,
+            visibility: item.visibility,
+            publish_at: item.publish_at,
+            status: item.status,
+            view_count: item.view_count,
+            caption_text: item.caption_text,
+            dominant_color: item.dominant_color,
+            palette: item.palette,
+            width: item.width,
+            height: item.height,
+            size_bytes: item.size_bytes,
+            content_hash: item.content_hash,
+            updated_at: item.updated_at,
+        }
+    }
+}
+
+/// Attributes a meme item only has while the meme has a value for them, including the TTL
+/// derived from `expires_at`.
+const OPTIONAL_ATTRIBUTES: &[&str] = &[
+    "source_url",
+    "expires_at",
+    MEME_TTL_ATTRIBUTE,
+    "created_at",
+    "publish_at",
+    "caption_text",
+    "dominant_color",
+    "width",
+    "height",
+    "size_bytes",
+    "content_hash",
+    "updated_at",
+];
+
+/// Builds the item stored for a meme: its attributes plus the listing index keys and TTL.
+/// Public for the benchmarks, like [`item_to_meme`].
+pub fn meme_to_item(meme: &Meme) -> HashMap<String, AttributeValue> {
+    // Strings, numbers and lists of strings only, which always serialize
+    let mut item: HashMap<String, AttributeValue> =
+        serde_dynamo::to_item(MemeItem::from(meme)).expect("meme items always serialize");
+    // Listing index keys; the title is sorted without regard to case
+    item.insert(LISTING_ATTRIBUTE.to_string(), AttributeValue::S(LISTING_PARTITION.to_string()));
+    item.insert("title_key".to_string(), AttributeValue::S(meme.title.to_lowercase()));
+    if let Some(expires_at) = meme.expires_at {
+        item.insert(MEME_TTL_ATTRIBUTE.to_string(), AttributeValue::N((expires_at.timestamp() + TTL_DELAY_SECS).to_string()));
+    }
+    item
+}
+
+/// Adds the listing index keys to meme items written before the indexes existed, so they
+/// show up in sorted listings. Returns how many items were updated.
+pub(crate) async fn backfill_listing_keys(client: &DynamoDbClient, table_name: &str) -> Result<u64, RepoError> {
+    let mut updated = 0;
+    let mut last_evaluated_key: Option<HashMap<String, AttributeValue>> = None;
+
+    loop {
+        let resp = client
+            .scan()
+            .table_name(table_name)
+            .filter_expression("attribute_not_exists(#listing)")
+            .projection_expression("meme_id, title")
+            .expression_attribute_names("#listing", LISTING_ATTRIBUTE)
+            .set_exclusive_start_key(last_evaluated_key)
+            .send()
+            .await
+            .context(format!("DynamoDB (table: {}): Failed to scan for items without listing keys", table_name))
+            .map_err(RepoError::BackendError)?;
+
+        for item in resp.items.unwrap_or_default() {
+            let (Some(meme_id), Some(title)) = (item.get("meme_id").cloned(), item.get("title").and_then(|v| v.as_s().ok())) else {
+                continue; // Not a meme; item_to_meme reports it when read
+            };
+            let result = client
+                .update_item()
+                .table_name(table_name)
+                .key("meme_id", meme_id)
+                .update_expression("SET #listing = :listing, title_key = :title_key")
+                // Deleted meanwhile; don't recreate it as a stub
+                .condition_expression("attribute_exists(meme_id)")
+                .expression_attribute_names("#listing", LISTING_ATTRIBUTE)
+                .expression_attribute_values(":listing", AttributeValue::S(LISTING_PARTITION.to_string()))
+                .expression_attribute_values(":title_key", AttributeValue::S(title.to_lowercase()))
+                .send()
+                .await;
+            match result {
+                Ok(_) => updated += 1,
+                Err(e) if matches!(e.as_service_error(), Some(UpdateItemError::ConditionalCheckFailedException(_))) => {}
+                Err(e) => {
+                    return Err(RepoError::BackendError(
+                        anyhow::Error::new(e).context(format!("DynamoDB (table: {}): Failed to add listing keys", table_name)),
+                    ))
+                }
+            }
+        }
+        last_evaluated_key = resp.last_evaluated_key;
+        if last_evaluated_key.is_none() {
+            break;
+        }
+    }
+    Ok(updated)
+}
+
+/// Reads a meme item. Also used to decode item images from the change stream. A missing or
+/// malformed attribute is reported by name (`tags[2]` for an element of a list).
+pub fn item_to_meme(item: &HashMap<String, AttributeValue>) -> Result<Meme, RepoError> {
+    let item = serde_dynamo::AttributeValue::M(serde_dynamo::Item::from(item.clone()).into());
+    serde_path_to_error::deserialize::<_, MemeItem>(serde_dynamo::Deserializer::from_attribute_value(item))
+        .map(Meme::from)
+        .map_err(|e| {
+            let reason = e.inner().to_string();
+            // Missing attributes fail on the item itself; the message names them
+            let field = match reason.strip_prefix("missing field `").and_then(|rest| rest.strip_suffix('`')) {
+                Some(field) => field.to_string(),
+                None => e.path().to_string(),
+            };
+            RepoError::DataCorruption { field, reason }
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generators::MemeBuilder;
+    use chrono::TimeZone;
+
+    fn text<'a>(item: &'a HashMap<String, AttributeValue>, name: &str) -> &'a str {
+        item[name].as_s().unwrap()
+    }
+
+    fn number<'a>(item: &'a HashMap<String, AttributeValue>, name: &str) -> &'a str {
+        item[name].as_n().unwrap()
+    }
+
+    /// A meme with a value for every optional attribute.
+    fn full_meme() -> Meme {
+        let at = Utc.with_ymd_and_hms(2024, 5, 1, 12, 30, 0).unwrap();
+        let precise = at + chrono::Duration::nanoseconds(5);
+        let mut meme = MemeBuilder::new()
+            .title("Round Trip")
+            .tags(["a", "b"])
+            .source_url("https://example.com/a.png")
+            .expires_at(at + chrono::Duration::days(1))
+            .created_at(precise)
+            .version(3)
+            .likes(4)
+            .views(5)
+            .visibility(Visibility::Unlisted)
+            .scheduled(at + chrono::Duration::hours(1))
+            .caption_text("text")
+            .palette(["#ff0000"])
+            .dimensions(2, 3)
+            .content_hash("abc")
+            .build();
+        meme.updated_at = Some(precise + chrono::Duration::minutes(1));
+        meme
+    }
+
+    #[test]
+    fn meme_items_round_trip() {
+        // Expiry and publish times are whole seconds; upload and update times keep nanoseconds
+        let meme = full_meme();
+        let read = item_to_meme(&meme_to_item(&meme)).unwrap();
+        assert_eq!(serde_json::to_value(&read).unwrap(), serde_json::to_value(&meme).unwrap());
+    }
+
+    #[test]
+    fn timestamps_keep_their_stored_formats() {
+        let at = Utc.with_ymd_and_hms(2024, 5, 1, 12, 30, 0).unwrap();
+        let mut meme = MemeBuilder::new()
+            .created_at(at)
+            .expires_at(at + chrono::Duration::seconds(60))
+            .scheduled(at + chrono::Duration::seconds(30))
+            .build();
+        meme.updated_at = Some(at + chrono::Duration::nanoseconds(1));
+        let item = meme_to_item(&meme);
+
+        // Fixed-width strings, which sort chronologically in the created_at index and
+        // count_created_since's key condition
+        assert_eq!(text(&item, "created_at"), "2024-05-01T12:30:00.000000000Z");
+        assert_eq!(text(&item, "updated_at"), "2024-05-01T12:30:00.000000001Z");
+        // Epoch seconds, as the TTL and the publishing scan need
+        assert_eq!(number(&item, "expires_at"), (at.timestamp() + 60).to_string());
+        assert_eq!(number(&item, "publish_at"), (at.timestamp() + 30).to_string());
+        assert_eq!(number(&item, MEME_TTL_ATTRIBUTE), (at.timestamp() + 60 + TTL_DELAY_SECS).to_string());
+        assert_eq!(text(&item, LISTING_ATTRIBUTE), LISTING_PARTITION);
+    }
+
+    #[test]
+    fn optional_attributes_are_left_out_and_default_when_read() {
+        let mut meme = MemeBuilder::new().build();
+        meme.created_at = None;
+        meme.width = None;
+        meme.height = None;
+        meme.size_bytes = None;
+        let item = meme_to_item(&meme);
+        for name in ["created_at", "updated_at", "expires_at", "publish_at", "source_url", "content_hash", MEME_TTL_ATTRIBUTE] {
+            assert!(!item.contains_key(name), "{} should not be stored", name);
+        }
+
+        // Items written before most attributes existed
+        let item = HashMap::from([
+            ("meme_id".to_string(), AttributeValue::S(meme.meme_id.to_string())),
+            ("title".to_string(), AttributeValue::S("Old".to_string())),
+            ("description".to_string(), AttributeValue::S("From the first release".to_string())),
+            ("image_key".to_string(), AttributeValue::S("old.png".to_string())),
+        ]);
+        let read = item_to_meme(&item).unwrap();
+        assert_eq!(read.version, crate::models::INITIAL_VERSION);
+        assert_eq!(read.visibility, Visibility::Public);
+        assert_eq!(read.status, MemeStatus::Published);
+        assert!(read.tags.is_empty() && read.created_at.is_none() && read.updated_at.is_none());
+    }
+
+    #[test]
+    fn updates_remove_every_attribute_a_meme_can_be_without() {
+        let mut bare = MemeBuilder::new().build();
+        bare.created_at = None;
+        bare.width = None;
+        bare.height = None;
+        bare.size_bytes = None;
+        let bare = meme_to_item(&bare);
+        let mut optional: Vec<_> = meme_to_item(&full_meme()).into_keys().filter(|name| !bare.contains_key(name)).collect();
+        optional.sort();
+        let mut expected = OPTIONAL_ATTRIBUTES.to_vec();
+        expected.sort();
+        assert_eq!(optional, expected);
+    }
+
+    #[test]
+    fn malformed_attributes_are_reported_by_name() {
+        let mut item = meme_to_item(&MemeBuilder::new().build());
+        item.insert("created_at".to_string(), AttributeValue::S("yesterday".to_string()));
+        let RepoError::DataCorruption { field, .. } = item_to_meme(&item).unwrap_err() else { panic!("expected corruption") };
+        assert_eq!(field, "created_at");
+
+        let mut item = meme_to_item(&MemeBuilder::new().build());
+        item.remove("title");
+        let RepoError::DataCorruption { field, .. } = item_to_meme(&item).unwrap_err() else { panic!("expected corruption") };
+        assert_eq!(field, "title");
+    }
 }
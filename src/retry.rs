@@ -0,0 +1,431 @@
+use crate::{
+    domain::{BlocklistRepository, ConsistencyLevel, FileStorage, MemeRepository, ObjectMetadata, StoredObject, TableInfo, UploadOptions},
+    errors::{RepoError, StorageError},
+    models::{Meme, SortOrder},
+};
+use async_trait::async_trait;
+use aws_sdk_s3::primitives::ByteStream;
+use axum::body::Bytes;
+use backoff::{backoff::Backoff, ExponentialBackoff};
+use chrono::{DateTime, Utc};
+use std::{
+    future::Future,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use uuid::Uuid;
+
+/// Retry tokens available when the service starts (and the most that can be saved up).
+const BUDGET_CAPACITY: f64 = 10.0;
+
+/// Jittered exponential backoff for runtime backend calls, limited by a shared retry budget.
+///
+/// Every call deposits `budget_ratio` tokens into the budget and every retry spends one, so
+/// over time at most that fraction of calls is retried. When a backend is failing hard the
+/// budget runs dry and calls fail after their first attempt instead of multiplying the load.
+#[derive(Debug)]
+pub struct RetryPolicy {
+    backend: &'static str,
+    max_attempts: u32,
+    base_delay: Duration,
+    max_delay: Duration,
+    budget_ratio: f64,
+    budget: Mutex<f64>,
+}
+
+impl RetryPolicy {
+    pub fn new(
+        backend: &'static str,
+        max_attempts: u32,
+        base_delay: Duration,
+        max_delay: Duration,
+        budget_ratio: f64,
+    ) -> Self {
+        Self {
+            backend,
+            max_attempts: max_attempts.max(1),
+            base_delay,
+            max_delay,
+            budget_ratio,
+            budget: Mutex::new(BUDGET_CAPACITY),
+        }
+    }
+
+    /// Runs `call` until it succeeds, fails with a non-retryable error, runs out of attempts,
+    /// or the retry budget is spent.
+    pub async fn run<T, E, F, Fut>(&self, operation: &'static str, mut call: F, retryable: fn(&E) -> bool) -> Result<T, E>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+        E: std::fmt::Display,
+    {
+        self.deposit();
+        let mut backoff = ExponentialBackoff {
+            initial_interval: self.base_delay,
+            max_interval: self.max_delay,
+            randomization_factor: 0.5, // Full-range jitter around each interval
+            multiplier: 2.0,
+            max_elapsed_time: None, // Bounded by max_attempts instead
+            ..Default::default()
+        };
+
+        let mut attempt = 1;
+        loop {
+            let error = match call().await {
+                Ok(value) => return Ok(value),
+                Err(e) => e,
+            };
+            if attempt >= self.max_attempts || !retryable(&error) {
+                return Err(error);
+            }
+            if !self.withdraw() {
+                metrics::counter!("backend_retry_budget_exhausted_total", "backend" => self.backend).increment(1);
+                tracing::warn!(backend = self.backend, operation, error = %error, "Retry budget exhausted, not retrying");
+                return Err(error);
+            }
+
+            let delay = backoff.next_backoff().unwrap_or(self.max_delay);
+            metrics::counter!("backend_retries_total", "backend" => self.backend, "operation" => operation).increment(1);
+            tracing::warn!(
+                backend = self.backend,
+                operation,
+                attempt,
+                delay_ms = delay.as_millis() as u64,
+                error = %error,
+                "Backend call failed, retrying"
+            );
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+
+    fn deposit(&self) {
+        let mut tokens = self.budget.lock().expect("retry budget lock poisoned");
+        *tokens = (*tokens + self.budget_ratio).min(BUDGET_CAPACITY);
+    }
+
+    fn withdraw(&self) -> bool {
+        let mut tokens = self.budget.lock().expect("retry budget lock poisoned");
+        if *tokens >= 1.0 {
+            *tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// Decorates a repository or storage backend with a [`RetryPolicy`].
+pub struct WithRetry<T> {
+    inner: T,
+    policy: Arc<RetryPolicy>,
+}
+
+impl<T> WithRetry<T> {
+    pub fn new(inner: T, policy: Arc<RetryPolicy>) -> Self {
+        Self { inner, policy }
+    }
+}
+
+/// Backend errors may be transient; missing or malformed data will not change on retry.
+fn repo_retryable(e: &RepoError) -> bool {
+    matches!(e, RepoError::BackendError(_))
+}
+
+fn storage_retryable(e: &StorageError) -> bool {
+    matches!(e, StorageError::UploadFailed(_) | StorageError::BackendError(_))
+}
+
+#[async_trait]
+impl<T: MemeRepository> MemeRepository for WithRetry<T> {
+    async fn create(&self, meme: &Meme) -> Result<(), RepoError> {
+        self.policy.run("create", || self.inner.create(meme), repo_retryable).await
+    }
+
+    async fn get_by_id(&self, id: Uuid, consistency: ConsistencyLevel) -> Result<Option<Meme>, RepoError> {
+        self.policy.run("get_by_id", || self.inner.get_by_id(id, consistency), repo_retryable).await
+    }
+
+    async fn exists(&self, id: Uuid) -> Result<bool, RepoError> {
+        self.policy.run("exists", || self.inner.exists(id), repo_retryable).await
+    }
+
+    async fn list_all(&self) -> Result<Vec<Meme>, RepoError> {
+        self.policy.run("list_all", || self.inner.list_all(), repo_retryable).await
+    }
+
+    async fn list_sorted(&self, order: SortOrder) -> Result<Vec<Meme>, RepoError> {
+        self.policy.run("list_sorted", || self.inner.list_sorted(order), repo_retryable).await
+    }
+
+    async fn list_expired(&self, now: DateTime<Utc>) -> Result<Vec<Meme>, RepoError> {
+        self.policy.run("list_expired", || self.inner.list_expired(now), repo_retryable).await
+    }
+
+    async fn list_due_for_publishing(&self, now: DateTime<Utc>) -> Result<Vec<Meme>, RepoError> {
+        self.policy.run("list_due_for_publishing", || self.inner.list_due_for_publishing(now), repo_retryable).await
+    }
+
+    async fn update(&self, meme: &Meme, expected_version: u64) -> Result<(), RepoError> {
+        self.policy.run("update", || self.inner.update(meme, expected_version), repo_retryable).await
+    }
+
+    async fn add_like(&self, id: Uuid) -> Result<u64, RepoError> {
+        // Not retried: an increment whose first attempt landed would count twice
+        self.inner.add_like(id).await
+    }
+
+    async fn add_views(&self, id: Uuid, views: u64) -> Result<(), RepoError> {
+        // Not retried either; views are approximate, so a failed flush is dropped
+        self.inner.add_views(id, views).await
+    }
+
+    async fn delete(&self, id: Uuid) -> Result<(), RepoError> {
+        self.policy.run("delete", || self.inner.delete(id), repo_retryable).await
+    }
+
+    async fn describe(&self) -> Result<TableInfo, RepoError> {
+        self.policy.run("describe", || self.inner.describe(), repo_retryable).await
+    }
+
+    async fn count_created_since(&self, since: DateTime<Utc>) -> Result<u64, RepoError> {
+        self.policy.run("count_created_since", || self.inner.count_created_since(since), repo_retryable).await
+    }
+
+    async fn count(&self) -> Result<u64, RepoError> {
+        self.policy.run("count", || self.inner.count(), repo_retryable).await
+    }
+
+    async fn ping(&self) -> Result<(), RepoError> {
+        self.inner.ping().await
+    }
+}
+
+#[async_trait]
+impl<T: BlocklistRepository> BlocklistRepository for WithRetry<T> {
+    async fn list_terms(&self) -> Result<Vec<String>, RepoError> {
+        self.policy.run("list_terms", || self.inner.list_terms(), repo_retryable).await
+    }
+
+    async fn add_term(&self, term: &str) -> Result<(), RepoError> {
+        self.policy.run("add_term", || self.inner.add_term(term), repo_retryable).await
+    }
+
+    async fn remove_term(&self, term: &str) -> Result<(), RepoError> {
+        self.policy.run("remove_term", || self.inner.remove_term(term), repo_retryable).await
+    }
+}
+
+#[async_trait]
+impl<T: FileStorage> FileStorage for WithRetry<T> {
+    async fn upload(&self, key: &str, data: Bytes, options: UploadOptions) -> Result<(), StorageError> {
+        self.policy
+            .run("upload", || self.inner.upload(key, data.clone(), options.clone()), storage_retryable)
+            .await
+    }
+
+    async fn download(&self, key: &str) -> Result<(ByteStream, ObjectMetadata), StorageError> {
+        // Only starting the download is retried; errors while streaming the body surface to the caller
+        self.policy.run("download", || self.inner.download(key), storage_retryable).await
+    }
+
+    async fn head(&self, key: &str) -> Result<ObjectMetadata, StorageError> {
+        self.policy.run("head", || self.inner.head(key), storage_retryable).await
+    }
+
+    async fn list(&self) -> Result<Vec<StoredObject>, StorageError> {
+        self.policy.run("list", || self.inner.list(), storage_retryable).await
+    }
+
+    /// Not retried: signing makes no request that could fail transiently.
+    async fn presigned_url(&self, key: &str, expires_in: Duration) -> Result<String, StorageError> {
+        self.inner.presigned_url(key, expires_in).await
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), StorageError> {
+        self.policy.run("delete", || self.inner.delete(key), storage_retryable).await
+    }
+
+    async fn copy(&self, from: &str, to: &str) -> Result<(), StorageError> {
+        self.policy.run("copy", || self.inner.copy(from, to), storage_retryable).await
+    }
+
+    /// A retry after an attempt that moved the file but lost the response finds `from` gone;
+    /// the rename then counts as done if `to` is there.
+    async fn rename(&self, from: &str, to: &str) -> Result<(), StorageError> {
+        let mut attempts = 0;
+        let result = self
+            .policy
+            .run(
+                "rename",
+                || {
+                    attempts += 1;
+                    self.inner.rename(from, to)
+                },
+                storage_retryable,
+            )
+            .await;
+        match result {
+            Err(StorageError::NotFound(_)) if attempts > 1 && self.inner.head(to).await.is_ok() => Ok(()),
+            result => result,
+        }
+    }
+
+    async fn ping(&self) -> Result<(), StorageError> {
+        self.inner.ping().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    const BASE_DELAY: Duration = Duration::from_millis(100);
+
+    fn policy(max_attempts: u32, budget_ratio: f64) -> RetryPolicy {
+        RetryPolicy::new("test", max_attempts, BASE_DELAY, Duration::from_secs(1), budget_ratio)
+    }
+
+    fn transient(e: &String) -> bool {
+        e == "transient"
+    }
+
+    /// Runs one call through `policy` that fails with `error` every time, and returns how
+    /// many attempts were made.
+    async fn attempts(policy: &RetryPolicy, error: &str) -> u32 {
+        let mut attempts = 0;
+        let result: Result<(), String> = policy
+            .run(
+                "test",
+                || {
+                    attempts += 1;
+                    async { Err(error.to_string()) }
+                },
+                transient,
+            )
+            .await;
+        assert_eq!(result, Err(error.to_string()));
+        attempts
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn retryable_errors_are_retried_with_backoff_up_to_max_attempts() {
+        let started = tokio::time::Instant::now();
+        assert_eq!(attempts(&policy(3, 0.1), "transient").await, 3);
+        // Two jittered delays of at least half the base delay each
+        assert!(started.elapsed() >= BASE_DELAY);
+
+        let mut attempts = 0;
+        let result = policy(3, 0.1)
+            .run(
+                "test",
+                || {
+                    attempts += 1;
+                    let attempt = attempts;
+                    async move { if attempt < 3 { Err("transient".to_string()) } else { Ok(attempt) } }
+                },
+                transient,
+            )
+            .await;
+        assert_eq!(result, Ok(3));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn other_errors_fail_right_away() {
+        let started = tokio::time::Instant::now();
+        assert_eq!(attempts(&policy(3, 0.1), "not found").await, 1);
+        assert_eq!(started.elapsed(), Duration::ZERO);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn an_empty_budget_stops_retries_until_calls_refill_it() {
+        let policy = policy(3, 0.5);
+        *policy.budget.lock().unwrap() = 0.0;
+        // The call's own deposit leaves half a token, too little for a retry
+        assert_eq!(attempts(&policy, "transient").await, 1);
+        // The next deposit makes a whole one
+        assert_eq!(attempts(&policy, "transient").await, 2);
+        assert_eq!(attempts(&policy, "transient").await, 1);
+    }
+
+    /// Storage of empty files whose renames move the file but fail the first time, as when
+    /// the response of a rename that landed is lost. Files cannot be downloaded or signed.
+    #[derive(Default)]
+    struct LostRenameResponse {
+        keys: Mutex<HashSet<String>>,
+        renames: Mutex<u32>,
+    }
+
+    #[async_trait]
+    impl FileStorage for LostRenameResponse {
+        async fn upload(&self, key: &str, _data: Bytes, _options: UploadOptions) -> Result<(), StorageError> {
+            self.keys.lock().unwrap().insert(key.to_string());
+            Ok(())
+        }
+
+        async fn download(&self, key: &str) -> Result<(ByteStream, ObjectMetadata), StorageError> {
+            Err(StorageError::NotFound(key.to_string()))
+        }
+
+        async fn head(&self, key: &str) -> Result<ObjectMetadata, StorageError> {
+            match self.keys.lock().unwrap().contains(key) {
+                true => Ok(ObjectMetadata::default()),
+                false => Err(StorageError::NotFound(key.to_string())),
+            }
+        }
+
+        async fn list(&self) -> Result<Vec<StoredObject>, StorageError> {
+            Ok(self.keys.lock().unwrap().iter().map(|key| StoredObject { key: key.clone(), size: 0 }).collect())
+        }
+
+        async fn presigned_url(&self, key: &str, _expires_in: Duration) -> Result<String, StorageError> {
+            Err(StorageError::NotFound(key.to_string()))
+        }
+
+        async fn delete(&self, key: &str) -> Result<(), StorageError> {
+            match self.keys.lock().unwrap().remove(key) {
+                true => Ok(()),
+                false => Err(StorageError::NotFound(key.to_string())),
+            }
+        }
+
+        async fn copy(&self, from: &str, to: &str) -> Result<(), StorageError> {
+            let mut keys = self.keys.lock().unwrap();
+            if !keys.contains(from) {
+                return Err(StorageError::NotFound(from.to_string()));
+            }
+            keys.insert(to.to_string());
+            Ok(())
+        }
+
+        async fn rename(&self, from: &str, to: &str) -> Result<(), StorageError> {
+            let mut keys = self.keys.lock().unwrap();
+            if !keys.remove(from) {
+                return Err(StorageError::NotFound(from.to_string()));
+            }
+            keys.insert(to.to_string());
+            let mut renames = self.renames.lock().unwrap();
+            *renames += 1;
+            match *renames {
+                1 => Err(StorageError::BackendError(anyhow::anyhow!("connection reset"))),
+                _ => Ok(()),
+            }
+        }
+
+        async fn ping(&self) -> Result<(), StorageError> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn a_rename_that_landed_before_its_retry_succeeds() {
+        let storage = WithRetry::new(LostRenameResponse::default(), Arc::new(policy(3, 0.1)));
+        storage.upload("from", Bytes::new(), UploadOptions::default()).await.unwrap();
+        storage.rename("from", "to").await.unwrap();
+        assert!(storage.head("to").await.is_ok());
+
+        // A source that was never there is still an error
+        assert!(matches!(storage.rename("missing", "elsewhere").await, Err(StorageError::NotFound(_))));
+    }
+}
@@ -1,37 +1,343 @@
 use crate::{
+    access_log,
+    activitypub,
+    admin,
+    auth,
+    backends::StorageBackend,
+    body_limit::BodyLimitLayer,
+    body_logging,
+    cdn,
+    embeds,
+    client_ip,
+    config::Config,
+    error_reporting,
+    errors::AppError,
+    formats,
     handlers,
+    panics,
+    progress,
+    share,
+    shortlinks,
+    shutdown,
+    sitemap,
+    slack,
+    slow_requests,
+    telemetry,
+    tenant,
+    timeout,
+    tus,
+    xray,
     AppState,
 };
+#[cfg(feature = "discord")]
+use crate::discord;
+#[cfg(feature = "telegram")]
+use crate::telegram;
 use axum::{
-    extract::DefaultBodyLimit,
-    routing::{get, post},
+    extract::{DefaultBodyLimit, OriginalUri, Request},
+    http::{header, HeaderName, Method},
+    middleware,
+    routing::{delete, get, head, post},
     Router,
 };
-use std::sync::Arc;
+use sentry::integrations::tower::NewSentryLayer;
+use std::{sync::Arc, time::Duration};
 use tower_http::{
-    cors::{Any, CorsLayer},
+    cors::{AllowHeaders, AllowMethods, AllowOrigin, CorsLayer},
+    request_id::{MakeRequestUuid, PropagateRequestIdLayer, SetRequestIdLayer},
     trace::TraceLayer,
 };
 
-/// Creates the Axum router and associates routes with handlers.
+/// Creates the public Axum router and associates routes with handlers.
+/// Operator routes (admin API, import, metrics) are included unless `APP_ADMIN_ADDRESS`
+/// moves them to the separate admin listener. With tenants configured, each tenant gets its
+/// own copy of the routes (see [`tenant::route_by_tenant`]).
 pub fn create_router(state: Arc<AppState>) -> Router {
-    Router::new()
-        .route("/health", get(handlers::health_check))
+    tenant::route_by_tenant(&state, public_router)
+}
+
+/// Creates the router for the admin listener: operator routes plus `/healthz`, per tenant
+/// like [`create_router`].
+#[cfg_attr(feature = "lambda", allow(dead_code))] // Lambda has a single entry point
+pub fn create_admin_router(state: Arc<AppState>) -> Router {
+    tenant::route_by_tenant(&state, admin_router)
+}
+
+fn public_router(state: Arc<AppState>) -> Router {
+    // Resumable uploads stage the image in S3 multipart uploads, so they need the S3 backend
+    let mut resumable_routes = Router::new();
+    if state.config.storage_backend == StorageBackend::S3 {
+        resumable_routes = resumable_routes
+            .route("/uploads/tus", post(tus::create_upload)) // OPTIONS: see tus::answer_discovery
+            .route("/uploads/tus/{id}",
+                head(tus::get_offset)
+                .patch(tus::append)
+                .delete(tus::terminate)
+            )
+            .route_layer(middleware::from_fn(tus::require_tus_version));
+    }
+
+    // Uploads move large bodies (or fetch remote images), so they get a longer time budget
+    let upload_routes = Router::new()
         .route("/upload_meme", post(handlers::upload_meme))
+        .route("/memes", post(handlers::create_meme_json)) // JSON upload (base64 or source_url)
+        .merge(resumable_routes)
+        .route_layer(middleware::from_fn_with_state(
+            Duration::from_secs(state.config.upload_timeout_secs),
+            timeout::enforce_timeout,
+        ))
+        // Outside the timeout, so uploads that time out are reported as failed
+        .route_layer(middleware::from_fn_with_state(state.clone(), progress::track_upload))
+        .route_layer(BodyLimitLayer::new(state.config.upload_body_limits.clone()));
+
+    // Share links are created by admins and opened by anyone holding the signed token
+    let share_routes = Router::new()
+        .route("/meme/{id}/share", post(handlers::create_share_link))
+        .route_layer(middleware::from_fn_with_state(state.clone(), auth::require_admin))
+        .merge(
+            Router::new()
+                .route("/shared/{token}", get(handlers::get_shared_meme))
+                .route("/shared/{token}/image", get(handlers::get_shared_image))
+                .route_layer(middleware::from_fn_with_state(state.clone(), share::require_share_token)),
+        );
+
+    // Chat platforms' callbacks, each checking its platform's signature
+    let mut bot_routes = Router::new();
+    if state.slack.as_ref().is_some_and(|slack| slack.accepts_requests()) {
+        bot_routes = bot_routes
+            .route("/slack/command", post(slack::handle_command))
+            .route("/slack/events", post(slack::handle_event));
+    }
+    #[cfg(feature = "discord")]
+    if state.discord.as_ref().is_some_and(|bot| bot.accepts_interactions()) {
+        bot_routes = bot_routes.route("/discord/interactions", post(discord::handle_interaction));
+    }
+    #[cfg(feature = "telegram")]
+    if state.telegram.as_ref().is_some_and(|bot| bot.uses_webhook()) {
+        bot_routes = bot_routes.route("/telegram/webhook", post(telegram::handle_webhook));
+    }
+
+    // Rich previews and the sitemap link to memes' pages, so they need the instance's public URL
+    let mut embed_routes = Router::new();
+    if state.config.public_url.is_some() {
+        embed_routes = embed_routes
+            .route("/oembed", get(embeds::get_oembed))
+            .route("/sitemap.xml", get(sitemap::get_sitemap));
+    }
+
+    // The instance's fediverse actor; the inbox checks every request's HTTP signature
+    let mut federation_routes = Router::new();
+    if state.activitypub.is_some() {
+        federation_routes = federation_routes
+            .route("/.well-known/webfinger", get(activitypub::webfinger))
+            .route("/activitypub/actor", get(activitypub::get_actor))
+            .route("/activitypub/inbox", post(activitypub::receive_activity))
+            .route("/activitypub/outbox", get(activitypub::get_outbox))
+            .route("/activitypub/followers", get(activitypub::get_followers))
+            .route("/activitypub/notes/{id}", get(activitypub::get_note));
+    }
+
+    let mut router = Router::new()
+        .route("/health", get(handlers::health_check))
         .route("/meme/{id}",
             get(handlers::get_meme)
+            .head(handlers::head_meme) // ETag only, without counting a view
+            .patch(handlers::update_meme) // Conditional on the meme's version (ETag/If-Match)
             .delete(handlers::delete_meme) // Add delete handler
         )
+        .route("/meme/{id}/history", get(handlers::get_meme_history))
+        .route("/meme/{id}/revert/{version}", post(handlers::revert_meme))
+        .route("/meme/{id}/publish", post(handlers::publish_meme))
+        .route("/meme/{id}/like", post(handlers::like_meme))
+        .route("/meme/{id}/download", get(handlers::download_meme))
+        .route("/meme/{id}/image", get(handlers::redirect_to_image))
+        .route("/meme/{id}/shortlink", post(shortlinks::create_shortlink))
+        .route("/s/{code}", get(shortlinks::follow_shortlink))
+        .route("/s/{code}/stats", get(shortlinks::get_shortlink_stats))
         .route("/memes", get(handlers::list_memes))
-        .route("/images/{key}", get(handlers::get_image))
+        .route("/memes/trending", get(handlers::trending_memes))
+        .route("/memes/search", get(handlers::search_memes))
+        .route("/uploads/{upload_id}/progress", get(progress::stream_progress)) // Server-sent events
+        .route("/stats", get(handlers::get_stats))
+        .route("/images/{*key}", get(handlers::get_image).head(handlers::head_image)) // HEAD skips the download
+        .route("/export", get(handlers::export_memes))
+        .route("/cdn/cookies", post(cdn::issue_signed_cookies))
+        .merge(share_routes)
+        .merge(bot_routes)
+        .merge(embed_routes)
+        .merge(federation_routes)
+        .route_layer(middleware::from_fn_with_state(
+            Duration::from_secs(state.config.request_timeout_secs),
+            timeout::enforce_timeout,
+        ))
+        .route_layer(BodyLimitLayer::new(state.config.body_limits.clone()))
+        .merge(upload_routes);
+    if state.config.admin_address.is_none() {
+        router = router.merge(operator_routes(&state));
+    }
+
+    router
+        // Inside each route's own layers, where the matched route is known
+        .route_layer(middleware::from_fn(error_reporting::tag_request))
+        .route_layer(middleware::from_fn_with_state(slow_request_threshold(&state.config), slow_requests::log_slow_requests))
+        // After all routes, so every path's method router gets the 405 handler
+        .fallback(route_not_found)
+        .method_not_allowed_fallback(method_not_allowed)
         // Middleware Layers
-        .layer(
-            CorsLayer::new()
-                .allow_origin(Any)
-                .allow_methods(Any) 
-                .allow_headers(Any),
-        )
+        .layer(middleware::from_fn(formats::negotiate_response_format))
+        .layer(cors_layer(&state.config))
+        .layer(middleware::from_fn_with_state(state.clone(), tus::answer_discovery))
         .layer(TraceLayer::new_for_http())
-        .layer(DefaultBodyLimit::max(10 * 1024 * 1024))
+        // Inside the request ID layer, so segments are annotated with it
+        .layer(middleware::from_fn_with_state(state.clone(), xray::trace_request))
+        // A Sentry hub per request, so scope tags stay with their request
+        .layer(NewSentryLayer::<Request>::new_from_top())
+        // Panics become 500s carrying the request ID rather than dropped connections
+        .layer(panics::catch_panics())
+        .layer(middleware::from_fn(panics::remember_request_id))
+        // Every request gets an X-Request-Id (unless the client sent one), echoed in the response
+        .layer(PropagateRequestIdLayer::x_request_id())
+        // Outside the format negotiation, so bodies are logged as sent
+        .layer(middleware::from_fn_with_state(state.clone(), body_logging::log_bodies))
+        // Outside everything that answers requests, and inside the request ID
+        .layer(middleware::from_fn_with_state(state.access_log.clone(), access_log::log_access))
+        // For the access log and everything inside it
+        .layer(middleware::from_fn_with_state(state.clone(), client_ip::resolve_client_ip))
+        .layer(SetRequestIdLayer::x_request_id(MakeRequestUuid))
+        .layer(middleware::from_fn_with_state(state.in_flight.clone(), shutdown::track_in_flight))
+        // Each route group limits bodies by media type with a `BodyLimitLayer` instead
+        .layer(DefaultBodyLimit::disable())
         .with_state(state)
 }
+
+/// Not meant for browsers, so no CORS layer.
+#[cfg_attr(feature = "lambda", allow(dead_code))]
+fn admin_router(state: Arc<AppState>) -> Router {
+    Router::new()
+        .route("/healthz", get(handlers::health_check))
+        .route_layer(middleware::from_fn_with_state(
+            Duration::from_secs(state.config.request_timeout_secs),
+            timeout::enforce_timeout,
+        ))
+        .merge(operator_routes(&state))
+        .route_layer(middleware::from_fn(error_reporting::tag_request))
+        .route_layer(middleware::from_fn_with_state(slow_request_threshold(&state.config), slow_requests::log_slow_requests))
+        .fallback(route_not_found)
+        .method_not_allowed_fallback(method_not_allowed)
+        .layer(middleware::from_fn(formats::negotiate_response_format))
+        .layer(TraceLayer::new_for_http())
+        .layer(middleware::from_fn_with_state(state.clone(), xray::trace_request))
+        .layer(NewSentryLayer::<Request>::new_from_top())
+        .layer(panics::catch_panics())
+        .layer(middleware::from_fn(panics::remember_request_id))
+        .layer(PropagateRequestIdLayer::x_request_id())
+        .layer(middleware::from_fn_with_state(state.clone(), body_logging::log_bodies))
+        .layer(middleware::from_fn_with_state(state.access_log.clone(), access_log::log_access))
+        .layer(middleware::from_fn_with_state(state.clone(), client_ip::resolve_client_ip))
+        .layer(SetRequestIdLayer::x_request_id(MakeRequestUuid))
+        .layer(middleware::from_fn_with_state(state.in_flight.clone(), shutdown::track_in_flight))
+        .layer(DefaultBodyLimit::disable())
+        .with_state(state)
+}
+
+/// Routes for operators rather than end users: the admin API, bulk import and metrics.
+fn operator_routes(state: &Arc<AppState>) -> Router<Arc<AppState>> {
+    // Admin routes, all guarded by the admin bearer token
+    let admin_routes = Router::new()
+        .route("/blocklist",
+            get(admin::list_blocklist)
+            .post(admin::add_blocklist_term)
+        )
+        .route("/blocklist/{term}", delete(admin::remove_blocklist_term))
+        .route("/backups", post(admin::create_backup))
+        .route("/backups/restore", post(admin::restore_backup))
+        .route("/expired/purge", post(admin::purge_expired))
+        .route("/resources", get(admin::describe_resources))
+        .route("/audit", get(admin::list_audit_log))
+        .route("/quarantine", get(admin::list_quarantine))
+        .route("/quarantine/{id}/approve", post(admin::approve_meme))
+        .route("/tenant-config", get(admin::get_tenant_config).put(admin::replace_tenant_config))
+        .route("/log_level", get(admin::get_log_level).put(admin::set_log_level))
+        .route("/backfills", get(admin::list_backfills))
+        .route("/backfills/{field}", post(admin::start_backfill))
+        .route_layer(middleware::from_fn_with_state(state.clone(), auth::require_admin))
+        .route_layer(BodyLimitLayer::new(state.config.body_limits.clone()));
+
+    // Imports stream whole archives, so they share the upload time budget and have no body
+    // limit (JSON manifests are limited by the handler)
+    let import_routes = Router::new()
+        .route("/import", post(admin::import_memes))
+        .route_layer(middleware::from_fn_with_state(state.clone(), auth::require_admin))
+        .route_layer(middleware::from_fn_with_state(
+            Duration::from_secs(state.config.upload_timeout_secs),
+            timeout::enforce_timeout,
+        ));
+
+    let mut router = Router::new();
+    if state.metrics.is_some() {
+        router = router.route("/metrics", get(telemetry::metrics_handler));
+    }
+    router
+        .nest("/admin", admin_routes)
+        .route_layer(middleware::from_fn_with_state(
+            Duration::from_secs(state.config.request_timeout_secs),
+            timeout::enforce_timeout,
+        ))
+        .merge(import_routes)
+}
+
+/// Fallback for paths no route matches, answered with the usual JSON error body.
+async fn route_not_found(OriginalUri(uri): OriginalUri) -> AppError {
+    AppError::RouteNotFound(uri.path().to_string())
+}
+
+/// Fallback for known paths requested with an unsupported method. Axum still sets `Allow`
+/// to the path's methods on this response. `OriginalUri` keeps the prefix of nested routes.
+async fn method_not_allowed(method: Method, OriginalUri(uri): OriginalUri) -> AppError {
+    AppError::MethodNotAllowed { method, path: uri.path().to_string() }
+}
+
+/// Threshold of [`slow_requests::log_slow_requests`]; `None` when set to 0.
+fn slow_request_threshold(config: &Config) -> Option<Duration> {
+    Some(Duration::from_millis(config.slow_request_threshold_ms)).filter(|threshold| !threshold.is_zero())
+}
+
+/// Builds the CORS policy from configuration. Entries were validated when the config was loaded.
+fn cors_layer(config: &Config) -> CorsLayer {
+    let is_any = |values: &[String]| values.iter().any(|v| v == "*");
+
+    let origins = if is_any(&config.cors_allowed_origins) {
+        AllowOrigin::any()
+    } else {
+        AllowOrigin::list(config.cors_allowed_origins.iter().filter_map(|o| o.parse().ok()))
+    };
+    let methods = if is_any(&config.cors_allowed_methods) {
+        AllowMethods::any()
+    } else {
+        AllowMethods::list(config.cors_allowed_methods.iter().filter_map(|m| m.parse().ok()))
+    };
+    let headers = if is_any(&config.cors_allowed_headers) {
+        AllowHeaders::any()
+    } else {
+        AllowHeaders::list(config.cors_allowed_headers.iter().filter_map(|h| h.parse().ok()))
+    };
+
+    CorsLayer::new()
+        .allow_origin(origins)
+        .allow_methods(methods)
+        .allow_headers(headers)
+        // Lets browser clients read them, e.g. to send the ETag back in If-Match, or to resume
+        // a tus upload
+        .expose_headers([
+            header::ETAG,
+            header::LOCATION,
+            HeaderName::from_static(auth::REQUEST_ID_HEADER),
+            tus::TUS_RESUMABLE,
+            tus::TUS_VERSION_HEADER,
+            tus::TUS_EXTENSION,
+            tus::TUS_MAX_SIZE,
+            tus::UPLOAD_OFFSET,
+            tus::UPLOAD_LENGTH,
+        ])
+        .max_age(Duration::from_secs(config.cors_max_age_secs))
+}
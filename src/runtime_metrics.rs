@@ -0,0 +1,52 @@
+//! Tokio runtime metrics, sampled periodically into the installed metrics recorder so they
+//! show up at `/metrics` (or in EMF) next to the application's own. Builds with
+//! `RUSTFLAGS="--cfg tokio_unstable"` also report the workers' local queues and the
+//! blocking pool, which shows whether uploads and streamed bodies wait for a thread.
+
+use std::time::Duration;
+use tokio::runtime::{Handle, RuntimeMetrics};
+
+/// Records a first sample of the current runtime and spawns the task sampling it every
+/// `interval` after that. Must be called from within the Tokio runtime.
+pub fn spawn_sampler(interval: Duration) {
+    let metrics = Handle::current().metrics();
+    let mut busy: Vec<Duration> = (0..metrics.num_workers()).map(|worker| metrics.worker_total_busy_duration(worker)).collect();
+    record(&metrics, &mut busy, interval);
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        ticker.tick().await; // The first tick is immediate
+        loop {
+            ticker.tick().await;
+            record(&metrics, &mut busy, interval);
+        }
+    });
+}
+
+/// Records one sample:
+/// - `tokio_workers`, `tokio_alive_tasks` and `tokio_global_queue_depth`,
+/// - `tokio_worker_busy_ratio{worker}`, the share of the last interval a worker spent
+///   polling tasks, and `tokio_worker_parks_total{worker}`,
+/// - with `tokio_unstable`, `tokio_worker_local_queue_depth{worker}`,
+///   `tokio_blocking_threads` and `tokio_blocking_queue_depth`.
+fn record(metrics: &RuntimeMetrics, busy: &mut [Duration], interval: Duration) {
+    metrics::gauge!("tokio_workers").set(metrics.num_workers() as f64);
+    metrics::gauge!("tokio_alive_tasks").set(metrics.num_alive_tasks() as f64);
+    metrics::gauge!("tokio_global_queue_depth").set(metrics.global_queue_depth() as f64);
+
+    for (worker, last_busy) in busy.iter_mut().enumerate() {
+        let label = worker.to_string();
+        let total_busy = metrics.worker_total_busy_duration(worker);
+        let ratio = total_busy.saturating_sub(*last_busy).as_secs_f64() / interval.as_secs_f64();
+        *last_busy = total_busy;
+        metrics::gauge!("tokio_worker_busy_ratio", "worker" => label.clone()).set(ratio.min(1.0));
+        metrics::counter!("tokio_worker_parks_total", "worker" => label.clone()).absolute(metrics.worker_park_count(worker));
+        #[cfg(tokio_unstable)]
+        metrics::gauge!("tokio_worker_local_queue_depth", "worker" => label).set(metrics.worker_local_queue_depth(worker) as f64);
+    }
+
+    #[cfg(tokio_unstable)]
+    {
+        metrics::gauge!("tokio_blocking_threads").set(metrics.num_blocking_threads() as f64);
+        metrics::gauge!("tokio_blocking_queue_depth").set(metrics.blocking_queue_depth() as f64);
+    }
+}
@@ -0,0 +1,135 @@
+use crate::{config::Config, errors::AppError, validation::ValidationErrors, AppState};
+use anyhow::Context;
+use async_trait::async_trait;
+use serde::Serialize;
+use std::{str::FromStr, sync::Arc, time::Duration};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+};
+
+/// Bytes sent per INSTREAM chunk. clamd accepts any chunk size up to its `StreamMaxLength`.
+const CLAMD_CHUNK_BYTES: usize = 64 * 1024;
+
+/// Longest reply read from clamd; real replies are a line of text.
+const MAX_CLAMD_REPLY_BYTES: u64 = 4096;
+
+/// Which scanner checks uploaded images for malware (`APP_SCANNER_BACKEND`).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ScannerBackend {
+    /// Uploads are stored unscanned.
+    #[default]
+    None,
+    /// A ClamAV daemon reached over TCP (`APP_CLAMAV_ADDRESS`).
+    Clamav,
+}
+
+impl FromStr for ScannerBackend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "none" => Ok(ScannerBackend::None),
+            "clamav" => Ok(ScannerBackend::Clamav),
+            other => Err(format!("unknown scanner backend '{}' (expected none or clamav)", other)),
+        }
+    }
+}
+
+/// What a scanner found in a file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ScanVerdict {
+    Clean,
+    /// Malware was found; holds the name of the matching signature.
+    Infected(String),
+}
+
+/// Checks files for malware before they are released from quarantine.
+#[async_trait]
+pub trait Scanner: Send + Sync + 'static {
+    /// The verdict on `data`. Errors mean the file could not be scanned, not that it is
+    /// infected.
+    async fn scan(&self, data: &[u8]) -> anyhow::Result<ScanVerdict>;
+}
+
+/// Streams files to clamd with the INSTREAM command, one connection per scan. clamd's
+/// `StreamMaxLength` must be at least `APP_MAX_UPLOAD_BYTES`, or large uploads fail to scan.
+pub struct ClamdScanner {
+    address: String,
+}
+
+impl ClamdScanner {
+    pub fn new(address: String) -> Self {
+        Self { address }
+    }
+}
+
+/// Reads clamd's reply to INSTREAM: `stream: OK`, `stream: <signature> FOUND` or
+/// `<message> ERROR`, terminated by a NUL byte.
+fn parse_clamd_reply(reply: &[u8]) -> anyhow::Result<ScanVerdict> {
+    let reply = String::from_utf8_lossy(reply);
+    let reply = reply.trim_end_matches('\0').trim();
+    match reply.strip_prefix("stream: ") {
+        Some("OK") => Ok(ScanVerdict::Clean),
+        Some(found) if found.ends_with(" FOUND") => Ok(ScanVerdict::Infected(found.trim_end_matches(" FOUND").to_string())),
+        _ => anyhow::bail!("clamd could not scan the file: {}", reply),
+    }
+}
+
+#[async_trait]
+impl Scanner for ClamdScanner {
+    async fn scan(&self, data: &[u8]) -> anyhow::Result<ScanVerdict> {
+        let context = || format!("clamd ({}): Failed to scan", self.address);
+        let mut stream = TcpStream::connect(&self.address).await.with_context(context)?;
+        stream.write_all(b"zINSTREAM\0").await.with_context(context)?;
+        for chunk in data.chunks(CLAMD_CHUNK_BYTES) {
+            stream.write_all(&(chunk.len() as u32).to_be_bytes()).await.with_context(context)?;
+            stream.write_all(chunk).await.with_context(context)?;
+        }
+        stream.write_all(&0u32.to_be_bytes()).await.with_context(context)?;
+        // clamd closes the connection after replying to a command outside a session
+        let mut reply = Vec::new();
+        stream.take(MAX_CLAMD_REPLY_BYTES).read_to_end(&mut reply).await.with_context(context)?;
+        parse_clamd_reply(&reply)
+    }
+}
+
+/// Builds the configured scanner; `None` when scanning is off.
+pub fn build_scanner(config: &Config) -> Option<Arc<dyn Scanner>> {
+    match config.scanner_backend {
+        ScannerBackend::None => None,
+        ScannerBackend::Clamav => Some(Arc::new(ClamdScanner::new(config.clamav_address.clone()))),
+    }
+}
+
+/// Scans an uploaded image while it waits in quarantine. Infected files are logged, counted
+/// in `uploads_infected_total` and rejected as invalid (422). Unlike OCR, scanning fails
+/// closed: a scanner that fails or takes longer than `APP_SCAN_TIMEOUT_SECS` is counted in
+/// `scan_failures_total` and the upload is refused with a 503. Either way the caller
+/// removes the quarantined file.
+pub async fn check_upload(state: &AppState, data: &[u8]) -> Result<(), AppError> {
+    let Some(scanner) = &state.scanner else {
+        return Ok(());
+    };
+    let timeout = Duration::from_secs(state.config.scan_timeout_secs);
+    let verdict = match tokio::time::timeout(timeout, scanner.scan(data)).await {
+        Ok(result) => result,
+        Err(_) => Err(anyhow::anyhow!("Scan timed out after {}s", timeout.as_secs())),
+    };
+    match verdict {
+        Ok(ScanVerdict::Clean) => Ok(()),
+        Ok(ScanVerdict::Infected(signature)) => {
+            metrics::counter!("uploads_infected_total").increment(1);
+            tracing::warn!(signature = %signature, bytes = data.len(), "Rejected an infected upload");
+            let mut errors = ValidationErrors::new();
+            errors.add("image", "was rejected by the virus scanner");
+            Err(AppError::ValidationFailed(errors))
+        }
+        Err(e) => {
+            metrics::counter!("scan_failures_total").increment(1);
+            tracing::error!(error = %e, "Failed to scan an upload for malware");
+            Err(AppError::ServiceUnavailable("Virus scanner".to_string()))
+        }
+    }
+}
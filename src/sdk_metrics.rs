@@ -0,0 +1,153 @@
+//! Latency and error metrics for every DynamoDB, S3 and SES SDK operation, recorded by an
+//! SDK interceptor on those clients. Unlike the `backend_call_*` metrics of
+//! [`crate::instrumentation`], which time whole repository and storage methods, these show
+//! which AWS operation, on which table or bucket, is slow or failing.
+
+use aws_sdk_dynamodb::error::ProvideErrorMetadata;
+use aws_smithy_runtime_api::{
+    box_error::BoxError,
+    client::{
+        interceptors::{
+            context::{BeforeSerializationInterceptorContextRef, Error, FinalizerInterceptorContextRef, Input},
+            Intercept,
+        },
+        orchestrator::Metadata,
+        runtime_components::RuntimeComponents,
+    },
+};
+use aws_smithy_types::config_bag::{ConfigBag, Storable, StoreReplace};
+use std::time::Instant;
+
+/// SDK interceptor recording, per call (the SDK's own retries are disabled):
+/// - `aws_sdk_call_duration_seconds{service, operation, resource}`,
+/// - `aws_sdk_call_errors_total{service, operation, resource, error_code}` for failed calls,
+///
+/// where `resource` is the table or bucket and `error_code` the AWS error code (e.g.
+/// `ConditionalCheckFailedException`, `NoSuchKey`), or `timeout`, `connector` or `response`
+/// for calls that got no error response.
+#[derive(Debug, Default)]
+pub struct SdkCallMetrics;
+
+/// What [`SdkCallMetrics`] remembers between the start and the end of a call.
+#[derive(Debug)]
+struct CallStarted {
+    at: Instant,
+    resource: String,
+}
+
+impl Storable for CallStarted {
+    type Storer = StoreReplace<Self>;
+}
+
+impl Intercept for SdkCallMetrics {
+    fn name(&self) -> &'static str {
+        "SdkCallMetrics"
+    }
+
+    fn read_before_execution(
+        &self,
+        context: &BeforeSerializationInterceptorContextRef<'_>,
+        cfg: &mut ConfigBag,
+    ) -> Result<(), BoxError> {
+        // The input is consumed by serialization, so the table or bucket is read up front
+        let resource = resource_of(context.input()).unwrap_or("unknown").to_string();
+        cfg.interceptor_state().store_put(CallStarted { at: Instant::now(), resource });
+        Ok(())
+    }
+
+    fn read_after_execution(
+        &self,
+        context: &FinalizerInterceptorContextRef<'_>,
+        _runtime_components: &RuntimeComponents,
+        cfg: &mut ConfigBag,
+    ) -> Result<(), BoxError> {
+        let (Some(started), Some(metadata)) = (cfg.load::<CallStarted>(), cfg.load::<Metadata>()) else {
+            return Ok(());
+        };
+        let service = metadata.service().to_string();
+        let operation = metadata.name().to_string();
+        let resource = started.resource.clone();
+        metrics::histogram!(
+            "aws_sdk_call_duration_seconds",
+            "service" => service.clone(), "operation" => operation.clone(), "resource" => resource.clone()
+        )
+        .record(started.at.elapsed().as_secs_f64());
+
+        if let Some(Err(error)) = context.output_or_error() {
+            let error_code = match error.as_operation_error() {
+                Some(error) => error_code_of(error).unwrap_or("unknown"),
+                None if error.is_timeout_error() => "timeout",
+                None if error.is_connector_error() => "connector",
+                None if error.is_response_error() => "response",
+                None => "other",
+            };
+            metrics::counter!(
+                "aws_sdk_call_errors_total",
+                "service" => service, "operation" => operation, "resource" => resource,
+                "error_code" => error_code.to_string()
+            )
+            .increment(1);
+        }
+        Ok(())
+    }
+}
+
+/// Generates `resource_of` and `error_code_of` for the listed operations, whose inputs and
+/// errors reach the interceptor type-erased. Operations missing here are still timed, with
+/// `unknown` table/bucket and error code.
+macro_rules! sdk_operations {
+    ($($sdk:ident::$op:ident::{$input:ident, $error:ident} => |$arg:ident| $resource:expr,)*) => {
+        /// The table or bucket a call acts on.
+        pub(crate) fn resource_of(input: &Input) -> Option<&str> {
+            $(
+                if let Some($arg) = input.downcast_ref::<$sdk::operation::$op::$input>() {
+                    return $resource;
+                }
+            )*
+            None
+        }
+
+        /// The AWS error code of a failed call.
+        fn error_code_of(error: &Error) -> Option<&str> {
+            $(
+                if let Some(error) = error.downcast_ref::<$sdk::operation::$op::$error>() {
+                    return error.code();
+                }
+            )*
+            None
+        }
+    };
+}
+
+sdk_operations! {
+    aws_sdk_dynamodb::get_item::{GetItemInput, GetItemError} => |input| input.table_name(),
+    aws_sdk_dynamodb::put_item::{PutItemInput, PutItemError} => |input| input.table_name(),
+    aws_sdk_dynamodb::update_item::{UpdateItemInput, UpdateItemError} => |input| input.table_name(),
+    aws_sdk_dynamodb::delete_item::{DeleteItemInput, DeleteItemError} => |input| input.table_name(),
+    aws_sdk_dynamodb::query::{QueryInput, QueryError} => |input| input.table_name(),
+    aws_sdk_dynamodb::scan::{ScanInput, ScanError} => |input| input.table_name(),
+    aws_sdk_dynamodb::describe_table::{DescribeTableInput, DescribeTableError} => |input| input.table_name(),
+    aws_sdk_dynamodb::create_table::{CreateTableInput, CreateTableError} => |input| input.table_name(),
+    aws_sdk_dynamodb::update_table::{UpdateTableInput, UpdateTableError} => |input| input.table_name(),
+    aws_sdk_dynamodb::describe_time_to_live::{DescribeTimeToLiveInput, DescribeTimeToLiveError} => |input| input.table_name(),
+    aws_sdk_dynamodb::update_time_to_live::{UpdateTimeToLiveInput, UpdateTimeToLiveError} => |input| input.table_name(),
+    aws_sdk_s3::get_object::{GetObjectInput, GetObjectError} => |input| input.bucket(),
+    aws_sdk_s3::head_object::{HeadObjectInput, HeadObjectError} => |input| input.bucket(),
+    aws_sdk_s3::put_object::{PutObjectInput, PutObjectError} => |input| input.bucket(),
+    aws_sdk_s3::delete_object::{DeleteObjectInput, DeleteObjectError} => |input| input.bucket(),
+    aws_sdk_s3::copy_object::{CopyObjectInput, CopyObjectError} => |input| input.bucket(),
+    aws_sdk_s3::list_objects_v2::{ListObjectsV2Input, ListObjectsV2Error} => |input| input.bucket(),
+    aws_sdk_s3::create_multipart_upload::{CreateMultipartUploadInput, CreateMultipartUploadError} => |input| input.bucket(),
+    aws_sdk_s3::upload_part::{UploadPartInput, UploadPartError} => |input| input.bucket(),
+    aws_sdk_s3::complete_multipart_upload::{CompleteMultipartUploadInput, CompleteMultipartUploadError} => |input| input.bucket(),
+    aws_sdk_s3::abort_multipart_upload::{AbortMultipartUploadInput, AbortMultipartUploadError} => |input| input.bucket(),
+    aws_sdk_s3::head_bucket::{HeadBucketInput, HeadBucketError} => |input| input.bucket(),
+    aws_sdk_s3::create_bucket::{CreateBucketInput, CreateBucketError} => |input| input.bucket(),
+    aws_sdk_s3::put_bucket_versioning::{PutBucketVersioningInput, PutBucketVersioningError} => |input| input.bucket(),
+    aws_sdk_s3::put_bucket_encryption::{PutBucketEncryptionInput, PutBucketEncryptionError} => |input| input.bucket(),
+    aws_sdk_s3::put_public_access_block::{PutPublicAccessBlockInput, PutPublicAccessBlockError} => |input| input.bucket(),
+    aws_sdk_s3::get_bucket_lifecycle_configuration::{GetBucketLifecycleConfigurationInput, GetBucketLifecycleConfigurationError} => |input| input.bucket(),
+    aws_sdk_s3::put_bucket_lifecycle_configuration::{PutBucketLifecycleConfigurationInput, PutBucketLifecycleConfigurationError} => |input| input.bucket(),
+    // Emails are counted by sending identity
+    aws_sdk_sesv2::send_email::{SendEmailInput, SendEmailError} => |input| input.from_email_address(),
+}
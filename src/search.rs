@@ -0,0 +1,48 @@
+use crate::models::Meme;
+use serde::Deserialize;
+
+/// Most words one search query may have.
+pub const MAX_QUERY_TERMS: usize = 10;
+
+/// How `GET /memes/search` matches memes (`?mode=`).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SearchMode {
+    /// Every word must appear in the meme; see [`relevance`].
+    #[default]
+    Keyword,
+    /// Ranked by embedding similarity; see [`crate::embeddings`].
+    Semantic,
+}
+
+/// Weight of a term found in each field: the title counts most, then tags, then the
+/// description and the text on the image.
+const TITLE_WEIGHT: u32 = 3;
+const TAG_WEIGHT: u32 = 2;
+const TEXT_WEIGHT: u32 = 1;
+
+/// The lowercase words of a search query.
+pub fn query_terms(query: &str) -> Vec<String> {
+    query.split_whitespace().map(str::to_lowercase).collect()
+}
+
+/// How well the meme matches the terms, higher being better; `None` unless every term is
+/// found in its title, tags, description or caption text. Terms match anywhere in a word
+/// ("cat" finds "cats"), ignoring case.
+pub fn relevance(meme: &Meme, terms: &[String]) -> Option<u32> {
+    let title = meme.title.to_lowercase();
+    let description = meme.description.to_lowercase();
+    let caption = meme.caption_text.as_deref().unwrap_or_default().to_lowercase();
+    terms.iter().try_fold(0, |score, term| {
+        let weight = [
+            (title.contains(term.as_str()), TITLE_WEIGHT),
+            (meme.tags.iter().any(|tag| tag.contains(term.as_str())), TAG_WEIGHT),
+            (description.contains(term.as_str()), TEXT_WEIGHT),
+            (caption.contains(term.as_str()), TEXT_WEIGHT),
+        ]
+        .into_iter()
+        .filter_map(|(found, weight)| found.then_some(weight))
+        .sum::<u32>();
+        (weight > 0).then_some(score + weight)
+    })
+}
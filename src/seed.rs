@@ -0,0 +1,59 @@
+use crate::{
+    errors::AppError,
+    services::{self, Caller, ImageInput, ImageUpload},
+    validation::MemeSubmission,
+    AppState,
+};
+use serde::Deserialize;
+use std::path::Path;
+
+/// One entry of a seed fixture file.
+#[derive(Deserialize, Debug)]
+pub struct FixtureMeme {
+    pub title: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Path to the image file, relative to the fixture file.
+    pub image: String,
+}
+
+/// Creates every meme listed in the JSON fixture file at `path`.
+///
+/// Memes go through the same validation and storage path as uploads, so fixtures that
+/// would be rejected by the API are rejected here too. Returns the number of memes created.
+pub async fn seed_from_file(state: &AppState, path: &Path) -> Result<usize, AppError> {
+    let raw = tokio::fs::read(path)
+        .await
+        .map_err(|e| AppError::InvalidInput(format!("Failed to read fixture file {}: {}", path.display(), e)))?;
+    let fixtures: Vec<FixtureMeme> = serde_json::from_slice(&raw)
+        .map_err(|e| AppError::InvalidInput(format!("Invalid fixture file {}: {}", path.display(), e)))?;
+    let base_dir = path.parent().unwrap_or_else(|| Path::new("."));
+
+    for fixture in &fixtures {
+        let image_path = base_dir.join(&fixture.image);
+        let data = tokio::fs::read(&image_path)
+            .await
+            .map_err(|e| AppError::InvalidInput(format!("Failed to read fixture image {}: {}", image_path.display(), e)))?;
+
+        let submission = MemeSubmission {
+            title: Some(fixture.title.clone()),
+            description: Some(fixture.description.clone()),
+            tags: fixture.tags.clone(),
+            expires_in: None,
+            publish_at: None,
+            status: None,
+        };
+        let image = ImageInput::Provided(ImageUpload {
+            data: data.into(),
+            filename: image_path.file_name().map(|name| name.to_string_lossy().into_owned()),
+            content_type: None,
+            source_url: None,
+        });
+        let meme = services::create_meme(state, submission, image, &Caller::seed()).await?;
+        tracing::info!(meme_id = %meme.meme_id, title = %meme.title, "Seeded meme");
+    }
+
+    Ok(fixtures.len())
+}
@@ -0,0 +1,399 @@
+use crate::{
+    audit,
+    domain::{AuditAction, UploadOptions},
+    embeddings,
+    errors::AppError,
+    imaging,
+    keys,
+    models::{Meme, MemeStatus, Visibility, INITIAL_VERSION},
+    notifications::{self, Notice},
+    ocr,
+    scanning,
+    validation::{self, MemeSubmission, ValidationErrors, ValidationLimits},
+    AppState,
+};
+use axum::body::Bytes;
+use chrono::{NaiveTime, Utc};
+use sha2::{Digest, Sha256};
+use std::net::IpAddr;
+use uuid::Uuid;
+
+/// Image bytes received from a client along with what it told us about them. The bytes are
+/// shared, not copied, on their way to OCR, color extraction, the scanner and storage.
+#[derive(Debug)]
+pub struct ImageUpload {
+    pub data: Bytes,
+    pub filename: Option<String>,
+    pub content_type: Option<String>,
+    /// Where the server fetched the image from, recorded on the meme for attribution.
+    pub source_url: Option<String>,
+}
+
+/// Who made a change, recorded on uploaded image objects and in the audit log. Memes have no
+/// per-user owners, so this only tells requests with owner credentials from anonymous ones,
+/// seeded fixtures, the publishing job and chat bots' users.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Actor {
+    Owner,
+    Anonymous,
+    Seed,
+    Scheduler,
+    /// Anyone in a Discord server with the bot, through its `/meme upload` command.
+    Discord,
+    /// Anyone chatting with the Telegram bot, by sending it a photo.
+    Telegram,
+}
+
+impl Actor {
+    pub fn from_owner_access(is_owner: bool) -> Self {
+        if is_owner { Actor::Owner } else { Actor::Anonymous }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Actor::Owner => "owner",
+            Actor::Anonymous => "anonymous",
+            Actor::Seed => "seed",
+            Actor::Scheduler => "scheduler",
+            Actor::Discord => "discord",
+            Actor::Telegram => "telegram",
+        }
+    }
+}
+
+/// The actor behind a change and the request that carried it. Extracted from requests
+/// (see [`crate::auth`]); seeding builds one without a request.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Caller {
+    pub actor: Actor,
+    pub request_id: Option<String>,
+    pub client_ip: Option<IpAddr>,
+}
+
+impl Caller {
+    pub fn seed() -> Self {
+        Self { actor: Actor::Seed, request_id: None, client_ip: None }
+    }
+
+    pub fn scheduler() -> Self {
+        Self { actor: Actor::Scheduler, request_id: None, client_ip: None }
+    }
+
+    pub fn discord() -> Self {
+        Self { actor: Actor::Discord, request_id: None, client_ip: None }
+    }
+
+    pub fn telegram() -> Self {
+        Self { actor: Actor::Telegram, request_id: None, client_ip: None }
+    }
+
+    pub fn is_owner(&self) -> bool {
+        self.actor == Actor::Owner
+    }
+}
+
+/// Outcome of reading the image part of a request, validated together with the metadata.
+#[derive(Debug)]
+pub enum ImageInput {
+    /// No image was supplied.
+    Missing,
+    /// The image could not be obtained; reported as a validation error on `field`.
+    Invalid { field: &'static str, message: String },
+    Provided(ImageUpload),
+}
+
+/// Validates a submission, stores the image and persists the meme metadata.
+/// Shared by the multipart and JSON upload handlers.
+pub async fn create_meme(
+    state: &AppState,
+    submission: MemeSubmission,
+    image: ImageInput,
+    caller: &Caller,
+) -> Result<Meme, AppError> {
+    let meme_id = Uuid::new_v4();
+
+    // Validate all fields together so the client sees every problem at once
+    let limits = upload_limits(state).await?;
+    let filter = state.content_filter.read().expect("content filter lock poisoned").clone();
+    let (fields, mut errors) = match validation::validate_submission(submission, &limits, &filter) {
+        Ok(fields) => (Some(fields), ValidationErrors::new()),
+        Err(errors) => (None, errors),
+    };
+    let image = match image {
+        ImageInput::Missing => {
+            errors.add("image", "is required");
+            None
+        }
+        ImageInput::Invalid { field, message } => {
+            errors.add(field, message);
+            None
+        }
+        ImageInput::Provided(upload) => {
+            validation::validate_image(&mut errors, &upload.data, &limits).then_some(upload)
+        }
+    };
+    let (Some(fields), Some(image)) = (fields, image.filter(|_| errors.is_empty())) else {
+        return Err(AppError::ValidationFailed(errors));
+    };
+    if let Some(quota) = limits.max_uploads_per_day {
+        check_daily_quota(state, quota).await?;
+    }
+
+    let extension = image.filename.as_ref()
+        .and_then(|name| name.split('.').next_back().map(|ext| ext.to_lowercase()))
+        .unwrap_or_else(|| "bin".to_string());
+    let image_key = state.key_strategy.image_key(meme_id, &extension, &image.data);
+
+    // Guess content type more reliably for upload if not provided
+    let final_content_type = image.content_type
+         .or_else(|| mime_guess::from_path(&image_key).first_raw().map(|s| s.to_string()))
+         .unwrap_or_else(|| "application/octet-stream".to_string());
+    let caption_text = ocr::caption_of(state, &image.data, &final_content_type).await;
+    let colors = imaging::colors_of(image.data.clone()).await;
+    let dimensions = imaging::dimensions(&image.data);
+    let size_bytes = image.data.len() as u64;
+
+    // Tags serve lifecycle rules and cost allocation; metadata comes back with every GET/HEAD
+    let content_sha256 = hex::encode(Sha256::digest(&image.data));
+    let mut options = UploadOptions::with_content_type(final_content_type)
+        .tag("meme_id", meme_id.to_string())
+        .tag("uploader", caller.actor.as_str())
+        .tag("content_sha256", &content_sha256)
+        .metadata("meme-id", meme_id.to_string())
+        .metadata("uploader", caller.actor.as_str())
+        .metadata("content-sha256", &content_sha256);
+    if let Some(tenant) = &state.config.tenant {
+        options = options.tag("tenant", tenant);
+    }
+    if let Some(filename) = image.filename.as_ref().filter(|name| name.chars().all(|c| c.is_ascii_graphic() || c == ' ')) {
+        options = options.metadata("original-filename", filename);
+    }
+    // With moderation or scanning on, the image waits in quarantine until it is approved
+    let quarantined = state.config.moderation_enabled || state.scanner.is_some();
+    let stored_key = if quarantined { keys::quarantine_key(&image_key) } else { image_key.clone() };
+    let scanned_data = state.scanner.is_some().then(|| image.data.clone());
+    state.file_storage.upload(&stored_key, image.data, options).await?;
+    if let Some(data) = scanned_data
+        && let Err(e) = scanning::check_upload(state, &data).await
+    {
+        discard_quarantined(state, &stored_key).await;
+        return Err(e);
+    }
+    let image_key = if quarantined && !state.config.moderation_enabled {
+        release_image(state, &stored_key).await?
+    } else {
+        stored_key
+    };
+
+    // Create and Store Meme Metadata
+    let now = chrono::Utc::now();
+    let meme = Meme {
+        meme_id,
+        title: fields.title,
+        description: fields.description,
+        image_key,
+        tags: fields.tags,
+        source_url: image.source_url,
+        expires_at: fields.expires_in.map(|lifetime| now + lifetime),
+        created_at: Some(now),
+        version: INITIAL_VERSION,
+        like_count: 0,
+        view_count: 0,
+        caption_text,
+        dominant_color: colors.as_ref().map(|colors| colors.dominant.to_string()),
+        palette: colors.map(|colors| colors.palette.iter().map(ToString::to_string).collect()).unwrap_or_default(),
+        width: dimensions.map(|(width, _)| width),
+        height: dimensions.map(|(_, height)| height),
+        size_bytes: Some(size_bytes),
+        content_hash: Some(content_sha256),
+        updated_at: None,
+        visibility: Visibility::default(),
+        publish_at: fields.publish_at,
+        status: fields.status,
+    };
+    state.meme_repo.create(&meme).await?;
+    audit::record(state, caller, AuditAction::Created, None, Some(&meme)).await;
+    embeddings::index_meme(state, &meme).await;
+    announce(state, &meme);
+
+    tracing::info!(meme_id = %meme_id, "Meme created successfully");
+    Ok(meme)
+}
+
+/// Sends a meme that just became publicly listed to the subscribers of
+/// [`AppState::new_memes`]. Called where memes can become listed: uploads, publishing and
+/// approval.
+fn announce(state: &AppState, meme: &Meme) {
+    if meme.is_listed(Utc::now()) {
+        // Only fails without subscribers
+        let _ = state.new_memes.send(meme.clone());
+    }
+}
+
+/// Moves a quarantined image to its own key. Returns the new key.
+async fn release_image(state: &AppState, quarantined_key: &str) -> Result<String, AppError> {
+    let Some(key) = keys::released_key(quarantined_key) else {
+        return Ok(quarantined_key.to_string());
+    };
+    state.file_storage.rename(quarantined_key, key).await?;
+    Ok(key.to_string())
+}
+
+/// Best-effort removal of a quarantined image that was approved or refused. A leftover copy
+/// is never served, and shows up in `GET /admin/resources` totals only.
+async fn discard_quarantined(state: &AppState, key: &str) {
+    if let Err(e) = state.file_storage.delete(key).await {
+        tracing::warn!(image_key = %key, error = ?e, "Failed to remove a quarantined image");
+    }
+}
+
+/// Validates a submission whose image arrives later, so clients learn about bad metadata
+/// before sending the image. Returns the limits the image will be held to.
+pub async fn precheck_submission(state: &AppState, submission: MemeSubmission) -> Result<ValidationLimits, AppError> {
+    let limits = upload_limits(state).await?;
+    let filter = state.content_filter.read().expect("content filter lock poisoned").clone();
+    validation::validate_submission(submission, &limits, &filter).map_err(AppError::ValidationFailed)?;
+    Ok(limits)
+}
+
+/// Limits for new memes: the deployment's settings with the tenant's overrides on top.
+pub async fn upload_limits(state: &AppState) -> Result<ValidationLimits, AppError> {
+    let limits = ValidationLimits::from(state.config.as_ref());
+    match &state.tenant_settings {
+        Some(settings) => {
+            let overrides = settings.overrides().await?;
+            Ok(limits.with_overrides(&overrides))
+        }
+        None => Ok(limits),
+    }
+}
+
+/// Rejects an upload once `quota` memes were created since midnight UTC. Concurrent uploads
+/// may overshoot the quota slightly; it is a fairness limit, not a hard cap.
+async fn check_daily_quota(state: &AppState, quota: u64) -> Result<(), AppError> {
+    let midnight = Utc::now().date_naive().and_time(NaiveTime::MIN).and_utc();
+    let uploaded = state.meme_repo.count_created_since(midnight).await?;
+    if uploaded >= quota {
+        return Err(AppError::QuotaExceeded(format!("At most {} memes can be uploaded per day", quota)));
+    }
+    Ok(())
+}
+
+/// Metadata changes requested for an existing meme. Unset fields keep their current value.
+#[derive(Debug, Default)]
+pub struct MemePatch {
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub tags: Option<Vec<String>>,
+    pub visibility: Option<Visibility>,
+}
+
+/// Applies `patch` to `current`, validates the result like a new submission and stores it
+/// as the next version. The write only succeeds if nobody updated the meme since `current`
+/// was read; otherwise the caller gets a 412 and should re-read and retry.
+///
+/// `current` is kept in the meme's history first. Keeping it is idempotent, so a version
+/// saved for an update that then loses the race is simply the one that was replaced.
+pub async fn update_meme(state: &AppState, current: Meme, patch: MemePatch, caller: &Caller) -> Result<Meme, AppError> {
+    let submission = MemeSubmission {
+        title: Some(patch.title.unwrap_or_else(|| current.title.clone())),
+        description: Some(patch.description.unwrap_or_else(|| current.description.clone())),
+        tags: patch.tags.unwrap_or_else(|| current.tags.clone()),
+        expires_in: None,
+        publish_at: None,
+        status: None,
+    };
+    let limits = upload_limits(state).await?;
+    let filter = state.content_filter.read().expect("content filter lock poisoned").clone();
+    let fields = validation::validate_submission(submission, &limits, &filter).map_err(AppError::ValidationFailed)?;
+
+    let meme = Meme {
+        title: fields.title,
+        description: fields.description,
+        tags: fields.tags,
+        visibility: patch.visibility.unwrap_or(current.visibility),
+        version: current.version + 1,
+        updated_at: Some(Utc::now()),
+        ..current.clone()
+    };
+    state.meme_history.save_version(&current).await?;
+    state.meme_repo.update(&meme, current.version).await?;
+    audit::record(state, caller, AuditAction::Updated, Some(&current), Some(&meme)).await;
+    embeddings::index_meme(state, &meme).await;
+
+    tracing::info!(meme_id = %meme.meme_id, version = meme.version, "Meme updated successfully");
+    Ok(meme)
+}
+
+/// Restores the metadata of an earlier `version` of `current` as its next version, going
+/// through [`update_meme`] so the revert is validated, kept in the history and audited like
+/// any other update. Reverting to the current version changes nothing.
+pub async fn revert_meme(state: &AppState, current: Meme, version: u64, caller: &Caller) -> Result<Meme, AppError> {
+    if version == current.version {
+        return Ok(current);
+    }
+    let not_found = || AppError::VersionNotFound { id: current.meme_id, version };
+    if version > current.version {
+        return Err(not_found());
+    }
+    let earlier = state.meme_history.get_version(current.meme_id, version).await?.ok_or_else(not_found)?;
+    let patch = MemePatch {
+        title: Some(earlier.title),
+        description: Some(earlier.description),
+        tags: Some(earlier.tags),
+        visibility: Some(earlier.visibility),
+    };
+    update_meme(state, current, patch, caller).await
+}
+
+/// Approves a meme awaiting moderation: its image is released from quarantine and the meme
+/// points at the released image from its next version on, kept in the history and audited
+/// like an update. The meme then appears according to its status and visibility. Approving
+/// an approved meme changes nothing.
+pub async fn approve_meme(state: &AppState, current: Meme, caller: &Caller) -> Result<Meme, AppError> {
+    let Some(image_key) = keys::released_key(&current.image_key) else {
+        return Ok(current);
+    };
+    // The quarantined copy goes last, so a lost race leaves the meme with its image. Until
+    // the update lands, the released copy is hidden like the meme itself.
+    state.file_storage.copy(&current.image_key, image_key).await?;
+    let meme = Meme {
+        image_key: image_key.to_string(),
+        version: current.version + 1,
+        updated_at: Some(Utc::now()),
+        ..current.clone()
+    };
+    state.meme_history.save_version(&current).await?;
+    state.meme_repo.update(&meme, current.version).await?;
+    discard_quarantined(state, &current.image_key).await;
+    audit::record(state, caller, AuditAction::Updated, Some(&current), Some(&meme)).await;
+    announce(state, &meme);
+    notifications::notify(state, Notice::Approved, &meme).await;
+
+    tracing::info!(meme_id = %meme.meme_id, version = meme.version, "Meme approved");
+    Ok(meme)
+}
+
+/// Publishes a draft as its next version, kept in the history and audited like an update.
+/// A publish time that has not come yet is cleared, since the meme is shown from now on.
+/// Publishing a published meme changes nothing.
+pub async fn publish_meme(state: &AppState, current: Meme, caller: &Caller) -> Result<Meme, AppError> {
+    if current.status == MemeStatus::Published {
+        return Ok(current);
+    }
+    let now = Utc::now();
+    let meme = Meme {
+        status: MemeStatus::Published,
+        publish_at: current.publish_at.filter(|publish_at| *publish_at <= now),
+        version: current.version + 1,
+        updated_at: Some(now),
+        ..current.clone()
+    };
+    state.meme_history.save_version(&current).await?;
+    state.meme_repo.update(&meme, current.version).await?;
+    audit::record(state, caller, AuditAction::Updated, Some(&current), Some(&meme)).await;
+    announce(state, &meme);
+
+    tracing::info!(meme_id = %meme.meme_id, version = meme.version, "Meme published");
+    Ok(meme)
+}
@@ -0,0 +1,143 @@
+use crate::{errors::AppError, AppState};
+use axum::{
+    extract::{Path, Request, State},
+    middleware::Next,
+    response::Response,
+};
+use base64::{prelude::BASE64_URL_SAFE_NO_PAD, Engine};
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::sync::Arc;
+use uuid::Uuid;
+
+/// Read access to one meme until `expires_at`, as carried by a share token.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ShareGrant {
+    pub meme_id: Uuid,
+    pub expires_at: DateTime<Utc>,
+}
+
+impl ShareGrant {
+    /// Encodes the grant as `<meme_id>.<expiry epoch seconds>.<signature>`, where the
+    /// signature is a base64url HMAC-SHA256 of the first two parts.
+    pub fn sign(&self, secret: &str) -> String {
+        let payload = format!("{}.{}", self.meme_id, self.expires_at.timestamp());
+        let signature = BASE64_URL_SAFE_NO_PAD.encode(mac(secret, &payload).finalize().into_bytes());
+        format!("{}.{}", payload, signature)
+    }
+
+    /// Checks a token's signature and expiry. Tampered and malformed tokens are reported the
+    /// same way, so the error does not help forging one.
+    pub fn verify(secret: &str, token: &str, now: DateTime<Utc>) -> Result<Self, AppError> {
+        let invalid = || AppError::Forbidden("Invalid share link".to_string());
+        let (payload, signature) = token.rsplit_once('.').ok_or_else(invalid)?;
+        let signature = BASE64_URL_SAFE_NO_PAD.decode(signature).map_err(|_| invalid())?;
+        mac(secret, payload).verify_slice(&signature).map_err(|_| invalid())?;
+
+        let (meme_id, expires_at) = payload.split_once('.').ok_or_else(invalid)?;
+        let grant = ShareGrant {
+            meme_id: meme_id.parse().map_err(|_| invalid())?,
+            expires_at: expires_at.parse().ok().and_then(|secs| DateTime::from_timestamp(secs, 0)).ok_or_else(invalid)?,
+        };
+        if grant.expires_at <= now {
+            return Err(AppError::Forbidden("Share link has expired".to_string()));
+        }
+        Ok(grant)
+    }
+}
+
+fn mac(secret: &str, payload: &str) -> Hmac<Sha256> {
+    // HMAC accepts keys of any length
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC key of any size");
+    mac.update(payload.as_bytes());
+    mac
+}
+
+/// Middleware for `/shared/{token}` routes: verifies the token and hands the resulting
+/// [`ShareGrant`] to the handler through the request extensions. No other credentials are
+/// needed; the token is the grant.
+pub async fn require_share_token(
+    State(state): State<Arc<AppState>>,
+    Path(token): Path<String>,
+    mut request: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    let Some(secret) = state.config.share_secret.as_deref() else {
+        return Err(AppError::Forbidden("Share links are disabled".to_string()));
+    };
+    let grant = ShareGrant::verify(secret, &token, Utc::now()).inspect_err(|e| {
+        tracing::debug!(path = %request.uri().path(), error = %e, "Rejected share link");
+    })?;
+    request.extensions_mut().insert(grant);
+    Ok(next.run(request).await)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECRET: &str = "0123456789abcdef0123456789abcdef";
+
+    fn grant(now: DateTime<Utc>) -> ShareGrant {
+        ShareGrant { meme_id: Uuid::new_v4(), expires_at: DateTime::from_timestamp(now.timestamp() + 60, 0).unwrap() }
+    }
+
+    fn is_invalid(result: Result<ShareGrant, AppError>) -> bool {
+        matches!(result, Err(AppError::Forbidden(message)) if message == "Invalid share link")
+    }
+
+    #[test]
+    fn signed_grants_verify() {
+        let now = Utc::now();
+        let grant = grant(now);
+        assert_eq!(ShareGrant::verify(SECRET, &grant.sign(SECRET), now).unwrap(), grant);
+    }
+
+    #[test]
+    fn tampered_grants_are_refused() {
+        let now = Utc::now();
+        let grant = grant(now);
+        let token = grant.sign(SECRET);
+        let other_meme = token.replacen(&grant.meme_id.to_string(), &Uuid::new_v4().to_string(), 1);
+        assert!(is_invalid(ShareGrant::verify(SECRET, &other_meme, now)));
+        let expiry = grant.expires_at.timestamp().to_string();
+        let later = token.replacen(&expiry, &(grant.expires_at.timestamp() + 3600).to_string(), 1);
+        assert!(is_invalid(ShareGrant::verify(SECRET, &later, now)));
+    }
+
+    #[test]
+    fn malformed_tokens_are_refused() {
+        let now = Utc::now();
+        let token = grant(now).sign(SECRET);
+        let (payload, _) = token.rsplit_once('.').unwrap();
+        assert!(is_invalid(ShareGrant::verify(SECRET, &format!("{}.not*base64", payload), now)));
+        assert!(is_invalid(ShareGrant::verify(SECRET, &format!("{}.", payload), now)));
+        assert!(is_invalid(ShareGrant::verify(SECRET, &token.replace('.', ""), now)));
+        assert!(is_invalid(ShareGrant::verify(SECRET, "", now)));
+        // A validly signed payload that lacks the separator between meme ID and expiry
+        let unseparated = payload.replacen('.', "", 1);
+        let signature = BASE64_URL_SAFE_NO_PAD.encode(mac(SECRET, &unseparated).finalize().into_bytes());
+        assert!(is_invalid(ShareGrant::verify(SECRET, &format!("{}.{}", unseparated, signature), now)));
+    }
+
+    #[test]
+    fn grants_expire_at_their_expiry() {
+        let now = Utc::now();
+        let grant = grant(now);
+        let token = grant.sign(SECRET);
+        let one_second = chrono::Duration::seconds(1);
+        assert!(ShareGrant::verify(SECRET, &token, grant.expires_at - one_second).is_ok());
+        for now in [grant.expires_at, grant.expires_at + one_second] {
+            let error = ShareGrant::verify(SECRET, &token, now).unwrap_err();
+            assert!(matches!(&error, AppError::Forbidden(message) if message == "Share link has expired"), "{:?}", error);
+        }
+    }
+
+    #[test]
+    fn grants_signed_with_another_secret_are_refused() {
+        let now = Utc::now();
+        let token = grant(now).sign("fedcba9876543210fedcba9876543210");
+        assert!(is_invalid(ShareGrant::verify(SECRET, &token, now)));
+    }
+}
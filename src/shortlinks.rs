@@ -0,0 +1,177 @@
+//! Short links to memes, for sharing where long URLs get in the way (chat apps, captions).
+//!
+//! `POST /meme/{id}/shortlink` stores a random code for the meme, and `GET /s/{code}`
+//! redirects to the meme or its image, counting the hit. Unlike share links, shortlinks grant
+//! nothing: the redirect only leads to memes anyone may see, so a shortlink to a private
+//! meme or a draft answers 404 until the meme is public.
+
+use crate::{
+    auth::OwnerAccess,
+    config::ReadEndpoint,
+    domain::{Shortlink, ShortlinkRepository, ShortlinkTarget},
+    errors::AppError,
+    handlers,
+    AppState,
+};
+use axum::{
+    extract::{Path, Query, State},
+    http::{header, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use uuid::Uuid;
+
+/// Characters of a code: 62^7 codes, so random ones rarely collide.
+const CODE_LENGTH: usize = 7;
+const CODE_ALPHABET: &[u8] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+/// New codes tried before giving up on a taken one.
+const MAX_ATTEMPTS: usize = 5;
+
+/// Query parameters for POST /meme/{id}/shortlink.
+#[derive(Deserialize)]
+pub struct ShortlinkQuery {
+    /// `page` (default) or `image`.
+    #[serde(default)]
+    pub target: ShortlinkTarget,
+}
+
+/// A shortlink as the API returns it. `url` is relative to this server.
+#[derive(Serialize)]
+pub struct ShortlinkView {
+    #[serde(flatten)]
+    pub link: Shortlink,
+    pub url: String,
+}
+
+impl From<Shortlink> for ShortlinkView {
+    fn from(link: Shortlink) -> Self {
+        Self { url: format!("/s/{}", link.code), link }
+    }
+}
+
+/// Handler for POST /meme/{id}/shortlink. Anyone who can see the meme may create one; each
+/// call creates a new code.
+pub async fn create_shortlink(
+    State(state): State<Arc<AppState>>,
+    OwnerAccess(is_owner): OwnerAccess,
+    Path(id_str): Path<String>,
+    Query(query): Query<ShortlinkQuery>,
+) -> Result<(StatusCode, Json<ShortlinkView>), AppError> {
+    let meme_id = Uuid::parse_str(&id_str)?;
+    handlers::find_visible_meme(&state, meme_id, is_owner, ReadEndpoint::Writes).await?;
+    let link = store_with_new_code(state.shortlink_repo.as_ref(), meme_id, query.target).await?;
+    tracing::info!(%meme_id, code = %link.code, target = link.target.as_str(), "Shortlink created");
+    Ok((StatusCode::CREATED, Json(link.into())))
+}
+
+/// Stores a shortlink under a random code. The repositories only store codes not yet taken,
+/// so a collision draws another code, up to `MAX_ATTEMPTS` times.
+async fn store_with_new_code(
+    repo: &dyn ShortlinkRepository,
+    meme_id: Uuid,
+    target: ShortlinkTarget,
+) -> Result<Shortlink, AppError> {
+    for _ in 0..MAX_ATTEMPTS {
+        let link = Shortlink { code: random_code(), meme_id, target, created_at: chrono::Utc::now(), hits: 0 };
+        if repo.create(&link).await? {
+            return Ok(link);
+        }
+        tracing::debug!(code = %link.code, "Shortlink code taken, drawing another");
+    }
+    Err(AppError::InternalServerError("Could not find a free shortlink code".to_string()))
+}
+
+/// Handler for GET /s/{code}. Redirects (302) to the meme, or to its image at a freshly
+/// issued URL, and counts the hit. A failed count is logged; the redirect is served anyway.
+pub async fn follow_shortlink(
+    State(state): State<Arc<AppState>>,
+    Path(code): Path<String>,
+) -> Result<impl IntoResponse, AppError> {
+    let link = find_shortlink(&state, &code).await?;
+    let meme = state.meme_repo.get_by_id(link.meme_id, state.config.read_consistency(ReadEndpoint::GetMeme)).await?
+        .filter(|meme| meme.is_visible_to(false))
+        .ok_or(AppError::MemeNotFound(link.meme_id))?;
+    let location = match link.target {
+        ShortlinkTarget::Page => format!("/meme/{}", meme.meme_id),
+        ShortlinkTarget::Image => handlers::meme_view(&state, meme).await?.image_url,
+    };
+    if let Err(e) = state.shortlink_repo.add_hit(&link.code).await {
+        tracing::warn!(code = %link.code, error = %e, "Failed to count a shortlink hit");
+    }
+    // Not cached, so every hit is counted and image URLs are fresh
+    Ok((StatusCode::FOUND, [(header::LOCATION, location), (header::CACHE_CONTROL, "no-store".to_string())]))
+}
+
+/// Handler for GET /s/{code}/stats. The shortlink with its hits so far.
+pub async fn get_shortlink_stats(
+    State(state): State<Arc<AppState>>,
+    Path(code): Path<String>,
+) -> Result<Json<ShortlinkView>, AppError> {
+    Ok(Json(find_shortlink(&state, &code).await?.into()))
+}
+
+/// Loads a shortlink. Strings that cannot be codes are reported missing without a lookup.
+async fn find_shortlink(state: &AppState, code: &str) -> Result<Shortlink, AppError> {
+    let not_found = || AppError::ShortlinkNotFound(code.to_string());
+    if code.len() != CODE_LENGTH || !code.bytes().all(|byte| byte.is_ascii_alphanumeric()) {
+        return Err(not_found());
+    }
+    state.shortlink_repo.get(code).await?.ok_or_else(not_found)
+}
+
+fn random_code() -> String {
+    (0..CODE_LENGTH).map(|_| CODE_ALPHABET[fastrand::usize(..CODE_ALPHABET.len())] as char).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::errors::RepoError;
+    use async_trait::async_trait;
+    use std::sync::Mutex;
+
+    /// Takes every code after the first `taken` attempts, recording the codes tried.
+    struct Crowded {
+        taken: usize,
+        tried: Mutex<Vec<String>>,
+    }
+
+    #[async_trait]
+    impl ShortlinkRepository for Crowded {
+        async fn create(&self, link: &Shortlink) -> Result<bool, RepoError> {
+            let mut tried = self.tried.lock().unwrap();
+            tried.push(link.code.clone());
+            Ok(tried.len() > self.taken)
+        }
+
+        async fn get(&self, _code: &str) -> Result<Option<Shortlink>, RepoError> {
+            Ok(None)
+        }
+
+        async fn add_hit(&self, _code: &str) -> Result<(), RepoError> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn taken_codes_are_replaced_by_new_ones() {
+        let repo = Crowded { taken: 2, tried: Mutex::default() };
+        let meme_id = Uuid::new_v4();
+        let link = store_with_new_code(&repo, meme_id, ShortlinkTarget::Image).await.unwrap();
+        let tried = repo.tried.into_inner().unwrap();
+        assert_eq!(tried.len(), 3);
+        assert_eq!(&link.code, tried.last().unwrap());
+        assert_eq!((link.meme_id, link.target, link.hits), (meme_id, ShortlinkTarget::Image, 0));
+        assert!(tried.iter().all(|code| code.len() == CODE_LENGTH && code.bytes().all(|byte| byte.is_ascii_alphanumeric())));
+    }
+
+    #[tokio::test]
+    async fn creating_fails_once_every_attempt_collided() {
+        let repo = Crowded { taken: MAX_ATTEMPTS, tried: Mutex::default() };
+        let result = store_with_new_code(&repo, Uuid::new_v4(), ShortlinkTarget::Page).await;
+        assert!(matches!(result, Err(AppError::InternalServerError(_))));
+        assert_eq!(repo.tried.into_inner().unwrap().len(), MAX_ATTEMPTS);
+    }
+}
@@ -0,0 +1,146 @@
+use axum::{
+    body::{Body, Bytes, HttpBody},
+    extract::{Request, State},
+    middleware::Next,
+    response::Response,
+};
+use http_body::{Frame, SizeHint};
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc,
+    },
+    task::{Context, Poll},
+};
+use tokio::{sync::Notify, task::JoinHandle};
+
+/// Counts requests that are still being handled or still streaming their response body,
+/// and work they left running in the background (see [`InFlightRequests::spawn`]), so
+/// shutdown can wait for them and report how many were drained and how many had to be cut off.
+#[derive(Debug, Default)]
+pub struct InFlightRequests {
+    active: AtomicUsize,
+    completed: AtomicU64,
+    idle: Notify,
+}
+
+#[cfg_attr(feature = "lambda", allow(dead_code))] // Only read by the TCP server's shutdown path
+impl InFlightRequests {
+    pub fn active(&self) -> usize {
+        self.active.load(Ordering::SeqCst)
+    }
+
+    /// Requests finished since startup, including ones that ended with an error.
+    pub fn completed(&self) -> u64 {
+        self.completed.load(Ordering::SeqCst)
+    }
+
+    /// Waits until nothing is in flight.
+    pub async fn wait_idle(&self) {
+        loop {
+            let notified = self.idle.notified();
+            tokio::pin!(notified);
+            // Registered before checking, so a drop to zero in between is not missed
+            notified.as_mut().enable();
+            if self.active() == 0 {
+                return;
+            }
+            notified.await;
+        }
+    }
+
+    /// Runs `task` in the background on behalf of a request, e.g. to answer a chat platform
+    /// after acknowledging its callback. It counts as in flight until it finishes, so
+    /// shutdown drains it like a response still being sent.
+    pub fn spawn<F>(self: &Arc<Self>, task: F) -> JoinHandle<()>
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        let guard = self.start();
+        tokio::spawn(async move {
+            task.await;
+            drop(guard);
+        })
+    }
+
+    fn start(self: &Arc<Self>) -> InFlightGuard {
+        self.active.fetch_add(1, Ordering::SeqCst);
+        metrics::gauge!("http_requests_in_flight").increment(1.0);
+        InFlightGuard(self.clone())
+    }
+}
+
+/// Marks a request as finished when dropped.
+struct InFlightGuard(Arc<InFlightRequests>);
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        if self.0.active.fetch_sub(1, Ordering::SeqCst) == 1 {
+            self.0.idle.notify_waiters();
+        }
+        self.0.completed.fetch_add(1, Ordering::SeqCst);
+        metrics::gauge!("http_requests_in_flight").decrement(1.0);
+    }
+}
+
+/// Middleware that tracks a request until its response body has been fully sent (or dropped).
+pub async fn track_in_flight(
+    State(in_flight): State<Arc<InFlightRequests>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let guard = in_flight.start();
+    next.run(request)
+        .await
+        .map(|body| Body::new(TrackedBody { inner: body, _guard: guard }))
+}
+
+/// Response body that holds an [`InFlightGuard`] until the body is dropped.
+struct TrackedBody {
+    inner: Body,
+    _guard: InFlightGuard,
+}
+
+impl HttpBody for TrackedBody {
+    type Data = Bytes;
+    type Error = axum::Error;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        Pin::new(&mut self.inner).poll_frame(cx)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::sync::oneshot;
+
+    #[tokio::test]
+    async fn background_work_is_in_flight_until_it_finishes() {
+        let in_flight = Arc::new(InFlightRequests::default());
+        let (finish, finished) = oneshot::channel::<()>();
+        let task = in_flight.spawn(async move {
+            finished.await.ok();
+        });
+        assert_eq!(in_flight.active(), 1);
+        assert!(tokio::time::timeout(std::time::Duration::from_millis(50), in_flight.wait_idle()).await.is_err());
+
+        finish.send(()).unwrap();
+        tokio::time::timeout(std::time::Duration::from_secs(5), in_flight.wait_idle()).await.expect("drained");
+        task.await.unwrap();
+        assert_eq!((in_flight.active(), in_flight.completed()), (0, 1));
+    }
+}
@@ -0,0 +1,144 @@
+//! `GET /sitemap.xml`, listing the pages of publicly listed memes (`/meme/{id}`, served as
+//! HTML to crawlers, see [`crate::embeds`]) so search engines find them.
+//!
+//! The memes are listed like an unsorted `GET /memes`, reading every page of the repository,
+//! so the generated sitemap is kept for `APP_SITEMAP_TTL_SECS` and served from memory until
+//! then. Each instance and tenant keeps its own, and rebuilds it once at a time: requests
+//! arriving meanwhile wait for that sitemap instead of listing the memes again.
+//!
+//! Its links start with `APP_PUBLIC_URL`; without it, there is no sitemap.
+
+use crate::{embeds, errors::AppError, models::Meme, AppState};
+use axum::{
+    extract::State,
+    http::header,
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, SecondsFormat, Utc};
+use std::sync::Arc;
+
+/// Most URLs one sitemap file may list, per the sitemaps protocol.
+const MAX_URLS: usize = 50_000;
+
+/// A generated sitemap.
+#[derive(Debug)]
+pub struct Sitemap {
+    generated_at: DateTime<Utc>,
+    xml: String,
+}
+
+/// Handler for GET /sitemap.xml. Links are made absolute like those of meme pages.
+pub async fn get_sitemap(State(state): State<Arc<AppState>>) -> Result<Response, AppError> {
+    let base = embeds::public_base(&state).expect("/sitemap.xml is only routed with a public URL");
+    let sitemap = current_sitemap(&state, base).await?;
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/xml; charset=utf-8".to_string()),
+            (header::CACHE_CONTROL, format!("public, max-age={}", state.config.sitemap_ttl_secs)),
+        ],
+        sitemap.xml.clone(),
+    )
+        .into_response())
+}
+
+/// The cached sitemap while it is younger than `APP_SITEMAP_TTL_SECS`, else a freshly
+/// generated one, which replaces it. The lock is held while generating, so concurrent
+/// requests wait for one rebuild.
+async fn current_sitemap(state: &AppState, base: &str) -> Result<Arc<Sitemap>, AppError> {
+    let max_age = chrono::Duration::seconds(state.config.sitemap_ttl_secs as i64);
+    let mut cached = state.sitemap.lock().await;
+    if let Some(sitemap) = cached.as_ref().filter(|sitemap| Utc::now() - sitemap.generated_at < max_age) {
+        return Ok(sitemap.clone());
+    }
+
+    let now = Utc::now();
+    let memes = sitemap_memes(state.meme_repo.list_all().await?, now);
+    let sitemap = Arc::new(Sitemap { xml: render(base, &memes), generated_at: now });
+    *cached = Some(sitemap.clone());
+    tracing::debug!(memes = memes.len(), "Sitemap generated");
+    Ok(sitemap)
+}
+
+/// The memes of the public listing at `now`, most recently changed first, so the newest are
+/// kept if there are more than one sitemap may list.
+fn sitemap_memes(mut memes: Vec<Meme>, now: DateTime<Utc>) -> Vec<Meme> {
+    memes.retain(|meme| meme.is_listed(now) && !meme.is_expired(now));
+    memes.sort_by_key(|meme| std::cmp::Reverse(last_modified(meme)));
+    if memes.len() > MAX_URLS {
+        tracing::warn!(memes = memes.len(), max = MAX_URLS, "Sitemap truncated to the most recently changed memes");
+        memes.truncate(MAX_URLS);
+    }
+    memes
+}
+
+/// When the meme's page last changed: its last update, else its upload. Memes stored before
+/// either was recorded have no `lastmod`.
+fn last_modified(meme: &Meme) -> Option<DateTime<Utc>> {
+    meme.updated_at.or(meme.created_at)
+}
+
+fn render(base: &str, memes: &[Meme]) -> String {
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\n");
+    for meme in memes {
+        xml.push_str(&format!("<url><loc>{}</loc>", embeds::escape(&format!("{}/meme/{}", base, meme.meme_id))));
+        if let Some(lastmod) = last_modified(meme) {
+            xml.push_str(&format!("<lastmod>{}</lastmod>", lastmod.to_rfc3339_opts(SecondsFormat::Secs, true)));
+        }
+        xml.push_str("</url>\n");
+    }
+    xml.push_str("</urlset>\n");
+    xml
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        generators::MemeBuilder,
+        models::{MemeStatus, Visibility},
+    };
+
+    fn at(timestamp: i64) -> DateTime<Utc> {
+        DateTime::from_timestamp(timestamp, 0).unwrap()
+    }
+
+    #[test]
+    fn only_listed_memes_are_included() {
+        let now = Utc::now();
+        let listed = MemeBuilder::new().build();
+        let memes = vec![
+            listed.clone(),
+            MemeBuilder::new().visibility(Visibility::Unlisted).build(),
+            MemeBuilder::new().visibility(Visibility::Private).build(),
+            MemeBuilder::new().status(MemeStatus::Draft).build(),
+            MemeBuilder::new().scheduled(now + chrono::Duration::hours(1)).build(),
+            MemeBuilder::new().image_key(crate::keys::quarantine_key("waiting.png")).build(),
+            MemeBuilder::new().expires_at(now - chrono::Duration::seconds(1)).build(),
+        ];
+        let ids: Vec<_> = sitemap_memes(memes, now).iter().map(|meme| meme.meme_id).collect();
+        assert_eq!(ids, [listed.meme_id]);
+    }
+
+    #[test]
+    fn lastmod_is_the_last_update_else_the_upload() {
+        let mut updated = MemeBuilder::new().created_at(at(1_700_000_000)).build();
+        updated.updated_at = Some(at(1_700_086_400));
+        let uploaded = MemeBuilder::new().created_at(at(1_700_000_500)).build();
+        let mut unknown = MemeBuilder::new().build();
+        unknown.created_at = None;
+
+        let memes = sitemap_memes(vec![unknown.clone(), uploaded.clone(), updated.clone()], Utc::now());
+        let ids: Vec<_> = memes.iter().map(|meme| meme.meme_id).collect();
+        assert_eq!(ids, [updated.meme_id, uploaded.meme_id, unknown.meme_id]);
+        assert_eq!(
+            render("https://memes.example.com", &memes),
+            format!(
+                "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\n\
+                 <url><loc>https://memes.example.com/meme/{}</loc><lastmod>2023-11-15T22:13:20Z</lastmod></url>\n\
+                 <url><loc>https://memes.example.com/meme/{}</loc><lastmod>2023-11-14T22:21:40Z</lastmod></url>\n\
+                 <url><loc>https://memes.example.com/meme/{}</loc></url>\n</urlset>\n",
+                updated.meme_id, uploaded.meme_id, unknown.meme_id
+            )
+        );
+    }
+}
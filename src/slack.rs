@@ -0,0 +1,329 @@
+//! Slack app: a `/meme` slash command, previews of links to memes and notifications of new
+//! memes.
+//!
+//! - `POST /slack/command` is the slash command's Request URL. `/meme random` answers in the
+//!   channel with a random listed meme as an image block.
+//! - `POST /slack/events` is the Events API Request URL. Links to `/meme/{id}` shared in
+//!   Slack are unfurled into the meme's image with `chat.unfurl`, given `APP_SLACK_BOT_TOKEN`
+//!   and the server's domain among the app's unfurl domains.
+//! - With `APP_SLACK_WEBHOOK_URL`, memes are posted to an incoming webhook as they become
+//!   publicly listed (see [`crate::announcements`]).
+//!
+//! Both routes need `APP_SLACK_SIGNING_SECRET`, and check every request's signature with it.
+
+use crate::{
+    announcements::{has_absolute_image_url, linked_meme, random_listed_meme, truncate, MemeAnnouncer},
+    config::{Config, ReadEndpoint},
+    embeds,
+    errors::AppError,
+    handlers,
+    models::{Meme, MemeView},
+    AppState,
+};
+use anyhow::{bail, Context};
+use async_trait::async_trait;
+use axum::{
+    body::Bytes,
+    extract::State,
+    http::HeaderMap,
+    Json,
+};
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use reqwest::{redirect, Client};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use sha2::Sha256;
+use std::{sync::Arc, time::Duration};
+
+const API_URL: &str = "https://slack.com/api";
+/// Per-request limit for calls to Slack.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+const SIGNATURE_HEADER: &str = "x-slack-signature";
+const TIMESTAMP_HEADER: &str = "x-slack-request-timestamp";
+/// How old a signed request may be; older ones are refused as possible replays.
+const MAX_REQUEST_AGE_SECS: i64 = 5 * 60;
+/// Limit of plain text in blocks.
+const MAX_BLOCK_TEXT: usize = 2000;
+
+const USAGE: &str = "Usage: `/meme random` posts a random meme.";
+
+/// The app's side of Slack: its credentials and an HTTP client for Slack's Web API.
+pub struct SlackApp {
+    http: Client,
+    signing_secret: Option<String>,
+    bot_token: Option<String>,
+    webhook_url: Option<String>,
+}
+
+impl SlackApp {
+    /// The app of `config`; `None` without any `APP_SLACK_*` settings.
+    pub fn from_config(config: &Config) -> Result<Option<Self>, AppError> {
+        if config.slack_signing_secret.is_none() && config.slack_webhook_url.is_none() {
+            return Ok(None);
+        }
+        let http = Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .redirect(redirect::Policy::none())
+            .build()
+            .map_err(|e| AppError::InitError(format!("Failed to build Slack HTTP client: {}", e)))?;
+        Ok(Some(Self {
+            http,
+            signing_secret: config.slack_signing_secret.clone(),
+            bot_token: config.slack_bot_token.clone(),
+            webhook_url: config.slack_webhook_url.clone(),
+        }))
+    }
+
+    /// Whether `/slack/command` and `/slack/events` are served: only with the secret to
+    /// verify them.
+    pub fn accepts_requests(&self) -> bool {
+        self.signing_secret.is_some()
+    }
+
+    /// Whether new memes are posted to the incoming webhook.
+    pub fn sends_notifications(&self) -> bool {
+        self.webhook_url.is_some()
+    }
+
+    /// Checks that Slack signed `body` with the app's signing secret, recently.
+    fn verify(&self, headers: &HeaderMap, body: &[u8]) -> Result<(), AppError> {
+        let invalid = || AppError::Unauthorized("Invalid Slack signature".to_string());
+        let secret = self.signing_secret.as_ref().ok_or_else(invalid)?;
+        let timestamp = headers.get(TIMESTAMP_HEADER).and_then(|value| value.to_str().ok()).ok_or_else(invalid)?;
+        let sent_at: i64 = timestamp.parse().map_err(|_| invalid())?;
+        if (Utc::now().timestamp() - sent_at).abs() > MAX_REQUEST_AGE_SECS {
+            return Err(invalid());
+        }
+        let signature = headers
+            .get(SIGNATURE_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("v0="))
+            .and_then(|value| hex::decode(value).ok())
+            .ok_or_else(invalid)?;
+        // HMAC accepts keys of any length
+        let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC key of any size");
+        mac.update(format!("v0:{}:", timestamp).as_bytes());
+        mac.update(body);
+        mac.verify_slice(&signature).map_err(|_| invalid())
+    }
+
+    /// Replaces the previews of `links` to memes in a message with the memes' images.
+    async fn unfurl(&self, state: &AppState, event: LinkShared) -> anyhow::Result<()> {
+        let (Some(token), Some(base)) = (&self.bot_token, embeds::public_base(state)) else {
+            return Ok(());
+        };
+        let mut unfurls = serde_json::Map::new();
+        for link in event.links {
+            let Some(meme_id) = linked_meme(&link.url, base) else { continue };
+            let meme = state.meme_repo.get_by_id(meme_id, state.config.read_consistency(ReadEndpoint::GetMeme)).await?;
+            if let Some(meme) = meme.filter(|meme| meme.is_visible_to(false)) {
+                let view = handlers::meme_view(state, meme).await?;
+                unfurls.insert(link.url, json!({ "blocks": meme_blocks(&view) }));
+            }
+        }
+        if unfurls.is_empty() {
+            return Ok(());
+        }
+        let response = self
+            .http
+            .post(format!("{}/chat.unfurl", API_URL))
+            .bearer_auth(token)
+            .json(&json!({ "channel": event.channel, "ts": event.message_ts, "unfurls": unfurls }))
+            .send()
+            .await
+            .context("Slack is unreachable")?;
+        // The Web API answers 200 with `"ok": false` on failure
+        let answer: Value = response.json().await.context("Unexpected chat.unfurl response")?;
+        if answer["ok"] != Value::Bool(true) {
+            bail!("chat.unfurl failed: {}", answer["error"].as_str().unwrap_or("unknown error"));
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl MemeAnnouncer for SlackApp {
+    fn name(&self) -> &'static str {
+        "slack"
+    }
+
+    /// Posts `meme` to the incoming webhook.
+    async fn announce(&self, state: &AppState, meme: Meme) -> anyhow::Result<()> {
+        let Some(url) = &self.webhook_url else {
+            return Ok(());
+        };
+        let view = handlers::meme_view(state, meme).await?;
+        let message = json!({ "text": format!("New meme: {}", view.meme.title), "blocks": meme_blocks(&view) });
+        // Not logged: the URL is the webhook's credential
+        let response = self.http.post(url).json(&message).send().await.context("Slack is unreachable")?;
+        let status = response.status();
+        if !status.is_success() {
+            bail!("Slack responded with {}: {}", status, response.text().await.unwrap_or_default());
+        }
+        Ok(())
+    }
+}
+
+/// Handler for POST /slack/command, the `/meme` slash command.
+pub async fn handle_command(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Json<Value>, AppError> {
+    let slack = state.slack.clone().expect("route is only served with a Slack app");
+    slack.verify(&headers, &body)?;
+    let text = url::form_urlencoded::parse(&body)
+        .find(|(name, _)| name == "text")
+        .map(|(_, text)| text.trim().to_lowercase())
+        .unwrap_or_default();
+
+    match text.as_str() {
+        "random" => {
+            let Some(meme) = random_listed_meme(&state).await? else {
+                return Ok(ephemeral("There are no memes yet."));
+            };
+            let view = handlers::meme_view(&state, meme).await?;
+            Ok(Json(json!({ "response_type": "in_channel", "text": view.meme.title, "blocks": meme_blocks(&view) })))
+        }
+        _ => Ok(ephemeral(USAGE)),
+    }
+}
+
+/// A request to the Events API; only the kinds the app reads.
+#[derive(Deserialize, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum EventRequest {
+    /// Sent once when the Request URL is saved.
+    UrlVerification { challenge: String },
+    EventCallback { event: Value },
+    #[serde(other)]
+    Other,
+}
+
+/// A `link_shared` event: links on the app's unfurl domains were posted.
+#[derive(Deserialize, Debug)]
+struct LinkShared {
+    channel: String,
+    message_ts: String,
+    links: Vec<SharedLink>,
+}
+
+#[derive(Deserialize, Debug)]
+struct SharedLink {
+    url: String,
+}
+
+/// Handler for POST /slack/events. Events are acknowledged right away and handled in the
+/// background, since Slack retries events that take longer than three seconds.
+pub async fn handle_event(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Json<Value>, AppError> {
+    let slack = state.slack.clone().expect("route is only served with a Slack app");
+    slack.verify(&headers, &body)?;
+    let request: EventRequest =
+        serde_json::from_slice(&body).map_err(|e| AppError::InvalidInput(format!("Invalid event: {}", e)))?;
+
+    match request {
+        EventRequest::UrlVerification { challenge } => Ok(Json(json!({ "challenge": challenge }))),
+        EventRequest::EventCallback { event } if event["type"] == "link_shared" => {
+            let event: LinkShared =
+                serde_json::from_value(event).map_err(|e| AppError::InvalidInput(format!("Invalid link_shared event: {}", e)))?;
+            state.in_flight.clone().spawn(async move {
+                if let Err(e) = slack.unfurl(&state, event).await {
+                    tracing::warn!(error = %e, "Failed to unfurl links to memes in Slack");
+                }
+            });
+            Ok(Json(json!({})))
+        }
+        EventRequest::EventCallback { event } => {
+            tracing::debug!(event_type = %event["type"], "Ignoring Slack event");
+            Ok(Json(json!({})))
+        }
+        EventRequest::Other => Ok(Json(json!({}))),
+    }
+}
+
+/// Blocks showing a meme: its image, titled, or its title and description when Slack cannot
+/// fetch the image.
+fn meme_blocks(view: &MemeView) -> Value {
+    let title = truncate(&view.meme.title, MAX_BLOCK_TEXT);
+    if has_absolute_image_url(view) {
+        json!([{
+            "type": "image",
+            "title": { "type": "plain_text", "text": title },
+            "image_url": view.image_url,
+            "alt_text": title,
+        }])
+    } else {
+        json!([{
+            "type": "section",
+            "text": { "type": "plain_text", "text": format!("{}\n{}", title, truncate(&view.meme.description, MAX_BLOCK_TEXT)) },
+        }])
+    }
+}
+
+/// A reply shown only to the user who ran the command.
+fn ephemeral(text: &str) -> Json<Value> {
+    Json(json!({ "response_type": "ephemeral", "text": text }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECRET: &str = "8f742231b10e8888abcd99yyyzzz85a5";
+    const BODY: &[u8] = b"token=x&command=%2Fmeme&text=random";
+
+    fn app() -> SlackApp {
+        SlackApp { http: Client::new(), signing_secret: Some(SECRET.to_string()), bot_token: None, webhook_url: None }
+    }
+
+    /// The headers Slack sends with `body`, signed at `timestamp`.
+    fn signed(body: &[u8], timestamp: i64) -> HeaderMap {
+        let mut mac = Hmac::<Sha256>::new_from_slice(SECRET.as_bytes()).unwrap();
+        mac.update(format!("v0:{}:", timestamp).as_bytes());
+        mac.update(body);
+        let signature = format!("v0={}", hex::encode(mac.finalize().into_bytes()));
+        HeaderMap::from_iter([
+            (TIMESTAMP_HEADER.parse().unwrap(), timestamp.to_string().parse().unwrap()),
+            (SIGNATURE_HEADER.parse().unwrap(), signature.parse().unwrap()),
+        ])
+    }
+
+    #[test]
+    fn requests_signed_with_the_secret_verify() {
+        assert!(app().verify(&signed(BODY, Utc::now().timestamp()), BODY).is_ok());
+        assert!(app().verify(&signed(BODY, Utc::now().timestamp() - MAX_REQUEST_AGE_SECS + 5), BODY).is_ok());
+    }
+
+    #[test]
+    fn stale_requests_are_refused() {
+        for age in [MAX_REQUEST_AGE_SECS + 1, -(MAX_REQUEST_AGE_SECS + 1)] {
+            let headers = signed(BODY, Utc::now().timestamp() - age);
+            assert!(matches!(app().verify(&headers, BODY), Err(AppError::Unauthorized(_))), "{} seconds old", age);
+        }
+    }
+
+    #[test]
+    fn tampered_bodies_and_other_secrets_are_refused() {
+        let headers = signed(BODY, Utc::now().timestamp());
+        assert!(matches!(app().verify(&headers, b"token=x&command=%2Fmeme&text=other"), Err(AppError::Unauthorized(_))));
+        let other = SlackApp { signing_secret: Some("another secret".to_string()), ..app() };
+        assert!(matches!(other.verify(&headers, BODY), Err(AppError::Unauthorized(_))));
+    }
+
+    #[test]
+    fn requests_without_both_headers_are_refused() {
+        for missing in [TIMESTAMP_HEADER, SIGNATURE_HEADER] {
+            let mut headers = signed(BODY, Utc::now().timestamp());
+            headers.remove(missing);
+            assert!(matches!(app().verify(&headers, BODY), Err(AppError::Unauthorized(_))), "without {}", missing);
+        }
+        let mut malformed = signed(BODY, Utc::now().timestamp());
+        malformed.insert(SIGNATURE_HEADER, "v1=abc".parse().unwrap());
+        assert!(matches!(app().verify(&malformed, BODY), Err(AppError::Unauthorized(_))));
+    }
+}
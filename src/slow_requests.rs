@@ -0,0 +1,38 @@
+use crate::auth::REQUEST_ID_HEADER;
+use axum::{
+    extract::{MatchedPath, Request, State},
+    middleware::Next,
+    response::Response,
+};
+use std::time::{Duration, Instant};
+
+/// Route middleware logging a warning for requests whose response took `threshold` or longer
+/// to start, with the matched route (e.g. `/meme/{id}`), status and request ID. `None`
+/// disables it. Streaming the body afterwards is not counted, as for timeouts.
+pub async fn log_slow_requests(
+    State(threshold): State<Option<Duration>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let Some(threshold) = threshold else {
+        return next.run(request).await;
+    };
+    let method = request.method().clone();
+    let route = request.extensions().get::<MatchedPath>().map(|path| path.as_str().to_string());
+    let request_id = request.headers().get(REQUEST_ID_HEADER).and_then(|value| value.to_str().ok()).map(str::to_string);
+    let started = Instant::now();
+    let response = next.run(request).await;
+    let elapsed = started.elapsed();
+    if elapsed >= threshold {
+        tracing::warn!(
+            %method,
+            route = route.as_deref(),
+            status = response.status().as_u16(),
+            request_id = request_id.as_deref(),
+            elapsed_ms = elapsed.as_millis() as u64,
+            threshold_ms = threshold.as_millis() as u64,
+            "Slow request"
+        );
+    }
+    response
+}
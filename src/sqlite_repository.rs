@@ -0,0 +1,1178 @@
+use crate::{
+    config::Config,
+    domain::{
+        AuditEntry, AuditRepository, BackfillProgress, BackfillRepository, BlocklistRepository, ConsistencyLevel,
+        Follower, FollowerRepository, MemeHistoryRepository, MemeRepository, Shortlink, ShortlinkRepository, TableInfo, TenantConfigRepository, TenantOverrides,
+        TrendingRepository, TrendingSnapshot, VectorIndex,
+    },
+    embeddings,
+    errors::{AppError, RepoError},
+    models::{Meme, MemeStatus, SortOrder, Visibility},
+    repositories::{
+        audit_partition, audit_sort_key, backfill_partition, embedding_partition, history_partition, history_sort_key, shortlink_partition,
+        trending_partition, BLOCKLIST_PK, FOLLOWER_PK,
+        TENANT_CONFIG_PK, TRENDING_SK,
+    },
+};
+use async_trait::async_trait;
+use chrono::{DateTime, SecondsFormat, Utc};
+use rusqlite::{params, types::Type, Connection, ErrorCode, OptionalExtension, Row};
+use std::{
+    path::Path,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tracing::info;
+use uuid::Uuid;
+
+/// How long a statement waits for another connection's write lock before failing.
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Indexes of the meme table, by name and column: the three listing orders and expiry scans.
+/// SQLite index names are per database, so each is created as `<table>_<name>`.
+const INDEXES: &[(&str, &str)] = &[
+    ("by_created_at", "created_at"),
+    ("by_title", "title_key"),
+    ("by_like_count", "like_count"),
+    ("by_expires_at", "expires_at"),
+];
+
+/// Columns read back into a [`Meme`], in the order [`row_to_meme`] expects.
+const MEME_COLUMNS: &str =
+    "meme_id, title, description, image_key, tags, source_url, expires_at, created_at, version, like_count, visibility, \
+     publish_at, status, view_count, caption_text, dominant_color, palette, width, height, size_bytes, content_hash, updated_at";
+
+/// Columns added to the meme table after it was first released, with their definitions.
+/// `ensure_tables` adds them to tables created before.
+const ADDED_COLUMNS: &[(&str, &str)] = &[
+    ("publish_at", "INTEGER"),
+    ("status", "TEXT NOT NULL DEFAULT 'published'"),
+    ("view_count", "INTEGER NOT NULL DEFAULT 0"),
+    ("caption_text", "TEXT"),
+    ("dominant_color", "TEXT"),
+    ("palette", "TEXT NOT NULL DEFAULT '[]'"),
+    ("width", "INTEGER"),
+    ("height", "INTEGER"),
+    ("size_bytes", "INTEGER"),
+    ("content_hash", "TEXT"),
+    ("updated_at", "TEXT"),
+];
+
+/// Quotes a table or index name for use in SQL.
+fn quoted(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+/// A connection to the database file. rusqlite is blocking, so statements run on the
+/// blocking thread pool, one at a time per connection; WAL mode lets the repositories'
+/// separate connections read while one of them writes.
+#[derive(Debug, Clone)]
+struct Database {
+    connection: Arc<Mutex<Connection>>,
+}
+
+impl Database {
+    fn open(path: &Path) -> Result<Self, AppError> {
+        if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| AppError::InitError(format!("Failed to create directory '{}': {}", parent.display(), e)))?;
+        }
+        let open_error = |e: rusqlite::Error| AppError::InitError(format!("Failed to open SQLite database '{}': {}", path.display(), e));
+        let connection = Connection::open(path).map_err(open_error)?;
+        connection.busy_timeout(BUSY_TIMEOUT).map_err(open_error)?;
+        connection.pragma_update(None, "journal_mode", "WAL").map_err(open_error)?;
+        Ok(Self { connection: Arc::new(Mutex::new(connection)) })
+    }
+
+    /// Runs `f` on the connection. Values that fail to convert are reported as corrupt data.
+    async fn call<T, F>(&self, context: String, f: F) -> Result<T, RepoError>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut Connection) -> rusqlite::Result<T> + Send + 'static,
+    {
+        let connection = self.connection.clone();
+        let result = tokio::task::spawn_blocking(move || {
+            // A panic while holding the lock leaves nothing half-applied: SQLite rolls back
+            // an unfinished transaction, so the poisoned connection is still usable
+            let mut connection = connection.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+            f(&mut connection)
+        })
+        .await
+        .map_err(|e| RepoError::BackendError(anyhow::Error::new(e).context(context.clone())))?;
+        result.map_err(|e| match e {
+            rusqlite::Error::FromSqlConversionFailure(index, ..) => RepoError::DataCorruption {
+                field: format!("column {}", index),
+                reason: format!("{}: {}", context, e),
+            },
+            rusqlite::Error::InvalidColumnType(_, ref name, _) => RepoError::DataCorruption {
+                field: name.clone(),
+                reason: format!("{}: {}", context, e),
+            },
+            e => RepoError::BackendError(anyhow::Error::new(e).context(context)),
+        })
+    }
+}
+
+/// Creates the meme and meta tables and the meme table's indexes in `create` mode, or
+/// checks they exist. Uses `CREATE ... IF NOT EXISTS`, so this is safe on every start.
+pub async fn ensure_tables(config: &Config, create: bool) -> Result<(), AppError> {
+    let path = &config.sqlite_path;
+    if !create && !path.exists() {
+        return Err(AppError::InitError(format!(
+            "SQLite database '{}' does not exist. Create it, or set APP_RESOURCE_INIT=create",
+            path.display()
+        )));
+    }
+    let database = Database::open(path)?;
+    let table = config.dynamodb_table_name.clone();
+    let meta_table = config.meta_table_name.clone();
+    let mut expected = vec![table.clone(), meta_table.clone()];
+    expected.extend(INDEXES.iter().map(|(index, _)| format!("{}_{}", table, index)));
+    let context = format!("SQLite: Failed to set up tables in '{}'", path.display());
+    let missing = database
+        .call(context, move |connection| {
+            if create {
+                connection.execute_batch(&create_tables_sql(&table, &meta_table))?;
+            }
+            let mut statement = connection.prepare("SELECT 1 FROM sqlite_master WHERE type IN ('table', 'index') AND name = ?1")?;
+            let mut missing = Vec::new();
+            for name in expected {
+                if !statement.exists([&name])? {
+                    missing.push(name);
+                }
+            }
+            if missing.contains(&table) {
+                return Ok(missing);
+            }
+            let mut statement = connection.prepare("SELECT 1 FROM pragma_table_info(?1) WHERE name = ?2")?;
+            for (column, definition) in ADDED_COLUMNS {
+                if statement.exists([&table, &column.to_string()])? {
+                    continue;
+                }
+                if create {
+                    connection.execute(&format!("ALTER TABLE {} ADD COLUMN {} {}", quoted(&table), column, definition), [])?;
+                } else {
+                    missing.push(format!("column {}.{}", table, column));
+                }
+            }
+            Ok(missing)
+        })
+        .await
+        .map_err(|e| AppError::InitError(e.to_string()))?;
+    if !missing.is_empty() {
+        return Err(AppError::InitError(format!(
+            "SQLite database '{}' is missing {}. Create them, or set APP_RESOURCE_INIT=create",
+            path.display(),
+            missing.join(", ")
+        )));
+    }
+    info!(path = %path.display(), table = %config.dynamodb_table_name, "SQLite tables {}.", if create { "created" } else { "verified" });
+    Ok(())
+}
+
+fn create_tables_sql(table: &str, meta_table: &str) -> String {
+    let mut sql = format!(
+        "CREATE TABLE IF NOT EXISTS {table} (
+            meme_id TEXT PRIMARY KEY NOT NULL,
+            title TEXT NOT NULL,
+            description TEXT NOT NULL,
+            image_key TEXT NOT NULL,
+            tags TEXT NOT NULL DEFAULT '[]',
+            source_url TEXT,
+            expires_at INTEGER,
+            created_at TEXT,
+            version INTEGER NOT NULL,
+            like_count INTEGER NOT NULL DEFAULT 0,
+            visibility TEXT NOT NULL DEFAULT 'public',
+            title_key TEXT NOT NULL,
+            publish_at INTEGER,
+            status TEXT NOT NULL DEFAULT 'published',
+            view_count INTEGER NOT NULL DEFAULT 0,
+            caption_text TEXT,
+            dominant_color TEXT,
+            palette TEXT NOT NULL DEFAULT '[]',
+            width INTEGER,
+            height INTEGER,
+            size_bytes INTEGER,
+            content_hash TEXT,
+            updated_at TEXT
+        );
+        CREATE TABLE IF NOT EXISTS {meta_table} (
+            pk TEXT NOT NULL,
+            sk TEXT NOT NULL,
+            value TEXT,
+            PRIMARY KEY (pk, sk)
+        );",
+        table = quoted(table),
+        meta_table = quoted(meta_table),
+    );
+    for (index, column) in INDEXES {
+        sql.push_str(&format!(
+            "CREATE INDEX IF NOT EXISTS {} ON {} ({});",
+            quoted(&format!("{}_{}", table, index)),
+            quoted(table),
+            column
+        ));
+    }
+    sql
+}
+
+/// Implements `MemeRepository` on a table of the `APP_SQLITE_PATH` database named like the
+/// DynamoDB meme table (so tenants get `<table>-<tenant>`). Rows mirror the DynamoDB item
+/// attributes; tags are a JSON array and timestamps are stored as DynamoDB stores them.
+#[derive(Debug, Clone)]
+pub struct SqliteMemeRepository {
+    database: Database,
+    table_name: String,
+    table: String, // Quoted for SQL
+}
+
+impl SqliteMemeRepository {
+    pub fn open(config: &Config) -> Result<Self, AppError> {
+        let table_name = config.dynamodb_table_name.clone();
+        info!(path = %config.sqlite_path.display(), table = %table_name, "Initializing SqliteMemeRepository");
+        Ok(Self {
+            database: Database::open(&config.sqlite_path)?,
+            table: quoted(&table_name),
+            table_name,
+        })
+    }
+
+    /// Runs `sql` with `params` and reads every returned row as a meme.
+    async fn query_memes(&self, sql: String, params: Vec<rusqlite::types::Value>) -> Result<Vec<Meme>, RepoError> {
+        let context = format!("SQLite (table: {}): Failed to query memes", self.table_name);
+        self.database
+            .call(context, move |connection| {
+                let mut statement = connection.prepare(&sql)?;
+                let memes = statement.query_map(rusqlite::params_from_iter(params), row_to_meme)?;
+                memes.collect()
+            })
+            .await
+    }
+}
+
+#[async_trait]
+impl MemeRepository for SqliteMemeRepository {
+    async fn create(&self, meme: &Meme) -> Result<(), RepoError> {
+        let sql = format!("INSERT INTO {} ({}, title_key) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20, ?21, ?22, ?23)", self.table, MEME_COLUMNS);
+        let context = format!("SQLite (table: {}): Failed to insert meme (id: {})", self.table_name, meme.meme_id);
+        let row = MemeRow::from(meme);
+        let inserted = self
+            .database
+            .call(context, move |connection| match connection.execute(&sql, row.params()) {
+                Ok(_) => Ok(true),
+                Err(rusqlite::Error::SqliteFailure(e, _)) if e.code == ErrorCode::ConstraintViolation => Ok(false),
+                Err(e) => Err(e),
+            })
+            .await?;
+        if inserted { Ok(()) } else { Err(RepoError::AlreadyExists(meme.meme_id)) }
+    }
+
+    /// SQLite reads are always strong.
+    async fn get_by_id(&self, id: Uuid, _consistency: ConsistencyLevel) -> Result<Option<Meme>, RepoError> {
+        let sql = format!("SELECT {} FROM {} WHERE meme_id = ?1", MEME_COLUMNS, self.table);
+        let context = format!("SQLite (table: {}): Failed to get meme (id: {})", self.table_name, id);
+        let meme = self
+            .database
+            .call(context, move |connection| connection.query_row(&sql, [id.to_string()], row_to_meme).optional())
+            .await?;
+        // Expired memes are hidden until the cleanup job removes them
+        Ok(meme.filter(|meme| !meme.is_expired(Utc::now())))
+    }
+
+    async fn exists(&self, id: Uuid) -> Result<bool, RepoError> {
+        let sql = format!("SELECT 1 FROM {} WHERE meme_id = ?1 AND (expires_at IS NULL OR expires_at > ?2)", self.table);
+        let context = format!("SQLite (table: {}): Failed to check meme (id: {})", self.table_name, id);
+        let now = Utc::now().timestamp();
+        let found = self
+            .database
+            .call(context, move |connection| {
+                connection.query_row(&sql, params![id.to_string(), now], |_| Ok(())).optional()
+            })
+            .await?;
+        Ok(found.is_some())
+    }
+
+    async fn list_all(&self) -> Result<Vec<Meme>, RepoError> {
+        let sql = format!("SELECT {} FROM {} WHERE expires_at IS NULL OR expires_at > ?1", MEME_COLUMNS, self.table);
+        self.query_memes(sql, vec![Utc::now().timestamp().into()]).await
+    }
+
+    /// Sorts on the same columns as the DynamoDB listing indexes, leaving out memes without
+    /// the sort column just as those sparse indexes do.
+    async fn list_sorted(&self, order: SortOrder) -> Result<Vec<Meme>, RepoError> {
+        let (column, direction) = match order {
+            SortOrder::Newest => ("created_at", "DESC"),
+            SortOrder::Oldest => ("created_at", "ASC"),
+            SortOrder::Title => ("title_key", "ASC"),
+            SortOrder::Top => ("like_count", "DESC"),
+        };
+        let sql = format!(
+            "SELECT {} FROM {} WHERE (expires_at IS NULL OR expires_at > ?1) AND {} IS NOT NULL ORDER BY {} {}",
+            MEME_COLUMNS, self.table, column, column, direction
+        );
+        self.query_memes(sql, vec![Utc::now().timestamp().into()]).await
+    }
+
+    async fn list_expired(&self, now: DateTime<Utc>) -> Result<Vec<Meme>, RepoError> {
+        let sql = format!("SELECT {} FROM {} WHERE expires_at <= ?1", MEME_COLUMNS, self.table);
+        self.query_memes(sql, vec![now.timestamp().into()]).await
+    }
+
+    async fn list_due_for_publishing(&self, now: DateTime<Utc>) -> Result<Vec<Meme>, RepoError> {
+        let sql = format!("SELECT {} FROM {} WHERE status = ?1 AND publish_at <= ?2", MEME_COLUMNS, self.table);
+        self.query_memes(sql, vec![MemeStatus::Draft.as_str().to_string().into(), now.timestamp().into()]).await
+    }
+
+    /// Sets every column but the like and view counts while the stored version matches. When no row
+    /// matched, reads the version back to tell a lost race from a deleted meme.
+    async fn update(&self, meme: &Meme, expected_version: u64) -> Result<(), RepoError> {
+        let sql = format!(
+            "UPDATE {} SET title = ?2, description = ?3, image_key = ?4, tags = ?5, source_url = ?6, expires_at = ?7, \
+             created_at = ?8, version = ?9, visibility = ?11, publish_at = ?12, status = ?13, caption_text = ?15, \
+             dominant_color = ?16, palette = ?17, width = ?18, height = ?19, size_bytes = ?20, content_hash = ?21, \
+             updated_at = ?22, title_key = ?23 WHERE meme_id = ?1 AND version = ?24",
+            self.table
+        );
+        let version_sql = format!("SELECT version FROM {} WHERE meme_id = ?1", self.table);
+        let context = format!("SQLite (table: {}): Failed to update meme (id: {})", self.table_name, meme.meme_id);
+        let row = MemeRow::from(meme);
+        let expected = expected_version as i64;
+        // `None` once updated, otherwise the stored version if the meme still exists
+        let current = self
+            .database
+            .call(context, move |connection| {
+                let mut params = row.params().to_vec();
+                params.push(&expected);
+                if connection.execute(&sql, params.as_slice())? > 0 {
+                    return Ok(None);
+                }
+                connection.query_row(&version_sql, [&row.meme_id], count_column(0)).optional().map(Some)
+            })
+            .await?;
+        match current {
+            None => Ok(()),
+            Some(None) => Err(RepoError::NotFound(meme.meme_id)),
+            Some(Some(actual)) => Err(RepoError::VersionConflict { id: meme.meme_id, expected: expected_version, actual }),
+        }
+    }
+
+    /// Increments `like_count` in place, so concurrent likes are all counted.
+    async fn add_like(&self, id: Uuid) -> Result<u64, RepoError> {
+        let sql = format!(
+            "UPDATE {} SET like_count = like_count + 1 WHERE meme_id = ?1 AND (expires_at IS NULL OR expires_at > ?2) RETURNING like_count",
+            self.table
+        );
+        let context = format!("SQLite (table: {}): Failed to like meme (id: {})", self.table_name, id);
+        let now = Utc::now().timestamp();
+        let like_count = self
+            .database
+            .call(context, move |connection| connection.query_row(&sql, params![id.to_string(), now], count_column(0)).optional())
+            .await?;
+        like_count.ok_or(RepoError::NotFound(id))
+    }
+
+    /// Adds to `view_count` in place, so views counted by several instances all add up.
+    async fn add_views(&self, id: Uuid, views: u64) -> Result<(), RepoError> {
+        let sql = format!(
+            "UPDATE {} SET view_count = view_count + ?2 WHERE meme_id = ?1 AND (expires_at IS NULL OR expires_at > ?3)",
+            self.table
+        );
+        let context = format!("SQLite (table: {}): Failed to count views of meme (id: {})", self.table_name, id);
+        let now = Utc::now().timestamp();
+        let updated = self
+            .database
+            .call(context, move |connection| connection.execute(&sql, params![id.to_string(), views as i64, now]))
+            .await?;
+        if updated == 0 {
+            return Err(RepoError::NotFound(id));
+        }
+        Ok(())
+    }
+
+    async fn delete(&self, id: Uuid) -> Result<(), RepoError> {
+        let sql = format!("DELETE FROM {} WHERE meme_id = ?1", self.table);
+        let context = format!("SQLite (table: {}): Failed to delete meme (id: {})", self.table_name, id);
+        self.database.call(context, move |connection| connection.execute(&sql, [id.to_string()])).await?;
+        Ok(())
+    }
+
+    /// Reports the exact row count and the indexes. SQLite builds an index as part of the
+    /// statement creating it, so every index is reported active; sizes are not tracked per table.
+    async fn describe(&self) -> Result<TableInfo, RepoError> {
+        let count_sql = format!("SELECT COUNT(*) FROM {}", self.table);
+        let table_name = self.table_name.clone();
+        let context = format!("SQLite: Failed to describe table '{}'", self.table_name);
+        let (item_count, indexes) = self
+            .database
+            .call(context, move |connection| {
+                let item_count = connection.query_row(&count_sql, [], count_column(0))?;
+                let mut statement =
+                    connection.prepare("SELECT name FROM sqlite_master WHERE type = 'index' AND tbl_name = ?1 AND sql IS NOT NULL")?;
+                let indexes = statement.query_map([&table_name], |row| row.get::<_, String>(0))?.collect::<rusqlite::Result<Vec<_>>>()?;
+                Ok((item_count, indexes))
+            })
+            .await?;
+        let prefix = format!("{}_", self.table_name);
+        Ok(TableInfo {
+            name: self.table_name.clone(),
+            status: "ACTIVE".to_string(),
+            item_count: Some(item_count),
+            size_bytes: None,
+            indexes: indexes
+                .into_iter()
+                .map(|index| (index.strip_prefix(prefix.as_str()).map(str::to_string).unwrap_or(index), "ACTIVE".to_string()))
+                .collect(),
+        })
+    }
+
+    async fn count_created_since(&self, since: DateTime<Utc>) -> Result<u64, RepoError> {
+        let sql = format!("SELECT COUNT(*) FROM {} WHERE created_at >= ?1", self.table);
+        let context = format!("SQLite: Failed to count recent memes in table '{}'", self.table_name);
+        let since = since.to_rfc3339_opts(SecondsFormat::Nanos, true);
+        self.database.call(context, move |connection| connection.query_row(&sql, [since], count_column(0))).await
+    }
+
+    async fn count(&self) -> Result<u64, RepoError> {
+        let sql = format!("SELECT COUNT(*) FROM {} WHERE expires_at IS NULL OR expires_at > ?1", self.table);
+        let context = format!("SQLite: Failed to count memes in table '{}'", self.table_name);
+        let now = Utc::now().timestamp();
+        self.database.call(context, move |connection| connection.query_row(&sql, [now], count_column(0))).await
+    }
+
+    async fn ping(&self) -> Result<(), RepoError> {
+        let context = format!("SQLite: Table '{}' is not reachable", self.table_name);
+        let sql = format!("SELECT 1 FROM {} LIMIT 1", self.table);
+        self.database.call(context, move |connection| connection.query_row(&sql, [], |_| Ok(())).optional()).await?;
+        Ok(())
+    }
+}
+
+/// A meme as bound to the columns of [`MEME_COLUMNS`] followed by `title_key`.
+struct MemeRow {
+    meme_id: String,
+    title: String,
+    description: String,
+    image_key: String,
+    tags: String,
+    source_url: Option<String>,
+    expires_at: Option<i64>,
+    created_at: Option<String>,
+    version: i64,
+    like_count: i64,
+    visibility: &'static str,
+    publish_at: Option<i64>,
+    status: &'static str,
+    view_count: i64,
+    caption_text: Option<String>,
+    dominant_color: Option<String>,
+    palette: String,
+    width: Option<i64>,
+    height: Option<i64>,
+    size_bytes: Option<i64>,
+    content_hash: Option<String>,
+    updated_at: Option<String>,
+    title_key: String,
+}
+
+impl From<&Meme> for MemeRow {
+    fn from(meme: &Meme) -> Self {
+        Self {
+            meme_id: meme.meme_id.to_string(),
+            title: meme.title.clone(),
+            description: meme.description.clone(),
+            image_key: meme.image_key.clone(),
+            tags: serde_json::to_string(&meme.tags).unwrap_or_else(|_| "[]".to_string()),
+            source_url: meme.source_url.clone(),
+            expires_at: meme.expires_at.map(|expires_at| expires_at.timestamp()),
+            // Fixed-width UTC timestamps sort chronologically as strings, as in DynamoDB
+            created_at: meme.created_at.map(|created_at| created_at.to_rfc3339_opts(SecondsFormat::Nanos, true)),
+            version: meme.version as i64,
+            like_count: meme.like_count as i64,
+            visibility: meme.visibility.as_str(),
+            publish_at: meme.publish_at.map(|publish_at| publish_at.timestamp()),
+            status: meme.status.as_str(),
+            view_count: meme.view_count as i64,
+            caption_text: meme.caption_text.clone(),
+            dominant_color: meme.dominant_color.clone(),
+            palette: serde_json::to_string(&meme.palette).unwrap_or_else(|_| "[]".to_string()),
+            width: meme.width.map(i64::from),
+            height: meme.height.map(i64::from),
+            size_bytes: meme.size_bytes.map(|size| size as i64),
+            content_hash: meme.content_hash.clone(),
+            updated_at: meme.updated_at.map(|updated_at| updated_at.to_rfc3339_opts(SecondsFormat::Nanos, true)),
+            // The title is sorted without regard to case
+            title_key: meme.title.to_lowercase(),
+        }
+    }
+}
+
+impl MemeRow {
+    fn params(&self) -> [&dyn rusqlite::ToSql; 23] {
+        [
+            &self.meme_id,
+            &self.title,
+            &self.description,
+            &self.image_key,
+            &self.tags,
+            &self.source_url,
+            &self.expires_at,
+            &self.created_at,
+            &self.version,
+            &self.like_count,
+            &self.visibility,
+            &self.publish_at,
+            &self.status,
+            &self.view_count,
+            &self.caption_text,
+            &self.dominant_color,
+            &self.palette,
+            &self.width,
+            &self.height,
+            &self.size_bytes,
+            &self.content_hash,
+            &self.updated_at,
+            &self.title_key,
+        ]
+    }
+}
+
+/// Reports a column value that does not parse as corrupt.
+fn corrupt<E: Into<Box<dyn std::error::Error + Send + Sync>>>(index: usize, error: E) -> rusqlite::Error {
+    rusqlite::Error::FromSqlConversionFailure(index, Type::Text, error.into())
+}
+
+/// Reads a non-negative whole number column.
+fn count_column(index: usize) -> impl Fn(&Row) -> rusqlite::Result<u64> {
+    move |row| u64::try_from(row.get::<_, i64>(index)?).map_err(|e| corrupt(index, e))
+}
+
+/// Reads a nullable non-negative whole number column.
+fn optional_count_column(index: usize, row: &Row) -> rusqlite::Result<Option<u64>> {
+    row.get::<_, Option<i64>>(index)?.map(|value| u64::try_from(value).map_err(|e| corrupt(index, e))).transpose()
+}
+
+fn row_to_meme(row: &Row) -> rusqlite::Result<Meme> {
+    let tags: String = row.get(4)?;
+    let palette: String = row.get(16)?;
+    let expires_at = match row.get::<_, Option<i64>>(6)? {
+        Some(timestamp) => Some(DateTime::from_timestamp(timestamp, 0).ok_or_else(|| corrupt(6, "expiry out of range"))?),
+        None => None,
+    };
+    let created_at = match row.get::<_, Option<String>>(7)? {
+        Some(created_at) => Some(DateTime::parse_from_rfc3339(&created_at).map_err(|e| corrupt(7, e))?.with_timezone(&Utc)),
+        None => None,
+    };
+    let updated_at = match row.get::<_, Option<String>>(21)? {
+        Some(updated_at) => Some(DateTime::parse_from_rfc3339(&updated_at).map_err(|e| corrupt(21, e))?.with_timezone(&Utc)),
+        None => None,
+    };
+    let visibility: String = row.get(10)?;
+    let status: String = row.get(12)?;
+    let publish_at = match row.get::<_, Option<i64>>(11)? {
+        Some(timestamp) => Some(DateTime::from_timestamp(timestamp, 0).ok_or_else(|| corrupt(11, "publish time out of range"))?),
+        None => None,
+    };
+    Ok(Meme {
+        meme_id: Uuid::parse_str(&row.get::<_, String>(0)?).map_err(|e| corrupt(0, e))?,
+        title: row.get(1)?,
+        description: row.get(2)?,
+        image_key: row.get(3)?,
+        tags: serde_json::from_str(&tags).map_err(|e| corrupt(4, e))?,
+        source_url: row.get(5)?,
+        expires_at,
+        created_at,
+        version: count_column(8)(row)?,
+        like_count: count_column(9)(row)?,
+        visibility: Visibility::from_name(&visibility).ok_or_else(|| corrupt(10, format!("unknown visibility '{}'", visibility)))?,
+        publish_at,
+        status: MemeStatus::from_name(&status).ok_or_else(|| corrupt(12, format!("unknown status '{}'", status)))?,
+        view_count: count_column(13)(row)?,
+        caption_text: row.get(14)?,
+        dominant_color: row.get(15)?,
+        palette: serde_json::from_str(&palette).map_err(|e| corrupt(16, e))?,
+        width: optional_count_column(17, row)?.map(|width| u32::try_from(width).map_err(|e| corrupt(17, e))).transpose()?,
+        height: optional_count_column(18, row)?.map(|height| u32::try_from(height).map_err(|e| corrupt(18, e))).transpose()?,
+        size_bytes: optional_count_column(19, row)?,
+        content_hash: row.get(20)?,
+        updated_at,
+    })
+}
+
+/// Stores content filter terms in the SQLite meta table (pk = "blocklist", sk = term),
+/// laid out like the DynamoDB meta table.
+#[derive(Debug, Clone)]
+pub struct SqliteBlocklistRepository {
+    database: Database,
+    table_name: String,
+    table: String, // Quoted for SQL
+}
+
+impl SqliteBlocklistRepository {
+    pub fn open(config: &Config) -> Result<Self, AppError> {
+        let table_name = config.meta_table_name.clone();
+        info!(path = %config.sqlite_path.display(), table = %table_name, "Initializing SqliteBlocklistRepository");
+        Ok(Self {
+            database: Database::open(&config.sqlite_path)?,
+            table: quoted(&table_name),
+            table_name,
+        })
+    }
+}
+
+#[async_trait]
+impl BlocklistRepository for SqliteBlocklistRepository {
+    async fn list_terms(&self) -> Result<Vec<String>, RepoError> {
+        let sql = format!("SELECT sk FROM {} WHERE pk = ?1 ORDER BY sk", self.table);
+        let context = format!("SQLite (table: {}): Failed to query blocklist terms", self.table_name);
+        let terms = self
+            .database
+            .call(context, move |connection| {
+                let mut statement = connection.prepare(&sql)?;
+                let terms = statement.query_map([BLOCKLIST_PK], |row| row.get(0))?;
+                terms.collect::<rusqlite::Result<Vec<String>>>()
+            })
+            .await?;
+        tracing::debug!(table_name = %self.table_name, count = terms.len(), "SQLite: Loaded blocklist terms");
+        Ok(terms)
+    }
+
+    async fn add_term(&self, term: &str) -> Result<(), RepoError> {
+        let sql = format!("INSERT OR IGNORE INTO {} (pk, sk) VALUES (?1, ?2)", self.table);
+        let context = format!("SQLite (table: {}): Failed to add blocklist term", self.table_name);
+        let term = term.to_string();
+        self.database.call(context, move |connection| connection.execute(&sql, [BLOCKLIST_PK, &term])).await?;
+        Ok(())
+    }
+
+    async fn remove_term(&self, term: &str) -> Result<(), RepoError> {
+        let sql = format!("DELETE FROM {} WHERE pk = ?1 AND sk = ?2", self.table);
+        let context = format!("SQLite (table: {}): Failed to remove blocklist term", self.table_name);
+        let term = term.to_string();
+        self.database.call(context, move |connection| connection.execute(&sql, [BLOCKLIST_PK, &term])).await?;
+        Ok(())
+    }
+}
+
+/// Stores per-tenant setting overrides in the SQLite meta table (pk = "tenant-config",
+/// sk = tenant ID), with the overrides as JSON in `value`.
+#[derive(Debug, Clone)]
+pub struct SqliteTenantConfigRepository {
+    database: Database,
+    table_name: String,
+    table: String, // Quoted for SQL
+}
+
+impl SqliteTenantConfigRepository {
+    pub fn open(config: &Config) -> Result<Self, AppError> {
+        let table_name = config.meta_table_name.clone();
+        info!(path = %config.sqlite_path.display(), table = %table_name, "Initializing SqliteTenantConfigRepository");
+        Ok(Self {
+            database: Database::open(&config.sqlite_path)?,
+            table: quoted(&table_name),
+            table_name,
+        })
+    }
+}
+
+#[async_trait]
+impl TenantConfigRepository for SqliteTenantConfigRepository {
+    async fn get_overrides(&self, tenant: &str) -> Result<Option<TenantOverrides>, RepoError> {
+        let sql = format!("SELECT value FROM {} WHERE pk = ?1 AND sk = ?2", self.table);
+        let context = format!("SQLite (table: {}): Failed to get overrides of tenant '{}'", self.table_name, tenant);
+        let key = tenant.to_string();
+        let value: Option<String> = self
+            .database
+            .call(context, move |connection| connection.query_row(&sql, [TENANT_CONFIG_PK, &key], |row| row.get(0)).optional())
+            .await?;
+        value
+            .map(|value| {
+                serde_json::from_str(&value).map_err(|e| RepoError::DataCorruption {
+                    field: "value".to_string(),
+                    reason: format!("Malformed overrides of tenant '{}' in table '{}': {}", tenant, self.table_name, e),
+                })
+            })
+            .transpose()
+    }
+
+    async fn put_overrides(&self, tenant: &str, overrides: &TenantOverrides) -> Result<(), RepoError> {
+        let sql = format!("INSERT OR REPLACE INTO {} (pk, sk, value) VALUES (?1, ?2, ?3)", self.table);
+        let context = format!("SQLite (table: {}): Failed to save overrides of tenant '{}'", self.table_name, tenant);
+        let value = serde_json::to_string(overrides)
+            .map_err(|e| RepoError::BackendError(anyhow::Error::new(e).context(context.clone())))?;
+        let key = tenant.to_string();
+        self.database.call(context, move |connection| connection.execute(&sql, [TENANT_CONFIG_PK, &key, &value])).await?;
+        Ok(())
+    }
+}
+
+/// Stores earlier meme versions in the SQLite meta table (pk = "meme-history#<id>",
+/// sk = zero-padded version), with each version as JSON in `value`, laid out like the
+/// DynamoDB meta table.
+#[derive(Debug, Clone)]
+pub struct SqliteMemeHistoryRepository {
+    database: Database,
+    table_name: String,
+    table: String, // Quoted for SQL
+}
+
+impl SqliteMemeHistoryRepository {
+    pub fn open(config: &Config) -> Result<Self, AppError> {
+        let table_name = config.meta_table_name.clone();
+        info!(path = %config.sqlite_path.display(), table = %table_name, "Initializing SqliteMemeHistoryRepository");
+        Ok(Self { database: Database::open(&config.sqlite_path)?, table: quoted(&table_name), table_name })
+    }
+
+    fn parse_version(&self, value: &str) -> Result<Meme, RepoError> {
+        serde_json::from_str(value).map_err(|e| RepoError::DataCorruption {
+            field: "value".to_string(),
+            reason: format!("Malformed meme version in table '{}': {}", self.table_name, e),
+        })
+    }
+}
+
+#[async_trait]
+impl MemeHistoryRepository for SqliteMemeHistoryRepository {
+    async fn save_version(&self, meme: &Meme) -> Result<(), RepoError> {
+        let sql = format!("INSERT OR REPLACE INTO {} (pk, sk, value) VALUES (?1, ?2, ?3)", self.table);
+        let context = format!(
+            "SQLite (table: {}): Failed to save version {} of meme {}",
+            self.table_name, meme.version, meme.meme_id
+        );
+        let value = serde_json::to_string(meme)
+            .map_err(|e| RepoError::BackendError(anyhow::Error::new(e).context(context.clone())))?;
+        let (partition, key) = (history_partition(meme.meme_id), history_sort_key(meme.version));
+        self.database.call(context, move |connection| connection.execute(&sql, [&partition, &key, &value])).await?;
+        Ok(())
+    }
+
+    async fn list_versions(&self, id: Uuid) -> Result<Vec<Meme>, RepoError> {
+        let sql = format!("SELECT value FROM {} WHERE pk = ?1 ORDER BY sk DESC", self.table);
+        let context = format!("SQLite (table: {}): Failed to query history of meme {}", self.table_name, id);
+        let partition = history_partition(id);
+        let values: Vec<String> = self
+            .database
+            .call(context, move |connection| {
+                let mut statement = connection.prepare(&sql)?;
+                let values = statement.query_map([partition], |row| row.get(0))?;
+                values.collect::<rusqlite::Result<Vec<String>>>()
+            })
+            .await?;
+        values.iter().map(|value| self.parse_version(value)).collect()
+    }
+
+    async fn get_version(&self, id: Uuid, version: u64) -> Result<Option<Meme>, RepoError> {
+        let sql = format!("SELECT value FROM {} WHERE pk = ?1 AND sk = ?2", self.table);
+        let context = format!("SQLite (table: {}): Failed to get version {} of meme {}", self.table_name, version, id);
+        let (partition, key) = (history_partition(id), history_sort_key(version));
+        let value: Option<String> = self
+            .database
+            .call(context, move |connection| connection.query_row(&sql, [partition, key], |row| row.get(0)).optional())
+            .await?;
+        value.map(|value| self.parse_version(&value)).transpose()
+    }
+
+    async fn delete_versions(&self, id: Uuid) -> Result<(), RepoError> {
+        let sql = format!("DELETE FROM {} WHERE pk = ?1", self.table);
+        let context = format!("SQLite (table: {}): Failed to delete history of meme {}", self.table_name, id);
+        let partition = history_partition(id);
+        self.database.call(context, move |connection| connection.execute(&sql, [partition])).await?;
+        Ok(())
+    }
+}
+
+/// Stores the audit log in the SQLite meta table (pk = "audit" or "audit#<tenant>",
+/// sk = time and entry ID), with each entry as JSON in `value`, laid out like the DynamoDB
+/// meta table.
+#[derive(Debug, Clone)]
+pub struct SqliteAuditRepository {
+    database: Database,
+    table_name: String,
+    table: String, // Quoted for SQL
+    partition: String,
+}
+
+impl SqliteAuditRepository {
+    pub fn open(config: &Config) -> Result<Self, AppError> {
+        let table_name = config.meta_table_name.clone();
+        info!(path = %config.sqlite_path.display(), table = %table_name, "Initializing SqliteAuditRepository");
+        Ok(Self {
+            database: Database::open(&config.sqlite_path)?,
+            table: quoted(&table_name),
+            table_name,
+            partition: audit_partition(config.tenant.as_deref()),
+        })
+    }
+}
+
+#[async_trait]
+impl AuditRepository for SqliteAuditRepository {
+    /// A plain INSERT, so an existing key fails instead of being overwritten.
+    async fn append(&self, entry: &AuditEntry) -> Result<(), RepoError> {
+        let sql = format!("INSERT INTO {} (pk, sk, value) VALUES (?1, ?2, ?3)", self.table);
+        let context = format!("SQLite (table: {}): Failed to append audit entry (meme: {})", self.table_name, entry.meme_id);
+        let value = serde_json::to_string(entry)
+            .map_err(|e| RepoError::BackendError(anyhow::Error::new(e).context(context.clone())))?;
+        let (partition, key) = (self.partition.clone(), audit_sort_key(entry));
+        self.database.call(context, move |connection| connection.execute(&sql, [&partition, &key, &value])).await?;
+        Ok(())
+    }
+
+    async fn list_since(&self, since: DateTime<Utc>, limit: usize) -> Result<Vec<AuditEntry>, RepoError> {
+        let sql = format!("SELECT value FROM {} WHERE pk = ?1 AND sk >= ?2 ORDER BY sk LIMIT ?3", self.table);
+        let context = format!("SQLite (table: {}): Failed to query the audit log", self.table_name);
+        let partition = self.partition.clone();
+        let since = since.to_rfc3339_opts(SecondsFormat::Nanos, true);
+        let limit = i64::try_from(limit).unwrap_or(i64::MAX);
+        let values: Vec<String> = self
+            .database
+            .call(context, move |connection| {
+                let mut statement = connection.prepare(&sql)?;
+                let values = statement.query_map(params![partition, since, limit], |row| row.get(0))?;
+                values.collect::<rusqlite::Result<Vec<String>>>()
+            })
+            .await?;
+        values
+            .iter()
+            .map(|value| {
+                serde_json::from_str(value).map_err(|e| RepoError::DataCorruption {
+                    field: "value".to_string(),
+                    reason: format!("Malformed audit entry in table '{}': {}", self.table_name, e),
+                })
+            })
+            .collect()
+    }
+}
+
+/// Stores the trending rankings as one row of the SQLite meta table (pk = "trending" or
+/// "trending#<tenant>", sk = "rankings"), with the snapshot as JSON in `value`, laid out like
+/// the DynamoDB meta table.
+#[derive(Debug, Clone)]
+pub struct SqliteTrendingRepository {
+    database: Database,
+    table_name: String,
+    table: String, // Quoted for SQL
+    partition: String,
+}
+
+impl SqliteTrendingRepository {
+    pub fn open(config: &Config) -> Result<Self, AppError> {
+        let table_name = config.meta_table_name.clone();
+        info!(path = %config.sqlite_path.display(), table = %table_name, "Initializing SqliteTrendingRepository");
+        Ok(Self {
+            database: Database::open(&config.sqlite_path)?,
+            table: quoted(&table_name),
+            table_name,
+            partition: trending_partition(config.tenant.as_deref()),
+        })
+    }
+}
+
+#[async_trait]
+impl TrendingRepository for SqliteTrendingRepository {
+    async fn load(&self) -> Result<Option<TrendingSnapshot>, RepoError> {
+        let sql = format!("SELECT value FROM {} WHERE pk = ?1 AND sk = ?2", self.table);
+        let context = format!("SQLite (table: {}): Failed to get the trending rankings", self.table_name);
+        let partition = self.partition.clone();
+        let value: Option<String> = self
+            .database
+            .call(context, move |connection| {
+                connection.query_row(&sql, [partition.as_str(), TRENDING_SK], |row| row.get(0)).optional()
+            })
+            .await?;
+        value
+            .map(|value| {
+                serde_json::from_str(&value).map_err(|e| RepoError::DataCorruption {
+                    field: "value".to_string(),
+                    reason: format!("Malformed trending rankings in table '{}': {}", self.table_name, e),
+                })
+            })
+            .transpose()
+    }
+
+    async fn save(&self, snapshot: &TrendingSnapshot) -> Result<(), RepoError> {
+        let sql = format!("INSERT OR REPLACE INTO {} (pk, sk, value) VALUES (?1, ?2, ?3)", self.table);
+        let context = format!("SQLite (table: {}): Failed to save the trending rankings", self.table_name);
+        let value = serde_json::to_string(snapshot)
+            .map_err(|e| RepoError::BackendError(anyhow::Error::new(e).context(context.clone())))?;
+        let partition = self.partition.clone();
+        self.database
+            .call(context, move |connection| connection.execute(&sql, [partition.as_str(), TRENDING_SK, value.as_str()]))
+            .await?;
+        Ok(())
+    }
+}
+
+/// Stores backfill progress in the SQLite meta table (pk = "backfill" or "backfill#<tenant>",
+/// sk = field), with the progress as JSON in `value`, laid out like the DynamoDB meta table.
+#[derive(Debug, Clone)]
+pub struct SqliteBackfillRepository {
+    database: Database,
+    table_name: String,
+    table: String, // Quoted for SQL
+    partition: String,
+}
+
+impl SqliteBackfillRepository {
+    pub fn open(config: &Config) -> Result<Self, AppError> {
+        let table_name = config.meta_table_name.clone();
+        info!(path = %config.sqlite_path.display(), table = %table_name, "Initializing SqliteBackfillRepository");
+        Ok(Self {
+            database: Database::open(&config.sqlite_path)?,
+            table: quoted(&table_name),
+            table_name,
+            partition: backfill_partition(config.tenant.as_deref()),
+        })
+    }
+
+    fn parse_progress(&self, value: &str) -> Result<BackfillProgress, RepoError> {
+        serde_json::from_str(value).map_err(|e| RepoError::DataCorruption {
+            field: "value".to_string(),
+            reason: format!("Malformed backfill progress in table '{}': {}", self.table_name, e),
+        })
+    }
+}
+
+#[async_trait]
+impl BackfillRepository for SqliteBackfillRepository {
+    async fn load(&self, field: &str) -> Result<Option<BackfillProgress>, RepoError> {
+        let sql = format!("SELECT value FROM {} WHERE pk = ?1 AND sk = ?2", self.table);
+        let context = format!("SQLite (table: {}): Failed to get backfill progress (field: {})", self.table_name, field);
+        let (partition, field) = (self.partition.clone(), field.to_string());
+        let value: Option<String> = self
+            .database
+            .call(context, move |connection| connection.query_row(&sql, [partition, field], |row| row.get(0)).optional())
+            .await?;
+        value.map(|value| self.parse_progress(&value)).transpose()
+    }
+
+    async fn list(&self) -> Result<Vec<BackfillProgress>, RepoError> {
+        let sql = format!("SELECT value FROM {} WHERE pk = ?1 ORDER BY sk", self.table);
+        let context = format!("SQLite (table: {}): Failed to query backfill progress", self.table_name);
+        let partition = self.partition.clone();
+        let values: Vec<String> = self
+            .database
+            .call(context, move |connection| {
+                let mut statement = connection.prepare(&sql)?;
+                let values = statement.query_map([partition], |row| row.get(0))?;
+                values.collect::<rusqlite::Result<Vec<String>>>()
+            })
+            .await?;
+        values.iter().map(|value| self.parse_progress(value)).collect()
+    }
+
+    async fn save(&self, progress: &BackfillProgress) -> Result<(), RepoError> {
+        let sql = format!("INSERT OR REPLACE INTO {} (pk, sk, value) VALUES (?1, ?2, ?3)", self.table);
+        let context = format!("SQLite (table: {}): Failed to save backfill progress (field: {})", self.table_name, progress.field);
+        let value = serde_json::to_string(progress)
+            .map_err(|e| RepoError::BackendError(anyhow::Error::new(e).context(context.clone())))?;
+        let (partition, field) = (self.partition.clone(), progress.field.clone());
+        self.database
+            .call(context, move |connection| connection.execute(&sql, [partition, field, value]))
+            .await?;
+        Ok(())
+    }
+}
+
+/// Stores meme vectors in the SQLite meta table (pk = "embedding" or "embedding#<tenant>",
+/// sk = meme ID), each as a JSON array in `value`, laid out like the DynamoDB meta table.
+/// Searches compare every vector, like [`crate::repositories::DynamoDbVectorIndex`].
+#[derive(Debug, Clone)]
+pub struct SqliteVectorIndex {
+    database: Database,
+    table_name: String,
+    table: String, // Quoted for SQL
+    partition: String,
+}
+
+impl SqliteVectorIndex {
+    pub fn open(config: &Config) -> Result<Self, AppError> {
+        let table_name = config.meta_table_name.clone();
+        info!(path = %config.sqlite_path.display(), table = %table_name, "Initializing SqliteVectorIndex");
+        Ok(Self {
+            database: Database::open(&config.sqlite_path)?,
+            table: quoted(&table_name),
+            table_name,
+            partition: embedding_partition(config.tenant.as_deref()),
+        })
+    }
+}
+
+#[async_trait]
+impl VectorIndex for SqliteVectorIndex {
+    async fn upsert(&self, id: Uuid, vector: &[f32]) -> Result<(), RepoError> {
+        let sql = format!("INSERT OR REPLACE INTO {} (pk, sk, value) VALUES (?1, ?2, ?3)", self.table);
+        let context = format!("SQLite (table: {}): Failed to store the vector of meme {}", self.table_name, id);
+        let value = serde_json::to_string(vector)
+            .map_err(|e| RepoError::BackendError(anyhow::Error::new(e).context(context.clone())))?;
+        let (partition, key) = (self.partition.clone(), id.to_string());
+        self.database.call(context, move |connection| connection.execute(&sql, [&partition, &key, &value])).await?;
+        Ok(())
+    }
+
+    async fn remove(&self, id: Uuid) -> Result<(), RepoError> {
+        let sql = format!("DELETE FROM {} WHERE pk = ?1 AND sk = ?2", self.table);
+        let context = format!("SQLite (table: {}): Failed to delete the vector of meme {}", self.table_name, id);
+        let (partition, key) = (self.partition.clone(), id.to_string());
+        self.database.call(context, move |connection| connection.execute(&sql, [partition, key])).await?;
+        Ok(())
+    }
+
+    async fn nearest(&self, query: &[f32], limit: usize) -> Result<Vec<(Uuid, f32)>, RepoError> {
+        let sql = format!("SELECT sk, value FROM {} WHERE pk = ?1", self.table);
+        let context = format!("SQLite (table: {}): Failed to query meme vectors", self.table_name);
+        let partition = self.partition.clone();
+        let rows: Vec<(String, String)> = self
+            .database
+            .call(context, move |connection| {
+                let mut statement = connection.prepare(&sql)?;
+                let rows = statement.query_map([partition], |row| Ok((row.get(0)?, row.get(1)?)))?;
+                rows.collect::<rusqlite::Result<Vec<_>>>()
+            })
+            .await?;
+        let vectors = rows
+            .iter()
+            .map(|(key, value)| match (key.parse(), serde_json::from_str(value)) {
+                (Ok(id), Ok(vector)) => Ok((id, vector)),
+                _ => Err(RepoError::DataCorruption {
+                    field: "value".to_string(),
+                    reason: format!("Malformed meme vector in table '{}'", self.table_name),
+                }),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(embeddings::nearest(vectors, query, limit))
+    }
+}
+
+/// Stores shortlinks in the SQLite meta table (pk = "shortlink" or "shortlink#<tenant>",
+/// sk = code), each as JSON in `value`, laid out like the DynamoDB meta table. Hits are
+/// counted inside the JSON, in a single statement.
+#[derive(Debug, Clone)]
+pub struct SqliteShortlinkRepository {
+    database: Database,
+    table_name: String,
+    table: String, // Quoted for SQL
+    partition: String,
+}
+
+impl SqliteShortlinkRepository {
+    pub fn open(config: &Config) -> Result<Self, AppError> {
+        let table_name = config.meta_table_name.clone();
+        info!(path = %config.sqlite_path.display(), table = %table_name, "Initializing SqliteShortlinkRepository");
+        Ok(Self {
+            database: Database::open(&config.sqlite_path)?,
+            table: quoted(&table_name),
+            table_name,
+            partition: shortlink_partition(config.tenant.as_deref()),
+        })
+    }
+}
+
+#[async_trait]
+impl ShortlinkRepository for SqliteShortlinkRepository {
+    async fn create(&self, link: &Shortlink) -> Result<bool, RepoError> {
+        let sql = format!("INSERT OR IGNORE INTO {} (pk, sk, value) VALUES (?1, ?2, ?3)", self.table);
+        let context = format!("SQLite (table: {}): Failed to create shortlink '{}'", self.table_name, link.code);
+        let value = serde_json::to_string(link)
+            .map_err(|e| RepoError::BackendError(anyhow::Error::new(e).context(context.clone())))?;
+        let (partition, code) = (self.partition.clone(), link.code.clone());
+        let inserted = self
+            .database
+            .call(context, move |connection| connection.execute(&sql, [partition, code, value]))
+            .await?;
+        Ok(inserted == 1)
+    }
+
+    async fn get(&self, code: &str) -> Result<Option<Shortlink>, RepoError> {
+        let sql = format!("SELECT value FROM {} WHERE pk = ?1 AND sk = ?2", self.table);
+        let context = format!("SQLite (table: {}): Failed to get shortlink '{}'", self.table_name, code);
+        let (partition, key) = (self.partition.clone(), code.to_string());
+        let value: Option<String> = self
+            .database
+            .call(context, move |connection| connection.query_row(&sql, [partition, key], |row| row.get(0)).optional())
+            .await?;
+        value
+            .map(|value| {
+                serde_json::from_str(&value).map_err(|e| RepoError::DataCorruption {
+                    field: "value".to_string(),
+                    reason: format!("Malformed shortlink '{}' in table '{}': {}", code, self.table_name, e),
+                })
+            })
+            .transpose()
+    }
+
+    async fn add_hit(&self, code: &str) -> Result<(), RepoError> {
+        let sql = format!(
+            "UPDATE {} SET value = json_set(value, '$.hits', json_extract(value, '$.hits') + 1) WHERE pk = ?1 AND sk = ?2",
+            self.table
+        );
+        let context = format!("SQLite (table: {}): Failed to count a hit of shortlink '{}'", self.table_name, code);
+        let (partition, key) = (self.partition.clone(), code.to_string());
+        self.database.call(context, move |connection| connection.execute(&sql, [partition, key])).await?;
+        Ok(())
+    }
+}
+
+/// Stores the ActivityPub actor's followers in the SQLite meta table (pk = "follower",
+/// sk = actor ID), with the follower as JSON in `value`.
+#[derive(Debug, Clone)]
+pub struct SqliteFollowerRepository {
+    database: Database,
+    table_name: String,
+    table: String, // Quoted for SQL
+}
+
+impl SqliteFollowerRepository {
+    pub fn open(config: &Config) -> Result<Self, AppError> {
+        let table_name = config.meta_table_name.clone();
+        info!(path = %config.sqlite_path.display(), table = %table_name, "Initializing SqliteFollowerRepository");
+        Ok(Self {
+            database: Database::open(&config.sqlite_path)?,
+            table: quoted(&table_name),
+            table_name,
+        })
+    }
+}
+
+#[async_trait]
+impl FollowerRepository for SqliteFollowerRepository {
+    async fn add(&self, follower: &Follower) -> Result<(), RepoError> {
+        let sql = format!("INSERT OR REPLACE INTO {} (pk, sk, value) VALUES (?1, ?2, ?3)", self.table);
+        let context = format!("SQLite (table: {}): Failed to add follower '{}'", self.table_name, follower.actor);
+        let value = serde_json::to_string(follower)
+            .map_err(|e| RepoError::BackendError(anyhow::Error::new(e).context(context.clone())))?;
+        let actor = follower.actor.clone();
+        self.database.call(context, move |connection| connection.execute(&sql, [FOLLOWER_PK, &actor, &value])).await?;
+        Ok(())
+    }
+
+    async fn remove(&self, actor: &str) -> Result<(), RepoError> {
+        let sql = format!("DELETE FROM {} WHERE pk = ?1 AND sk = ?2", self.table);
+        let context = format!("SQLite (table: {}): Failed to remove follower '{}'", self.table_name, actor);
+        let actor = actor.to_string();
+        self.database.call(context, move |connection| connection.execute(&sql, [FOLLOWER_PK, &actor])).await?;
+        Ok(())
+    }
+
+    async fn list(&self) -> Result<Vec<Follower>, RepoError> {
+        let sql = format!("SELECT value FROM {} WHERE pk = ?1 ORDER BY sk", self.table);
+        let context = format!("SQLite (table: {}): Failed to query followers", self.table_name);
+        let values = self
+            .database
+            .call(context, move |connection| {
+                let mut statement = connection.prepare(&sql)?;
+                let values = statement.query_map([FOLLOWER_PK], |row| row.get(0))?;
+                values.collect::<rusqlite::Result<Vec<String>>>()
+            })
+            .await?;
+        values
+            .iter()
+            .map(|value| {
+                serde_json::from_str(value).map_err(|e| RepoError::DataCorruption {
+                    field: "value".to_string(),
+                    reason: format!("Malformed follower in table '{}': {}", self.table_name, e),
+                })
+            })
+            .collect()
+    }
+}
@@ -1,18 +1,126 @@
-use crate::errors::AppError;
+use crate::{
+    errors::AppError,
+    repositories::{ListingIndex, LISTING_ATTRIBUTE, LISTING_INDEXES, MEME_TTL_ATTRIBUTE},
+};
 use aws_sdk_dynamodb::{
     operation::create_table::CreateTableError,
-    types::{AttributeDefinition, BillingMode, KeySchemaElement, KeyType, ScalarAttributeType},
+    types::{
+        AttributeDefinition, BillingMode, CreateGlobalSecondaryIndexAction, GlobalSecondaryIndex,
+        GlobalSecondaryIndexUpdate, IndexStatus, KeySchemaElement, KeyType, Projection, ProjectionType,
+        ProvisionedThroughput, ProvisionedThroughputDescription, ScalarAttributeType, TableDescription,
+    },
     Client as DynamoDbClient, error::SdkError as DynamoSdkError_CreateTable,
 };
+use aws_sdk_dynamodb::{
+    error::ProvideErrorMetadata,
+    types::{StreamSpecification, StreamViewType, TableStatus, TimeToLiveSpecification, TimeToLiveStatus},
+};
 use aws_sdk_s3::{
     operation::create_bucket::CreateBucketError,
-    types::{BucketLocationConstraint, CreateBucketConfiguration},
+    types::{
+        AbortIncompleteMultipartUpload, AccelerateConfiguration, BucketAccelerateStatus, BucketLifecycleConfiguration,
+        BucketLocationConstraint, BucketVersioningStatus, CreateBucketConfiguration, ExpirationStatus, LifecycleRule, LifecycleRuleFilter,
+        PublicAccessBlockConfiguration, ServerSideEncryption, ServerSideEncryptionByDefault,
+        ServerSideEncryptionConfiguration, ServerSideEncryptionRule, VersioningConfiguration,
+    },
     Client as S3Client, error::SdkError as S3SdkError_CreateBucket,
 };
 use backoff::{future::retry, ExponentialBackoff};
-use std::time::Duration;
+use serde::Serialize;
+use std::{future::Future, str::FromStr, time::Duration};
 use tracing::{error, info, warn};
 
+/// How startup treats the DynamoDB tables and S3 bucket.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ResourceInitMode {
+    /// Create missing resources; needs CreateTable/CreateBucket permissions.
+    Create,
+    /// Check that resources exist and look right (DescribeTable/HeadBucket) without creating anything.
+    Verify,
+    /// Trust external provisioning and make no calls at startup.
+    Skip,
+}
+
+impl FromStr for ResourceInitMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "create" => Ok(ResourceInitMode::Create),
+            "verify" => Ok(ResourceInitMode::Verify),
+            "skip" => Ok(ResourceInitMode::Skip),
+            other => Err(format!("unknown resource init mode '{}' (expected create, verify or skip)", other)),
+        }
+    }
+}
+
+/// Read and write capacity units of a provisioned table or index.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub struct Throughput {
+    pub read: i64,
+    pub write: i64,
+}
+
+impl Throughput {
+    fn provisioned(&self) -> Result<ProvisionedThroughput, aws_sdk_dynamodb::error::BuildError> {
+        ProvisionedThroughput::builder().read_capacity_units(self.read).write_capacity_units(self.write).build()
+    }
+}
+
+/// How a DynamoDB table created at startup is billed (`APP_DYNAMODB_BILLING_MODE`).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(tag = "mode", rename_all = "snake_case")]
+pub enum TableCapacity {
+    /// On-demand: billed per request, no capacity to plan.
+    PayPerRequest,
+    /// Fixed throughput for the table, and for each of its global secondary indexes.
+    Provisioned { table: Throughput, indexes: Throughput },
+}
+
+/// Default server-side encryption applied to the bucket.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BucketEncryption {
+    /// Leave the bucket's encryption configuration as it is.
+    Unchanged,
+    /// SSE-S3 (AES-256 with S3-managed keys).
+    #[serde(rename = "aes256")]
+    S3Managed,
+    /// SSE-KMS, with the AWS-managed key unless `APP_S3_KMS_KEY_ID` is set.
+    Kms,
+}
+
+impl FromStr for BucketEncryption {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "unchanged" => Ok(BucketEncryption::Unchanged),
+            "aes256" => Ok(BucketEncryption::S3Managed),
+            "kms" | "aws:kms" => Ok(BucketEncryption::Kms),
+            other => Err(format!("unknown bucket encryption '{}' (expected unchanged, aes256 or kms)", other)),
+        }
+    }
+}
+
+/// Hardening applied to the meme bucket when resources are created.
+#[derive(Clone, Debug)]
+pub struct BucketSettings {
+    pub block_public_access: bool,
+    pub encryption: BucketEncryption,
+    pub kms_key_id: Option<String>,
+    /// Versioning is only ever enabled here, never suspended.
+    pub versioning: bool,
+    /// Adds a lifecycle rule aborting multipart uploads left incomplete for this many days.
+    pub abort_incomplete_upload_days: Option<i32>,
+    /// Enables S3 Transfer Acceleration; like versioning, never suspended here.
+    pub transfer_acceleration: bool,
+}
+
+/// ID of the lifecycle rule managed by this application; other rules on the bucket are kept.
+const ABORT_MULTIPART_RULE_ID: &str = "axum-meme-abort-incomplete-uploads";
+
 // --- Retry Configuration ---
 
 fn default_resource_backoff() -> ExponentialBackoff {
@@ -38,27 +146,61 @@ fn is_dynamodb_create_error_retryable(err: &DynamoSdkError_CreateTable<CreateTab
 }
 
 /// Attempts to create the DynamoDB table if it doesn't exist, applying retry logic.
-// Added table_name parameter
-async fn try_create_dynamodb_table(client: &DynamoDbClient, table_name: &str) -> Result<(), AppError> {
+/// `keys` lists the string-typed key attributes in schema order (hash key first, optional range key).
+/// `indexes` are created along with the table, billed as `capacity` says.
+async fn try_create_dynamodb_table(
+    client: &DynamoDbClient,
+    table_name: &str,
+    keys: &[(&str, KeyType)],
+    indexes: &[ListingIndex],
+    capacity: &TableCapacity,
+) -> Result<(), AppError> {
     let operation = || async {
-        let attr_def = AttributeDefinition::builder()
-            .attribute_name("meme_id")
-            .attribute_type(ScalarAttributeType::S)
-            .build()
-            .map_err(|e| backoff::Error::permanent(DynamoSdkError_CreateTable::construction_failure(e)))?;
-
-        let key_schema = KeySchemaElement::builder()
-            .attribute_name("meme_id")
-            .key_type(KeyType::Hash)
-            .build()
-            .map_err(|e| backoff::Error::permanent(DynamoSdkError_CreateTable::construction_failure(e)))?;
+        let capacity_error = |e| backoff::Error::permanent(DynamoSdkError_CreateTable::construction_failure(e));
+        let mut request = client.create_table().table_name(table_name); // Use parameter
+        request = match capacity {
+            TableCapacity::PayPerRequest => request.billing_mode(BillingMode::PayPerRequest),
+            TableCapacity::Provisioned { table, .. } => request
+                .billing_mode(BillingMode::Provisioned)
+                .provisioned_throughput(table.provisioned().map_err(capacity_error)?),
+        };
 
-        client
-            .create_table()
-            .table_name(table_name) // Use parameter
-            .attribute_definitions(attr_def)
-            .key_schema(key_schema)
-            .billing_mode(BillingMode::PayPerRequest)
+        for (attribute_name, key_type) in keys {
+            let attr_def = AttributeDefinition::builder()
+                .attribute_name(*attribute_name)
+                .attribute_type(ScalarAttributeType::S)
+                .build()
+                .map_err(|e| backoff::Error::permanent(DynamoSdkError_CreateTable::construction_failure(e)))?;
+
+            let key_schema = KeySchemaElement::builder()
+                .attribute_name(*attribute_name)
+                .key_type(key_type.clone())
+                .build()
+                .map_err(|e| backoff::Error::permanent(DynamoSdkError_CreateTable::construction_failure(e)))?;
+
+            request = request.attribute_definitions(attr_def).key_schema(key_schema);
+        }
+        let index_error = |e| backoff::Error::permanent(DynamoSdkError_CreateTable::construction_failure(e));
+        if !indexes.is_empty() {
+            request = request.attribute_definitions(attribute_definition(LISTING_ATTRIBUTE, false).map_err(index_error)?);
+        }
+        for index in indexes {
+            let attr_def = attribute_definition(index.sort_key, index.numeric).map_err(index_error)?;
+            let index_throughput = match capacity {
+                TableCapacity::Provisioned { indexes, .. } => Some(indexes.provisioned().map_err(index_error)?),
+                TableCapacity::PayPerRequest => None,
+            };
+            let global_index = GlobalSecondaryIndex::builder()
+                .index_name(index.name)
+                .set_key_schema(Some(index_key_schema(index).map_err(index_error)?))
+                .projection(Projection::builder().projection_type(ProjectionType::All).build())
+                .set_provisioned_throughput(index_throughput)
+                .build()
+                .map_err(index_error)?;
+            request = request.attribute_definitions(attr_def).global_secondary_indexes(global_index);
+        }
+
+        request
             .send()
             .await
             .map_err(|sdk_error| {
@@ -104,6 +246,341 @@ fn handle_final_dynamodb_error(sdk_error: DynamoSdkError_CreateTable<CreateTable
 }
 
 
+/// Waits for a newly created table to become ACTIVE; table settings cannot change before then.
+async fn wait_until_active(client: &DynamoDbClient, table_name: &str) -> Result<(), AppError> {
+    use aws_sdk_dynamodb::client::Waiters;
+
+    client
+        .wait_until_table_exists()
+        .table_name(table_name)
+        .wait(Duration::from_secs(60))
+        .await
+        .map_err(|e| AppError::InitError(format!("DynamoDB table '{}' did not become active: {}", table_name, e)))?;
+    Ok(())
+}
+
+/// Enables DynamoDB TTL on `attribute` unless TTL is already on (or being turned on) for it.
+/// With `enable` false the setting is only checked and a mismatch is logged.
+pub(crate) async fn ensure_ttl(client: &DynamoDbClient, table_name: &str, attribute: &str, enable: bool) -> Result<(), AppError> {
+    let description = client
+        .describe_time_to_live()
+        .table_name(table_name)
+        .send()
+        .await
+        .map_err(|e| {
+            AppError::InitError(format!(
+                "Failed to read TTL settings of DynamoDB table '{}' (requires dynamodb:DescribeTimeToLive): {}",
+                table_name,
+                aws_sdk_dynamodb::error::DisplayErrorContext(&e)
+            ))
+        })?
+        .time_to_live_description;
+    let status = description.as_ref().and_then(|d| d.time_to_live_status());
+    let current_attribute = description.as_ref().and_then(|d| d.attribute_name());
+
+    match status {
+        Some(TimeToLiveStatus::Enabled | TimeToLiveStatus::Enabling) if current_attribute == Some(attribute) => {
+            info!(%table_name, attribute, "DynamoDB TTL already enabled.");
+            return Ok(());
+        }
+        Some(TimeToLiveStatus::Enabled | TimeToLiveStatus::Enabling) => {
+            // A table has at most one TTL attribute; don't take it over from whoever set it
+            warn!(%table_name, current = ?current_attribute, expected = attribute,
+                "DynamoDB TTL uses a different attribute; expired memes are only removed by the cleanup job");
+            return Ok(());
+        }
+        _ if !enable => {
+            warn!(%table_name, attribute, "DynamoDB TTL is not enabled; expired memes are only removed by the cleanup job");
+            return Ok(());
+        }
+        _ => {}
+    }
+
+    let specification = TimeToLiveSpecification::builder()
+        .enabled(true)
+        .attribute_name(attribute)
+        .build()
+        .map_err(|e| AppError::InitError(format!("Invalid TTL specification: {}", e)))?;
+    client
+        .update_time_to_live()
+        .table_name(table_name)
+        .time_to_live_specification(specification)
+        .send()
+        .await
+        .map_err(|e| {
+            AppError::InitError(format!(
+                "Failed to enable TTL on DynamoDB table '{}' (requires dynamodb:UpdateTimeToLive): {}",
+                table_name,
+                aws_sdk_dynamodb::error::DisplayErrorContext(&e)
+            ))
+        })?;
+    info!(%table_name, attribute, "DynamoDB TTL enabled.");
+    Ok(())
+}
+
+/// Enables a NEW_AND_OLD_IMAGES stream on the meme table for the change stream consumer.
+/// With `enable` false the stream is only checked. A stream with another view type is an
+/// error: DynamoDB cannot change it in place and it may have other consumers.
+async fn ensure_stream(client: &DynamoDbClient, table_name: &str, enable: bool) -> Result<(), AppError> {
+    let table = client
+        .describe_table()
+        .table_name(table_name)
+        .send()
+        .await
+        .map_err(|e| {
+            AppError::InitError(format!(
+                "Failed to describe DynamoDB table '{}': {}",
+                table_name,
+                aws_sdk_dynamodb::error::DisplayErrorContext(&e)
+            ))
+        })?
+        .table;
+    let specification = table.as_ref().and_then(|t| t.stream_specification());
+
+    match specification {
+        Some(spec) if spec.stream_enabled() => {
+            if spec.stream_view_type() != Some(&StreamViewType::NewAndOldImages) {
+                return Err(AppError::InitError(format!(
+                    "DynamoDB table '{}' streams {:?}, but the change stream consumer needs NEW_AND_OLD_IMAGES; \
+                     disable the stream and enable it again with that view type",
+                    table_name,
+                    spec.stream_view_type()
+                )));
+            }
+            info!(%table_name, "DynamoDB stream already enabled.");
+            return Ok(());
+        }
+        _ if !enable => {
+            return Err(AppError::InitError(format!(
+                "DynamoDB table '{}' has no stream but APP_STREAM_CONSUMER is enabled; enable a NEW_AND_OLD_IMAGES \
+                 stream or use APP_RESOURCE_INIT=create",
+                table_name
+            )));
+        }
+        _ => {}
+    }
+
+    let specification = StreamSpecification::builder()
+        .stream_enabled(true)
+        .stream_view_type(StreamViewType::NewAndOldImages)
+        .build()
+        .map_err(|e| AppError::InitError(format!("Invalid stream specification: {}", e)))?;
+    client
+        .update_table()
+        .table_name(table_name)
+        .stream_specification(specification)
+        .send()
+        .await
+        .map_err(|e| {
+            AppError::InitError(format!(
+                "Failed to enable the stream on DynamoDB table '{}' (requires dynamodb:UpdateTable): {}",
+                table_name,
+                aws_sdk_dynamodb::error::DisplayErrorContext(&e)
+            ))
+        })?;
+    wait_until_active(client, table_name).await?;
+    info!(%table_name, "DynamoDB stream enabled.");
+    Ok(())
+}
+
+// --- Listing Indexes ---
+
+/// How long startup waits for a listing index added to an existing table to backfill.
+const INDEX_BACKFILL_TIMEOUT: Duration = Duration::from_secs(15 * 60);
+
+fn attribute_definition(name: &str, numeric: bool) -> Result<AttributeDefinition, aws_sdk_dynamodb::error::BuildError> {
+    let attribute_type = if numeric { ScalarAttributeType::N } else { ScalarAttributeType::S };
+    AttributeDefinition::builder().attribute_name(name).attribute_type(attribute_type).build()
+}
+
+/// Key schema of a listing index: the shared listing partition, then the index's sort key.
+fn index_key_schema(index: &ListingIndex) -> Result<Vec<KeySchemaElement>, aws_sdk_dynamodb::error::BuildError> {
+    Ok(vec![
+        KeySchemaElement::builder().attribute_name(LISTING_ATTRIBUTE).key_type(KeyType::Hash).build()?,
+        KeySchemaElement::builder().attribute_name(index.sort_key).key_type(KeyType::Range).build()?,
+    ])
+}
+
+async fn describe_table(client: &DynamoDbClient, table_name: &str) -> Result<Option<TableDescription>, AppError> {
+    let output = client
+        .describe_table()
+        .table_name(table_name)
+        .send()
+        .await
+        .map_err(|e| {
+            AppError::InitError(format!(
+                "Failed to describe DynamoDB table '{}': {}",
+                table_name,
+                aws_sdk_dynamodb::error::DisplayErrorContext(&e)
+            ))
+        })?;
+    Ok(output.table)
+}
+
+/// Status of a global secondary index of the table, or `None` if it does not exist.
+async fn index_status(client: &DynamoDbClient, table_name: &str, index_name: &str) -> Result<Option<IndexStatus>, AppError> {
+    let table = describe_table(client, table_name).await?;
+    let index = table
+        .as_ref()
+        .and_then(|t| t.global_secondary_indexes().iter().find(|i| i.index_name() == Some(index_name)));
+    // Emulators may leave the status out; an index that is listed can be queried
+    Ok(index.map(|i| i.index_status().cloned().unwrap_or(IndexStatus::Active)))
+}
+
+/// Adds the listing indexes missing from the meme table, billed like the table. Tables
+/// created before sorting existed get their indexes this way (see [`crate::migrations`]).
+pub(crate) async fn create_listing_indexes(client: &DynamoDbClient, table_name: &str, capacity: &TableCapacity) -> Result<(), AppError> {
+    let table = describe_table(client, table_name).await?;
+    let index_throughput = table.as_ref().and_then(|table| new_index_throughput(table, capacity));
+    ensure_listing_indexes(client, table_name, true, index_throughput).await
+}
+
+/// Adds the listing indexes missing from the meme table, one at a time as DynamoDB requires,
+/// and waits for each to backfill. New indexes of a provisioned table get `index_throughput`.
+/// With `create` false they are only checked: a missing index is an error, since sorted
+/// listings would fail.
+async fn ensure_listing_indexes(
+    client: &DynamoDbClient,
+    table_name: &str,
+    create: bool,
+    index_throughput: Option<Throughput>,
+) -> Result<(), AppError> {
+    for index in LISTING_INDEXES {
+        match index_status(client, table_name, index.name).await? {
+            Some(IndexStatus::Active) => continue,
+            Some(status) if !create => {
+                warn!(%table_name, index = index.name, ?status, "DynamoDB listing index is not active yet; sorted listings fail until it is");
+                continue;
+            }
+            None if !create => {
+                return Err(AppError::InitError(format!(
+                    "DynamoDB table '{}' has no index '{}' (partition key '{}', sort key '{}', projection ALL) needed for \
+                     sorted listings; add it or use APP_RESOURCE_INIT=create",
+                    table_name, index.name, LISTING_ATTRIBUTE, index.sort_key
+                )));
+            }
+            Some(_) => {} // Already being created, e.g. by another instance
+            None => add_listing_index(client, table_name, index, index_throughput).await?,
+        }
+        wait_until_index_active(client, table_name, index.name).await?;
+    }
+    Ok(())
+}
+
+async fn add_listing_index(
+    client: &DynamoDbClient,
+    table_name: &str,
+    index: &ListingIndex,
+    throughput: Option<Throughput>,
+) -> Result<(), AppError> {
+    let build_error = |e: aws_sdk_dynamodb::error::BuildError| AppError::InitError(format!("Invalid index definition: {}", e));
+    let action = CreateGlobalSecondaryIndexAction::builder()
+        .index_name(index.name)
+        .set_key_schema(Some(index_key_schema(index).map_err(build_error)?))
+        .projection(Projection::builder().projection_type(ProjectionType::All).build())
+        .set_provisioned_throughput(throughput.map(|throughput| throughput.provisioned()).transpose().map_err(build_error)?)
+        .build()
+        .map_err(build_error)?;
+    client
+        .update_table()
+        .table_name(table_name)
+        .attribute_definitions(attribute_definition(LISTING_ATTRIBUTE, false).map_err(build_error)?)
+        .attribute_definitions(attribute_definition(index.sort_key, index.numeric).map_err(build_error)?)
+        .global_secondary_index_updates(GlobalSecondaryIndexUpdate::builder().create(action).build())
+        .send()
+        .await
+        .map_err(|e| {
+            AppError::InitError(format!(
+                "Failed to add index '{}' to DynamoDB table '{}' (requires dynamodb:UpdateTable): {}",
+                index.name,
+                table_name,
+                aws_sdk_dynamodb::error::DisplayErrorContext(&e)
+            ))
+        })?;
+    info!(%table_name, index = index.name, "DynamoDB listing index added; waiting for it to backfill.");
+    Ok(())
+}
+
+async fn wait_until_index_active(client: &DynamoDbClient, table_name: &str, index_name: &str) -> Result<(), AppError> {
+    let started = std::time::Instant::now();
+    loop {
+        let status = index_status(client, table_name, index_name).await?;
+        if status == Some(IndexStatus::Active) {
+            info!(%table_name, index = index_name, "DynamoDB listing index active.");
+            return Ok(());
+        }
+        if started.elapsed() > INDEX_BACKFILL_TIMEOUT {
+            return Err(AppError::InitError(format!(
+                "DynamoDB index '{}' of table '{}' is still {:?} after {:?}; start again once it is ACTIVE",
+                index_name, table_name, status, INDEX_BACKFILL_TIMEOUT
+            )));
+        }
+        tokio::time::sleep(Duration::from_secs(5)).await;
+    }
+}
+
+// --- Capacity ---
+
+fn throughput_of(provisioned: Option<&ProvisionedThroughputDescription>) -> Throughput {
+    Throughput {
+        read: provisioned.and_then(|p| p.read_capacity_units()).unwrap_or_default(),
+        write: provisioned.and_then(|p| p.write_capacity_units()).unwrap_or_default(),
+    }
+}
+
+fn billing_mode_of(table: &TableDescription) -> BillingMode {
+    // Tables created before on-demand billing existed have no billing summary
+    table
+        .billing_mode_summary()
+        .and_then(|summary| summary.billing_mode())
+        .cloned()
+        .unwrap_or(BillingMode::Provisioned)
+}
+
+/// The throughput new indexes of `table` need: `None` when it is billed per request. New
+/// indexes must be billed like their table, whatever `capacity` says.
+fn new_index_throughput(table: &TableDescription, capacity: &TableCapacity) -> Option<Throughput> {
+    match (billing_mode_of(table), capacity) {
+        (BillingMode::Provisioned, TableCapacity::Provisioned { indexes, .. }) => Some(*indexes),
+        (BillingMode::Provisioned, TableCapacity::PayPerRequest) => Some(throughput_of(table.provisioned_throughput())),
+        _ => None,
+    }
+}
+
+/// Compares an existing table's billing mode and throughput, and those of its indexes, with
+/// `capacity`. Differences are logged rather than changed: the billing mode can only be
+/// switched once a day, and auto scaling moves provisioned throughput on its own.
+async fn check_capacity(client: &DynamoDbClient, table_name: &str, capacity: &TableCapacity) -> Result<(), AppError> {
+    let Some(table) = describe_table(client, table_name).await? else {
+        return Ok(());
+    };
+    let billing_mode = billing_mode_of(&table);
+    match (capacity, &billing_mode) {
+        (TableCapacity::PayPerRequest, BillingMode::PayPerRequest) => {}
+        (TableCapacity::Provisioned { table: expected, indexes }, BillingMode::Provisioned) => {
+            let actual = throughput_of(table.provisioned_throughput());
+            if actual != *expected {
+                warn!(%table_name, ?expected, ?actual, "DynamoDB table throughput differs from the configuration; auto scaling may have changed it");
+            }
+            for index in table.global_secondary_indexes() {
+                let actual = throughput_of(index.provisioned_throughput());
+                if actual != *indexes {
+                    warn!(%table_name, index = index.index_name(), expected = ?indexes, ?actual, "DynamoDB index throughput differs from the configuration; auto scaling may have changed it");
+                }
+            }
+        }
+        (_, actual) => {
+            warn!(
+                %table_name,
+                expected = ?capacity,
+                actual = actual.as_str(),
+                "DynamoDB table billing mode differs from APP_DYNAMODB_BILLING_MODE; change it on the table (at most once a day) to match"
+            );
+        }
+    }
+    Ok(())
+}
+
 // --- S3 Initialization ---
 
 // No changes needed in S3 retry logic itself for this refactor
@@ -183,24 +660,445 @@ async fn try_create_s3_bucket(
      }
  }
 
+// --- S3 Bucket Hardening ---
+
+/// Runs one idempotent bucket configuration call with the startup retry policy. `permission`
+/// names the IAM action the call needs, so a failure says what to grant (or which setting to turn off).
+async fn apply_bucket_setting<T, E, F, Fut>(
+    bucket_name: &str,
+    setting: &str,
+    permission: &str,
+    call: F,
+) -> Result<T, AppError>
+where
+    F: Fn() -> Fut,
+    Fut: Future<Output = Result<T, S3SdkError_CreateBucket<E, aws_sdk_s3::config::http::HttpResponse>>>,
+    E: std::error::Error + Send + Sync + 'static,
+{
+    let operation = || async {
+        call().await.map_err(|sdk_error| {
+            if is_transient(&sdk_error) {
+                warn!(%bucket_name, setting, error = %sdk_error, "Transient error configuring S3 bucket, retrying...");
+                backoff::Error::transient(sdk_error)
+            } else {
+                backoff::Error::permanent(sdk_error)
+            }
+        })
+    };
+
+    let output = retry(default_resource_backoff(), operation).await.map_err(|sdk_error| {
+        let context = format!(
+            "Failed to apply {} to S3 bucket '{}' (requires {}; grant it or disable the setting)",
+            setting, bucket_name, permission
+        );
+        error!(error = %aws_sdk_s3::error::DisplayErrorContext(&sdk_error), %context);
+        AppError::InitError(format!("{}: {}", context, aws_sdk_s3::error::DisplayErrorContext(&sdk_error)))
+    })?;
+    info!(%bucket_name, setting, "S3 bucket setting applied.");
+    Ok(output)
+}
+
+fn build_error(e: aws_sdk_s3::error::BuildError) -> AppError {
+    AppError::InitError(format!("Invalid S3 bucket configuration: {}", e))
+}
+
+/// Applies the configured public access block, default encryption, versioning and lifecycle
+/// rule. Every call replaces the previous value, so this is safe to run on each startup.
+async fn harden_s3_bucket(client: &S3Client, bucket_name: &str, settings: &BucketSettings) -> Result<(), AppError> {
+    if settings.block_public_access {
+        let block_all = PublicAccessBlockConfiguration::builder()
+            .block_public_acls(true)
+            .ignore_public_acls(true)
+            .block_public_policy(true)
+            .restrict_public_buckets(true)
+            .build();
+        apply_bucket_setting(bucket_name, "public access block", "s3:PutBucketPublicAccessBlock", || {
+            client
+                .put_public_access_block()
+                .bucket(bucket_name)
+                .public_access_block_configuration(block_all.clone())
+                .send()
+        })
+        .await?;
+    }
+
+    let default_encryption = match settings.encryption {
+        BucketEncryption::Unchanged => None,
+        BucketEncryption::S3Managed => Some(ServerSideEncryptionByDefault::builder().sse_algorithm(ServerSideEncryption::Aes256)),
+        BucketEncryption::Kms => Some(
+            ServerSideEncryptionByDefault::builder()
+                .sse_algorithm(ServerSideEncryption::AwsKms)
+                .set_kms_master_key_id(settings.kms_key_id.clone()),
+        ),
+    };
+    if let Some(default_encryption) = default_encryption {
+        let rule = ServerSideEncryptionRule::builder()
+            .apply_server_side_encryption_by_default(default_encryption.build().map_err(build_error)?)
+            .bucket_key_enabled(settings.encryption == BucketEncryption::Kms) // Fewer KMS requests
+            .build();
+        let encryption = ServerSideEncryptionConfiguration::builder().rules(rule).build().map_err(build_error)?;
+        apply_bucket_setting(bucket_name, "default encryption", "s3:PutEncryptionConfiguration", || {
+            client
+                .put_bucket_encryption()
+                .bucket(bucket_name)
+                .server_side_encryption_configuration(encryption.clone())
+                .send()
+        })
+        .await?;
+    }
+
+    if settings.versioning {
+        let versioning = VersioningConfiguration::builder().status(BucketVersioningStatus::Enabled).build();
+        apply_bucket_setting(bucket_name, "versioning", "s3:PutBucketVersioning", || {
+            client
+                .put_bucket_versioning()
+                .bucket(bucket_name)
+                .versioning_configuration(versioning.clone())
+                .send()
+        })
+        .await?;
+    }
+
+    if settings.transfer_acceleration {
+        let accelerate = AccelerateConfiguration::builder().status(BucketAccelerateStatus::Enabled).build();
+        apply_bucket_setting(bucket_name, "transfer acceleration", "s3:PutAccelerateConfiguration", || {
+            client
+                .put_bucket_accelerate_configuration()
+                .bucket(bucket_name)
+                .accelerate_configuration(accelerate.clone())
+                .send()
+        })
+        .await?;
+    }
+
+    if let Some(days) = settings.abort_incomplete_upload_days {
+        // The lifecycle configuration is replaced as a whole, so keep any rules managed elsewhere
+        let existing = client.get_bucket_lifecycle_configuration().bucket(bucket_name).send().await;
+        let mut rules = match existing {
+            Ok(output) => output.rules.unwrap_or_default(),
+            Err(e) if e.as_service_error().map(|se| se.meta().code()) == Some(Some("NoSuchLifecycleConfiguration")) => Vec::new(),
+            Err(e) => {
+                return Err(AppError::InitError(format!(
+                    "Failed to read lifecycle rules of S3 bucket '{}' (requires s3:GetLifecycleConfiguration): {}",
+                    bucket_name,
+                    aws_sdk_s3::error::DisplayErrorContext(&e)
+                )));
+            }
+        };
+        rules.retain(|rule| rule.id() != Some(ABORT_MULTIPART_RULE_ID));
+        rules.push(
+            LifecycleRule::builder()
+                .id(ABORT_MULTIPART_RULE_ID)
+                .status(ExpirationStatus::Enabled)
+                .filter(LifecycleRuleFilter::builder().prefix("").build())
+                .abort_incomplete_multipart_upload(
+                    AbortIncompleteMultipartUpload::builder().days_after_initiation(days).build(),
+                )
+                .build()
+                .map_err(build_error)?,
+        );
+        let lifecycle = BucketLifecycleConfiguration::builder().set_rules(Some(rules)).build().map_err(build_error)?;
+        apply_bucket_setting(bucket_name, "incomplete upload lifecycle rule", "s3:PutLifecycleConfiguration", || {
+            client
+                .put_bucket_lifecycle_configuration()
+                .bucket(bucket_name)
+                .lifecycle_configuration(lifecycle.clone())
+                .send()
+        })
+        .await?;
+    }
+
+    Ok(())
+}
+
 // --- Main Initialization Function ---
 
-/// Initializes required AWS resources (DynamoDB table, S3 bucket) during application startup.
+/// Initializes required AWS resources (DynamoDB tables, S3 bucket) during application startup,
+/// then applies the configured bucket hardening.
 /// Applies retry logic with exponential backoff for transient connection or service errors.
-// Added table_name parameter
+#[allow(clippy::too_many_arguments)] // Names and settings come straight from Config
 pub async fn init_resources(
     db_client: &DynamoDbClient,
     s3_client: &S3Client,
-    table_name: &str, // Accept table_name from config
-    bucket_name: &str,
+    table_name: Option<&str>, // None when memes are stored outside DynamoDB
+    meta_table_name: Option<&str>, // None when the blocklist and tenant overrides are stored outside DynamoDB
+    bucket_name: Option<&str>, // None when images are stored outside S3
     region_str: &str,
+    bucket_settings: &BucketSettings,
+    change_stream: bool,
+    capacity: &TableCapacity,
+    meta_capacity: &TableCapacity,
 ) -> Result<(), AppError> {
     info!("Initializing AWS resources...");
 
-    // Pass table_name from config
-    try_create_dynamodb_table(db_client, table_name).await?;
-    try_create_s3_bucket(s3_client, bucket_name, region_str).await?;
+    if let Some(table_name) = table_name {
+        try_create_dynamodb_table(db_client, table_name, &[("meme_id", KeyType::Hash)], LISTING_INDEXES, capacity).await?;
+        wait_until_active(db_client, table_name).await?;
+        check_capacity(db_client, table_name, capacity).await?;
+        // Listing indexes and TTL of older tables are added by migrations
+        if change_stream {
+            ensure_stream(db_client, table_name, true).await?;
+        }
+    }
+    // Auxiliary table for non-meme records (blocklist terms, etc.), keyed by pk/sk
+    if let Some(meta_table_name) = meta_table_name {
+        try_create_dynamodb_table(
+            db_client,
+            meta_table_name,
+            &[("pk", KeyType::Hash), ("sk", KeyType::Range)],
+            &[],
+            meta_capacity,
+        )
+        .await?;
+        wait_until_active(db_client, meta_table_name).await?;
+        check_capacity(db_client, meta_table_name, meta_capacity).await?;
+    }
+    if let Some(bucket_name) = bucket_name {
+        try_create_s3_bucket(s3_client, bucket_name, region_str).await?;
+        harden_s3_bucket(s3_client, bucket_name, bucket_settings).await?;
+    }
 
     info!("AWS resource initialization complete.");
     Ok(())
 }
+
+// --- Verification ---
+
+/// Connection problems are worth retrying while e.g. LocalStack is still starting.
+/// (Every SDK crate re-exports the same `SdkError` type.)
+fn is_transient<E, R>(err: &aws_sdk_dynamodb::error::SdkError<E, R>) -> bool {
+    use aws_sdk_dynamodb::error::SdkError;
+    matches!(err, SdkError::DispatchFailure(_) | SdkError::TimeoutError(_))
+}
+
+/// Checks that a DynamoDB table exists, is usable, and has the expected string key schema.
+async fn verify_dynamodb_table(
+    client: &DynamoDbClient,
+    table_name: &str,
+    keys: &[(&str, KeyType)],
+) -> Result<(), AppError> {
+    let operation = || async {
+        client.describe_table().table_name(table_name).send().await.map_err(|sdk_error| {
+            if is_transient(&sdk_error) {
+                warn!(%table_name, error = %sdk_error, "Transient error describing DynamoDB table, retrying...");
+                backoff::Error::transient(sdk_error)
+            } else {
+                backoff::Error::permanent(sdk_error)
+            }
+        })
+    };
+
+    let output = retry(default_resource_backoff(), operation).await.map_err(|sdk_error| {
+        let message = match sdk_error.as_service_error() {
+            Some(se) if se.is_resource_not_found_exception() => format!(
+                "DynamoDB table '{}' does not exist. Provision it (or run `init-resources` with CreateTable permission), or set APP_RESOURCE_INIT=create",
+                table_name
+            ),
+            Some(se) if se.code() == Some("AccessDeniedException") => format!(
+                "Access denied describing DynamoDB table '{}'. Grant dynamodb:DescribeTable on it, or set APP_RESOURCE_INIT=skip to trust external provisioning",
+                table_name
+            ),
+            _ => format!("Failed to describe DynamoDB table '{}': {}", table_name, sdk_error),
+        };
+        AppError::InitError(message)
+    })?;
+
+    let table = output
+        .table()
+        .ok_or_else(|| AppError::InitError(format!("DescribeTable returned no details for '{}'", table_name)))?;
+    match table.table_status() {
+        Some(TableStatus::Active) | Some(TableStatus::Updating) => {}
+        status => {
+            return Err(AppError::InitError(format!(
+                "DynamoDB table '{}' is not ready (status {:?}); wait until it is ACTIVE",
+                table_name, status
+            )));
+        }
+    }
+
+    let actual: Vec<(&str, &KeyType)> = table
+        .key_schema()
+        .iter()
+        .map(|key| (key.attribute_name(), key.key_type()))
+        .collect();
+    let expected: Vec<(&str, &KeyType)> = keys.iter().map(|(name, key_type)| (*name, key_type)).collect();
+    if actual != expected {
+        return Err(AppError::InitError(format!(
+            "DynamoDB table '{}' has key schema {:?}, expected {:?}; check APP_DYNAMODB_TABLE_NAME / APP_DYNAMODB_META_TABLE_NAME point at this application's tables",
+            table_name, actual, expected
+        )));
+    }
+
+    info!(%table_name, "DynamoDB table verified.");
+    Ok(())
+}
+
+/// Checks that the S3 bucket exists and is reachable with the current credentials.
+async fn verify_s3_bucket(client: &S3Client, bucket_name: &str, region_str: &str) -> Result<(), AppError> {
+    let operation = || async {
+        client.head_bucket().bucket(bucket_name).send().await.map_err(|sdk_error| {
+            if is_transient(&sdk_error) {
+                warn!(%bucket_name, error = %sdk_error, "Transient error checking S3 bucket, retrying...");
+                backoff::Error::transient(sdk_error)
+            } else {
+                backoff::Error::permanent(sdk_error)
+            }
+        })
+    };
+
+    retry(default_resource_backoff(), operation).await.map_err(|sdk_error| {
+        // HeadBucket responses have no body, so the HTTP status is all there is to go on
+        let message = match sdk_error.raw_response().map(|response| response.status().as_u16()) {
+            Some(404) => format!(
+                "S3 bucket '{}' does not exist. Provision it (or run `init-resources` with CreateBucket permission), or set APP_RESOURCE_INIT=create",
+                bucket_name
+            ),
+            Some(403) => format!(
+                "Access denied to S3 bucket '{}'. Grant s3:ListBucket on it (required by HeadBucket), check it belongs to this account, or set APP_RESOURCE_INIT=skip",
+                bucket_name
+            ),
+            Some(301) => format!(
+                "S3 bucket '{}' is not in region '{}'; set AWS_REGION to the bucket's region",
+                bucket_name, region_str
+            ),
+            _ => format!("Failed to check S3 bucket '{}': {}", bucket_name, sdk_error),
+        };
+        AppError::InitError(message)
+    })?;
+
+    info!(%bucket_name, "S3 bucket verified.");
+    Ok(())
+}
+
+/// Checks that Transfer Acceleration is enabled on the bucket; accelerated requests to a
+/// bucket without it are rejected.
+async fn verify_transfer_acceleration(client: &S3Client, bucket_name: &str) -> Result<(), AppError> {
+    let output = client
+        .get_bucket_accelerate_configuration()
+        .bucket(bucket_name)
+        .send()
+        .await
+        .map_err(|e| {
+            AppError::InitError(format!(
+                "Failed to read the transfer acceleration of S3 bucket '{}' (requires s3:GetAccelerateConfiguration): {}",
+                bucket_name,
+                aws_sdk_s3::error::DisplayErrorContext(&e)
+            ))
+        })?;
+    if output.status() != Some(&BucketAccelerateStatus::Enabled) {
+        return Err(AppError::InitError(format!(
+            "S3 bucket '{}' does not have Transfer Acceleration enabled; enable it, set APP_RESOURCE_INIT=create, or unset APP_S3_ACCELERATE",
+            bucket_name
+        )));
+    }
+    info!(%bucket_name, "S3 transfer acceleration verified.");
+    Ok(())
+}
+
+/// Key of the object written to check the upload KMS key; deleted right after.
+const KMS_PROBE_KEY: &str = ".kms-check";
+
+/// Checks that uploads can be encrypted with `kms_key_id` by writing (and deleting) a small
+/// object with it under `key_prefix`. Neither S3 nor KMS offers a dry run, and a DescribeKey
+/// would miss the `kms:GenerateDataKey` grant that every upload actually needs.
+pub async fn verify_upload_kms_key(
+    client: &S3Client,
+    bucket_name: &str,
+    key_prefix: &str,
+    kms_key_id: &str,
+) -> Result<(), AppError> {
+    let probe_key = format!("{}{}", key_prefix, KMS_PROBE_KEY);
+    let operation = || async {
+        client
+            .put_object()
+            .bucket(bucket_name)
+            .key(&probe_key)
+            .body(aws_sdk_s3::primitives::ByteStream::from_static(b"ok"))
+            .server_side_encryption(ServerSideEncryption::AwsKms)
+            .ssekms_key_id(kms_key_id)
+            .send()
+            .await
+            .map_err(|sdk_error| {
+                if is_transient(&sdk_error) {
+                    warn!(%bucket_name, error = %sdk_error, "Transient error checking upload KMS key, retrying...");
+                    backoff::Error::transient(sdk_error)
+                } else {
+                    backoff::Error::permanent(sdk_error)
+                }
+            })
+    };
+
+    retry(default_resource_backoff(), operation).await.map_err(|sdk_error| {
+        let detail = aws_sdk_s3::error::DisplayErrorContext(&sdk_error);
+        let message = match sdk_error.code() {
+            Some("KMS.NotFoundException") => format!(
+                "KMS key '{}' in APP_S3_UPLOAD_KMS_KEY_ID does not exist in this account and region: {}",
+                kms_key_id, detail
+            ),
+            Some("KMS.DisabledException") | Some("KMS.KMSInvalidStateException") => format!(
+                "KMS key '{}' in APP_S3_UPLOAD_KMS_KEY_ID is disabled or pending deletion: {}",
+                kms_key_id, detail
+            ),
+            Some("AccessDenied") | Some("KMS.AccessDeniedException") => format!(
+                "Access denied encrypting an object in S3 bucket '{}' with KMS key '{}'. Grant kms:GenerateDataKey (and kms:Decrypt for reads) on the key, and s3:PutObject/s3:DeleteObject on the bucket: {}",
+                bucket_name, kms_key_id, detail
+            ),
+            _ => format!("Failed to check KMS key '{}' for uploads to S3 bucket '{}': {}", kms_key_id, bucket_name, detail),
+        };
+        AppError::InitError(message)
+    })?;
+
+    // Leaving the probe behind is harmless, so a failed delete only warrants a warning
+    if let Err(e) = client.delete_object().bucket(bucket_name).key(&probe_key).send().await {
+        warn!(%bucket_name, key = %probe_key, error = %e, "Failed to delete KMS probe object");
+    }
+    info!(%bucket_name, %kms_key_id, "Upload KMS key verified.");
+    Ok(())
+}
+
+/// Verifies that the resources created by [`init_resources`] exist, without creating anything.
+/// Needs only read permissions (DescribeTable, ListBucket), for deployments where tables and
+/// buckets are provisioned externally. Capacity mismatches are logged, not fatal.
+#[allow(clippy::too_many_arguments)] // Names and settings come straight from Config
+pub async fn verify_resources(
+    db_client: &DynamoDbClient,
+    s3_client: &S3Client,
+    table_name: Option<&str>, // None when memes are stored outside DynamoDB
+    meta_table_name: Option<&str>, // None when the blocklist and tenant overrides are stored outside DynamoDB
+    bucket_name: Option<&str>, // None when images are stored outside S3
+    region_str: &str,
+    transfer_acceleration: bool,
+    change_stream: bool,
+    capacity: &TableCapacity,
+    meta_capacity: &TableCapacity,
+) -> Result<(), AppError> {
+    info!("Verifying AWS resources...");
+
+    if let Some(table_name) = table_name {
+        verify_dynamodb_table(db_client, table_name, &[("meme_id", KeyType::Hash)]).await?;
+        check_capacity(db_client, table_name, capacity).await?;
+        ensure_listing_indexes(db_client, table_name, false, None).await?;
+        ensure_ttl(db_client, table_name, MEME_TTL_ATTRIBUTE, false).await?;
+        if change_stream {
+            ensure_stream(db_client, table_name, false).await?;
+        }
+    }
+    if let Some(meta_table_name) = meta_table_name {
+        verify_dynamodb_table(
+            db_client,
+            meta_table_name,
+            &[("pk", KeyType::Hash), ("sk", KeyType::Range)],
+        )
+        .await?;
+        check_capacity(db_client, meta_table_name, meta_capacity).await?;
+    }
+    if let Some(bucket_name) = bucket_name {
+        verify_s3_bucket(s3_client, bucket_name, region_str).await?;
+        if transfer_acceleration {
+            verify_transfer_acceleration(s3_client, bucket_name).await?;
+        }
+    }
+
+    info!("AWS resource verification complete.");
+    Ok(())
+}
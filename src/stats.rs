@@ -0,0 +1,109 @@
+use crate::{
+    domain::{FileStorage, MemeRepository},
+    errors::AppError,
+    AppState,
+};
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::Serialize;
+use std::{
+    collections::{BTreeMap, HashSet},
+    sync::Arc,
+    time::Duration,
+};
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+
+/// Aggregate numbers served by `GET /stats`. Counts cover the memes in the public listing;
+/// storage covers the images of every live meme.
+#[derive(Serialize, Debug, Clone)]
+pub struct MemeStats {
+    /// Public memes that have not expired.
+    pub total_memes: u64,
+    /// Public memes per tag.
+    pub tags: BTreeMap<String, u64>,
+    /// Public memes uploaded on each UTC day. Memes stored before upload times were recorded
+    /// are not counted.
+    pub uploads_per_day: BTreeMap<NaiveDate, u64>,
+    /// Stored images of live memes, private ones included.
+    pub image_count: u64,
+    /// Total size of those images.
+    pub stored_bytes: u64,
+    pub computed_at: DateTime<Utc>,
+}
+
+/// Computes the statistics from a full table scan and bucket listing. Too slow to run per
+/// request, which is why the result is cached in [`AppState::stats`].
+pub async fn compute_stats(repo: &dyn MemeRepository, storage: &dyn FileStorage) -> Result<MemeStats, AppError> {
+    let memes = repo.list_all().await?;
+    let objects = storage.list().await?;
+
+    let now = Utc::now();
+    let mut stats = MemeStats {
+        total_memes: 0,
+        tags: BTreeMap::new(),
+        uploads_per_day: BTreeMap::new(),
+        image_count: 0,
+        stored_bytes: 0,
+        computed_at: now,
+    };
+    for meme in memes.iter().filter(|meme| meme.is_listed(now)) {
+        stats.total_memes += 1;
+        for tag in &meme.tags {
+            *stats.tags.entry(tag.clone()).or_default() += 1;
+        }
+        if let Some(created_at) = meme.created_at {
+            *stats.uploads_per_day.entry(created_at.date_naive()).or_default() += 1;
+        }
+    }
+    // The bucket also holds backups and orphans, so only objects that belong to a meme count
+    let image_keys: HashSet<&str> = memes.iter().map(|meme| meme.image_key.as_str()).collect();
+    for object in objects.iter().filter(|object| image_keys.contains(object.key.as_str())) {
+        stats.image_count += 1;
+        stats.stored_bytes += object.size;
+    }
+    Ok(stats)
+}
+
+/// Recomputes the statistics and replaces the cached copy.
+pub async fn refresh_stats(state: &AppState) -> Result<Arc<MemeStats>, AppError> {
+    let stats = Arc::new(compute_stats(state.meme_repo.as_ref(), state.file_storage.as_ref()).await?);
+    *state.stats.write().expect("stats lock poisoned") = Some(stats.clone());
+    tracing::debug!(total_memes = stats.total_memes, stored_bytes = stats.stored_bytes, "Statistics refreshed");
+    Ok(stats)
+}
+
+/// The cached statistics, computed on the spot when the aggregation job has not produced a
+/// recent copy (right after startup, or on Lambda where the job does not run).
+pub async fn current_stats(state: &AppState) -> Result<Arc<MemeStats>, AppError> {
+    // Twice the interval, so a request never races a job that is merely running late
+    let max_age = chrono::Duration::seconds(2 * state.config.stats_interval_secs as i64);
+    let cached = state.stats.read().expect("stats lock poisoned").clone();
+    match cached {
+        Some(stats) if Utc::now() - stats.computed_at < max_age => Ok(stats),
+        _ => refresh_stats(state).await,
+    }
+}
+
+/// Spawns a task that refreshes the statistics every `interval`, starting immediately.
+/// Failures are logged and the previous copy is kept until the next tick. The task exits
+/// once `shutdown` is cancelled.
+#[cfg_attr(feature = "lambda", allow(dead_code))] // Not scheduled on Lambda
+pub fn spawn_stats_aggregation(
+    state: Arc<AppState>,
+    interval: Duration,
+    shutdown: CancellationToken,
+) -> JoinHandle<()> {
+    tracing::info!(interval_secs = interval.as_secs(), "Scheduling statistics aggregation");
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            tokio::select! {
+                _ = ticker.tick() => {}
+                _ = shutdown.cancelled() => break,
+            }
+            if let Err(e) = refresh_stats(&state).await {
+                tracing::error!(error = %e, "Statistics aggregation failed");
+            }
+        }
+    })
+}
@@ -1,44 +1,81 @@
 use crate::{
-    domain::FileStorage,
+    domain::{FileStorage, ObjectMetadata, StoredObject, UploadOptions},
     errors::StorageError,
 };
 use anyhow::Context;
 use async_trait::async_trait;
 use aws_sdk_s3::{
+    presigning::PresigningConfig,
     primitives::ByteStream,
+    types::{ServerSideEncryption, TaggingDirective},
     Client as S3Client,
     error::SdkError,
 };
-use tracing;
+use axum::body::Bytes;
+use chrono::{DateTime, Utc};
+use std::time::Duration;
+
+/// An S3 timestamp as a chrono one; `None` out of chrono's range.
+fn chrono_time(time: &aws_sdk_s3::primitives::DateTime) -> Option<DateTime<Utc>> {
+    DateTime::from_timestamp(time.secs(), time.subsec_nanos())
+}
 
 #[derive(Debug, Clone)]
 pub struct S3FileStorage {
     client: S3Client,
     bucket_name: String,
+    key_prefix: String, // Prepended to every key, e.g. "tenants/cats/"; empty for the whole bucket
+    kms_key_id: Option<String>, // Requests SSE-KMS with this key on every upload; bucket default when unset
+    object_tagging: bool, // Sends upload tags; needs s3:PutObjectTagging
 }
 
 impl S3FileStorage {
-    pub fn new(client: S3Client, bucket_name: String) -> Self {
-        Self { client, bucket_name }
+    pub fn new(client: S3Client, bucket_name: String, key_prefix: String) -> Self {
+        Self { client, bucket_name, key_prefix, kms_key_id: None, object_tagging: false }
+    }
+
+    /// Encrypts every uploaded object with the given KMS key (SSE-KMS), whatever the
+    /// bucket's default encryption.
+    pub fn with_kms_key(mut self, kms_key_id: Option<String>) -> Self {
+        self.kms_key_id = kms_key_id;
+        self
+    }
+
+    /// Stores the tags passed to `upload` on the objects. Off by default, since PutObject
+    /// with tags also needs the `s3:PutObjectTagging` permission.
+    pub fn with_object_tagging(mut self, enabled: bool) -> Self {
+        self.object_tagging = enabled;
+        self
+    }
+
+    /// The S3 object key for a storage key.
+    fn object_key(&self, key: &str) -> String {
+        format!("{}{}", self.key_prefix, key)
     }
 }
 
 #[async_trait]
 impl FileStorage for S3FileStorage {
-    /// Uploads data to S3 using PutObject. Sets Content-Type.
-    async fn upload(&self, key: &str, data: Vec<u8>, content_type: Option<String>) -> Result<(), StorageError> {
-        let ct_log = content_type.clone().unwrap_or_else(|| "application/octet-stream".to_string()); // Clone for logging if needed, or use ? directly
-        tracing::debug!(s3_key = %key, bucket = %self.bucket_name, content_type = ?content_type, "S3: Uploading file");
+    /// Uploads data to S3 using PutObject with the content type, user metadata, tags (when
+    /// tagging is on) and SSE-KMS (when a key is set). Images are small enough for a single
+    /// PutObject, so there is no multipart path.
+    async fn upload(&self, key: &str, data: Bytes, options: UploadOptions) -> Result<(), StorageError> {
+        let content_type = options.content_type.unwrap_or_else(|| "application/octet-stream".to_string());
+        tracing::debug!(s3_key = %key, bucket = %self.bucket_name, %content_type, "S3: Uploading file");
 
-        let body = ByteStream::from(data);
+        let tagging = (self.object_tagging && !options.tags.is_empty()).then(|| {
+            url::form_urlencoded::Serializer::new(String::new()).extend_pairs(&options.tags).finish()
+        });
         self.client
             .put_object()
             .bucket(&self.bucket_name)
-            .key(key)
-            .body(body)
-            // --- Set the Content-Type metadata on the S3 object ---
-            .content_type(ct_log)
-            // ------------------------------------------------------
+            .key(self.object_key(key))
+            .body(ByteStream::from(data))
+            .content_type(content_type)
+            .set_metadata(Some(options.metadata.into_iter().collect()))
+            .set_tagging(tagging)
+            .set_server_side_encryption(self.kms_key_id.as_ref().map(|_| ServerSideEncryption::AwsKms))
+            .set_ssekms_key_id(self.kms_key_id.clone())
             .send()
             .await
             .context(format!("S3: Failed to upload object with key '{}'", key))
@@ -48,34 +85,108 @@ impl FileStorage for S3FileStorage {
         Ok(())
     }
 
-    /// Downloads file data and its content type from S3 using GetObject.
-    async fn download(&self, key: &str) -> Result<(ByteStream, Option<String>), StorageError> {
+    /// Downloads file data and its metadata from S3 using GetObject.
+    async fn download(&self, key: &str) -> Result<(ByteStream, ObjectMetadata), StorageError> {
         tracing::debug!(s3_key = %key, bucket = %self.bucket_name, "S3: Downloading file");
 
         let output = self.client
             .get_object()
             .bucket(&self.bucket_name)
-            .key(key)
+            .key(self.object_key(key))
             .send()
             .await
             .map_err(|sdk_err| { // Map SdkError
                 // Check specifically for NoSuchKey
-                if let SdkError::ServiceError(service_err) = &sdk_err {
-                    if service_err.err().meta().code() == Some("NoSuchKey") {
-                         tracing::warn!(s3_key = %key, bucket = %self.bucket_name, "S3: NoSuchKey error downloading file");
-                         return StorageError::NotFound(key.to_string()); // Return specific NotFound error
-                    }
+                if let SdkError::ServiceError(service_err) = &sdk_err
+                    && service_err.err().meta().code() == Some("NoSuchKey")
+                {
+                    tracing::warn!(s3_key = %key, bucket = %self.bucket_name, "S3: NoSuchKey error downloading file");
+                    return StorageError::NotFound(key.to_string()); // Return specific NotFound error
                 }
                 // For other errors, wrap them in BackendError
                 tracing::error!(s3_key = %key, bucket = %self.bucket_name, error = %sdk_err, "S3: Error downloading file");
                 StorageError::BackendError(anyhow::Error::new(sdk_err).context(format!("S3: Failed to download object with key '{}'", key)))
             })?;
 
-        let content_type = output.content_type().map(|s| s.to_string());
-        tracing::debug!(s3_key = %key, bucket = %self.bucket_name, ?content_type, "S3: Download successful");
+        let metadata = ObjectMetadata {
+            content_type: output.content_type().map(|s| s.to_string()),
+            content_length: output.content_length().and_then(|len| u64::try_from(len).ok()),
+            etag: output.e_tag().map(|s| s.to_string()),
+            last_modified: output.last_modified().and_then(chrono_time),
+        };
+        tracing::debug!(s3_key = %key, bucket = %self.bucket_name, content_type = ?metadata.content_type, "S3: Download successful");
 
         // output.body is the ByteStream
-        Ok((output.body, content_type))
+        Ok((output.body, metadata))
+    }
+
+    /// Reads an object's metadata from S3 using HeadObject.
+    async fn head(&self, key: &str) -> Result<ObjectMetadata, StorageError> {
+        let output = self.client
+            .head_object()
+            .bucket(&self.bucket_name)
+            .key(self.object_key(key))
+            .send()
+            .await
+            .map_err(|sdk_err| {
+                // HeadObject responses have no body, so a missing key is only known by its status
+                if let SdkError::ServiceError(service_err) = &sdk_err
+                    && service_err.err().is_not_found()
+                {
+                    return StorageError::NotFound(key.to_string());
+                }
+                tracing::error!(s3_key = %key, bucket = %self.bucket_name, error = %sdk_err, "S3: Error reading object metadata");
+                StorageError::BackendError(anyhow::Error::new(sdk_err).context(format!("S3: Failed to head object with key '{}'", key)))
+            })?;
+
+        Ok(ObjectMetadata {
+            content_type: output.content_type().map(|s| s.to_string()),
+            content_length: output.content_length().and_then(|len| u64::try_from(len).ok()),
+            etag: output.e_tag().map(|s| s.to_string()),
+            last_modified: output.last_modified().and_then(chrono_time),
+        })
+    }
+
+    /// Lists the bucket (under the key prefix) with ListObjectsV2, following every page.
+    async fn list(&self) -> Result<Vec<StoredObject>, StorageError> {
+        tracing::debug!(bucket = %self.bucket_name, "S3: Listing objects");
+        let mut objects = Vec::new();
+        let mut pages = self.client
+            .list_objects_v2()
+            .bucket(&self.bucket_name)
+            .prefix(&self.key_prefix)
+            .into_paginator()
+            .send();
+        while let Some(page) = pages.next().await {
+            let page = page
+                .context(format!("S3: Failed to list objects in bucket '{}'", self.bucket_name))
+                .map_err(StorageError::BackendError)?;
+            for object in page.contents() {
+                let Some(key) = object.key().and_then(|key| key.strip_prefix(self.key_prefix.as_str())) else { continue };
+                objects.push(StoredObject {
+                    key: key.to_string(),
+                    size: object.size().and_then(|size| u64::try_from(size).ok()).unwrap_or(0),
+                });
+            }
+        }
+        tracing::debug!(bucket = %self.bucket_name, count = objects.len(), "S3: Listed objects");
+        Ok(objects)
+    }
+
+    /// Presigns a GetObject request with the client's credentials.
+    async fn presigned_url(&self, key: &str, expires_in: Duration) -> Result<String, StorageError> {
+        let presigning = PresigningConfig::expires_in(expires_in)
+            .context("S3: Invalid presigned URL lifetime")
+            .map_err(StorageError::BackendError)?;
+        let request = self.client
+            .get_object()
+            .bucket(&self.bucket_name)
+            .key(self.object_key(key))
+            .presigned(presigning)
+            .await
+            .context(format!("S3: Failed to presign object with key '{}'", key))
+            .map_err(StorageError::BackendError)?;
+        Ok(request.uri().to_string())
     }
 
     /// Deletes an object from S3 using DeleteObject.
@@ -85,7 +196,7 @@ impl FileStorage for S3FileStorage {
         self.client
             .delete_object()
             .bucket(&self.bucket_name)
-            .key(key)
+            .key(self.object_key(key))
             .send()
             .await
             .map_err(|sdk_err| { // Map SdkError
@@ -98,4 +209,50 @@ impl FileStorage for S3FileStorage {
         tracing::debug!(s3_key = %key, bucket = %self.bucket_name, "S3: Delete request successful (object might not have existed)");
         Ok(())
     }
+
+    /// Copies within the bucket with CopyObject, which keeps the content type and metadata.
+    /// Tags are copied when tagging is on; otherwise the copy has none, so the permission to
+    /// read tags is not needed. SSE-KMS is requested again, as copies do not inherit it.
+    async fn copy(&self, from: &str, to: &str) -> Result<(), StorageError> {
+        tracing::debug!(from = %from, to = %to, bucket = %self.bucket_name, "S3: Copying object");
+        // The source is `<bucket>/<key>`, URL-encoded except for the separators
+        let source_key: Vec<String> = self
+            .object_key(from)
+            .split('/')
+            .map(|segment| url::form_urlencoded::byte_serialize(segment.as_bytes()).collect::<String>().replace('+', "%20"))
+            .collect();
+        let tagging_directive = if self.object_tagging { TaggingDirective::Copy } else { TaggingDirective::Replace };
+        self.client
+            .copy_object()
+            .bucket(&self.bucket_name)
+            .copy_source(format!("{}/{}", self.bucket_name, source_key.join("/")))
+            .key(self.object_key(to))
+            .tagging_directive(tagging_directive)
+            .set_server_side_encryption(self.kms_key_id.as_ref().map(|_| ServerSideEncryption::AwsKms))
+            .set_ssekms_key_id(self.kms_key_id.clone())
+            .send()
+            .await
+            .map_err(|sdk_err| {
+                if let SdkError::ServiceError(service_err) = &sdk_err
+                    && service_err.err().meta().code() == Some("NoSuchKey")
+                {
+                    return StorageError::NotFound(from.to_string());
+                }
+                tracing::error!(from = %from, to = %to, bucket = %self.bucket_name, error = %sdk_err, "S3: Error copying object");
+                StorageError::BackendError(anyhow::Error::new(sdk_err).context(format!("S3: Failed to copy object '{}' to '{}'", from, to)))
+            })?;
+        Ok(())
+    }
+
+    /// Checks the bucket with HeadBucket.
+    async fn ping(&self) -> Result<(), StorageError> {
+        self.client
+            .head_bucket()
+            .bucket(&self.bucket_name)
+            .send()
+            .await
+            .context(format!("S3: Bucket '{}' is not reachable", self.bucket_name))
+            .map_err(StorageError::BackendError)?;
+        Ok(())
+    }
 }
@@ -0,0 +1,427 @@
+//! Telegram bot (only with the `telegram` feature), long-polling the Bot API from the server,
+//! or taking the updates Telegram POSTs to `/telegram/webhook` when
+//! `APP_TELEGRAM_WEBHOOK_SECRET` is set:
+//!
+//! - A photo sent to the bot becomes a meme through [`services::create_meme`]: the caption's
+//!   first line is the title, the whole caption the description, and its `#hashtags` the
+//!   tags. The bot replies with the new meme's ID or why it was rejected.
+//! - `/random` and `/latest` answer with a random or the newest meme of the public listing.
+//!
+//! Telegram hands each update to one poller, so only one instance should poll; any instance
+//! answers the webhook.
+//!
+//! This is not built on teloxide: the bot calls four Bot API methods, and its webhook is a
+//! route of the app's own router, behind the same middleware as the other bot callbacks,
+//! rather than the listener or dispatcher teloxide would run beside it.
+
+use crate::{
+    announcements::{has_absolute_image_url, latest_listed_meme, random_listed_meme, truncate},
+    auth::constant_time_eq,
+    config::Config,
+    errors::AppError,
+    handlers,
+    models::Meme,
+    services::{self, Caller, ImageInput, ImageUpload},
+    validation::MemeSubmission,
+    AppState,
+};
+use anyhow::{bail, Context};
+use axum::{
+    body::Bytes,
+    extract::State,
+    http::{HeaderMap, StatusCode},
+};
+use reqwest::{redirect, Client};
+use serde::{de::DeserializeOwned, Deserialize};
+use serde_json::{json, Value};
+use std::{sync::Arc, time::Duration};
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+
+const API_URL: &str = "https://api.telegram.org";
+/// How long a `getUpdates` call waits for new messages.
+const POLL_TIMEOUT_SECS: u64 = 30;
+/// Per-request limit for calls to Telegram, longer than a poll.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(POLL_TIMEOUT_SECS + 10);
+/// Pause after a failed poll.
+const RETRY_DELAY: Duration = Duration::from_secs(5);
+/// Limit of photo captions.
+const MAX_CAPTION: usize = 1024;
+/// Carries `APP_TELEGRAM_WEBHOOK_SECRET` on every webhook delivery.
+const SECRET_HEADER: &str = "x-telegram-bot-api-secret-token";
+
+const USAGE: &str = "Send me a photo with a caption to upload it as a meme: the first line is the title, \
+and #hashtags become tags.\n/random shows a random meme, /latest the newest one.";
+
+/// The bot's side of Telegram: its token and an HTTP client for the Bot API.
+pub struct TelegramBot {
+    http: Client,
+    token: String,
+    webhook: Option<Webhook>,
+}
+
+/// Where Telegram delivers updates instead of being polled, and the secret it sends along.
+struct Webhook {
+    url: String,
+    secret: String,
+}
+
+impl TelegramBot {
+    /// The bot of `config`; `None` without `APP_TELEGRAM_BOT_TOKEN`.
+    pub fn from_config(config: &Config) -> Result<Option<Self>, AppError> {
+        let Some(token) = config.telegram_bot_token.clone() else {
+            return Ok(None);
+        };
+        let http = Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .redirect(redirect::Policy::none())
+            .build()
+            .map_err(|e| AppError::InitError(format!("Failed to build Telegram HTTP client: {}", e)))?;
+        let webhook = config.telegram_webhook_secret.clone().map(|secret| Webhook {
+            // Checked when the configuration was loaded
+            url: format!("{}/telegram/webhook", config.public_url.as_deref().expect("webhooks need a public URL")),
+            secret,
+        });
+        Ok(Some(Self { http, token, webhook }))
+    }
+
+    /// Whether Telegram POSTs updates to `/telegram/webhook` rather than being polled.
+    pub fn uses_webhook(&self) -> bool {
+        self.webhook.is_some()
+    }
+
+    /// Points the bot's webhook at `/telegram/webhook`, replacing any earlier one. Failures
+    /// are logged; the webhook set before stays in place.
+    pub async fn register_webhook(&self) {
+        let Some(webhook) = &self.webhook else {
+            return;
+        };
+        let params = json!({ "url": webhook.url, "secret_token": webhook.secret, "allowed_updates": ["message"] });
+        match self.call::<Value>("setWebhook", params).await {
+            Ok(_) => tracing::info!(url = %webhook.url, "Registered the Telegram webhook"),
+            Err(e) => tracing::warn!(url = %webhook.url, error = %e, "Failed to register the Telegram webhook"),
+        }
+    }
+
+    /// Checks that a webhook delivery carries the secret the webhook was registered with.
+    fn verify(&self, headers: &HeaderMap) -> Result<(), AppError> {
+        let provided = headers.get(SECRET_HEADER).map(|value| value.as_bytes());
+        match (&self.webhook, provided) {
+            (Some(webhook), Some(provided)) if constant_time_eq(provided, webhook.secret.as_bytes()) => Ok(()),
+            _ => Err(AppError::Unauthorized("Invalid webhook secret".to_string())),
+        }
+    }
+
+    /// Calls a Bot API method. Errors never include the URL, which holds the token.
+    async fn call<T: DeserializeOwned>(&self, method: &str, params: Value) -> anyhow::Result<T> {
+        let response = self
+            .http
+            .post(format!("{}/bot{}/{}", API_URL, self.token, method))
+            .json(&params)
+            .send()
+            .await
+            .map_err(reqwest::Error::without_url)
+            .context("Telegram is unreachable")?;
+        let answer: ApiResponse<T> =
+            response.json().await.map_err(reqwest::Error::without_url).context("Unexpected Bot API response")?;
+        match answer.result {
+            Some(result) if answer.ok => Ok(result),
+            _ => bail!("{} failed: {}", method, answer.description.unwrap_or_else(|| "unknown error".to_string())),
+        }
+    }
+
+    /// Downloads the file of a photo.
+    async fn download(&self, file_id: &str) -> anyhow::Result<Bytes> {
+        let file: File = self.call("getFile", json!({ "file_id": file_id })).await?;
+        let path = file.file_path.context("Telegram did not provide the file")?;
+        let response = self
+            .http
+            .get(format!("{}/file/bot{}/{}", API_URL, self.token, path))
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(reqwest::Error::without_url)
+            .context("Failed to download the photo")?;
+        Ok(response.bytes().await.map_err(reqwest::Error::without_url)?)
+    }
+
+    async fn reply(&self, message: &Message, text: &str) -> anyhow::Result<()> {
+        let params = json!({
+            "chat_id": message.chat.id,
+            "text": text,
+            "reply_parameters": { "message_id": message.message_id },
+        });
+        self.call::<Value>("sendMessage", params).await?;
+        Ok(())
+    }
+
+    /// Sends `meme` to a chat: its image with the title and description as caption, or the
+    /// text alone when Telegram cannot fetch the image.
+    async fn send_meme(&self, state: &AppState, chat_id: i64, meme: Meme) -> anyhow::Result<()> {
+        let view = handlers::meme_view(state, meme).await?;
+        let text = truncate(&format!("{}\n\n{}", view.meme.title, view.meme.description), MAX_CAPTION);
+        if has_absolute_image_url(&view) {
+            self.call::<Value>("sendPhoto", json!({ "chat_id": chat_id, "photo": view.image_url, "caption": text })).await?;
+        } else {
+            self.call::<Value>("sendMessage", json!({ "chat_id": chat_id, "text": text })).await?;
+        }
+        Ok(())
+    }
+
+    /// Answers one message: uploads its photo, or runs its command.
+    async fn handle(&self, state: &AppState, message: Message) -> anyhow::Result<()> {
+        if let Some(photo) = message.photo.last() {
+            let Some(caption) = message.caption.as_deref().filter(|caption| !caption.trim().is_empty()) else {
+                return self.reply(&message, "Add a caption to the photo; its first line becomes the meme's title.").await;
+            };
+            let text = match self.upload(state, &photo.file_id, caption).await {
+                Ok(meme) => format!("Uploaded \"{}\" ({}).", meme.title, meme.meme_id),
+                Err(e) => failure_message(&e),
+            };
+            return self.reply(&message, &text).await;
+        }
+
+        let Some(command) = message.text.as_deref().and_then(command_of) else {
+            return Ok(());
+        };
+        let meme = match command {
+            "random" => random_listed_meme(state).await?,
+            "latest" => latest_listed_meme(state).await?,
+            "start" | "help" => return self.reply(&message, USAGE).await,
+            _ => return self.reply(&message, "Unknown command. Try /random or /latest.").await,
+        };
+        match meme {
+            Some(meme) => self.send_meme(state, message.chat.id, meme).await,
+            None => self.reply(&message, "There are no memes yet.").await,
+        }
+    }
+
+    /// Creates a meme from a photo and its caption.
+    async fn upload(&self, state: &AppState, file_id: &str, caption: &str) -> Result<Meme, AppError> {
+        let image = match self.download(file_id).await {
+            // Telegram re-encodes photos as JPEG
+            Ok(data) => ImageInput::Provided(ImageUpload {
+                data,
+                filename: Some("telegram.jpg".to_string()),
+                content_type: Some("image/jpeg".to_string()),
+                source_url: None,
+            }),
+            Err(e) => {
+                tracing::warn!(error = %e, "Failed to download a Telegram photo");
+                ImageInput::Invalid { field: "image", message: "could not be downloaded from Telegram".to_string() }
+            }
+        };
+        services::create_meme(state, submission(caption), image, &Caller::telegram()).await
+    }
+}
+
+/// Handler for POST /telegram/webhook. The update is acknowledged right away and its message
+/// answered in the background, since Telegram resends updates it waited too long for.
+pub async fn handle_webhook(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<StatusCode, AppError> {
+    let bot = state.telegram.clone().expect("route is only served with a Telegram webhook");
+    bot.verify(&headers)?;
+    let update: Update = serde_json::from_slice(&body).map_err(|e| AppError::InvalidInput(format!("Invalid update: {}", e)))?;
+    if let Some(message) = update.message {
+        state.in_flight.clone().spawn(async move {
+            if let Err(e) = bot.handle(&state, message).await {
+                tracing::warn!(error = %e, "Failed to answer a Telegram message");
+            }
+        });
+    }
+    Ok(StatusCode::OK)
+}
+
+/// The meme metadata of a photo's caption.
+fn submission(caption: &str) -> MemeSubmission {
+    let caption = caption.trim();
+    MemeSubmission {
+        title: caption.lines().next().map(str::to_string),
+        description: Some(caption.to_string()),
+        tags: caption
+            .split_whitespace()
+            .filter_map(|word| word.strip_prefix('#'))
+            .filter(|tag| !tag.is_empty())
+            .map(str::to_string)
+            .collect(),
+        ..MemeSubmission::default()
+    }
+}
+
+/// Spawns a task polling Telegram for messages to the bot and answering them one at a time.
+/// Failed polls are logged and retried after a pause. The task exits once `shutdown` is
+/// cancelled.
+pub fn spawn_polling(state: Arc<AppState>, bot: Arc<TelegramBot>, shutdown: CancellationToken) -> JoinHandle<()> {
+    tracing::info!("Polling Telegram for messages to the bot");
+    tokio::spawn(async move {
+        let mut offset = 0;
+        loop {
+            let params = json!({ "offset": offset, "timeout": POLL_TIMEOUT_SECS, "allowed_updates": ["message"] });
+            let polled = tokio::select! {
+                polled = bot.call::<Vec<Update>>("getUpdates", params) => polled,
+                _ = shutdown.cancelled() => break,
+            };
+            let updates = match polled {
+                Ok(updates) => updates,
+                Err(e) => {
+                    tracing::warn!(error = %e, "Polling Telegram failed");
+                    tokio::select! {
+                        _ = tokio::time::sleep(RETRY_DELAY) => continue,
+                        _ = shutdown.cancelled() => break,
+                    }
+                }
+            };
+            for update in updates {
+                // Confirmed by the next poll, so a message that fails is not handled again
+                offset = update.update_id + 1;
+                let Some(message) = update.message else { continue };
+                if let Err(e) = bot.handle(&state, message).await {
+                    tracing::warn!(error = %e, "Failed to answer a Telegram message");
+                }
+            }
+        }
+    })
+}
+
+/// The name of the bot command `text` starts with: `/random` or `/random@SomeBot` in groups.
+fn command_of(text: &str) -> Option<&str> {
+    let word = text.split_whitespace().next()?.strip_prefix('/')?;
+    Some(word.split('@').next().unwrap_or(word))
+}
+
+/// What to tell the user about a rejected or failed upload.
+fn failure_message(error: &AppError) -> String {
+    match error {
+        AppError::ValidationFailed(errors) => {
+            let failures: Vec<String> =
+                errors.iter().map(|(field, messages)| format!("{} {}", field, messages.join(", "))).collect();
+            format!("The meme was not uploaded: {}.", failures.join("; "))
+        }
+        AppError::PayloadTooLarge(message) | AppError::QuotaExceeded(message) => {
+            format!("The meme was not uploaded: {}.", message)
+        }
+        other => {
+            tracing::warn!(error = %other, "Telegram upload failed");
+            "The meme could not be stored, please try again later.".to_string()
+        }
+    }
+}
+
+/// The envelope of every Bot API answer.
+#[derive(Deserialize, Debug)]
+struct ApiResponse<T> {
+    ok: bool,
+    result: Option<T>,
+    description: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct Update {
+    update_id: i64,
+    message: Option<Message>,
+}
+
+/// A message to the bot; only the fields it reads.
+#[derive(Deserialize, Debug)]
+struct Message {
+    message_id: i64,
+    chat: Chat,
+    text: Option<String>,
+    caption: Option<String>,
+    /// The photo in increasing sizes.
+    #[serde(default)]
+    photo: Vec<PhotoSize>,
+}
+
+#[derive(Deserialize, Debug)]
+struct Chat {
+    id: i64,
+}
+
+#[derive(Deserialize, Debug)]
+struct PhotoSize {
+    file_id: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct File {
+    file_path: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    fn bot_with_secret(webhook_secret: Option<&str>) -> TelegramBot {
+        TelegramBot {
+            http: Client::new(),
+            token: "123:token".to_string(),
+            webhook: webhook_secret.map(|secret| Webhook { url: "https://memes.example.com/telegram/webhook".to_string(), secret: secret.to_string() }),
+        }
+    }
+
+    fn secret_headers(secret: &'static str) -> HeaderMap {
+        HeaderMap::from_iter([(axum::http::HeaderName::from_static(SECRET_HEADER), HeaderValue::from_static(secret))])
+    }
+
+    #[test]
+    fn webhook_deliveries_need_the_registered_secret() {
+        let bot = bot_with_secret(Some("s3cret-token"));
+        assert!(bot.verify(&secret_headers("s3cret-token")).is_ok());
+        assert!(bot.verify(&secret_headers("s3cret-tokem")).is_err());
+        assert!(bot.verify(&secret_headers("s3cret")).is_err());
+        assert!(bot.verify(&HeaderMap::new()).is_err());
+        // A polling bot takes no deliveries
+        assert!(bot_with_secret(None).verify(&secret_headers("s3cret-token")).is_err());
+    }
+
+    #[test]
+    fn photo_updates_are_parsed_with_their_largest_size() {
+        let update: Update = serde_json::from_value(json!({
+            "update_id": 10,
+            "message": {
+                "message_id": 5,
+                "date": 1700000000,
+                "chat": { "id": -100, "type": "group" },
+                "caption": "Monday\nEvery week #mood #coffee #",
+                "photo": [
+                    { "file_id": "small", "file_unique_id": "a", "width": 90, "height": 90 },
+                    { "file_id": "large", "file_unique_id": "b", "width": 800, "height": 800 },
+                ],
+            },
+        }))
+        .unwrap();
+        assert_eq!(update.update_id, 10);
+        let message = update.message.unwrap();
+        assert_eq!((message.message_id, message.chat.id), (5, -100));
+        assert_eq!(message.photo.last().unwrap().file_id, "large");
+
+        let submission = submission(message.caption.as_deref().unwrap());
+        assert_eq!(submission.title.as_deref(), Some("Monday"));
+        assert_eq!(submission.description.as_deref(), Some("Monday\nEvery week #mood #coffee #"));
+        assert_eq!(submission.tags, ["mood", "coffee"]);
+    }
+
+    #[test]
+    fn commands_are_read_from_text_messages() {
+        let update: Update = serde_json::from_value(json!({
+            "update_id": 11,
+            "message": { "message_id": 6, "chat": { "id": 42 }, "text": "/random@MemeBot please" },
+        }))
+        .unwrap();
+        let message = update.message.unwrap();
+        assert!(message.photo.is_empty());
+        assert_eq!(message.text.as_deref().and_then(command_of), Some("random"));
+        assert_eq!(command_of("/latest"), Some("latest"));
+        assert_eq!(command_of("hello /random"), None);
+        assert_eq!(command_of(""), None);
+
+        // Other kinds of updates carry no message
+        let update: Update = serde_json::from_value(json!({ "update_id": 12, "edited_message": { "message_id": 6 } })).unwrap();
+        assert!(update.message.is_none());
+    }
+}
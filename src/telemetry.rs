@@ -0,0 +1,104 @@
+use crate::{config::Config, emf, errors::AppError, runtime_metrics, AppState};
+use axum::{extract::State, http::header, response::IntoResponse};
+use metrics_exporter_prometheus::{PrometheusBuilder, PrometheusHandle};
+use serde::Serialize;
+use std::{
+    str::FromStr,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+/// Where recorded metrics go (`APP_METRICS_SINK`).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MetricsSink {
+    /// Served in the Prometheus text format at `/metrics`.
+    #[default]
+    Prometheus,
+    /// Written as CloudWatch Embedded Metric Format documents (see [`emf`]).
+    Emf,
+    /// Not recorded at all.
+    None,
+}
+
+impl FromStr for MetricsSink {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "prometheus" => Ok(MetricsSink::Prometheus),
+            "emf" => Ok(MetricsSink::Emf),
+            "none" => Ok(MetricsSink::None),
+            other => Err(format!("unknown metrics sink '{}' (expected prometheus, emf or none)", other)),
+        }
+    }
+}
+
+/// The recorder installed in this process.
+enum Installed {
+    Prometheus(PrometheusHandle),
+    Emf,
+}
+
+/// The recorder is process-global; tests build several app states in one process.
+static RECORDER: Mutex<Option<Installed>> = Mutex::new(None);
+
+/// Installs the global metrics recorder of the configured sink on first use, along with the
+/// Tokio runtime sampler, and returns the handle used to render the `/metrics` endpoint,
+/// `None` unless metrics go to Prometheus. Later calls share the first recorder. Must be
+/// called from within the Tokio runtime.
+pub fn install_metrics_recorder(config: &Config) -> Result<Option<PrometheusHandle>, AppError> {
+    let mut installed = RECORDER.lock().expect("metrics recorder lock poisoned");
+    match installed.as_ref() {
+        Some(Installed::Prometheus(handle)) => return Ok(Some(handle.clone())),
+        Some(Installed::Emf) => return Ok(None),
+        None => {}
+    }
+
+    match config.metrics_sink {
+        MetricsSink::Prometheus => {
+            let handle = PrometheusBuilder::new()
+                .install_recorder()
+                .map_err(|e| AppError::InitError(format!("Failed to install metrics recorder: {}", e)))?;
+
+            // Histograms are drained periodically rather than on every render
+            let upkeep = handle.clone();
+            tokio::spawn(async move {
+                let mut ticker = tokio::time::interval(Duration::from_secs(5));
+                loop {
+                    ticker.tick().await;
+                    upkeep.run_upkeep();
+                }
+            });
+            *installed = Some(Installed::Prometheus(handle.clone()));
+            spawn_runtime_sampler(config);
+            Ok(Some(handle))
+        }
+        MetricsSink::Emf => {
+            emf::install(
+                config.emf_namespace.clone(),
+                Duration::from_secs(config.emf_flush_interval_secs),
+                config.emf_destination.clone(),
+            )?;
+            *installed = Some(Installed::Emf);
+            spawn_runtime_sampler(config);
+            Ok(None)
+        }
+        MetricsSink::None => Ok(None),
+    }
+}
+
+fn spawn_runtime_sampler(config: &Config) {
+    if config.runtime_metrics_interval_secs > 0 {
+        runtime_metrics::spawn_sampler(Duration::from_secs(config.runtime_metrics_interval_secs));
+    }
+}
+
+/// Serves all recorded metrics in the Prometheus text format. Only routed when metrics go
+/// to Prometheus.
+pub async fn metrics_handler(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        state.metrics.as_ref().map(PrometheusHandle::render).unwrap_or_default(),
+    )
+}
@@ -0,0 +1,205 @@
+use crate::{
+    domain::{TenantConfigRepository, TenantOverrides},
+    errors::AppError,
+    AppState,
+};
+use axum::{
+    extract::{Request, State},
+    http::{header, HeaderMap, HeaderName},
+    response::{IntoResponse, Response},
+    Router,
+};
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, RwLock},
+    time::{Duration, Instant},
+};
+use tower::ServiceExt;
+
+/// Header naming the tenant a request is for.
+pub static TENANT_HEADER: HeaderName = HeaderName::from_static("x-tenant-id");
+
+/// Longest accepted tenant ID; it becomes part of a DynamoDB table name.
+const MAX_TENANT_ID_LEN: usize = 32;
+
+/// Whether `id` can name a tenant: lowercase letters, digits and `-`, usable as is in table
+/// names, S3 keys and subdomains.
+pub fn is_valid_tenant_id(id: &str) -> bool {
+    (1..=MAX_TENANT_ID_LEN).contains(&id.len())
+        && id.bytes().all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b == b'-')
+        && !id.starts_with('-')
+        && !id.ends_with('-')
+}
+
+/// One router per tenant, each built over that tenant's [`AppState`], so handlers can only
+/// reach the tenant's own table and key prefix. Requests that name no tenant get the default
+/// (unscoped) router.
+struct TenantRouters {
+    default: Router,
+    tenants: BTreeMap<String, Router>,
+    domain: Option<String>,
+}
+
+impl TenantRouters {
+    /// Picks the router for a request from the `X-Tenant-Id` header or, with
+    /// `APP_TENANT_DOMAIN` set, the subdomain in `Host`.
+    fn resolve(&self, headers: &HeaderMap) -> Result<&Router, AppError> {
+        let named = match headers.get(&TENANT_HEADER) {
+            Some(value) => Some(
+                value.to_str().map_err(|_| AppError::InvalidInput("X-Tenant-Id header is not valid text".to_string()))?,
+            ),
+            None => self.domain.as_deref().and_then(|domain| subdomain(headers, domain)),
+        };
+        match named {
+            None => Ok(&self.default),
+            Some(tenant) => self.tenants.get(tenant).ok_or_else(|| AppError::TenantNotFound(tenant.to_string())),
+        }
+    }
+}
+
+/// The label directly in front of `domain` in the request's `Host`, e.g. `cats` for
+/// `cats.memes.example.com:8080` under `memes.example.com`.
+fn subdomain<'a>(headers: &'a HeaderMap, domain: &str) -> Option<&'a str> {
+    let host = headers.get(header::HOST)?.to_str().ok()?;
+    let host = host.rsplit_once(':').map_or(host, |(name, _port)| name);
+    let label = host.strip_suffix(domain)?.strip_suffix('.')?;
+    (!label.is_empty() && !label.contains('.')).then_some(label)
+}
+
+/// Builds the router for `state` and, when tenants are configured, one per tenant behind a
+/// dispatcher that sends each request to its tenant's router. Unknown tenants get a 404.
+pub fn route_by_tenant(state: &Arc<AppState>, build: fn(Arc<AppState>) -> Router) -> Router {
+    if state.tenants.is_empty() {
+        return build(state.clone());
+    }
+    let routers = TenantRouters {
+        default: build(state.clone()),
+        tenants: state
+            .tenants
+            .iter()
+            .map(|(name, tenant_state)| (name.clone(), build(tenant_state.clone())))
+            .collect(),
+        domain: state.config.tenant_domain.clone(),
+    };
+    Router::new().fallback(dispatch).with_state(Arc::new(routers))
+}
+
+async fn dispatch(State(routers): State<Arc<TenantRouters>>, request: Request) -> Response {
+    match routers.resolve(request.headers()) {
+        Ok(router) => router.clone().oneshot(request).await.into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+/// A tenant's setting overrides as last read from the tenant config table, read again once
+/// they are older than the TTL. Each instance caches on its own, so changes made elsewhere
+/// take up to one TTL to apply.
+pub struct TenantSettings {
+    tenant: String,
+    repo: Arc<dyn TenantConfigRepository>,
+    ttl: Duration,
+    cached: RwLock<Option<(Instant, Arc<TenantOverrides>)>>,
+}
+
+impl TenantSettings {
+    pub fn new(tenant: String, repo: Arc<dyn TenantConfigRepository>, ttl: Duration) -> Self {
+        Self { tenant, repo, ttl, cached: RwLock::new(None) }
+    }
+
+    pub fn tenant(&self) -> &str {
+        &self.tenant
+    }
+
+    /// The tenant's overrides, read from the table when the cached copy is missing or stale.
+    /// A stale copy is still served, with a warning, while the table cannot be read, so a
+    /// backend hiccup does not fail every upload.
+    pub async fn overrides(&self) -> Result<Arc<TenantOverrides>, AppError> {
+        let cached = self.cached.read().expect("tenant settings lock poisoned").clone();
+        if let Some((loaded_at, overrides)) = &cached
+            && loaded_at.elapsed() < self.ttl
+        {
+            return Ok(overrides.clone());
+        }
+        match self.repo.get_overrides(&self.tenant).await {
+            Ok(overrides) => Ok(self.store(overrides.unwrap_or_default())),
+            Err(e) => match cached {
+                Some((_, overrides)) => {
+                    tracing::warn!(tenant = %self.tenant, error = %e, "Failed to reload tenant overrides; using the cached copy");
+                    Ok(overrides)
+                }
+                None => Err(e.into()),
+            },
+        }
+    }
+
+    /// Stores new overrides and replaces the cached copy.
+    pub async fn replace(&self, overrides: TenantOverrides) -> Result<Arc<TenantOverrides>, AppError> {
+        self.repo.put_overrides(&self.tenant, &overrides).await?;
+        tracing::info!(tenant = %self.tenant, ?overrides, "Tenant overrides replaced");
+        Ok(self.store(overrides))
+    }
+
+    fn store(&self, overrides: TenantOverrides) -> Arc<TenantOverrides> {
+        let overrides = Arc::new(overrides);
+        *self.cached.write().expect("tenant settings lock poisoned") = Some((Instant::now(), overrides.clone()));
+        overrides
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    const DOMAIN: &str = "memes.example.com";
+
+    fn subdomain_of(host: &str) -> Option<String> {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::HOST, HeaderValue::from_str(host).unwrap());
+        subdomain(&headers, DOMAIN).map(str::to_string)
+    }
+
+    #[test]
+    fn tenants_are_named_by_the_label_before_the_domain() {
+        assert_eq!(subdomain_of("cats.memes.example.com").as_deref(), Some("cats"));
+        assert_eq!(subdomain_of("cats.memes.example.com:8080").as_deref(), Some("cats"));
+    }
+
+    #[test]
+    fn other_hosts_name_no_tenant() {
+        for host in [
+            "memes.example.com",
+            "memes.example.com:8080",
+            ".memes.example.com",
+            "evil-memes.example.com",
+            "cats.evil-memes.example.com",
+            "deep.cats.memes.example.com",
+            "cats.memes.example.com.evil.org",
+            "example.com",
+        ] {
+            assert_eq!(subdomain_of(host), None, "{}", host);
+        }
+        assert_eq!(subdomain(&HeaderMap::new(), DOMAIN), None);
+    }
+
+    #[test]
+    fn tenant_ids_are_lowercase_letters_digits_and_inner_dashes() {
+        for id in ["cats", "team-7", "a", "0", &"a".repeat(MAX_TENANT_ID_LEN)] {
+            assert!(is_valid_tenant_id(id), "{}", id);
+        }
+        for id in [
+            "",
+            "-cats",
+            "cats-",
+            "-",
+            "Cats",
+            "CATS",
+            "team_7",
+            "cats.dogs",
+            "caté",
+            &"a".repeat(MAX_TENANT_ID_LEN + 1),
+        ] {
+            assert!(!is_valid_tenant_id(id), "{}", id);
+        }
+    }
+}
@@ -0,0 +1,301 @@
+//! Helpers for integration tests (only with the `testing` feature).
+//!
+//! [`TestApp::spawn`] starts LocalStack with testcontainers, builds the real [`AppState`]
+//! against it and serves the public router on an ephemeral port. Set
+//! `APP_TEST_AWS_ENDPOINT_URL` to use an already running LocalStack (or other AWS-compatible
+//! endpoint) instead. Every app gets its own bucket and tables, so tests can share one endpoint.
+//! Tests using it are marked `#[ignore]` and run with `cargo test -- --ignored`; without
+//! Docker or an endpoint they fail rather than pass without having run.
+//!
+//! [`TestApp::local`] runs on SQLite and the filesystem in a temporary directory, for tests
+//! that need no AWS at all; it needs no Docker either.
+//!
+//! [`TestApp::replay`] serves the meme table and bucket from a cassette recorded by an
+//! earlier LocalStack run (see [`crate::recording`]) and everything else from SQLite and
+//! the filesystem, so it needs no Docker. With `APP_TEST_RECORD` set it runs against
+//! LocalStack instead and records the cassette again.
+
+use crate::{
+    build_app_state_with_sdk_config,
+    client::MemeClient,
+    config::{Config, ConfigSource},
+    generators::UploadPayload,
+    recording::{RecordingFileStorage, RecordingMemeRepository, Recorder, ReplayFileStorage, ReplayMemeRepository, Replayer},
+    routes::create_router,
+    AppState,
+};
+use aws_config::{retry::RetryConfig, BehaviorVersion, Region};
+use aws_credential_types::Credentials;
+use reqwest::multipart::{Form, Part};
+use std::{
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+use testcontainers_modules::{
+    localstack::LocalStack,
+    testcontainers::{core::client::docker_client_instance, runners::AsyncRunner, ContainerAsync, ImageExt},
+};
+use tokio::task::JoinHandle;
+use uuid::Uuid;
+
+/// Points the harness at an existing endpoint instead of starting a container.
+pub const ENDPOINT_VAR: &str = "APP_TEST_AWS_ENDPOINT_URL";
+/// Makes [`TestApp::replay`] record its cassette against LocalStack instead of replaying it.
+pub const RECORD_VAR: &str = "APP_TEST_RECORD";
+/// LocalStack's edge port inside the container.
+const LOCALSTACK_PORT: u16 = 4566;
+const REGION: &str = "us-east-1";
+
+/// A running instance of the API backed by LocalStack. The server stops on drop, and the
+/// container (if this app started one) is removed.
+pub struct TestApp {
+    /// The state behind the router, for arranging data or checking backends directly.
+    pub state: Arc<AppState>,
+    pub address: SocketAddr,
+    pub client: reqwest::Client,
+    server: JoinHandle<()>,
+    _localstack: Option<ContainerAsync<LocalStack>>,
+    cassette: Option<CassetteMode>,
+    /// Holds the SQLite file and images of a local or replaying app; removed on drop.
+    dir: Option<PathBuf>,
+}
+
+/// What happens to the cassette of a [`TestApp::replay`] app when it is dropped.
+enum CassetteMode {
+    /// Saved to `path`.
+    Recording { recorder: Arc<Recorder>, path: PathBuf },
+    /// Checked to have been played through.
+    Replaying { replayer: Arc<Replayer> },
+}
+
+impl TestApp {
+    /// Starts an app with the default test settings.
+    ///
+    /// Panics when Docker is unreachable and `APP_TEST_AWS_ENDPOINT_URL` is unset, as on
+    /// any other setup failure.
+    pub async fn spawn() -> Self {
+        Self::spawn_with(&[]).await
+    }
+
+    /// Like [`TestApp::spawn`], with extra settings keyed by environment variable name
+    /// (e.g. `("APP_ADMIN_TOKEN", "secret")`). Real environment variables still take precedence.
+    pub async fn spawn_with(settings: &[(&str, &str)]) -> Self {
+        let (state, localstack) = start_localstack_state(settings).await;
+        Self::serve(state, localstack, None, None).await
+    }
+
+    /// Starts an app on SQLite and the filesystem in a temporary directory, with extra
+    /// settings as for [`TestApp::spawn_with`]. Needs neither Docker nor an AWS endpoint.
+    pub async fn local(settings: &[(&str, &str)]) -> Self {
+        let dir = temp_dir("memes-local");
+        let state = local_state(replay_config(&dir, settings)).await;
+        Self::serve(state, None, None, Some(dir)).await
+    }
+
+    /// Starts an app whose meme table and bucket replay the cassette at `path`, recorded
+    /// by an earlier run with `APP_TEST_RECORD` set. Replaying needs no Docker; it panics on
+    /// drop when the app's calls did not match the cassette.
+    ///
+    /// With `APP_TEST_RECORD` set, starts the app against LocalStack like
+    /// [`TestApp::spawn_with`] instead and writes every call to `path` on drop (unless the
+    /// test panicked).
+    pub async fn replay(path: impl AsRef<Path>, settings: &[(&str, &str)]) -> Self {
+        let path = path.as_ref().to_path_buf();
+        if std::env::var_os(RECORD_VAR).is_some() {
+            let (state, localstack) = start_localstack_state(settings).await;
+            let recorder = Arc::new(Recorder::default());
+            let mut state = (*state).clone();
+            state.meme_repo = Arc::new(RecordingMemeRepository::new(state.meme_repo, recorder.clone()));
+            state.file_storage = Arc::new(RecordingFileStorage::new(state.file_storage, recorder.clone()));
+            let cassette = CassetteMode::Recording { recorder, path };
+            return Self::serve(Arc::new(state), localstack, Some(cassette), None).await;
+        }
+
+        let replayer = Arc::new(Replayer::load(&path).expect("failed to load the cassette"));
+        let dir = temp_dir("memes-replay");
+        let mut state = (*local_state(replay_config(&dir, settings)).await).clone();
+        state.meme_repo = Arc::new(ReplayMemeRepository::new(replayer.clone()));
+        state.file_storage = Arc::new(ReplayFileStorage::new(replayer.clone()));
+        Self::serve(Arc::new(state), None, Some(CassetteMode::Replaying { replayer }), Some(dir)).await
+    }
+
+    /// Serves the public router over `state` on an ephemeral port.
+    async fn serve(
+        state: Arc<AppState>,
+        localstack: Option<ContainerAsync<LocalStack>>,
+        cassette: Option<CassetteMode>,
+        dir: Option<PathBuf>,
+    ) -> Self {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.expect("failed to bind test listener");
+        let address = listener.local_addr().expect("test listener address");
+        let app = create_router(state.clone());
+        let server = tokio::spawn(async move {
+            axum::serve(listener, app.into_make_service_with_connect_info::<crate::client_ip::PeerAddr>()).await.expect("test server failed");
+        });
+
+        Self {
+            state,
+            address,
+            client: reqwest::Client::new(),
+            server,
+            _localstack: localstack,
+            cassette,
+            dir,
+        }
+    }
+
+    /// A typed client of this app.
+    pub fn meme_client(&self) -> MemeClient {
+        MemeClient::with_http_client(format!("http://{}", self.address), self.client.clone())
+    }
+
+    /// Absolute URL of `path` on this app.
+    pub fn url(&self, path: &str) -> String {
+        format!("http://{}{}", self.address, path)
+    }
+
+    /// Uploads a meme through `POST /upload_meme` with a small PNG image.
+    pub async fn upload_meme(&self, title: &str, description: &str) -> reqwest::Response {
+        let image = Part::bytes(sample_png())
+            .file_name("meme.png")
+            .mime_str("image/png")
+            .expect("valid MIME type");
+        let form = Form::new()
+            .text("title", title.to_string())
+            .text("description", description.to_string())
+            .part("image", image);
+        self.client
+            .post(self.url("/upload_meme"))
+            .multipart(form)
+            .send()
+            .await
+            .expect("upload request failed")
+    }
+
+    /// Uploads `payload` through `POST /upload_meme`.
+    pub async fn upload(&self, payload: &UploadPayload) -> reqwest::Response {
+        self.client
+            .post(self.url("/upload_meme"))
+            .multipart(payload.form())
+            .send()
+            .await
+            .expect("upload request failed")
+    }
+}
+
+impl Drop for TestApp {
+    fn drop(&mut self) {
+        self.server.abort();
+        match self.cassette.take() {
+            Some(CassetteMode::Recording { recorder, path }) if !std::thread::panicking() => {
+                recorder.save(&path).expect("failed to save the cassette");
+            }
+            Some(CassetteMode::Replaying { replayer }) if !std::thread::panicking() => replayer.assert_finished(),
+            _ => {}
+        }
+        if let Some(dir) = self.dir.take() {
+            let _ = std::fs::remove_dir_all(&dir);
+        }
+    }
+}
+
+/// Starts (or finds) LocalStack and builds the app state against it, per
+/// [`TestApp::spawn_with`].
+async fn start_localstack_state(settings: &[(&str, &str)]) -> (Arc<AppState>, Option<ContainerAsync<LocalStack>>) {
+    let (endpoint, localstack) = match std::env::var(ENDPOINT_VAR) {
+        Ok(endpoint) => (endpoint, None),
+        Err(_) => {
+            assert!(docker_available().await, "LocalStack tests need Docker or {} (skip them by leaving out --ignored)", ENDPOINT_VAR);
+            let container = LocalStack::default()
+                .with_env_var("SERVICES", "dynamodb,s3")
+                .start()
+                .await
+                .expect("failed to start LocalStack");
+            let host = container.get_host().await.expect("LocalStack host");
+            let port = container.get_host_port_ipv4(LOCALSTACK_PORT).await.expect("LocalStack port");
+            (format!("http://{}:{}", host, port), Some(container))
+        }
+    };
+
+    let config = test_config(&endpoint, settings);
+    let sdk_config = aws_config::defaults(BehaviorVersion::latest())
+        .region(Region::new(REGION))
+        .endpoint_url(&endpoint)
+        .credentials_provider(Credentials::new("test", "test", None, None, "testing"))
+        .retry_config(RetryConfig::disabled())
+        .load()
+        .await;
+    let state = build_app_state_with_sdk_config(config, &sdk_config)
+        .await
+        .expect("failed to build app state");
+    (state, localstack)
+}
+
+/// Settings for one test app: unique resource names so apps can share an endpoint, and
+/// resources created on startup.
+fn test_config(endpoint: &str, settings: &[(&str, &str)]) -> Config {
+    let suffix = Uuid::new_v4().simple().to_string();
+    let name = format!("memes-test-{}", &suffix[..12]);
+    let mut values = vec![
+        ("APP_S3_BUCKET_NAME", name.clone()),
+        ("APP_DYNAMODB_TABLE_NAME", name),
+        ("APP_RESOURCE_INIT", "create".to_string()),
+        ("AWS_REGION", REGION.to_string()),
+        ("AWS_ENDPOINT_URL", endpoint.to_string()),
+    ];
+    values.extend(settings.iter().map(|(key, value)| (*key, value.to_string())));
+    Config::from_source(&ConfigSource::from_values(values)).expect("invalid test configuration")
+}
+
+/// Builds the state of a local or replaying app, which reaches no AWS endpoint.
+async fn local_state(config: Config) -> Arc<AppState> {
+    let sdk_config = aws_config::defaults(BehaviorVersion::latest())
+        .region(Region::new(REGION))
+        .credentials_provider(Credentials::new("test", "test", None, None, "testing"))
+        .load()
+        .await;
+    build_app_state_with_sdk_config(config, &sdk_config)
+        .await
+        .expect("failed to build app state")
+}
+
+/// A fresh directory path under the system's temporary directory.
+fn temp_dir(prefix: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("{}-{}", prefix, Uuid::new_v4().simple()))
+}
+
+/// Settings for a local or replaying app: SQLite and the filesystem under `dir` (for a
+/// replaying app, everything the cassette does not serve), so no AWS endpoint is needed.
+fn replay_config(dir: &Path, settings: &[(&str, &str)]) -> Config {
+    let mut values = vec![
+        ("APP_REPOSITORY_BACKEND", "sqlite".to_string()),
+        ("APP_STORAGE_BACKEND", "filesystem".to_string()),
+        ("APP_SQLITE_PATH", dir.join("memes.db").display().to_string()),
+        ("APP_FILESYSTEM_ROOT", dir.join("images").display().to_string()),
+        ("APP_DYNAMODB_TABLE_NAME", "memes-replay".to_string()),
+        ("AWS_REGION", REGION.to_string()),
+    ];
+    values.extend(settings.iter().map(|(key, value)| (*key, value.to_string())));
+    Config::from_source(&ConfigSource::from_values(values)).expect("invalid test configuration")
+}
+
+/// Whether a Docker daemon answers, per testcontainers' usual lookup (`DOCKER_HOST` etc.).
+async fn docker_available() -> bool {
+    match docker_client_instance().await {
+        Ok(docker) => docker.ping().await.is_ok(),
+        Err(_) => false,
+    }
+}
+
+/// A valid 1x1 PNG image.
+pub fn sample_png() -> Vec<u8> {
+    const PNG: [u8; 67] = [
+        0x89, 0x50, 0x4e, 0x47, 0x0d, 0x0a, 0x1a, 0x0a, 0x00, 0x00, 0x00, 0x0d, 0x49, 0x48, 0x44, 0x52,
+        0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x01, 0x08, 0x06, 0x00, 0x00, 0x00, 0x1f, 0x15, 0xc4,
+        0x89, 0x00, 0x00, 0x00, 0x0a, 0x49, 0x44, 0x41, 0x54, 0x78, 0x9c, 0x63, 0x00, 0x01, 0x00, 0x00,
+        0x05, 0x00, 0x01, 0x0d, 0x0a, 0x2d, 0xb4, 0x00, 0x00, 0x00, 0x00, 0x49, 0x45, 0x4e, 0x44, 0xae,
+        0x42, 0x60, 0x82,
+    ];
+    PNG.to_vec()
+}
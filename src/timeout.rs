@@ -0,0 +1,23 @@
+use crate::errors::AppError;
+use axum::{
+    extract::{Request, State},
+    middleware::Next,
+    response::Response,
+};
+use std::time::Duration;
+
+/// Middleware that fails the request with a 504 if the handler does not produce a response
+/// within `limit`. Only the time until the response starts counts, so streamed bodies
+/// (e.g. exports) are not cut off mid-transfer.
+pub async fn enforce_timeout(
+    State(limit): State<Duration>,
+    request: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    let method = request.method().clone();
+    let path = request.uri().path().to_string();
+    tokio::time::timeout(limit, next.run(request)).await.map_err(|_| {
+        tracing::warn!(%method, %path, limit_secs = limit.as_secs(), "Request timed out");
+        AppError::Timeout(limit)
+    })
+}
@@ -0,0 +1,62 @@
+use crate::errors::AppError;
+use axum::{
+    extract::Request,
+    http::{header, uri::Authority, StatusCode, Uri},
+    response::{IntoResponse, Redirect, Response},
+    Router,
+};
+use axum_server::tls_rustls::RustlsConfig;
+use rustls::pki_types::{pem::PemObject, CertificateDer, PrivateKeyDer};
+use std::{path::Path, sync::Arc};
+
+/// Loads a PEM certificate chain and private key into a rustls server config with
+/// HTTP/2 and HTTP/1.1 offered through ALPN.
+pub fn load_rustls_config(cert_path: &Path, key_path: &Path) -> Result<RustlsConfig, AppError> {
+    let read_error = |path: &Path, e: rustls::pki_types::pem::Error| {
+        AppError::InitError(format!("Failed to read TLS file {}: {}", path.display(), e))
+    };
+    let certs = CertificateDer::pem_file_iter(cert_path)
+        .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+        .map_err(|e| read_error(cert_path, e))?;
+    if certs.is_empty() {
+        return Err(AppError::InitError(format!("No certificates found in {}", cert_path.display())));
+    }
+    let key = PrivateKeyDer::from_pem_file(key_path).map_err(|e| read_error(key_path, e))?;
+
+    // Name the crypto provider explicitly: several are compiled in through the AWS SDK and
+    // reqwest, so rustls cannot pick a process-wide default on its own.
+    let mut config = rustls::ServerConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+        .with_safe_default_protocol_versions()
+        .and_then(|builder| builder.with_no_client_auth().with_single_cert(certs, key))
+        .map_err(|e| AppError::InitError(format!("Invalid TLS certificate or key: {}", e)))?;
+    config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+    Ok(RustlsConfig::from_config(Arc::new(config)))
+}
+
+/// Router for the plain-HTTP companion listener: every request is permanently redirected
+/// to the same host and path on the HTTPS port.
+pub fn redirect_router(https_port: u16) -> Router {
+    Router::new().fallback(move |request: Request| async move { redirect_to_https(request, https_port) })
+}
+
+fn redirect_to_https(request: Request, https_port: u16) -> Response {
+    let host = request
+        .headers()
+        .get(header::HOST)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<Authority>().ok());
+    let Some(host) = host else {
+        return (StatusCode::BAD_REQUEST, "Missing or invalid Host header").into_response();
+    };
+
+    let authority = if https_port == 443 {
+        host.host().to_string()
+    } else {
+        format!("{}:{}", host.host(), https_port)
+    };
+    let path_and_query = request.uri().path_and_query().map(|pq| pq.as_str()).unwrap_or("/");
+    match Uri::builder().scheme("https").authority(authority).path_and_query(path_and_query).build() {
+        Ok(uri) => Redirect::permanent(&uri.to_string()).into_response(),
+        Err(_) => (StatusCode::BAD_REQUEST, "Invalid redirect target").into_response(),
+    }
+}
@@ -0,0 +1,152 @@
+use crate::{
+    domain::{TrendingMeme, TrendingSnapshot},
+    errors::{AppError, RepoError},
+    models::{Meme, TrendingWindow},
+    AppState,
+};
+use chrono::{DateTime, Utc};
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+use uuid::Uuid;
+
+/// How many views one like is worth in a meme's popularity.
+pub const LIKE_WEIGHT: f64 = 10.0;
+
+/// Views of memes not yet added to their `view_count`. Counting in memory keeps
+/// `GET /meme/{id}` free of writes; the trending job adds them up with one write per meme.
+#[derive(Debug, Default)]
+pub struct ViewCounter {
+    pending: Mutex<HashMap<Uuid, u64>>,
+}
+
+impl ViewCounter {
+    /// Counts one view of the meme.
+    pub fn record(&self, id: Uuid) {
+        self.add(id, 1);
+    }
+
+    fn add(&self, id: Uuid, views: u64) {
+        *self.pending.lock().expect("view counter lock poisoned").entry(id).or_default() += views;
+    }
+
+    fn take(&self) -> HashMap<Uuid, u64> {
+        std::mem::take(&mut *self.pending.lock().expect("view counter lock poisoned"))
+    }
+}
+
+/// Adds the views counted since the last flush to the memes and returns how many were added.
+/// Views of memes deleted meanwhile are dropped; those whose write fails are kept for the
+/// next flush.
+pub async fn flush_views(state: &AppState) -> u64 {
+    let mut flushed = 0;
+    for (meme_id, views) in state.views.take() {
+        match state.meme_repo.add_views(meme_id, views).await {
+            Ok(()) => flushed += views,
+            Err(RepoError::NotFound(_)) => {}
+            Err(e) => {
+                tracing::warn!(%meme_id, views, error = %e, "Failed to count views");
+                state.views.add(meme_id, views);
+            }
+        }
+    }
+    flushed
+}
+
+/// A meme's popularity (likes weighted by [`LIKE_WEIGHT`], plus views), halved for every
+/// `half_life` since it was uploaded, so newer memes overtake older ones with more votes.
+pub fn score(meme: &Meme, now: DateTime<Utc>, half_life: chrono::Duration) -> f64 {
+    let popularity = meme.like_count as f64 * LIKE_WEIGHT + meme.view_count as f64;
+    let age = meme.created_at.map_or(0, |created_at| (now - created_at).num_seconds().max(0));
+    popularity * 0.5_f64.powf(age as f64 / half_life.num_seconds().max(1) as f64)
+}
+
+/// Ranks the listed memes uploaded within each window by [`score`], keeping the best
+/// `limit`. Memes nobody has viewed or liked, and those stored before upload times were
+/// recorded, are left out.
+pub fn rank(memes: &[Meme], now: DateTime<Utc>, half_life: chrono::Duration, limit: usize) -> BTreeMap<TrendingWindow, Vec<TrendingMeme>> {
+    let candidates: Vec<_> = memes
+        .iter()
+        .filter(|meme| meme.is_listed(now) && !meme.is_expired(now))
+        .filter_map(|meme| Some((meme.created_at?, meme)))
+        .map(|(created_at, meme)| (created_at, TrendingMeme { score: score(meme, now, half_life), meme: meme.clone() }))
+        .filter(|(_, ranked)| ranked.score > 0.0)
+        .collect();
+    TrendingWindow::ALL
+        .into_iter()
+        .map(|window| {
+            let since = now - window.duration();
+            let mut ranking: Vec<_> =
+                candidates.iter().filter(|(created_at, _)| *created_at >= since).map(|(_, ranked)| ranked.clone()).collect();
+            ranking.sort_by(|a, b| b.score.total_cmp(&a.score));
+            ranking.truncate(limit);
+            (window, ranking)
+        })
+        .collect()
+}
+
+/// Flushes the counted views, recomputes the rankings from a table scan, stores them for
+/// other instances and replaces the cached copy.
+pub async fn refresh_trending(state: &AppState) -> Result<Arc<TrendingSnapshot>, AppError> {
+    flush_views(state).await;
+    let memes = state.meme_repo.list_all().await?;
+    let now = Utc::now();
+    let half_life = chrono::Duration::hours(state.config.trending_half_life_hours as i64);
+    let snapshot = Arc::new(TrendingSnapshot {
+        computed_at: now,
+        rankings: rank(&memes, now, half_life, state.config.trending_limit),
+    });
+    state.trending_repo.save(&snapshot).await?;
+    *state.trending.write().expect("trending lock poisoned") = Some(snapshot.clone());
+    tracing::debug!(memes = memes.len(), "Trending rankings refreshed");
+    Ok(snapshot)
+}
+
+/// The latest rankings: the cached copy, else the stored one (computed by another instance),
+/// else computed on the spot when neither is recent (right after startup, or on Lambda
+/// where the job does not run).
+pub async fn current_trending(state: &AppState) -> Result<Arc<TrendingSnapshot>, AppError> {
+    // Twice the interval, so a request never races a job that is merely running late
+    let max_age = chrono::Duration::seconds(2 * state.config.trending_interval_secs as i64);
+    let is_recent = |snapshot: &TrendingSnapshot| Utc::now() - snapshot.computed_at < max_age;
+    let cached = state.trending.read().expect("trending lock poisoned").clone();
+    if let Some(snapshot) = cached.filter(|snapshot| is_recent(snapshot)) {
+        return Ok(snapshot);
+    }
+    match state.trending_repo.load().await? {
+        Some(snapshot) if is_recent(&snapshot) => {
+            let snapshot = Arc::new(snapshot);
+            *state.trending.write().expect("trending lock poisoned") = Some(snapshot.clone());
+            Ok(snapshot)
+        }
+        _ => refresh_trending(state).await,
+    }
+}
+
+/// Spawns a task that refreshes the rankings every `interval`, starting immediately.
+/// Failures are logged and the previous rankings are kept until the next tick. Once
+/// `shutdown` is cancelled, the views counted since the last run are flushed and the task
+/// exits.
+#[cfg_attr(feature = "lambda", allow(dead_code))] // Not scheduled on Lambda
+pub fn spawn_trending(state: Arc<AppState>, interval: Duration, shutdown: CancellationToken) -> JoinHandle<()> {
+    tracing::info!(interval_secs = interval.as_secs(), "Scheduling trending rankings");
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            tokio::select! {
+                _ = ticker.tick() => {}
+                _ = shutdown.cancelled() => {
+                    flush_views(&state).await;
+                    break;
+                }
+            }
+            if let Err(e) = refresh_trending(&state).await {
+                tracing::error!(error = %e, "Computing trending rankings failed");
+            }
+        }
+    })
+}
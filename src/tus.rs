@@ -0,0 +1,730 @@
+//! Resumable uploads with the tus protocol (<https://tus.io/protocols/resumable-upload>),
+//! version 1.0.0 with the `creation` and `termination` extensions, under `/uploads/tus`.
+//!
+//! Each upload is staged in the meme bucket under `tus/<id>` as an S3 multipart upload.
+//! S3 parts must be at least 5 MiB (but the last), so smaller chunks are held in a
+//! `tus/<id>.part` object until enough data arrived; `tus/<id>.info` records the declared
+//! length, the metadata, the multipart upload ID and the uploaded parts. All state lives in
+//! the bucket, so any instance can continue an upload. Once the last byte arrives the staged
+//! object is read back and goes through the same pipeline as `POST /upload_meme`, with the
+//! meme's fields taken from `Upload-Metadata`. Only the S3 storage backend supports resumable
+//! uploads.
+
+use crate::{
+    backends::StorageBackend,
+    errors::{AppError, StorageError},
+    services::{self, Caller, ImageInput, ImageUpload},
+    validation::{self, MemeSubmission},
+    AppState,
+};
+use anyhow::Context;
+use axum::{
+    body::{Body, Bytes},
+    extract::{Path, Request, State},
+    http::{header, HeaderMap, HeaderName, HeaderValue, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use aws_sdk_s3::{
+    error::SdkError,
+    primitives::ByteStream,
+    types::{CompletedMultipartUpload, CompletedPart, ServerSideEncryption},
+    Client as S3Client,
+};
+use base64::{prelude::BASE64_STANDARD, Engine};
+use chrono::{DateTime, Utc};
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, sync::Arc};
+use uuid::Uuid;
+
+/// The only protocol version spoken.
+pub const TUS_VERSION: &str = "1.0.0";
+
+const TUS_EXTENSIONS: &str = "creation,termination";
+
+/// Smallest S3 multipart part other than the last one.
+const MIN_PART_BYTES: usize = 5 * 1024 * 1024;
+
+/// Prefix of the staged uploads within the bucket (after the tenant's key prefix).
+pub const STAGING_PREFIX: &str = "tus/";
+
+pub const TUS_RESUMABLE: HeaderName = HeaderName::from_static("tus-resumable");
+pub const TUS_VERSION_HEADER: HeaderName = HeaderName::from_static("tus-version");
+pub const TUS_EXTENSION: HeaderName = HeaderName::from_static("tus-extension");
+pub const TUS_MAX_SIZE: HeaderName = HeaderName::from_static("tus-max-size");
+pub const UPLOAD_OFFSET: HeaderName = HeaderName::from_static("upload-offset");
+pub const UPLOAD_LENGTH: HeaderName = HeaderName::from_static("upload-length");
+pub const UPLOAD_METADATA: HeaderName = HeaderName::from_static("upload-metadata");
+
+/// Content type of PATCH bodies required by the protocol.
+const OFFSET_OCTET_STREAM: &str = "application/offset+octet-stream";
+
+/// How long a PATCH holds its claim on an upload. A claim left behind by a crashed instance
+/// blocks the upload for at most this long.
+const CLAIM_SECS: i64 = 120;
+
+/// What `tus/<id>.info` holds about an upload.
+#[derive(Debug, Serialize, Deserialize)]
+struct UploadInfo {
+    /// Declared with `Upload-Length` when the upload was created.
+    length: u64,
+    multipart_upload_id: String,
+    /// Decoded `Upload-Metadata` pairs; valueless keys map to an empty string.
+    metadata: BTreeMap<String, String>,
+    /// Uploaded parts in order. A part that was uploaded but not recorded here is uploaded
+    /// again under the same number, replacing it.
+    #[serde(default)]
+    parts: Vec<StagedPart>,
+    /// Set once the meme was created, so a client that missed the last response can still
+    /// find it with HEAD.
+    #[serde(default)]
+    meme_id: Option<Uuid>,
+    /// Set while a PATCH is writing the upload's data; other PATCHes are refused until then.
+    #[serde(default)]
+    claimed_until: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct StagedPart {
+    number: i32,
+    etag: String,
+    bytes: u64,
+}
+
+impl UploadInfo {
+    fn parts_bytes(&self) -> u64 {
+        self.parts.iter().map(|part| part.bytes).sum()
+    }
+
+    fn is_claimed(&self, now: DateTime<Utc>) -> bool {
+        self.claimed_until.is_some_and(|until| until > now)
+    }
+}
+
+/// Middleware for the tus routes: refuses requests for other protocol versions and adds
+/// `Tus-Resumable` to every response, errors included.
+pub async fn require_tus_version(request: Request, next: Next) -> Response {
+    let supported = request.headers().get(&TUS_RESUMABLE).is_some_and(|version| version == TUS_VERSION);
+    let mut response = if supported {
+        next.run(request).await
+    } else {
+        let mut response =
+            AppError::PreconditionFailed(format!("Tus-Resumable must be {}", TUS_VERSION)).into_response();
+        response.headers_mut().insert(TUS_VERSION_HEADER, HeaderValue::from_static(TUS_VERSION));
+        response
+    };
+    response.headers_mut().insert(TUS_RESUMABLE, HeaderValue::from_static(TUS_VERSION));
+    response
+}
+
+/// Middleware answering OPTIONS /uploads/tus with the protocol versions, extensions and
+/// largest upload this server accepts. It runs outside the CORS layer, which takes every
+/// OPTIONS request for a preflight; actual preflights (with `Access-Control-Request-Method`)
+/// are passed on to it.
+pub async fn answer_discovery(State(state): State<Arc<AppState>>, request: Request, next: Next) -> Response {
+    let is_discovery = state.config.storage_backend == StorageBackend::S3
+        && request.method() == Method::OPTIONS
+        && request.uri().path() == "/uploads/tus"
+        && !request.headers().contains_key(header::ACCESS_CONTROL_REQUEST_METHOD);
+    if !is_discovery {
+        return next.run(request).await;
+    }
+    let limits = match services::upload_limits(&state).await {
+        Ok(limits) => limits,
+        Err(e) => return e.into_response(),
+    };
+    (
+        StatusCode::NO_CONTENT,
+        [
+            (TUS_RESUMABLE, TUS_VERSION.to_string()),
+            (TUS_VERSION_HEADER, TUS_VERSION.to_string()),
+            (TUS_EXTENSION, TUS_EXTENSIONS.to_string()),
+            (TUS_MAX_SIZE, limits.max_upload_bytes.to_string()),
+        ],
+    )
+        .into_response()
+}
+
+/// Handler for POST /uploads/tus (creation extension). Takes the image size in
+/// `Upload-Length` and the meme's fields in `Upload-Metadata`: `title`, `description`, `tags`
+/// (comma-separated), `expires_in`, `publish_at` and `status` as in the multipart upload, plus
+/// `filename` and `filetype` for the image. The metadata is validated right away, and
+/// `Upload-Length` against the upload limit and the body limit listed for `filetype`; the
+/// image is validated once it arrived.
+pub async fn create_upload(State(state): State<Arc<AppState>>, headers: HeaderMap) -> Result<Response, AppError> {
+    let length: u64 = header_value(&headers, &UPLOAD_LENGTH)?
+        .ok_or_else(|| AppError::InvalidInput("Upload-Length is required (deferred lengths are not supported)".to_string()))?;
+    let metadata = match headers.get(&UPLOAD_METADATA) {
+        Some(value) => parse_metadata(value.to_str().unwrap_or_default())?,
+        None => BTreeMap::new(),
+    };
+    let limits = services::precheck_submission(&state, submission_from(&metadata)).await?;
+    // The limit of the declared filetype is checked again against the bytes once they arrived
+    let type_limit = metadata.get("filetype").and_then(|filetype| state.config.upload_body_limits.listed_limit(filetype));
+    check_length(length, type_limit.map_or(limits.max_upload_bytes, |limit| limit.min(limits.max_upload_bytes)))?;
+
+    let store = StagingArea::of(&state);
+    let id = Uuid::new_v4();
+    let multipart_upload_id = store.start(id, metadata.get("filetype").map(String::as_str)).await?;
+    let info = UploadInfo { length, multipart_upload_id, metadata, parts: Vec::new(), meme_id: None, claimed_until: None };
+    store.write_info(id, &info, None).await?;
+    tracing::info!(upload_id = %id, length, "Created a resumable upload");
+
+    Ok((StatusCode::CREATED, [(header::LOCATION, format!("/uploads/tus/{}", id))]).into_response())
+}
+
+/// Handler for HEAD /uploads/tus/{id}: how many bytes arrived so far. Finished uploads also
+/// carry the meme's URL in `Location`.
+pub async fn get_offset(State(state): State<Arc<AppState>>, Path(id_str): Path<String>) -> Result<Response, AppError> {
+    let id = Uuid::parse_str(&id_str)?;
+    let store = StagingArea::of(&state);
+    let (info, _) = store.read_info(id).await?;
+    let offset = match info.meme_id {
+        Some(_) => info.length,
+        None => info.parts_bytes() + store.pending_bytes(id).await?,
+    };
+    Ok(progress_response(StatusCode::OK, offset, &info))
+}
+
+/// Handler for PATCH /uploads/tus/{id}: appends the body at `Upload-Offset`, which must be
+/// the current offset. Data received before a dropped connection is kept, so the client can
+/// resume from the offset HEAD reports. The request carrying the last byte creates the meme
+/// and answers with its URL in `Location`; if that fails (e.g. the image is invalid), the
+/// upload is discarded and the error returned.
+///
+/// Once its body arrived, a PATCH claims the upload by rewriting `.info` on the condition
+/// that it is unchanged since the offset was checked, and releases the claim the same way
+/// when done. Of two PATCHes at the same offset only one wins; the other, like any PATCH
+/// while the upload is claimed, is refused with 409 and writes nothing.
+pub async fn append(
+    State(state): State<Arc<AppState>>,
+    caller: Caller,
+    Path(id_str): Path<String>,
+    headers: HeaderMap,
+    body: Body,
+) -> Result<Response, AppError> {
+    let id = Uuid::parse_str(&id_str)?;
+    let is_offset_stream = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.eq_ignore_ascii_case(OFFSET_OCTET_STREAM));
+    if !is_offset_stream {
+        return Err(AppError::UnsupportedMediaType(format!("Content-Type must be {}", OFFSET_OCTET_STREAM)));
+    }
+    let claimed_offset: u64 = header_value(&headers, &UPLOAD_OFFSET)?
+        .ok_or_else(|| AppError::InvalidInput("Upload-Offset is required".to_string()))?;
+
+    let store = StagingArea::of(&state);
+    let (mut info, etag) = store.read_info(id).await?;
+    if info.meme_id.is_some() {
+        // The last response went missing; nothing left to send
+        if claimed_offset != info.length {
+            return Err(AppError::Conflict(format!("Upload-Offset must be {}", info.length)));
+        }
+        return Ok(progress_response(StatusCode::NO_CONTENT, info.length, &info));
+    }
+    if info.is_claimed(Utc::now()) {
+        return Err(busy(id));
+    }
+    let pending_bytes = store.pending_bytes(id).await?;
+    let offset = info.parts_bytes() + pending_bytes;
+    check_offset(claimed_offset, offset)?;
+
+    let remaining = (info.length - offset) as usize;
+    let mut received = Vec::new();
+    let mut interrupted = None;
+    let mut stream = body.into_data_stream();
+    while let Some(chunk) = stream.next().await {
+        match chunk {
+            Ok(chunk) if received.len() + chunk.len() > remaining => {
+                return Err(AppError::PayloadTooLarge(format!(
+                    "The body goes past Upload-Length ({} bytes left)",
+                    remaining
+                )));
+            }
+            Ok(chunk) => received.extend_from_slice(&chunk),
+            Err(e) => {
+                interrupted = Some(e);
+                break;
+            }
+        }
+    }
+    // Claimed only now, so a slow client holds no claim while sending
+    info.claimed_until = Some(Utc::now() + chrono::Duration::seconds(CLAIM_SECS));
+    let claim = store.write_info(id, &info, Some(&etag)).await?;
+    info.claimed_until = None;
+    let offset = match store.append(id, &mut info, pending_bytes, received).await {
+        Ok(offset) => offset,
+        Err(e) => {
+            // Released right away, so the client can resume without waiting for the claim to run out
+            if let Err(release_error) = store.write_info(id, &info, Some(&claim)).await {
+                tracing::warn!(upload_id = %id, error = %release_error, "Failed to release a resumable upload");
+            }
+            return Err(e);
+        }
+    };
+    if interrupted.is_some() || offset < info.length {
+        store.write_info(id, &info, Some(&claim)).await?;
+    }
+    if let Some(e) = interrupted {
+        tracing::info!(upload_id = %id, offset, error = %e, "Resumable upload interrupted");
+        return Err(AppError::InvalidInput(format!("Failed to read the request body: {}", e)));
+    }
+    if offset < info.length {
+        return Ok(progress_response(StatusCode::NO_CONTENT, offset, &info));
+    }
+
+    let meme = match finish(&state, &store, id, &info, &caller).await {
+        Ok(meme) => meme,
+        Err(e) => {
+            store.discard(id, &info).await;
+            return Err(e);
+        }
+    };
+    let info = UploadInfo { meme_id: Some(meme.meme_id), ..info };
+    if let Err(e) = store.write_info(id, &info, Some(&claim)).await {
+        tracing::warn!(upload_id = %id, error = %e, "Failed to record the meme of a finished upload");
+    }
+    Ok(progress_response(StatusCode::NO_CONTENT, info.length, &info))
+}
+
+/// Handler for DELETE /uploads/tus/{id} (termination extension): cancels an upload, or
+/// forgets a finished one. The meme of a finished upload is kept.
+pub async fn terminate(State(state): State<Arc<AppState>>, Path(id_str): Path<String>) -> Result<StatusCode, AppError> {
+    let id = Uuid::parse_str(&id_str)?;
+    let store = StagingArea::of(&state);
+    let (info, _) = store.read_info(id).await?;
+    store.discard(id, &info).await;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Completes the multipart upload and creates the meme from the staged object.
+async fn finish(
+    state: &AppState,
+    store: &StagingArea,
+    id: Uuid,
+    info: &UploadInfo,
+    caller: &Caller,
+) -> Result<crate::models::Meme, AppError> {
+    let data = store.complete(id, info).await?;
+    let image = ImageUpload {
+        data,
+        filename: info.metadata.get("filename").cloned(),
+        content_type: info.metadata.get("filetype").cloned(),
+        source_url: None,
+    };
+    state.config.upload_body_limits.check_file(image.content_type.as_deref(), &image.data)?;
+    let meme = services::create_meme(state, submission_from(&info.metadata), ImageInput::Provided(image), caller).await?;
+    store.delete_object(&store.key(id, "")).await;
+    tracing::info!(upload_id = %id, meme_id = %meme.meme_id, "Finished a resumable upload");
+    Ok(meme)
+}
+
+/// Refuses a PATCH that lost the race for the upload to another one.
+fn busy(id: Uuid) -> AppError {
+    AppError::Conflict(format!("Upload {} is being written by another request; retry with HEAD", id))
+}
+
+/// Refuses an `Upload-Length` that is empty or over the upload limit.
+fn check_length(length: u64, max_upload_bytes: usize) -> Result<(), AppError> {
+    if length == 0 {
+        return Err(AppError::InvalidInput("Upload-Length must be at least 1".to_string()));
+    }
+    if length > max_upload_bytes as u64 {
+        return Err(AppError::PayloadTooLarge(format!(
+            "Upload-Length {} is over the limit of {} bytes",
+            length, max_upload_bytes
+        )));
+    }
+    Ok(())
+}
+
+/// Refuses a PATCH that does not continue at the current offset.
+fn check_offset(claimed_offset: u64, offset: u64) -> Result<(), AppError> {
+    if claimed_offset != offset {
+        return Err(AppError::Conflict(format!("Upload-Offset must be {}", offset)));
+    }
+    Ok(())
+}
+
+fn progress_response(status: StatusCode, offset: u64, info: &UploadInfo) -> Response {
+    let mut response = (
+        status,
+        [
+            (UPLOAD_OFFSET, offset.to_string()),
+            (UPLOAD_LENGTH, info.length.to_string()),
+            (header::CACHE_CONTROL, "no-store".to_string()),
+        ],
+    )
+        .into_response();
+    if let Some(meme_id) = info.meme_id
+        && let Ok(location) = HeaderValue::from_str(&format!("/meme/{}", meme_id))
+    {
+        response.headers_mut().insert(header::LOCATION, location);
+    }
+    response
+}
+
+/// A non-negative integer header, `None` when absent.
+fn header_value(headers: &HeaderMap, name: &HeaderName) -> Result<Option<u64>, AppError> {
+    headers
+        .get(name)
+        .map(|value| {
+            value
+                .to_str()
+                .ok()
+                .and_then(|value| value.trim().parse().ok())
+                .ok_or_else(|| AppError::InvalidInput(format!("{} must be a non-negative integer", name)))
+        })
+        .transpose()
+}
+
+/// Decodes `Upload-Metadata`: comma-separated pairs of a key and its base64-encoded value,
+/// separated by a space. Values may be left out.
+fn parse_metadata(value: &str) -> Result<BTreeMap<String, String>, AppError> {
+    let mut metadata = BTreeMap::new();
+    for pair in value.split(',').map(str::trim).filter(|pair| !pair.is_empty()) {
+        let (key, encoded) = pair.split_once(' ').unwrap_or((pair, ""));
+        let decoded = BASE64_STANDARD
+            .decode(encoded.trim())
+            .ok()
+            .and_then(|bytes| String::from_utf8(bytes).ok())
+            .ok_or_else(|| AppError::InvalidInput(format!("Upload-Metadata value of '{}' is not base64-encoded UTF-8", key)))?;
+        metadata.insert(key.to_string(), decoded);
+    }
+    Ok(metadata)
+}
+
+/// The meme fields among the upload's metadata.
+fn submission_from(metadata: &BTreeMap<String, String>) -> MemeSubmission {
+    MemeSubmission {
+        title: metadata.get("title").cloned(),
+        description: metadata.get("description").cloned(),
+        tags: metadata.get("tags").map(|tags| validation::split_tags(tags).collect()).unwrap_or_default(),
+        expires_in: metadata.get("expires_in").cloned(),
+        publish_at: metadata.get("publish_at").cloned(),
+        status: metadata.get("status").cloned(),
+    }
+}
+
+/// The staged uploads in the meme bucket. Calls S3 directly: multipart uploads are not part
+/// of [`crate::domain::FileStorage`].
+struct StagingArea {
+    client: S3Client,
+    bucket_name: String,
+    key_prefix: String,
+    kms_key_id: Option<String>,
+}
+
+impl StagingArea {
+    fn of(state: &AppState) -> Self {
+        Self {
+            client: state.s3_client.clone(),
+            bucket_name: state.config.meme_bucket_name.clone(),
+            key_prefix: format!("{}{}", state.config.s3_key_prefix(), STAGING_PREFIX),
+            kms_key_id: state.config.s3_upload_kms_key_id.clone(),
+        }
+    }
+
+    fn key(&self, id: Uuid, suffix: &str) -> String {
+        format!("{}{}{}", self.key_prefix, id, suffix)
+    }
+
+    fn encryption(&self) -> Option<ServerSideEncryption> {
+        self.kms_key_id.as_ref().map(|_| ServerSideEncryption::AwsKms)
+    }
+
+    /// Starts the multipart upload and returns its ID.
+    async fn start(&self, id: Uuid, content_type: Option<&str>) -> Result<String, AppError> {
+        let key = self.key(id, "");
+        let output = self
+            .client
+            .create_multipart_upload()
+            .bucket(&self.bucket_name)
+            .key(&key)
+            .content_type(content_type.unwrap_or("application/octet-stream"))
+            .set_server_side_encryption(self.encryption())
+            .set_ssekms_key_id(self.kms_key_id.clone())
+            .send()
+            .await
+            .with_context(|| format!("S3: Failed to start a multipart upload for '{}'", key))
+            .map_err(backend_error)?;
+        output
+            .upload_id()
+            .map(str::to_string)
+            .ok_or_else(|| AppError::InternalServerError(format!("S3 returned no upload ID for '{}'", key)))
+    }
+
+    /// The upload's state and the ETag of `.info`, for writing it back conditionally.
+    async fn read_info(&self, id: Uuid) -> Result<(UploadInfo, String), AppError> {
+        let (data, etag) = self.read_tagged_object(&self.key(id, ".info")).await?.ok_or(AppError::UploadNotFound(id))?;
+        let info = serde_json::from_slice(&data)
+            .map_err(|e| AppError::InternalServerError(format!("Unreadable state of upload {}: {}", id, e)))?;
+        Ok((info, etag))
+    }
+
+    /// Writes `.info`, only if its ETag is still `if_match` when given, and returns the new
+    /// ETag. A lost condition means another request wrote the upload in between.
+    async fn write_info(&self, id: Uuid, info: &UploadInfo, if_match: Option<&str>) -> Result<String, AppError> {
+        let data = serde_json::to_vec(info).map_err(|e| AppError::InternalServerError(e.to_string()))?;
+        let key = self.key(id, ".info");
+        let output = self
+            .client
+            .put_object()
+            .bucket(&self.bucket_name)
+            .key(&key)
+            .body(ByteStream::from(data))
+            .set_if_match(if_match.map(str::to_string))
+            .set_server_side_encryption(self.encryption())
+            .set_ssekms_key_id(self.kms_key_id.clone())
+            .send()
+            .await
+            .map_err(|e| match e.as_service_error().and_then(|se| se.meta().code()) {
+                Some("PreconditionFailed" | "ConditionalRequestConflict") => busy(id),
+                _ => backend_error(anyhow::Error::new(e).context(format!("S3: Failed to upload '{}'", key))),
+            })?;
+        Ok(output.e_tag().unwrap_or_default().to_string())
+    }
+
+    /// Size of the pending tail, which is not yet a part.
+    async fn pending_bytes(&self, id: Uuid) -> Result<u64, AppError> {
+        let key = self.key(id, ".part");
+        match self.client.head_object().bucket(&self.bucket_name).key(&key).send().await {
+            Ok(output) => Ok(output.content_length().unwrap_or_default() as u64),
+            Err(SdkError::ServiceError(service_err)) if service_err.err().is_not_found() => Ok(0),
+            Err(e) => Err(backend_error(anyhow::Error::new(e).context(format!("S3: Failed to head '{}'", key)))),
+        }
+    }
+
+    /// Adds `data` after the pending tail, uploading the tail as the next part once it is big
+    /// enough or complete, and records the part in `info` for the caller to write back.
+    /// Returns the new offset.
+    async fn append(&self, id: Uuid, info: &mut UploadInfo, pending_bytes: u64, data: Vec<u8>) -> Result<u64, AppError> {
+        let parts_bytes = info.parts_bytes();
+        if data.is_empty() {
+            return Ok(parts_bytes + pending_bytes);
+        }
+        let pending_key = self.key(id, ".part");
+        let mut pending = match pending_bytes {
+            0 => Vec::new(),
+            _ => self.read_object(&pending_key).await?.map(Vec::from).unwrap_or_default(),
+        };
+        pending.extend(data);
+        let offset = parts_bytes + pending.len() as u64;
+        if pending.len() < MIN_PART_BYTES && offset < info.length {
+            self.write_object(&pending_key, pending).await?;
+            return Ok(offset);
+        }
+
+        // Dropping the tail first means a failure loses progress (the client resends it)
+        // rather than counting the tail twice
+        if pending_bytes > 0 {
+            self.client
+                .delete_object()
+                .bucket(&self.bucket_name)
+                .key(&pending_key)
+                .send()
+                .await
+                .with_context(|| format!("S3: Failed to delete '{}'", pending_key))
+                .map_err(backend_error)?;
+        }
+        let key = self.key(id, "");
+        let number = info.parts.len() as i32 + 1;
+        let bytes = pending.len() as u64;
+        let output = self
+            .client
+            .upload_part()
+            .bucket(&self.bucket_name)
+            .key(&key)
+            .upload_id(&info.multipart_upload_id)
+            .part_number(number)
+            .body(ByteStream::from(pending))
+            .send()
+            .await
+            .map_err(|e| match &e {
+                // Aborted, e.g. by the bucket's lifecycle rule
+                SdkError::ServiceError(service_err) if service_err.err().meta().code() == Some("NoSuchUpload") => {
+                    AppError::UploadNotFound(id)
+                }
+                _ => backend_error(anyhow::Error::new(e).context(format!("S3: Failed to upload part {} of '{}'", number, key))),
+            })?;
+        let etag = output.e_tag().unwrap_or_default().to_string();
+        info.parts.push(StagedPart { number, etag, bytes });
+        Ok(offset)
+    }
+
+    /// Completes the multipart upload and reads the assembled object back.
+    async fn complete(&self, id: Uuid, info: &UploadInfo) -> Result<Bytes, AppError> {
+        let parts = info
+            .parts
+            .iter()
+            .map(|part| CompletedPart::builder().part_number(part.number).e_tag(&part.etag).build())
+            .collect();
+        let key = self.key(id, "");
+        self.client
+            .complete_multipart_upload()
+            .bucket(&self.bucket_name)
+            .key(&key)
+            .upload_id(&info.multipart_upload_id)
+            .multipart_upload(CompletedMultipartUpload::builder().set_parts(Some(parts)).build())
+            .send()
+            .await
+            .with_context(|| format!("S3: Failed to complete the multipart upload of '{}'", key))
+            .map_err(backend_error)?;
+        self.read_object(&key).await?.ok_or_else(|| AppError::InternalServerError(format!("Completed upload '{}' is missing", key)))
+    }
+
+    /// Best-effort removal of everything staged for an upload. The bucket's lifecycle rule
+    /// (`APP_S3_ABORT_INCOMPLETE_UPLOAD_DAYS`) cleans up multipart uploads left behind.
+    async fn discard(&self, id: Uuid, info: &UploadInfo) {
+        if info.meme_id.is_none() {
+            let key = self.key(id, "");
+            let aborted = self
+                .client
+                .abort_multipart_upload()
+                .bucket(&self.bucket_name)
+                .key(&key)
+                .upload_id(&info.multipart_upload_id)
+                .send()
+                .await;
+            if let Err(e) = aborted
+                && e.as_service_error().and_then(|se| se.meta().code()) != Some("NoSuchUpload")
+            {
+                tracing::warn!(upload_id = %id, error = %e, "Failed to abort a multipart upload");
+            }
+            self.delete_object(&key).await;
+            self.delete_object(&self.key(id, ".part")).await;
+        }
+        self.delete_object(&self.key(id, ".info")).await;
+    }
+
+    /// An object's contents, `None` if there is no such object.
+    async fn read_object(&self, key: &str) -> Result<Option<Bytes>, AppError> {
+        Ok(self.read_tagged_object(key).await?.map(|(data, _)| data))
+    }
+
+    /// An object's contents and ETag, `None` if there is no such object.
+    async fn read_tagged_object(&self, key: &str) -> Result<Option<(Bytes, String)>, AppError> {
+        let output = match self.client.get_object().bucket(&self.bucket_name).key(key).send().await {
+            Ok(output) => output,
+            Err(SdkError::ServiceError(service_err)) if service_err.err().is_no_such_key() => return Ok(None),
+            Err(e) => return Err(backend_error(anyhow::Error::new(e).context(format!("S3: Failed to download '{}'", key)))),
+        };
+        let etag = output.e_tag().unwrap_or_default().to_string();
+        let data = output
+            .body
+            .collect()
+            .await
+            .with_context(|| format!("S3: Failed to read '{}'", key))
+            .map_err(backend_error)?;
+        Ok(Some((data.into_bytes(), etag)))
+    }
+
+    async fn write_object(&self, key: &str, data: Vec<u8>) -> Result<(), AppError> {
+        self.client
+            .put_object()
+            .bucket(&self.bucket_name)
+            .key(key)
+            .body(ByteStream::from(data))
+            .set_server_side_encryption(self.encryption())
+            .set_ssekms_key_id(self.kms_key_id.clone())
+            .send()
+            .await
+            .with_context(|| format!("S3: Failed to upload '{}'", key))
+            .map_err(backend_error)?;
+        Ok(())
+    }
+
+    async fn delete_object(&self, key: &str) {
+        if let Err(e) = self.client.delete_object().bucket(&self.bucket_name).key(key).send().await {
+            tracing::warn!(s3_key = %key, error = %e, "Failed to delete a staged upload object");
+        }
+    }
+}
+
+fn backend_error(error: anyhow::Error) -> AppError {
+    AppError::StorageError(StorageError::BackendError(error))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn info(claimed_until: Option<DateTime<Utc>>) -> UploadInfo {
+        UploadInfo {
+            length: 10,
+            multipart_upload_id: "upload".to_string(),
+            metadata: BTreeMap::new(),
+            parts: vec![StagedPart { number: 1, etag: "\"a\"".to_string(), bytes: 4 }],
+            meme_id: None,
+            claimed_until,
+        }
+    }
+
+    #[test]
+    fn metadata_values_are_base64_decoded() {
+        let metadata = parse_metadata("title SGVsbG8=, tags YSxi,flag ,  filename bWVtZS5wbmc= ").unwrap();
+        assert_eq!(metadata["title"], "Hello");
+        assert_eq!(metadata["tags"], "a,b");
+        assert_eq!(metadata["flag"], "");
+        assert_eq!(metadata["filename"], "meme.png");
+        assert_eq!(metadata.len(), 4);
+        assert!(parse_metadata("").unwrap().is_empty());
+        assert_eq!(parse_metadata("empty").unwrap()["empty"], "");
+    }
+
+    #[test]
+    fn metadata_values_must_be_base64_encoded_utf8() {
+        let error = parse_metadata("title Hello!").unwrap_err();
+        assert!(matches!(&error, AppError::InvalidInput(message) if message.contains("'title'")), "{:?}", error);
+        // 0xff is not UTF-8
+        assert!(matches!(parse_metadata("title /w=="), Err(AppError::InvalidInput(_))));
+    }
+
+    #[test]
+    fn upload_lengths_must_fit_the_upload_limit() {
+        assert!(check_length(1, 1024).is_ok());
+        assert!(check_length(1024, 1024).is_ok());
+        assert!(matches!(check_length(0, 1024), Err(AppError::InvalidInput(_))));
+        assert!(matches!(check_length(1025, 1024), Err(AppError::PayloadTooLarge(_))));
+    }
+
+    #[test]
+    fn patches_must_continue_at_the_current_offset() {
+        assert!(check_offset(4, 4).is_ok());
+        for claimed in [0, 3, 5] {
+            let error = check_offset(claimed, 4).unwrap_err();
+            assert!(matches!(&error, AppError::Conflict(message) if message == "Upload-Offset must be 4"), "{:?}", error);
+        }
+    }
+
+    #[test]
+    fn claims_hold_until_they_run_out() {
+        let now = Utc::now();
+        assert!(!info(None).is_claimed(now));
+        assert!(info(Some(now + chrono::Duration::seconds(CLAIM_SECS))).is_claimed(now));
+        assert!(!info(Some(now)).is_claimed(now));
+        assert!(!info(Some(now - chrono::Duration::seconds(1))).is_claimed(now));
+    }
+
+    #[test]
+    fn info_written_before_claims_existed_reads_as_unclaimed() {
+        let stored = r#"{"length":10,"multipart_upload_id":"upload","metadata":{},"parts":[{"number":1,"etag":"\"a\"","bytes":4}]}"#;
+        let info: UploadInfo = serde_json::from_str(stored).unwrap();
+        assert_eq!(info.parts_bytes(), 4);
+        assert!(!info.is_claimed(Utc::now()));
+    }
+
+    #[test]
+    fn offsets_and_lengths_must_be_non_negative_integers() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(header_value(&headers, &UPLOAD_OFFSET).unwrap(), None);
+        headers.insert(UPLOAD_OFFSET, HeaderValue::from_static(" 42 "));
+        assert_eq!(header_value(&headers, &UPLOAD_OFFSET).unwrap(), Some(42));
+        for invalid in ["-1", "4.5", "many"] {
+            headers.insert(UPLOAD_OFFSET, HeaderValue::from_static(invalid));
+            assert!(matches!(header_value(&headers, &UPLOAD_OFFSET), Err(AppError::InvalidInput(_))), "{}", invalid);
+        }
+    }
+}
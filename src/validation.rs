@@ -0,0 +1,496 @@
+use crate::config::Config;
+use crate::content_filter::{ContentFilter, FilterOutcome};
+use crate::domain::TenantOverrides;
+use crate::fetcher::sniff_image_type;
+use crate::imaging;
+use crate::models::MemeStatus;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::time::Duration;
+
+/// Collects validation failures keyed by field name, so a client can fix
+/// every problem with a submission in one round trip.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct ValidationErrors(BTreeMap<String, Vec<String>>);
+
+impl ValidationErrors {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records a failure message for `field`.
+    pub fn add(&mut self, field: &str, message: impl Into<String>) {
+        self.0.entry(field.to_string()).or_default().push(message.into());
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Every failing field with its messages, by field name.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &[String])> {
+        self.0.iter().map(|(field, messages)| (field.as_str(), messages.as_slice()))
+    }
+
+    /// The failure messages of `field`; empty when it passed.
+    pub fn get(&self, field: &str) -> &[String] {
+        self.0.get(field).map_or(&[], Vec::as_slice)
+    }
+
+    /// Returns `Ok(value)` if no failures were recorded, otherwise `Err(self)`.
+    pub fn into_result<T>(self, value: T) -> Result<T, ValidationErrors> {
+        if self.is_empty() { Ok(value) } else { Err(self) }
+    }
+}
+
+impl fmt::Display for ValidationErrors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let fields: Vec<&str> = self.0.keys().map(String::as_str).collect();
+        write!(f, "invalid fields: {}", fields.join(", "))
+    }
+}
+
+/// Length and count limits applied to user-supplied memes.
+#[derive(Clone, Debug)]
+pub struct ValidationLimits {
+    pub max_title_length: usize,
+    pub max_description_length: usize,
+    pub max_tags: usize,
+    pub max_tag_length: usize,
+    pub max_expires_in_secs: u64,
+    pub max_upload_bytes: usize,
+    /// Image types accepted as sniffed from the bytes; any when empty.
+    pub allowed_image_types: Vec<String>,
+    /// Checked against the repository when a meme is created, not by [`validate_submission`].
+    pub max_uploads_per_day: Option<u64>,
+    /// Longest side of an image in pixels; any size when unset.
+    pub max_image_dimension: Option<u32>,
+}
+
+impl ValidationLimits {
+    /// Applies a tenant's overrides. The upload size cannot exceed the deployment's, which
+    /// also bounds the request body.
+    pub fn with_overrides(self, overrides: &TenantOverrides) -> Self {
+        Self {
+            max_upload_bytes: overrides.max_upload_bytes.map_or(self.max_upload_bytes, |bytes| bytes.min(self.max_upload_bytes)),
+            allowed_image_types: overrides.allowed_image_types.clone().unwrap_or(self.allowed_image_types),
+            max_uploads_per_day: overrides.max_uploads_per_day.or(self.max_uploads_per_day),
+            ..self
+        }
+    }
+}
+
+impl From<&Config> for ValidationLimits {
+    fn from(config: &Config) -> Self {
+        Self {
+            max_title_length: config.max_title_length,
+            max_description_length: config.max_description_length,
+            max_tags: config.max_tags,
+            max_tag_length: config.max_tag_length,
+            max_expires_in_secs: config.max_expires_in_secs,
+            max_upload_bytes: config.max_upload_bytes,
+            allowed_image_types: config.allowed_image_types.clone(),
+            max_uploads_per_day: config.max_uploads_per_day,
+            max_image_dimension: config.max_image_dimension,
+        }
+    }
+}
+
+/// Raw meme metadata as submitted by a client, before validation.
+#[derive(Debug, Default)]
+pub struct MemeSubmission {
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub tags: Vec<String>,
+    /// Lifetime in seconds for ephemeral memes, as sent by the client.
+    pub expires_in: Option<String>,
+    /// RFC 3339 time at which a scheduled meme appears publicly, as sent by the client.
+    pub publish_at: Option<String>,
+    /// `draft` or `published`, as sent by the client.
+    pub status: Option<String>,
+}
+
+/// Meme metadata that passed validation. Text is trimmed and tags are normalized.
+#[derive(Debug, Clone)]
+pub struct ValidatedMeme {
+    pub title: String,
+    pub description: String,
+    pub tags: Vec<String>,
+    pub expires_in: Option<Duration>,
+    pub publish_at: Option<DateTime<Utc>>,
+    pub status: MemeStatus,
+}
+
+/// Validates a submission against `limits` and the content `filter`,
+/// reporting every failing field at once.
+pub fn validate_submission(
+    submission: MemeSubmission,
+    limits: &ValidationLimits,
+    filter: &ContentFilter,
+) -> Result<ValidatedMeme, ValidationErrors> {
+    let mut errors = ValidationErrors::new();
+
+    let title = validate_text(
+        &mut errors,
+        "title",
+        submission.title.as_deref(),
+        limits.max_title_length,
+        false,
+    );
+    let title = apply_filter(&mut errors, "title", title, filter);
+    let description = validate_text(
+        &mut errors,
+        "description",
+        submission.description.as_deref(),
+        limits.max_description_length,
+        true,
+    );
+    let description = apply_filter(&mut errors, "description", description, filter);
+    let tags = validate_tags(&mut errors, &submission.tags, limits);
+    let expires_in = validate_expires_in(&mut errors, submission.expires_in.as_deref(), limits);
+    let publish_at = validate_publish_at(&mut errors, submission.publish_at.as_deref(), expires_in);
+    let status = validate_status(&mut errors, submission.status.as_deref(), publish_at);
+
+    errors.into_result(ValidatedMeme { title, description, tags, expires_in, publish_at, status })
+}
+
+/// Checks an image's size, its dimensions when they are limited (images whose header cannot
+/// be read are rejected then) and, when types are restricted, its type as sniffed from the
+/// bytes (declared types are not trusted). Returns whether it passed.
+pub fn validate_image(errors: &mut ValidationErrors, data: &[u8], limits: &ValidationLimits) -> bool {
+    let mut valid = true;
+    if data.is_empty() {
+        errors.add("image", "must not be empty");
+        return false;
+    }
+    if data.len() > limits.max_upload_bytes {
+        errors.add("image", format!("must be at most {} bytes (got {})", limits.max_upload_bytes, data.len()));
+        valid = false;
+    }
+    if let Some(max_dimension) = limits.max_image_dimension {
+        match imaging::dimensions(data) {
+            Some((width, height)) if width.max(height) <= max_dimension => {}
+            Some((width, height)) => {
+                errors.add("image", format!("must be at most {} pixels wide and high (got {}x{})", max_dimension, width, height));
+                valid = false;
+            }
+            None => {
+                errors.add("image", "has dimensions that cannot be read");
+                valid = false;
+            }
+        }
+    }
+    if !limits.allowed_image_types.is_empty() {
+        match sniff_image_type(data) {
+            Some((media_type, _)) if limits.allowed_image_types.iter().any(|allowed| allowed == media_type) => {}
+            Some((media_type, _)) => {
+                errors.add("image", format!("type {} is not allowed; allowed types: {}", media_type, limits.allowed_image_types.join(", ")));
+                valid = false;
+            }
+            None => {
+                errors.add("image", "is not a recognized image type");
+                valid = false;
+            }
+        }
+    }
+    valid
+}
+
+/// Checks presence, length and allowed characters of a text field. Returns the trimmed value.
+fn validate_text(
+    errors: &mut ValidationErrors,
+    field: &str,
+    value: Option<&str>,
+    max_length: usize,
+    allow_newlines: bool,
+) -> String {
+    let Some(value) = value else {
+        errors.add(field, "is required");
+        return String::new();
+    };
+
+    let trimmed = value.trim();
+    if trimmed.is_empty() {
+        errors.add(field, "must not be empty");
+    }
+    let length = trimmed.chars().count();
+    if length > max_length {
+        errors.add(field, format!("must be at most {} characters (got {})", max_length, length));
+    }
+    if trimmed
+        .chars()
+        .any(|c| c.is_control() && !(allow_newlines && (c == '\n' || c == '\t')))
+    {
+        errors.add(field, "must not contain control characters");
+    }
+    trimmed.to_string()
+}
+
+/// Runs a text field through the content filter, masking or rejecting blocked terms.
+fn apply_filter(
+    errors: &mut ValidationErrors,
+    field: &str,
+    value: String,
+    filter: &ContentFilter,
+) -> String {
+    match filter.apply(&value) {
+        FilterOutcome::Clean => value,
+        FilterOutcome::Masked(masked) => masked,
+        FilterOutcome::Rejected => {
+            errors.add(field, "contains blocked terms");
+            value
+        }
+    }
+}
+
+/// Normalizes tags (trimmed, lowercased, de-duplicated) and checks their count and format.
+fn validate_tags(
+    errors: &mut ValidationErrors,
+    raw_tags: &[String],
+    limits: &ValidationLimits,
+) -> Vec<String> {
+    let mut tags: Vec<String> = Vec::new();
+    for raw in raw_tags {
+        let tag = raw.trim().to_lowercase();
+        if tag.is_empty() || tags.contains(&tag) {
+            continue;
+        }
+        if tag.chars().count() > limits.max_tag_length {
+            errors.add(
+                "tags",
+                format!("tag '{}' must be at most {} characters", tag, limits.max_tag_length),
+            );
+        }
+        if !tag.chars().all(|c| c.is_alphanumeric() || c == '-' || c == '_') {
+            errors.add(
+                "tags",
+                format!("tag '{}' may only contain letters, digits, '-' and '_'", tag),
+            );
+        }
+        tags.push(tag);
+    }
+    if tags.len() > limits.max_tags {
+        errors.add(
+            "tags",
+            format!("at most {} tags are allowed (got {})", limits.max_tags, tags.len()),
+        );
+    }
+    tags
+}
+
+/// Parses an optional lifetime in whole seconds and checks it against the configured maximum.
+fn validate_expires_in(
+    errors: &mut ValidationErrors,
+    value: Option<&str>,
+    limits: &ValidationLimits,
+) -> Option<Duration> {
+    let value = value?.trim();
+    match value.parse::<u64>() {
+        Ok(0) => {
+            errors.add("expires_in", "must be at least 1 second");
+            None
+        }
+        Ok(secs) if secs > limits.max_expires_in_secs => {
+            errors.add(
+                "expires_in",
+                format!("must be at most {} seconds (got {})", limits.max_expires_in_secs, secs),
+            );
+            None
+        }
+        Ok(secs) => Some(Duration::from_secs(secs)),
+        Err(_) => {
+            errors.add("expires_in", "must be a whole number of seconds");
+            None
+        }
+    }
+}
+
+/// Parses the publish time. A time in the past publishes the meme right away; one after the
+/// meme would expire would never show it.
+fn validate_publish_at(
+    errors: &mut ValidationErrors,
+    value: Option<&str>,
+    expires_in: Option<Duration>,
+) -> Option<DateTime<Utc>> {
+    let value = value?.trim();
+    let Ok(publish_at) = DateTime::parse_from_rfc3339(value) else {
+        errors.add("publish_at", "must be an RFC 3339 timestamp");
+        return None;
+    };
+    let publish_at = publish_at.with_timezone(&Utc);
+    if expires_in.is_some_and(|lifetime| publish_at >= Utc::now() + lifetime) {
+        errors.add("publish_at", "must be before the meme expires");
+        return None;
+    }
+    Some(publish_at)
+}
+
+/// Parses the status. Memes scheduled for later start as drafts, so they cannot be sent as
+/// published; otherwise memes are published unless sent as drafts.
+fn validate_status(errors: &mut ValidationErrors, value: Option<&str>, publish_at: Option<DateTime<Utc>>) -> MemeStatus {
+    let scheduled = publish_at.is_some_and(|publish_at| publish_at > Utc::now());
+    let default = if scheduled { MemeStatus::Draft } else { MemeStatus::Published };
+    let Some(value) = value.map(str::trim) else { return default };
+    match MemeStatus::from_name(value) {
+        Some(MemeStatus::Published) if scheduled => {
+            errors.add("status", "must be draft while publish_at is in the future");
+            default
+        }
+        Some(status) => status,
+        None => {
+            errors.add("status", "must be draft or published");
+            default
+        }
+    }
+}
+
+/// Splits a comma-separated tag list as sent in form fields or query strings.
+pub fn split_tags(raw: &str) -> impl Iterator<Item = String> + '_ {
+    raw.split(',').map(str::trim).filter(|t| !t.is_empty()).map(str::to_string)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::content_filter::FilterMode;
+
+    fn limits() -> ValidationLimits {
+        ValidationLimits {
+            max_title_length: 10,
+            max_description_length: 20,
+            max_tags: 2,
+            max_tag_length: 5,
+            max_expires_in_secs: 3600,
+            max_upload_bytes: 1024,
+            allowed_image_types: Vec::new(),
+            max_uploads_per_day: None,
+            max_image_dimension: None,
+        }
+    }
+
+    fn submitted(title: &str, description: &str) -> MemeSubmission {
+        MemeSubmission { title: Some(title.to_string()), description: Some(description.to_string()), ..Default::default() }
+    }
+
+    fn png(width: u32, height: u32) -> Vec<u8> {
+        let mut bytes = std::io::Cursor::new(Vec::new());
+        image::RgbImage::new(width, height).write_to(&mut bytes, image::ImageFormat::Png).unwrap();
+        bytes.into_inner()
+    }
+
+    #[test]
+    fn accepts_and_normalizes_a_valid_submission() {
+        let submission = MemeSubmission {
+            tags: vec![" Cats ".to_string(), "cats".to_string(), "".to_string(), "dogs".to_string()],
+            expires_in: Some("60".to_string()),
+            ..submitted("  Title ", "Line one\nline two")
+        };
+        let meme = validate_submission(submission, &limits(), &ContentFilter::default()).unwrap();
+        assert_eq!(meme.title, "Title");
+        assert_eq!(meme.description, "Line one\nline two");
+        assert_eq!(meme.tags, ["cats", "dogs"]);
+        assert_eq!(meme.expires_in, Some(Duration::from_secs(60)));
+        assert_eq!(meme.status, MemeStatus::Published);
+    }
+
+    #[test]
+    fn reports_every_failing_field_at_once() {
+        let submission = MemeSubmission {
+            title: None,
+            description: Some("x".repeat(21)),
+            tags: vec!["toolong".to_string(), "a b".to_string(), "c".to_string()],
+            expires_in: Some("0".to_string()),
+            publish_at: Some("tomorrow".to_string()),
+            status: Some("live".to_string()),
+        };
+        let errors = validate_submission(submission, &limits(), &ContentFilter::default()).unwrap_err();
+        assert_eq!(errors.get("title"), ["is required"]);
+        assert_eq!(errors.get("description"), ["must be at most 20 characters (got 21)"]);
+        assert_eq!(errors.get("tags").len(), 3);
+        assert_eq!(errors.get("expires_in"), ["must be at least 1 second"]);
+        assert_eq!(errors.get("publish_at"), ["must be an RFC 3339 timestamp"]);
+        assert_eq!(errors.get("status"), ["must be draft or published"]);
+    }
+
+    #[test]
+    fn rejects_blank_text_and_control_characters() {
+        let errors = validate_submission(submitted("   ", "a\u{7}b"), &limits(), &ContentFilter::default()).unwrap_err();
+        assert_eq!(errors.get("title"), ["must not be empty"]);
+        assert_eq!(errors.get("description"), ["must not contain control characters"]);
+        // Newlines are only allowed in descriptions
+        let errors = validate_submission(submitted("a\nb", "ok"), &limits(), &ContentFilter::default()).unwrap_err();
+        assert_eq!(errors.get("title"), ["must not contain control characters"]);
+    }
+
+    #[test]
+    fn checks_expiry_against_the_limit() {
+        let submission = MemeSubmission { expires_in: Some("3601".to_string()), ..submitted("Title", "x") };
+        let errors = validate_submission(submission, &limits(), &ContentFilter::default()).unwrap_err();
+        assert_eq!(errors.get("expires_in"), ["must be at most 3600 seconds (got 3601)"]);
+        let submission = MemeSubmission { expires_in: Some("soon".to_string()), ..submitted("Title", "x") };
+        let errors = validate_submission(submission, &limits(), &ContentFilter::default()).unwrap_err();
+        assert_eq!(errors.get("expires_in"), ["must be a whole number of seconds"]);
+    }
+
+    #[test]
+    fn schedules_future_memes_as_drafts() {
+        let later = (Utc::now() + chrono::Duration::hours(1)).to_rfc3339();
+        let submission = MemeSubmission { publish_at: Some(later.clone()), ..submitted("Title", "x") };
+        let meme = validate_submission(submission, &limits(), &ContentFilter::default()).unwrap();
+        assert_eq!(meme.status, MemeStatus::Draft);
+
+        let submission = MemeSubmission { publish_at: Some(later.clone()), status: Some("published".to_string()), ..submitted("Title", "x") };
+        let errors = validate_submission(submission, &limits(), &ContentFilter::default()).unwrap_err();
+        assert_eq!(errors.get("status"), ["must be draft while publish_at is in the future"]);
+
+        let submission = MemeSubmission { publish_at: Some(later), expires_in: Some("60".to_string()), ..submitted("Title", "x") };
+        let errors = validate_submission(submission, &limits(), &ContentFilter::default()).unwrap_err();
+        assert_eq!(errors.get("publish_at"), ["must be before the meme expires"]);
+    }
+
+    #[test]
+    fn applies_the_content_filter() {
+        let mask = ContentFilter::build(FilterMode::Mask, ["darn"]).unwrap();
+        let meme = validate_submission(submitted("Darn it", "darned"), &limits(), &mask).unwrap();
+        assert_eq!(meme.title, "**** it");
+        assert_eq!(meme.description, "darned");
+
+        let reject = ContentFilter::build(FilterMode::Reject, ["darn"]).unwrap();
+        let errors = validate_submission(submitted("Darn it", "fine"), &limits(), &reject).unwrap_err();
+        assert_eq!(errors.get("title"), ["contains blocked terms"]);
+        assert!(errors.get("description").is_empty());
+    }
+
+    #[test]
+    fn checks_image_size_type_and_dimensions() {
+        let mut errors = ValidationErrors::new();
+        assert!(!validate_image(&mut errors, &[], &limits()));
+        assert_eq!(errors.get("image"), ["must not be empty"]);
+
+        let mut errors = ValidationErrors::new();
+        assert!(!validate_image(&mut errors, &[0; 1025], &limits()));
+        assert_eq!(errors.get("image"), ["must be at most 1024 bytes (got 1025)"]);
+
+        let restricted = ValidationLimits { allowed_image_types: vec!["image/jpeg".to_string()], ..limits() };
+        let mut errors = ValidationErrors::new();
+        assert!(!validate_image(&mut errors, &png(4, 4), &restricted));
+        assert_eq!(errors.get("image"), ["type image/png is not allowed; allowed types: image/jpeg"]);
+        let mut errors = ValidationErrors::new();
+        assert!(!validate_image(&mut errors, b"not an image", &restricted));
+        assert_eq!(errors.get("image"), ["is not a recognized image type"]);
+
+        let small = ValidationLimits { max_image_dimension: Some(4), ..limits() };
+        let mut errors = ValidationErrors::new();
+        assert!(validate_image(&mut errors, &png(4, 2), &small));
+        assert!(!validate_image(&mut errors, &png(5, 2), &small));
+        assert_eq!(errors.get("image"), ["must be at most 4 pixels wide and high (got 5x2)"]);
+    }
+
+    #[test]
+    fn splits_tag_lists() {
+        assert_eq!(split_tags(" a, b ,,c ").collect::<Vec<_>>(), ["a", "b", "c"]);
+    }
+}
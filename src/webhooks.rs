@@ -0,0 +1,106 @@
+use crate::{
+    change_stream::{ChangeHandler, ChangeKind, MemeChange},
+    models::Meme,
+};
+use anyhow::{bail, Context};
+use async_trait::async_trait;
+use hmac::{Hmac, Mac};
+use reqwest::{header, redirect, Client};
+use serde::Serialize;
+use sha2::Sha256;
+use std::time::Duration;
+use uuid::Uuid;
+
+/// Per-request limit for webhook deliveries.
+const DELIVERY_TIMEOUT: Duration = Duration::from_secs(10);
+/// Header carrying `sha256=<hex HMAC of the body>` when `APP_WEBHOOK_SECRET` is set.
+pub const SIGNATURE_HEADER: &str = "x-meme-signature";
+/// Header carrying the change's stream sequence number, for deduplicating redeliveries.
+pub const EVENT_ID_HEADER: &str = "x-meme-event-id";
+
+/// JSON body POSTed for every meme change.
+#[derive(Serialize, Debug)]
+struct WebhookPayload<'a> {
+    /// `meme.created`, `meme.updated` or `meme.removed`.
+    event: &'static str,
+    meme_id: Uuid,
+    /// The meme after the change, or as it was before removal.
+    meme: Option<&'a Meme>,
+    /// Set for removals done by DynamoDB TTL after the meme expired.
+    expired: bool,
+}
+
+/// Delivers meme changes from the change stream to the configured webhook URLs.
+///
+/// A delivery fails unless every URL answers with a 2xx status; the stream consumer then
+/// retries the whole change, so receivers may see an event twice and should deduplicate
+/// on the `X-Meme-Event-Id` header.
+pub struct WebhookNotifier {
+    client: Client,
+    urls: Vec<String>,
+    secret: Option<String>,
+}
+
+impl WebhookNotifier {
+    pub fn new(urls: Vec<String>, secret: Option<String>) -> Result<Self, reqwest::Error> {
+        let client = Client::builder()
+            .timeout(DELIVERY_TIMEOUT)
+            .redirect(redirect::Policy::none())
+            .build()?;
+        Ok(Self { client, urls, secret })
+    }
+
+    fn sign(&self, body: &[u8]) -> Option<String> {
+        let secret = self.secret.as_ref()?;
+        // HMAC accepts keys of any length
+        let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC key of any size");
+        mac.update(body);
+        Some(format!("sha256={}", hex::encode(mac.finalize().into_bytes())))
+    }
+}
+
+#[async_trait]
+impl ChangeHandler for WebhookNotifier {
+    fn name(&self) -> &'static str {
+        "webhooks"
+    }
+
+    async fn handle(&self, change: &MemeChange) -> anyhow::Result<()> {
+        // Likes modify the item without a new version; subscribers only hear about edits
+        if matches!(change.kind, ChangeKind::Updated)
+            && change.old.as_ref().map(|meme| meme.version) == change.new.as_ref().map(|meme| meme.version)
+        {
+            return Ok(());
+        }
+        let event = match change.kind {
+            ChangeKind::Created => "meme.created",
+            ChangeKind::Updated => "meme.updated",
+            ChangeKind::Removed => "meme.removed",
+        };
+        let payload = WebhookPayload {
+            event,
+            meme_id: change.meme_id,
+            meme: change.new.as_ref().or(change.old.as_ref()),
+            expired: change.removed_by_ttl,
+        };
+        let body = serde_json::to_vec(&payload).context("Failed to serialize webhook payload")?;
+        let signature = self.sign(&body);
+
+        for url in &self.urls {
+            let mut request = self.client
+                .post(url)
+                .header(header::CONTENT_TYPE, "application/json")
+                .header(EVENT_ID_HEADER, &change.sequence_number)
+                .body(body.clone());
+            if let Some(signature) = &signature {
+                request = request.header(SIGNATURE_HEADER, signature);
+            }
+            let response = request.send().await.with_context(|| format!("Webhook delivery to {} failed", url))?;
+            if !response.status().is_success() {
+                bail!("Webhook {} responded with status {}", url, response.status());
+            }
+            tracing::debug!(%url, event, meme_id = %change.meme_id, "Webhook delivered");
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,310 @@
+//! AWS X-Ray tracing (`APP_XRAY_ENABLED`): a segment for every sampled request and a
+//! subsegment for every DynamoDB and S3 call made while handling it, sent to the X-Ray daemon
+//! over UDP. Requests carrying an `X-Amzn-Trace-Id` header join the caller's trace and keep
+//! its sampling decision; the trace is passed on to DynamoDB and S3 in the same header.
+
+use crate::{config::Config, errors::AppError, sdk_metrics, AppState};
+use aws_config::Region;
+use aws_smithy_runtime_api::{
+    box_error::BoxError,
+    client::{
+        interceptors::{
+            context::{BeforeSerializationInterceptorContextRef, BeforeTransmitInterceptorContextMut, FinalizerInterceptorContextRef},
+            Intercept,
+        },
+        orchestrator::Metadata,
+        runtime_components::RuntimeComponents,
+    },
+};
+use aws_smithy_types::config_bag::{ConfigBag, Storable, StoreReplace};
+use axum::{
+    extract::{Request, State},
+    http::{header, HeaderValue},
+    middleware::Next,
+    response::Response,
+};
+use serde_json::{json, Value};
+use std::{
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+use tokio::net::UdpSocket;
+use uuid::Uuid;
+
+/// Header carrying the trace ID, parent segment and sampling decision between services.
+pub const TRACE_HEADER: &str = "x-amzn-trace-id";
+
+/// Precedes every document sent to the daemon.
+const DAEMON_HEADER: &str = "{\"format\": \"json\", \"version\": 1}\n";
+
+/// Sends segment documents to the X-Ray daemon.
+pub struct XrayEmitter {
+    socket: UdpSocket,
+    service_name: String,
+    sample_rate: f64,
+}
+
+impl XrayEmitter {
+    /// Connects to the configured daemon; `None` when X-Ray is off.
+    pub async fn from_config(config: &Config) -> Result<Option<Arc<Self>>, AppError> {
+        if !config.xray_enabled {
+            return Ok(None);
+        }
+        let socket = UdpSocket::bind("0.0.0.0:0")
+            .await
+            .map_err(|e| AppError::InitError(format!("Failed to open a socket for X-Ray: {}", e)))?;
+        socket
+            .connect(config.xray_daemon_address.as_str())
+            .await
+            .map_err(|e| AppError::InitError(format!("Failed to resolve X-Ray daemon '{}': {}", config.xray_daemon_address, e)))?;
+        Ok(Some(Arc::new(XrayEmitter {
+            socket,
+            service_name: config.xray_service_name.clone(),
+            sample_rate: config.xray_sample_rate,
+        })))
+    }
+
+    /// Sends without waiting; traces are best effort, so a full buffer or a missing daemon
+    /// only costs the document.
+    fn send(&self, document: &Value) {
+        let packet = format!("{}{}", DAEMON_HEADER, document);
+        if let Err(e) = self.socket.try_send(packet.as_bytes()) {
+            tracing::debug!(error = %e, "Failed to send X-Ray document");
+        }
+    }
+}
+
+/// The parts of an `X-Amzn-Trace-Id` header used here.
+#[derive(Debug, Default, PartialEq, Eq)]
+struct TraceHeader {
+    root: Option<String>,
+    parent: Option<String>,
+    sampled: Option<bool>,
+}
+
+impl TraceHeader {
+    /// Parses `Root=1-5759e988-bd862e3fe1be46a994272793;Parent=53995c3f42cd8ad8;Sampled=1`.
+    /// Unknown fields are ignored, and a `?` sampling decision is left to this service.
+    fn parse(value: &str) -> Self {
+        let mut header = TraceHeader::default();
+        for field in value.split(';') {
+            match field.trim().split_once('=') {
+                Some(("Root", root)) if !root.is_empty() => header.root = Some(root.to_string()),
+                Some(("Parent", parent)) if !parent.is_empty() => header.parent = Some(parent.to_string()),
+                Some(("Sampled", "1")) => header.sampled = Some(true),
+                Some(("Sampled", "0")) => header.sampled = Some(false),
+                _ => {}
+            }
+        }
+        header
+    }
+}
+
+/// The trace of the request being handled, for the SDK calls it makes.
+struct RequestTrace {
+    emitter: Arc<XrayEmitter>,
+    trace_id: String,
+    segment_id: String,
+    sampled: bool,
+}
+
+tokio::task_local! {
+    static CURRENT_TRACE: Arc<RequestTrace>;
+}
+
+/// Middleware recording a segment for every sampled request (when X-Ray is on). The segment
+/// ends when the response headers are ready; time spent streaming the body is not included.
+/// The response carries the trace ID in `X-Amzn-Trace-Id`, to look the request up in the
+/// X-Ray console.
+pub async fn trace_request(State(state): State<Arc<AppState>>, request: Request, next: Next) -> Response {
+    let Some(emitter) = state.xray.clone() else {
+        return next.run(request).await;
+    };
+    let incoming = request
+        .headers()
+        .get(TRACE_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .map(TraceHeader::parse)
+        .unwrap_or_default();
+    let trace = Arc::new(RequestTrace {
+        trace_id: incoming.root.unwrap_or_else(new_trace_id),
+        segment_id: new_id(),
+        sampled: incoming.sampled.unwrap_or_else(|| fastrand::f64() < emitter.sample_rate),
+        emitter,
+    });
+
+    let started = now();
+    let http_request = json!({
+        "method": request.method().as_str(),
+        "url": request.uri().to_string(),
+        "user_agent": request.headers().get(header::USER_AGENT).and_then(|value| value.to_str().ok()),
+    });
+    let request_id = request.headers().get("x-request-id").and_then(|value| value.to_str().ok()).map(str::to_string);
+    let mut response = CURRENT_TRACE.scope(trace.clone(), next.run(request)).await;
+
+    let echoed = format!("Root={};Sampled={}", trace.trace_id, u8::from(trace.sampled));
+    if let Ok(value) = HeaderValue::from_str(&echoed) {
+        response.headers_mut().insert(TRACE_HEADER, value);
+    }
+    if trace.sampled {
+        let status = response.status().as_u16();
+        let mut segment = json!({
+            "name": trace.emitter.service_name,
+            "id": trace.segment_id,
+            "trace_id": trace.trace_id,
+            "start_time": started,
+            "end_time": now(),
+            "http": { "request": http_request, "response": { "status": status } },
+        });
+        if let Some(parent) = incoming.parent {
+            segment["parent_id"] = json!(parent);
+        }
+        if let Some(request_id) = request_id {
+            segment["annotations"] = json!({ "request_id": request_id });
+        }
+        mark_outcome(&mut segment, Some(status));
+        trace.emitter.send(&segment);
+    }
+    response
+}
+
+/// SDK interceptor adding a subsegment for every DynamoDB/S3/SES call made while a request is
+/// traced, and passing the trace on in `X-Amzn-Trace-Id`. Calls outside requests (background
+/// jobs) are not traced. Subsegments are sent on their own as they finish, so requests
+/// making many calls do not outgrow a UDP packet.
+#[derive(Debug, Default)]
+pub struct XrayTracing;
+
+/// What [`XrayTracing`] remembers about a traced call.
+struct TracedCall {
+    trace: Arc<RequestTrace>,
+    id: String,
+    started: f64,
+    resource: Option<String>,
+}
+
+impl std::fmt::Debug for TracedCall {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TracedCall").field("trace_id", &self.trace.trace_id).field("id", &self.id).finish()
+    }
+}
+
+impl Storable for TracedCall {
+    type Storer = StoreReplace<Self>;
+}
+
+impl Intercept for XrayTracing {
+    fn name(&self) -> &'static str {
+        "XrayTracing"
+    }
+
+    fn read_before_execution(
+        &self,
+        context: &BeforeSerializationInterceptorContextRef<'_>,
+        cfg: &mut ConfigBag,
+    ) -> Result<(), BoxError> {
+        if let Ok(trace) = CURRENT_TRACE.try_with(Arc::clone) {
+            let resource = sdk_metrics::resource_of(context.input()).map(str::to_string);
+            cfg.interceptor_state().store_put(TracedCall { trace, id: new_id(), started: now(), resource });
+        }
+        Ok(())
+    }
+
+    fn modify_before_signing(
+        &self,
+        context: &mut BeforeTransmitInterceptorContextMut<'_>,
+        _runtime_components: &RuntimeComponents,
+        cfg: &mut ConfigBag,
+    ) -> Result<(), BoxError> {
+        if let Some(call) = cfg.load::<TracedCall>() {
+            // Unsampled calls get no subsegment, so the request's segment stays the parent
+            let parent = if call.trace.sampled { &call.id } else { &call.trace.segment_id };
+            let value = format!("Root={};Parent={};Sampled={}", call.trace.trace_id, parent, u8::from(call.trace.sampled));
+            context.request_mut().headers_mut().insert(TRACE_HEADER, value);
+        }
+        Ok(())
+    }
+
+    fn read_after_execution(
+        &self,
+        context: &FinalizerInterceptorContextRef<'_>,
+        _runtime_components: &RuntimeComponents,
+        cfg: &mut ConfigBag,
+    ) -> Result<(), BoxError> {
+        let (Some(call), Some(metadata)) = (cfg.load::<TracedCall>(), cfg.load::<Metadata>()) else {
+            return Ok(());
+        };
+        if !call.trace.sampled {
+            return Ok(());
+        }
+        let (name, resource_field) = match metadata.service() {
+            "dynamodb" => ("DynamoDB", "table_name"),
+            "s3" => ("S3", "bucket_name"),
+            other => (other, "resource"),
+        };
+        let mut aws = json!({ "operation": metadata.name() });
+        if let Some(region) = cfg.load::<Region>() {
+            aws["region"] = json!(region.as_ref());
+        }
+        if let Some(resource) = &call.resource {
+            aws[resource_field] = json!(resource);
+        }
+        let response = context.response();
+        let request_id = response.and_then(|response| {
+            let headers = response.headers();
+            headers.get("x-amzn-requestid").or_else(|| headers.get("x-amz-request-id"))
+        });
+        if let Some(request_id) = request_id {
+            aws["request_id"] = json!(request_id);
+        }
+
+        let status = response.map(|response| response.status().as_u16());
+        let mut subsegment = json!({
+            "type": "subsegment",
+            "name": name,
+            "namespace": "aws",
+            "id": call.id,
+            "trace_id": call.trace.trace_id,
+            "parent_id": call.trace.segment_id,
+            "start_time": call.started,
+            "end_time": now(),
+            "aws": aws,
+        });
+        if let Some(status) = status {
+            subsegment["http"] = json!({ "response": { "status": status } });
+        }
+        let failed_without_response = status.is_none() && matches!(context.output_or_error(), Some(Err(_)));
+        mark_outcome(&mut subsegment, if failed_without_response { Some(500) } else { status });
+        call.trace.emitter.send(&subsegment);
+        Ok(())
+    }
+}
+
+/// Sets X-Ray's `error` (4xx), `throttle` (429) and `fault` (5xx) flags.
+fn mark_outcome(document: &mut Value, status: Option<u16>) {
+    match status {
+        Some(429) => {
+            document["error"] = json!(true);
+            document["throttle"] = json!(true);
+        }
+        Some(400..=499) => document["error"] = json!(true),
+        Some(500..) => document["fault"] = json!(true),
+        _ => {}
+    }
+}
+
+/// Seconds since the epoch, as X-Ray timestamps are given.
+fn now() -> f64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs_f64()
+}
+
+/// A new trace ID: version 1, the start time in seconds and 96 random bits, all in hex.
+fn new_trace_id() -> String {
+    let random = Uuid::new_v4().simple().to_string();
+    format!("1-{:08x}-{}", now() as u64, &random[..24])
+}
+
+/// A new segment or subsegment ID: 64 random bits in hex.
+fn new_id() -> String {
+    format!("{:016x}", fastrand::u64(..))
+}
@@ -0,0 +1,59 @@
+//! Tests of the PROXY protocol listener. They serve a bare router on it, so unlike `api.rs`
+//! they need no LocalStack.
+
+use axum::{extract::ConnectInfo, routing::get, serve::Listener, Router};
+use axum_meme_posting_example::client_ip::{parse_network, PeerAddr, ProxyProtocolListener};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+/// Serves the peer address of each request, over a PROXY protocol listener trusting `trusted_proxies`.
+async fn spawn_echo_server(trusted_proxies: &str) -> std::net::SocketAddr {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let trusted_proxies = trusted_proxies.split(',').filter_map(parse_network).collect();
+    let listener = ProxyProtocolListener::new(listener, trusted_proxies).unwrap();
+    let address = listener.local_addr().unwrap();
+    let app = Router::new().route("/", get(|ConnectInfo(PeerAddr(peer)): ConnectInfo<PeerAddr>| async move { peer.to_string() }));
+    tokio::spawn(async move {
+        axum::serve(listener, app.into_make_service_with_connect_info::<PeerAddr>()).await.unwrap();
+    });
+    address
+}
+
+/// Sends `header` and a request on a new connection; the response body, or `None` if the
+/// connection was closed without one.
+async fn peer_seen(address: std::net::SocketAddr, header: &[u8]) -> Option<String> {
+    let mut connection = tokio::net::TcpStream::connect(address).await.unwrap();
+    connection.write_all(header).await.unwrap();
+    connection.write_all(b"GET / HTTP/1.1\r\nHost: test\r\nConnection: close\r\n\r\n").await.ok()?;
+    let mut response = String::new();
+    connection.read_to_string(&mut response).await.ok()?;
+    response.split_once("\r\n\r\n").map(|(_, body)| body.to_string())
+}
+
+#[tokio::test]
+async fn proxy_protocol_headers_name_the_peer() {
+    let address = spawn_echo_server("127.0.0.1").await;
+
+    let v1 = b"PROXY TCP4 203.0.113.7 10.0.0.1 56324 443\r\n";
+    assert_eq!(peer_seen(address, v1).await.as_deref(), Some("203.0.113.7:56324"));
+
+    let mut v2 = b"\r\n\r\n\0\r\nQUIT\n".to_vec();
+    v2.extend([0x21, 0x21, 0, 36]); // v2 PROXY, TCP over IPv6, 36 bytes of addresses
+    v2.extend("2001:db8::17".parse::<std::net::Ipv6Addr>().unwrap().octets());
+    v2.extend([0; 16]);
+    v2.extend([0x12, 0x67, 0x01, 0xbb]); // Ports 4711 and 443
+    assert_eq!(peer_seen(address, &v2).await.as_deref(), Some("[2001:db8::17]:4711"));
+
+    // Health checks declare no address
+    let local = peer_seen(address, b"PROXY UNKNOWN\r\n").await.unwrap();
+    assert!(local.starts_with("127.0.0.1:"), "{}", local);
+
+    // Connections without a header are dropped
+    assert_eq!(peer_seen(address, b"").await, None);
+}
+
+#[tokio::test]
+async fn proxy_protocol_addresses_from_untrusted_peers_are_ignored() {
+    let address = spawn_echo_server("10.0.0.0/8").await;
+    let peer = peer_seen(address, b"PROXY TCP4 203.0.113.7 10.0.0.1 56324 443\r\n").await.unwrap();
+    assert!(peer.starts_with("127.0.0.1:"), "{}", peer);
+}
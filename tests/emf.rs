@@ -0,0 +1,46 @@
+//! End-to-end test of the CloudWatch EMF metrics sink. The metrics recorder is
+//! process-global, so this runs in its own test binary rather than next to the Prometheus
+//! tests in `api.rs`.
+
+use axum_meme_posting_example::testing::TestApp;
+use reqwest::StatusCode;
+use tokio::io::{AsyncBufReadExt, BufReader};
+
+#[tokio::test]
+#[ignore = "needs LocalStack: Docker or APP_TEST_AWS_ENDPOINT_URL"]
+async fn metrics_are_sent_to_the_cloudwatch_agent_as_emf() {
+    let agent = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let endpoint = format!("tcp://{}", agent.local_addr().unwrap());
+    let app = TestApp::spawn_with(&[
+        ("APP_METRICS_SINK", "emf"),
+        ("APP_EMF_NAMESPACE", "MemeTests"),
+        ("APP_EMF_FLUSH_INTERVAL_SECS", "1"),
+        ("APP_EMF_AGENT_ENDPOINT", &endpoint),
+    ])
+    .await;
+    app.upload_meme("Emitted", "Counted in CloudWatch").await;
+    // Prometheus is not served with another sink
+    let response = app.client.get(app.url("/metrics")).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    let (connection, _) = tokio::time::timeout(std::time::Duration::from_secs(10), agent.accept()).await.unwrap().unwrap();
+    let mut lines = BufReader::new(connection).lines();
+    let document = tokio::time::timeout(std::time::Duration::from_secs(10), async {
+        loop {
+            let line = lines.next_line().await.unwrap().expect("the agent connection closed");
+            let document: serde_json::Value = serde_json::from_str(&line).unwrap();
+            if document["operation"] == "PutObject" && document.get("aws_sdk_call_duration_seconds").is_some() {
+                return document;
+            }
+        }
+    })
+    .await
+    .unwrap();
+
+    let directive = &document["_aws"]["CloudWatchMetrics"][0];
+    assert_eq!(directive["Namespace"], "MemeTests");
+    assert_eq!(directive["Dimensions"], serde_json::json!([["service", "operation", "resource"]]));
+    assert_eq!(directive["Metrics"], serde_json::json!([{ "Name": "aws_sdk_call_duration_seconds", "Unit": "Seconds" }]));
+    assert_eq!(document["resource"], app.state.config.meme_bucket_name.as_str());
+    assert!(document["aws_sdk_call_duration_seconds"].as_array().is_some_and(|samples| !samples.is_empty()));
+}
@@ -0,0 +1,98 @@
+//! API tests on the SQLite and filesystem backends (`testing::TestApp::local`). They need
+//! neither Docker nor AWS, so they always run.
+
+use axum_meme_posting_example::{
+    backup,
+    domain::ConsistencyLevel,
+    expiry,
+    generators::{MemeBuilder, UploadPayload},
+    models::{Meme, Visibility},
+    testing::{sample_png, TestApp},
+};
+use reqwest::{header, StatusCode};
+
+#[tokio::test]
+async fn memes_round_trip_through_sqlite_and_the_filesystem() {
+    let app = TestApp::local(&[]).await;
+    let images = app.state.config.filesystem_root.clone();
+
+    let created: Meme = app.upload_meme("Local", "No cloud involved").await.json().await.unwrap();
+    assert!(images.join(&created.image_key).is_file());
+    let response = app.client.post(app.url(&format!("/meme/{}/like", created.meme_id))).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let listed: Vec<serde_json::Value> = app.client.get(app.url("/memes?sort=top")).send().await.unwrap().json().await.unwrap();
+    assert_eq!(listed.len(), 1);
+    assert_eq!(listed[0]["like_count"], 1);
+    let image_url = listed[0]["image_url"].as_str().unwrap();
+    assert_eq!(image_url, format!("/images/{}", created.image_key));
+    let response = app.client.get(app.url(image_url)).send().await.unwrap();
+    assert_eq!(response.headers()[header::CONTENT_TYPE], "image/png");
+    assert_eq!(response.bytes().await.unwrap(), sample_png());
+
+    let response = app.client.delete(app.url(&format!("/meme/{}", created.meme_id))).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    assert!(!images.join(&created.image_key).exists());
+}
+
+#[tokio::test]
+async fn memes_lost_from_the_table_are_restored_from_a_backup() {
+    let app = TestApp::local(&[]).await;
+    let created: Meme = app.upload_meme("Backed up", "Outlives its row").await.json().await.unwrap();
+    let state = &app.state;
+    let summary = backup::run_backup(state.meme_repo.as_ref(), state.file_storage.as_ref(), &state.config.backup_prefix).await.unwrap();
+    assert_eq!(summary.memes, 1);
+    state.meme_repo.delete(created.meme_id).await.unwrap();
+
+    let report = backup::restore(state, &summary.key).await.unwrap();
+    assert_eq!((report.created, report.skipped, report.failed), (1, 0, 0));
+    let restored = state.meme_repo.get_by_id(created.meme_id, ConsistencyLevel::Strong).await.unwrap().unwrap();
+    assert_eq!(restored.title, "Backed up");
+    // Restoring again leaves the meme as it is
+    assert_eq!(backup::restore(state, &summary.key).await.unwrap().skipped, 1);
+}
+
+#[tokio::test]
+async fn exports_of_every_meme_include_private_ones_only_for_the_owner() {
+    let app = TestApp::local(&[("APP_ADMIN_TOKEN", "test-admin")]).await;
+    let public: Meme = app.upload_meme("Public", "Listed").await.json().await.unwrap();
+    let private = MemeBuilder::new().visibility(Visibility::Private).build();
+    app.state.meme_repo.create(&private).await.unwrap();
+
+    // The manifest is stored uncompressed at the end of the archive
+    let anonymous = app.client.get(app.url("/export")).send().await.unwrap().bytes().await.unwrap();
+    let owner = app.client.get(app.url("/export")).bearer_auth("test-admin").send().await.unwrap().bytes().await.unwrap();
+    let contains = |archive: &[u8], meme: &Meme| archive.windows(36).any(|window| window == meme.meme_id.to_string().as_bytes());
+    assert!(contains(&anonymous, &public) && !contains(&anonymous, &private));
+    assert!(contains(&owner, &public) && contains(&owner, &private));
+}
+
+#[tokio::test]
+async fn expired_memes_and_their_images_are_hidden_until_purged() {
+    let app = TestApp::local(&[]).await;
+    let images = app.state.config.filesystem_root.clone();
+    let form = UploadPayload::default().form().text("expires_in", "1");
+    let response = app.client.post(app.url("/upload_meme")).multipart(form).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let created: Meme = response.json().await.unwrap();
+    assert!(created.expires_at.is_some());
+    let meme_url = app.url(&format!("/meme/{}", created.meme_id));
+    let image_url = app.url(&format!("/images/{}", created.image_key));
+    assert_eq!(app.client.get(&meme_url).send().await.unwrap().status(), StatusCode::OK);
+    assert_eq!(app.client.get(&image_url).send().await.unwrap().status(), StatusCode::OK);
+
+    // Expiry times are stored in whole seconds
+    tokio::time::sleep(std::time::Duration::from_secs(2)).await;
+    assert_eq!(app.client.get(&meme_url).send().await.unwrap().status(), StatusCode::NOT_FOUND);
+    assert_eq!(app.client.get(&image_url).send().await.unwrap().status(), StatusCode::NOT_FOUND);
+    assert!(images.join(&created.image_key).is_file());
+
+    let state = &app.state;
+    let summary = expiry::purge_expired(state.meme_repo.as_ref(), state.file_storage.as_ref(), state.meme_history.as_ref()).await.unwrap();
+    assert_eq!((summary.purged, summary.failed), (1, 0));
+    assert!(!images.join(&created.image_key).exists());
+    assert!(state.meme_repo.list_expired(chrono::Utc::now()).await.unwrap().is_empty());
+    // Nothing is left for the next pass
+    let summary = expiry::purge_expired(state.meme_repo.as_ref(), state.file_storage.as_ref(), state.meme_history.as_ref()).await.unwrap();
+    assert_eq!(summary.purged, 0);
+}
@@ -0,0 +1,83 @@
+//! Properties of validation and meme mapping over generated data (`generators`). They need
+//! no backends; set `APP_TEST_SEED` to the seed a failing run printed to repeat it.
+
+use axum_meme_posting_example::{
+    content_filter::ContentFilter,
+    generators::{self, MemeBuilder, UploadPayload},
+    models::{Meme, Visibility},
+    repositories::{item_to_meme, meme_to_item},
+    validation::{validate_submission, MemeSubmission, ValidationLimits},
+};
+
+const CASES: usize = 500;
+
+fn limits() -> ValidationLimits {
+    ValidationLimits {
+        max_title_length: 100,
+        max_description_length: 1000,
+        max_tags: 10,
+        max_tag_length: 30,
+        max_expires_in_secs: 30 * 24 * 60 * 60,
+        max_upload_bytes: 10 * 1024 * 1024,
+        allowed_image_types: Vec::new(),
+        max_uploads_per_day: None,
+        max_image_dimension: None,
+    }
+}
+
+#[test]
+fn accepted_submissions_are_within_limits() {
+    let mut rng = generators::rng();
+    let limits = limits();
+    for _ in 0..CASES {
+        let payload: UploadPayload = generators::generate(&mut rng);
+        let submission = MemeSubmission {
+            title: Some(payload.title.clone()),
+            description: Some(payload.description.clone()),
+            tags: payload.tags.clone(),
+            ..MemeSubmission::default()
+        };
+        let Ok(validated) = validate_submission(submission, &limits, &ContentFilter::default()) else { continue };
+        assert_eq!(validated.title, validated.title.trim(), "{:?}", payload);
+        assert!(!validated.title.is_empty(), "{:?}", payload);
+        assert!(validated.title.chars().count() <= limits.max_title_length, "{:?}", payload);
+        assert!(!validated.title.chars().any(char::is_control), "{:?}", payload);
+        assert!(validated.description.chars().count() <= limits.max_description_length, "{:?}", payload);
+        assert!(validated.tags.len() <= limits.max_tags, "{:?}", payload);
+        for tag in &validated.tags {
+            assert_eq!(tag, &tag.to_lowercase(), "{:?}", payload);
+            assert!(tag.chars().count() <= limits.max_tag_length, "{:?}", payload);
+        }
+    }
+}
+
+#[test]
+fn memes_survive_the_dynamodb_item_mapping() {
+    let mut rng = generators::rng();
+    for _ in 0..CASES {
+        let meme: Meme = generators::generate(&mut rng);
+        let mapped = item_to_meme(&meme_to_item(&meme)).unwrap_or_else(|e| panic!("{}: {:?}", e, meme));
+        assert_eq!(serde_json::to_value(&mapped).unwrap(), serde_json::to_value(&meme).unwrap());
+    }
+}
+
+#[test]
+fn memes_survive_json() {
+    let mut rng = generators::rng();
+    for _ in 0..CASES {
+        let meme: Meme = generators::generate(&mut rng);
+        let json = serde_json::to_string(&meme).unwrap();
+        let parsed: Meme = serde_json::from_str(&json).unwrap_or_else(|e| panic!("{}: {}", e, json));
+        assert_eq!(serde_json::to_value(&parsed).unwrap(), serde_json::to_value(&meme).unwrap());
+    }
+}
+
+#[test]
+fn builder_overrides_only_what_it_is_told() {
+    let meme = MemeBuilder::new().title("Ünïcödé 😀").visibility(Visibility::Unlisted).likes(3).build();
+    assert_eq!(meme.title, "Ünïcödé 😀");
+    assert_eq!(meme.visibility, Visibility::Unlisted);
+    assert_eq!(meme.like_count, 3);
+    assert_eq!(meme.image_key, format!("{}.png", meme.meme_id));
+    assert_eq!(meme.description, MemeBuilder::new().build().description);
+}
@@ -0,0 +1,56 @@
+//! API tests replayed from cassettes in `tests/fixtures`, recorded by an earlier LocalStack
+//! run, so they run without Docker.
+//!
+//! Record a cassette again after changing which backend calls a request makes:
+//! `APP_TEST_RECORD=1 cargo test --test replay` (with Docker or `APP_TEST_AWS_ENDPOINT_URL`).
+
+use axum_meme_posting_example::{
+    client::NewMeme,
+    models::Meme,
+    testing::{sample_png, TestApp},
+};
+use reqwest::StatusCode;
+
+const UPLOAD_AND_FETCH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/upload_and_fetch.json");
+
+#[tokio::test]
+async fn uploaded_meme_can_be_fetched_and_downloaded() {
+    let app = TestApp::replay(UPLOAD_AND_FETCH, &[]).await;
+
+    let response = app.upload_meme("Replayed", "Served from a cassette").await;
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let created: Meme = response.json().await.unwrap();
+
+    let response = app.client.get(app.url(&format!("/meme/{}", created.meme_id))).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let fetched: Meme = response.json().await.unwrap();
+    assert_eq!(fetched.meme_id, created.meme_id);
+    assert_eq!(fetched.title, "Replayed");
+
+    let response = app.client.get(app.url(&format!("/meme/{}/download", created.meme_id))).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.bytes().await.unwrap().as_ref(), sample_png().as_slice());
+
+    let response = app.client.get(app.url("/memes")).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let memes: Vec<Meme> = response.json().await.unwrap();
+    assert!(memes.iter().any(|meme| meme.meme_id == created.meme_id));
+
+    let response = app.client.get(app.url(&format!("/meme/{}", uuid::Uuid::new_v4()))).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn the_client_uploads_fetches_and_downloads() {
+    let app = TestApp::replay(UPLOAD_AND_FETCH, &[]).await;
+    let client = app.meme_client();
+
+    let created = client.upload(NewMeme::new("Replayed", "Served from a cassette", sample_png(), "meme.png")).await.unwrap();
+    let fetched = client.get(created.meme.meme_id).await.unwrap();
+    assert_eq!(fetched.meme.title, "Replayed");
+    assert!(!fetched.image_url.is_empty());
+    assert_eq!(client.download_image(created.meme.meme_id).await.unwrap().as_ref(), sample_png().as_slice());
+    assert!(client.list().await.unwrap().iter().any(|view| view.meme.meme_id == created.meme.meme_id));
+    let missing = client.get(uuid::Uuid::new_v4()).await.unwrap_err();
+    assert!(missing.is_not_found(), "{}", missing);
+}
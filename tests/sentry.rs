@@ -0,0 +1,62 @@
+//! End-to-end test of error reporting to Sentry. The Sentry client is process-global, so this
+//! runs in its own test binary rather than next to the tests in `api.rs`.
+
+use axum::{body::Bytes, routing::post, Router};
+use axum_meme_posting_example::testing::TestApp;
+use reqwest::StatusCode;
+use tokio::sync::mpsc;
+
+#[tokio::test]
+#[ignore = "needs LocalStack: Docker or APP_TEST_AWS_ENDPOINT_URL"]
+async fn server_errors_are_reported_to_sentry_and_client_errors_are_not() {
+    // Stands in for Sentry's envelope endpoint, passing on every event received
+    let (events, mut received) = mpsc::unbounded_channel::<serde_json::Value>();
+    let sentry = Router::new().route(
+        "/api/1/envelope/",
+        post(move |envelope: Bytes| async move {
+            // An envelope header, then an item header and payload per item
+            for line in String::from_utf8_lossy(&envelope).lines() {
+                if let Ok(item) = serde_json::from_str::<serde_json::Value>(line)
+                    && item.get("exception").is_some()
+                {
+                    let _ = events.send(item);
+                }
+            }
+        }),
+    );
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let dsn = format!("http://public@{}/1", listener.local_addr().unwrap());
+    tokio::spawn(async move { axum::serve(listener, sentry).await.unwrap() });
+
+    let app = TestApp::spawn_with(&[("APP_SENTRY_DSN", &dsn), ("APP_SENTRY_ENVIRONMENT", "test")]).await;
+
+    // A client error first, so it would arrive first if it were reported
+    let response = app.client.get(app.url("/meme/not-a-uuid")).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    // Without its table, reads fail on the server's side
+    app.state.db_client.delete_table().table_name(&app.state.config.dynamodb_table_name).send().await.unwrap();
+    let response = app
+        .client
+        .get(app.url(&format!("/meme/{}?token=secret", uuid::Uuid::new_v4())))
+        .header("x-request-id", "sentry-test-request")
+        .header("cookie", "session=secret")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+
+    let event = tokio::time::timeout(std::time::Duration::from_secs(10), received.recv()).await.unwrap().unwrap();
+    assert_eq!(event["tags"]["request_id"], "sentry-test-request");
+    assert_eq!(event["tags"]["route"], "/meme/{id}");
+    assert_eq!(event["transaction"], "/meme/{id}");
+    assert_eq!(event["environment"], "test");
+    assert_eq!(event["contexts"]["request"]["method"], "GET");
+    assert_eq!(event["contexts"]["request"]["route"], "/meme/{id}");
+    // Nothing the client sent beyond the method and route
+    assert!(event.get("request").is_none());
+    assert!(!event.to_string().contains("secret"));
+
+    tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+    assert!(received.try_recv().is_err(), "only the server error is reported");
+}